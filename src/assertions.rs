@@ -0,0 +1,269 @@
+//! Assertions about the nodes of any [`FileSystem`], like those of the `assert_fs` crate, which
+//! are available with the `assertions` feature.
+//!
+//! [`FileSystemAssert::assert`] starts a chain of checks on a path, each of which panics with a
+//! message describing the path and what it found instead, such as when run against a
+//! [`FakeFileSystem`] in a unit test and an [`OsFileSystem`] in an integration test.
+//!
+//! ```
+//! use filesystem::assertions::FileSystemAssert;
+//! use filesystem::{FakeFileSystem, FileSystem};
+//!
+//! let fs = FakeFileSystem::new();
+//!
+//! fs.write_file("/config.toml", "key = 1").unwrap();
+//!
+//! fs.assert("/config.toml").is_file().has_content("key = 1");
+//! fs.assert("/missing").does_not_exist();
+//! ```
+//!
+//! [`FileSystem`]: ../trait.FileSystem.html
+//! [`FileSystemAssert::assert`]: trait.FileSystemAssert.html#method.assert
+//! [`FakeFileSystem`]: ../struct.FakeFileSystem.html
+//! [`OsFileSystem`]: ../struct.OsFileSystem.html
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+use UnixFileSystem;
+use {DirEntry, FileSystem, Metadata, SymlinkFileSystem};
+
+/// Starts assertions about paths, for every [`FileSystem`].
+///
+/// [`FileSystem`]: ../trait.FileSystem.html
+pub trait FileSystemAssert: FileSystem + Sized {
+    /// Returns the assertions about the node at `path`.
+    fn assert<P: AsRef<Path>>(&self, path: P) -> PathAssert<'_, Self> {
+        PathAssert {
+            fs: self,
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl<T: FileSystem> FileSystemAssert for T {}
+
+/// Assertions about the node at a path, as returned by [`FileSystemAssert::assert`].
+///
+/// Each assertion returns the same `PathAssert`, so that several can be chained.
+/// Assertions follow symlinks, apart from [`is_symlink`] and [`points_to`].
+///
+/// [`FileSystemAssert::assert`]: trait.FileSystemAssert.html#method.assert
+/// [`is_symlink`]: #method.is_symlink
+/// [`points_to`]: #method.points_to
+#[derive(Debug)]
+pub struct PathAssert<'a, T: 'a> {
+    fs: &'a T,
+    path: PathBuf,
+}
+
+impl<'a, T: FileSystem> PathAssert<'a, T> {
+    /// Returns the path that the assertions are about.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Asserts that a node exists at the path.
+    #[track_caller]
+    pub fn exists(&self) -> &Self {
+        if let Err(err) = self.fs.metadata(&self.path) {
+            self.fail(format_args!("to exist, but {}", err));
+        }
+
+        self
+    }
+
+    /// Asserts that no node exists at the path, or that it is a broken symlink.
+    #[track_caller]
+    pub fn does_not_exist(&self) -> &Self {
+        if let Ok(metadata) = self.fs.metadata(&self.path) {
+            self.fail(format_args!(
+                "not to exist, but it is a {:?}",
+                metadata.file_type()
+            ));
+        }
+
+        self
+    }
+
+    /// Asserts that the path is a file.
+    #[track_caller]
+    pub fn is_file(&self) -> &Self {
+        match self.fs.metadata(&self.path) {
+            Ok(ref metadata) if metadata.is_file() => {}
+            Ok(metadata) => self.fail(format_args!(
+                "to be a file, but it is a {:?}",
+                metadata.file_type()
+            )),
+            Err(err) => self.fail(format_args!("to be a file, but {}", err)),
+        }
+
+        self
+    }
+
+    /// Asserts that the path is a directory.
+    #[track_caller]
+    pub fn is_dir(&self) -> &Self {
+        match self.fs.metadata(&self.path) {
+            Ok(ref metadata) if metadata.is_dir() => {}
+            Ok(metadata) => self.fail(format_args!(
+                "to be a directory, but it is a {:?}",
+                metadata.file_type()
+            )),
+            Err(err) => self.fail(format_args!("to be a directory, but {}", err)),
+        }
+
+        self
+    }
+
+    /// Asserts that the path is a file containing exactly `contents`.
+    #[track_caller]
+    pub fn has_content<C: AsRef<[u8]>>(&self, contents: C) -> &Self {
+        let expected = contents.as_ref();
+        let actual = self.read();
+
+        if actual != expected {
+            self.fail(format_args!(
+                "to contain {:?}, but it contains {:?}",
+                String::from_utf8_lossy(expected),
+                String::from_utf8_lossy(&actual)
+            ));
+        }
+
+        self
+    }
+
+    /// Asserts that the path is a file whose contents include `substring`.
+    #[track_caller]
+    pub fn contains<S: AsRef<str>>(&self, substring: S) -> &Self {
+        let substring = substring.as_ref();
+        let actual = self.read();
+
+        if !String::from_utf8_lossy(&actual).contains(substring) {
+            self.fail(format_args!(
+                "to contain {:?} somewhere, but it contains {:?}",
+                substring,
+                String::from_utf8_lossy(&actual)
+            ));
+        }
+
+        self
+    }
+
+    /// Asserts that the path is a file of `len` bytes.
+    #[track_caller]
+    pub fn has_len(&self, len: u64) -> &Self {
+        match self.fs.metadata(&self.path) {
+            Ok(ref metadata) if metadata.is_file() && metadata.len() == len => {}
+            Ok(ref metadata) if metadata.is_file() => self.fail(format_args!(
+                "to be {} bytes long, but it is {}",
+                len,
+                metadata.len()
+            )),
+            Ok(metadata) => self.fail(format_args!(
+                "to be a file of {} bytes, but it is a {:?}",
+                len,
+                metadata.file_type()
+            )),
+            Err(err) => self.fail(format_args!("to be {} bytes long, but {}", len, err)),
+        }
+
+        self
+    }
+
+    /// Asserts that the path is a directory with no children.
+    #[track_caller]
+    pub fn is_empty_dir(&self) -> &Self {
+        self.is_dir();
+
+        let mut names: Vec<String> = match self.fs.read_dir(&self.path) {
+            Ok(entries) => entries
+                .map(|entry| match entry {
+                    Ok(entry) => entry.file_name().to_string_lossy().into_owned(),
+                    Err(err) => self.fail(format_args!("to be empty, but {}", err)),
+                })
+                .collect(),
+            Err(err) => self.fail(format_args!("to be empty, but {}", err)),
+        };
+
+        names.sort();
+
+        if !names.is_empty() {
+            self.fail(format_args!("to be empty, but it contains {:?}", names));
+        }
+
+        self
+    }
+
+    #[track_caller]
+    fn read(&self) -> Vec<u8> {
+        self.fs
+            .read_file(&self.path)
+            .unwrap_or_else(|err| self.fail(format_args!("to be a readable file, but {}", err)))
+    }
+
+    #[track_caller]
+    fn fail(&self, expectation: fmt::Arguments) -> ! {
+        panic!("expected {} {}", self.path.display(), expectation)
+    }
+}
+
+impl<'a, T: FileSystem + SymlinkFileSystem> PathAssert<'a, T> {
+    /// Asserts that the path is a symlink, without following it.
+    #[track_caller]
+    pub fn is_symlink(&self) -> &Self {
+        match self.fs.symlink_metadata(&self.path) {
+            Ok(ref metadata) if metadata.file_type().is_symlink() => {}
+            Ok(metadata) => self.fail(format_args!(
+                "to be a symlink, but it is a {:?}",
+                metadata.file_type()
+            )),
+            Err(err) => self.fail(format_args!("to be a symlink, but {}", err)),
+        }
+
+        self
+    }
+
+    /// Asserts that the path is a symlink to `target`, as it was given when creating the link.
+    #[track_caller]
+    pub fn points_to<P: AsRef<Path>>(&self, target: P) -> &Self {
+        let target = target.as_ref();
+
+        match self.fs.read_link(&self.path) {
+            Ok(ref actual) if actual == target => {}
+            Ok(actual) => self.fail(format_args!(
+                "to point to {}, but it points to {}",
+                target.display(),
+                actual.display()
+            )),
+            Err(err) => self.fail(format_args!(
+                "to point to {}, but {}",
+                target.display(),
+                err
+            )),
+        }
+
+        self
+    }
+}
+
+#[cfg(unix)]
+impl<'a, T: FileSystem + UnixFileSystem> PathAssert<'a, T> {
+    /// Asserts that the permission bits of the path are `mode`, such as `0o600`.
+    /// Only the bits covered by `0o7777` are compared, leaving out the type of the node.
+    #[track_caller]
+    pub fn has_mode(&self, mode: u32) -> &Self {
+        match self.fs.mode(&self.path) {
+            Ok(actual) if actual & 0o7777 == mode & 0o7777 => {}
+            Ok(actual) => self.fail(format_args!(
+                "to have mode {:o}, but it has {:o}",
+                mode & 0o7777,
+                actual & 0o7777
+            )),
+            Err(err) => self.fail(format_args!("to have mode {:o}, but {}", mode, err)),
+        }
+
+        self
+    }
+}
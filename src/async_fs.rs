@@ -0,0 +1,207 @@
+use std::future::Future;
+#[cfg(feature = "std-os")]
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+#[cfg(any(feature = "fake", feature = "std-os"))]
+use std::task::{Context, Poll};
+#[cfg(feature = "fake")]
+use std::time::Duration;
+
+#[cfg(feature = "std-os")]
+use tokio::task::{self, JoinHandle};
+#[cfg(feature = "fake")]
+use tokio::time::{self, Sleep};
+
+use {FileSystem, Result};
+
+/// A boxed future returned by [`AsyncFileSystem`] operations.
+///
+/// [`AsyncFileSystem`]: trait.AsyncFileSystem.html
+pub type FsFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+/// Provides asynchronous versions of the standard file system operations.
+///
+/// Every method corresponds to the [`FileSystem`] method of the same name.
+/// Paths and buffers are copied before the returned future is created,
+/// so the future does not borrow from its arguments.
+/// As with other futures, nothing happens until the returned future is polled.
+///
+/// Operations that return handles or lazy iterators, such as `open` and `walk_dir`,
+/// are only available through [`FileSystem`].
+///
+/// [`FileSystem`]: trait.FileSystem.html
+pub trait AsyncFileSystem: FileSystem {
+    /// See [`FileSystem::current_dir`](trait.FileSystem.html#tymethod.current_dir).
+    fn current_dir(&self) -> FsFuture<Result<PathBuf>>;
+    /// See [`FileSystem::set_current_dir`](trait.FileSystem.html#tymethod.set_current_dir).
+    fn set_current_dir<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<()>>;
+
+    /// See [`FileSystem::metadata`](trait.FileSystem.html#tymethod.metadata).
+    fn metadata<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<Self::Metadata>>;
+    /// See [`FileSystem::canonicalize`](trait.FileSystem.html#tymethod.canonicalize).
+    fn canonicalize<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<PathBuf>>;
+
+    /// See [`FileSystem::is_dir`](trait.FileSystem.html#method.is_dir).
+    fn is_dir<P: AsRef<Path>>(&self, path: P) -> FsFuture<bool>;
+    /// See [`FileSystem::is_file`](trait.FileSystem.html#method.is_file).
+    fn is_file<P: AsRef<Path>>(&self, path: P) -> FsFuture<bool>;
+    /// See [`FileSystem::exists`](trait.FileSystem.html#method.exists).
+    fn exists<P: AsRef<Path>>(&self, path: P) -> FsFuture<bool>;
+    /// See [`FileSystem::try_exists`](trait.FileSystem.html#method.try_exists).
+    fn try_exists<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<bool>>;
+
+    /// See [`FileSystem::create_dir`](trait.FileSystem.html#tymethod.create_dir).
+    fn create_dir<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<()>>;
+    /// See [`FileSystem::create_dir_all`](trait.FileSystem.html#method.create_dir_all).
+    fn create_dir_all<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<()>>;
+    /// See [`FileSystem::remove_dir`](trait.FileSystem.html#tymethod.remove_dir).
+    fn remove_dir<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<()>>;
+    /// See [`FileSystem::remove_dir_all`](trait.FileSystem.html#method.remove_dir_all).
+    fn remove_dir_all<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<()>>;
+    /// Returns the entries in a directory.
+    /// See [`FileSystem::read_dir`](trait.FileSystem.html#tymethod.read_dir).
+    fn read_dir<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<Vec<Self::DirEntry>>>;
+
+    /// See [`FileSystem::create_file`](trait.FileSystem.html#method.create_file).
+    fn create_file<P, B>(&self, path: P, buf: B) -> FsFuture<Result<()>>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>;
+    /// See [`FileSystem::write_file`](trait.FileSystem.html#method.write_file).
+    fn write_file<P, B>(&self, path: P, buf: B) -> FsFuture<Result<()>>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>;
+    /// See [`FileSystem::overwrite_file`](trait.FileSystem.html#method.overwrite_file).
+    fn overwrite_file<P, B>(&self, path: P, buf: B) -> FsFuture<Result<()>>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>;
+    /// See [`FileSystem::read_file`](trait.FileSystem.html#method.read_file).
+    fn read_file<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<Vec<u8>>>;
+    /// See [`FileSystem::read_file_to_string`](trait.FileSystem.html#method.read_file_to_string).
+    fn read_file_to_string<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<String>>;
+    /// See [`FileSystem::remove_file`](trait.FileSystem.html#tymethod.remove_file).
+    fn remove_file<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<()>>;
+    /// See [`FileSystem::copy_file`](trait.FileSystem.html#method.copy_file).
+    fn copy_file<P, Q>(&self, from: P, to: Q) -> FsFuture<Result<()>>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>;
+    /// See [`FileSystem::copy_dir`](trait.FileSystem.html#tymethod.copy_dir).
+    fn copy_dir<P, Q>(&self, from: P, to: Q) -> FsFuture<Result<()>>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>;
+
+    /// See [`FileSystem::rename`](trait.FileSystem.html#tymethod.rename).
+    fn rename<P, Q>(&self, from: P, to: Q) -> FsFuture<Result<()>>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>;
+
+    /// See [`FileSystem::readonly`](trait.FileSystem.html#method.readonly).
+    fn readonly<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<bool>>;
+    /// See [`FileSystem::set_readonly`](trait.FileSystem.html#tymethod.set_readonly).
+    fn set_readonly<P: AsRef<Path>>(&self, path: P, readonly: bool) -> FsFuture<Result<()>>;
+
+    /// See [`FileSystem::len`](trait.FileSystem.html#method.len).
+    fn len<P: AsRef<Path>>(&self, path: P) -> FsFuture<u64>;
+}
+
+/// Runs `f` on tokio's blocking thread pool once the returned future is first polled.
+#[cfg(feature = "std-os")]
+pub fn blocking<F, T>(f: F) -> FsFuture<T>
+where
+    F: FnOnce() -> T + Send + Unpin + 'static,
+    T: Send + 'static,
+{
+    Box::pin(Blocking {
+        f: Some(f),
+        handle: None,
+    })
+}
+
+/// Runs `f` when the returned future is first polled, after waiting for `latency`.
+///
+/// A zero `latency` does not require a tokio runtime.
+#[cfg(feature = "fake")]
+pub fn deferred<F, T>(latency: Duration, f: F) -> FsFuture<T>
+where
+    F: FnOnce() -> T + Send + Unpin + 'static,
+    T: Send + 'static,
+{
+    Box::pin(Deferred {
+        latency,
+        delay: None,
+        f: Some(f),
+    })
+}
+
+#[cfg(feature = "std-os")]
+struct Blocking<F, T> {
+    f: Option<F>,
+    handle: Option<JoinHandle<T>>,
+}
+
+#[cfg(feature = "std-os")]
+impl<F, T> Future for Blocking<F, T>
+where
+    F: FnOnce() -> T + Send + Unpin + 'static,
+    T: Send + 'static,
+{
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<T> {
+        if let Some(f) = self.f.take() {
+            self.handle = Some(task::spawn_blocking(f));
+        }
+
+        let handle = self
+            .handle
+            .as_mut()
+            .expect("future polled after completion");
+
+        match Pin::new(handle).poll(cx) {
+            Poll::Ready(Ok(value)) => {
+                self.handle = None;
+                Poll::Ready(value)
+            }
+            Poll::Ready(Err(err)) if err.is_panic() => panic::resume_unwind(err.into_panic()),
+            Poll::Ready(Err(err)) => panic!("blocking file system task failed: {}", err),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(feature = "fake")]
+struct Deferred<F> {
+    latency: Duration,
+    delay: Option<Pin<Box<Sleep>>>,
+    f: Option<F>,
+}
+
+#[cfg(feature = "fake")]
+impl<F, T> Future for Deferred<F>
+where
+    F: FnOnce() -> T + Unpin,
+{
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<T> {
+        if self.delay.is_none() && self.latency > Duration::from_secs(0) {
+            self.delay = Some(Box::pin(time::sleep(self.latency)));
+        }
+
+        if let Some(ref mut delay) = self.delay {
+            if delay.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+
+        let f = self.f.take().expect("future polled after completion");
+
+        Poll::Ready(f())
+    }
+}
@@ -0,0 +1,689 @@
+use std::ffi::OsString;
+use std::io::{self, ErrorKind};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use error::{self, Context};
+use walk::WalkSource;
+#[cfg(unix)]
+use UnixFileSystem;
+#[cfg(feature = "hash")]
+use {Algorithm, Digest};
+use {
+    FileSystem, FileType, Metadata, OpenOptions, Result, SymlinkFileSystem, WalkDir, WalkDirOptions,
+};
+
+/// The maximum number of symlinks followed while resolving a single path.
+/// This matches Linux's `MAXSYMLINKS`.
+const MAX_SYMLINK_DEPTH: usize = 40;
+
+/// A file system that confines every path to a directory of another file system, like `chroot`.
+///
+/// Paths are interpreted as if `root` were `/`, and paths returned by the file system, such as
+/// the entries of [`read_dir`], are given in the same terms.
+/// Relative paths are resolved against a current directory that is tracked separately from the
+/// inner file system's, starting at `/`.
+///
+/// Paths that would escape `root`, whether through `..` components or through symlinks to
+/// absolute paths outside of it, fail with `ErrorKind::PermissionDenied`.
+///
+/// [`read_dir`]: trait.FileSystem.html#tymethod.read_dir
+#[derive(Clone, Debug)]
+pub struct ChrootFileSystem<F> {
+    inner: F,
+    root: PathBuf,
+    cwd: Arc<Mutex<PathBuf>>,
+}
+
+impl<F: SymlinkFileSystem> ChrootFileSystem<F> {
+    /// Creates a file system that confines all paths under `root` in `inner`.
+    pub fn new<P: AsRef<Path>>(inner: F, root: P) -> Self {
+        ChrootFileSystem {
+            inner,
+            root: root.as_ref().to_path_buf(),
+            cwd: Arc::new(Mutex::new(PathBuf::from("/"))),
+        }
+    }
+
+    /// Returns the inner file system.
+    pub fn inner(&self) -> &F {
+        &self.inner
+    }
+
+    /// Returns the directory of the inner file system that all paths are confined to.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the path in the inner file system that `path` refers to.
+    /// If `follow` is false and the last component of `path` is a symlink, the symlink itself is
+    /// returned rather than its target.
+    fn host(&self, path: &Path, follow: bool) -> Result<PathBuf> {
+        self.resolve(path, follow).map(|path| self.host_path(&path))
+    }
+
+    /// Returns the absolute path under the root that `path` refers to, with symlinks resolved.
+    fn resolve(&self, path: &Path, follow: bool) -> Result<PathBuf> {
+        let cwd = self.cwd.lock().unwrap().clone();
+
+        resolve(&self.inner, &self.root, cwd, path, follow)
+    }
+
+    /// Converts an absolute path under the root into a path in the inner file system.
+    fn host_path(&self, path: &Path) -> PathBuf {
+        host_path(&self.root, path)
+    }
+
+    /// Converts an absolute path in the inner file system into a path under the root.
+    /// Relative paths are returned as is.
+    fn guest_path(&self, path: PathBuf) -> Result<PathBuf> {
+        guest_path(&self.root, path)
+    }
+
+    /// Runs `f` on the path in the inner file system that `path` refers to, with `operation` and
+    /// `path` attached to any error in place of the inner file system's path.
+    fn on_host<T, G>(&self, operation: &'static str, path: &Path, follow: bool, f: G) -> Result<T>
+    where
+        G: FnOnce(PathBuf) -> Result<T>,
+    {
+        self.host(path, follow)
+            .and_then(f)
+            .context(operation, &[path])
+    }
+}
+
+impl<F> FileSystem for ChrootFileSystem<F>
+where
+    F: SymlinkFileSystem + Clone + Send + 'static,
+{
+    type DirEntry = DirEntry<F::DirEntry>;
+    type ReadDir = ReadDir<F::ReadDir>;
+    type Metadata = F::Metadata;
+    type OpenFile = F::OpenFile;
+    type WalkDir = WalkDir;
+
+    fn current_dir(&self) -> Result<PathBuf> {
+        Ok(self.cwd.lock().unwrap().clone())
+    }
+
+    fn set_current_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let guest = path.as_ref();
+
+        self.resolve(guest, true)
+            .and_then(|path| {
+                if !self.inner.metadata(self.host_path(&path))?.is_dir() {
+                    return Err(io::Error::new(ErrorKind::NotADirectory, "not a directory").into());
+                }
+
+                *self.cwd.lock().unwrap() = path;
+
+                Ok(())
+            })
+            .context("set_current_dir", &[guest])
+    }
+
+    fn metadata<P: AsRef<Path>>(&self, path: P) -> Result<Self::Metadata> {
+        self.on_host("metadata", path.as_ref(), true, |host| {
+            self.inner.metadata(host)
+        })
+    }
+
+    fn canonicalize<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf> {
+        let guest = path.as_ref();
+
+        self.resolve(guest, true)
+            .and_then(|path| self.inner.metadata(self.host_path(&path)).and(Ok(path)))
+            .context("canonicalize", &[guest])
+    }
+
+    fn is_dir<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.host(path.as_ref(), true)
+            .map(|path| self.inner.is_dir(path))
+            .unwrap_or(false)
+    }
+
+    fn is_file<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.host(path.as_ref(), true)
+            .map(|path| self.inner.is_file(path))
+            .unwrap_or(false)
+    }
+
+    fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.host(path.as_ref(), true)
+            .map(|path| self.inner.exists(path))
+            .unwrap_or(false)
+    }
+
+    fn try_exists<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        self.on_host("try_exists", path.as_ref(), true, |host| {
+            self.inner.try_exists(host)
+        })
+    }
+
+    fn create_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.on_host("create_dir", path.as_ref(), false, |host| {
+            self.inner.create_dir(host)
+        })
+    }
+
+    fn create_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.on_host("create_dir_all", path.as_ref(), true, |host| {
+            self.inner.create_dir_all(host)
+        })
+    }
+
+    fn remove_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.on_host("remove_dir", path.as_ref(), false, |host| {
+            self.inner.remove_dir(host)
+        })
+    }
+
+    fn remove_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.on_host("remove_dir_all", path.as_ref(), false, |host| {
+            self.inner.remove_dir_all(host)
+        })
+    }
+
+    fn read_dir<P: AsRef<Path>>(&self, path: P) -> Result<Self::ReadDir> {
+        let path = path.as_ref();
+
+        self.on_host("read_dir", path, true, |host| {
+            self.inner
+                .read_dir(host)
+                .map(|entries| ReadDir::new(entries, path))
+        })
+    }
+
+    fn walk_dir_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &WalkDirOptions,
+    ) -> Result<Self::WalkDir> {
+        let path = path.as_ref();
+
+        WalkDir::new(ChrootWalkSource(self.clone()), path, options).context("walk_dir", &[path])
+    }
+
+    fn create_file<P, B>(&self, path: P, buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        self.on_host("create_file", path.as_ref(), false, |host| {
+            self.inner.create_file(host, buf)
+        })
+    }
+
+    fn write_file<P, B>(&self, path: P, buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        self.on_host("write_file", path.as_ref(), true, |host| {
+            self.inner.write_file(host, buf)
+        })
+    }
+
+    fn overwrite_file<P, B>(&self, path: P, buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        self.on_host("overwrite_file", path.as_ref(), true, |host| {
+            self.inner.overwrite_file(host, buf)
+        })
+    }
+
+    fn set_len<P: AsRef<Path>>(&self, path: P, size: u64) -> Result<()> {
+        self.on_host("set_len", path.as_ref(), true, |host| {
+            self.inner.set_len(host, size)
+        })
+    }
+
+    fn sync_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.on_host("sync_file", path.as_ref(), true, |host| {
+            self.inner.sync_file(host)
+        })
+    }
+
+    fn sync_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.on_host("sync_dir", path.as_ref(), true, |host| {
+            self.inner.sync_dir(host)
+        })
+    }
+
+    fn read_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
+        self.on_host("read_file", path.as_ref(), true, |host| {
+            self.inner.read_file(host)
+        })
+    }
+
+    fn read_file_to_string<P: AsRef<Path>>(&self, path: P) -> Result<String> {
+        self.on_host("read_file_to_string", path.as_ref(), true, |host| {
+            self.inner.read_file_to_string(host)
+        })
+    }
+
+    fn read_file_into<P, B>(&self, path: P, buf: B) -> Result<usize>
+    where
+        P: AsRef<Path>,
+        B: AsMut<Vec<u8>>,
+    {
+        self.on_host("read_file_into", path.as_ref(), true, |host| {
+            self.inner.read_file_into(host, buf)
+        })
+    }
+
+    fn open<P: AsRef<Path>>(&self, path: P) -> Result<Self::OpenFile> {
+        self.on_host("open", path.as_ref(), true, |host| self.inner.open(host))
+    }
+
+    #[cfg(feature = "hash")]
+    fn hash_file<P: AsRef<Path>>(&self, path: P, algorithm: Algorithm) -> Result<Digest> {
+        self.on_host("hash_file", path.as_ref(), true, |host| {
+            self.inner.hash_file(host, algorithm)
+        })
+    }
+
+    fn open_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &OpenOptions,
+    ) -> Result<Self::OpenFile> {
+        self.on_host("open_with_options", path.as_ref(), true, |host| {
+            self.inner.open_with_options(host, options)
+        })
+    }
+
+    fn remove_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.on_host("remove_file", path.as_ref(), false, |host| {
+            self.inner.remove_file(host)
+        })
+    }
+
+    fn copy_file<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref(), to.as_ref());
+
+        self.host(from, true)
+            .and_then(|from| self.inner.copy_file(from, self.host(to, true)?))
+            .context("copy_file", &[from, to])
+    }
+
+    fn copy_dir<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref(), to.as_ref());
+
+        self.host(from, true)
+            .and_then(|from| self.inner.copy_dir(from, self.host(to, false)?))
+            .context("copy_dir", &[from, to])
+    }
+
+    fn rename<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref(), to.as_ref());
+
+        self.host(from, false)
+            .and_then(|from| self.inner.rename(from, self.host(to, false)?))
+            .context("rename", &[from, to])
+    }
+
+    fn readonly<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        self.on_host("readonly", path.as_ref(), true, |host| {
+            self.inner.readonly(host)
+        })
+    }
+
+    fn set_readonly<P: AsRef<Path>>(&self, path: P, readonly: bool) -> Result<()> {
+        self.on_host("set_readonly", path.as_ref(), true, |host| {
+            self.inner.set_readonly(host, readonly)
+        })
+    }
+
+    fn is_hidden<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        self.on_host("is_hidden", path.as_ref(), false, |host| {
+            self.inner.is_hidden(host)
+        })
+    }
+
+    fn modified<P: AsRef<Path>>(&self, path: P) -> Result<SystemTime> {
+        self.on_host("modified", path.as_ref(), true, |host| {
+            self.inner.modified(host)
+        })
+    }
+
+    fn set_modified<P: AsRef<Path>>(&self, path: P, time: SystemTime) -> Result<()> {
+        self.on_host("set_modified", path.as_ref(), true, |host| {
+            self.inner.set_modified(host, time)
+        })
+    }
+
+    fn accessed<P: AsRef<Path>>(&self, path: P) -> Result<SystemTime> {
+        self.on_host("accessed", path.as_ref(), true, |host| {
+            self.inner.accessed(host)
+        })
+    }
+
+    fn set_accessed<P: AsRef<Path>>(&self, path: P, time: SystemTime) -> Result<()> {
+        self.on_host("set_accessed", path.as_ref(), true, |host| {
+            self.inner.set_accessed(host, time)
+        })
+    }
+
+    fn len<P: AsRef<Path>>(&self, path: P) -> u64 {
+        self.host(path.as_ref(), true)
+            .map(|path| self.inner.len(path))
+            .unwrap_or(0)
+    }
+
+    fn available_space<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+        self.on_host("available_space", path.as_ref(), true, |host| {
+            self.inner.available_space(host)
+        })
+    }
+
+    fn total_space<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+        self.on_host("total_space", path.as_ref(), true, |host| {
+            self.inner.total_space(host)
+        })
+    }
+}
+
+impl<F> SymlinkFileSystem for ChrootFileSystem<F>
+where
+    F: SymlinkFileSystem + Clone + Send + 'static,
+{
+    fn symlink_file<P, Q>(&self, original: P, link: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (original, link) = (original.as_ref(), link.as_ref());
+
+        self.host(link, false)
+            .and_then(|host| self.inner.symlink_file(self.link_target(original), host))
+            .context("symlink_file", &[original, link])
+    }
+
+    fn symlink_dir<P, Q>(&self, original: P, link: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (original, link) = (original.as_ref(), link.as_ref());
+
+        self.host(link, false)
+            .and_then(|host| self.inner.symlink_dir(self.link_target(original), host))
+            .context("symlink_dir", &[original, link])
+    }
+
+    fn read_link<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf> {
+        self.on_host("read_link", path.as_ref(), false, |host| {
+            self.guest_path(self.inner.read_link(host)?)
+        })
+    }
+
+    fn symlink_metadata<P: AsRef<Path>>(&self, path: P) -> Result<Self::Metadata> {
+        self.on_host("symlink_metadata", path.as_ref(), false, |host| {
+            self.inner.symlink_metadata(host)
+        })
+    }
+}
+
+impl<F: SymlinkFileSystem> ChrootFileSystem<F> {
+    /// Returns the target to store in the inner file system for a symlink to `original`.
+    /// Absolute targets are rebased under the root, while relative targets are kept as is.
+    fn link_target(&self, original: &Path) -> PathBuf {
+        if original.is_absolute() {
+            self.host_path(original)
+        } else {
+            original.to_path_buf()
+        }
+    }
+}
+
+#[cfg(unix)]
+impl<F> UnixFileSystem for ChrootFileSystem<F>
+where
+    F: SymlinkFileSystem + UnixFileSystem,
+{
+    fn mode<P: AsRef<Path>>(&self, path: P) -> Result<u32> {
+        self.on_host("mode", path.as_ref(), true, |host| self.inner.mode(host))
+    }
+
+    fn set_mode<P: AsRef<Path>>(&self, path: P, mode: u32) -> Result<()> {
+        self.on_host("set_mode", path.as_ref(), true, |host| {
+            self.inner.set_mode(host, mode)
+        })
+    }
+
+    fn owner<P: AsRef<Path>>(&self, path: P) -> Result<(u32, u32)> {
+        self.on_host("owner", path.as_ref(), true, |host| self.inner.owner(host))
+    }
+
+    fn set_owner<P: AsRef<Path>>(&self, path: P, uid: u32, gid: u32) -> Result<()> {
+        self.on_host("set_owner", path.as_ref(), true, |host| {
+            self.inner.set_owner(host, uid, gid)
+        })
+    }
+
+    fn create_file_with_mode<P, B>(&self, path: P, buf: B, mode: u32) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        self.on_host("create_file_with_mode", path.as_ref(), false, |host| {
+            self.inner.create_file_with_mode(host, buf, mode)
+        })
+    }
+
+    fn create_dir_with_mode<P: AsRef<Path>>(&self, path: P, mode: u32) -> Result<()> {
+        self.on_host("create_dir_with_mode", path.as_ref(), false, |host| {
+            self.inner.create_dir_with_mode(host, mode)
+        })
+    }
+
+    fn mknod<P: AsRef<Path>>(
+        &self,
+        path: P,
+        file_type: FileType,
+        mode: u32,
+        device: u64,
+    ) -> Result<()> {
+        self.on_host("mknod", path.as_ref(), false, |host| {
+            self.inner.mknod(host, file_type, mode, device)
+        })
+    }
+}
+
+/// An entry of a directory, with a path relative to the path given to `read_dir`.
+#[derive(Debug, Clone)]
+pub struct DirEntry<E> {
+    path: PathBuf,
+    entry: E,
+}
+
+impl<E: ::DirEntry> ::DirEntry for DirEntry<E> {
+    type Metadata = E::Metadata;
+
+    fn file_name(&self) -> OsString {
+        self.entry.file_name()
+    }
+
+    fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    fn file_type(&self) -> io::Result<FileType> {
+        self.entry.file_type()
+    }
+
+    fn metadata(&self) -> io::Result<E::Metadata> {
+        self.entry.metadata()
+    }
+}
+
+/// The entries of a directory, with paths relative to the path given to `read_dir`.
+#[derive(Debug)]
+pub struct ReadDir<R> {
+    entries: R,
+    dir: PathBuf,
+}
+
+impl<R> ReadDir<R> {
+    fn new(entries: R, dir: &Path) -> Self {
+        ReadDir {
+            entries,
+            dir: dir.to_path_buf(),
+        }
+    }
+}
+
+impl<R, E> Iterator for ReadDir<R>
+where
+    R: Iterator<Item = io::Result<E>>,
+    E: ::DirEntry,
+{
+    type Item = io::Result<DirEntry<E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().map(|entry| {
+            entry.map(|entry| DirEntry {
+                path: self.dir.join(entry.file_name()),
+                entry,
+            })
+        })
+    }
+}
+
+impl<R, E> ::ReadDir<DirEntry<E>> for ReadDir<R>
+where
+    R: Iterator<Item = io::Result<E>>,
+    E: ::DirEntry,
+{
+}
+
+struct ChrootWalkSource<F>(ChrootFileSystem<F>);
+
+impl<F> WalkSource for ChrootWalkSource<F>
+where
+    F: SymlinkFileSystem + Clone + Send + 'static,
+{
+    fn list(&self, dir: &Path) -> io::Result<Vec<(PathBuf, FileType)>> {
+        let mut children = vec![];
+
+        for entry in self.0.read_dir(dir)? {
+            let path = ::DirEntry::path(&entry?);
+            let file_type = self.0.symlink_metadata(&path)?.file_type();
+
+            children.push((path, file_type));
+        }
+
+        Ok(children)
+    }
+
+    fn follow(&self, path: &Path) -> io::Result<(PathBuf, FileType)> {
+        let file_type = self.0.metadata(path)?.file_type();
+
+        Ok((self.0.canonicalize(path)?, file_type))
+    }
+}
+
+/// Returns the absolute path under `root` in `inner` that `path` refers to, with symlinks
+/// resolved, where `/` stands for `root` and relative paths are resolved against `cwd`.
+/// If `follow` is false and the last component of `path` is a symlink, it is left unresolved.
+pub fn resolve<F: SymlinkFileSystem>(
+    inner: &F,
+    root: &Path,
+    cwd: PathBuf,
+    path: &Path,
+    follow: bool,
+) -> Result<PathBuf> {
+    resolve_from(inner, root, cwd, path, follow, &mut 0)
+}
+
+fn resolve_from<F: SymlinkFileSystem>(
+    inner: &F,
+    root: &Path,
+    mut resolved: PathBuf,
+    path: &Path,
+    follow: bool,
+    depth: &mut usize,
+) -> Result<PathBuf> {
+    let mut components = path.components().peekable();
+
+    while let Some(component) = components.next() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => resolved = PathBuf::from("/"),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return Err(escape_error().into());
+                }
+            }
+            Component::Normal(name) => {
+                resolved.push(name);
+
+                if components.peek().is_none() && !follow {
+                    break;
+                }
+
+                let host = host_path(root, &resolved);
+                let is_symlink = inner
+                    .symlink_metadata(&host)
+                    .map(|m| m.file_type().is_symlink())
+                    .unwrap_or(false);
+
+                if !is_symlink {
+                    continue;
+                }
+
+                if *depth >= MAX_SYMLINK_DEPTH {
+                    return Err(error::filesystem_loop("too many levels of symbolic links").into());
+                }
+
+                *depth += 1;
+
+                let target = guest_path(root, inner.read_link(&host)?)?;
+
+                resolved.pop();
+                resolved = resolve_from(inner, root, resolved, &target, true, depth)?;
+            }
+        }
+    }
+
+    Ok(resolved)
+}
+
+/// Converts an absolute path under `root`, where `/` stands for `root`, into a path in the inner
+/// file system.
+pub fn host_path(root: &Path, path: &Path) -> PathBuf {
+    root.join(path.strip_prefix("/").unwrap_or(path))
+}
+
+/// Converts an absolute path in the inner file system into a path under `root`, where `/` stands
+/// for `root`.
+/// Relative paths are returned as is.
+pub fn guest_path(root: &Path, path: PathBuf) -> Result<PathBuf> {
+    if path.is_relative() {
+        return Ok(path);
+    }
+
+    path.strip_prefix(root)
+        .map(|path| Path::new("/").join(path))
+        .map_err(|_| escape_error().into())
+}
+
+pub fn escape_error() -> io::Error {
+    io::Error::new(ErrorKind::PermissionDenied, "path escapes the root")
+}
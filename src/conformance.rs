@@ -0,0 +1,730 @@
+//! Checks that an implementation of [`FileSystem`] behaves like the OS does, which are available
+//! with the `conformance` feature.
+//!
+//! Each check takes a file system and an empty directory to work in, and panics if the file
+//! system behaves differently from [`OsFileSystem`].
+//! Errors are checked for their `ErrorKind` only where it is the same on every platform, such as
+//! `NotFound` and `AlreadyExists`, and otherwise only for being errors.
+//! Checks run as a user who can write to the directory, but they never rely on permissions.
+//!
+//! The [`conformance_tests!`] macro generates a test for every check, given an expression that
+//! returns a file system and a directory, which is anything that implements `AsRef<Path>`, such
+//! as an [`OsTempDir`] that is removed once the test ends.
+//! Checks that use symlinks are also generated when `symlinks` follows the expression.
+//!
+//! ```
+//! mod conformance {
+//!     use filesystem::{OsFileSystem, TempFileSystem};
+//!
+//!     fn setup() -> (OsFileSystem, filesystem::OsTempDir) {
+//!         let fs = OsFileSystem::new();
+//!         let dir = fs.temp_dir("conformance").unwrap();
+//!
+//!         (fs, dir)
+//!     }
+//!
+//!     filesystem::conformance_tests!(setup, symlinks);
+//! }
+//! ```
+//!
+//! [`FileSystem`]: ../trait.FileSystem.html
+//! [`OsFileSystem`]: ../struct.OsFileSystem.html
+//! [`OsTempDir`]: ../struct.OsTempDir.html
+//! [`conformance_tests!`]: ../macro.conformance_tests.html
+
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use {DirEntry, FileSystem, Metadata, OpenOptions, Result, SymlinkFileSystem};
+
+/// Asserts that `result` is an error of `kind`, describing `operation` if it is not.
+fn assert_error_kind<T>(result: Result<T>, kind: ErrorKind, operation: &str) {
+    match result {
+        Ok(_) => panic!(
+            "{} succeeded, but should have failed with {:?}",
+            operation, kind
+        ),
+        Err(err) => assert_eq!(err.kind(), kind, "{} failed with {}", operation, err),
+    }
+}
+
+/// Asserts that `result` is an error, describing `operation` if it is not.
+fn assert_error<T>(result: Result<T>, operation: &str) {
+    assert!(
+        result.is_err(),
+        "{} succeeded, but should have failed",
+        operation
+    );
+}
+
+fn sorted_names<T: FileSystem>(fs: &T, path: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs
+        .read_dir(path)
+        .expect("read_dir failed")
+        .map(|entry| {
+            let entry = entry.expect("read_dir entry failed");
+
+            entry.file_name().to_string_lossy().into_owned()
+        })
+        .collect();
+
+    names.sort();
+    names
+}
+
+/// `create_dir` creates an empty directory.
+pub fn create_dir_creates_empty_dir<T: FileSystem>(fs: &T, dir: &Path) {
+    let path = dir.join("new");
+
+    fs.create_dir(&path).unwrap();
+
+    assert!(fs.is_dir(&path));
+    assert!(sorted_names(fs, &path).is_empty());
+}
+
+/// `create_dir` fails with `AlreadyExists` if any node exists at the path.
+pub fn create_dir_fails_if_node_exists<T: FileSystem>(fs: &T, dir: &Path) {
+    let file = dir.join("file");
+    let subdir = dir.join("dir");
+
+    fs.create_file(&file, "").unwrap();
+    fs.create_dir(&subdir).unwrap();
+
+    assert_error_kind(
+        fs.create_dir(&file),
+        ErrorKind::AlreadyExists,
+        "create_dir on a file",
+    );
+    assert_error_kind(
+        fs.create_dir(&subdir),
+        ErrorKind::AlreadyExists,
+        "create_dir on a directory",
+    );
+}
+
+/// `create_dir` fails with `NotFound` if the parent does not exist.
+pub fn create_dir_fails_if_parent_does_not_exist<T: FileSystem>(fs: &T, dir: &Path) {
+    assert_error_kind(
+        fs.create_dir(dir.join("missing").join("new")),
+        ErrorKind::NotFound,
+        "create_dir without a parent",
+    );
+}
+
+/// `create_dir_all` creates every missing ancestor, and succeeds if the directory exists.
+pub fn create_dir_all_creates_ancestors<T: FileSystem>(fs: &T, dir: &Path) {
+    let path = dir.join("a").join("b").join("c");
+
+    fs.create_dir_all(&path).unwrap();
+    fs.create_dir_all(&path).unwrap();
+
+    assert!(fs.is_dir(dir.join("a").join("b")));
+    assert!(fs.is_dir(&path));
+}
+
+/// `create_dir_all` fails if an ancestor is a file.
+pub fn create_dir_all_fails_if_ancestor_is_file<T: FileSystem>(fs: &T, dir: &Path) {
+    let file = dir.join("file");
+
+    fs.create_file(&file, "").unwrap();
+
+    assert_error(
+        fs.create_dir_all(file.join("dir")),
+        "create_dir_all below a file",
+    );
+}
+
+/// `create_file` creates a file with the given contents.
+pub fn create_file_writes_contents<T: FileSystem>(fs: &T, dir: &Path) {
+    let path = dir.join("file");
+
+    fs.create_file(&path, "contents").unwrap();
+
+    assert!(fs.is_file(&path));
+    assert_eq!(fs.read_file(&path).unwrap(), b"contents");
+    assert_eq!(fs.len(&path), 8);
+}
+
+/// `create_file` fails with `AlreadyExists` if any node exists at the path, leaving it as it is.
+pub fn create_file_fails_if_node_exists<T: FileSystem>(fs: &T, dir: &Path) {
+    let file = dir.join("file");
+    let subdir = dir.join("dir");
+
+    fs.create_file(&file, "old").unwrap();
+    fs.create_dir(&subdir).unwrap();
+
+    assert_error_kind(
+        fs.create_file(&file, "new"),
+        ErrorKind::AlreadyExists,
+        "create_file on a file",
+    );
+    assert_error_kind(
+        fs.create_file(&subdir, "new"),
+        ErrorKind::AlreadyExists,
+        "create_file on a directory",
+    );
+    assert_eq!(fs.read_file(&file).unwrap(), b"old");
+}
+
+/// `create_file` fails with `NotFound` if the parent does not exist.
+pub fn create_file_fails_if_parent_does_not_exist<T: FileSystem>(fs: &T, dir: &Path) {
+    assert_error_kind(
+        fs.create_file(dir.join("missing").join("file"), ""),
+        ErrorKind::NotFound,
+        "create_file without a parent",
+    );
+}
+
+/// `write_file` creates a file, or replaces the contents of an existing one.
+pub fn write_file_creates_or_truncates<T: FileSystem>(fs: &T, dir: &Path) {
+    let path = dir.join("file");
+
+    fs.write_file(&path, "longer contents").unwrap();
+    fs.write_file(&path, "short").unwrap();
+
+    assert_eq!(fs.read_file(&path).unwrap(), b"short");
+}
+
+/// `write_file` fails if the path is a directory.
+pub fn write_file_fails_if_node_is_dir<T: FileSystem>(fs: &T, dir: &Path) {
+    assert_error(fs.write_file(dir, ""), "write_file on a directory");
+}
+
+/// `overwrite_file` replaces the contents of a file, and fails with `NotFound` if there is none.
+pub fn overwrite_file_requires_existing_file<T: FileSystem>(fs: &T, dir: &Path) {
+    let path = dir.join("file");
+
+    assert_error_kind(
+        fs.overwrite_file(&path, "new"),
+        ErrorKind::NotFound,
+        "overwrite_file on a missing file",
+    );
+    assert!(!fs.exists(&path));
+
+    fs.create_file(&path, "old contents").unwrap();
+    fs.overwrite_file(&path, "new").unwrap();
+
+    assert_eq!(fs.read_file(&path).unwrap(), b"new");
+}
+
+/// `read_file` fails with `NotFound` if there is no node at the path, and fails for directories.
+pub fn read_file_fails_if_node_is_not_file<T: FileSystem>(fs: &T, dir: &Path) {
+    assert_error_kind(
+        fs.read_file(dir.join("missing")),
+        ErrorKind::NotFound,
+        "read_file on a missing file",
+    );
+    assert_error(fs.read_file(dir), "read_file on a directory");
+}
+
+/// `read_dir` lists the names of every child, but not those of grandchildren.
+pub fn read_dir_lists_children<T: FileSystem>(fs: &T, dir: &Path) {
+    fs.create_file(dir.join("file"), "").unwrap();
+    fs.create_dir(dir.join("dir")).unwrap();
+    fs.create_file(dir.join("dir").join("grandchild"), "")
+        .unwrap();
+
+    assert_eq!(sorted_names(fs, dir), vec!["dir", "file"]);
+}
+
+/// `read_dir` fails with `NotFound` if there is no node at the path, and fails for files.
+pub fn read_dir_fails_if_node_is_not_dir<T: FileSystem>(fs: &T, dir: &Path) {
+    let file = dir.join("file");
+
+    fs.create_file(&file, "").unwrap();
+
+    assert_error_kind(
+        fs.read_dir(dir.join("missing")),
+        ErrorKind::NotFound,
+        "read_dir on a missing directory",
+    );
+    assert_error(fs.read_dir(&file), "read_dir on a file");
+}
+
+/// `remove_file` removes a file, and fails with `NotFound` if there is none.
+pub fn remove_file_removes_file<T: FileSystem>(fs: &T, dir: &Path) {
+    let path = dir.join("file");
+
+    fs.create_file(&path, "").unwrap();
+    fs.remove_file(&path).unwrap();
+
+    assert!(!fs.exists(&path));
+    assert_error_kind(
+        fs.remove_file(&path),
+        ErrorKind::NotFound,
+        "remove_file on a missing file",
+    );
+}
+
+/// `remove_file` fails for directories, leaving them in place.
+pub fn remove_file_fails_if_node_is_dir<T: FileSystem>(fs: &T, dir: &Path) {
+    let path = dir.join("dir");
+
+    fs.create_dir(&path).unwrap();
+
+    assert_error(fs.remove_file(&path), "remove_file on a directory");
+    assert!(fs.is_dir(&path));
+}
+
+/// `remove_dir` removes an empty directory, and fails for one with children.
+pub fn remove_dir_requires_empty_dir<T: FileSystem>(fs: &T, dir: &Path) {
+    let path = dir.join("dir");
+
+    fs.create_dir(&path).unwrap();
+    fs.create_file(path.join("file"), "").unwrap();
+
+    assert_error(
+        fs.remove_dir(&path),
+        "remove_dir on a directory with children",
+    );
+    assert!(fs.is_file(path.join("file")));
+
+    fs.remove_file(path.join("file")).unwrap();
+    fs.remove_dir(&path).unwrap();
+
+    assert!(!fs.exists(&path));
+}
+
+/// `remove_dir` fails with `NotFound` if there is no node at the path, and fails for files.
+pub fn remove_dir_fails_if_node_is_not_dir<T: FileSystem>(fs: &T, dir: &Path) {
+    let file = dir.join("file");
+
+    fs.create_file(&file, "").unwrap();
+
+    assert_error_kind(
+        fs.remove_dir(dir.join("missing")),
+        ErrorKind::NotFound,
+        "remove_dir on a missing directory",
+    );
+    assert_error(fs.remove_dir(&file), "remove_dir on a file");
+    assert!(fs.is_file(&file));
+}
+
+/// `remove_dir_all` removes a directory along with all of its descendants.
+pub fn remove_dir_all_removes_descendants<T: FileSystem>(fs: &T, dir: &Path) {
+    let path = dir.join("dir");
+
+    fs.create_dir_all(path.join("a").join("b")).unwrap();
+    fs.create_file(path.join("a").join("file"), "").unwrap();
+    fs.remove_dir_all(&path).unwrap();
+
+    assert!(!fs.exists(&path));
+    assert!(fs.is_dir(dir));
+}
+
+/// `rename` moves a file, which keeps its contents.
+pub fn rename_moves_file<T: FileSystem>(fs: &T, dir: &Path) {
+    let from = dir.join("from");
+    let to = dir.join("to");
+
+    fs.create_file(&from, "contents").unwrap();
+    fs.rename(&from, &to).unwrap();
+
+    assert!(!fs.exists(&from));
+    assert_eq!(fs.read_file(&to).unwrap(), b"contents");
+}
+
+/// `rename` moves a directory along with all of its descendants.
+pub fn rename_moves_descendants<T: FileSystem>(fs: &T, dir: &Path) {
+    let from = dir.join("from");
+    let to = dir.join("to");
+
+    fs.create_dir_all(from.join("child")).unwrap();
+    fs.create_file(from.join("child").join("file"), "contents")
+        .unwrap();
+    fs.rename(&from, &to).unwrap();
+
+    assert!(!fs.exists(&from));
+    assert_eq!(
+        fs.read_file(to.join("child").join("file")).unwrap(),
+        b"contents"
+    );
+}
+
+/// `rename` replaces an existing file.
+pub fn rename_replaces_file<T: FileSystem>(fs: &T, dir: &Path) {
+    let from = dir.join("from");
+    let to = dir.join("to");
+
+    fs.create_file(&from, "new").unwrap();
+    fs.create_file(&to, "old").unwrap();
+    fs.rename(&from, &to).unwrap();
+
+    assert!(!fs.exists(&from));
+    assert_eq!(fs.read_file(&to).unwrap(), b"new");
+}
+
+/// `rename` replaces an existing empty directory, but fails for one with children.
+pub fn rename_replaces_only_empty_dir<T: FileSystem>(fs: &T, dir: &Path) {
+    let from = dir.join("from");
+    let empty = dir.join("empty");
+    let full = dir.join("full");
+
+    fs.create_dir(&from).unwrap();
+    fs.create_dir(&empty).unwrap();
+    fs.create_dir(&full).unwrap();
+    fs.create_file(full.join("file"), "").unwrap();
+
+    assert_error(
+        fs.rename(&from, &full),
+        "rename onto a directory with children",
+    );
+    assert!(fs.is_file(full.join("file")));
+
+    fs.rename(&from, &empty).unwrap();
+
+    assert!(!fs.exists(&from));
+    assert!(fs.is_dir(&empty));
+}
+
+/// `rename` fails if one of the nodes is a file and the other a directory, leaving both in place.
+pub fn rename_fails_if_types_differ<T: FileSystem>(fs: &T, dir: &Path) {
+    let file = dir.join("file");
+    let subdir = dir.join("dir");
+
+    fs.create_file(&file, "").unwrap();
+    fs.create_dir(&subdir).unwrap();
+
+    assert_error(
+        fs.rename(&file, &subdir),
+        "rename of a file onto a directory",
+    );
+    assert_error(
+        fs.rename(&subdir, &file),
+        "rename of a directory onto a file",
+    );
+    assert!(fs.is_file(&file));
+    assert!(fs.is_dir(&subdir));
+}
+
+/// `rename` fails if a directory would be moved inside itself, leaving it in place.
+pub fn rename_fails_if_destination_is_inside_original<T: FileSystem>(fs: &T, dir: &Path) {
+    let from = dir.join("from");
+
+    fs.create_dir(&from).unwrap();
+
+    assert_error(
+        fs.rename(&from, from.join("inside")),
+        "rename of a directory inside itself",
+    );
+    assert!(fs.is_dir(&from));
+}
+
+/// `rename` succeeds without changing anything if both paths are the same.
+pub fn rename_to_same_path_does_nothing<T: FileSystem>(fs: &T, dir: &Path) {
+    let path = dir.join("file");
+
+    fs.create_file(&path, "contents").unwrap();
+    fs.rename(&path, &path).unwrap();
+
+    assert_eq!(fs.read_file(&path).unwrap(), b"contents");
+}
+
+/// `rename` fails with `NotFound` if there is no node at the original path, or no parent at the
+/// destination.
+pub fn rename_fails_if_path_does_not_exist<T: FileSystem>(fs: &T, dir: &Path) {
+    let file = dir.join("file");
+
+    fs.create_file(&file, "").unwrap();
+
+    assert_error_kind(
+        fs.rename(dir.join("missing"), dir.join("to")),
+        ErrorKind::NotFound,
+        "rename of a missing node",
+    );
+    assert_error_kind(
+        fs.rename(&file, dir.join("missing").join("to")),
+        ErrorKind::NotFound,
+        "rename to a missing directory",
+    );
+    assert!(fs.is_file(&file));
+}
+
+/// `copy_file` copies the contents of a file, replacing those of any existing destination.
+pub fn copy_file_copies_contents<T: FileSystem>(fs: &T, dir: &Path) {
+    let from = dir.join("from");
+    let to = dir.join("to");
+
+    fs.create_file(&from, "new").unwrap();
+    fs.create_file(&to, "old contents").unwrap();
+    fs.copy_file(&from, &to).unwrap();
+
+    assert_eq!(fs.read_file(&from).unwrap(), b"new");
+    assert_eq!(fs.read_file(&to).unwrap(), b"new");
+}
+
+/// `copy_file` fails with `NotFound` if there is no file to copy.
+pub fn copy_file_fails_if_original_does_not_exist<T: FileSystem>(fs: &T, dir: &Path) {
+    let to = dir.join("to");
+
+    assert_error_kind(
+        fs.copy_file(dir.join("missing"), &to),
+        ErrorKind::NotFound,
+        "copy_file of a missing file",
+    );
+    assert!(!fs.exists(&to));
+}
+
+/// `set_len` truncates a file, or extends it with zeros.
+pub fn set_len_truncates_and_extends<T: FileSystem>(fs: &T, dir: &Path) {
+    let path = dir.join("file");
+
+    fs.create_file(&path, "contents").unwrap();
+    fs.set_len(&path, 3).unwrap();
+
+    assert_eq!(fs.read_file(&path).unwrap(), b"con");
+
+    fs.set_len(&path, 5).unwrap();
+
+    assert_eq!(fs.read_file(&path).unwrap(), b"con\0\0");
+}
+
+/// `metadata` describes files and directories, and fails with `NotFound` if there is no node.
+pub fn metadata_describes_nodes<T: FileSystem>(fs: &T, dir: &Path) {
+    let file = dir.join("file");
+
+    fs.create_file(&file, "contents").unwrap();
+
+    let metadata = fs.metadata(&file).unwrap();
+
+    assert!(metadata.is_file());
+    assert_eq!(metadata.len(), 8);
+    assert!(fs.metadata(dir).unwrap().is_dir());
+    assert_error_kind(
+        fs.metadata(dir.join("missing")),
+        ErrorKind::NotFound,
+        "metadata of a missing node",
+    );
+}
+
+/// Opening a file with `create_new` fails with `AlreadyExists` if it exists.
+pub fn open_with_create_new_fails_if_file_exists<T: FileSystem>(fs: &T, dir: &Path) {
+    let path = dir.join("file");
+    let mut options = OpenOptions::new();
+
+    options.write(true).create_new(true);
+
+    fs.open_with_options(&path, &options)
+        .unwrap()
+        .write_all(b"contents")
+        .unwrap();
+
+    assert_error_kind(
+        fs.open_with_options(&path, &options),
+        ErrorKind::AlreadyExists,
+        "open with create_new on a file",
+    );
+    assert_eq!(fs.read_file(&path).unwrap(), b"contents");
+}
+
+/// Paths with `.` and `..` components are resolved like the OS does.
+pub fn dot_components_are_resolved<T: FileSystem>(fs: &T, dir: &Path) {
+    fs.create_dir(dir.join("a")).unwrap();
+    fs.create_file(dir.join("a").join("..").join("file"), "contents")
+        .unwrap();
+
+    assert_eq!(fs.read_file(dir.join("file")).unwrap(), b"contents");
+    assert!(fs.is_dir(dir.join("a").join(".")));
+}
+
+/// `read_link` returns the target of a symlink as it was given, and fails for other nodes.
+pub fn read_link_returns_target<T: SymlinkFileSystem>(fs: &T, dir: &Path) {
+    let link = dir.join("link");
+    let file = dir.join("file");
+
+    fs.create_file(&file, "").unwrap();
+    fs.symlink_file("file", &link).unwrap();
+
+    assert_eq!(fs.read_link(&link).unwrap(), PathBuf::from("file"));
+    assert_error(fs.read_link(&file), "read_link on a file");
+}
+
+/// Reading through a symlink reads its target, including relative targets, which are resolved
+/// against the directory containing the symlink.
+pub fn symlinks_are_followed<T: SymlinkFileSystem>(fs: &T, dir: &Path) {
+    fs.create_dir(dir.join("dir")).unwrap();
+    fs.create_file(dir.join("dir").join("file"), "contents")
+        .unwrap();
+    fs.symlink_file("dir/file", dir.join("file_link")).unwrap();
+    fs.symlink_dir(dir.join("dir"), dir.join("dir_link"))
+        .unwrap();
+
+    assert_eq!(fs.read_file(dir.join("file_link")).unwrap(), b"contents");
+    assert_eq!(
+        fs.read_file(dir.join("dir_link").join("file")).unwrap(),
+        b"contents"
+    );
+    assert!(fs.is_file(dir.join("file_link")));
+    assert!(fs.is_dir(dir.join("dir_link")));
+    assert!(fs.metadata(dir.join("file_link")).unwrap().is_file());
+}
+
+/// `symlink_metadata` describes the symlink itself rather than its target.
+pub fn symlink_metadata_does_not_follow_symlinks<T: SymlinkFileSystem>(fs: &T, dir: &Path) {
+    let link = dir.join("link");
+
+    fs.create_file(dir.join("file"), "contents").unwrap();
+    fs.symlink_file(dir.join("file"), &link).unwrap();
+
+    let metadata = fs.symlink_metadata(&link).unwrap();
+
+    assert!(!metadata.is_file());
+    assert!(!metadata.is_dir());
+}
+
+/// A symlink whose target does not exist is not reported as existing, but can still be read and
+/// removed.
+pub fn broken_symlinks_do_not_exist<T: SymlinkFileSystem>(fs: &T, dir: &Path) {
+    let link = dir.join("link");
+
+    fs.symlink_file(dir.join("missing"), &link).unwrap();
+
+    assert!(!fs.exists(&link));
+    assert_error_kind(
+        fs.read_file(&link),
+        ErrorKind::NotFound,
+        "read_file through a broken symlink",
+    );
+    assert_eq!(fs.read_link(&link).unwrap(), dir.join("missing"));
+
+    fs.remove_file(&link).unwrap();
+
+    assert_error(fs.read_link(&link), "read_link on a removed symlink");
+}
+
+/// Removing or renaming a symlink affects the symlink rather than its target.
+pub fn symlinks_are_removed_and_renamed_themselves<T: SymlinkFileSystem>(fs: &T, dir: &Path) {
+    let file = dir.join("file");
+    let link = dir.join("link");
+    let renamed = dir.join("renamed");
+
+    fs.create_file(&file, "contents").unwrap();
+    fs.symlink_file(&file, &link).unwrap();
+    fs.rename(&link, &renamed).unwrap();
+
+    assert_eq!(fs.read_link(&renamed).unwrap(), file);
+    assert!(!fs.exists(&link));
+
+    fs.remove_file(&renamed).unwrap();
+
+    assert!(!fs.exists(&renamed));
+    assert_eq!(fs.read_file(&file).unwrap(), b"contents");
+}
+
+/// Creating a symlink fails with `AlreadyExists` if any node exists at the path.
+pub fn symlink_fails_if_node_exists<T: SymlinkFileSystem>(fs: &T, dir: &Path) {
+    let file = dir.join("file");
+
+    fs.create_file(&file, "contents").unwrap();
+
+    assert_error_kind(
+        fs.symlink_file(dir.join("target"), &file),
+        ErrorKind::AlreadyExists,
+        "symlink_file onto a file",
+    );
+    assert_eq!(fs.read_file(&file).unwrap(), b"contents");
+}
+
+/// `canonicalize` resolves symlinks, along with `.` and `..` components.
+pub fn canonicalize_resolves_symlinks<T: SymlinkFileSystem>(fs: &T, dir: &Path) {
+    let dir = fs.canonicalize(dir).unwrap();
+
+    fs.create_dir(dir.join("real")).unwrap();
+    fs.create_file(dir.join("real").join("file"), "").unwrap();
+    fs.symlink_dir(dir.join("real"), dir.join("link")).unwrap();
+
+    assert_eq!(
+        fs.canonicalize(dir.join("link").join(".").join("file"))
+            .unwrap(),
+        dir.join("real").join("file")
+    );
+    assert_eq!(fs.canonicalize(dir.join("link").join("..")).unwrap(), dir);
+}
+
+/// Generates a test for every check in [`conformance`], given an expression, such as the name of
+/// a function, that is called to get a file system and an empty directory for each test.
+///
+/// The directory can be anything that implements `AsRef<Path>`, and is dropped once the test
+/// ends.
+/// Checks that use symlinks, which need the file system to implement [`SymlinkFileSystem`], are
+/// only generated when `symlinks` follows the expression.
+///
+/// ```
+/// mod conformance {
+///     use filesystem::{FakeFileSystem, FileSystem};
+///
+///     fn setup() -> (FakeFileSystem, &'static str) {
+///         let fs = FakeFileSystem::new();
+///
+///         fs.create_dir("/test").unwrap();
+///         (fs, "/test")
+///     }
+///
+///     filesystem::conformance_tests!(setup, symlinks);
+/// }
+/// ```
+///
+/// [`conformance`]: conformance/index.html
+/// [`SymlinkFileSystem`]: trait.SymlinkFileSystem.html
+#[macro_export]
+macro_rules! conformance_tests {
+    ($setup:expr) => {
+        $crate::conformance_tests!(@tests $setup;
+            create_dir_creates_empty_dir,
+            create_dir_fails_if_node_exists,
+            create_dir_fails_if_parent_does_not_exist,
+            create_dir_all_creates_ancestors,
+            create_dir_all_fails_if_ancestor_is_file,
+            create_file_writes_contents,
+            create_file_fails_if_node_exists,
+            create_file_fails_if_parent_does_not_exist,
+            write_file_creates_or_truncates,
+            write_file_fails_if_node_is_dir,
+            overwrite_file_requires_existing_file,
+            read_file_fails_if_node_is_not_file,
+            read_dir_lists_children,
+            read_dir_fails_if_node_is_not_dir,
+            remove_file_removes_file,
+            remove_file_fails_if_node_is_dir,
+            remove_dir_requires_empty_dir,
+            remove_dir_fails_if_node_is_not_dir,
+            remove_dir_all_removes_descendants,
+            rename_moves_file,
+            rename_moves_descendants,
+            rename_replaces_file,
+            rename_replaces_only_empty_dir,
+            rename_fails_if_types_differ,
+            rename_fails_if_destination_is_inside_original,
+            rename_to_same_path_does_nothing,
+            rename_fails_if_path_does_not_exist,
+            copy_file_copies_contents,
+            copy_file_fails_if_original_does_not_exist,
+            set_len_truncates_and_extends,
+            metadata_describes_nodes,
+            open_with_create_new_fails_if_file_exists,
+            dot_components_are_resolved
+        );
+    };
+    ($setup:expr, symlinks) => {
+        $crate::conformance_tests!($setup);
+        $crate::conformance_tests!(@tests $setup;
+            read_link_returns_target,
+            symlinks_are_followed,
+            symlink_metadata_does_not_follow_symlinks,
+            broken_symlinks_do_not_exist,
+            symlinks_are_removed_and_renamed_themselves,
+            symlink_fails_if_node_exists,
+            canonicalize_resolves_symlinks
+        );
+    };
+    (@tests $setup:expr; $($check:ident),*) => {
+        $(
+            #[test]
+            fn $check() {
+                let (fs, dir) = $setup();
+
+                $crate::conformance::$check(&fs, ::std::convert::AsRef::as_ref(&dir));
+            }
+        )*
+    };
+}
@@ -0,0 +1,736 @@
+use std::ffi::OsString;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[cfg(feature = "lock")]
+use LockableFile;
+#[cfg(feature = "hash")]
+use {Algorithm, Digest};
+use {
+    DirEntry, FileSystem, FileType, GlobOptions, Metadata, OpenFile, OpenOptions, ReadDir,
+    ReadDirOptions, Result, WalkDirEntry, WalkDirOptions,
+};
+
+/// An object-safe counterpart to [`FileSystem`].
+///
+/// [`FileSystem`] uses generic methods, so it cannot be used as a trait object.
+/// `DynFileSystem` takes concrete argument types instead, which allows file systems to be stored
+/// as `Box<dyn DynFileSystem>`.
+///
+/// Every [`FileSystem`] implements `DynFileSystem`, and `Box<dyn DynFileSystem>` implements
+/// [`FileSystem`], so a boxed file system can be passed to any code that is generic over
+/// [`FileSystem`].
+///
+/// [`FileSystem`]: trait.FileSystem.html
+pub trait DynFileSystem {
+    /// See [`FileSystem::current_dir`](trait.FileSystem.html#tymethod.current_dir).
+    fn current_dir(&self) -> Result<PathBuf>;
+    /// See [`FileSystem::set_current_dir`](trait.FileSystem.html#tymethod.set_current_dir).
+    fn set_current_dir(&self, path: &Path) -> Result<()>;
+
+    /// See [`FileSystem::metadata`](trait.FileSystem.html#tymethod.metadata).
+    fn metadata(&self, path: &Path) -> Result<DynMetadata>;
+    /// See [`FileSystem::canonicalize`](trait.FileSystem.html#tymethod.canonicalize).
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf>;
+
+    /// See [`FileSystem::is_dir`](trait.FileSystem.html#method.is_dir).
+    fn is_dir(&self, path: &Path) -> bool;
+    /// See [`FileSystem::is_file`](trait.FileSystem.html#method.is_file).
+    fn is_file(&self, path: &Path) -> bool;
+    /// See [`FileSystem::exists`](trait.FileSystem.html#method.exists).
+    fn exists(&self, path: &Path) -> bool;
+    /// See [`FileSystem::try_exists`](trait.FileSystem.html#method.try_exists).
+    fn try_exists(&self, path: &Path) -> Result<bool>;
+
+    /// See [`FileSystem::create_dir`](trait.FileSystem.html#tymethod.create_dir).
+    fn create_dir(&self, path: &Path) -> Result<()>;
+    /// See [`FileSystem::create_dir_all`](trait.FileSystem.html#method.create_dir_all).
+    fn create_dir_all(&self, path: &Path) -> Result<()>;
+    /// See [`FileSystem::remove_dir`](trait.FileSystem.html#tymethod.remove_dir).
+    fn remove_dir(&self, path: &Path) -> Result<()>;
+    /// See [`FileSystem::remove_dir_all`](trait.FileSystem.html#method.remove_dir_all).
+    fn remove_dir_all(&self, path: &Path) -> Result<()>;
+    /// See [`FileSystem::read_dir`](trait.FileSystem.html#tymethod.read_dir).
+    fn read_dir(&self, path: &Path) -> Result<DynReadDir>;
+    /// See [`FileSystem::read_dir_with_options`](trait.FileSystem.html#method.read_dir_with_options).
+    fn read_dir_with_options(
+        &self,
+        path: &Path,
+        options: &ReadDirOptions,
+    ) -> Result<Vec<DynDirEntry>>;
+    /// See [`FileSystem::walk_dir`](trait.FileSystem.html#method.walk_dir).
+    fn walk_dir(&self, path: &Path) -> Result<DynWalkDir>;
+    /// See [`FileSystem::walk_dir_with_options`](trait.FileSystem.html#tymethod.walk_dir_with_options).
+    fn walk_dir_with_options(&self, path: &Path, options: &WalkDirOptions) -> Result<DynWalkDir>;
+    /// See [`FileSystem::glob`](trait.FileSystem.html#method.glob).
+    fn glob(&self, pattern: &str) -> Result<Vec<PathBuf>>;
+    /// See [`FileSystem::glob_with`](trait.FileSystem.html#method.glob_with).
+    fn glob_with(&self, pattern: &str, options: &GlobOptions) -> Result<Vec<PathBuf>>;
+
+    /// See [`FileSystem::create_file`](trait.FileSystem.html#method.create_file).
+    fn create_file(&self, path: &Path, buf: &[u8]) -> Result<()>;
+    /// See [`FileSystem::write_file`](trait.FileSystem.html#method.write_file).
+    fn write_file(&self, path: &Path, buf: &[u8]) -> Result<()>;
+    /// See [`FileSystem::overwrite_file`](trait.FileSystem.html#method.overwrite_file).
+    fn overwrite_file(&self, path: &Path, buf: &[u8]) -> Result<()>;
+    /// See [`FileSystem::set_len`](trait.FileSystem.html#method.set_len).
+    fn set_len(&self, path: &Path, size: u64) -> Result<()>;
+    /// See [`FileSystem::sync_file`](trait.FileSystem.html#method.sync_file).
+    fn sync_file(&self, path: &Path) -> Result<()>;
+    /// See [`FileSystem::sync_dir`](trait.FileSystem.html#tymethod.sync_dir).
+    fn sync_dir(&self, path: &Path) -> Result<()>;
+    /// See [`FileSystem::read_file`](trait.FileSystem.html#method.read_file).
+    fn read_file(&self, path: &Path) -> Result<Vec<u8>>;
+    /// See [`FileSystem::read_file_to_string`](trait.FileSystem.html#method.read_file_to_string).
+    fn read_file_to_string(&self, path: &Path) -> Result<String>;
+    /// See [`FileSystem::read_file_into`](trait.FileSystem.html#method.read_file_into).
+    fn read_file_into(&self, path: &Path, buf: &mut Vec<u8>) -> Result<usize>;
+    /// See [`FileSystem::open`](trait.FileSystem.html#method.open).
+    fn open(&self, path: &Path) -> Result<DynOpenFile>;
+    /// See [`FileSystem::open_with_options`](trait.FileSystem.html#tymethod.open_with_options).
+    fn open_with_options(&self, path: &Path, options: &OpenOptions) -> Result<DynOpenFile>;
+    /// See [`FileSystem::hash_file`](trait.FileSystem.html#method.hash_file).
+    #[cfg(feature = "hash")]
+    fn hash_file(&self, path: &Path, algorithm: Algorithm) -> Result<Digest>;
+    /// See [`FileSystem::remove_file`](trait.FileSystem.html#tymethod.remove_file).
+    fn remove_file(&self, path: &Path) -> Result<()>;
+    /// See [`FileSystem::copy_file`](trait.FileSystem.html#method.copy_file).
+    fn copy_file(&self, from: &Path, to: &Path) -> Result<()>;
+    /// See [`FileSystem::copy_dir`](trait.FileSystem.html#tymethod.copy_dir).
+    fn copy_dir(&self, from: &Path, to: &Path) -> Result<()>;
+
+    /// See [`FileSystem::rename`](trait.FileSystem.html#tymethod.rename).
+    fn rename(&self, from: &Path, to: &Path) -> Result<()>;
+
+    /// See [`FileSystem::readonly`](trait.FileSystem.html#method.readonly).
+    fn readonly(&self, path: &Path) -> Result<bool>;
+    /// See [`FileSystem::set_readonly`](trait.FileSystem.html#tymethod.set_readonly).
+    fn set_readonly(&self, path: &Path, readonly: bool) -> Result<()>;
+    /// See [`FileSystem::is_hidden`](trait.FileSystem.html#method.is_hidden).
+    fn is_hidden(&self, path: &Path) -> Result<bool>;
+
+    /// See [`FileSystem::modified`](trait.FileSystem.html#method.modified).
+    fn modified(&self, path: &Path) -> Result<SystemTime>;
+    /// See [`FileSystem::set_modified`](trait.FileSystem.html#tymethod.set_modified).
+    fn set_modified(&self, path: &Path, time: SystemTime) -> Result<()>;
+    /// See [`FileSystem::accessed`](trait.FileSystem.html#method.accessed).
+    fn accessed(&self, path: &Path) -> Result<SystemTime>;
+    /// See [`FileSystem::set_accessed`](trait.FileSystem.html#tymethod.set_accessed).
+    fn set_accessed(&self, path: &Path, time: SystemTime) -> Result<()>;
+
+    /// See [`FileSystem::len`](trait.FileSystem.html#method.len).
+    fn len(&self, path: &Path) -> u64;
+    /// See [`FileSystem::dir_size`](trait.FileSystem.html#method.dir_size).
+    fn dir_size(&self, path: &Path, follow_links: bool) -> Result<u64>;
+
+    /// See [`FileSystem::available_space`](trait.FileSystem.html#tymethod.available_space).
+    fn available_space(&self, path: &Path) -> Result<u64>;
+    /// See [`FileSystem::total_space`](trait.FileSystem.html#tymethod.total_space).
+    fn total_space(&self, path: &Path) -> Result<u64>;
+}
+
+/// A boxed [`DirEntry`] returned by [`DynFileSystem::read_dir`].
+///
+/// [`DirEntry`]: trait.DirEntry.html
+/// [`DynFileSystem::read_dir`]: trait.DynFileSystem.html#tymethod.read_dir
+pub type DynDirEntry = Box<dyn DirEntry<Metadata = DynMetadata>>;
+
+/// A boxed [`Metadata`] returned by [`DynFileSystem::metadata`].
+///
+/// [`Metadata`]: trait.Metadata.html
+/// [`DynFileSystem::metadata`]: trait.DynFileSystem.html#tymethod.metadata
+pub type DynMetadata = Box<dyn Metadata>;
+
+/// A boxed [`OpenFile`] returned by [`DynFileSystem::open`].
+///
+/// [`OpenFile`]: trait.OpenFile.html
+/// [`DynFileSystem::open`]: trait.DynFileSystem.html#method.open
+pub type DynOpenFile = Box<dyn OpenFile>;
+
+/// Iterator over the entries in a directory, returned by [`DynFileSystem::read_dir`].
+///
+/// [`DynFileSystem::read_dir`]: trait.DynFileSystem.html#tymethod.read_dir
+pub struct DynReadDir(Box<dyn Iterator<Item = io::Result<DynDirEntry>>>);
+
+impl DynReadDir {
+    fn new<T, I>(entries: I) -> Self
+    where
+        T: DirEntry + 'static,
+        T::Metadata: 'static,
+        I: Iterator<Item = io::Result<T>> + 'static,
+    {
+        DynReadDir(Box::new(
+            entries.map(|e| e.map(|e| Box::new(BoxedDirEntry(e)) as DynDirEntry)),
+        ))
+    }
+}
+
+/// A [`DirEntry`] whose metadata is boxed, so that it can be returned as a [`DynDirEntry`].
+///
+/// [`DirEntry`]: trait.DirEntry.html
+/// [`DynDirEntry`]: type.DynDirEntry.html
+struct BoxedDirEntry<T>(T);
+
+impl<T> DirEntry for BoxedDirEntry<T>
+where
+    T: DirEntry,
+    T::Metadata: 'static,
+{
+    type Metadata = DynMetadata;
+
+    fn file_name(&self) -> OsString {
+        self.0.file_name()
+    }
+
+    fn path(&self) -> PathBuf {
+        self.0.path()
+    }
+
+    fn file_type(&self) -> io::Result<FileType> {
+        self.0.file_type()
+    }
+
+    fn metadata(&self) -> io::Result<DynMetadata> {
+        self.0
+            .metadata()
+            .map(|metadata| Box::new(metadata) as DynMetadata)
+    }
+}
+
+impl fmt::Debug for DynReadDir {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("DynReadDir").finish()
+    }
+}
+
+impl Iterator for DynReadDir {
+    type Item = io::Result<DynDirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+impl ReadDir<DynDirEntry> for DynReadDir {}
+
+/// A boxed recursive directory iterator returned by [`DynFileSystem::walk_dir`].
+///
+/// [`DynFileSystem::walk_dir`]: trait.DynFileSystem.html#method.walk_dir
+pub type DynWalkDir = Box<dyn Iterator<Item = io::Result<WalkDirEntry>>>;
+
+impl<T: DirEntry + ?Sized> DirEntry for Box<T> {
+    type Metadata = T::Metadata;
+
+    fn file_name(&self) -> OsString {
+        (**self).file_name()
+    }
+
+    fn path(&self) -> PathBuf {
+        (**self).path()
+    }
+
+    fn file_type(&self) -> io::Result<FileType> {
+        (**self).file_type()
+    }
+
+    fn metadata(&self) -> io::Result<T::Metadata> {
+        (**self).metadata()
+    }
+}
+
+impl<T: Metadata + ?Sized> Metadata for Box<T> {
+    fn file_type(&self) -> FileType {
+        (**self).file_type()
+    }
+
+    fn len(&self) -> u64 {
+        (**self).len()
+    }
+
+    fn readonly(&self) -> bool {
+        (**self).readonly()
+    }
+
+    fn created(&self) -> io::Result<SystemTime> {
+        (**self).created()
+    }
+
+    fn modified(&self) -> io::Result<SystemTime> {
+        (**self).modified()
+    }
+
+    fn accessed(&self) -> io::Result<SystemTime> {
+        (**self).accessed()
+    }
+}
+
+impl<T: OpenFile + ?Sized> OpenFile for Box<T> {
+    fn set_len(&self, size: u64) -> io::Result<()> {
+        (**self).set_len(size)
+    }
+
+    fn sync_all(&self) -> io::Result<()> {
+        (**self).sync_all()
+    }
+}
+
+#[cfg(feature = "lock")]
+impl<T: LockableFile + ?Sized> LockableFile for Box<T> {
+    fn lock_shared(&self) -> io::Result<()> {
+        (**self).lock_shared()
+    }
+
+    fn lock_exclusive(&self) -> io::Result<()> {
+        (**self).lock_exclusive()
+    }
+
+    fn try_lock_shared(&self) -> io::Result<()> {
+        (**self).try_lock_shared()
+    }
+
+    fn try_lock_exclusive(&self) -> io::Result<()> {
+        (**self).try_lock_exclusive()
+    }
+
+    fn unlock(&self) -> io::Result<()> {
+        (**self).unlock()
+    }
+}
+
+impl<T> DynFileSystem for T
+where
+    T: FileSystem,
+    T::DirEntry: 'static,
+    T::ReadDir: 'static,
+    T::Metadata: 'static,
+    T::OpenFile: 'static,
+    T::WalkDir: 'static,
+{
+    fn current_dir(&self) -> Result<PathBuf> {
+        FileSystem::current_dir(self)
+    }
+
+    fn set_current_dir(&self, path: &Path) -> Result<()> {
+        FileSystem::set_current_dir(self, path)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<DynMetadata> {
+        FileSystem::metadata(self, path).map(|m| Box::new(m) as DynMetadata)
+    }
+
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf> {
+        FileSystem::canonicalize(self, path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        FileSystem::is_dir(self, path)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        FileSystem::is_file(self, path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        FileSystem::exists(self, path)
+    }
+
+    fn try_exists(&self, path: &Path) -> Result<bool> {
+        FileSystem::try_exists(self, path)
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        FileSystem::create_dir(self, path)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        FileSystem::create_dir_all(self, path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        FileSystem::remove_dir(self, path)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> Result<()> {
+        FileSystem::remove_dir_all(self, path)
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<DynReadDir> {
+        FileSystem::read_dir(self, path).map(DynReadDir::new)
+    }
+
+    fn read_dir_with_options(
+        &self,
+        path: &Path,
+        options: &ReadDirOptions,
+    ) -> Result<Vec<DynDirEntry>> {
+        FileSystem::read_dir_with_options(self, path, options).map(|entries| {
+            entries
+                .into_iter()
+                .map(|e| Box::new(BoxedDirEntry(e)) as DynDirEntry)
+                .collect()
+        })
+    }
+
+    fn walk_dir(&self, path: &Path) -> Result<DynWalkDir> {
+        FileSystem::walk_dir(self, path).map(|w| Box::new(w) as DynWalkDir)
+    }
+
+    fn walk_dir_with_options(&self, path: &Path, options: &WalkDirOptions) -> Result<DynWalkDir> {
+        FileSystem::walk_dir_with_options(self, path, options).map(|w| Box::new(w) as DynWalkDir)
+    }
+
+    fn glob(&self, pattern: &str) -> Result<Vec<PathBuf>> {
+        FileSystem::glob(self, pattern)
+    }
+
+    fn glob_with(&self, pattern: &str, options: &GlobOptions) -> Result<Vec<PathBuf>> {
+        FileSystem::glob_with(self, pattern, options)
+    }
+
+    fn create_file(&self, path: &Path, buf: &[u8]) -> Result<()> {
+        FileSystem::create_file(self, path, buf)
+    }
+
+    fn write_file(&self, path: &Path, buf: &[u8]) -> Result<()> {
+        FileSystem::write_file(self, path, buf)
+    }
+
+    fn overwrite_file(&self, path: &Path, buf: &[u8]) -> Result<()> {
+        FileSystem::overwrite_file(self, path, buf)
+    }
+
+    fn set_len(&self, path: &Path, size: u64) -> Result<()> {
+        FileSystem::set_len(self, path, size)
+    }
+
+    fn sync_file(&self, path: &Path) -> Result<()> {
+        FileSystem::sync_file(self, path)
+    }
+
+    fn sync_dir(&self, path: &Path) -> Result<()> {
+        FileSystem::sync_dir(self, path)
+    }
+
+    fn read_file(&self, path: &Path) -> Result<Vec<u8>> {
+        FileSystem::read_file(self, path)
+    }
+
+    fn read_file_to_string(&self, path: &Path) -> Result<String> {
+        FileSystem::read_file_to_string(self, path)
+    }
+
+    fn read_file_into(&self, path: &Path, buf: &mut Vec<u8>) -> Result<usize> {
+        FileSystem::read_file_into(self, path, buf)
+    }
+
+    fn open(&self, path: &Path) -> Result<DynOpenFile> {
+        FileSystem::open(self, path).map(|f| Box::new(f) as DynOpenFile)
+    }
+
+    #[cfg(feature = "hash")]
+    fn hash_file(&self, path: &Path, algorithm: Algorithm) -> Result<Digest> {
+        FileSystem::hash_file(self, path, algorithm)
+    }
+
+    fn open_with_options(&self, path: &Path, options: &OpenOptions) -> Result<DynOpenFile> {
+        FileSystem::open_with_options(self, path, options).map(|f| Box::new(f) as DynOpenFile)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        FileSystem::remove_file(self, path)
+    }
+
+    fn copy_file(&self, from: &Path, to: &Path) -> Result<()> {
+        FileSystem::copy_file(self, from, to)
+    }
+
+    fn copy_dir(&self, from: &Path, to: &Path) -> Result<()> {
+        FileSystem::copy_dir(self, from, to)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        FileSystem::rename(self, from, to)
+    }
+
+    fn readonly(&self, path: &Path) -> Result<bool> {
+        FileSystem::readonly(self, path)
+    }
+
+    fn set_readonly(&self, path: &Path, readonly: bool) -> Result<()> {
+        FileSystem::set_readonly(self, path, readonly)
+    }
+
+    fn is_hidden(&self, path: &Path) -> Result<bool> {
+        FileSystem::is_hidden(self, path)
+    }
+
+    fn modified(&self, path: &Path) -> Result<SystemTime> {
+        FileSystem::modified(self, path)
+    }
+
+    fn set_modified(&self, path: &Path, time: SystemTime) -> Result<()> {
+        FileSystem::set_modified(self, path, time)
+    }
+
+    fn accessed(&self, path: &Path) -> Result<SystemTime> {
+        FileSystem::accessed(self, path)
+    }
+
+    fn set_accessed(&self, path: &Path, time: SystemTime) -> Result<()> {
+        FileSystem::set_accessed(self, path, time)
+    }
+
+    fn len(&self, path: &Path) -> u64 {
+        FileSystem::len(self, path)
+    }
+
+    fn dir_size(&self, path: &Path, follow_links: bool) -> Result<u64> {
+        FileSystem::dir_size(self, path, follow_links)
+    }
+
+    fn available_space(&self, path: &Path) -> Result<u64> {
+        FileSystem::available_space(self, path)
+    }
+
+    fn total_space(&self, path: &Path) -> Result<u64> {
+        FileSystem::total_space(self, path)
+    }
+}
+
+macro_rules! impl_file_system {
+    ($($t:tt)*) => {
+        impl<'a> FileSystem for $($t)* {
+            type DirEntry = DynDirEntry;
+            type ReadDir = DynReadDir;
+            type Metadata = DynMetadata;
+            type OpenFile = DynOpenFile;
+            type WalkDir = DynWalkDir;
+
+            fn current_dir(&self) -> Result<PathBuf> {
+                DynFileSystem::current_dir(&**self)
+            }
+
+            fn set_current_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+                DynFileSystem::set_current_dir(&**self, path.as_ref())
+            }
+
+            fn metadata<P: AsRef<Path>>(&self, path: P) -> Result<Self::Metadata> {
+                DynFileSystem::metadata(&**self, path.as_ref())
+            }
+
+            fn canonicalize<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf> {
+                DynFileSystem::canonicalize(&**self, path.as_ref())
+            }
+
+            fn is_dir<P: AsRef<Path>>(&self, path: P) -> bool {
+                DynFileSystem::is_dir(&**self, path.as_ref())
+            }
+
+            fn is_file<P: AsRef<Path>>(&self, path: P) -> bool {
+                DynFileSystem::is_file(&**self, path.as_ref())
+            }
+
+            fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+                DynFileSystem::exists(&**self, path.as_ref())
+            }
+
+            fn try_exists<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+                DynFileSystem::try_exists(&**self, path.as_ref())
+            }
+
+            fn create_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+                DynFileSystem::create_dir(&**self, path.as_ref())
+            }
+
+            fn create_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+                DynFileSystem::create_dir_all(&**self, path.as_ref())
+            }
+
+            fn remove_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+                DynFileSystem::remove_dir(&**self, path.as_ref())
+            }
+
+            fn remove_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+                DynFileSystem::remove_dir_all(&**self, path.as_ref())
+            }
+
+            fn read_dir<P: AsRef<Path>>(&self, path: P) -> Result<Self::ReadDir> {
+                DynFileSystem::read_dir(&**self, path.as_ref())
+            }
+
+            fn read_dir_with_options<P: AsRef<Path>>(
+                &self,
+                path: P,
+                options: &ReadDirOptions,
+            ) -> Result<Vec<Self::DirEntry>> {
+                DynFileSystem::read_dir_with_options(&**self, path.as_ref(), options)
+            }
+
+            fn walk_dir<P: AsRef<Path>>(&self, path: P) -> Result<Self::WalkDir> {
+                DynFileSystem::walk_dir(&**self, path.as_ref())
+            }
+
+            fn walk_dir_with_options<P: AsRef<Path>>(
+                &self,
+                path: P,
+                options: &WalkDirOptions,
+            ) -> Result<Self::WalkDir> {
+                DynFileSystem::walk_dir_with_options(&**self, path.as_ref(), options)
+            }
+
+            fn glob(&self, pattern: &str) -> Result<Vec<PathBuf>> {
+                DynFileSystem::glob(&**self, pattern)
+            }
+
+            fn glob_with(&self, pattern: &str, options: &GlobOptions) -> Result<Vec<PathBuf>> {
+                DynFileSystem::glob_with(&**self, pattern, options)
+            }
+
+            fn create_file<P, B>(&self, path: P, buf: B) -> Result<()>
+            where
+                P: AsRef<Path>,
+                B: AsRef<[u8]>,
+            {
+                DynFileSystem::create_file(&**self, path.as_ref(), buf.as_ref())
+            }
+
+            fn write_file<P, B>(&self, path: P, buf: B) -> Result<()>
+            where
+                P: AsRef<Path>,
+                B: AsRef<[u8]>,
+            {
+                DynFileSystem::write_file(&**self, path.as_ref(), buf.as_ref())
+            }
+
+            fn overwrite_file<P, B>(&self, path: P, buf: B) -> Result<()>
+            where
+                P: AsRef<Path>,
+                B: AsRef<[u8]>,
+            {
+                DynFileSystem::overwrite_file(&**self, path.as_ref(), buf.as_ref())
+            }
+
+            fn set_len<P: AsRef<Path>>(&self, path: P, size: u64) -> Result<()> {
+                DynFileSystem::set_len(&**self, path.as_ref(), size)
+            }
+
+            fn sync_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+                DynFileSystem::sync_file(&**self, path.as_ref())
+            }
+
+            fn sync_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+                DynFileSystem::sync_dir(&**self, path.as_ref())
+            }
+
+            fn read_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
+                DynFileSystem::read_file(&**self, path.as_ref())
+            }
+
+            fn read_file_to_string<P: AsRef<Path>>(&self, path: P) -> Result<String> {
+                DynFileSystem::read_file_to_string(&**self, path.as_ref())
+            }
+
+            fn read_file_into<P, B>(&self, path: P, mut buf: B) -> Result<usize>
+            where
+                P: AsRef<Path>,
+                B: AsMut<Vec<u8>>,
+            {
+                DynFileSystem::read_file_into(&**self, path.as_ref(), buf.as_mut())
+            }
+
+            fn open<P: AsRef<Path>>(&self, path: P) -> Result<Self::OpenFile> {
+                DynFileSystem::open(&**self, path.as_ref())
+            }
+
+            #[cfg(feature = "hash")]
+            fn hash_file<P: AsRef<Path>>(&self, path: P, algorithm: Algorithm) -> Result<Digest> {
+                DynFileSystem::hash_file(&**self, path.as_ref(), algorithm)
+            }
+
+            fn open_with_options<P: AsRef<Path>>(
+                &self,
+                path: P,
+                options: &OpenOptions,
+            ) -> Result<Self::OpenFile> {
+                DynFileSystem::open_with_options(&**self, path.as_ref(), options)
+            }
+
+            fn remove_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+                DynFileSystem::remove_file(&**self, path.as_ref())
+            }
+
+            fn copy_file<P, Q>(&self, from: P, to: Q) -> Result<()>
+            where
+                P: AsRef<Path>,
+                Q: AsRef<Path>,
+            {
+                DynFileSystem::copy_file(&**self, from.as_ref(), to.as_ref())
+            }
+
+            fn copy_dir<P, Q>(&self, from: P, to: Q) -> Result<()>
+            where
+                P: AsRef<Path>,
+                Q: AsRef<Path>,
+            {
+                DynFileSystem::copy_dir(&**self, from.as_ref(), to.as_ref())
+            }
+
+            fn rename<P, Q>(&self, from: P, to: Q) -> Result<()>
+            where
+                P: AsRef<Path>,
+                Q: AsRef<Path>,
+            {
+                DynFileSystem::rename(&**self, from.as_ref(), to.as_ref())
+            }
+
+            fn readonly<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+                DynFileSystem::readonly(&**self, path.as_ref())
+            }
+
+            fn set_readonly<P: AsRef<Path>>(&self, path: P, readonly: bool) -> Result<()> {
+                DynFileSystem::set_readonly(&**self, path.as_ref(), readonly)
+            }
+
+            fn is_hidden<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+                DynFileSystem::is_hidden(&**self, path.as_ref())
+            }
+
+            fn modified<P: AsRef<Path>>(&self, path: P) -> Result<SystemTime> {
+                DynFileSystem::modified(&**self, path.as_ref())
+            }
+
+            fn set_modified<P: AsRef<Path>>(&self, path: P, time: SystemTime) -> Result<()> {
+                DynFileSystem::set_modified(&**self, path.as_ref(), time)
+            }
+
+            fn accessed<P: AsRef<Path>>(&self, path: P) -> Result<SystemTime> {
+                DynFileSystem::accessed(&**self, path.as_ref())
+            }
+
+            fn set_accessed<P: AsRef<Path>>(&self, path: P, time: SystemTime) -> Result<()> {
+                DynFileSystem::set_accessed(&**self, path.as_ref(), time)
+            }
+
+            fn len<P: AsRef<Path>>(&self, path: P) -> u64 {
+                DynFileSystem::len(&**self, path.as_ref())
+            }
+
+            fn dir_size<P: AsRef<Path>>(&self, path: P, follow_links: bool) -> Result<u64> {
+                DynFileSystem::dir_size(&**self, path.as_ref(), follow_links)
+            }
+
+            fn available_space<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+                DynFileSystem::available_space(&**self, path.as_ref())
+            }
+
+            fn total_space<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+                DynFileSystem::total_space(&**self, path.as_ref())
+            }
+        }
+    };
+}
+
+impl_file_system!(Box<dyn DynFileSystem + 'a>);
+impl_file_system!(Box<dyn DynFileSystem + Send + Sync + 'a>);
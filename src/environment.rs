@@ -0,0 +1,76 @@
+#[cfg(feature = "std-os")]
+use std::env;
+use std::env::VarError;
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+use std::result;
+
+#[cfg(feature = "std-os")]
+use OsFileSystem;
+
+/// Provides access to the environment variables and well-known directories of the current
+/// process, so that code reading them can be tested in isolation.
+pub trait Environment {
+    /// Returns the value of the environment variable `key`.
+    /// This is based on [`std::env::var`].
+    ///
+    /// # Errors
+    ///
+    /// * `key` is not set.
+    /// * The value of `key` is not valid Unicode.
+    ///
+    /// [`std::env::var`]: https://doc.rust-lang.org/std/env/fn.var.html
+    fn var<K: AsRef<OsStr>>(&self, key: K) -> result::Result<String, VarError>;
+    /// Sets the environment variable `key` to `value`.
+    /// This is based on [`std::env::set_var`].
+    ///
+    /// [`std::env::set_var`]: https://doc.rust-lang.org/std/env/fn.set_var.html
+    fn set_var<K: AsRef<OsStr>, V: AsRef<OsStr>>(&self, key: K, value: V);
+    /// Removes the environment variable `key`.
+    /// This is based on [`std::env::remove_var`].
+    ///
+    /// [`std::env::remove_var`]: https://doc.rust-lang.org/std/env/fn.remove_var.html
+    fn remove_var<K: AsRef<OsStr>>(&self, key: K);
+    /// Returns every environment variable and its value.
+    /// This is based on [`std::env::vars_os`].
+    ///
+    /// [`std::env::vars_os`]: https://doc.rust-lang.org/std/env/fn.vars_os.html
+    fn vars(&self) -> Vec<(OsString, OsString)>;
+    /// Returns the current user's home directory, if known.
+    /// This is based on [`std::env::home_dir`].
+    ///
+    /// [`std::env::home_dir`]: https://doc.rust-lang.org/std/env/fn.home_dir.html
+    fn home_dir(&self) -> Option<PathBuf>;
+    /// Returns the directory for temporary files.
+    /// This is based on [`std::env::temp_dir`].
+    ///
+    /// [`std::env::temp_dir`]: https://doc.rust-lang.org/std/env/fn.temp_dir.html
+    fn temp_dir(&self) -> PathBuf;
+}
+
+#[cfg(feature = "std-os")]
+impl Environment for OsFileSystem {
+    fn var<K: AsRef<OsStr>>(&self, key: K) -> result::Result<String, VarError> {
+        env::var(key)
+    }
+
+    fn set_var<K: AsRef<OsStr>, V: AsRef<OsStr>>(&self, key: K, value: V) {
+        env::set_var(key, value)
+    }
+
+    fn remove_var<K: AsRef<OsStr>>(&self, key: K) {
+        env::remove_var(key)
+    }
+
+    fn vars(&self) -> Vec<(OsString, OsString)> {
+        env::vars_os().collect()
+    }
+
+    fn home_dir(&self) -> Option<PathBuf> {
+        env::home_dir()
+    }
+
+    fn temp_dir(&self) -> PathBuf {
+        env::temp_dir()
+    }
+}
@@ -0,0 +1,189 @@
+use std::error;
+use std::fmt;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::result;
+
+/// A specialized `Result` for file system operations, whose errors say which operation failed
+/// and on which paths.
+pub type Result<T> = result::Result<T, Error>;
+
+/// An error from a file system operation, which wraps the underlying [`io::Error`] along with the
+/// name of the operation and the paths that it was given, such as
+/// `read_file "/a/b": entity not found`.
+///
+/// Errors can be converted back into an [`io::Error`] with `?` or [`into`], which keeps the
+/// operation and paths in its message but not its [`raw_os_error`], or unwrapped with
+/// [`into_inner`].
+/// Errors converted from an [`io::Error`] have no operation or paths.
+///
+/// [`io::Error`]: https://doc.rust-lang.org/std/io/struct.Error.html
+/// [`into`]: https://doc.rust-lang.org/std/convert/trait.Into.html#tymethod.into
+/// [`raw_os_error`]: https://doc.rust-lang.org/std/io/struct.Error.html#method.raw_os_error
+/// [`into_inner`]: #method.into_inner
+#[derive(Debug)]
+pub struct Error {
+    operation: Option<&'static str>,
+    paths: Vec<PathBuf>,
+    inner: io::Error,
+}
+
+impl Error {
+    /// Creates an error for `operation` on `paths` that failed with `error`.
+    pub fn new<E: Into<io::Error>>(operation: &'static str, paths: &[&Path], error: E) -> Self {
+        Error {
+            operation: Some(operation),
+            paths: paths.iter().map(|path| path.to_path_buf()).collect(),
+            inner: unwrap(error.into()),
+        }
+    }
+
+    /// Returns the kind of the underlying error.
+    pub fn kind(&self) -> ErrorKind {
+        self.inner.kind()
+    }
+
+    /// Returns the name of the operation that failed, such as `read_file`, if known.
+    pub fn operation(&self) -> Option<&'static str> {
+        self.operation
+    }
+
+    /// Returns the paths that the failed operation was given, in the order it took them.
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Returns whether the operation failed because a path has too many symlinks, such as because
+    /// they form a loop.
+    /// See [`is_filesystem_loop`](fn.is_filesystem_loop.html).
+    pub fn is_filesystem_loop(&self) -> bool {
+        is_filesystem_loop(&self.inner)
+    }
+
+    /// Returns the underlying error.
+    pub fn get_ref(&self) -> &io::Error {
+        &self.inner
+    }
+
+    /// Returns the underlying error, without the operation and paths.
+    pub fn into_inner(self) -> io::Error {
+        self.inner
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(operation) = self.operation {
+            write!(f, "{}", operation)?;
+
+            for (i, path) in self.paths.iter().enumerate() {
+                write!(f, "{}{:?}", if i == 0 { " " } else { ", " }, path)?;
+            }
+
+            write!(f, ": ")?;
+        }
+
+        write!(f, "{}", self.inner)
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.inner)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error {
+            operation: None,
+            paths: Vec::new(),
+            inner: unwrap(error),
+        }
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        io::Error::from(kind).into()
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> Self {
+        if error.operation.is_none() {
+            return error.inner;
+        }
+
+        io::Error::new(error.kind(), error)
+    }
+}
+
+/// Removes the operation and paths from an `io::Error` that was converted from an `Error`, so
+/// that they aren't repeated when it is given new ones.
+fn unwrap(error: io::Error) -> io::Error {
+    let wrapped = error
+        .get_ref()
+        .is_some_and(|inner| inner.downcast_ref::<Error>().is_some());
+
+    if !wrapped {
+        return error;
+    }
+
+    let kind = error.kind();
+
+    error
+        .into_inner()
+        .and_then(|inner| inner.downcast::<Error>().ok())
+        .map_or_else(|| io::Error::from(kind), |inner| inner.inner)
+}
+
+/// Returns whether `error` means that a path could not be resolved because it has too many
+/// symlinks, such as because they form a loop, like `ELOOP` on Unix.
+///
+/// Every file system fails with the same kind of error as the OS for this, which is
+/// `ErrorKind::FilesystemLoop` where the standard library supports it, but that kind can't be
+/// named on stable Rust.
+pub fn is_filesystem_loop(error: &io::Error) -> bool {
+    let kind = filesystem_loop_kind();
+
+    kind != ErrorKind::Other && error.kind() == kind
+}
+
+/// Creates the error for a path with too many symlinks, with `message` as its description.
+pub fn filesystem_loop<M: Into<String>>(message: M) -> io::Error {
+    io::Error::new(filesystem_loop_kind(), message.into())
+}
+
+/// Returns the kind of error that the OS uses for a path with too many symlinks.
+fn filesystem_loop_kind() -> ErrorKind {
+    #[cfg(unix)]
+    let code = Some(libc::ELOOP);
+    #[cfg(windows)]
+    let code = Some(winapi::shared::winerror::ERROR_CANT_RESOLVE_FILENAME as i32);
+    #[cfg(not(any(unix, windows)))]
+    let code = None;
+
+    code.map_or(ErrorKind::Other, |code| {
+        io::Error::from_raw_os_error(code).kind()
+    })
+}
+
+/// Attaches an operation and its paths to the error of a result.
+pub trait Context<T> {
+    /// Returns the result with `operation` and `paths` attached to its error, replacing any that
+    /// it already had, such as those of a file system that this one wraps.
+    fn context(self, operation: &'static str, paths: &[&Path]) -> Result<T>;
+}
+
+impl<T> Context<T> for io::Result<T> {
+    fn context(self, operation: &'static str, paths: &[&Path]) -> Result<T> {
+        self.map_err(|error| Error::new(operation, paths, error))
+    }
+}
+
+impl<T> Context<T> for Result<T> {
+    fn context(self, operation: &'static str, paths: &[&Path]) -> Result<T> {
+        self.map_err(|error| Error::new(operation, paths, error.inner))
+    }
+}
@@ -0,0 +1,335 @@
+use std::cmp;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use proptest::arbitrary::Arbitrary;
+use proptest::collection;
+use proptest::prelude::*;
+use proptest::sample;
+use proptest::strategy::Union;
+
+use super::journal::{Operation, OperationKind};
+use super::FakeFileSystem;
+
+const DIR_MODES: [u32; 4] = [0o755, 0o750, 0o700, 0o555];
+const FILE_MODES: [u32; 4] = [0o644, 0o600, 0o444, 0o755];
+
+/// Options for generating random file systems and operations with [`proptest`], which are
+/// available with the `proptest` feature.
+///
+/// Use them with `any_with::<FakeFileSystem>(options)` for file systems, or
+/// `any_with::<OperationKind>(options)` for operations, such as in a `vec` of operations to
+/// make one at a time with [`FakeFileSystem::apply_operation`].
+/// Operations use the same names as file systems do, so that they often refer to nodes that
+/// exist, but they can still fail, such as by removing a directory that does not exist.
+///
+/// [`proptest`]: https://docs.rs/proptest/1
+/// [`FakeFileSystem::apply_operation`]: struct.FakeFileSystem.html#method.apply_operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArbitraryOptions {
+    names: Vec<String>,
+    max_depth: usize,
+    max_entries: usize,
+    max_file_len: usize,
+    symlinks: bool,
+    modes: bool,
+}
+
+impl ArbitraryOptions {
+    /// Creates the default options: names from `a` to `e`, directories nested up to 3 deep with
+    /// up to 4 entries each, files of up to 64 bytes, symlinks, and random modes.
+    pub fn new() -> Self {
+        ArbitraryOptions {
+            names: ["a", "b", "c", "d", "e"]
+                .iter()
+                .map(|name| String::from(*name))
+                .collect(),
+            max_depth: 3,
+            max_entries: 4,
+            max_file_len: 64,
+            symlinks: true,
+            modes: true,
+        }
+    }
+
+    /// Sets the names that nodes can have.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `names` is empty.
+    pub fn names<I, S>(&mut self, names: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.names = names.into_iter().map(Into::into).collect();
+
+        assert!(!self.names.is_empty(), "names must not be empty");
+
+        self
+    }
+
+    /// Sets how deep directories can be nested, with `1` allowing only entries of the root.
+    pub fn max_depth(&mut self, depth: usize) -> &mut Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Sets how many entries a directory can have, which is also limited by the number of names.
+    pub fn max_entries(&mut self, entries: usize) -> &mut Self {
+        self.max_entries = entries;
+        self
+    }
+
+    /// Sets the maximum length of the contents of a file, in bytes.
+    pub fn max_file_len(&mut self, len: usize) -> &mut Self {
+        self.max_file_len = len;
+        self
+    }
+
+    /// Sets whether to generate symlinks, whose targets are absolute paths that might not exist
+    /// or might lead back to the symlink.
+    pub fn symlinks(&mut self, symlinks: bool) -> &mut Self {
+        self.symlinks = symlinks;
+        self
+    }
+
+    /// Sets whether to give nodes random modes, some of which are read-only, rather than the
+    /// default ones.
+    pub fn modes(&mut self, modes: bool) -> &mut Self {
+        self.modes = modes;
+        self
+    }
+
+    fn path(&self) -> BoxedStrategy<PathBuf> {
+        collection::vec(
+            sample::select(self.names.clone()),
+            1..=cmp::max(self.max_depth, 1),
+        )
+        .prop_map(|names| {
+            names
+                .iter()
+                .fold(PathBuf::from("/"), |path, name| path.join(name))
+        })
+        .boxed()
+    }
+
+    fn contents(&self) -> BoxedStrategy<Vec<u8>> {
+        collection::vec(any::<u8>(), 0..=self.max_file_len).boxed()
+    }
+
+    fn mode(&self, modes: &'static [u32]) -> BoxedStrategy<u32> {
+        if self.modes {
+            sample::select(modes).boxed()
+        } else {
+            Just(modes[0]).boxed()
+        }
+    }
+}
+
+impl Default for ArbitraryOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A node of a generated tree, which keeps its shape so that it shrinks well.
+#[derive(Debug, Clone)]
+enum Entry {
+    File {
+        mode: u32,
+        contents: Vec<u8>,
+    },
+    Dir {
+        mode: u32,
+        entries: Vec<(String, Entry)>,
+    },
+    Symlink {
+        target: PathBuf,
+    },
+}
+
+/// Returns a strategy for the entries of a directory `depth` levels below the root.
+fn entries(options: Arc<ArbitraryOptions>, depth: usize) -> BoxedStrategy<Vec<(String, Entry)>> {
+    let max = cmp::min(options.max_entries, options.names.len());
+
+    sample::subsequence(options.names.clone(), 0..=max)
+        .prop_flat_map(move |names| {
+            names
+                .into_iter()
+                .map(|name| (Just(name), entry(options.clone(), depth + 1)))
+                .collect::<Vec<_>>()
+        })
+        .boxed()
+}
+
+/// Returns a strategy for a node `depth` levels below the root.
+fn entry(options: Arc<ArbitraryOptions>, depth: usize) -> BoxedStrategy<Entry> {
+    let mut kinds = vec![(options.mode(&FILE_MODES), options.contents())
+        .prop_map(|(mode, contents)| Entry::File { mode, contents })
+        .boxed()];
+
+    if options.symlinks {
+        kinds.push(
+            options
+                .path()
+                .prop_map(|target| Entry::Symlink { target })
+                .boxed(),
+        );
+    }
+
+    let dir_entries = if depth < options.max_depth {
+        entries(options.clone(), depth)
+    } else {
+        Just(vec![]).boxed()
+    };
+
+    kinds.push(
+        (options.mode(&DIR_MODES), dir_entries)
+            .prop_map(|(mode, entries)| Entry::Dir { mode, entries })
+            .boxed(),
+    );
+
+    Union::new(kinds).boxed()
+}
+
+/// Adds the operations that create `entries` in the directory at `dir` to `operations`, parents
+/// first.
+fn create(dir: &Path, entries: &[(String, Entry)], operations: &mut Vec<Operation>) {
+    for (name, entry) in entries {
+        let path = dir.join(name);
+
+        match *entry {
+            Entry::File { mode, ref contents } => push(
+                operations,
+                OperationKind::CreateFile {
+                    path,
+                    mode,
+                    contents: contents.clone(),
+                },
+            ),
+            // Directories are only made read-only once their entries are in them
+            Entry::Dir { mode, ref entries } => {
+                push(
+                    operations,
+                    OperationKind::CreateDir {
+                        path: path.clone(),
+                        mode: DIR_MODES[0],
+                    },
+                );
+                create(&path, entries, operations);
+
+                if mode != DIR_MODES[0] {
+                    push(operations, OperationKind::SetMode { path, mode });
+                }
+            }
+            Entry::Symlink { ref target } => push(
+                operations,
+                OperationKind::CreateSymlink {
+                    path,
+                    target: target.clone(),
+                },
+            ),
+        }
+    }
+}
+
+fn push(operations: &mut Vec<Operation>, kind: OperationKind) {
+    let seq = operations.len() as u64;
+
+    operations.push(Operation { seq, kind });
+}
+
+impl Arbitrary for FakeFileSystem {
+    type Parameters = ArbitraryOptions;
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(options: Self::Parameters) -> Self::Strategy {
+        entries(Arc::new(options), 0)
+            .prop_map(|entries| {
+                let fs = FakeFileSystem::new();
+                let mut operations = vec![];
+
+                create(Path::new("/"), &entries, &mut operations);
+                fs.replay(&operations)
+                    .expect("generated nodes are created in writable directories");
+                fs
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for OperationKind {
+    type Parameters = ArbitraryOptions;
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(options: Self::Parameters) -> Self::Strategy {
+        let path = options.path();
+        let mut kinds = vec![
+            (path.clone(), options.mode(&DIR_MODES))
+                .prop_map(|(path, mode)| OperationKind::CreateDir { path, mode })
+                .boxed(),
+            (path.clone(), options.mode(&FILE_MODES), options.contents())
+                .prop_map(|(path, mode, contents)| OperationKind::CreateFile {
+                    path,
+                    mode,
+                    contents,
+                })
+                .boxed(),
+            path.clone()
+                .prop_map(|path| OperationKind::Remove { path })
+                .boxed(),
+            (path.clone(), path.clone())
+                .prop_map(|(from, to)| OperationKind::Rename { from, to })
+                .boxed(),
+            (path.clone(), options.contents())
+                .prop_map(|(path, contents)| OperationKind::Write { path, contents })
+                .boxed(),
+            (
+                path.clone(),
+                0..=options.max_file_len as u64,
+                options.contents(),
+            )
+                .prop_map(|(path, position, buf)| OperationKind::WriteAt {
+                    path,
+                    position,
+                    buf,
+                })
+                .boxed(),
+            (path.clone(), 0..=options.max_file_len as u64)
+                .prop_map(|(path, len)| OperationKind::SetLen { path, len })
+                .boxed(),
+            (path.clone(), 0..=u32::MAX as u64)
+                .prop_map(|(path, secs)| OperationKind::SetModified {
+                    path,
+                    time: UNIX_EPOCH + Duration::from_secs(secs),
+                })
+                .boxed(),
+            path.clone()
+                .prop_map(|path| OperationKind::SyncFile { path })
+                .boxed(),
+            path.clone()
+                .prop_map(|path| OperationKind::SyncDir { path })
+                .boxed(),
+        ];
+
+        if options.symlinks {
+            kinds.push(
+                (path.clone(), path.clone())
+                    .prop_map(|(path, target)| OperationKind::CreateSymlink { path, target })
+                    .boxed(),
+            );
+        }
+
+        if options.modes {
+            kinds.push(
+                (path, sample::select(&DIR_MODES[..]))
+                    .prop_map(|(path, mode)| OperationKind::SetMode { path, mode })
+                    .boxed(),
+            );
+        }
+
+        Union::new(kinds).boxed()
+    }
+}
@@ -0,0 +1,56 @@
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+
+use super::node::Dir;
+use super::normalization::{self, UnicodeNormalization};
+
+/// Whether a [`FakeFileSystem`] distinguishes names that differ only in case, as set by
+/// [`FakeFileSystem::with_case_insensitivity`].
+///
+/// [`FakeFileSystem`]: struct.FakeFileSystem.html
+/// [`FakeFileSystem::with_case_insensitivity`]: struct.FakeFileSystem.html#method.with_case_insensitivity
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CaseSensitivity {
+    /// Names that differ only in case refer to different nodes, as on Linux.
+    #[default]
+    Sensitive,
+    /// Names that differ only in case refer to the same node, which keeps the name it was
+    /// created with, as on macOS and Windows by default.
+    InsensitivePreserving,
+}
+
+/// Returns the name that `dir` stores the child referred to by `name` under, if it has one.
+pub fn child_key<'a>(
+    dir: &Dir,
+    name: &'a OsStr,
+    case: CaseSensitivity,
+    normalization: UnicodeNormalization,
+) -> Option<Cow<'a, OsStr>> {
+    if dir.children.contains_key(name) {
+        return Some(Cow::Borrowed(name));
+    }
+
+    if case == CaseSensitivity::Sensitive && normalization == UnicodeNormalization::Exact {
+        return None;
+    }
+
+    let folded = fold(name, case, normalization);
+
+    dir.children
+        .keys()
+        .find(|key| fold(key, case, normalization) == folded)
+        .map(|key| Cow::Owned(key.to_os_string()))
+}
+
+/// Returns `name` with its case and normalization folded as set, so that names referring to the
+/// same node are equal.
+/// Names that are not valid Unicode are only folded within ASCII.
+fn fold(name: &OsStr, case: CaseSensitivity, normalization: UnicodeNormalization) -> OsString {
+    let name = normalization::fold(name, normalization);
+
+    match (case, name.to_str()) {
+        (CaseSensitivity::Sensitive, _) => name.into_owned(),
+        (CaseSensitivity::InsensitivePreserving, Some(name)) => OsString::from(name.to_lowercase()),
+        (CaseSensitivity::InsensitivePreserving, None) => name.to_ascii_lowercase(),
+    }
+}
@@ -0,0 +1,19 @@
+use std::time::SystemTime;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+use std::time::{Duration, UNIX_EPOCH};
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+use js_sys::Date;
+
+/// Returns the current time, for the timestamps of nodes.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub fn now() -> SystemTime {
+    SystemTime::now()
+}
+
+/// Returns the current time, for the timestamps of nodes.
+/// `SystemTime::now` panics in the browser, so the time is read from JavaScript instead.
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub fn now() -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(Date::now() as u64)
+}
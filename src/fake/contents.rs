@@ -0,0 +1,320 @@
+use std::io::{self, Read};
+use std::mem;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Contents are copied into a single buffer once their pieces average fewer bytes than this, so
+/// that many small writes cannot fragment a file without bound.
+const MIN_AVERAGE_PIECE_LEN: usize = 4096;
+
+/// Contents with at most this many pieces are never copied into a single buffer.
+const MAX_UNCOMPACTED_PIECES: usize = 64;
+
+/// The contents of a file, as a sequence of slices of buffers that may be shared with other
+/// files, such as copies and the synced contents of the same file.
+///
+/// Writing to part of the contents only copies the bytes written, and leaves the rest shared, so
+/// that appending to or patching a large file takes time proportional to the change.
+#[derive(Clone, Debug, Default)]
+pub struct Contents {
+    /// Non-empty pieces in order, which cover the contents without gaps.
+    pieces: Vec<Piece>,
+    len: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Piece {
+    /// The position of the piece in the contents.
+    offset: usize,
+    buf: Arc<Vec<u8>>,
+    /// The part of `buf` that belongs to the contents.
+    range: Range<usize>,
+}
+
+impl Piece {
+    fn new(offset: usize, buf: Arc<Vec<u8>>) -> Self {
+        Piece {
+            offset,
+            range: 0..buf.len(),
+            buf,
+        }
+    }
+
+    fn end(&self) -> usize {
+        self.offset + self.range.len()
+    }
+
+    fn bytes(&self) -> &[u8] {
+        &self.buf[self.range.clone()]
+    }
+
+    /// Shortens the piece to end at the position `at` in the contents, returning the rest.
+    fn split_off(&mut self, at: usize) -> Piece {
+        let mid = self.range.start + (at - self.offset);
+        let rest = Piece {
+            offset: at,
+            buf: self.buf.clone(),
+            range: mid..self.range.end,
+        };
+
+        self.range.end = mid;
+
+        rest
+    }
+}
+
+impl Contents {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Copies the bytes from `position` onwards into `buf`, returning how many there were room
+    /// for.
+    pub fn read_at(&self, position: usize, buf: &mut [u8]) -> usize {
+        let n = buf.len().min(self.len.saturating_sub(position));
+        let mut read = 0;
+
+        for piece in &self.pieces[self.find(position)..] {
+            if read == n {
+                break;
+            }
+
+            let bytes = &piece.bytes()[position + read - piece.offset..];
+            let m = bytes.len().min(n - read);
+
+            buf[read..read + m].copy_from_slice(&bytes[..m]);
+            read += m;
+        }
+
+        n
+    }
+
+    /// Replaces the bytes from `position` onwards with `buf`, extending the contents if needed,
+    /// with zeros up to `position` if it is past the end.
+    pub fn write_at(&mut self, position: usize, buf: &[u8]) {
+        if self.len < position {
+            self.set_len(position);
+        }
+
+        if buf.is_empty() {
+            return;
+        }
+
+        if position == self.len {
+            return self.append_with(|contents| contents.extend_from_slice(buf));
+        }
+
+        if self.overwrite_in_place(position, buf) {
+            return;
+        }
+
+        let end = position + buf.len();
+        let first = self.split_at(position);
+        let last = self.split_at(end.min(self.len));
+
+        self.pieces.splice(
+            first..last,
+            Some(Piece::new(position, Arc::new(buf.to_vec()))),
+        );
+        self.len = self.len.max(end);
+
+        if self.pieces.len() > MAX_UNCOMPACTED_PIECES
+            && self.pieces.len() > self.len / MIN_AVERAGE_PIECE_LEN
+        {
+            *self = Contents::from(self.to_vec());
+        }
+    }
+
+    /// Truncates the contents to `len` bytes, or extends them with zeros.
+    pub fn set_len(&mut self, len: usize) {
+        if len < self.len {
+            let end = self.split_at(len);
+
+            self.pieces.truncate(end);
+            self.len = len;
+        } else if len > self.len {
+            let zeros = len - self.len;
+
+            self.append_with(|contents| contents.resize(contents.len() + zeros, 0));
+        }
+    }
+
+    /// Returns the buffers that the pieces are slices of, which may repeat.
+    pub fn buffers(&self) -> impl Iterator<Item = &Arc<Vec<u8>>> {
+        self.pieces.iter().map(|piece| &piece.buf)
+    }
+
+    /// Returns the bytes of memory used to keep track of the pieces, apart from their buffers.
+    pub fn overhead(&self) -> usize {
+        self.pieces.capacity() * mem::size_of::<Piece>()
+    }
+
+    /// Returns the pieces of the contents in order.
+    pub fn slices(&self) -> impl Iterator<Item = &[u8]> {
+        self.pieces.iter().map(Piece::bytes)
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut vec = Vec::with_capacity(self.len);
+
+        for slice in self.slices() {
+            vec.extend_from_slice(slice);
+        }
+
+        vec
+    }
+
+    /// Returns the contents in a single buffer, which is shared rather than copied if they
+    /// already are one, such as after the whole file was written at once.
+    pub fn to_arc(&self) -> Arc<Vec<u8>> {
+        match self.pieces[..] {
+            [ref piece] if piece.range == (0..piece.buf.len()) => piece.buf.clone(),
+            _ => Arc::new(self.to_vec()),
+        }
+    }
+
+    /// Returns a reader over the contents as they are now, without copying them.
+    pub fn into_reader(self) -> Reader {
+        Reader {
+            contents: self,
+            position: 0,
+        }
+    }
+
+    /// Returns the index of the piece containing `position`, or the number of pieces if it is
+    /// past the end.
+    fn find(&self, position: usize) -> usize {
+        self.pieces.partition_point(|piece| piece.end() <= position)
+    }
+
+    /// Splits the piece containing `position` so that a piece starts there, returning its index.
+    fn split_at(&mut self, position: usize) -> usize {
+        let i = self.find(position);
+
+        if i == self.pieces.len() || self.pieces[i].offset == position {
+            return i;
+        }
+
+        let rest = self.pieces[i].split_off(position);
+
+        self.pieces.insert(i + 1, rest);
+
+        i + 1
+    }
+
+    /// Writes `buf` over the bytes at `position` in the buffer of a single piece, if nothing else
+    /// shares it, returning whether it could.
+    fn overwrite_in_place(&mut self, position: usize, buf: &[u8]) -> bool {
+        let i = self.find(position);
+        let piece = &mut self.pieces[i];
+
+        if position + buf.len() > piece.end() {
+            return false;
+        }
+
+        let start = piece.range.start + (position - piece.offset);
+
+        match Arc::get_mut(&mut piece.buf) {
+            Some(contents) => {
+                contents[start..start + buf.len()].copy_from_slice(buf);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Adds bytes to the end with `extend`, in the buffer of the last piece if nothing else shares
+    /// it, or otherwise in a new one.
+    fn append_with<F: FnOnce(&mut Vec<u8>)>(&mut self, extend: F) {
+        if let Some(piece) = self.pieces.last_mut() {
+            if piece.range.end == piece.buf.len() {
+                if let Some(buf) = Arc::get_mut(&mut piece.buf) {
+                    extend(buf);
+                    self.len += buf.len() - piece.range.end;
+                    piece.range.end = buf.len();
+
+                    return;
+                }
+            }
+        }
+
+        let mut buf = Vec::new();
+
+        extend(&mut buf);
+
+        if !buf.is_empty() {
+            let piece = Piece::new(self.len, Arc::new(buf));
+
+            self.len = piece.end();
+            self.pieces.push(piece);
+        }
+    }
+}
+
+impl From<Vec<u8>> for Contents {
+    fn from(buf: Vec<u8>) -> Self {
+        Contents::from(Arc::new(buf))
+    }
+}
+
+impl From<Arc<Vec<u8>>> for Contents {
+    fn from(buf: Arc<Vec<u8>>) -> Self {
+        let len = buf.len();
+        let pieces = if len == 0 {
+            Vec::new()
+        } else {
+            vec![Piece::new(0, buf)]
+        };
+
+        Contents { pieces, len }
+    }
+}
+
+impl PartialEq for Contents {
+    fn eq(&self, other: &Self) -> bool {
+        if self.len != other.len {
+            return false;
+        }
+
+        let (mut ours, mut theirs) = (self.slices(), other.slices());
+        let (mut a, mut b): (&[u8], &[u8]) = (&[], &[]);
+
+        loop {
+            if a.is_empty() {
+                match ours.next() {
+                    Some(slice) => a = slice,
+                    None => return true,
+                }
+            }
+
+            if b.is_empty() {
+                b = theirs.next().unwrap_or_default();
+            }
+
+            let n = a.len().min(b.len());
+
+            if a[..n] != b[..n] {
+                return false;
+            }
+
+            a = &a[n..];
+            b = &b[n..];
+        }
+    }
+}
+
+/// Reads contents from the start, unaffected by any later changes to the file they came from.
+pub struct Reader {
+    contents: Contents,
+    position: usize,
+}
+
+impl Read for Reader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.contents.read_at(self.position, buf);
+
+        self.position += n;
+
+        Ok(n)
+    }
+}
@@ -0,0 +1,63 @@
+use std::mem;
+use std::sync::Arc;
+
+use super::node::{Dir, Node};
+
+/// Marks `node` and its descendants as synced, so that a simulated crash keeps them as they are.
+pub fn mark_synced(node: &mut Node) {
+    match *node {
+        Node::File(ref mut file) => file.synced = file.contents.clone(),
+        Node::Dir(ref mut dir) => {
+            dir.synced = None;
+
+            for child in dir.children_mut().values_mut() {
+                mark_synced(child);
+            }
+        }
+        Node::Symlink(_) | Node::Special(_) => {}
+    }
+}
+
+/// Remembers the children of `dir` as of its last sync, if they are about to change for the
+/// first time since.
+pub fn preserve(dir: &mut Dir) {
+    if dir.synced.is_none() {
+        dir.synced = Some(dir.children.clone());
+    }
+}
+
+/// Returns `node` as it is found after a crash, with the contents that files had and the children
+/// that directories had when they were last synced.
+/// A child that still has the name it had then is taken as it is now, so that it keeps anything
+/// synced since.
+pub fn crashed(node: Node) -> Node {
+    match node {
+        Node::File(mut file) => {
+            file.contents = file.synced.clone();
+            Node::File(file)
+        }
+        Node::Dir(mut dir) => {
+            let mut children = mem::take(dir.children_mut());
+
+            if let Some(synced) = dir.synced.take() {
+                children = synced
+                    .iter()
+                    .map(|(name, old)| {
+                        let child = children.remove(name).unwrap_or_else(|| old.clone());
+
+                        (name.clone(), child)
+                    })
+                    .collect();
+            }
+
+            dir.children = Arc::new(
+                children
+                    .into_iter()
+                    .map(|(name, child)| (name, crashed(child)))
+                    .collect(),
+            );
+            Node::Dir(dir)
+        }
+        other => other,
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use recover::Recover;
+use trace::CallLog;
+
+use super::process::Process;
+use super::{FakeFileSystem, Metadata};
+
+/// A difference between two [`FakeFileSystem`]s, as returned by [`FakeFileSystem::diff`].
+///
+/// A node is modified if its type, contents, symlink target, mode, or owner changed.
+/// Changes to timestamps alone are ignored.
+///
+/// [`FakeFileSystem`]: struct.FakeFileSystem.html
+/// [`FakeFileSystem::diff`]: struct.FakeFileSystem.html#method.diff
+#[derive(Clone, Debug)]
+pub enum FsChange {
+    /// A node exists only in the later file system.
+    Added { path: PathBuf, after: Metadata },
+    /// A node exists only in the earlier file system.
+    Removed { path: PathBuf, before: Metadata },
+    /// A node exists in both file systems, but differs between them.
+    Modified {
+        path: PathBuf,
+        before: Metadata,
+        after: Metadata,
+    },
+}
+
+impl FsChange {
+    /// Returns the path of the node that changed.
+    pub fn path(&self) -> &Path {
+        match *self {
+            FsChange::Added { ref path, .. }
+            | FsChange::Removed { ref path, .. }
+            | FsChange::Modified { ref path, .. } => path,
+        }
+    }
+
+    /// Returns `true` if the node exists only in the later file system.
+    pub fn is_added(&self) -> bool {
+        matches!(*self, FsChange::Added { .. })
+    }
+
+    /// Returns `true` if the node exists only in the earlier file system.
+    pub fn is_removed(&self) -> bool {
+        matches!(*self, FsChange::Removed { .. })
+    }
+
+    /// Returns `true` if the node exists in both file systems, but differs between them.
+    pub fn is_modified(&self) -> bool {
+        matches!(*self, FsChange::Modified { .. })
+    }
+}
+
+/// Formats the change as the kind of change followed by the path, such as `added /app/log`.
+impl fmt::Display for FsChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match *self {
+            FsChange::Added { .. } => "added",
+            FsChange::Removed { .. } => "removed",
+            FsChange::Modified { .. } => "modified",
+        };
+
+        write!(f, "{} {}", kind, self.path().display())
+    }
+}
+
+impl FakeFileSystem {
+    /// Returns an independent copy of the file system as it is now.
+    ///
+    /// Unlike clones, which share their contents, changes made to the copy are not visible
+    /// through this file system, and vice versa.
+    /// The copy does not record calls, run hooks, add latency, or inject failures.
+    pub fn snapshot(&self) -> Self {
+        let registry = self.registry.read().recover().snapshot();
+        let mut fs = self.clone();
+
+        fs.registry = Arc::new(RwLock::new(registry));
+        fs.process = self.process.as_ref().map(Process::snapshot);
+        fs.calls = None;
+        fs.hooks = Default::default();
+        fs.latency = Default::default();
+        fs.faults = Default::default();
+        fs
+    }
+
+    /// Returns an isolated copy of the file system, for a test case to change without affecting
+    /// this file system or other copies, such as when several tests start from the same fixture.
+    ///
+    /// Unlike a [`snapshot`], the copy also has its own environment variables, and continues
+    /// generating the same temporary names as this file system would.
+    /// If calls are being recorded, it records its own, starting with none.
+    /// Like a snapshot, it does not run hooks, add latency, or inject failures, since those keep
+    /// state that would otherwise be shared.
+    ///
+    /// The copy shares the contents of files and directories with this file system until either
+    /// changes them, so creating it takes constant time, and changes only copy the parts of the
+    /// tree they touch.
+    ///
+    /// [`snapshot`]: #method.snapshot
+    pub fn fork(&self) -> Self {
+        let mut fs = self.snapshot();
+
+        fs.vars = self.vars.snapshot();
+        fs.calls = self.calls.as_ref().map(|_| CallLog::default());
+        #[cfg(feature = "temp")]
+        {
+            fs.temp_names = self.temp_names.snapshot();
+        }
+        fs
+    }
+
+    /// Returns the changes that turn this file system into `other`, sorted by path.
+    ///
+    /// This is typically used with a [`snapshot`] taken before the code under test runs.
+    ///
+    /// [`snapshot`]: #method.snapshot
+    pub fn diff(&self, other: &FakeFileSystem) -> Vec<FsChange> {
+        if Arc::ptr_eq(&self.registry, &other.registry) {
+            return vec![];
+        }
+
+        let registry = self.registry.read().recover();
+        let other_registry = other.registry.read().recover();
+        let (before, after) = (registry.nodes(), other_registry.nodes());
+        let paths: BTreeSet<&PathBuf> = before.keys().chain(after.keys()).collect();
+
+        paths
+            .into_iter()
+            .filter_map(
+                |path| match (before.get(path).cloned(), after.get(path).cloned()) {
+                    (Some(a), Some(b)) if a.same_as(b) => None,
+                    (Some(a), Some(b)) => Some(FsChange::Modified {
+                        path: registry.user_path(path),
+                        before: Metadata::from(a),
+                        after: Metadata::from(b),
+                    }),
+                    (Some(a), None) => Some(FsChange::Removed {
+                        path: registry.user_path(path),
+                        before: Metadata::from(a),
+                    }),
+                    (None, Some(b)) => Some(FsChange::Added {
+                        path: registry.user_path(path),
+                        after: Metadata::from(b),
+                    }),
+                    (None, None) => None,
+                },
+            )
+            .collect()
+    }
+}
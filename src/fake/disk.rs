@@ -0,0 +1,124 @@
+use std::env;
+use std::path::Path;
+
+#[cfg(not(unix))]
+use FileSystem;
+#[cfg(unix)]
+use UnixFileSystem;
+use {DirEntry, FileType, Metadata, OsFileSystem, Result, SymlinkFileSystem};
+
+use super::FakeFileSystem;
+
+impl FakeFileSystem {
+    /// Creates a file system containing a copy of the directory tree at `path` on disk,
+    /// at the same absolute path.
+    ///
+    /// Contents, symlinks, and modes are preserved, or readonly flags on platforms without
+    /// modes, but owners and timestamps are not.
+    /// FIFOs, sockets, and devices are skipped.
+    /// Missing parent directories of `path` are created with the default mode.
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * Current user has insufficient permissions to read a node under `path`.
+    pub fn load_from_disk<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = env::current_dir()?.join(path);
+        let fs = FakeFileSystem::new();
+
+        copy_tree(&OsFileSystem::new(), &fs, &path)?;
+
+        Ok(fs)
+    }
+
+    /// Writes a copy of the directory tree at `path` to the same path on disk, skipping FIFOs,
+    /// sockets, and devices.
+    /// This is the inverse of [`load_from_disk`].
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * A file or directory already exists at `path` on disk.
+    /// * Current user has insufficient permissions.
+    ///
+    /// [`load_from_disk`]: #method.load_from_disk
+    pub fn dump_to_disk<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        copy_tree(self, &OsFileSystem::new(), path.as_ref())
+    }
+}
+
+/// Copies the node at `path` and all of its descendants from `source` to `dest`,
+/// creating any missing parent directories in `dest`.
+fn copy_tree<S, D>(source: &S, dest: &D, path: &Path) -> Result<()>
+where
+    S: SymlinkFileSystem + Permissions,
+    D: SymlinkFileSystem + Permissions,
+{
+    if let Some(parent) = path.parent() {
+        dest.create_dir_all(parent)?;
+    }
+
+    copy_node(source, dest, path)
+}
+
+fn copy_node<S, D>(source: &S, dest: &D, path: &Path) -> Result<()>
+where
+    S: SymlinkFileSystem + Permissions,
+    D: SymlinkFileSystem + Permissions,
+{
+    match source.symlink_metadata(path)?.file_type() {
+        FileType::Symlink => {
+            let target = source.read_link(path)?;
+
+            if source.is_dir(path) {
+                dest.symlink_dir(target, path)
+            } else {
+                dest.symlink_file(target, path)
+            }
+        }
+        FileType::File => {
+            dest.create_file(path, source.read_file(path)?)?;
+            dest.set_permissions(path, source.permissions(path)?)
+        }
+        FileType::Dir => {
+            dest.create_dir(path)?;
+
+            for entry in source.read_dir(path)? {
+                copy_node(source, dest, &entry?.path())?;
+            }
+
+            // Directories are restricted last so that their contents can still be created
+            dest.set_permissions(path, source.permissions(path)?)
+        }
+        // Special files can't be copied by reading them, and only root can create devices
+        FileType::Fifo | FileType::Socket | FileType::BlockDevice | FileType::CharDevice => Ok(()),
+    }
+}
+
+/// The permissions that are preserved when copying trees between file systems.
+trait Permissions {
+    fn permissions(&self, path: &Path) -> Result<u32>;
+    fn set_permissions(&self, path: &Path, permissions: u32) -> Result<()>;
+}
+
+#[cfg(unix)]
+impl<T: UnixFileSystem> Permissions for T {
+    fn permissions(&self, path: &Path) -> Result<u32> {
+        self.mode(path)
+    }
+
+    fn set_permissions(&self, path: &Path, permissions: u32) -> Result<()> {
+        self.set_mode(path, permissions & 0o7777)
+    }
+}
+
+#[cfg(not(unix))]
+impl<T: FileSystem> Permissions for T {
+    fn permissions(&self, path: &Path) -> Result<u32> {
+        self.readonly(path).map(u32::from)
+    }
+
+    fn set_permissions(&self, path: &Path, permissions: u32) -> Result<()> {
+        self.set_readonly(path, permissions != 0)
+    }
+}
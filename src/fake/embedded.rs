@@ -0,0 +1,63 @@
+use std::io::Result;
+use std::path::Path;
+
+use include_dir::Dir;
+
+use super::FakeFileSystem;
+use FileSystem;
+
+impl FakeFileSystem {
+    /// Creates a file system containing a copy of a directory that was embedded in the binary at
+    /// compile time with [`include_dir!`], at `/`, so that tests do not need fixture files next to
+    /// them when they run.
+    ///
+    /// ```
+    /// static FIXTURES: Dir = include_dir!("$CARGO_MANIFEST_DIR/tests/fixtures");
+    ///
+    /// let fs = FakeFileSystem::from_include_dir(&FIXTURES)?;
+    /// ```
+    ///
+    /// Files that are embedded some other way, such as with `rust-embed`, can be given to
+    /// [`from_tree`] as paths and contents instead.
+    ///
+    /// [`include_dir!`]: https://docs.rs/include_dir/0.7/include_dir/macro.include_dir.html
+    /// [`from_tree`]: #method.from_tree
+    pub fn from_include_dir(dir: &Dir) -> Result<Self> {
+        let fs = Self::new();
+
+        fs.add_include_dir("/", dir)?;
+
+        Ok(fs)
+    }
+
+    /// Copies a directory that was embedded with [`include_dir!`] to `path`, creating `path` and
+    /// any missing parent directories first.
+    /// Files that already exist are overwritten.
+    ///
+    /// # Errors
+    ///
+    /// * A node on the way to a copied file is not a directory.
+    /// * Current user has insufficient permissions.
+    ///
+    /// [`include_dir!`]: https://docs.rs/include_dir/0.7/include_dir/macro.include_dir.html
+    pub fn add_include_dir<P: AsRef<Path>>(&self, path: P, dir: &Dir) -> Result<()> {
+        let path = path.as_ref();
+
+        FileSystem::create_dir_all(self, path)?;
+        add_entries(self, path, dir)
+    }
+}
+
+/// Copies the entries of `dir`, whose paths are relative to the embedded root, to below `base`.
+fn add_entries(fs: &FakeFileSystem, base: &Path, dir: &Dir) -> Result<()> {
+    for file in dir.files() {
+        fs.write_file(base.join(file.path()), file.contents())?;
+    }
+
+    for child in dir.dirs() {
+        fs.create_dir_all(base.join(child.path()))?;
+        add_entries(fs, base, child)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::env;
+use std::env::VarError;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::result;
+use std::sync::{Arc, Mutex};
+
+use recover::Recover;
+use Environment;
+
+use super::FakeFileSystem;
+
+/// The environment variable that sets the home directory.
+#[cfg(not(windows))]
+const HOME_VAR: &str = "HOME";
+#[cfg(windows)]
+const HOME_VAR: &str = "USERPROFILE";
+
+/// The environment variable that sets the directory for temporary files.
+#[cfg(not(windows))]
+const TEMP_VAR: &str = "TMPDIR";
+#[cfg(windows)]
+const TEMP_VAR: &str = "TMP";
+
+/// Environment variables of a `FakeFileSystem`, shared between clones.
+#[derive(Clone, Debug, Default)]
+pub struct Vars(Arc<Mutex<BTreeMap<OsString, OsString>>>);
+
+impl Vars {
+    /// Returns an independent copy of the variables as they are now.
+    pub fn snapshot(&self) -> Self {
+        Vars(Arc::new(Mutex::new(self.0.lock().recover().clone())))
+    }
+}
+
+impl FakeFileSystem {
+    /// Returns the value of `key` as an absolute path, if it is set and not empty.
+    fn var_path(&self, key: &str) -> Option<PathBuf> {
+        let vars = self.vars.0.lock().recover();
+
+        vars.get(OsStr::new(key))
+            .filter(|value| !value.is_empty())
+            .map(|value| self.absolute(Path::new(value)))
+    }
+}
+
+/// The environment of a `FakeFileSystem` starts out empty, rather than copying the real one, so
+/// that tests do not depend on the machine they run on.
+///
+/// The home directory and the directory for temporary files are read from the usual variables,
+/// such as `HOME` and `TMPDIR`, with relative paths resolved against the fake current directory.
+/// The directory for temporary files falls back to the real one, and is also where
+/// `TempFileSystem` creates temporary directories and files.
+impl Environment for FakeFileSystem {
+    fn var<K: AsRef<OsStr>>(&self, key: K) -> result::Result<String, VarError> {
+        match self.vars.0.lock().recover().get(key.as_ref()) {
+            Some(value) => value.clone().into_string().map_err(VarError::NotUnicode),
+            None => Err(VarError::NotPresent),
+        }
+    }
+
+    fn set_var<K: AsRef<OsStr>, V: AsRef<OsStr>>(&self, key: K, value: V) {
+        self.vars
+            .0
+            .lock()
+            .recover()
+            .insert(key.as_ref().to_os_string(), value.as_ref().to_os_string());
+    }
+
+    fn remove_var<K: AsRef<OsStr>>(&self, key: K) {
+        self.vars.0.lock().recover().remove(key.as_ref());
+    }
+
+    fn vars(&self) -> Vec<(OsString, OsString)> {
+        let vars = self.vars.0.lock().recover();
+
+        vars.iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+
+    fn home_dir(&self) -> Option<PathBuf> {
+        self.var_path(HOME_VAR)
+    }
+
+    fn temp_dir(&self) -> PathBuf {
+        self.var_path(TEMP_VAR).unwrap_or_else(default_temp_dir)
+    }
+}
+
+/// Returns the directory for temporary files when the environment does not set one.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn default_temp_dir() -> PathBuf {
+    env::temp_dir()
+}
+
+/// Returns the directory for temporary files when the environment does not set one.
+/// The browser has no such directory, and `env::temp_dir` panics there.
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+fn default_temp_dir() -> PathBuf {
+    PathBuf::from("/tmp")
+}
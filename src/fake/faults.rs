@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::io::{ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use recover::Recover;
+
+use super::registry::create_error;
+use super::FakeFileSystem;
+
+/// Failures injected into a `FakeFileSystem`'s operations, shared between clones.
+#[derive(Clone, Debug, Default)]
+pub struct Faults(Arc<Mutex<FaultList>>);
+
+#[derive(Debug, Default)]
+struct FaultList {
+    read_limit: Option<usize>,
+    write_limit: Option<usize>,
+    torn_writes: HashMap<PathBuf, (usize, ErrorKind)>,
+    flakes: Vec<Flake>,
+    interruptions: HashMap<(&'static str, PathBuf), ErrorKind>,
+}
+
+/// A number of calls to a method on a path that should fail.
+#[derive(Debug)]
+struct Flake {
+    method: &'static str,
+    path: PathBuf,
+    remaining: usize,
+    kind: ErrorKind,
+}
+
+impl Faults {
+    /// Returns how many of `len` requested bytes a read through an open file should transfer.
+    pub fn read_len(&self, len: usize) -> usize {
+        limit(self.0.lock().recover().read_limit, len)
+    }
+
+    /// Returns how many of `len` requested bytes a write through an open file should transfer.
+    pub fn write_len(&self, len: usize) -> usize {
+        limit(self.0.lock().recover().write_limit, len)
+    }
+
+    /// Returns the kind of error a call to `method` on `paths` should fail with, if any, using
+    /// `absolute` to resolve relative paths only when there are flakes to match.
+    pub fn take_flake<F>(&self, method: &str, paths: &[&Path], absolute: F) -> Option<ErrorKind>
+    where
+        F: Fn(&Path) -> PathBuf,
+    {
+        let mut faults = self.0.lock().recover();
+
+        if faults.flakes.is_empty() {
+            return None;
+        }
+
+        let paths: Vec<PathBuf> = paths.iter().map(|p| absolute(p)).collect();
+        let index = faults
+            .flakes
+            .iter()
+            .position(|flake| flake.method == method && paths.contains(&flake.path))?;
+        let kind = faults.flakes[index].kind;
+
+        faults.flakes[index].remaining -= 1;
+
+        if faults.flakes[index].remaining == 0 {
+            faults.flakes.remove(index);
+        }
+
+        Some(kind)
+    }
+
+    /// Takes the torn write set for the absolute `path`, if any.
+    pub fn take_torn_write(&self, path: &Path) -> Option<(usize, ErrorKind)> {
+        self.0.lock().recover().torn_writes.remove(path)
+    }
+
+    /// Fails with the error set to interrupt a call to `method` when it reaches the absolute
+    /// `path`, if any.
+    pub fn check_interruption(&self, method: &'static str, path: &Path) -> Result<()> {
+        let mut faults = self.0.lock().recover();
+
+        if faults.interruptions.is_empty() {
+            return Ok(());
+        }
+
+        match faults.interruptions.remove(&(method, path.to_path_buf())) {
+            Some(kind) => Err(create_error(kind)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Limits `len` to at most `limit` bytes, but always transfers at least one byte so that a short
+/// read isn't mistaken for the end of the file.
+fn limit(limit: Option<usize>, len: usize) -> usize {
+    match limit {
+        Some(limit) => len.min(limit.max(1)),
+        None => len,
+    }
+}
+
+impl FakeFileSystem {
+    /// Limits every subsequent read through an open file to at most `limit` bytes, so that
+    /// `Read::read` returns fewer bytes than requested, or removes the limit if `None`.
+    /// Clones share the same limits.
+    pub fn set_read_limit(&self, limit: Option<usize>) {
+        self.faults.0.lock().recover().read_limit = limit;
+    }
+
+    /// Limits every subsequent write through an open file to at most `limit` bytes, so that
+    /// `Write::write` accepts fewer bytes than given, or removes the limit if `None`.
+    pub fn set_write_limit(&self, limit: Option<usize>) {
+        self.faults.0.lock().recover().write_limit = limit;
+    }
+
+    /// Makes the next call to `create_file`, `write_file`, or `overwrite_file` for `path` write
+    /// only the first `len` bytes of its contents, and then fail with `kind`, as though it was
+    /// interrupted midway.
+    /// A call to `write_file_atomic` fails without changing `path`, since only its temporary file
+    /// would have been written.
+    /// Later calls write normally.
+    pub fn fail_write_after<P: AsRef<Path>>(&self, path: P, len: usize, kind: ErrorKind) {
+        let path = self.absolute(path.as_ref());
+
+        self.faults
+            .0
+            .lock()
+            .recover()
+            .torn_writes
+            .insert(path, (len, kind));
+    }
+
+    /// Makes the next `times` calls to `method` on `path` fail with `kind`, leaving the file
+    /// system unchanged, after which they succeed again.
+    /// This can exercise retry loops, such as with `ErrorKind::Interrupted`.
+    ///
+    /// Methods are named as in recorded calls, such as `"read_file"`, and only those that return
+    /// a `Result` can fail.
+    /// Calls on several paths, such as `rename`, fail if any of their paths is `path`.
+    pub fn flake<P: AsRef<Path>>(
+        &self,
+        method: &'static str,
+        path: P,
+        times: usize,
+        kind: ErrorKind,
+    ) {
+        let path = self.absolute(path.as_ref());
+
+        if times > 0 {
+            self.faults.0.lock().recover().flakes.push(Flake {
+                method,
+                path,
+                remaining: times,
+                kind,
+            });
+        }
+    }
+
+    /// Makes the next call to `method` fail with `kind` when it reaches `path` partway through,
+    /// as though it was interrupted there.
+    /// `remove_dir_all` reaches each node it removes, and `copy_dir` each copy it creates.
+    ///
+    /// Both put back whatever they changed before failing, so this shows that a failed call
+    /// leaves the file system unchanged rather than half removed or half copied.
+    pub fn interrupt<P: AsRef<Path>>(&self, method: &'static str, path: P, kind: ErrorKind) {
+        let path = self.absolute(path.as_ref());
+
+        self.faults
+            .0
+            .lock()
+            .recover()
+            .interruptions
+            .insert((method, path), kind);
+    }
+}
@@ -0,0 +1,104 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::io::Result;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use recover::Recover;
+
+use super::FakeFileSystem;
+
+type WriteHook = dyn Fn(&Path, &mut Vec<u8>) -> Result<()> + Send + Sync;
+type RemoveHook = dyn Fn(&Path) -> Result<()> + Send + Sync;
+
+/// Callbacks that run before a `FakeFileSystem` writes or removes a node, shared between clones.
+#[derive(Clone, Default)]
+pub struct Hooks(Arc<Mutex<HookList>>);
+
+#[derive(Default)]
+struct HookList {
+    write: Vec<Arc<WriteHook>>,
+    remove: Vec<Arc<RemoveHook>>,
+}
+
+impl Hooks {
+    pub fn has_write(&self) -> bool {
+        !self.0.lock().recover().write.is_empty()
+    }
+
+    /// Runs every write hook in the order they were added, returning the contents they leave.
+    /// The first hook to fail vetoes the write.
+    pub fn write<'a>(&self, path: &Path, buf: &'a [u8]) -> Result<Cow<'a, [u8]>> {
+        // Hooks run without the lock held, so that they can use the file system themselves
+        let hooks = self.0.lock().recover().write.clone();
+
+        if hooks.is_empty() {
+            return Ok(Cow::Borrowed(buf));
+        }
+
+        let mut buf = buf.to_vec();
+
+        for hook in hooks {
+            hook(path, &mut buf)?;
+        }
+
+        Ok(Cow::Owned(buf))
+    }
+
+    /// Runs every remove hook in the order they were added.
+    /// The first hook to fail vetoes the removal.
+    pub fn remove(&self, path: &Path) -> Result<()> {
+        let hooks = self.0.lock().recover().remove.clone();
+
+        hooks.iter().try_for_each(|hook| hook(path))
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hooks = self.0.lock().recover();
+
+        f.debug_struct("Hooks")
+            .field("write", &hooks.write.len())
+            .field("remove", &hooks.remove.len())
+            .finish()
+    }
+}
+
+impl FakeFileSystem {
+    /// Calls `hook` with the absolute path and new contents before every write to a file,
+    /// including writes through open files and the destination of `copy_file`.
+    /// Clones share the same hooks.
+    ///
+    /// The hook may change the contents that are written, or return an error to fail the write
+    /// with it, leaving the file system unchanged.
+    /// Hooks run in the order they were added, and may use the file system themselves, such as
+    /// to simulate a concurrent change.
+    pub fn on_write<F>(&self, hook: F)
+    where
+        F: Fn(&Path, &mut Vec<u8>) -> Result<()> + Send + Sync + 'static,
+    {
+        self.hooks.0.lock().recover().write.push(Arc::new(hook));
+    }
+
+    /// Calls `hook` with the absolute path before every call to `remove_file`, `remove_dir`, or
+    /// `remove_dir_all`.
+    /// Clones share the same hooks.
+    ///
+    /// The hook may return an error to fail the removal with it, leaving the file system
+    /// unchanged.
+    pub fn on_remove<F>(&self, hook: F)
+    where
+        F: Fn(&Path) -> Result<()> + Send + Sync + 'static,
+    {
+        self.hooks.0.lock().recover().remove.push(Arc::new(hook));
+    }
+
+    /// Removes every hook added with [`on_write`] or [`on_remove`].
+    ///
+    /// [`on_write`]: #method.on_write
+    /// [`on_remove`]: #method.on_remove
+    pub fn clear_hooks(&self) {
+        *self.hooks.0.lock().recover() = HookList::default();
+    }
+}
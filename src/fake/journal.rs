@@ -0,0 +1,157 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::node::Node;
+use FileType;
+
+/// A change made to a [`FakeFileSystem`], as returned by [`FakeFileSystem::operation_log`].
+///
+/// [`FakeFileSystem`]: struct.FakeFileSystem.html
+/// [`FakeFileSystem::operation_log`]: struct.FakeFileSystem.html#method.operation_log
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Operation {
+    /// The sequence number of the operation, which is one more than that of the operation before
+    /// it, even if the log has been cleared in between.
+    pub seq: u64,
+    /// What the operation changed.
+    pub kind: OperationKind,
+}
+
+/// What an [`Operation`] changed.
+/// Paths are absolute, with any symlinks in them resolved.
+///
+/// [`Operation`]: struct.Operation.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OperationKind {
+    /// A directory was created with `mode`.
+    CreateDir { path: PathBuf, mode: u32 },
+    /// A file was created with `mode` and `contents`.
+    CreateFile {
+        path: PathBuf,
+        mode: u32,
+        contents: Vec<u8>,
+    },
+    /// A symlink to `target` was created.
+    CreateSymlink { path: PathBuf, target: PathBuf },
+    /// A FIFO, socket, or device of type `file_type` was created with `mode` and the device
+    /// number `device`.
+    CreateSpecial {
+        path: PathBuf,
+        file_type: FileType,
+        mode: u32,
+        device: u64,
+    },
+    /// A node was removed, along with its descendants.
+    Remove { path: PathBuf },
+    /// A node was moved from one path to another, along with its descendants, replacing any node
+    /// that was already at `to`.
+    Rename { from: PathBuf, to: PathBuf },
+    /// The contents of a file were replaced.
+    Write { path: PathBuf, contents: Vec<u8> },
+    /// `buf` was written to an open file at `position`, extending it if needed.
+    WriteAt {
+        path: PathBuf,
+        position: u64,
+        buf: Vec<u8>,
+    },
+    /// A file was truncated or extended with zeros to `len` bytes.
+    SetLen { path: PathBuf, len: u64 },
+    /// The mode of a node was changed.
+    SetMode { path: PathBuf, mode: u32 },
+    /// The owner of a node was changed.
+    SetOwner { path: PathBuf, uid: u32, gid: u32 },
+    /// The modification time of a node was changed.
+    SetModified { path: PathBuf, time: SystemTime },
+    /// The access time of a node was changed.
+    SetAccessed { path: PathBuf, time: SystemTime },
+    /// The stored Windows attributes of a node were changed.
+    SetAttributes { path: PathBuf, attributes: u32 },
+    /// The contents of a file were synced.
+    SyncFile { path: PathBuf },
+    /// The entries of a directory were synced.
+    SyncDir { path: PathBuf },
+}
+
+/// The operations logged so far, along with the sequence number of the next one.
+#[derive(Clone, Debug, Default)]
+pub struct Journal {
+    next: u64,
+    operations: Vec<Operation>,
+}
+
+impl Journal {
+    pub fn push(&mut self, kind: OperationKind) {
+        self.operations.push(Operation {
+            seq: self.next,
+            kind,
+        });
+        self.next += 1;
+    }
+
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    /// Forgets every operation logged so far, without restarting the sequence numbers.
+    pub fn clear(&mut self) {
+        self.operations.clear();
+    }
+}
+
+/// Returns the operation that creates `node` at `path`.
+pub fn created(path: PathBuf, node: &Node) -> OperationKind {
+    match *node {
+        Node::File(ref file) => OperationKind::CreateFile {
+            path,
+            mode: file.mode,
+            contents: file.contents.to_vec(),
+        },
+        Node::Dir(ref dir) => OperationKind::CreateDir {
+            path,
+            mode: dir.mode,
+        },
+        Node::Symlink(ref symlink) => OperationKind::CreateSymlink {
+            path,
+            target: symlink.target.clone(),
+        },
+        Node::Special(ref special) => OperationKind::CreateSpecial {
+            path,
+            file_type: special.file_type,
+            mode: special.mode,
+            device: special.device,
+        },
+    }
+}
+
+/// Returns `kind` with each of its paths replaced by the result of `f`.
+/// Symlink targets are left as they are.
+pub fn map_paths<F: FnMut(&Path) -> PathBuf>(kind: &OperationKind, mut f: F) -> OperationKind {
+    let mut kind = kind.clone();
+
+    match kind {
+        OperationKind::Rename {
+            ref mut from,
+            ref mut to,
+        } => {
+            *from = f(from);
+            *to = f(to);
+        }
+        OperationKind::CreateDir { ref mut path, .. }
+        | OperationKind::CreateFile { ref mut path, .. }
+        | OperationKind::CreateSymlink { ref mut path, .. }
+        | OperationKind::CreateSpecial { ref mut path, .. }
+        | OperationKind::Remove { ref mut path }
+        | OperationKind::Write { ref mut path, .. }
+        | OperationKind::WriteAt { ref mut path, .. }
+        | OperationKind::SetLen { ref mut path, .. }
+        | OperationKind::SetMode { ref mut path, .. }
+        | OperationKind::SetOwner { ref mut path, .. }
+        | OperationKind::SetModified { ref mut path, .. }
+        | OperationKind::SetAccessed { ref mut path, .. }
+        | OperationKind::SyncFile { ref mut path }
+        | OperationKind::SyncDir { ref mut path } => *path = f(path),
+        OperationKind::SetAttributes { ref mut path, .. } => *path = f(path),
+    }
+
+    kind
+}
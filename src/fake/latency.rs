@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use recover::Recover;
+
+use super::FakeFileSystem;
+
+/// Delays added to a `FakeFileSystem`'s operations, shared between clones.
+#[derive(Clone, Debug, Default)]
+pub struct Latency(Arc<Mutex<Delays>>);
+
+#[derive(Debug, Default)]
+struct Delays {
+    default: Duration,
+    operations: HashMap<&'static str, Duration>,
+    per_byte: Duration,
+}
+
+impl Latency {
+    /// Sleeps for the latency of `method`.
+    pub fn wait(&self, method: &str) {
+        let delay = {
+            let delays = self.0.lock().recover();
+
+            delays
+                .operations
+                .get(method)
+                .cloned()
+                .unwrap_or(delays.default)
+        };
+
+        sleep(delay);
+    }
+
+    /// Sleeps for the latency of reading or writing `len` bytes.
+    pub fn transfer(&self, len: usize) {
+        let per_byte = self.0.lock().recover().per_byte;
+
+        sleep(per_byte.saturating_mul(len.min(u32::MAX as usize) as u32));
+    }
+}
+
+fn sleep(delay: Duration) {
+    if delay > Duration::from_secs(0) {
+        thread::sleep(delay);
+    }
+}
+
+impl FakeFileSystem {
+    /// Delays every subsequent operation by `latency`, including each read or write through an
+    /// open file, unless the operation has its own latency.
+    /// Clones share the same latencies.
+    ///
+    /// Operations block the calling thread for the delay, but not other threads using the file
+    /// system.
+    pub fn set_latency(&self, latency: Duration) {
+        self.latency.0.lock().recover().default = latency;
+    }
+
+    /// Delays every subsequent call to `method` by `latency` instead of the latency set with
+    /// [`set_latency`].
+    /// Methods are named as in recorded calls, such as `"read_file"`, with reads and writes
+    /// through open files named `"read"` and `"write"`.
+    ///
+    /// [`set_latency`]: #method.set_latency
+    pub fn set_operation_latency(&self, method: &'static str, latency: Duration) {
+        self.latency
+            .0
+            .lock()
+            .recover()
+            .operations
+            .insert(method, latency);
+    }
+
+    /// Further delays every subsequent read or write of file contents by `latency` for each
+    /// byte transferred, such as to exercise progress reporting.
+    pub fn set_byte_latency(&self, latency: Duration) {
+        self.latency.0.lock().recover().per_byte = latency;
+    }
+}
@@ -0,0 +1,120 @@
+use std::ffi::OsStr;
+use std::io::{ErrorKind, Result};
+use std::path::Path;
+
+use super::registry::create_error;
+
+/// Limits on the names and paths that a [`FakeFileSystem`] accepts, as set by
+/// [`FakeFileSystem::set_path_limits`].
+///
+/// Paths that break a limit are rejected by every operation, with `ErrorKind::InvalidFilename`
+/// like on the OS, or `ErrorKind::InvalidInput` for a NUL character, like the standard library.
+///
+/// [`FakeFileSystem`]: struct.FakeFileSystem.html
+/// [`FakeFileSystem::set_path_limits`]: struct.FakeFileSystem.html#method.set_path_limits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathLimits {
+    max_name_len: Option<usize>,
+    max_path_len: Option<usize>,
+    invalid_chars: Vec<char>,
+}
+
+impl PathLimits {
+    /// Creates limits that accept every name and path.
+    pub fn new() -> Self {
+        PathLimits {
+            max_name_len: None,
+            max_path_len: None,
+            invalid_chars: vec![],
+        }
+    }
+
+    /// Creates the limits of Linux: names of up to 255 bytes, absolute paths of up to 4095 bytes,
+    /// and no NUL characters.
+    pub fn linux() -> Self {
+        let mut limits = Self::new();
+
+        limits
+            .max_name_len(255)
+            .max_path_len(4095)
+            .invalid_chars("\0");
+        limits
+    }
+
+    /// Creates the limits of Windows without long path support: names of up to 255 bytes,
+    /// absolute paths of up to 259 bytes, which is `MAX_PATH` less its terminating NUL, and none
+    /// of `<>:"/\|?*` or control characters.
+    pub fn windows() -> Self {
+        let control: String = (0..0x20u8).map(char::from).collect();
+        let mut limits = Self::new();
+
+        limits
+            .max_name_len(255)
+            .max_path_len(259)
+            .invalid_chars("<>:\"/\\|?*")
+            .invalid_chars(control);
+        limits
+    }
+
+    /// Sets the maximum length of a name, in bytes.
+    pub fn max_name_len(&mut self, len: usize) -> &mut Self {
+        self.max_name_len = Some(len);
+        self
+    }
+
+    /// Sets the maximum length of an absolute path, in bytes.
+    pub fn max_path_len(&mut self, len: usize) -> &mut Self {
+        self.max_path_len = Some(len);
+        self
+    }
+
+    /// Adds the characters of `chars` to those that names cannot contain.
+    pub fn invalid_chars<S: AsRef<str>>(&mut self, chars: S) -> &mut Self {
+        self.invalid_chars.extend(chars.as_ref().chars());
+        self
+    }
+
+    /// Determines whether every name and path is accepted.
+    pub fn accept_all(&self) -> bool {
+        *self == Self::new()
+    }
+
+    /// Checks the absolute `path` and its `names` against the limits.
+    pub fn check<'a, I>(&self, path: &Path, names: I) -> Result<()>
+    where
+        I: IntoIterator<Item = &'a OsStr>,
+    {
+        if self
+            .max_path_len
+            .is_some_and(|max| path.as_os_str().len() > max)
+        {
+            return Err(create_error(ErrorKind::InvalidFilename));
+        }
+
+        for name in names {
+            if self.max_name_len.is_some_and(|max| name.len() > max) {
+                return Err(create_error(ErrorKind::InvalidFilename));
+            }
+
+            let name = name.to_string_lossy();
+
+            if let Some(c) = name.chars().find(|c| self.invalid_chars.contains(c)) {
+                let kind = if c == '\0' {
+                    ErrorKind::InvalidInput
+                } else {
+                    ErrorKind::InvalidFilename
+                };
+
+                return Err(create_error(kind));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for PathLimits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
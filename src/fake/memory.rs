@@ -0,0 +1,150 @@
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::mem;
+use std::sync::Arc;
+
+use super::contents::Contents;
+use super::names::Name;
+use super::node::{Children, Node};
+
+/// Returns an estimate of the bytes of memory used by `root` and its descendants, counting
+/// buffers, directories and names shared between nodes once.
+pub fn usage(root: &Node) -> usize {
+    let mut usage = Usage {
+        bytes: mem::size_of::<Node>(),
+        buffers: HashSet::new(),
+        dirs: HashSet::new(),
+        names: HashSet::new(),
+    };
+
+    usage.add_node(root);
+    usage.bytes
+}
+
+/// Returns the bytes of memory used by the entry for `node` named `name` in its parent directory,
+/// apart from its contents and children, if its name isn't shared with any other node.
+pub fn entry_size(name: &OsStr, node: &Node) -> usize {
+    node_size(node) + name.len()
+}
+
+/// The length of the contents of a node and its descendants, and an estimate of the memory they
+/// use, which can be added to and taken from a running total as nodes change.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Footprint {
+    pub len: u64,
+    pub memory: usize,
+}
+
+impl Footprint {
+    /// Returns the footprint of the contents of a file of `len` bytes.
+    pub fn of_contents(len: usize) -> Self {
+        Footprint {
+            len: len as u64,
+            memory: len,
+        }
+    }
+
+    pub fn add(&mut self, other: Footprint) {
+        self.len += other.len;
+        self.memory = self.memory.saturating_add(other.memory);
+    }
+
+    pub fn sub(&mut self, other: Footprint) {
+        self.len = self.len.saturating_sub(other.len);
+        self.memory = self.memory.saturating_sub(other.memory);
+    }
+}
+
+/// Returns the footprint of the entry for `node` named `name`, along with its descendants.
+///
+/// Unlike [`usage`], contents and names shared with other nodes are counted for each node, and
+/// contents by their length, so that adding a node and removing it again leaves a running total
+/// as it was.
+pub fn footprint(name: &OsStr, node: &Node) -> Footprint {
+    let mut footprint = Footprint {
+        len: 0,
+        memory: entry_size(name, node),
+    };
+
+    match *node {
+        Node::File(ref file) => footprint.add(Footprint::of_contents(file.contents.len())),
+        Node::Dir(ref dir) => footprint.add(children_footprint(&dir.children)),
+        Node::Symlink(_) | Node::Special(_) => {}
+    }
+
+    footprint
+}
+
+/// Returns the footprint of `children` and their descendants.
+pub fn children_footprint(children: &Children) -> Footprint {
+    let mut footprint = Footprint::default();
+
+    for (name, child) in children.iter() {
+        footprint.add(self::footprint(name, child));
+    }
+
+    footprint
+}
+
+/// Returns the bytes of memory used by an entry for `node`, apart from its name, contents and
+/// children.
+fn node_size(node: &Node) -> usize {
+    let target = match *node {
+        Node::Symlink(ref symlink) => symlink.target.as_os_str().len(),
+        _ => 0,
+    };
+
+    mem::size_of::<(Name, Node)>() + target
+}
+
+struct Usage {
+    bytes: usize,
+    buffers: HashSet<*const Vec<u8>>,
+    dirs: HashSet<*const Children>,
+    names: HashSet<*const OsStr>,
+}
+
+impl Usage {
+    fn add_node(&mut self, node: &Node) {
+        match *node {
+            Node::File(ref file) => {
+                self.add_contents(&file.contents);
+                self.add_contents(&file.synced);
+            }
+            Node::Dir(ref dir) => {
+                self.add_children(&dir.children);
+
+                if let Some(ref synced) = dir.synced {
+                    self.add_children(synced);
+                }
+            }
+            Node::Symlink(_) | Node::Special(_) => {}
+        }
+    }
+
+    fn add_children(&mut self, children: &Arc<Children>) {
+        if !self.dirs.insert(Arc::as_ptr(children)) {
+            return;
+        }
+
+        for (name, child) in children.iter() {
+            self.bytes += node_size(child);
+
+            if self.names.insert(Arc::as_ptr(name)) {
+                self.bytes += name.len();
+            }
+
+            self.add_node(child);
+        }
+    }
+
+    fn add_contents(&mut self, contents: &Contents) {
+        self.bytes += contents.overhead();
+
+        for buf in contents.buffers() {
+            if self.buffers.insert(Arc::as_ptr(buf)) {
+                self.bytes += buf.capacity();
+            }
+        }
+    }
+}
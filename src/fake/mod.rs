@@ -1,306 +1,2062 @@
-use std::env;
+use std::borrow::Cow;
 use std::ffi::{OsStr, OsString};
-use std::io::Result;
+use std::io::{self, Read};
 use std::iter::Iterator;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex, MutexGuard};
+#[cfg(feature = "watch")]
+use std::sync::mpsc;
+use std::sync::{Arc, RwLock};
+#[cfg(feature = "async")]
+use std::time::Duration;
+use std::time::SystemTime;
 use std::vec::IntoIter;
 
-use FileSystem;
+use self::node::Node;
+#[cfg(feature = "async")]
+use async_fs::{self, AsyncFileSystem, FsFuture};
+use error::Context;
+use pattern;
+use recover::Recover;
+#[cfg(feature = "temp")]
+use temp;
+use temp_sibling;
+use trace::{Call, CallLog};
+use walk::WalkSource;
 #[cfg(unix)]
 use UnixFileSystem;
+#[cfg(windows)]
+use WindowsFileSystem;
+#[cfg(feature = "hash")]
+use {hash, Algorithm, Digest};
 #[cfg(feature = "temp")]
-use {TempDir, TempFileSystem};
+use {Environment, TempDir, TempFile, TempFileSystem, TempOptions};
+use {
+    Error, FileSystem, FileType, GlobOptions, OpenOptions, ReadDirOptions, Result,
+    SymlinkFileSystem, WalkDir, WalkDirOptions,
+};
+#[cfg(feature = "watch")]
+use {FsWatcher, WatchFileSystem};
 
+#[cfg(feature = "proptest")]
+pub use self::arbitrary::ArbitraryOptions;
+pub use self::case::CaseSensitivity;
+pub use self::diff::FsChange;
+pub use self::journal::{Operation, OperationKind};
+pub use self::limits::PathLimits;
+pub use self::normalization::UnicodeNormalization;
+pub use self::open_file::FakeOpenFile;
+pub use self::paths::AllPaths;
 #[cfg(feature = "temp")]
 pub use self::tempdir::FakeTempDir;
+#[cfg(feature = "temp")]
+pub use self::tempfile::FakeTempFile;
+
+use self::environment::Vars;
+use self::faults::Faults;
+use self::hooks::Hooks;
+use self::latency::Latency;
+use self::process::{Locked, Process, ReadLocked};
+use self::registry::{create_error, Registry, READ, WRITE};
+#[cfg(feature = "temp")]
+use self::tempdir::TempNames;
+
+#[cfg(feature = "proptest")]
+mod arbitrary;
+mod case;
+mod clock;
+mod contents;
+mod crash;
+mod diff;
+#[cfg(feature = "std-os")]
+mod disk;
+#[cfg(feature = "include_dir")]
+mod embedded;
+mod environment;
+mod faults;
+mod hooks;
+mod journal;
+mod latency;
+mod limits;
+mod memory;
+mod names;
+mod node;
+mod normalization;
+mod open_file;
+mod paths;
+mod process;
+mod registry;
+#[cfg(feature = "serde")]
+mod snapshot;
+#[cfg(feature = "tar")]
+mod tarball;
+#[cfg(feature = "temp")]
+mod tempdir;
+#[cfg(feature = "temp")]
+mod tempfile;
+mod tree;
+mod windows;
+
+/// An in-memory file system.
+///
+/// Clones share the same contents and can be used from several threads at once.
+/// Operations that only read the contents, such as `read_file` and `metadata`, run concurrently
+/// with each other, while operations that change them run one at a time.
+/// Operations on whole trees, such as `rename`, `remove_dir_all`, and `copy_dir` of a directory,
+/// are atomic: other threads never see them partly done, and if they fail partway they undo
+/// their changes, so that watchers and the operation log only see them once they complete.
+/// No lock is held while hooks run, so hooks may use the file system themselves.
+/// A thread that panics during an operation does not stop other threads from using the file
+/// system afterwards.
+///
+/// Operations on the wrong type of node fail with the same `NotADirectory`, `IsADirectory`, and
+/// `DirectoryNotEmpty` error kinds as the OS.
+#[derive(Clone, Debug, Default)]
+pub struct FakeFileSystem {
+    registry: Arc<RwLock<Registry>>,
+    calls: Option<CallLog>,
+    hooks: Hooks,
+    latency: Latency,
+    faults: Faults,
+    vars: Vars,
+    process: Option<Process>,
+    #[cfg(feature = "async")]
+    async_latency: Duration,
+    #[cfg(feature = "temp")]
+    temp_names: TempNames,
+}
+
+impl FakeFileSystem {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+
+        FakeFileSystem {
+            registry: Arc::new(RwLock::new(registry)),
+            calls: None,
+            hooks: Hooks::default(),
+            latency: Latency::default(),
+            faults: Faults::default(),
+            vars: Vars::default(),
+            process: None,
+            #[cfg(feature = "async")]
+            async_latency: Duration::from_secs(0),
+            #[cfg(feature = "temp")]
+            temp_names: TempNames::default(),
+        }
+    }
+
+    /// Creates an empty file system that can hold at most `capacity` bytes of file contents.
+    ///
+    /// Writes that would exceed the capacity fail with `ErrorKind::StorageFull`,
+    /// leaving the file system unchanged.
+    /// [`total_space`] returns the capacity, and [`available_space`] the part of it that file
+    /// contents don't use, while file systems without a capacity report `u64::MAX` as their total.
+    ///
+    /// [`total_space`]: trait.FileSystem.html#tymethod.total_space
+    /// [`available_space`]: trait.FileSystem.html#tymethod.available_space
+    pub fn with_capacity(capacity: u64) -> Self {
+        let fs = Self::new();
+
+        fs.registry.write().recover().set_capacity(capacity);
+        fs
+    }
+
+    /// Creates an empty file system that treats names differing only in case as the same name or
+    /// not, as set by `sensitivity`.
+    ///
+    /// With [`CaseSensitivity::InsensitivePreserving`], as on macOS and Windows by default, a
+    /// path finds a node regardless of the case of its components, creating a node fails with
+    /// `ErrorKind::AlreadyExists` if another one's name differs only in case, and nodes keep the
+    /// names they were created with, so that [`read_dir`] lists them as such.
+    /// A node can be renamed to change only the case of its name.
+    ///
+    /// [`CaseSensitivity::InsensitivePreserving`]: enum.CaseSensitivity.html#variant.InsensitivePreserving
+    /// [`read_dir`]: trait.FileSystem.html#tymethod.read_dir
+    pub fn with_case_insensitivity(sensitivity: CaseSensitivity) -> Self {
+        let fs = Self::new();
+
+        fs.registry
+            .write()
+            .recover()
+            .set_case_sensitivity(sensitivity);
+        fs
+    }
+
+    /// Creates an empty file system that behaves like Windows, so that Windows path handling can
+    /// be tested on other platforms.
+    ///
+    /// Paths such as `C:\dir\file` start at the root of a drive, and both `\` and `/` separate
+    /// components.
+    /// Paths without a drive, such as `\dir`, start at the root of the current directory's
+    /// drive, and paths relative to a drive, such as `D:dir`, are resolved against the current
+    /// directory if it is on that drive, or the root of the drive otherwise.
+    /// Paths returned by the file system, such as by [`current_dir`] and [`canonicalize`], are
+    /// Windows paths.
+    ///
+    /// The file system starts with drive `C:`, which is also the current directory, and more
+    /// drives can be added with [`add_drive`].
+    /// Like on Windows, names ignore case, as with [`CaseSensitivity::InsensitivePreserving`], and
+    /// creating a node named after a device, such as `CON` or `nul.txt`, fails with
+    /// `ErrorKind::InvalidInput`.
+    ///
+    /// [`current_dir`]: trait.FileSystem.html#tymethod.current_dir
+    /// [`canonicalize`]: trait.FileSystem.html#tymethod.canonicalize
+    /// [`add_drive`]: #method.add_drive
+    /// [`CaseSensitivity::InsensitivePreserving`]: enum.CaseSensitivity.html#variant.InsensitivePreserving
+    pub fn with_windows_paths() -> Self {
+        let fs = Self::new();
+
+        fs.registry
+            .write()
+            .recover()
+            .set_windows_paths()
+            .expect("new file system already has drive C:");
+        fs
+    }
+
+    /// Adds an empty drive, such as `D:`, to a file system created with [`with_windows_paths`].
+    ///
+    /// # Errors
+    ///
+    /// * `drive` is not an ASCII letter.
+    /// * The drive already exists.
+    ///
+    /// [`with_windows_paths`]: #method.with_windows_paths
+    pub fn add_drive(&self, drive: char) -> Result<()> {
+        self.lock().add_drive(drive).context("add_drive", &[])
+    }
+
+    /// Sets or unsets the simulated `FILE_ATTRIBUTE_HIDDEN` attribute of `path`, without
+    /// following symlinks, so that the Windows behavior of [`is_hidden`] can be tested on any
+    /// platform.
+    /// The attribute is only checked by file systems created with [`with_windows_paths`], while
+    /// others go by names starting with `.`.
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    ///
+    /// [`is_hidden`]: trait.FileSystem.html#method.is_hidden
+    /// [`with_windows_paths`]: #method.with_windows_paths
+    pub fn set_hidden<P: AsRef<Path>>(&self, path: P, hidden: bool) -> Result<()> {
+        let path = path.as_ref();
+
+        self.apply_mut(path, |r, p| r.set_hidden(p, hidden))
+            .context("set_hidden", &[path])
+    }
+
+    /// Makes the directory at `path` the root of a separate device, as if another file system
+    /// were mounted there, so that code which falls back to copying when a rename fails with
+    /// `EXDEV` can be tested.
+    /// Clones share the same devices.
+    ///
+    /// Renaming a node from one device to another then fails with `ErrorKind::CrossesDevices`,
+    /// while renaming or removing the root of a device, or a directory that contains one, fails
+    /// with `ErrorKind::ResourceBusy`.
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * `path` is not a directory.
+    /// * Current user has insufficient permissions.
+    pub fn add_device<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.apply_mut(path, |r, p| r.add_device(p))
+            .context("add_device", &[path])
+    }
+
+    /// Denies the current and any later user permission to read the node at `path`, regardless of
+    /// its mode, as an ACL or SELinux policy might.
+    /// Clones share the same rules.
+    ///
+    /// Rules take precedence over mode bits, even for root, and the latest rule for a kind of
+    /// access wins.
+    /// They belong to the path rather than the node, so they apply to whatever is created there
+    /// later, but not to its descendants.
+    ///
+    /// # Errors
+    ///
+    /// * `path` can't be resolved, such as because of a symlink loop.
+    pub fn deny_read<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.apply_mut(path, |r, p| r.set_access_rule(p, READ, false))
+            .context("deny_read", &[path])
+    }
+
+    /// Denies permission to write to the node at `path`, regardless of its mode.
+    /// See [`deny_read`] for how rules apply.
+    ///
+    /// [`deny_read`]: #method.deny_read
+    pub fn deny_write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.apply_mut(path, |r, p| r.set_access_rule(p, WRITE, false))
+            .context("deny_write", &[path])
+    }
+
+    /// Grants permission to read the node at `path`, regardless of its mode.
+    /// See [`deny_read`] for how rules apply.
+    ///
+    /// [`deny_read`]: #method.deny_read
+    pub fn allow_read<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.apply_mut(path, |r, p| r.set_access_rule(p, READ, true))
+            .context("allow_read", &[path])
+    }
+
+    /// Grants permission to write to the node at `path`, regardless of its mode.
+    /// See [`deny_read`] for how rules apply.
+    ///
+    /// [`deny_read`]: #method.deny_read
+    pub fn allow_write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.apply_mut(path, |r, p| r.set_access_rule(p, WRITE, true))
+            .context("allow_write", &[path])
+    }
+
+    /// Removes every rule added for `path` by methods like [`deny_read`], so that its mode
+    /// applies again.
+    ///
+    /// [`deny_read`]: #method.deny_read
+    pub fn clear_access_rules<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.apply_mut(path, |r, p| r.clear_access_rules(p))
+            .context("clear_access_rules", &[path])
+    }
+
+    /// Creates a file system containing the given files, along with their parent directories.
+    /// Empty directories can be created with [`fake_fs!`] instead.
+    ///
+    /// [`fake_fs!`]: macro.fake_fs.html
+    pub fn from_tree<P, B>(files: &[(P, B)]) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let fs = Self::new();
+
+        for (path, buf) in files {
+            fs.write_file_all(path, buf)?;
+        }
+
+        Ok(fs)
+    }
+
+    /// Writes `buf` to a new or existing file at `path`, creating any missing parent directories
+    /// first.
+    pub fn write_file_all<P, B>(&self, path: P, buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent() {
+            FileSystem::create_dir_all(self, parent)?;
+        }
+
+        FileSystem::write_file(self, path, buf)
+    }
+
+    /// Returns the contents of the file at `path` without copying them, unlike [`read_file`],
+    /// unless the file has been written to in parts since it was last written as a whole.
+    /// The contents are shared with the file until it is next written to, after which the file
+    /// has its own copy of the part written, so later writes do not change the returned contents.
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * `path` is a directory.
+    /// * Current user has insufficient permissions.
+    ///
+    /// [`read_file`]: trait.FileSystem.html#method.read_file
+    pub fn read_file_arc<P: AsRef<Path>>(&self, path: P) -> Result<Arc<Vec<u8>>> {
+        let path = path.as_ref();
+
+        self.call("read_file_arc", &[path], || {
+            self.apply(path, |r, p| r.read_contents(p))
+                .inspect(|contents| self.latency.transfer(contents.len()))
+                .map(|contents| contents.to_arc())
+        })
+    }
+
+    /// Runs all subsequent operations as the user with the given user and group IDs.
+    ///
+    /// Reads and writes are checked against the owner, group, or other mode bits of each node,
+    /// depending on how the user relates to the node's owner, and new nodes are owned by the user.
+    /// As on the OS, root (user ID 0) may read and write anything.
+    /// By default, operations run as user 1000 in group 1000.
+    pub fn set_current_user(&self, uid: u32, gid: u32) {
+        let own_user = self
+            .process
+            .as_ref()
+            .is_some_and(|process| process.set_user(uid, gid));
+
+        if !own_user {
+            self.registry.write().recover().set_current_user(uid, gid);
+        }
+    }
+
+    /// Sets the mode bits that are cleared from every node created afterwards, as with the OS's
+    /// umask.
+    /// Clones share the same umask, which defaults to `0o022`, so that new files have mode
+    /// `0o644` and new directories have mode `0o755`.
+    /// Handles returned by [`process`] have their own umask instead.
+    ///
+    /// [`process`]: #method.process
+    pub fn set_umask(&self, umask: u32) {
+        let own_umask = self
+            .process
+            .as_ref()
+            .is_some_and(|process| process.set_umask(umask));
+
+        if !own_umask {
+            self.registry.write().recover().set_umask(umask);
+        }
+    }
+
+    /// Makes every subsequent operation require the execute bit, for the current user's class, on
+    /// each directory that it looks up a path through, as on Unix.
+    /// This includes the new current directory given to [`set_current_dir`].
+    /// Clones share the same setting.
+    ///
+    /// By default, directories can be traversed regardless of their execute bits, so that code
+    /// which sets modes like `0o644` on directories keeps working.
+    ///
+    /// [`set_current_dir`]: trait.FileSystem.html#tymethod.set_current_dir
+    pub fn set_strict_permissions(&self, strict: bool) {
+        self.registry
+            .write()
+            .recover()
+            .set_strict_permissions(strict);
+    }
+
+    /// Makes every subsequent operation reject paths that break `limits`, such as names that are
+    /// too long, as the OS would.
+    /// Clones share the same limits.
+    ///
+    /// By default, every name and path is accepted.
+    pub fn set_path_limits(&self, limits: &PathLimits) {
+        self.registry
+            .write()
+            .recover()
+            .set_path_limits(limits.clone());
+    }
+
+    /// Makes every subsequent operation fail if resolving a path would follow more than `depth`
+    /// symlinks, as the OS does to stop at symlink loops.
+    /// Clones share the same limit, which defaults to 40, like Linux's `SYMLOOP_MAX`.
+    ///
+    /// Such failures can be told apart with [`is_filesystem_loop`].
+    ///
+    /// [`is_filesystem_loop`]: fn.is_filesystem_loop.html
+    pub fn set_max_symlink_depth(&self, depth: usize) {
+        self.registry.write().recover().set_max_symlink_depth(depth);
+    }
+
+    /// Returns an estimate of the bytes of memory used by the nodes of the file system, including
+    /// their names, metadata, and contents, as well as what a simulated crash would restore.
+    /// Contents and directories that are shared with a [`snapshot`] or [`fork`] are counted in
+    /// full, but only once.
+    /// Each node stores only its own name, and nodes with the same name share it, so a name that
+    /// many directories contain, such as `mod.rs`, is only counted once, and the memory used per
+    /// node doesn't grow with its depth.
+    ///
+    /// [`snapshot`]: #method.snapshot
+    /// [`fork`]: #method.fork
+    pub fn memory_usage(&self) -> usize {
+        self.registry.read().recover().memory_usage()
+    }
+
+    /// Makes every subsequent write or creation of a node fail with `ErrorKind::OutOfMemory` if
+    /// it would take the [`memory_usage`] of the file system over `limit` bytes, leaving the file
+    /// system unchanged.
+    /// Clones share the same limit.
+    ///
+    /// This stops tests that generate pathological writes, such as to an offset far past the end
+    /// of a file, from exhausting the memory of the machine running them.
+    /// So that checking it takes constant time, the limit applies to the memory usage when it
+    /// was set plus a running total of the nodes and contents added since, less those removed,
+    /// which counts contents by their length even where they are shared.
+    ///
+    /// [`memory_usage`]: #method.memory_usage
+    pub fn set_memory_limit(&self, limit: usize) {
+        self.registry.write().recover().set_memory_limit(limit);
+    }
+
+    /// Makes every subsequent operation normalize the Unicode in names as set by `normalization`,
+    /// like macOS does, so that code which looks up a name in another normalization than it was
+    /// created with can be tested.
+    /// Clones share the same setting.
+    ///
+    /// With [`UnicodeNormalization::Nfc`] or [`UnicodeNormalization::Nfd`], names are converted
+    /// when nodes are created, so that [`read_dir`] lists them normalized.
+    /// With any setting but [`UnicodeNormalization::Exact`], which is the default, a path finds
+    /// a node regardless of the normalization of its components, and creating a node fails with
+    /// `ErrorKind::AlreadyExists` if another one's name differs only in normalization.
+    /// Existing names are left as they are.
+    ///
+    /// [`UnicodeNormalization::Nfc`]: enum.UnicodeNormalization.html#variant.Nfc
+    /// [`UnicodeNormalization::Nfd`]: enum.UnicodeNormalization.html#variant.Nfd
+    /// [`UnicodeNormalization::Exact`]: enum.UnicodeNormalization.html#variant.Exact
+    /// [`read_dir`]: trait.FileSystem.html#tymethod.read_dir
+    pub fn set_unicode_normalization(&self, normalization: UnicodeNormalization) {
+        self.registry
+            .write()
+            .recover()
+            .set_unicode_normalization(normalization);
+    }
+
+    /// Starts or stops keeping track of which changes have been synced, so that
+    /// [`simulate_crash`] can drop the rest.
+    /// Everything in the file system when this is enabled counts as synced.
+    /// Clones share the same setting.
+    ///
+    /// The contents of a file are synced by [`sync_file`] or [`OpenFile::sync_all`], while the
+    /// entries of a directory, such as files created, removed, or renamed in it, are synced by
+    /// [`sync_dir`], as on Linux.
+    ///
+    /// [`simulate_crash`]: #method.simulate_crash
+    /// [`sync_file`]: trait.FileSystem.html#method.sync_file
+    /// [`OpenFile::sync_all`]: trait.OpenFile.html#tymethod.sync_all
+    /// [`sync_dir`]: trait.FileSystem.html#tymethod.sync_dir
+    pub fn set_crash_simulation(&self, enabled: bool) {
+        self.registry
+            .write()
+            .recover()
+            .set_crash_simulation(enabled);
+    }
+
+    /// Drops every change that has not been synced since crash simulation was enabled with
+    /// [`set_crash_simulation`], as if the OS had crashed, so that crash-safe ways of writing
+    /// files can be tested.
+    ///
+    /// Files get back the contents they had when last synced, which are empty for files that
+    /// never were, and directories get back the entries they had when last synced.
+    /// Every lock is released, but open files should no longer be used, since the process that
+    /// opened them would not have survived the crash.
+    /// Without crash simulation, this does nothing.
+    ///
+    /// [`set_crash_simulation`]: #method.set_crash_simulation
+    pub fn simulate_crash(&self) {
+        self.lock().simulate_crash();
+    }
+
+    /// Starts or stops logging every change made to the file system, through any handle, along
+    /// with a sequence number, so that the changes can be inspected with [`operation_log`] and
+    /// made again with [`replay`].
+    /// Clones share the same log.
+    ///
+    /// Stopping forgets every operation logged so far.
+    ///
+    /// [`operation_log`]: #method.operation_log
+    /// [`replay`]: #method.replay
+    pub fn set_operation_logging(&self, enabled: bool) {
+        self.registry
+            .write()
+            .recover()
+            .set_operation_logging(enabled);
+    }
+
+    /// Returns the operations logged since logging was started with [`set_operation_logging`]
+    /// or the log was last cleared, oldest first.
+    ///
+    /// [`set_operation_logging`]: #method.set_operation_logging
+    pub fn operation_log(&self) -> Vec<Operation> {
+        self.registry.read().recover().operation_log()
+    }
+
+    /// Forgets the operations logged so far.
+    /// Operations logged afterwards keep counting up from the last sequence number.
+    pub fn clear_operation_log(&self) {
+        self.registry.write().recover().clear_operation_log();
+    }
+
+    /// Makes the changes in `operations` again, in order, such as to rebuild the state of a file
+    /// system after a prefix of its [`operation_log`].
+    /// Limits and faults are not checked, and neither are permissions, except that read-only
+    /// directories and files cannot be changed.
+    ///
+    /// # Errors
+    ///
+    /// Stops at the first operation that cannot be made, such as creating a file whose parent
+    /// directory does not exist, leaving the changes made before it in place.
+    ///
+    /// [`operation_log`]: #method.operation_log
+    pub fn replay(&self, operations: &[Operation]) -> Result<()> {
+        let mut registry = self.lock();
+
+        for operation in operations {
+            let kind = journal::map_paths(&operation.kind, |path| {
+                self.locate(&registry, path).into_owned()
+            });
+
+            registry.replay(&kind)?;
+        }
+
+        Ok(())
+    }
+
+    /// Makes the change `kind` like [`replay`] does for each operation, such as to make a change
+    /// generated with the `proptest` feature.
+    ///
+    /// [`replay`]: #method.replay
+    pub fn apply_operation(&self, kind: &OperationKind) -> Result<()> {
+        let mut registry = self.lock();
+        let kind = journal::map_paths(kind, |path| self.locate(&registry, path).into_owned());
+
+        registry.replay(&kind).map_err(Error::from)
+    }
+
+    /// Delays every [`AsyncFileSystem`] operation performed through this handle by `latency`.
+    /// Clones share the same contents but keep their own latency.
+    ///
+    /// A non-zero latency uses tokio's timer, so operations must then be awaited within a tokio
+    /// runtime that has time enabled.
+    ///
+    /// [`AsyncFileSystem`]: trait.AsyncFileSystem.html
+    #[cfg(feature = "async")]
+    pub fn with_async_latency(mut self, latency: Duration) -> Self {
+        self.async_latency = latency;
+        self
+    }
+
+    /// Names temporary directories and files created through [`TempFileSystem`] with sequential
+    /// numbers, such as `test_0`, rather than random characters, so that their paths are the same
+    /// on every run.
+    /// Clones made afterwards share the same sequence.
+    ///
+    /// [`TempFileSystem`]: trait.TempFileSystem.html
+    #[cfg(feature = "temp")]
+    pub fn with_deterministic_temp_names(mut self) -> Self {
+        self.temp_names = TempNames::sequential();
+        self
+    }
+
+    /// Records every subsequent operation performed through this handle, so that they can be
+    /// inspected with [`calls`] and [`calls_for`].
+    /// Clones made afterwards share the same call log.
+    ///
+    /// Calls are recorded in the same way as by [`TracingFileSystem`].
+    ///
+    /// [`calls`]: #method.calls
+    /// [`calls_for`]: #method.calls_for
+    /// [`TracingFileSystem`]: struct.TracingFileSystem.html
+    pub fn with_call_recording(mut self) -> Self {
+        self.calls = Some(CallLog::default());
+        self
+    }
+
+    /// Returns every call recorded so far, in the order they were made.
+    /// Nothing is recorded unless the file system was created [`with_call_recording`].
+    ///
+    /// [`with_call_recording`]: #method.with_call_recording
+    pub fn calls(&self) -> Vec<Call> {
+        self.calls.as_ref().map(CallLog::calls).unwrap_or_default()
+    }
+
+    /// Returns every call to `method` recorded so far, in the order they were made.
+    pub fn calls_for(&self, method: &str) -> Vec<Call> {
+        self.calls
+            .as_ref()
+            .map(|calls| calls.calls_for(method))
+            .unwrap_or_default()
+    }
+
+    /// Forgets every call recorded so far.
+    pub fn clear_calls(&self) {
+        if let Some(ref calls) = self.calls {
+            calls.clear();
+        }
+    }
+
+    /// Performs the operation `method` on `paths` with `f`, unless it is failed by a flake, and
+    /// records the call along with any error, which is given `method` and `paths` as its context.
+    fn call<T, F, R>(&self, method: &'static str, paths: &[&Path], f: F) -> Result<T>
+    where
+        F: FnOnce() -> R,
+        R: Context<T>,
+    {
+        let result = match self.faults.take_flake(method, paths, |p| self.absolute(p)) {
+            Some(kind) => Err(create_error(kind)).context(method, paths),
+            None => f().context(method, paths),
+        };
+
+        self.record(method, paths, result)
+    }
+
+    fn record<T>(&self, method: &'static str, paths: &[&Path], result: Result<T>) -> Result<T> {
+        self.latency.wait(method);
+
+        match self.calls {
+            Some(ref calls) => calls.record(method, paths, result),
+            None => result,
+        }
+    }
+
+    fn record_value<T>(&self, method: &'static str, paths: &[&Path], value: T) -> T {
+        self.latency.wait(method);
+
+        match self.calls {
+            Some(ref calls) => calls.record_value(method, paths, value),
+            None => value,
+        }
+    }
+
+    /// Returns a handle to the same file system that does not record calls or add latency, for
+    /// operations that are implemented in terms of other operations.
+    fn untraced(&self) -> Self {
+        FakeFileSystem {
+            calls: None,
+            latency: Latency::default(),
+            ..self.clone()
+        }
+    }
+
+    fn open_file(&self, path: &Path, options: &OpenOptions) -> io::Result<FakeOpenFile> {
+        self.apply_mut(path, |r, p| {
+            r.open(p, options).map(|p| {
+                FakeOpenFile::new(
+                    self.registry.clone(),
+                    self.hooks.clone(),
+                    self.latency.clone(),
+                    self.faults.clone(),
+                    &p,
+                    options,
+                )
+            })
+        })
+    }
+
+    /// Writes `buf` to `path` with `write`, after running the write hooks and injecting any
+    /// torn write set for `path`.
+    fn write_contents<F>(&self, path: &Path, buf: &[u8], write: F) -> io::Result<()>
+    where
+        F: Fn(&mut Registry, &Path, &[u8]) -> io::Result<()>,
+    {
+        let absolute = self.absolute(path);
+        let buf = self.hooks.write(&absolute, buf)?;
+        let (buf, error) = match self.faults.take_torn_write(&absolute) {
+            Some((len, kind)) => (&buf[..len.min(buf.len())], Some(create_error(kind))),
+            None => (&buf[..], None),
+        };
+
+        self.apply_mut(path, |r, p| write(r, p, buf))?;
+        self.latency.transfer(buf.len());
+
+        match error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Locks the registry exclusively to run an operation that changes it through this handle.
+    fn lock(&self) -> Locked<'_> {
+        Locked::new(&self.registry, self.process.as_ref())
+    }
+
+    /// Locks the registry to run an operation that only reads it through this handle.
+    fn read(&self) -> ReadLocked<'_> {
+        ReadLocked::new(&self.registry, self.process.as_ref())
+    }
+
+    /// Returns the current directory of this handle, which is the shared one unless the handle
+    /// has its own process.
+    fn cwd(&self, registry: &Registry) -> io::Result<PathBuf> {
+        match self.process {
+            Some(ref process) => process.cwd(registry),
+            None => registry.current_dir(),
+        }
+    }
+
+    /// Returns `path` joined onto the current directory if it is relative.
+    fn absolute(&self, path: &Path) -> PathBuf {
+        self.apply(path, |r, p| r.user_path(p))
+    }
+
+    /// Returns the absolute path in `registry` that `path` refers to through this handle.
+    fn locate<'a>(&self, registry: &Registry, path: &'a Path) -> Cow<'a, Path> {
+        if registry.windows_paths() || path.is_relative() {
+            let cwd = self.cwd(registry).unwrap_or_else(|_| PathBuf::from("/"));
+
+            if registry.windows_paths() {
+                return Cow::Owned(windows::to_registry(path, &cwd));
+            }
+
+            return Cow::Owned(cwd.join(path));
+        }
+
+        Cow::Borrowed(path)
+    }
+
+    fn apply<F, T>(&self, path: &Path, f: F) -> T
+    where
+        F: FnOnce(&Registry, &Path) -> T,
+    {
+        let registry = self.read();
+        let path = self.locate(&registry, path);
+
+        f(&registry, &path)
+    }
+
+    fn apply_mut<F, T>(&self, path: &Path, mut f: F) -> T
+    where
+        F: FnMut(&mut Registry, &Path) -> T,
+    {
+        let mut registry = self.lock();
+        let path = self.locate(&registry, path);
+
+        f(&mut registry, &path)
+    }
+
+    fn apply_mut_from_to<F, T>(&self, from: &Path, to: &Path, mut f: F) -> T
+    where
+        F: FnMut(&mut Registry, &Path, &Path) -> T,
+    {
+        let mut registry = self.lock();
+        let from = self.locate(&registry, from);
+        let to = self.locate(&registry, to);
+
+        f(&mut registry, &from, &to)
+    }
+}
+
+impl FileSystem for FakeFileSystem {
+    type DirEntry = DirEntry;
+    type ReadDir = ReadDir;
+    type Metadata = Metadata;
+    type OpenFile = FakeOpenFile;
+    type WalkDir = WalkDir;
+
+    fn current_dir(&self) -> Result<PathBuf> {
+        self.call("current_dir", &[], || {
+            let registry = self.read();
+
+            self.cwd(&registry).map(|cwd| registry.user_path(&cwd))
+        })
+    }
+
+    fn set_current_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.call("set_current_dir", &[path], || {
+            self.apply_mut(path, |r, p| match self.process {
+                Some(ref process) => process.set_cwd(r, p),
+                None => r.set_current_dir(p.to_path_buf()),
+            })
+        })
+    }
+
+    fn metadata<P: AsRef<Path>>(&self, path: P) -> Result<Self::Metadata> {
+        let path = path.as_ref();
+
+        self.call("metadata", &[path], || {
+            self.apply(path, |r, p| r.metadata(p))
+        })
+    }
+
+    fn canonicalize<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf> {
+        let path = path.as_ref();
+
+        self.call("canonicalize", &[path], || {
+            self.apply(path, |r, p| r.canonicalize(p).map(|p| r.user_path(&p)))
+        })
+    }
+
+    fn is_dir<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path = path.as_ref();
+
+        self.record_value("is_dir", &[path], self.apply(path, |r, p| r.is_dir(p)))
+    }
+
+    fn is_file<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path = path.as_ref();
+
+        self.record_value("is_file", &[path], self.apply(path, |r, p| r.is_file(p)))
+    }
+
+    fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path = path.as_ref();
+
+        self.record_value("exists", &[path], self.apply(path, |r, p| r.exists(p)))
+    }
+
+    fn try_exists<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+
+        self.call("try_exists", &[path], || {
+            self.apply(path, |r, p| r.try_exists(p))
+        })
+    }
+
+    fn create_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.call("create_dir", &[path], || {
+            self.apply_mut(path, |r, p| r.create_dir(p))
+        })
+    }
+
+    fn create_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.call("create_dir_all", &[path], || {
+            self.apply_mut(path, |r, p| r.create_dir_all(p))
+        })
+    }
+
+    fn remove_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.call("remove_dir", &[path], || {
+            self.hooks
+                .remove(&self.absolute(path))
+                .and_then(|_| self.apply_mut(path, |r, p| r.remove_dir(p)))
+        })
+    }
+
+    fn remove_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.call("remove_dir_all", &[path], || {
+            self.hooks.remove(&self.absolute(path)).and_then(|_| {
+                self.apply_mut(path, |r, p| {
+                    r.remove_dir_all(p, |p| self.faults.check_interruption("remove_dir_all", p))
+                })
+            })
+        })
+    }
+
+    fn read_dir<P: AsRef<Path>>(&self, path: P) -> Result<Self::ReadDir> {
+        let path = path.as_ref();
+
+        self.call("read_dir", &[path], || {
+            self.apply(path, |r, p| r.read_dir(p))
+                .map(|children| ReadDir::new(self.untraced(), path, children))
+        })
+    }
+
+    fn read_dir_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &ReadDirOptions,
+    ) -> Result<Vec<Self::DirEntry>> {
+        let path = path.as_ref();
+
+        // Children are filtered by name before any of their entries are created
+        self.call("read_dir_with_options", &[path], || {
+            self.apply(path, |r, p| r.read_dir(p)).and_then(|children| {
+                let children = children
+                    .into_iter()
+                    .filter(|child| child.file_name().is_some_and(|name| options.matches(name)))
+                    .collect();
+                let mut entries = ReadDir::new(self.untraced(), path, children)
+                    .collect::<io::Result<Vec<_>>>()?;
+
+                if options.sorted() {
+                    entries.sort_by_key(crate::DirEntry::file_name);
+                }
+
+                Ok(entries)
+            })
+        })
+    }
+
+    fn walk_dir<P: AsRef<Path>>(&self, path: P) -> Result<Self::WalkDir> {
+        let path = path.as_ref();
+
+        self.call("walk_dir", &[path], || {
+            self.untraced()
+                .walk_dir_with_options(path, &WalkDirOptions::new())
+        })
+    }
+
+    fn walk_dir_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &WalkDirOptions,
+    ) -> Result<Self::WalkDir> {
+        let path = path.as_ref();
+
+        self.call("walk_dir_with_options", &[path], || {
+            WalkDir::new(FakeWalkSource(self.untraced()), path, options)
+        })
+    }
+
+    fn glob(&self, pattern: &str) -> Result<Vec<PathBuf>> {
+        self.call("glob", &[Path::new(pattern)], || {
+            pattern::glob(&self.untraced(), pattern, &GlobOptions::new())
+        })
+    }
+
+    fn glob_with(&self, pattern: &str, options: &GlobOptions) -> Result<Vec<PathBuf>> {
+        self.call("glob_with", &[Path::new(pattern)], || {
+            pattern::glob(&self.untraced(), pattern, options)
+        })
+    }
+
+    fn create_file<P, B>(&self, path: P, buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+
+        self.call("create_file", &[path], || {
+            self.write_contents(path, buf.as_ref(), |r, p, buf| r.create_file(p, buf))
+        })
+    }
+
+    fn write_file<P, B>(&self, path: P, buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+
+        self.call("write_file", &[path], || {
+            self.write_contents(path, buf.as_ref(), |r, p, buf| r.write_file(p, buf))
+        })
+    }
+
+    fn overwrite_file<P, B>(&self, path: P, buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+
+        self.call("overwrite_file", &[path], || {
+            self.write_contents(path, buf.as_ref(), |r, p, buf| r.overwrite_file(p, buf))
+        })
+    }
+
+    fn set_len<P: AsRef<Path>>(&self, path: P, size: u64) -> Result<()> {
+        let path = path.as_ref();
+
+        self.call("set_len", &[path], || {
+            self.apply_mut(path, |r, p| r.set_len(p, size))
+        })
+    }
+
+    fn sync_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.call("sync_file", &[path], || {
+            self.apply_mut(path, |r, p| r.sync_file(p))
+        })
+    }
+
+    fn sync_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.call("sync_dir", &[path], || {
+            self.apply_mut(path, |r, p| r.sync_dir(p))
+        })
+    }
+
+    fn write_from_reader<P, R>(&self, path: P, reader: &mut R) -> Result<u64>
+    where
+        P: AsRef<Path>,
+        R: Read + ?Sized,
+    {
+        let path = path.as_ref();
+
+        // Each chunk is written through an open file, so hooks, limits, and latency apply to it
+        self.call("write_from_reader", &[path], || {
+            let mut options = OpenOptions::new();
+
+            options.write(true).create(true).truncate(true);
+            io::copy(reader, &mut self.open_file(path, &options)?)
+        })
+    }
+
+    fn write_file_atomic<P, B>(&self, path: P, buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+
+        self.call("write_file_atomic", &[path], || {
+            let absolute = self.absolute(path);
+            let buf = self.hooks.write(&absolute, buf.as_ref())?;
+
+            // A torn write would only reach the temporary file, which is removed rather than
+            // renamed, so it leaves `path` unchanged
+            if let Some((_, kind)) = self.faults.take_torn_write(&absolute) {
+                return Err(create_error(kind));
+            }
+
+            self.apply_mut(path, |r, p| {
+                let temp = temp_sibling(p)?;
+
+                r.create_file(&temp, &buf)?;
+                r.rename(&temp, p).inspect_err(|_| {
+                    let _ = r.remove_file(&temp);
+                })
+            })?;
+            self.latency.transfer(buf.len());
+
+            Ok(())
+        })
+    }
+
+    fn read_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
+        let path = path.as_ref();
+
+        self.call("read_file", &[path], || {
+            self.apply(path, |r, p| r.read_file(p))
+                .inspect(|buf| self.latency.transfer(buf.len()))
+        })
+    }
+
+    fn read_file_to_string<P: AsRef<Path>>(&self, path: P) -> Result<String> {
+        let path = path.as_ref();
+
+        self.call("read_file_to_string", &[path], || {
+            self.apply(path, |r, p| r.read_file_to_string(p))
+                .inspect(|buf| self.latency.transfer(buf.len()))
+        })
+    }
+
+    fn read_file_into<P, B>(&self, path: P, mut buf: B) -> Result<usize>
+    where
+        P: AsRef<Path>,
+        B: AsMut<Vec<u8>>,
+    {
+        let path = path.as_ref();
+
+        self.call("read_file_into", &[path], || {
+            self.apply(path, |r, p| r.read_file_into(p, buf.as_mut()))
+                .inspect(|n| self.latency.transfer(*n))
+        })
+    }
+
+    #[cfg(feature = "hash")]
+    fn hash_file<P: AsRef<Path>>(&self, path: P, algorithm: Algorithm) -> Result<Digest> {
+        let path = path.as_ref();
+
+        // Contents are shared rather than copied, and hashed after the registry is unlocked
+        self.call("hash_file", &[path], || {
+            self.apply(path, |r, p| r.read_contents(p))
+                .inspect(|contents| self.latency.transfer(contents.len()))
+                .and_then(|contents| hash::hash_reader(contents.into_reader(), algorithm))
+        })
+    }
+
+    fn open<P: AsRef<Path>>(&self, path: P) -> Result<Self::OpenFile> {
+        let path = path.as_ref();
+
+        self.call("open", &[path], || {
+            self.open_file(path, OpenOptions::new().read(true))
+        })
+    }
+
+    fn open_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &OpenOptions,
+    ) -> Result<Self::OpenFile> {
+        let path = path.as_ref();
+
+        self.call("open_with_options", &[path], || {
+            self.open_file(path, options)
+        })
+    }
+
+    fn remove_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.call("remove_file", &[path], || {
+            self.hooks
+                .remove(&self.absolute(path))
+                .and_then(|_| self.apply_mut(path, |r, p| r.remove_file(p)))
+        })
+    }
+
+    fn copy_file<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref(), to.as_ref());
+        let contents = if self.hooks.has_write() {
+            self.apply(from, |r, p| r.read_file(p)).ok()
+        } else {
+            None
+        };
+        // Without write hooks, or if `from` can't be read, the registry copies the file itself
+        self.call("copy_file", &[from, to], || match contents {
+            Some(buf) => self
+                .hooks
+                .write(&self.absolute(to), &buf)
+                .and_then(|buf| self.apply_mut(to, |r, p| r.write_file(p, &buf))),
+            None => self.apply_mut_from_to(from, to, |r, from, to| r.copy_file(from, to)),
+        })
+    }
+
+    fn copy_dir<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref(), to.as_ref());
+
+        self.call("copy_dir", &[from, to], || {
+            self.apply_mut_from_to(from, to, |r, from, to| {
+                r.copy_dir(from, to, |p| self.faults.check_interruption("copy_dir", p))
+            })
+        })
+    }
+
+    fn rename<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref(), to.as_ref());
+
+        self.call("rename", &[from, to], || {
+            self.apply_mut_from_to(from, to, |r, from, to| r.rename(from, to))
+        })
+    }
+
+    fn readonly<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+
+        self.call("readonly", &[path], || {
+            self.apply(path, |r, p| r.readonly(p))
+        })
+    }
+
+    fn set_readonly<P: AsRef<Path>>(&self, path: P, readonly: bool) -> Result<()> {
+        let path = path.as_ref();
+
+        self.call("set_readonly", &[path], || {
+            self.apply_mut(path, |r, p| r.set_readonly(p, readonly))
+        })
+    }
+
+    fn is_hidden<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+
+        self.call("is_hidden", &[path], || {
+            self.apply(path, |r, p| r.is_hidden(p))
+        })
+    }
+
+    fn modified<P: AsRef<Path>>(&self, path: P) -> Result<SystemTime> {
+        let path = path.as_ref();
+
+        self.call("modified", &[path], || {
+            self.apply(path, |r, p| r.metadata(p).map(|m| m.modified))
+        })
+    }
+
+    fn set_modified<P: AsRef<Path>>(&self, path: P, time: SystemTime) -> Result<()> {
+        let path = path.as_ref();
+
+        self.call("set_modified", &[path], || {
+            self.apply_mut(path, |r, p| r.set_modified(p, time))
+        })
+    }
+
+    fn accessed<P: AsRef<Path>>(&self, path: P) -> Result<SystemTime> {
+        let path = path.as_ref();
+
+        self.call("accessed", &[path], || {
+            self.apply(path, |r, p| r.metadata(p).map(|m| m.accessed))
+        })
+    }
+
+    fn set_accessed<P: AsRef<Path>>(&self, path: P, time: SystemTime) -> Result<()> {
+        let path = path.as_ref();
+
+        self.call("set_accessed", &[path], || {
+            self.apply_mut(path, |r, p| r.set_accessed(p, time))
+        })
+    }
+
+    fn len<P: AsRef<Path>>(&self, path: P) -> u64 {
+        let path = path.as_ref();
+
+        self.record_value("len", &[path], self.apply(path, |r, p| r.len(p)))
+    }
+
+    fn dir_size<P: AsRef<Path>>(&self, path: P, follow_links: bool) -> Result<u64> {
+        let path = path.as_ref();
+
+        // The registry adds up the sizes in one pass, rather than walking through this handle
+        self.call("dir_size", &[path], || {
+            self.apply(path, |r, p| r.dir_size(p, follow_links))
+        })
+    }
+
+    fn available_space<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+        let path = path.as_ref();
+
+        self.call("available_space", &[path], || {
+            self.apply(path, |r, p| r.available_space(p))
+        })
+    }
+
+    fn total_space<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+        let path = path.as_ref();
+
+        self.call("total_space", &[path], || {
+            self.apply(path, |r, p| r.total_space(p))
+        })
+    }
+}
+
+/// An entry of a directory, with the metadata it had when it was returned by [`ReadDir`].
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    path: PathBuf,
+    file_name: OsString,
+    metadata: Metadata,
+}
+
+impl DirEntry {
+    fn new<S: AsRef<OsStr>>(path: PathBuf, file_name: S, metadata: Metadata) -> Self {
+        DirEntry {
+            path,
+            file_name: file_name.as_ref().to_os_string(),
+            metadata,
+        }
+    }
+}
+
+impl crate::DirEntry for DirEntry {
+    type Metadata = Metadata;
+
+    fn file_name(&self) -> OsString {
+        self.file_name.clone()
+    }
+
+    fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    fn metadata(&self) -> io::Result<Metadata> {
+        Ok(self.metadata.clone())
+    }
+}
+
+/// The entries of a directory, sorted by name.
+///
+/// The names are listed when the directory is read, but each entry is only looked up once it is
+/// reached, so entries that are removed before then are skipped.
+#[derive(Debug)]
+pub struct ReadDir {
+    fs: FakeFileSystem,
+    parent: PathBuf,
+    children: IntoIter<PathBuf>,
+}
 
-use self::registry::Registry;
+impl ReadDir {
+    fn new(fs: FakeFileSystem, parent: &Path, children: Vec<PathBuf>) -> Self {
+        ReadDir {
+            fs,
+            parent: parent.to_path_buf(),
+            children: children.into_iter(),
+        }
+    }
+}
 
-mod node;
-mod registry;
-#[cfg(feature = "temp")]
-mod tempdir;
+impl Iterator for ReadDir {
+    type Item = io::Result<DirEntry>;
 
-/// An in-memory file system.
-#[derive(Clone, Debug, Default)]
-pub struct FakeFileSystem {
-    registry: Arc<Mutex<Registry>>,
-}
+    fn next(&mut self) -> Option<Self::Item> {
+        for child in &mut self.children {
+            let file_name = child.file_name().unwrap_or_else(|| child.as_os_str());
 
-impl FakeFileSystem {
-    pub fn new() -> Self {
-        let registry = Registry::new();
+            let registry = self.fs.read();
 
-        FakeFileSystem {
-            registry: Arc::new(Mutex::new(registry)),
+            match registry.symlink_metadata(&child) {
+                Ok(metadata) => {
+                    let path = registry.join(&self.parent, file_name);
+
+                    return Some(Ok(DirEntry::new(path, file_name, metadata)));
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Some(Err(err)),
+            }
         }
+
+        None
     }
+}
 
-    fn apply<F, T>(&self, path: &Path, f: F) -> T
-    where
-        F: FnOnce(&MutexGuard<Registry>, &Path) -> T,
-    {
-        let registry = self.registry.lock().unwrap();
-        let storage;
-        let path = if path.is_relative() {
-            storage = registry
-                .current_dir()
-                .unwrap_or_else(|_| PathBuf::from("/"))
-                .join(path);
-            &storage
-        } else {
-            path
-        };
+impl crate::ReadDir<DirEntry> for ReadDir {}
+
+#[cfg(feature = "async")]
+impl AsyncFileSystem for FakeFileSystem {
+    fn current_dir(&self) -> FsFuture<Result<PathBuf>> {
+        let fs = self.clone();
 
-        f(&registry, path)
+        async_fs::deferred(self.async_latency, move || FileSystem::current_dir(&fs))
     }
 
-    fn apply_mut<F, T>(&self, path: &Path, mut f: F) -> T
-    where
-        F: FnMut(&mut MutexGuard<Registry>, &Path) -> T,
-    {
-        let mut registry = self.registry.lock().unwrap();
-        let storage;
-        let path = if path.is_relative() {
-            storage = registry
-                .current_dir()
-                .unwrap_or_else(|_| PathBuf::from("/"))
-                .join(path);
-            &storage
-        } else {
-            path
-        };
+    fn set_current_dir<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<()>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
 
-        f(&mut registry, path)
+        async_fs::deferred(self.async_latency, move || {
+            FileSystem::set_current_dir(&fs, path)
+        })
     }
 
-    fn apply_mut_from_to<F, T>(&self, from: &Path, to: &Path, mut f: F) -> T
-    where
-        F: FnMut(&mut MutexGuard<Registry>, &Path, &Path) -> T,
-    {
-        let mut registry = self.registry.lock().unwrap();
-        let from_storage;
-        let from = if from.is_relative() {
-            from_storage = registry
-                .current_dir()
-                .unwrap_or_else(|_| PathBuf::from("/"))
-                .join(from);
-            &from_storage
-        } else {
-            from
-        };
-        let to_storage;
-        let to = if to.is_relative() {
-            to_storage = registry
-                .current_dir()
-                .unwrap_or_else(|_| PathBuf::from("/"))
-                .join(to);
-            &to_storage
-        } else {
-            to
-        };
+    fn metadata<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<Self::Metadata>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
 
-        f(&mut registry, from, to)
+        async_fs::deferred(self.async_latency, move || FileSystem::metadata(&fs, path))
     }
-}
 
-impl FileSystem for FakeFileSystem {
-    type DirEntry = DirEntry;
-    type ReadDir = ReadDir;
+    fn canonicalize<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<PathBuf>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
 
-    fn current_dir(&self) -> Result<PathBuf> {
-        let registry = self.registry.lock().unwrap();
-        registry.current_dir()
+        async_fs::deferred(self.async_latency, move || {
+            FileSystem::canonicalize(&fs, path)
+        })
     }
 
-    fn set_current_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        self.apply_mut(path.as_ref(), |r, p| r.set_current_dir(p.to_path_buf()))
+    fn is_dir<P: AsRef<Path>>(&self, path: P) -> FsFuture<bool> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::deferred(self.async_latency, move || FileSystem::is_dir(&fs, path))
     }
 
-    fn is_dir<P: AsRef<Path>>(&self, path: P) -> bool {
-        self.apply(path.as_ref(), |r, p| r.is_dir(p))
+    fn is_file<P: AsRef<Path>>(&self, path: P) -> FsFuture<bool> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::deferred(self.async_latency, move || FileSystem::is_file(&fs, path))
     }
 
-    fn is_file<P: AsRef<Path>>(&self, path: P) -> bool {
-        self.apply(path.as_ref(), |r, p| r.is_file(p))
+    fn exists<P: AsRef<Path>>(&self, path: P) -> FsFuture<bool> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::deferred(self.async_latency, move || FileSystem::exists(&fs, path))
     }
 
-    fn create_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        self.apply_mut(path.as_ref(), |r, p| r.create_dir(p))
+    fn try_exists<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<bool>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::deferred(self.async_latency, move || {
+            FileSystem::try_exists(&fs, path)
+        })
     }
 
-    fn create_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        self.apply_mut(path.as_ref(), |r, p| r.create_dir_all(p))
+    fn create_dir<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<()>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::deferred(self.async_latency, move || {
+            FileSystem::create_dir(&fs, path)
+        })
     }
 
-    fn remove_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        self.apply_mut(path.as_ref(), |r, p| r.remove_dir(p))
+    fn create_dir_all<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<()>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::deferred(self.async_latency, move || {
+            FileSystem::create_dir_all(&fs, path)
+        })
     }
 
-    fn remove_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        self.apply_mut(path.as_ref(), |r, p| r.remove_dir_all(p))
+    fn remove_dir<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<()>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::deferred(self.async_latency, move || {
+            FileSystem::remove_dir(&fs, path)
+        })
     }
 
-    fn read_dir<P: AsRef<Path>>(&self, path: P) -> Result<Self::ReadDir> {
-        let path = path.as_ref();
+    fn remove_dir_all<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<()>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
 
-        self.apply(path, |r, p| r.read_dir(p)).map(|entries| {
-            let entries = entries
-                .iter()
-                .map(|e| {
-                    let file_name = e.file_name().unwrap_or_else(|| e.as_os_str());
+        async_fs::deferred(self.async_latency, move || {
+            FileSystem::remove_dir_all(&fs, path)
+        })
+    }
 
-                    Ok(DirEntry::new(path, &file_name))
-                })
-                .collect();
+    fn read_dir<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<Vec<Self::DirEntry>>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
 
-            ReadDir::new(entries)
+        async_fs::deferred(self.async_latency, move || {
+            FileSystem::read_dir(&fs, &path)?
+                .collect::<io::Result<_>>()
+                .context("read_dir", &[&path])
         })
     }
 
-    fn create_file<P, B>(&self, path: P, buf: B) -> Result<()>
+    fn create_file<P, B>(&self, path: P, buf: B) -> FsFuture<Result<()>>
     where
         P: AsRef<Path>,
         B: AsRef<[u8]>,
     {
-        self.apply_mut(path.as_ref(), |r, p| r.create_file(p, buf.as_ref()))
+        let (fs, path, buf) = (
+            self.clone(),
+            path.as_ref().to_path_buf(),
+            buf.as_ref().to_vec(),
+        );
+
+        async_fs::deferred(self.async_latency, move || {
+            FileSystem::create_file(&fs, path, buf)
+        })
     }
 
-    fn write_file<P, B>(&self, path: P, buf: B) -> Result<()>
+    fn write_file<P, B>(&self, path: P, buf: B) -> FsFuture<Result<()>>
     where
         P: AsRef<Path>,
         B: AsRef<[u8]>,
     {
-        self.apply_mut(path.as_ref(), |r, p| r.write_file(p, buf.as_ref()))
+        let (fs, path, buf) = (
+            self.clone(),
+            path.as_ref().to_path_buf(),
+            buf.as_ref().to_vec(),
+        );
+
+        async_fs::deferred(self.async_latency, move || {
+            FileSystem::write_file(&fs, path, buf)
+        })
     }
 
-    fn overwrite_file<P, B>(&self, path: P, buf: B) -> Result<()>
+    fn overwrite_file<P, B>(&self, path: P, buf: B) -> FsFuture<Result<()>>
     where
         P: AsRef<Path>,
         B: AsRef<[u8]>,
     {
-        self.apply_mut(path.as_ref(), |r, p| r.overwrite_file(p, buf.as_ref()))
+        let (fs, path, buf) = (
+            self.clone(),
+            path.as_ref().to_path_buf(),
+            buf.as_ref().to_vec(),
+        );
+
+        async_fs::deferred(self.async_latency, move || {
+            FileSystem::overwrite_file(&fs, path, buf)
+        })
     }
 
-    fn read_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
-        self.apply(path.as_ref(), |r, p| r.read_file(p))
+    fn read_file<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<Vec<u8>>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::deferred(self.async_latency, move || FileSystem::read_file(&fs, path))
     }
 
-    fn read_file_to_string<P: AsRef<Path>>(&self, path: P) -> Result<String> {
-        self.apply(path.as_ref(), |r, p| r.read_file_to_string(p))
+    fn read_file_to_string<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<String>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::deferred(self.async_latency, move || {
+            FileSystem::read_file_to_string(&fs, path)
+        })
     }
 
-    fn read_file_into<P, B>(&self, path: P, mut buf: B) -> Result<usize>
+    fn remove_file<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<()>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::deferred(self.async_latency, move || {
+            FileSystem::remove_file(&fs, path)
+        })
+    }
+
+    fn copy_file<P, Q>(&self, from: P, to: Q) -> FsFuture<Result<()>>
     where
         P: AsRef<Path>,
-        B: AsMut<Vec<u8>>,
+        Q: AsRef<Path>,
     {
-        self.apply(path.as_ref(), |r, p| r.read_file_into(p, buf.as_mut()))
-    }
+        let (fs, from, to) = (
+            self.clone(),
+            from.as_ref().to_path_buf(),
+            to.as_ref().to_path_buf(),
+        );
 
-    fn remove_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        self.apply_mut(path.as_ref(), |r, p| r.remove_file(p))
+        async_fs::deferred(self.async_latency, move || {
+            FileSystem::copy_file(&fs, from, to)
+        })
     }
 
-    fn copy_file<P, Q>(&self, from: P, to: Q) -> Result<()>
+    fn copy_dir<P, Q>(&self, from: P, to: Q) -> FsFuture<Result<()>>
     where
         P: AsRef<Path>,
         Q: AsRef<Path>,
     {
-        self.apply_mut_from_to(from.as_ref(), to.as_ref(), |r, from, to| {
-            r.copy_file(from, to)
+        let (fs, from, to) = (
+            self.clone(),
+            from.as_ref().to_path_buf(),
+            to.as_ref().to_path_buf(),
+        );
+
+        async_fs::deferred(self.async_latency, move || {
+            FileSystem::copy_dir(&fs, from, to)
         })
     }
 
-    fn rename<P, Q>(&self, from: P, to: Q) -> Result<()>
+    fn rename<P, Q>(&self, from: P, to: Q) -> FsFuture<Result<()>>
     where
         P: AsRef<Path>,
         Q: AsRef<Path>,
     {
-        self.apply_mut_from_to(from.as_ref(), to.as_ref(), |r, from, to| r.rename(from, to))
+        let (fs, from, to) = (
+            self.clone(),
+            from.as_ref().to_path_buf(),
+            to.as_ref().to_path_buf(),
+        );
+
+        async_fs::deferred(self.async_latency, move || {
+            FileSystem::rename(&fs, from, to)
+        })
     }
 
-    fn readonly<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
-        self.apply(path.as_ref(), |r, p| r.readonly(p))
+    fn readonly<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<bool>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::deferred(self.async_latency, move || FileSystem::readonly(&fs, path))
     }
 
-    fn set_readonly<P: AsRef<Path>>(&self, path: P, readonly: bool) -> Result<()> {
-        self.apply_mut(path.as_ref(), |r, p| r.set_readonly(p, readonly))
+    fn set_readonly<P: AsRef<Path>>(&self, path: P, readonly: bool) -> FsFuture<Result<()>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::deferred(self.async_latency, move || {
+            FileSystem::set_readonly(&fs, path, readonly)
+        })
     }
 
-    fn len<P: AsRef<Path>>(&self, path: P) -> u64 {
-        self.apply(path.as_ref(), |r, p| r.len(p))
+    fn len<P: AsRef<Path>>(&self, path: P) -> FsFuture<u64> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::deferred(self.async_latency, move || FileSystem::len(&fs, path))
+    }
+}
+
+#[derive(Debug)]
+struct FakeWalkSource(FakeFileSystem);
+
+impl WalkSource for FakeWalkSource {
+    fn list(&self, dir: &Path) -> io::Result<Vec<(PathBuf, FileType)>> {
+        self.0.apply(dir, |r, p| {
+            r.read_dir(p)?
+                .iter()
+                .map(|child| {
+                    let file_name = child.file_name().unwrap_or_else(|| child.as_os_str());
+                    let file_type = r.symlink_metadata(child)?.file_type;
+
+                    Ok((r.join(dir, file_name), file_type))
+                })
+                .collect()
+        })
+    }
+
+    fn follow(&self, path: &Path) -> io::Result<(PathBuf, FileType)> {
+        self.0.apply(path, |r, p| {
+            let file_type = r.metadata(p)?.file_type;
+
+            Ok((r.user_path(&r.resolve_path(p)?), file_type))
+        })
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct DirEntry {
-    parent: PathBuf,
-    file_name: OsString,
+pub struct Metadata {
+    file_type: FileType,
+    len: u64,
+    mode: u32,
+    created: SystemTime,
+    modified: SystemTime,
+    accessed: SystemTime,
 }
 
-impl DirEntry {
-    fn new<P, S>(parent: P, file_name: S) -> Self
-    where
-        P: AsRef<Path>,
-        S: AsRef<OsStr>,
-    {
-        DirEntry {
-            parent: parent.as_ref().to_path_buf(),
-            file_name: file_name.as_ref().to_os_string(),
+impl<'a> From<&'a Node> for Metadata {
+    fn from(node: &'a Node) -> Self {
+        match *node {
+            Node::File(ref file) => Metadata {
+                file_type: FileType::File,
+                len: file.contents.len() as u64,
+                mode: file.mode,
+                created: file.created,
+                modified: file.modified,
+                accessed: file.accessed,
+            },
+            Node::Dir(ref dir) => Metadata {
+                file_type: FileType::Dir,
+                len: 4096,
+                mode: dir.mode,
+                created: dir.created,
+                modified: dir.modified,
+                accessed: dir.accessed,
+            },
+            Node::Symlink(ref symlink) => Metadata {
+                file_type: FileType::Symlink,
+                len: symlink.target.as_os_str().len() as u64,
+                mode: symlink.mode,
+                created: symlink.created,
+                modified: symlink.modified,
+                accessed: symlink.accessed,
+            },
+            Node::Special(ref special) => Metadata {
+                file_type: special.file_type,
+                len: 0,
+                mode: special.mode,
+                created: special.created,
+                modified: special.modified,
+                accessed: special.accessed,
+            },
         }
     }
 }
 
-impl crate::DirEntry for DirEntry {
-    fn file_name(&self) -> OsString {
-        self.file_name.clone()
+impl crate::Metadata for Metadata {
+    fn file_type(&self) -> FileType {
+        self.file_type
     }
 
-    fn path(&self) -> PathBuf {
-        self.parent.join(&self.file_name)
+    fn len(&self) -> u64 {
+        self.len
     }
-}
 
-#[derive(Debug)]
-pub struct ReadDir(IntoIter<Result<DirEntry>>);
+    fn readonly(&self) -> bool {
+        self.mode & 0o222 == 0
+    }
 
-impl ReadDir {
-    fn new(entries: Vec<Result<DirEntry>>) -> Self {
-        ReadDir(entries.into_iter())
+    fn created(&self) -> io::Result<SystemTime> {
+        Ok(self.created)
     }
-}
 
-impl Iterator for ReadDir {
-    type Item = Result<DirEntry>;
+    fn modified(&self) -> io::Result<SystemTime> {
+        Ok(self.modified)
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.0.next()
+    fn accessed(&self) -> io::Result<SystemTime> {
+        Ok(self.accessed)
     }
 }
 
-impl crate::ReadDir<DirEntry> for ReadDir {}
+impl SymlinkFileSystem for FakeFileSystem {
+    fn symlink_file<P, Q>(&self, original: P, link: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (original, link) = (original.as_ref(), link.as_ref());
+        self.call("symlink_file", &[original, link], || {
+            self.apply_mut(link, |r, p| r.symlink(original, p))
+        })
+    }
+
+    fn symlink_dir<P, Q>(&self, original: P, link: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (original, link) = (original.as_ref(), link.as_ref());
+        self.call("symlink_dir", &[original, link], || {
+            self.apply_mut(link, |r, p| r.symlink(original, p))
+        })
+    }
+
+    fn read_link<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf> {
+        let path = path.as_ref();
+
+        self.call("read_link", &[path], || {
+            self.apply(path, |r, p| r.read_link(p))
+        })
+    }
+
+    fn symlink_metadata<P: AsRef<Path>>(&self, path: P) -> Result<Self::Metadata> {
+        let path = path.as_ref();
+
+        self.call("symlink_metadata", &[path], || {
+            self.apply(path, |r, p| r.symlink_metadata(p))
+        })
+    }
+}
 
 #[cfg(unix)]
 impl UnixFileSystem for FakeFileSystem {
     fn mode<P: AsRef<Path>>(&self, path: P) -> Result<u32> {
-        self.apply(path.as_ref(), |r, p| r.mode(p))
+        let path = path.as_ref();
+
+        self.call("mode", &[path], || self.apply(path, |r, p| r.mode(p)))
     }
 
     fn set_mode<P: AsRef<Path>>(&self, path: P, mode: u32) -> Result<()> {
-        self.apply_mut(path.as_ref(), |r, p| r.set_mode(p, mode))
+        let path = path.as_ref();
+
+        self.call("set_mode", &[path], || {
+            self.apply_mut(path, |r, p| r.set_mode(p, mode))
+        })
+    }
+
+    fn owner<P: AsRef<Path>>(&self, path: P) -> Result<(u32, u32)> {
+        let path = path.as_ref();
+
+        self.call("owner", &[path], || self.apply(path, |r, p| r.owner(p)))
+    }
+
+    fn set_owner<P: AsRef<Path>>(&self, path: P, uid: u32, gid: u32) -> Result<()> {
+        let path = path.as_ref();
+
+        self.call("set_owner", &[path], || {
+            self.apply_mut(path, |r, p| r.set_owner(p, uid, gid))
+        })
+    }
+
+    fn create_file_with_mode<P, B>(&self, path: P, buf: B, mode: u32) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+
+        self.call("create_file_with_mode", &[path], || {
+            self.write_contents(path, buf.as_ref(), |r, p, buf| {
+                r.create_file_with_mode(p, buf, mode)
+            })
+        })
+    }
+
+    fn create_dir_with_mode<P: AsRef<Path>>(&self, path: P, mode: u32) -> Result<()> {
+        let path = path.as_ref();
+
+        self.call("create_dir_with_mode", &[path], || {
+            self.apply_mut(path, |r, p| r.create_dir_with_mode(p, mode))
+        })
+    }
+
+    fn mknod<P: AsRef<Path>>(
+        &self,
+        path: P,
+        file_type: FileType,
+        mode: u32,
+        device: u64,
+    ) -> Result<()> {
+        let path = path.as_ref();
+
+        self.call("mknod", &[path], || {
+            self.apply_mut(path, |r, p| r.mknod(p, file_type, mode, device))
+        })
+    }
+}
+
+#[cfg(windows)]
+impl WindowsFileSystem for FakeFileSystem {
+    fn attributes<P: AsRef<Path>>(&self, path: P) -> Result<u32> {
+        let path = path.as_ref();
+
+        self.call("attributes", &[path], || {
+            self.apply(path, |r, p| r.attributes(p))
+        })
+    }
+
+    fn set_attributes<P: AsRef<Path>>(&self, path: P, attributes: u32) -> Result<()> {
+        let path = path.as_ref();
+
+        self.call("set_attributes", &[path], || {
+            self.apply_mut(path, |r, p| r.set_attributes(p, attributes))
+        })
+    }
+
+    fn create_junction<P, Q>(&self, original: P, junction: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (original, junction) = (original.as_ref(), junction.as_ref());
+        // Junctions always store an absolute target, which is what `from` is
+        self.call("create_junction", &[original, junction], || {
+            self.apply_mut_from_to(original, junction, |r, from, to| r.symlink(from, to))
+        })
+    }
+}
+
+#[cfg(feature = "watch")]
+impl WatchFileSystem for FakeFileSystem {
+    fn watch<P: AsRef<Path>>(&self, path: P, recursive: bool) -> Result<FsWatcher> {
+        let path = path.as_ref();
+        let (sender, receiver) = mpsc::channel();
+
+        self.call("watch", &[path], || {
+            self.apply_mut(path, |r, p| r.watch(p, recursive, sender.clone()))
+        })
+        .map(|_| FsWatcher::from(receiver))
     }
 }
 
 #[cfg(feature = "temp")]
 impl TempFileSystem for FakeFileSystem {
     type TempDir = FakeTempDir;
+    type TempFile = FakeTempFile;
 
     fn temp_dir<S: AsRef<str>>(&self, prefix: S) -> Result<Self::TempDir> {
-        let base = env::temp_dir();
-        let dir = FakeTempDir::new(Arc::downgrade(&self.registry), &base, prefix.as_ref());
+        let prefix = prefix.as_ref();
+        let name = format!("{}_{}", prefix, self.temp_names.next());
+        let path = Environment::temp_dir(self).join(prefix).join(name);
+        let dir = FakeTempDir::new(Arc::downgrade(&self.registry), path);
+
+        FileSystem::create_dir_all(&self.untraced(), dir.path()).and(Ok(dir))
+    }
+
+    fn temp_dir_with_options<P: AsRef<Path>>(
+        &self,
+        base: P,
+        options: &TempOptions,
+    ) -> Result<Self::TempDir> {
+        let path = self.temp_path(base.as_ref(), options);
+
+        FileSystem::create_dir(&self.untraced(), &path)?;
+        self.set_temp_mode(&path, options)?;
+
+        Ok(FakeTempDir::new(Arc::downgrade(&self.registry), path))
+    }
+
+    fn temp_file<S: AsRef<str>>(&self, prefix: S) -> Result<Self::TempFile> {
+        let base = Environment::temp_dir(self);
+        let name = format!("{}_{}", prefix.as_ref(), self.temp_names.next());
+        let file = FakeTempFile::new(Arc::downgrade(&self.registry), base.join(name));
+        let fs = self.untraced();
+
+        FileSystem::create_dir_all(&fs, &base)?;
+        FileSystem::create_file(&fs, file.path(), "").and(Ok(file))
+    }
+
+    fn temp_file_with_options<P: AsRef<Path>>(
+        &self,
+        base: P,
+        options: &TempOptions,
+    ) -> Result<Self::TempFile> {
+        let path = self.temp_path(base.as_ref(), options);
+
+        FileSystem::create_file(&self.untraced(), &path, "")?;
+        self.set_temp_mode(&path, options)?;
+
+        Ok(FakeTempFile::new(Arc::downgrade(&self.registry), path))
+    }
+}
+
+#[cfg(feature = "temp")]
+impl FakeFileSystem {
+    /// Returns the absolute path of a new temporary node inside `base`, named with `options`.
+    fn temp_path(&self, base: &Path, options: &TempOptions) -> PathBuf {
+        self.absolute(base)
+            .join(temp::name(options, &self.temp_names.next()))
+    }
+
+    /// Sets the mode bits of the temporary node at `path`, if `options` has any.
+    #[cfg(unix)]
+    fn set_temp_mode(&self, path: &Path, options: &TempOptions) -> Result<()> {
+        if let Some(mode) = temp::mode(options) {
+            return self
+                .apply_mut(path, |r, p| r.set_mode(p, mode))
+                .context("set_mode", &[path]);
+        }
+
+        Ok(())
+    }
 
-        self.create_dir_all(&dir.path()).and(Ok(dir))
+    /// Modes only exist on Unix, so there are none to set.
+    #[cfg(not(unix))]
+    fn set_temp_mode(&self, _path: &Path, _options: &TempOptions) -> Result<()> {
+        Ok(())
     }
 }
+
+/// Creates a [`FakeFileSystem`] containing the given files and directories.
+///
+/// Files are given as paths and contents, and directories as paths followed by `dir`.
+/// Missing parent directories are created for both.
+///
+/// ```
+/// # #[macro_use] extern crate filesystem;
+/// # fn main() {
+/// let fs = fake_fs! {
+///     "/etc/app.conf" => "key=1",
+///     "/var/lib/app" => dir,
+/// };
+/// # }
+/// ```
+///
+/// # Panics
+///
+/// Panics if a file or directory cannot be created, such as when a path is used as both.
+///
+/// [`FakeFileSystem`]: struct.FakeFileSystem.html
+#[macro_export]
+macro_rules! fake_fs {
+    (@insert $fs:ident;) => {};
+    (@insert $fs:ident; $path:expr => dir $(, $($rest:tt)*)?) => {
+        let path = $path;
+
+        $crate::FileSystem::create_dir_all(&$fs, &path)
+            .unwrap_or_else(|err| panic!("failed to create directory {:?}: {}", path, err));
+        $crate::fake_fs!(@insert $fs; $($($rest)*)?);
+    };
+    (@insert $fs:ident; $path:expr => $contents:expr $(, $($rest:tt)*)?) => {
+        let path = $path;
+
+        $fs.write_file_all(&path, $contents)
+            .unwrap_or_else(|err| panic!("failed to create file {:?}: {}", path, err));
+        $crate::fake_fs!(@insert $fs; $($($rest)*)?);
+    };
+    ($($entries:tt)*) => {{
+        let fs = $crate::FakeFileSystem::new();
+
+        $crate::fake_fs!(@insert fs; $($entries)*);
+
+        fs
+    }};
+}
+
+/// Asserts that two [`FakeFileSystem`]s have the same nodes, as [`FakeFileSystem::diff`] compares
+/// them.
+///
+/// On failure, the message draws both trees with [`format_tree`] and lists the changes that turn
+/// the left file system into the right one.
+/// Like `assert_eq!`, it takes an optional message to add after the first two arguments.
+///
+/// ```
+/// # #[macro_use] extern crate filesystem;
+/// # use filesystem::FileSystem;
+/// # fn main() {
+/// let fs = fake_fs! { "/app/config.toml" => "key = 1" };
+///
+/// fs.write_file("/app/config.toml", "key = 2").unwrap();
+///
+/// assert_fs_eq!(fs, fake_fs! { "/app/config.toml" => "key = 2" });
+/// # }
+/// ```
+///
+/// [`FakeFileSystem`]: struct.FakeFileSystem.html
+/// [`FakeFileSystem::diff`]: struct.FakeFileSystem.html#method.diff
+/// [`format_tree`]: struct.FakeFileSystem.html#method.format_tree
+#[macro_export]
+macro_rules! assert_fs_eq {
+    (@assert $left:expr, $right:expr, $message:expr) => {{
+        let (left, right): (&$crate::FakeFileSystem, &$crate::FakeFileSystem) = (&$left, &$right);
+        let changes = left.diff(right);
+
+        if !changes.is_empty() {
+            let changes: Vec<String> = changes.iter().map(|change| format!("  {}", change)).collect();
+
+            panic!(
+                "{}\nleft:\n{}\nright:\n{}\nchanges from left to right:\n{}",
+                $message,
+                left,
+                right,
+                changes.join("\n")
+            );
+        }
+    }};
+    ($left:expr, $right:expr $(,)?) => {
+        $crate::assert_fs_eq!(@assert $left, $right, "assertion `left == right` failed")
+    };
+    ($left:expr, $right:expr, $($arg:tt)+) => {
+        $crate::assert_fs_eq!(
+            @assert $left,
+            $right,
+            format_args!("assertion `left == right` failed: {}", format_args!($($arg)+))
+        )
+    };
+}
@@ -0,0 +1,48 @@
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::sync::{Arc, Mutex};
+
+use recover::Recover;
+
+/// The name of a node in its parent directory, which is shared with every other node of the same
+/// name.
+pub type Name = Arc<OsStr>;
+
+/// The fewest names at which unused ones are dropped.
+const MIN_PRUNE_AT: usize = 1024;
+
+/// Interns the names of nodes, so that a name that many directories contain, such as `src` or
+/// `mod.rs`, is only stored once.
+///
+/// Clones share their names, so that snapshots and forks of a file system share them as well.
+#[derive(Debug, Clone, Default)]
+pub struct Names(Arc<Mutex<Interned>>);
+
+#[derive(Debug, Default)]
+struct Interned {
+    names: BTreeSet<Name>,
+    /// The number of names at which those that no node uses any longer are next dropped.
+    prune_at: usize,
+}
+
+impl Names {
+    /// Returns the shared copy of `name`, adding it if there isn't one yet.
+    pub fn intern(&self, name: &OsStr) -> Name {
+        let mut interned = self.0.lock().recover();
+
+        if let Some(name) = interned.names.get(name) {
+            return name.clone();
+        }
+
+        let name = Name::from(name);
+
+        interned.names.insert(name.clone());
+
+        if interned.names.len() >= interned.prune_at.max(MIN_PRUNE_AT) {
+            interned.names.retain(|name| Arc::strong_count(name) > 1);
+            interned.prune_at = interned.names.len() * 2;
+        }
+
+        name
+    }
+}
@@ -1,26 +1,171 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use FileType;
+
+use super::clock;
+use super::contents::Contents;
+use super::names::Name;
+
+/// Windows marks newly created and modified files for backup with this attribute.
+const FILE_ATTRIBUTE_ARCHIVE: u32 = 0x20;
+
+/// Windows hides nodes with this attribute from directory listings by default.
+pub const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+
+/// A file, whose contents are shared with any copies of it until either is written to.
 #[derive(Debug, Clone)]
 pub struct File {
-    pub contents: Vec<u8>,
+    pub contents: Contents,
+    /// The contents as of the last sync, which are all that a simulated crash keeps.
+    pub synced: Contents,
     pub mode: u32,
+    pub attributes: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub created: SystemTime,
+    pub modified: SystemTime,
+    pub accessed: SystemTime,
 }
 
 impl File {
-    pub fn new(contents: Vec<u8>) -> Self {
+    pub fn new<C: Into<Contents>>(contents: C) -> Self {
+        let now = clock::now();
+
         File {
-            contents,
+            contents: contents.into(),
+            synced: Contents::default(),
             mode: 0o644,
+            attributes: FILE_ATTRIBUTE_ARCHIVE,
+            uid: 0,
+            gid: 0,
+            created: now,
+            modified: now,
+            accessed: now,
         }
     }
+
+    pub fn set_contents(&mut self, contents: Vec<u8>) {
+        self.contents = Contents::from(contents);
+        self.modified = clock::now();
+    }
 }
 
-#[derive(Debug, Clone, Default)]
+/// The children of a directory by name.
+pub type Children = BTreeMap<Name, Node>;
+
+/// A directory, which owns its children by name, so that the nodes form a tree rooted at `/`.
+///
+/// The children are shared with any copies of the directory until either changes them, so that
+/// copying a tree only copies the directories that are changed afterwards, along with their
+/// ancestors.
+#[derive(Debug, Clone)]
 pub struct Dir {
+    pub children: Arc<Children>,
+    /// The children as of the last sync, which are all that a simulated crash keeps, or `None`
+    /// if they have not changed since.
+    pub synced: Option<Arc<Children>>,
     pub mode: u32,
+    pub attributes: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub created: SystemTime,
+    pub modified: SystemTime,
+    pub accessed: SystemTime,
 }
 
 impl Dir {
     pub fn new() -> Self {
-        Dir { mode: 0o644 }
+        let now = clock::now();
+
+        Dir {
+            children: Arc::default(),
+            synced: None,
+            mode: 0o755,
+            attributes: 0,
+            uid: 0,
+            gid: 0,
+            created: now,
+            modified: now,
+            accessed: now,
+        }
+    }
+}
+
+impl Dir {
+    /// Returns the children to change, which are copied first if they are shared.
+    pub fn children_mut(&mut self) -> &mut Children {
+        Arc::make_mut(&mut self.children)
+    }
+}
+
+impl Default for Dir {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Symlink {
+    pub target: PathBuf,
+    pub mode: u32,
+    pub attributes: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub created: SystemTime,
+    pub modified: SystemTime,
+    pub accessed: SystemTime,
+}
+
+impl Symlink {
+    pub fn new(target: PathBuf) -> Self {
+        let now = clock::now();
+
+        Symlink {
+            target,
+            mode: 0o777,
+            attributes: 0,
+            uid: 0,
+            gid: 0,
+            created: now,
+            modified: now,
+            accessed: now,
+        }
+    }
+}
+
+/// A FIFO, socket, or device node, which has no contents of its own.
+#[derive(Debug, Clone)]
+pub struct Special {
+    pub file_type: FileType,
+    /// The device number, which is only meaningful for devices.
+    pub device: u64,
+    pub mode: u32,
+    pub attributes: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub created: SystemTime,
+    pub modified: SystemTime,
+    pub accessed: SystemTime,
+}
+
+impl Special {
+    pub fn new(file_type: FileType, device: u64) -> Self {
+        let now = clock::now();
+
+        Special {
+            file_type,
+            device,
+            mode: 0o644,
+            attributes: 0,
+            uid: 0,
+            gid: 0,
+            created: now,
+            modified: now,
+            accessed: now,
+        }
     }
 }
 
@@ -28,20 +173,152 @@ impl Dir {
 pub enum Node {
     File(File),
     Dir(Dir),
+    Symlink(Symlink),
+    Special(Special),
 }
 
 impl Node {
-    pub fn is_file(&self) -> bool {
+    pub fn file_type(&self) -> FileType {
         match *self {
-            Self::File(_) => true,
-            _ => false,
+            Self::File(_) => FileType::File,
+            Self::Dir(_) => FileType::Dir,
+            Self::Symlink(_) => FileType::Symlink,
+            Self::Special(ref special) => special.file_type,
         }
     }
 
+    pub fn is_file(&self) -> bool {
+        matches!(*self, Self::File(_))
+    }
+
     pub fn is_dir(&self) -> bool {
+        matches!(*self, Self::Dir(_))
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        matches!(*self, Self::Symlink(_))
+    }
+
+    pub fn mode(&self) -> u32 {
+        match *self {
+            Self::File(ref file) => file.mode,
+            Self::Dir(ref dir) => dir.mode,
+            Self::Symlink(ref symlink) => symlink.mode,
+            Self::Special(ref special) => special.mode,
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: u32) {
+        match *self {
+            Self::File(ref mut file) => file.mode = mode,
+            Self::Dir(ref mut dir) => dir.mode = mode,
+            Self::Symlink(ref mut symlink) => symlink.mode = mode,
+            Self::Special(ref mut special) => special.mode = mode,
+        }
+    }
+
+    /// Returns the Windows attributes that are stored rather than derived from the node's type
+    /// and mode.
+    pub fn attributes(&self) -> u32 {
+        match *self {
+            Self::File(ref file) => file.attributes,
+            Self::Dir(ref dir) => dir.attributes,
+            Self::Symlink(ref symlink) => symlink.attributes,
+            Self::Special(ref special) => special.attributes,
+        }
+    }
+
+    pub fn set_attributes(&mut self, attributes: u32) {
+        match *self {
+            Self::File(ref mut file) => file.attributes = attributes,
+            Self::Dir(ref mut dir) => dir.attributes = attributes,
+            Self::Symlink(ref mut symlink) => symlink.attributes = attributes,
+            Self::Special(ref mut special) => special.attributes = attributes,
+        }
+    }
+
+    /// Returns the user and group IDs of the node's owner.
+    pub fn owner(&self) -> (u32, u32) {
+        match *self {
+            Self::File(ref file) => (file.uid, file.gid),
+            Self::Dir(ref dir) => (dir.uid, dir.gid),
+            Self::Symlink(ref symlink) => (symlink.uid, symlink.gid),
+            Self::Special(ref special) => (special.uid, special.gid),
+        }
+    }
+
+    pub fn set_owner(&mut self, uid: u32, gid: u32) {
         match *self {
-            Self::Dir(_) => true,
+            Self::File(ref mut file) => {
+                file.uid = uid;
+                file.gid = gid;
+            }
+            Self::Dir(ref mut dir) => {
+                dir.uid = uid;
+                dir.gid = gid;
+            }
+            Self::Symlink(ref mut symlink) => {
+                symlink.uid = uid;
+                symlink.gid = gid;
+            }
+            Self::Special(ref mut special) => {
+                special.uid = uid;
+                special.gid = gid;
+            }
+        }
+    }
+
+    /// Returns a new node with the same contents, mode, and attributes, as if it had just been
+    /// copied.
+    pub fn copied(&self) -> Self {
+        let mut node = match *self {
+            Self::File(ref file) => Self::File(File::new(file.contents.clone())),
+            Self::Dir(_) => Self::Dir(Dir::new()),
+            Self::Symlink(ref symlink) => Self::Symlink(Symlink::new(symlink.target.clone())),
+            Self::Special(ref special) => {
+                Self::Special(Special::new(special.file_type, special.device))
+            }
+        };
+
+        node.set_mode(self.mode());
+        node.set_attributes(self.attributes());
+        node
+    }
+
+    /// Returns `true` if both nodes have the same type, contents or target, mode, attributes, and
+    /// owner, regardless of their timestamps.
+    pub fn same_as(&self, other: &Node) -> bool {
+        let same_contents = match (self, other) {
+            (Self::File(a), Self::File(b)) => a.contents == b.contents,
+            (Self::Dir(_), Self::Dir(_)) => true,
+            (Self::Symlink(a), Self::Symlink(b)) => a.target == b.target,
+            (Self::Special(a), Self::Special(b)) => {
+                a.file_type == b.file_type && a.device == b.device
+            }
             _ => false,
+        };
+
+        same_contents
+            && self.mode() == other.mode()
+            && self.attributes() == other.attributes()
+            && self.owner() == other.owner()
+    }
+
+    pub fn set_modified(&mut self, time: SystemTime) {
+        match *self {
+            Self::File(ref mut file) => file.modified = time,
+            Self::Dir(ref mut dir) => dir.modified = time,
+            Self::Symlink(ref mut symlink) => symlink.modified = time,
+            Self::Special(ref mut special) => special.modified = time,
+        }
+    }
+
+    pub fn set_accessed(&mut self, time: SystemTime) {
+        match *self {
+            Self::File(ref mut file) => file.accessed = time,
+            Self::Dir(ref mut dir) => dir.accessed = time,
+            Self::Symlink(ref mut symlink) => symlink.accessed = time,
+            Self::Special(ref mut special) => special.accessed = time,
         }
     }
 }
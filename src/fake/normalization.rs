@@ -0,0 +1,50 @@
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+
+use unicode_normalization::UnicodeNormalization as Normalize;
+
+/// Whether a [`FakeFileSystem`] normalizes the Unicode in names, as set by
+/// [`FakeFileSystem::set_unicode_normalization`].
+///
+/// Names that are not valid Unicode are never normalized.
+///
+/// [`FakeFileSystem`]: struct.FakeFileSystem.html
+/// [`FakeFileSystem::set_unicode_normalization`]: struct.FakeFileSystem.html#method.set_unicode_normalization
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnicodeNormalization {
+    /// Names are kept byte for byte, so names that differ only in their normalization refer to
+    /// different nodes, as on Linux.
+    #[default]
+    Exact,
+    /// Names are stored in Normalization Form C, with characters composed where possible.
+    Nfc,
+    /// Names are stored in Normalization Form D, with characters decomposed, as on HFS+.
+    Nfd,
+    /// Names that differ only in their normalization refer to the same node, which keeps the name
+    /// it was created with, as on APFS.
+    InsensitivePreserving,
+}
+
+/// Returns `name` as it is stored with `normalization`.
+pub fn normalize(name: &OsStr, normalization: UnicodeNormalization) -> Cow<'_, OsStr> {
+    let unicode = match name.to_str() {
+        Some(unicode) => unicode,
+        None => return Cow::Borrowed(name),
+    };
+
+    match normalization {
+        UnicodeNormalization::Exact | UnicodeNormalization::InsensitivePreserving => {
+            Cow::Borrowed(name)
+        }
+        UnicodeNormalization::Nfc => Cow::Owned(OsString::from(unicode.nfc().collect::<String>())),
+        UnicodeNormalization::Nfd => Cow::Owned(OsString::from(unicode.nfd().collect::<String>())),
+    }
+}
+
+/// Returns `name` in a form in which names that `normalization` treats as the same are equal.
+pub fn fold(name: &OsStr, normalization: UnicodeNormalization) -> Cow<'_, OsStr> {
+    match (normalization, name.to_str()) {
+        (UnicodeNormalization::Exact, _) | (_, None) => Cow::Borrowed(name),
+        (_, Some(unicode)) => Cow::Owned(OsString::from(unicode.nfd().collect::<String>())),
+    }
+}
@@ -0,0 +1,227 @@
+use std::io::{ErrorKind, Read, Result, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+#[cfg(feature = "lock")]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+#[cfg(feature = "lock")]
+use std::thread;
+#[cfg(feature = "lock")]
+use std::time::Duration;
+
+use recover::Recover;
+#[cfg(feature = "lock")]
+use LockableFile;
+use {OpenFile, OpenOptions};
+
+use super::faults::Faults;
+use super::hooks::Hooks;
+use super::latency::Latency;
+use super::process::Locked;
+use super::registry::create_error;
+use super::Registry;
+
+/// Identifies each open file so that locks are held per handle, as they are for real files.
+#[cfg(feature = "lock")]
+static NEXT_HANDLE: AtomicU64 = AtomicU64::new(0);
+
+/// A handle to an open file in a [`FakeFileSystem`].
+///
+/// Every read and write goes directly to the underlying registry, so changes made through the
+/// handle are immediately visible to the file system, and vice versa.
+/// Unlike a handle to a real file, it refers to the file by its path, so once the file or one of
+/// its ancestors is renamed, reads and writes fail with `ErrorKind::NotFound`.
+///
+/// [`FakeFileSystem`]: struct.FakeFileSystem.html
+#[derive(Debug)]
+pub struct FakeOpenFile {
+    registry: Arc<RwLock<Registry>>,
+    hooks: Hooks,
+    latency: Latency,
+    faults: Faults,
+    path: PathBuf,
+    position: u64,
+    options: OpenOptions,
+    #[cfg(feature = "lock")]
+    handle: u64,
+}
+
+impl FakeOpenFile {
+    pub fn new(
+        registry: Arc<RwLock<Registry>>,
+        hooks: Hooks,
+        latency: Latency,
+        faults: Faults,
+        path: &Path,
+        options: &OpenOptions,
+    ) -> Self {
+        FakeOpenFile {
+            registry,
+            hooks,
+            latency,
+            faults,
+            path: path.to_path_buf(),
+            position: 0,
+            options: options.clone(),
+            #[cfg(feature = "lock")]
+            handle: NEXT_HANDLE.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    #[cfg(feature = "lock")]
+    fn lock(&self, exclusive: bool) -> Result<()> {
+        // Another handle can only release its lock from another thread, so poll until it does
+        loop {
+            match self.try_lock(exclusive) {
+                Err(ref err) if err.kind() == ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(1))
+                }
+                result => return result,
+            }
+        }
+    }
+
+    #[cfg(feature = "lock")]
+    fn try_lock(&self, exclusive: bool) -> Result<()> {
+        self.registry
+            .write()
+            .recover()
+            .try_lock(&self.path, self.handle, exclusive)
+    }
+
+    /// Returns the path of the file, with any symlinks resolved.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Read for FakeOpenFile {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if !self.options.is_read() {
+            return Err(create_error(ErrorKind::PermissionDenied));
+        }
+
+        let len = self.faults.read_len(buf.len());
+        let n =
+            self.registry
+                .read()
+                .recover()
+                .read_at(&self.path, self.position, &mut buf[..len])?;
+
+        self.position += n as u64;
+        self.latency.wait("read");
+        self.latency.transfer(n);
+
+        Ok(n)
+    }
+}
+
+impl Write for FakeOpenFile {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if !self.options.is_write() {
+            return Err(create_error(ErrorKind::PermissionDenied));
+        }
+
+        let buf = &buf[..self.faults.write_len(buf.len())];
+        let contents = self.hooks.write(&self.path, buf)?;
+        let mut registry = Locked::new(&self.registry, None);
+
+        if self.options.is_append() {
+            self.position = registry.file_len(&self.path)?;
+        }
+
+        let n = registry.write_at(&self.path, self.position, &contents)?;
+
+        drop(registry);
+
+        self.position += n as u64;
+        self.latency.wait("write");
+        self.latency.transfer(n);
+
+        // Hooks may change the length of what is written, but all of `buf` has been consumed
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for FakeOpenFile {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(n) => {
+                self.position = n;
+                return Ok(n);
+            }
+            SeekFrom::End(n) => (self.registry.read().recover().file_len(&self.path)?, n),
+            SeekFrom::Current(n) => (self.position, n),
+        };
+
+        let position = if offset >= 0 {
+            base.checked_add(offset as u64)
+        } else {
+            base.checked_sub(offset.unsigned_abs())
+        };
+
+        match position {
+            Some(n) => {
+                self.position = n;
+                Ok(n)
+            }
+            None => Err(create_error(ErrorKind::InvalidInput)),
+        }
+    }
+}
+
+impl OpenFile for FakeOpenFile {
+    fn set_len(&self, size: u64) -> Result<()> {
+        // Like `ftruncate`, this needs a handle opened for writing rather than write permission
+        if !self.options.is_write() {
+            return Err(create_error(ErrorKind::InvalidInput));
+        }
+
+        Locked::new(&self.registry, None).resize(&self.path, size)
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        self.registry.write().recover().sync_file(&self.path)
+    }
+}
+
+#[cfg(feature = "lock")]
+impl LockableFile for FakeOpenFile {
+    fn lock_shared(&self) -> Result<()> {
+        self.lock(false)
+    }
+
+    fn lock_exclusive(&self) -> Result<()> {
+        self.lock(true)
+    }
+
+    fn try_lock_shared(&self) -> Result<()> {
+        self.try_lock(false)
+    }
+
+    fn try_lock_exclusive(&self) -> Result<()> {
+        self.try_lock(true)
+    }
+
+    fn unlock(&self) -> Result<()> {
+        self.registry
+            .write()
+            .recover()
+            .unlock(&self.path, self.handle);
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "lock")]
+impl Drop for FakeOpenFile {
+    fn drop(&mut self) {
+        self.registry
+            .write()
+            .recover()
+            .unlock(&self.path, self.handle);
+    }
+}
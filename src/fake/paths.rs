@@ -0,0 +1,113 @@
+use std::ffi::OsStr;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use {FileType, Result};
+
+use super::names::Name;
+use super::node::{Children, Node};
+use super::windows;
+use super::FakeFileSystem;
+
+/// An iterator over the paths of the nodes under a directory of a [`FakeFileSystem`], along with
+/// their types, as returned by [`FakeFileSystem::all_paths`] and
+/// [`FakeFileSystem::all_paths_under`].
+///
+/// Each directory comes before its children, which are in order of name.
+/// The iterator walks the nodes as they were when it was created, unaffected by later changes to
+/// the file system.
+///
+/// [`FakeFileSystem`]: struct.FakeFileSystem.html
+/// [`FakeFileSystem::all_paths`]: struct.FakeFileSystem.html#method.all_paths
+/// [`FakeFileSystem::all_paths_under`]: struct.FakeFileSystem.html#method.all_paths_under
+#[derive(Debug)]
+pub struct AllPaths {
+    /// The directories being walked, innermost last, each with the name of the child that was
+    /// last returned from it.
+    stack: Vec<(PathBuf, Arc<Children>, Option<Name>)>,
+    windows: bool,
+}
+
+impl Iterator for AllPaths {
+    type Item = (PathBuf, FileType);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (path, file_type, grandchildren) = {
+                let (ref dir, ref children, ref mut last) = *self.stack.last_mut()?;
+                let lower = match *last {
+                    Some(ref name) => Bound::Excluded(&**name),
+                    None => Bound::Unbounded,
+                };
+
+                match children.range::<OsStr, _>((lower, Bound::Unbounded)).next() {
+                    Some((name, node)) => {
+                        let grandchildren = match *node {
+                            Node::Dir(ref dir) => Some(dir.children.clone()),
+                            _ => None,
+                        };
+
+                        *last = Some(name.clone());
+                        (dir.join(&**name), node.file_type(), grandchildren)
+                    }
+                    None => {
+                        self.stack.pop();
+                        continue;
+                    }
+                }
+            };
+
+            if let Some(grandchildren) = grandchildren {
+                self.stack.push((path.clone(), grandchildren, None));
+            }
+
+            let path = if self.windows {
+                windows::from_registry(&path)
+            } else {
+                path
+            };
+
+            return Some((path, file_type));
+        }
+    }
+}
+
+impl FakeFileSystem {
+    /// Returns every path in the file system, apart from the root, along with the type of its
+    /// node, such as to check that the code under test created nothing it wasn't meant to.
+    ///
+    /// The paths are absolute, and are Windows paths if the file system uses those.
+    /// Creating the iterator takes constant time, since it shares the nodes with the file system
+    /// until either changes them, and walks them lazily.
+    pub fn all_paths(&self) -> AllPaths {
+        let registry = self.read();
+        let root = PathBuf::from("/");
+        let children = registry.shared_children(&root).unwrap_or_default();
+
+        AllPaths {
+            stack: vec![(root, children, None)],
+            windows: registry.windows_paths(),
+        }
+    }
+
+    /// Returns every path under the directory at `path`, apart from `path` itself, along with
+    /// the type of its node, like [`all_paths`] does for the whole file system.
+    ///
+    /// The paths start with `path`, made absolute, even if it leads through a symlink.
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * `path` is not a directory.
+    ///
+    /// [`all_paths`]: #method.all_paths
+    pub fn all_paths_under<P: AsRef<Path>>(&self, path: P) -> Result<AllPaths> {
+        self.apply(path.as_ref(), |registry, path| {
+            Ok(AllPaths {
+                stack: vec![(path.to_path_buf(), registry.shared_children(path)?, None)],
+                windows: registry.windows_paths(),
+            })
+        })
+    }
+}
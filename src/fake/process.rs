@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+use std::io::Result;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::thread::{self, ThreadId};
+
+use recover::Recover;
+
+use super::{FakeFileSystem, Registry};
+
+/// The state of a simulated process using a `FakeFileSystem`, shared between clones of its
+/// handle.
+#[derive(Clone, Debug)]
+pub struct Process(Arc<Mutex<State>>);
+
+#[derive(Clone, Debug)]
+struct State {
+    cwd: PathBuf,
+    /// The current directories of the threads that have changed theirs, if each thread has its
+    /// own, in which case `cwd` is where the others start.
+    thread_cwds: Option<HashMap<ThreadId, PathBuf>>,
+    user: Option<(u32, u32)>,
+    umask: Option<u32>,
+}
+
+impl Process {
+    /// Returns the process's current directory, or the calling thread's if each has its own.
+    pub fn cwd(&self, registry: &Registry) -> Result<PathBuf> {
+        let state = self.0.lock().recover();
+        let cwd = state
+            .thread_cwds
+            .as_ref()
+            .and_then(|cwds| cwds.get(&thread::current().id()))
+            .unwrap_or(&state.cwd);
+
+        registry.resolve_dir(cwd)
+    }
+
+    /// Changes the process's current directory, or the calling thread's if each has its own, to
+    /// the directory at `path`.
+    pub fn set_cwd(&self, registry: &Registry, path: &Path) -> Result<()> {
+        let cwd = registry.resolve_dir(path)?;
+        let mut state = self.0.lock().recover();
+
+        match state.thread_cwds {
+            Some(ref mut cwds) => {
+                cwds.insert(thread::current().id(), cwd);
+            }
+            None => state.cwd = cwd,
+        }
+
+        Ok(())
+    }
+
+    /// Changes the user that the process runs as, returning `false` if it runs as the shared
+    /// user instead.
+    pub fn set_user(&self, uid: u32, gid: u32) -> bool {
+        let mut state = self.0.lock().recover();
+
+        if state.user.is_none() {
+            return false;
+        }
+
+        state.user = Some((uid, gid));
+        true
+    }
+
+    /// Returns the user that the process runs as, if it has its own.
+    fn user(&self) -> Option<(u32, u32)> {
+        self.0.lock().recover().user
+    }
+
+    /// Changes the umask of the process, returning `false` if it uses the shared umask instead.
+    pub fn set_umask(&self, umask: u32) -> bool {
+        let mut state = self.0.lock().recover();
+
+        if state.umask.is_none() {
+            return false;
+        }
+
+        state.umask = Some(umask);
+        true
+    }
+
+    /// Returns the umask of the process, if it has its own.
+    fn umask(&self) -> Option<u32> {
+        self.0.lock().recover().umask
+    }
+
+    /// Returns an independent copy of the process.
+    pub fn snapshot(&self) -> Self {
+        Process(Arc::new(Mutex::new(self.0.lock().recover().clone())))
+    }
+}
+
+/// An exclusive lock on the registry that runs operations as the user and with the umask of a
+/// process, if it has its own, and restores the shared ones once released.
+pub struct Locked<'a> {
+    registry: RwLockWriteGuard<'a, Registry>,
+    shared_user: Option<(u32, u32)>,
+    shared_umask: Option<u32>,
+}
+
+impl<'a> Locked<'a> {
+    pub fn new(registry: &'a RwLock<Registry>, process: Option<&Process>) -> Self {
+        let mut registry = registry.write().recover();
+        let user = process.and_then(Process::user);
+        let shared_user = user.map(|(uid, gid)| {
+            let shared = registry.current_user();
+
+            registry.set_current_user(uid, gid);
+            shared
+        });
+        let umask = process.and_then(Process::umask);
+        let shared_umask = umask.map(|umask| {
+            let shared = registry.umask();
+
+            registry.set_umask(umask);
+            shared
+        });
+
+        Locked {
+            registry,
+            shared_user,
+            shared_umask,
+        }
+    }
+}
+
+impl<'a> Deref for Locked<'a> {
+    type Target = Registry;
+
+    fn deref(&self) -> &Registry {
+        &self.registry
+    }
+}
+
+impl<'a> DerefMut for Locked<'a> {
+    fn deref_mut(&mut self) -> &mut Registry {
+        &mut self.registry
+    }
+}
+
+impl<'a> Drop for Locked<'a> {
+    fn drop(&mut self) {
+        if let Some((uid, gid)) = self.shared_user {
+            self.registry.set_current_user(uid, gid);
+        }
+
+        if let Some(umask) = self.shared_umask {
+            self.registry.set_umask(umask);
+        }
+
+        // A persistent registry is written out once the operation holding the lock completes,
+        // while failures are left for `FakeFileSystem::persist` to report
+        #[cfg(all(feature = "serde", feature = "std-os"))]
+        let _ = self.registry.save();
+    }
+}
+
+/// A lock on the registry for operations that only read it, which is shared with other readers
+/// unless the process runs as its own user, since that user is swapped into the registry.
+pub enum ReadLocked<'a> {
+    Shared(RwLockReadGuard<'a, Registry>),
+    Exclusive(Locked<'a>),
+}
+
+impl<'a> ReadLocked<'a> {
+    pub fn new(registry: &'a RwLock<Registry>, process: Option<&Process>) -> Self {
+        match process {
+            Some(process) if process.user().is_some() => {
+                ReadLocked::Exclusive(Locked::new(registry, Some(process)))
+            }
+            _ => ReadLocked::Shared(registry.read().recover()),
+        }
+    }
+}
+
+impl<'a> Deref for ReadLocked<'a> {
+    type Target = Registry;
+
+    fn deref(&self) -> &Registry {
+        match *self {
+            ReadLocked::Shared(ref registry) => registry,
+            ReadLocked::Exclusive(ref registry) => registry,
+        }
+    }
+}
+
+impl FakeFileSystem {
+    /// Gives this handle its own current directory, starting from the shared one, so that
+    /// [`set_current_dir`] through it does not affect other handles, and vice versa.
+    /// Clones made afterwards share the same current directory, along with the same contents.
+    /// A handle returned by [`process`] keeps running as its user, with its umask.
+    ///
+    /// [`set_current_dir`]: trait.FileSystem.html#tymethod.set_current_dir
+    /// [`process`]: #method.process
+    pub fn with_isolated_cwd(mut self) -> Self {
+        let cwd = self
+            .cwd(&self.read())
+            .unwrap_or_else(|_| PathBuf::from("/"));
+
+        let user = self.process.as_ref().and_then(Process::user);
+        let umask = self.process.as_ref().and_then(Process::umask);
+
+        self.process = Some(Process(Arc::new(Mutex::new(State {
+            cwd,
+            thread_cwds: None,
+            user,
+            umask,
+        }))));
+        self
+    }
+
+    /// Gives each thread its own current directory when using this handle, starting from the
+    /// handle's current one, so that [`set_current_dir`] in one thread does not affect the
+    /// others, such as tests that run in parallel over one fixture.
+    /// Clones made afterwards share the same current directory for each thread, along with the
+    /// same contents, while other handles keep theirs.
+    ///
+    /// The user and umask of the handle are kept, if it has its own.
+    /// The current directories of threads that have finished are kept until the handle and its
+    /// clones are dropped.
+    ///
+    /// [`set_current_dir`]: trait.FileSystem.html#tymethod.set_current_dir
+    pub fn with_thread_local_cwd(mut self) -> Self {
+        let cwd = self
+            .cwd(&self.read())
+            .unwrap_or_else(|_| PathBuf::from("/"));
+        let user = self.process.as_ref().and_then(Process::user);
+        let umask = self.process.as_ref().and_then(Process::umask);
+
+        self.process = Some(Process(Arc::new(Mutex::new(State {
+            cwd,
+            thread_cwds: Some(HashMap::new()),
+            user,
+            umask,
+        }))));
+        self
+    }
+
+    /// Returns a handle to the same contents for a simulated process that runs as the user with
+    /// the given user and group IDs, starting in the directory `cwd`.
+    /// The process's current directory, user and umask are its own, starting from the shared
+    /// umask, so changing them through [`set_current_dir`], [`set_current_user`] or
+    /// [`set_umask`] does not affect other handles, and vice versa.
+    /// Clones of the handle belong to the same process.
+    ///
+    /// This can model cooperating processes, such as a writer and a reader, over one file
+    /// system.
+    ///
+    /// # Errors
+    ///
+    /// * `cwd` does not exist.
+    /// * `cwd` is not a directory.
+    ///
+    /// [`set_current_dir`]: trait.FileSystem.html#tymethod.set_current_dir
+    /// [`set_current_user`]: #method.set_current_user
+    /// [`set_umask`]: #method.set_umask
+    pub fn process<P: AsRef<Path>>(&self, uid: u32, gid: u32, cwd: P) -> Result<Self> {
+        let cwd = self.apply(cwd.as_ref(), |r, p| r.resolve_dir(p))?;
+        let mut fs = self.clone();
+        let user = Some((uid, gid));
+        let umask = Some(self.registry.read().recover().umask());
+
+        fs.process = Some(Process(Arc::new(Mutex::new(State {
+            cwd,
+            thread_cwds: None,
+            user,
+            umask,
+        }))));
+        Ok(fs)
+    }
+}
@@ -1,349 +1,2048 @@
+use std::borrow::Cow;
+#[cfg(feature = "lock")]
 use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsStr;
 use std::io::{Error, ErrorKind, Result};
-use std::path::{Path, PathBuf};
+use std::mem;
+use std::path::{Component, Path, PathBuf};
+use std::ptr;
+#[cfg(feature = "watch")]
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::SystemTime;
 
-use super::node::{Dir, File, Node};
+#[cfg(windows)]
+use winapi::um::winnt::{
+    FILE_ATTRIBUTE_DIRECTORY, FILE_ATTRIBUTE_READONLY, FILE_ATTRIBUTE_REPARSE_POINT,
+};
 
+use super::case::{child_key, CaseSensitivity};
+use super::clock;
+use super::contents::Contents;
+use super::crash;
+use super::journal::{self, Journal, Operation, OperationKind};
+use super::limits::PathLimits;
+use super::memory::{self, Footprint};
+use super::names::Names;
+use super::node::{Children, Dir, File, Node, Special, Symlink, FILE_ATTRIBUTE_HIDDEN};
+use super::normalization::{normalize, UnicodeNormalization};
+#[cfg(all(feature = "serde", feature = "std-os"))]
+use super::snapshot;
+use super::windows;
+use super::Metadata;
+use error;
+#[cfg(all(feature = "serde", feature = "std-os"))]
+use os;
+use read_dir;
+#[cfg(unix)]
+use FileType;
+#[cfg(feature = "watch")]
+use FsEvent;
+use OpenOptions;
+
+/// The maximum number of symlinks followed while resolving a single path, unless set otherwise.
+/// This matches Linux's `MAXSYMLINKS`.
+const DEFAULT_SYMLINK_DEPTH: usize = 40;
+
+/// The user and group IDs that the fake's operations run as, and that own the nodes it creates.
+const DEFAULT_UID: u32 = 1000;
+const DEFAULT_GID: u32 = 1000;
+
+/// The mode bits cleared from new nodes, which is the usual default on the OS.
+const DEFAULT_UMASK: u32 = 0o022;
+
+/// The most bytes that a file can hold, as many as a `Vec` can.
+/// Writing past this, or extending a file beyond it, fails with `ErrorKind::FileTooLarge`, as it
+/// does on the OS.
+const MAX_FILE_LEN: u64 = isize::MAX as u64;
+
+/// The mode bits that new files and directories start with, before the umask is applied.
+const FILE_MODE: u32 = 0o666;
+const DIR_MODE: u32 = 0o777;
+
+/// The mode bits that grant read, write, and search access to a single class of user.
+pub const READ: u32 = 0o4;
+pub const WRITE: u32 = 0o2;
+const SEARCH: u32 = 0o1;
+
+/// Access that is granted or denied to a path regardless of the mode of the node there, as
+/// bits like `READ` and `WRITE`.
 #[derive(Debug, Clone, Default)]
+struct AccessRule {
+    allow: u32,
+    deny: u32,
+}
+
+/// An advisory lock on a file, held by one or more open file handles.
+#[cfg(feature = "lock")]
+#[derive(Debug, Clone)]
+enum Lock {
+    Shared(Vec<u64>),
+    Exclusive(u64),
+}
+
+/// A receiver of events for changes to `path`, its children, and its other descendants if
+/// `recursive` is set.
+#[cfg(feature = "watch")]
+#[derive(Debug, Clone)]
+struct Watcher {
+    path: PathBuf,
+    recursive: bool,
+    sender: Sender<FsEvent>,
+}
+
+#[cfg(feature = "watch")]
+impl Watcher {
+    fn covers(&self, path: &Path) -> bool {
+        if self.recursive {
+            path.starts_with(&self.path)
+        } else {
+            path == self.path || path.parent() == Some(&self.path)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Registry {
     cwd: PathBuf,
-    files: HashMap<PathBuf, Node>,
+    root: Node,
+    capacity: Option<u64>,
+    uid: u32,
+    gid: u32,
+    umask: u32,
+    strict: bool,
+    case: CaseSensitivity,
+    normalization: UnicodeNormalization,
+    windows: bool,
+    crash_simulation: bool,
+    journal: Option<Journal>,
+    limits: PathLimits,
+    max_symlink_depth: usize,
+    /// The length of the contents of every file, and a running estimate of the memory used by
+    /// the nodes, kept up to date as they change so that limits can be checked without walking
+    /// the tree.
+    footprint: Footprint,
+    /// The memory limit, converted when it was set into a limit on `footprint.memory`.
+    memory_limit: Option<usize>,
+    names: Names,
+    devices: BTreeSet<PathBuf>,
+    access_rules: BTreeMap<PathBuf, AccessRule>,
+    #[cfg(feature = "lock")]
+    locks: HashMap<PathBuf, Lock>,
+    #[cfg(feature = "watch")]
+    watchers: Vec<Watcher>,
+    /// Events held back until the operation that caused them completes, if one is running.
+    #[cfg(feature = "watch")]
+    pending: Option<Vec<FsEvent>>,
+    /// The file that the nodes are written to after each change, if the registry is persistent.
+    #[cfg(all(feature = "serde", feature = "std-os"))]
+    backing: Option<PathBuf>,
+    /// Whether the nodes have changed since they were last written to the backing file.
+    #[cfg(all(feature = "serde", feature = "std-os"))]
+    unsaved: bool,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl Registry {
-    pub fn new() -> Self {
-        let cwd = PathBuf::from("/");
-        let mut files = HashMap::new();
+impl Registry {
+    pub fn new() -> Self {
+        let mut root = Node::Dir(Dir::new());
+
+        root.set_owner(DEFAULT_UID, DEFAULT_GID);
+
+        let footprint = memory::footprint(OsStr::new(""), &root);
+
+        Registry {
+            cwd: PathBuf::from("/"),
+            root,
+            capacity: None,
+            uid: DEFAULT_UID,
+            gid: DEFAULT_GID,
+            umask: DEFAULT_UMASK,
+            strict: false,
+            case: CaseSensitivity::Sensitive,
+            normalization: UnicodeNormalization::Exact,
+            windows: false,
+            crash_simulation: false,
+            journal: None,
+            limits: PathLimits::new(),
+            max_symlink_depth: DEFAULT_SYMLINK_DEPTH,
+            footprint,
+            memory_limit: None,
+            names: Names::default(),
+            devices: BTreeSet::new(),
+            access_rules: BTreeMap::new(),
+            #[cfg(feature = "lock")]
+            locks: HashMap::new(),
+            #[cfg(feature = "watch")]
+            watchers: Vec::new(),
+            #[cfg(feature = "watch")]
+            pending: None,
+            #[cfg(all(feature = "serde", feature = "std-os"))]
+            backing: None,
+            #[cfg(all(feature = "serde", feature = "std-os"))]
+            unsaved: false,
+        }
+    }
+
+    /// Returns an independent copy of every node and setting, without any locks held by open
+    /// files or watchers.
+    pub fn snapshot(&self) -> Self {
+        Registry {
+            #[cfg(feature = "lock")]
+            locks: HashMap::new(),
+            #[cfg(feature = "watch")]
+            watchers: Vec::new(),
+            #[cfg(all(feature = "serde", feature = "std-os"))]
+            backing: None,
+            ..self.clone()
+        }
+    }
+
+    pub fn set_capacity(&mut self, capacity: u64) {
+        self.capacity = Some(capacity);
+    }
+
+    pub fn set_current_user(&mut self, uid: u32, gid: u32) {
+        self.uid = uid;
+        self.gid = gid;
+    }
+
+    pub fn umask(&self) -> u32 {
+        self.umask
+    }
+
+    pub fn set_umask(&mut self, umask: u32) {
+        self.umask = umask;
+    }
+
+    pub fn set_strict_permissions(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn set_case_sensitivity(&mut self, case: CaseSensitivity) {
+        self.case = case;
+    }
+
+    pub fn set_unicode_normalization(&mut self, normalization: UnicodeNormalization) {
+        self.normalization = normalization;
+    }
+
+    pub fn set_path_limits(&mut self, limits: PathLimits) {
+        self.limits = limits;
+    }
+
+    pub fn set_max_symlink_depth(&mut self, depth: usize) {
+        self.max_symlink_depth = depth;
+    }
+
+    /// Limits the memory used by the nodes to `limit` bytes, as measured by [`memory_usage`]
+    /// now, and by the running footprint of the changes made from then on.
+    ///
+    /// [`memory_usage`]: #method.memory_usage
+    pub fn set_memory_limit(&mut self, limit: usize) {
+        let limit = limit.saturating_add(self.footprint.memory);
+
+        self.memory_limit = Some(limit.saturating_sub(self.memory_usage()));
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        memory::usage(&self.root)
+    }
+
+    /// Starts or stops keeping track of what a simulated crash would lose, starting with
+    /// everything as it is now having been synced.
+    pub fn set_crash_simulation(&mut self, enabled: bool) {
+        self.crash_simulation = enabled;
+
+        if enabled {
+            crash::mark_synced(&mut self.root);
+        }
+    }
+
+    /// Reverts every file and directory to how it was when last synced, and releases every lock,
+    /// as after the OS crashes.
+    pub fn simulate_crash(&mut self) {
+        if !self.crash_simulation {
+            return;
+        }
+
+        let root = mem::replace(&mut self.root, Node::Dir(Dir::new()));
+
+        self.root = crash::crashed(root);
+        self.footprint = memory::footprint(OsStr::new(""), &self.root);
+        self.changed();
+
+        #[cfg(feature = "lock")]
+        self.locks.clear();
+    }
+
+    /// Makes the registry persistent, writing its nodes to the file at `path` now and after each
+    /// change from then on.
+    #[cfg(all(feature = "serde", feature = "std-os"))]
+    pub fn set_backing(&mut self, path: PathBuf) -> Result<()> {
+        self.backing = Some(path);
+        self.unsaved = true;
+        self.save()
+    }
+
+    /// Writes the nodes to the backing file if they have changed since they last were, leaving
+    /// them marked as unsaved if that fails.
+    #[cfg(all(feature = "serde", feature = "std-os"))]
+    pub fn save(&mut self) -> Result<()> {
+        if let (true, Some(path)) = (self.unsaved, self.backing.as_ref()) {
+            os::write_file_atomic(path, snapshot::to_string(self)?.as_bytes())?;
+            self.unsaved = false;
+        }
+
+        Ok(())
+    }
+
+    /// Starts or stops logging operations, forgetting those logged so far when stopping.
+    pub fn set_operation_logging(&mut self, enabled: bool) {
+        match (enabled, self.journal.is_some()) {
+            (true, false) => self.journal = Some(Journal::default()),
+            (false, true) => self.journal = None,
+            _ => {}
+        }
+    }
+
+    pub fn operation_log(&self) -> Vec<Operation> {
+        self.journal
+            .as_ref()
+            .map(|journal| journal.operations().to_vec())
+            .unwrap_or_default()
+    }
+
+    pub fn clear_operation_log(&mut self) {
+        if let Some(ref mut journal) = self.journal {
+            journal.clear();
+        }
+    }
+
+    /// Performs the logged operation `kind`, whose paths are absolute paths in the registry,
+    /// checking only that it can write to the directories and files whose entries or contents
+    /// it changes.
+    pub fn replay(&mut self, kind: &OperationKind) -> Result<()> {
+        match *kind {
+            OperationKind::CreateDir { ref path, mode } => {
+                let mut dir = self.owned(Node::Dir(Dir::new()));
+
+                dir.set_mode(mode);
+                return self.insert(path.clone(), dir);
+            }
+            OperationKind::CreateFile {
+                ref path,
+                mode,
+                ref contents,
+            } => {
+                let mut file = self.owned(Node::File(File::new(contents.clone())));
+
+                file.set_mode(mode);
+                return self.insert(path.clone(), file);
+            }
+            OperationKind::CreateSymlink {
+                ref path,
+                ref target,
+            } => {
+                let symlink = self.owned(Node::Symlink(Symlink::new(target.clone())));
+
+                return self.insert(path.clone(), symlink);
+            }
+            OperationKind::CreateSpecial {
+                ref path,
+                file_type,
+                mode,
+                device,
+            } => {
+                let mut special = self.owned(Node::Special(Special::new(file_type, device)));
+
+                special.set_mode(mode);
+                return self.insert(path.clone(), special);
+            }
+            OperationKind::Remove { ref path } => return self.remove(path).and(Ok(())),
+            OperationKind::Rename { ref from, ref to } => return self.rename(from, to),
+            OperationKind::WriteAt {
+                ref path,
+                position,
+                ref buf,
+            } => return self.write_at(path, position, buf).and(Ok(())),
+            OperationKind::SetLen { ref path, len } => return self.resize(path, len),
+            OperationKind::SyncFile { ref path } => return self.sync_file(path),
+            OperationKind::SyncDir { ref path } => return self.sync_dir(path),
+            // The rest change a node in place, without logging themselves
+            OperationKind::Write {
+                ref path,
+                ref contents,
+            } => {
+                let len = self.file_len(path)?;
+
+                self.ensure_space(len, contents.len() as u64)?;
+                self.modify(path, |node| {
+                    if let Node::File(ref mut file) = *node {
+                        file.set_contents(contents.clone());
+                    }
+                })?;
+                self.resized(len as usize, contents.len());
+            }
+            OperationKind::SetMode { ref path, mode } => {
+                self.modify(path, |node| node.set_mode(mode))?
+            }
+            OperationKind::SetOwner { ref path, uid, gid } => {
+                self.modify(path, |node| node.set_owner(uid, gid))?
+            }
+            OperationKind::SetModified { ref path, time } => {
+                self.modify(path, |node| node.set_modified(time))?
+            }
+            OperationKind::SetAccessed { ref path, time } => {
+                self.modify(path, |node| node.set_accessed(time))?
+            }
+            OperationKind::SetAttributes {
+                ref path,
+                attributes,
+            } => self.modify(path, |node| node.set_attributes(attributes))?,
+        }
+
+        self.log(|r| journal::map_paths(kind, |path| r.user_path(path)));
+
+        Ok(())
+    }
+
+    /// Switches to Windows paths, ignoring the case of names and starting in the root of drive
+    /// `C:`.
+    pub fn set_windows_paths(&mut self) -> Result<()> {
+        self.add_drive('C')?;
+        self.windows = true;
+        self.case = CaseSensitivity::InsensitivePreserving;
+        self.cwd = windows::drive_root('C');
+
+        Ok(())
+    }
+
+    /// Determines whether paths given to the file system are Windows paths.
+    pub fn windows_paths(&self) -> bool {
+        self.windows
+    }
+
+    /// Adds an empty root directory for `drive`, without checking permissions.
+    pub fn add_drive(&mut self, drive: char) -> Result<()> {
+        if !drive.is_ascii_alphabetic() {
+            return Err(create_error(ErrorKind::InvalidInput));
+        }
+
+        let path = windows::drive_root(drive);
+
+        if self.get(&path).is_ok() {
+            return Err(create_error(ErrorKind::AlreadyExists));
+        }
+
+        let dir = self.owned(Node::Dir(Dir::new()));
+        let name = self.names.intern(path.file_name().unwrap_or_default());
+
+        self.footprint.add(memory::footprint(&name, &dir));
+
+        if let Node::Dir(ref mut root) = self.root {
+            if self.crash_simulation {
+                crash::preserve(root);
+            }
+
+            root.children_mut().insert(name, dir);
+        }
+
+        Ok(())
+    }
+
+    /// Makes the directory at `path` the root of a separate device, so that nodes can't be
+    /// renamed into or out of it.
+    pub fn add_device(&mut self, path: &Path) -> Result<()> {
+        let path = self.resolve_dir(path)?;
+
+        self.devices.insert(path);
+
+        Ok(())
+    }
+
+    /// Grants or denies `access` to whatever node is at `path`, regardless of its mode.
+    pub fn set_access_rule(&mut self, path: &Path, access: u32, allowed: bool) -> Result<()> {
+        let path = self.resolve_path(path)?;
+        let rule = self.access_rules.entry(path).or_default();
+
+        if allowed {
+            rule.allow |= access;
+            rule.deny &= !access;
+        } else {
+            rule.deny |= access;
+            rule.allow &= !access;
+        }
+
+        Ok(())
+    }
+
+    pub fn clear_access_rules(&mut self, path: &Path) -> Result<()> {
+        let path = self.resolve_path(path)?;
+
+        self.access_rules.remove(&path);
+
+        Ok(())
+    }
+
+    /// Returns the root of the device that the resolved `path` is on, or `None` for the device
+    /// of `/`.
+    fn device<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+        path.ancestors()
+            .find(|ancestor| self.devices.contains(*ancestor))
+    }
+
+    /// Fails if the resolved `path` is the root of a device or contains one, like removing a
+    /// mount point does.
+    fn check_not_busy(&self, path: &Path) -> Result<()> {
+        if self.devices.iter().any(|device| device.starts_with(path)) {
+            return Err(create_error(ErrorKind::ResourceBusy));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the absolute `path` as users of the file system see it, which is as a Windows
+    /// path if they use those.
+    pub fn user_path(&self, path: &Path) -> PathBuf {
+        if self.windows {
+            windows::from_registry(path)
+        } else {
+            path.to_path_buf()
+        }
+    }
+
+    /// Joins `name` onto `path`, which users of the file system gave, with their separator.
+    pub fn join(&self, path: &Path, name: &OsStr) -> PathBuf {
+        if self.windows {
+            windows::join(path, name)
+        } else {
+            path.join(name)
+        }
+    }
+
+    /// Returns the user and group IDs that operations run as.
+    pub fn current_user(&self) -> (u32, u32) {
+        (self.uid, self.gid)
+    }
+
+    /// Returns every node by its absolute path.
+    pub fn nodes(&self) -> BTreeMap<PathBuf, &Node> {
+        let root = PathBuf::from("/");
+        let mut nodes = BTreeMap::new();
+
+        visit(&self.root, &root, &mut |path, node| {
+            nodes.insert(path, node);
+        });
+        nodes.insert(root, &self.root);
+
+        nodes
+    }
+
+    /// Returns the node at `path`, following symlinks, without checking permissions.
+    pub fn node(&self, path: &Path) -> Result<&Node> {
+        self.get(&self.resolve_path(path)?)
+    }
+
+    /// Returns the children of the directory at `path`, following symlinks, without checking
+    /// permissions.
+    /// They are shared with the directory until either changes, so this takes constant time.
+    pub fn shared_children(&self, path: &Path) -> Result<Arc<Children>> {
+        match *self.node(path)? {
+            Node::Dir(ref dir) => Ok(dir.children.clone()),
+            _ => Err(create_error(ErrorKind::NotADirectory)),
+        }
+    }
+
+    /// Places `node` at the absolute `path`, replacing any node that is already there, without
+    /// checking permissions.
+    /// Missing ancestors are created as directories owned by the current user.
+    #[cfg(any(feature = "serde", feature = "tar"))]
+    pub fn restore(&mut self, path: PathBuf, mut node: Node) -> Result<()> {
+        let mut ancestors: Vec<&Path> = path.ancestors().skip(1).collect();
+
+        ancestors.reverse();
+
+        for ancestor in ancestors {
+            match self.get(ancestor) {
+                Ok(node) if !node.is_dir() => return Err(create_error(ErrorKind::NotADirectory)),
+                Ok(_) => {}
+                Err(_) => {
+                    let dir = self.owned(Node::Dir(Dir::new()));
+
+                    self.attach(ancestor, dir);
+                }
+            }
+        }
+
+        // A directory that replaces another keeps its children, which are counted again once
+        // it is attached
+        let mut moved = Footprint::default();
+
+        if let (Ok(Node::Dir(ref mut old)), Node::Dir(ref mut new)) =
+            (self.get_mut(&path), &mut node)
+        {
+            new.children = mem::take(&mut old.children);
+            moved = memory::children_footprint(&new.children);
+        }
+
+        self.footprint.sub(moved);
+
+        self.attach(&path, node);
+
+        Ok(())
+    }
+
+    pub fn current_dir(&self) -> Result<PathBuf> {
+        self.get_dir(&self.cwd).map(|_| self.cwd.clone())
+    }
+
+    pub fn set_current_dir(&mut self, cwd: PathBuf) -> Result<()> {
+        self.cwd = self.resolve_dir(&cwd)?;
+
+        Ok(())
+    }
+
+    /// Returns the resolved path of the directory at `path`.
+    pub fn resolve_dir(&self, path: &Path) -> Result<PathBuf> {
+        let path = self.resolve_path(path)?;
+
+        self.get_dir(&path)?;
+        self.check_search(&path)?;
+
+        Ok(path)
+    }
+
+    pub fn metadata(&self, path: &Path) -> Result<Metadata> {
+        let path = self.resolve_path(path)?;
+
+        self.get(&path).map(Metadata::from)
+    }
+
+    pub fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        let path = self.resolve_parent(path)?;
+
+        self.get(&path).map(Metadata::from)
+    }
+
+    pub fn canonicalize(&self, path: &Path) -> Result<PathBuf> {
+        let path = self.resolve_path(path)?;
+
+        self.get(&path).map(|_| path)
+    }
+
+    pub fn is_dir(&self, path: &Path) -> bool {
+        self.resolve_path(path)
+            .and_then(|p| self.get(&p).map(Node::is_dir))
+            .unwrap_or(false)
+    }
+
+    pub fn is_file(&self, path: &Path) -> bool {
+        self.resolve_path(path)
+            .and_then(|p| self.get(&p).map(Node::is_file))
+            .unwrap_or(false)
+    }
+
+    pub fn exists(&self, path: &Path) -> bool {
+        self.try_exists(path).unwrap_or(false)
+    }
+
+    pub fn try_exists(&self, path: &Path) -> Result<bool> {
+        let path = self.resolve_path(path)?;
+
+        // Directories without execute bits still default to being searchable, so only those that
+        // can be neither read nor searched block traversal
+        let blocked = path
+            .ancestors()
+            .skip(1)
+            .any(|ancestor| match self.get(ancestor) {
+                Ok(Node::Dir(ref dir)) => dir.mode & 0o555 == 0,
+                _ => false,
+            });
+
+        if blocked {
+            return Err(create_error(ErrorKind::PermissionDenied));
+        }
+
+        match self.get(&path) {
+            Ok(_) => Ok(true),
+            Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn create_dir(&mut self, path: &Path) -> Result<()> {
+        self.create_dir_with_mode(path, DIR_MODE)
+    }
+
+    pub fn create_dir_with_mode(&mut self, path: &Path, mode: u32) -> Result<()> {
+        let path = self.resolve_parent(path)?;
+
+        let dir = self.created(Node::Dir(Dir::new()), mode);
+
+        self.insert(path, dir)
+    }
+
+    pub fn create_dir_all(&mut self, path: &Path) -> Result<()> {
+        // Based on std::fs::DirBuilder::create_dir_all
+        if path == Path::new("") {
+            return Ok(());
+        }
+
+        match self.create_dir(path) {
+            Ok(_) => return Ok(()),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => {}
+            Err(_) if self.is_dir(path) => return Ok(()),
+            Err(e) => return Err(e),
+        }
+
+        match path.parent() {
+            Some(p) => self.create_dir_all(p)?,
+            None => return Err(create_error(ErrorKind::Other)),
+        }
+
+        self.create_dir_all(path)
+    }
+
+    pub fn remove_dir(&mut self, path: &Path) -> Result<()> {
+        let path = self.resolve_parent(path)?;
+
+        self.check_not_busy(&path)?;
+
+        match self.get_dir(&path) {
+            Ok(dir) if dir.children.is_empty() => {}
+            Ok(_) => return Err(create_error(ErrorKind::DirectoryNotEmpty)),
+            Err(e) => return Err(e),
+        };
+
+        self.remove(&path).and(Ok(()))
+    }
+
+    /// Removes the directory at `path` along with its descendants, calling `step` with the
+    /// absolute path of each node, as users see it, before it is removed.
+    /// If `step` or a removal fails, every node is put back.
+    pub fn remove_dir_all<F>(&mut self, path: &Path, mut step: F) -> Result<()>
+    where
+        F: FnMut(&Path) -> Result<()>,
+    {
+        let path = self.resolve_parent(path)?;
+
+        // Like std::fs::remove_dir_all, remove a symlink rather than its target
+        if self.get(&path)?.is_symlink() {
+            return self.remove(&path).and(Ok(()));
+        }
+
+        self.get_dir_mut(&path)?;
+        self.check_not_busy(&path)?;
+
+        let descendants = self.descendants(&path);
+
+        for child in &descendants {
+            self.check_access(child, READ)?;
+        }
+
+        self.atomically(|r| {
+            // Children come after their parents, so remove them first
+            for child in descendants.iter().rev().chain(Some(&path)) {
+                step(&r.user_path(child))?;
+                r.remove(child)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Returns the absolute paths of the children of the directory at `path`, sorted by name.
+    pub fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let path = self.resolve_path(path)?;
+
+        self.get_dir(&path)?;
+
+        Ok(self.children(&path))
+    }
+
+    /// Returns the total length of the files under the directory at `path`, counting the targets
+    /// of symlinks if `follow` is set.
+    pub fn dir_size(&self, path: &Path, follow: bool) -> Result<u64> {
+        let path = self.resolve_path(path)?;
+
+        self.get_dir(&path)?;
+        self.size_of(&path, follow, &mut vec![])
+    }
+
+    pub fn create_file(&mut self, path: &Path, buf: &[u8]) -> Result<()> {
+        self.create_file_with_mode(path, buf, FILE_MODE)
+    }
+
+    pub fn create_file_with_mode(&mut self, path: &Path, buf: &[u8], mode: u32) -> Result<()> {
+        let path = self.resolve_parent(path)?;
+
+        self.ensure_space(0, buf.len() as u64)?;
+
+        let file = self.created(Node::File(File::new(buf.to_vec())), mode);
+
+        self.insert(path, file)
+    }
+
+    /// Creates a FIFO, socket, or device, which only root may do for devices.
+    #[cfg(unix)]
+    pub fn mknod(
+        &mut self,
+        path: &Path,
+        file_type: FileType,
+        mode: u32,
+        device: u64,
+    ) -> Result<()> {
+        if !file_type.is_special() {
+            return Err(create_error(ErrorKind::InvalidInput));
+        }
+
+        let path = self.resolve_parent(path)?;
+
+        if (file_type.is_block_device() || file_type.is_char_device()) && self.uid != 0 {
+            return Err(create_error(ErrorKind::PermissionDenied));
+        }
+
+        let special = self.created(Node::Special(Special::new(file_type, device)), mode);
+
+        self.insert(path, special)
+    }
+
+    pub fn write_file(&mut self, path: &Path, buf: &[u8]) -> Result<()> {
+        let path = self.resolve_path(path)?;
+
+        if let Ok(f) = self.get_file(&path) {
+            self.ensure_space(f.contents.len() as u64, buf.len() as u64)?;
+        }
+
+        self.set_contents(&path, buf.to_vec()).or_else(|e| {
+            if e.kind() == ErrorKind::NotFound {
+                self.create_file(&path, buf)
+            } else {
+                Err(e)
+            }
+        })
+    }
+
+    pub fn overwrite_file(&mut self, path: &Path, buf: &[u8]) -> Result<()> {
+        let path = self.resolve_path(path)?;
+
+        if let Ok(f) = self.get_file(&path) {
+            self.ensure_space(f.contents.len() as u64, buf.len() as u64)?;
+        }
+
+        self.set_contents(&path, buf.to_vec())
+    }
+
+    pub fn read_file(&self, path: &Path) -> Result<Vec<u8>> {
+        let path = self.resolve_path(path)?;
+
+        let file = self.get_file(&path)?;
+
+        self.check_access(&path, READ)?;
+
+        Ok(file.contents.to_vec())
+    }
+
+    /// Returns the contents of the file at `path` without copying them.
+    pub fn read_contents(&self, path: &Path) -> Result<Contents> {
+        let path = self.resolve_path(path)?;
+
+        let file = self.get_file(&path)?;
+
+        self.check_access(&path, READ)?;
+
+        Ok(file.contents.clone())
+    }
+
+    pub fn read_file_to_string(&self, path: &Path) -> Result<String> {
+        match self.read_file(path) {
+            Ok(vec) => String::from_utf8(vec).map_err(|_| create_error(ErrorKind::InvalidData)),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn read_file_into(&self, path: &Path, buf: &mut Vec<u8>) -> Result<usize> {
+        let path = self.resolve_path(path)?;
+
+        let file = self.get_file(&path)?;
+
+        self.check_access(&path, READ)?;
+
+        for slice in file.contents.slices() {
+            buf.extend_from_slice(slice);
+        }
+
+        Ok(file.contents.len())
+    }
+
+    /// Validates `options` and prepares the file at `path` for opening, returning the resolved
+    /// path that the open file should use.
+    pub fn open(&mut self, path: &Path, options: &OpenOptions) -> Result<PathBuf> {
+        // Based on the validation in std's unix OpenOptions
+        let can_write = options.is_write();
+        let invalid = if can_write {
+            options.is_truncate() && options.is_append() && !options.is_create_new()
+        } else {
+            !options.is_read()
+                || options.is_truncate()
+                || options.is_create()
+                || options.is_create_new()
+        };
+
+        if invalid {
+            return Err(create_error(ErrorKind::InvalidInput));
+        }
+
+        if options.is_create_new() {
+            self.create_file(path, &[])?;
+
+            return self.resolve_path(path);
+        }
+
+        let path = self.resolve_path(path)?;
+        let exists = match self.get_file(&path) {
+            Ok(_) => true,
+            Err(ref err) if err.kind() == ErrorKind::NotFound => false,
+            Err(err) => return Err(err),
+        };
+
+        if !exists {
+            return if options.is_create() {
+                self.create_file(&path, &[]).and(Ok(path))
+            } else {
+                Err(create_error(ErrorKind::NotFound))
+            };
+        }
+
+        if options.is_read() {
+            self.check_access(&path, READ)?;
+        }
+
+        if can_write {
+            self.check_access(&path, WRITE)?;
+        }
+
+        if options.is_truncate() {
+            self.set_contents(&path, vec![])?;
+        }
+
+        Ok(path)
+    }
+
+    pub fn read_at(&self, path: &Path, position: u64, buf: &mut [u8]) -> Result<usize> {
+        let file = self.get_file(path)?;
+
+        match checked_file_len(Some(position)) {
+            Ok(position) => Ok(file.contents.read_at(position, buf)),
+            Err(_) => Ok(0),
+        }
+    }
+
+    pub fn write_at(&mut self, path: &Path, position: u64, buf: &[u8]) -> Result<usize> {
+        let len = self.get_file(path)?.contents.len();
+        let end = position.checked_add(buf.len() as u64);
+
+        self.ensure_space(len as u64, end.map_or(u64::MAX, |end| end.max(len as u64)))?;
+
+        let end = checked_file_len(end)?;
+
+        let file = match self.get_mut(path)? {
+            Node::File(ref mut file) => file,
+            _ => return Err(create_error(ErrorKind::IsADirectory)),
+        };
+        file.contents.write_at(end - buf.len(), buf);
+        file.modified = clock::now();
+        self.resized(len, len.max(end));
+
+        #[cfg(feature = "watch")]
+        self.emit(FsEvent::Modified(path.to_path_buf()));
+
+        self.log(|r| OperationKind::WriteAt {
+            path: r.user_path(path),
+            position,
+            buf: buf.to_vec(),
+        });
+
+        Ok(buf.len())
+    }
+
+    pub fn set_len(&mut self, path: &Path, len: u64) -> Result<()> {
+        let path = self.resolve_path(path)?;
+
+        self.get_file_mut(&path)?;
+        self.resize(&path, len)
+    }
+
+    /// Truncates or zero-extends the file at the resolved `path` to `len` bytes, without checking
+    /// permissions, as for an open file.
+    pub fn resize(&mut self, path: &Path, len: u64) -> Result<()> {
+        let old_len = self.get_file(path)?.contents.len();
+
+        self.ensure_space(old_len as u64, len)?;
+
+        let new_len = checked_file_len(Some(len))?;
+
+        let file = match self.get_mut(path)? {
+            Node::File(ref mut file) => file,
+            _ => return Err(create_error(ErrorKind::IsADirectory)),
+        };
+
+        file.contents.set_len(new_len);
+        file.modified = clock::now();
+        self.resized(old_len, new_len);
+
+        #[cfg(feature = "watch")]
+        self.emit(FsEvent::Modified(path.to_path_buf()));
+
+        self.log(|r| OperationKind::SetLen {
+            path: r.user_path(path),
+            len,
+        });
+
+        Ok(())
+    }
+
+    pub fn sync_file(&mut self, path: &Path) -> Result<()> {
+        let path = self.resolve_path(path)?;
+
+        match self.get_mut(&path)? {
+            Node::File(ref mut file) => file.synced = file.contents.clone(),
+            Node::Dir(_) => return Err(create_error(ErrorKind::IsADirectory)),
+            _ => return Err(create_error(ErrorKind::Other)),
+        }
+
+        self.log(|r| OperationKind::SyncFile {
+            path: r.user_path(&path),
+        });
+
+        Ok(())
+    }
+
+    pub fn sync_dir(&mut self, path: &Path) -> Result<()> {
+        let path = self.resolve_path(path)?;
+
+        match self.get_mut(&path)? {
+            Node::Dir(ref mut dir) => dir.synced = None,
+            _ => return Err(create_error(ErrorKind::NotADirectory)),
+        }
+
+        self.log(|r| OperationKind::SyncDir {
+            path: r.user_path(&path),
+        });
+
+        Ok(())
+    }
+
+    pub fn file_len(&self, path: &Path) -> Result<u64> {
+        self.get_file(path).map(|f| f.contents.len() as u64)
+    }
+
+    pub fn remove_file(&mut self, path: &Path) -> Result<()> {
+        let path = self.resolve_parent(path)?;
+
+        match self.get(&path) {
+            Ok(&Node::Dir(_)) => Err(create_error(ErrorKind::IsADirectory)),
+            Ok(_) => self.remove(&path).and(Ok(())),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn copy_file(&mut self, from: &Path, to: &Path) -> Result<()> {
+        match self.read_file(from) {
+            Ok(ref buf) => self.write_file(to, buf),
+            Err(ref err)
+                if err.kind() == ErrorKind::IsADirectory || err.kind() == ErrorKind::Other =>
+            {
+                Err(create_error(ErrorKind::InvalidInput))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Copies the directory at `from` along with its descendants to `to`, calling `step` with
+    /// the absolute path of each copy, as users see it, before it is created.
+    /// If `step` or a copy fails, every copy is removed again.
+    pub fn copy_dir<F>(&mut self, from: &Path, to: &Path, mut step: F) -> Result<()>
+    where
+        F: FnMut(&Path) -> Result<()>,
+    {
+        let from = self.resolve_path(from)?;
+        let to = self.resolve_parent(to)?;
+
+        self.get_dir(&from)?;
+
+        if to.starts_with(&from) {
+            return Err(create_error(ErrorKind::InvalidInput));
+        }
+
+        let mut descendants = self.descendants(&from);
+
+        for path in &descendants {
+            self.check_access(path, READ)?;
+        }
+
+        // Parents sort before their children, so every parent is created first
+        descendants.sort();
+
+        let mut copies = vec![(to.clone(), self.owned(self.get(&from)?.copied()))];
+
+        for path in descendants {
+            let stem = path.strip_prefix(&from).unwrap_or(&path);
+
+            copies.push((to.join(stem), self.owned(self.get(&path)?.copied())));
+        }
+
+        let size = copies
+            .iter()
+            .map(|(_, node)| match node {
+                Node::File(ref file) => file.contents.len() as u64,
+                _ => 0,
+            })
+            .sum();
+
+        self.ensure_space(0, size)?;
+
+        self.atomically(|r| {
+            // Directories stay writable until their contents are copied, like `cp -r`
+            let mut modes = vec![];
+
+            for (path, mut node) in copies {
+                if node.is_dir() {
+                    modes.push((path.clone(), node.mode()));
+                    node.set_mode(node.mode() | 0o200);
+                }
+
+                step(&r.user_path(&path))?;
+                r.insert(path, node)?;
+            }
+
+            for (path, mode) in modes.into_iter().rev() {
+                r.get_mut(&path)?.set_mode(mode);
+                r.log(|r| OperationKind::SetMode {
+                    path: r.user_path(&path),
+                    mode,
+                });
+            }
+
+            Ok(())
+        })
+    }
+
+    pub fn rename(&mut self, from: &Path, to: &Path) -> Result<()> {
+        let from = self.resolve_parent(from)?;
+        let to = self.resolve_parent(to)?;
+
+        self.check_not_busy(&from)?;
+        self.check_not_busy(&to)?;
+
+        if self.device(&from) != self.device(&to) {
+            return Err(create_error(ErrorKind::CrossesDevices));
+        }
+
+        // Renaming a node to another of its names, such as one that differs only in case, must
+        // not replace the node with itself
+        if let (Ok(a), Ok(b)) = (self.get(&from), self.get(&to)) {
+            if ptr::eq(a, b) {
+                return if from == to {
+                    Ok(())
+                } else {
+                    self.rename_path(&from, to)
+                };
+            }
+        }
+
+        match (
+            self.get(&from).map(Node::is_dir),
+            self.get(&to).map(Node::is_dir),
+        ) {
+            (Ok(false), Ok(false)) => self.rename_path(&from, to),
+            (Ok(false), Err(ref err)) if err.kind() == ErrorKind::NotFound => {
+                self.rename_path(&from, to)
+            }
+            (Ok(true), Ok(true)) if self.children(&to).is_empty() => self.move_dir(&from, &to),
+            (Ok(false), Ok(true)) => Err(create_error(ErrorKind::IsADirectory)),
+            (Ok(true), Ok(false)) => Err(create_error(ErrorKind::NotADirectory)),
+            (Ok(true), Ok(true)) => Err(create_error(ErrorKind::DirectoryNotEmpty)),
+            (Ok(true), Err(ref err)) if err.kind() == ErrorKind::NotFound => {
+                self.move_dir(&from, &to)
+            }
+            (Err(err), _) => Err(err),
+            (_, Err(err)) => Err(err),
+        }
+    }
+
+    pub fn readonly(&self, path: &Path) -> Result<bool> {
+        self.mode(path).map(|mode| mode & 0o222 == 0)
+    }
+
+    pub fn set_readonly(&mut self, path: &Path, readonly: bool) -> Result<()> {
+        let mode = self.mode(path)?;
+
+        if readonly {
+            self.set_mode(path, mode & !0o222)
+        } else {
+            self.set_mode(path, mode | 0o222)
+        }
+    }
+
+    /// Returns whether the node at `path`, rather than any target of it, is hidden: by its
+    /// `FILE_ATTRIBUTE_HIDDEN` attribute if the registry uses Windows paths, and otherwise by its
+    /// name.
+    pub fn is_hidden(&self, path: &Path) -> Result<bool> {
+        let path = self.resolve_parent(path)?;
+        let node = self.get(&path)?;
+
+        if self.windows {
+            Ok(node.attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+        } else {
+            Ok(path.file_name().is_some_and(read_dir::is_hidden_name))
+        }
+    }
+
+    pub fn set_hidden(&mut self, path: &Path, hidden: bool) -> Result<()> {
+        let path = self.resolve_parent(path)?;
+        let attributes = self.get(&path)?.attributes();
+        let attributes = if hidden {
+            attributes | FILE_ATTRIBUTE_HIDDEN
+        } else {
+            attributes & !FILE_ATTRIBUTE_HIDDEN
+        };
+
+        self.modify(&path, |node| node.set_attributes(attributes))?;
+        self.log(|r| OperationKind::SetAttributes {
+            path: r.user_path(&path),
+            attributes,
+        });
+
+        Ok(())
+    }
+
+    pub fn mode(&self, path: &Path) -> Result<u32> {
+        let path = self.resolve_path(path)?;
+
+        self.get(&path).map(Node::mode)
+    }
+
+    pub fn set_mode(&mut self, path: &Path, mode: u32) -> Result<()> {
+        let path = self.resolve_path(path)?;
+
+        self.ensure_owner(&path)?;
+        self.modify(&path, |node| node.set_mode(mode))?;
+        self.log(|r| OperationKind::SetMode {
+            path: r.user_path(&path),
+            mode,
+        });
+
+        Ok(())
+    }
+
+    pub fn owner(&self, path: &Path) -> Result<(u32, u32)> {
+        let path = self.resolve_path(path)?;
+
+        self.get(&path).map(Node::owner)
+    }
+
+    /// Changes the owner of `path`.
+    /// Unlike most systems, owners can give their nodes away to other users, so that nodes owned by
+    /// other users can be set up without switching users.
+    pub fn set_owner(&mut self, path: &Path, uid: u32, gid: u32) -> Result<()> {
+        let path = self.resolve_path(path)?;
+
+        self.ensure_owner(&path)?;
+        self.modify(&path, |node| node.set_owner(uid, gid))?;
+        self.log(|r| OperationKind::SetOwner {
+            path: r.user_path(&path),
+            uid,
+            gid,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_modified(&mut self, path: &Path, time: SystemTime) -> Result<()> {
+        let path = self.resolve_path(path)?;
+
+        self.ensure_owner(&path)?;
+        self.modify(&path, |node| node.set_modified(time))?;
+        self.log(|r| OperationKind::SetModified {
+            path: r.user_path(&path),
+            time,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_accessed(&mut self, path: &Path, time: SystemTime) -> Result<()> {
+        let path = self.resolve_path(path)?;
+
+        self.ensure_owner(&path)?;
+        self.modify(&path, |node| node.set_accessed(time))?;
+        self.log(|r| OperationKind::SetAccessed {
+            path: r.user_path(&path),
+            time,
+        });
+
+        Ok(())
+    }
+
+    /// Returns the node's stored attributes, along with those Windows derives from its type and
+    /// mode.
+    #[cfg(windows)]
+    pub fn attributes(&self, path: &Path) -> Result<u32> {
+        let path = self.resolve_path(path)?;
+        let node = self.get(&path)?;
+        let mut attributes = node.attributes();
 
-        files.insert(cwd.clone(), Node::Dir(Dir::new()));
+        if node.is_dir() {
+            attributes |= FILE_ATTRIBUTE_DIRECTORY;
+        }
 
-        Registry { cwd, files }
-    }
+        if node.mode() & 0o222 == 0 {
+            attributes |= FILE_ATTRIBUTE_READONLY;
+        }
 
-    pub fn current_dir(&self) -> Result<PathBuf> {
-        self.get_dir(&self.cwd).map(|_| self.cwd.clone())
+        Ok(attributes)
     }
 
-    pub fn set_current_dir(&mut self, cwd: PathBuf) -> Result<()> {
-        match self.get_dir(&cwd) {
-            Ok(_) => {
-                self.cwd = cwd;
-                Ok(())
-            }
-            Err(e) => Err(e),
-        }
+    #[cfg(windows)]
+    pub fn set_attributes(&mut self, path: &Path, attributes: u32) -> Result<()> {
+        self.set_readonly(path, attributes & FILE_ATTRIBUTE_READONLY != 0)?;
+
+        let path = self.resolve_path(path)?;
+        let derived =
+            FILE_ATTRIBUTE_READONLY | FILE_ATTRIBUTE_DIRECTORY | FILE_ATTRIBUTE_REPARSE_POINT;
+
+        self.modify(&path, |node| node.set_attributes(attributes & !derived))?;
+        self.log(|r| OperationKind::SetAttributes {
+            path: r.user_path(&path),
+            attributes: attributes & !derived,
+        });
+
+        Ok(())
     }
 
-    pub fn is_dir(&self, path: &Path) -> bool {
-        self.get(path).map(Node::is_dir).unwrap_or(false)
+    pub fn len(&self, path: &Path) -> u64 {
+        self.resolve_path(path)
+            .and_then(|p| {
+                self.get(&p).map(|node| match node {
+                    Node::File(ref file) => file.contents.len() as u64,
+                    Node::Special(_) => 0,
+                    _ => 4096,
+                })
+            })
+            .unwrap_or(0)
     }
 
-    pub fn is_file(&self, path: &Path) -> bool {
-        self.get(path).map(Node::is_file).unwrap_or(false)
+    /// Returns the capacity less the contents of every file, or as much as fits in a `u64` if
+    /// there is no capacity.
+    pub fn available_space(&self, path: &Path) -> Result<u64> {
+        let total = self.total_space(path)?;
+
+        Ok(total.saturating_sub(self.footprint.len))
     }
 
-    pub fn create_dir(&mut self, path: &Path) -> Result<()> {
-        self.insert(path.to_path_buf(), Node::Dir(Dir::new()))
+    pub fn total_space(&self, path: &Path) -> Result<u64> {
+        let path = self.resolve_path(path)?;
+
+        self.get(&path)?;
+
+        Ok(self.capacity.unwrap_or(u64::MAX))
     }
 
-    pub fn create_dir_all(&mut self, path: &Path) -> Result<()> {
-        // Based on std::fs::DirBuilder::create_dir_all
-        if path == Path::new("") {
-            return Ok(());
-        }
+    /// Acquires a lock on the file at the resolved `path` for `handle`, converting any lock the
+    /// handle already holds.
+    #[cfg(feature = "lock")]
+    pub fn try_lock(&mut self, path: &Path, handle: u64, exclusive: bool) -> Result<()> {
+        let lock = match (self.locks.get(path), exclusive) {
+            (None, true) => Lock::Exclusive(handle),
+            (None, false) => Lock::Shared(vec![handle]),
+            (Some(&Lock::Exclusive(owner)), true) if owner == handle => Lock::Exclusive(handle),
+            (Some(&Lock::Exclusive(owner)), false) if owner == handle => Lock::Shared(vec![handle]),
+            (Some(&Lock::Exclusive(_)), _) => return Err(create_error(ErrorKind::WouldBlock)),
+            (Some(Lock::Shared(ref owners)), true) if owners == &[handle] => {
+                Lock::Exclusive(handle)
+            }
+            (Some(&Lock::Shared(_)), true) => return Err(create_error(ErrorKind::WouldBlock)),
+            (Some(Lock::Shared(ref owners)), false) => {
+                let mut owners = owners.clone();
 
-        match self.create_dir(path) {
-            Ok(_) => return Ok(()),
-            Err(ref e) if e.kind() == ErrorKind::NotFound => {}
-            Err(_) if self.is_dir(path) => return Ok(()),
-            Err(e) => return Err(e),
-        }
+                if !owners.contains(&handle) {
+                    owners.push(handle);
+                }
 
-        match path.parent() {
-            Some(p) => self.create_dir_all(p)?,
-            None => return Err(create_error(ErrorKind::Other)),
-        }
+                Lock::Shared(owners)
+            }
+        };
 
-        self.create_dir_all(path)
+        self.locks.insert(path.to_path_buf(), lock);
+
+        Ok(())
     }
 
-    pub fn remove_dir(&mut self, path: &Path) -> Result<()> {
-        match self.get_dir(path) {
-            Ok(_) if self.descendants(path).is_empty() => {}
-            Ok(_) => return Err(create_error(ErrorKind::Other)),
-            Err(e) => return Err(e),
+    /// Releases any lock on the file at the resolved `path` held by `handle`.
+    #[cfg(feature = "lock")]
+    pub fn unlock(&mut self, path: &Path, handle: u64) {
+        let released = match self.locks.get_mut(path) {
+            Some(Lock::Exclusive(owner)) => *owner == handle,
+            Some(Lock::Shared(ref mut owners)) => {
+                owners.retain(|owner| *owner != handle);
+                owners.is_empty()
+            }
+            None => false,
         };
 
-        self.remove(path).and(Ok(()))
+        if released {
+            self.locks.remove(path);
+        }
     }
 
-    pub fn remove_dir_all(&mut self, path: &Path) -> Result<()> {
-        self.get_dir_mut(path)?;
+    /// Starts sending events for changes to `path` to `sender`, until its receiver is dropped.
+    #[cfg(feature = "watch")]
+    pub fn watch(&mut self, path: &Path, recursive: bool, sender: Sender<FsEvent>) -> Result<()> {
+        let path = self.resolve_path(path)?;
 
-        let descendants = self.descendants(path);
-        let all_readable = descendants.iter().all(|(_, mode)| mode & 0o444 != 0);
+        self.get(&path)?;
+        self.watchers.push(Watcher {
+            path,
+            recursive,
+            sender,
+        });
 
-        if !all_readable {
-            return Err(create_error(ErrorKind::PermissionDenied));
-        }
+        Ok(())
+    }
 
-        for (child, _) in descendants {
-            self.remove(&child)?;
-        }
+    pub fn symlink(&mut self, original: &Path, link: &Path) -> Result<()> {
+        let link = self.resolve_parent(link)?;
+
+        let symlink = self.owned(Node::Symlink(Symlink::new(original.to_path_buf())));
 
-        self.remove(path).and(Ok(()))
+        self.insert(link, symlink)
     }
 
-    pub fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
-        self.get_dir(path)?;
+    pub fn read_link(&self, path: &Path) -> Result<PathBuf> {
+        let path = self.resolve_parent(path)?;
 
-        Ok(self.children(path))
+        match self.get(&path)? {
+            Node::Symlink(ref symlink) => Ok(symlink.target.clone()),
+            _ => Err(create_error(ErrorKind::InvalidInput)),
+        }
     }
 
-    pub fn create_file(&mut self, path: &Path, buf: &[u8]) -> Result<()> {
-        let file = File::new(buf.to_vec());
+    /// Returns `path` with every symlink in it replaced by its target.
+    pub fn resolve_path(&self, path: &Path) -> Result<PathBuf> {
+        self.resolve(path, true, 0)
+    }
 
-        self.insert(path.to_path_buf(), Node::File(file))
+    /// Returns `path` with every symlink in its parent replaced by its target.
+    /// The last component is left as is, so the result can refer to a symlink itself.
+    fn resolve_parent(&self, path: &Path) -> Result<PathBuf> {
+        self.resolve(path, false, 0)
     }
 
-    pub fn write_file(&mut self, path: &Path, buf: &[u8]) -> Result<()> {
-        self.get_file_mut(path)
-            .map(|ref mut f| f.contents = buf.to_vec())
-            .or_else(|e| {
-                if e.kind() == ErrorKind::NotFound {
-                    self.create_file(path, buf)
-                } else {
-                    Err(e)
+    fn resolve(&self, path: &Path, follow: bool, mut depth: usize) -> Result<PathBuf> {
+        if !self.limits.accept_all() {
+            // The drive of a Windows path is not a name, even though it is stored like one
+            let drives = if self.windows { 1 } else { 0 };
+
+            self.limits
+                .check(&self.user_path(path), names(path).skip(drives))?;
+        }
+
+        let mut resolved = PathBuf::new();
+        let mut components = path.components().peekable();
+
+        while let Some(component) = components.next() {
+            match component {
+                Component::CurDir => continue,
+                Component::ParentDir => {
+                    // As on the OS, `..` can only be taken from a directory that exists
+                    self.get_dir(&resolved)?;
+                    self.check_search(&resolved)?;
+                    resolved.pop();
+                    continue;
                 }
-            })
-    }
+                Component::Normal(_) => {
+                    self.check_search(&resolved)?;
+                    resolved.push(component);
+                }
+                _ => resolved.push(component),
+            }
 
-    pub fn overwrite_file(&mut self, path: &Path, buf: &[u8]) -> Result<()> {
-        self.get_file_mut(path)
-            .map(|ref mut f| f.contents = buf.to_vec())
-    }
+            if components.peek().is_none() && !follow {
+                break;
+            }
 
-    pub fn read_file(&self, path: &Path) -> Result<Vec<u8>> {
-        match self.get_file(path) {
-            Ok(f) if f.mode & 0o444 != 0 => Ok(f.contents.clone()),
-            Ok(_) => Err(create_error(ErrorKind::PermissionDenied)),
-            Err(err) => Err(err),
+            while let Ok(Node::Symlink(ref symlink)) = self.get(&resolved) {
+                if depth >= self.max_symlink_depth {
+                    return Err(error::filesystem_loop("too many levels of symbolic links"));
+                }
+
+                depth += 1;
+
+                // Relative targets are relative to the directory that holds the symlink, which
+                // has itself been resolved, so `..` leads to that directory's actual parent
+                let target = match resolved.parent() {
+                    Some(parent) if self.windows => windows::to_registry(&symlink.target, parent),
+                    Some(parent) => parent.join(&symlink.target),
+                    None => symlink.target.clone(),
+                };
+
+                resolved = self.resolve(&target, true, depth)?;
+            }
         }
+
+        Ok(resolved)
     }
 
-    pub fn read_file_to_string(&self, path: &Path) -> Result<String> {
-        match self.read_file(path) {
-            Ok(vec) => String::from_utf8(vec).map_err(|_| create_error(ErrorKind::InvalidData)),
-            Err(err) => Err(err),
-        }
+    /// Marks `node` as owned by the current user.
+    fn owned(&self, mut node: Node) -> Node {
+        node.set_owner(self.uid, self.gid);
+        node
     }
 
-    pub fn read_file_into(&self, path: &Path, buf: &mut Vec<u8>) -> Result<usize> {
-        match self.get_file(path) {
-            Ok(f) if f.mode & 0o444 != 0 => {
-                buf.extend(&f.contents);
-                Ok(f.contents.len())
+    /// Marks the new `node` as owned by the current user, with `mode` less the umask.
+    fn created(&self, node: Node, mode: u32) -> Node {
+        let mut node = self.owned(node);
+
+        node.set_mode(mode & !self.umask);
+        node
+    }
+
+    /// Determines whether the access rules for the resolved `path`, or otherwise the mode bits of
+    /// its `node` for the current user's class, as the node's owner, a member of its group, or
+    /// anyone else, grant `access`.
+    fn permits(&self, path: &Path, node: &Node, access: u32) -> bool {
+        // Like SELinux, rules take precedence even over root
+        if let Some(rule) = self.access_rules.get(path) {
+            if rule.deny & access != 0 {
+                return false;
+            } else if rule.allow & access != 0 {
+                return true;
             }
-            Ok(_) => Err(create_error(ErrorKind::PermissionDenied)),
-            Err(err) => Err(err),
         }
-    }
 
-    pub fn remove_file(&mut self, path: &Path) -> Result<()> {
-        match self.get_file(path) {
-            Ok(_) => self.remove(path).and(Ok(())),
-            Err(e) => Err(e),
+        // Like the OS, root may read and write anything
+        if self.uid == 0 {
+            return true;
         }
+
+        let (uid, gid) = node.owner();
+        let shift = if uid == self.uid {
+            6
+        } else if gid == self.gid {
+            3
+        } else {
+            0
+        };
+
+        (node.mode() >> shift) & access != 0
     }
 
-    pub fn copy_file(&mut self, from: &Path, to: &Path) -> Result<()> {
-        match self.read_file(from) {
-            Ok(ref buf) => self.write_file(to, buf),
-            Err(ref err) if err.kind() == ErrorKind::Other => {
-                Err(create_error(ErrorKind::InvalidInput))
-            }
-            Err(err) => Err(err),
+    /// Checks that the current user has `access` to the node at the resolved `path`.
+    fn check_access(&self, path: &Path, access: u32) -> Result<()> {
+        if !self.permits(path, self.get(path)?, access) {
+            return Err(create_error(ErrorKind::PermissionDenied));
         }
+
+        Ok(())
     }
 
-    pub fn rename(&mut self, from: &Path, to: &Path) -> Result<()> {
-        match (self.get(from), self.get(to)) {
-            (Ok(&Node::File(_)), Ok(&Node::File(_))) => {
-                self.remove_file(to)?;
-                self.rename_path(from, to.to_path_buf())
-            }
-            (Ok(&Node::File(_)), Err(ref err)) if err.kind() == ErrorKind::NotFound => {
-                self.rename_path(from, to.to_path_buf())
+    /// Checks that the current user may look up names in the directory at the resolved `path`,
+    /// which requires its execute bit only if permissions are strict.
+    fn check_search(&self, path: &Path) -> Result<()> {
+        match self.get(path) {
+            Ok(node) if self.strict && node.is_dir() && !self.permits(path, node, SEARCH) => {
+                Err(create_error(ErrorKind::PermissionDenied))
             }
-            (Ok(&Node::Dir(_)), Ok(&Node::Dir(_))) if self.descendants(to).is_empty() => {
-                self.remove(to)?;
-                self.move_dir(from, to)
-            }
-            (Ok(&Node::File(_)), Ok(&Node::Dir(_)))
-            | (Ok(&Node::Dir(_)), Ok(&Node::File(_)))
-            | (Ok(&Node::Dir(_)), Ok(&Node::Dir(_))) => Err(create_error(ErrorKind::Other)),
-            (Ok(&Node::Dir(_)), Err(ref err)) if err.kind() == ErrorKind::NotFound => {
-                self.move_dir(from, to)
-            }
-            (Err(err), _) => Err(err),
-            (_, Err(err)) => Err(err),
+            _ => Ok(()),
         }
     }
 
-    pub fn readonly(&self, path: &Path) -> Result<bool> {
-        self.get(path).map(|node| match node {
-            Node::File(ref file) => file.mode & 0o222 == 0,
-            Node::Dir(ref dir) => dir.mode & 0o222 == 0,
-        })
+    /// Checks that the current user may change the metadata of the node at the resolved `path`,
+    /// which requires either owning it or being root.
+    fn ensure_owner(&self, path: &Path) -> Result<()> {
+        let (uid, _) = self.get(path)?.owner();
+
+        if self.uid != 0 && self.uid != uid {
+            return Err(create_error(ErrorKind::PermissionDenied));
+        }
+
+        Ok(())
     }
 
-    pub fn set_readonly(&mut self, path: &Path, readonly: bool) -> Result<()> {
-        self.get_mut(path).map(|node| match node {
-            Node::File(ref mut file) => {
-                if readonly {
-                    file.mode &= !0o222
-                } else {
-                    file.mode |= 0o222
-                }
-            }
-            Node::Dir(ref mut dir) => {
-                if readonly {
-                    dir.mode &= !0o222
-                } else {
-                    dir.mode |= 0o222
-                }
+    /// Checks that replacing `old_len` bytes of file contents with `new_len` bytes
+    /// fits within the capacity and the memory limit, if there are any.
+    fn ensure_space(&self, old_len: u64, new_len: u64) -> Result<()> {
+        if let Some(capacity) = self.capacity {
+            let used = self.footprint.len.saturating_sub(old_len);
+
+            if used.saturating_add(new_len) > capacity {
+                return Err(create_error(ErrorKind::StorageFull));
             }
-        })
-    }
+        }
 
-    pub fn mode(&self, path: &Path) -> Result<u32> {
-        self.get(path).map(|node| match node {
-            Node::File(ref file) => file.mode,
-            Node::Dir(ref dir) => dir.mode,
-        })
+        let additional = new_len.saturating_sub(old_len);
+
+        self.ensure_memory(additional.min(usize::MAX as u64) as usize)
     }
 
-    pub fn set_mode(&mut self, path: &Path, mode: u32) -> Result<()> {
-        self.get_mut(path).map(|node| match node {
-            Node::File(ref mut file) => file.mode = mode,
-            Node::Dir(ref mut dir) => dir.mode = mode,
-        })
+    /// Checks that using `additional` more bytes of memory fits within the memory limit, if there
+    /// is one.
+    fn ensure_memory(&self, additional: usize) -> Result<()> {
+        match self.memory_limit {
+            Some(limit) if self.footprint.memory.saturating_add(additional) > limit => {
+                Err(create_error(ErrorKind::OutOfMemory))
+            }
+            _ => Ok(()),
+        }
     }
 
-    pub fn len(&self, path: &Path) -> u64 {
-        self.get(path)
-            .map(|node| match node {
-                Node::File(ref file) => file.contents.len() as u64,
-                Node::Dir(_) => 4096,
-            })
-            .unwrap_or(0)
+    /// Notes that the contents of a file changed from `old_len` to `new_len` bytes.
+    fn resized(&mut self, old_len: usize, new_len: usize) {
+        self.footprint.sub(Footprint::of_contents(old_len));
+        self.footprint.add(Footprint::of_contents(new_len));
     }
 
+    /// Returns the node at the resolved `path`, found by walking down from the root.
     fn get(&self, path: &Path) -> Result<&Node> {
-        self.files
-            .get(path)
-            .ok_or_else(|| create_error(ErrorKind::NotFound))
+        let mut node = &self.root;
+
+        for name in names(path) {
+            node = match *node {
+                Node::Dir(ref dir) => child_key(dir, name, self.case, self.normalization)
+                    .and_then(|key| dir.children.get(&*key)),
+                _ => None,
+            }
+            .ok_or_else(|| create_error(ErrorKind::NotFound))?;
+        }
+
+        Ok(node)
     }
 
     fn get_mut(&mut self, path: &Path) -> Result<&mut Node> {
-        self.files
-            .get_mut(path)
-            .ok_or_else(|| create_error(ErrorKind::NotFound))
+        let (case, normalization) = (self.case, self.normalization);
+        let mut node = &mut self.root;
+
+        for name in names(path) {
+            node = match node {
+                Node::Dir(dir) => match child_key(dir, name, case, normalization) {
+                    Some(key) => dir.children_mut().get_mut(&*key),
+                    None => None,
+                },
+                _ => None,
+            }
+            .ok_or_else(|| create_error(ErrorKind::NotFound))?;
+        }
+
+        Ok(node)
     }
 
     fn get_dir(&self, path: &Path) -> Result<&Dir> {
         self.get(path).and_then(|node| match node {
             Node::Dir(ref dir) => Ok(dir),
-            Node::File(_) => Err(create_error(ErrorKind::Other)),
+            _ => Err(create_error(ErrorKind::NotADirectory)),
         })
     }
 
     fn get_dir_mut(&mut self, path: &Path) -> Result<&mut Dir> {
+        let writable = self.get(path).map(|node| self.permits(path, node, WRITE))?;
+
         self.get_mut(path).and_then(|node| match node {
-            Node::Dir(ref mut dir) if dir.mode & 0o222 != 0 => Ok(dir),
+            Node::Dir(ref mut dir) if writable => Ok(dir),
             Node::Dir(_) => Err(create_error(ErrorKind::PermissionDenied)),
-            Node::File(_) => Err(create_error(ErrorKind::Other)),
+            _ => Err(create_error(ErrorKind::NotADirectory)),
         })
     }
 
     fn get_file(&self, path: &Path) -> Result<&File> {
         self.get(path).and_then(|node| match node {
             Node::File(ref file) => Ok(file),
-            Node::Dir(_) => Err(create_error(ErrorKind::Other)),
+            Node::Dir(_) => Err(create_error(ErrorKind::IsADirectory)),
+            _ => Err(create_error(ErrorKind::Other)),
         })
     }
 
     fn get_file_mut(&mut self, path: &Path) -> Result<&mut File> {
+        let writable = self.get(path).map(|node| self.permits(path, node, WRITE))?;
+
         self.get_mut(path).and_then(|node| match node {
-            Node::File(ref mut file) if file.mode & 0o222 != 0 => Ok(file),
+            Node::File(ref mut file) if writable => Ok(file),
             Node::File(_) => Err(create_error(ErrorKind::PermissionDenied)),
-            Node::Dir(_) => Err(create_error(ErrorKind::Other)),
+            Node::Dir(_) => Err(create_error(ErrorKind::IsADirectory)),
+            _ => Err(create_error(ErrorKind::Other)),
         })
     }
 
     fn insert(&mut self, path: PathBuf, file: Node) -> Result<()> {
-        if self.files.contains_key(&path) {
+        if self.get(&path).is_ok() {
             return Err(create_error(ErrorKind::AlreadyExists));
-        } else if let Some(p) = path.parent() {
-            self.get_dir_mut(p)?;
         }
 
-        self.files.insert(path, file);
+        if self.windows && path.file_name().is_some_and(windows::is_reserved) {
+            return Err(create_error(ErrorKind::InvalidInput));
+        }
+
+        let (parent, name) = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => (
+                parent,
+                self.names.intern(&normalize(name, self.normalization)),
+            ),
+            _ => return Err(create_error(ErrorKind::AlreadyExists)),
+        };
+
+        self.ensure_memory(memory::entry_size(&name, &file))?;
+
+        let footprint = memory::footprint(&name, &file);
+        let logged = self
+            .journal
+            .as_ref()
+            .map(|_| journal::created(self.user_path(&path), &file));
+        let crash_simulation = self.crash_simulation;
+        let dir = self.get_dir_mut(parent)?;
+
+        if crash_simulation {
+            crash::preserve(dir);
+        }
+
+        dir.modified = clock::now();
+        dir.children_mut().insert(name, file);
+        self.footprint.add(footprint);
+
+        #[cfg(feature = "watch")]
+        self.emit(FsEvent::Created(path));
+
+        if let Some(kind) = logged {
+            self.log(|_| kind);
+        }
+
+        self.changed();
 
         Ok(())
     }
 
+    /// Detaches the node at `path` from its parent, returning it along with its descendants.
     fn remove(&mut self, path: &Path) -> Result<Node> {
-        match self.files.remove(path) {
-            Some(f) => Ok(f),
-            None => Err(create_error(ErrorKind::NotFound)),
+        let (parent, name) = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => (parent, name),
+            _ => return Err(create_error(ErrorKind::NotFound)),
+        };
+        let (case, normalization) = (self.case, self.normalization);
+        let crash_simulation = self.crash_simulation;
+        let dir = match self.get_mut(parent) {
+            Ok(Node::Dir(dir)) => dir,
+            _ => return Err(create_error(ErrorKind::NotFound)),
+        };
+        let key = child_key(dir, name, case, normalization)
+            .map(Cow::into_owned)
+            .ok_or_else(|| create_error(ErrorKind::NotFound))?;
+
+        if crash_simulation {
+            crash::preserve(dir);
         }
+
+        let node = dir
+            .children_mut()
+            .remove(&*key)
+            .ok_or_else(|| create_error(ErrorKind::NotFound))?;
+
+        dir.modified = clock::now();
+        self.footprint.sub(memory::footprint(&key, &node));
+
+        #[cfg(feature = "watch")]
+        self.emit(FsEvent::Removed(path.to_path_buf()));
+
+        self.log(|r| OperationKind::Remove {
+            path: r.user_path(path),
+        });
+
+        Ok(node)
     }
 
-    fn descendants(&self, path: &Path) -> Vec<(PathBuf, u32)> {
-        self.files
-            .iter()
-            .filter(|(p, _)| p.starts_with(path) && *p != path)
-            .map(|(p, n)| {
-                (
-                    p.to_path_buf(),
-                    match n {
-                        Node::File(ref file) => file.mode,
-                        Node::Dir(ref dir) => dir.mode,
-                    },
-                )
-            })
-            .collect()
+    /// Applies `change` to the node at `path`, notifying any watchers.
+    fn modify<F: FnOnce(&mut Node)>(&mut self, path: &Path, change: F) -> Result<()> {
+        change(self.get_mut(path)?);
+
+        #[cfg(feature = "watch")]
+        self.emit(FsEvent::Modified(path.to_path_buf()));
+
+        Ok(())
+    }
+
+    /// Replaces the contents of the file at `path`, notifying any watchers.
+    fn set_contents(&mut self, path: &Path, contents: Vec<u8>) -> Result<()> {
+        let logged = self.journal.as_ref().map(|_| contents.clone());
+
+        let file = self.get_file_mut(path)?;
+        let old_len = file.contents.len();
+        let new_len = contents.len();
+
+        file.set_contents(contents);
+        self.resized(old_len, new_len);
+
+        #[cfg(feature = "watch")]
+        self.emit(FsEvent::Modified(path.to_path_buf()));
+
+        if let Some(contents) = logged {
+            self.log(|r| OperationKind::Write {
+                path: r.user_path(path),
+                contents,
+            });
+        }
+
+        self.changed();
+
+        Ok(())
+    }
+
+    /// Runs `f`, which may change many nodes, and puts back every node and logged operation
+    /// as they were if it fails, so that it either completes or changes nothing.
+    /// Watchers are only sent its events once it completes.
+    fn atomically<T, F>(&mut self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        let (root, journal, footprint) = (self.root.clone(), self.journal.clone(), self.footprint);
+        #[cfg(feature = "watch")]
+        let outer = self.pending.replace(vec![]);
+        let result = f(self);
+        #[cfg(feature = "watch")]
+        let events = mem::replace(&mut self.pending, outer).unwrap_or_default();
+
+        if result.is_err() {
+            self.root = root;
+            self.journal = journal;
+            self.footprint = footprint;
+
+            return result;
+        }
+
+        #[cfg(feature = "watch")]
+        for event in events {
+            self.emit(event);
+        }
+
+        result
+    }
+
+    /// Sends `event` to every watcher of its path, dropping watchers whose receivers are gone,
+    /// or holds it back if an operation that changes many nodes is still running.
+    #[cfg(feature = "watch")]
+    fn emit(&mut self, event: FsEvent) {
+        if let Some(ref mut pending) = self.pending {
+            pending.push(event);
+
+            return;
+        }
+
+        let path = event.path().to_path_buf();
+        let event = match event {
+            FsEvent::Created(ref path) => FsEvent::Created(self.user_path(path)),
+            FsEvent::Modified(ref path) => FsEvent::Modified(self.user_path(path)),
+            FsEvent::Removed(ref path) => FsEvent::Removed(self.user_path(path)),
+        };
+
+        self.watchers
+            .retain(|watcher| !watcher.covers(&path) || watcher.sender.send(event.clone()).is_ok());
+    }
+
+    /// Returns the absolute paths of the descendants of the node at `path`, with each
+    /// directory before its children.
+    fn descendants(&self, path: &Path) -> Vec<PathBuf> {
+        let mut descendants = vec![];
+
+        if let Ok(node) = self.get(path) {
+            visit(node, path, &mut |path, _| descendants.push(path));
+        }
+
+        descendants
+    }
+
+    /// Adds up the lengths of the files under the resolved directory at `path`, failing if a
+    /// followed symlink leads back to the directory or one of its `ancestors`.
+    fn size_of(&self, path: &Path, follow: bool, ancestors: &mut Vec<PathBuf>) -> Result<u64> {
+        let mut size = 0;
+
+        ancestors.push(path.to_path_buf());
+
+        for child in self.children(path) {
+            let resolved = match self.get(&child)? {
+                Node::Symlink(_) if follow => self.resolve_path(&child)?,
+                _ => child.clone(),
+            };
+
+            size += match *self.get(&resolved)? {
+                Node::File(ref file) => file.contents.len() as u64,
+                Node::Dir(_) if ancestors.contains(&resolved) => {
+                    return Err(error::filesystem_loop(format!(
+                        "file system loop found at {}",
+                        self.user_path(&child).display()
+                    )));
+                }
+                Node::Dir(_) => self.size_of(&resolved, follow, ancestors)?,
+                _ => 0,
+            };
+        }
+
+        ancestors.pop();
+
+        Ok(size)
     }
 
+    /// Returns the absolute paths of the children of the directory at `path`, sorted by name.
     fn children(&self, path: &Path) -> Vec<PathBuf> {
-        self.files
-            .keys()
-            .filter(|p| p.parent().map(|parent| parent == path).unwrap_or(false))
-            .map(|p| p.to_path_buf())
-            .collect()
+        match self.get(path) {
+            Ok(Node::Dir(ref dir)) => dir.children.keys().map(|name| path.join(&**name)).collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Places `node` at the absolute `path` without checking permissions, replacing the root if
+    /// `path` has no parent.
+    #[cfg(any(feature = "serde", feature = "tar"))]
+    fn attach(&mut self, path: &Path, node: Node) {
+        match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => {
+                let crash_simulation = self.crash_simulation;
+                let name = self.names.intern(name);
+                let footprint = memory::footprint(&name, &node);
+
+                if let Ok(Node::Dir(dir)) = self.get_mut(parent) {
+                    if crash_simulation {
+                        crash::preserve(dir);
+                    }
+
+                    let old = dir.children_mut().insert(name.clone(), node);
+
+                    if let Some(old) = old {
+                        self.footprint.sub(memory::footprint(&name, &old));
+                    }
+
+                    self.footprint.add(footprint);
+                }
+            }
+            _ => {
+                self.root = node;
+                self.footprint = memory::footprint(OsStr::new(""), &self.root);
+            }
+        }
     }
 
+    /// Moves the node at `from`, along with its descendants, to `to` in one step, replacing any
+    /// other node at `to`.
+    /// Nothing is changed unless the move succeeds.
     fn rename_path(&mut self, from: &Path, to: PathBuf) -> Result<()> {
-        let file = self.remove(from)?;
-        self.insert(to, file)
+        // The node must have somewhere to go before it is detached, or it would be lost
+        if to != from && to.starts_with(from) {
+            return Err(create_error(ErrorKind::InvalidInput));
+        }
+
+        if self.windows && to.file_name().is_some_and(windows::is_reserved) {
+            return Err(create_error(ErrorKind::InvalidInput));
+        }
+
+        for parent in from.parent().into_iter().chain(to.parent()) {
+            self.get_dir_mut(parent)?;
+        }
+
+        // The node is logged as moved rather than as removed and created again, and the node
+        // it replaces is only removed once the node is detached, since it may be the same node
+        // under a name that differs in case
+        let journal = self.journal.take();
+        let result = self.remove(from).and_then(|node| {
+            if self.get(&to).is_ok() {
+                self.remove(&to)?;
+            }
+
+            self.insert(to.clone(), node)
+        });
+
+        self.journal = journal;
+        result?;
+        self.log(|r| OperationKind::Rename {
+            from: r.user_path(from),
+            to: r.user_path(&to),
+        });
+
+        Ok(())
+    }
+
+    /// Notes that the nodes have changed, so that a persistent registry writes them out once the
+    /// operation completes.
+    fn changed(&mut self) {
+        #[cfg(all(feature = "serde", feature = "std-os"))]
+        {
+            self.unsaved = true;
+        }
+    }
+
+    /// Logs the operation returned by `operation`, if operations are being logged.
+    fn log<F: FnOnce(&Self) -> OperationKind>(&mut self, operation: F) {
+        self.changed();
+
+        if self.journal.is_some() {
+            let kind = operation(self);
+
+            if let Some(ref mut journal) = self.journal {
+                journal.push(kind);
+            }
+        }
     }
 
     fn move_dir(&mut self, from: &Path, to: &Path) -> Result<()> {
         self.rename_path(from, to.to_path_buf())?;
 
-        for child in self.children(from) {
-            let stem = child.strip_prefix(from).unwrap_or(&child);
-            let new_path = to.join(stem);
+        // The directory's descendants move along with it, but watchers still see each of them
+        // leave its old path and arrive at its new one
+        #[cfg(feature = "watch")]
+        for new_path in self.descendants(to) {
+            let stem = new_path.strip_prefix(to).unwrap_or(&new_path);
 
-            self.rename(&child, &new_path)?;
+            self.emit(FsEvent::Removed(from.join(stem)));
+            self.emit(FsEvent::Created(new_path));
         }
 
         Ok(())
     }
 }
 
-fn create_error(kind: ErrorKind) -> Error {
+/// Returns the names of the nodes between the root and the node at the absolute `path`.
+fn names(path: &Path) -> impl Iterator<Item = &OsStr> {
+    path.components().filter_map(|component| match component {
+        Component::Normal(name) => Some(name),
+        _ => None,
+    })
+}
+
+/// Calls `f` with the absolute path and node of every descendant of `node`, which is at `path`,
+/// with each directory before its children.
+fn visit<'a, F: FnMut(PathBuf, &'a Node)>(node: &'a Node, path: &Path, f: &mut F) {
+    if let Node::Dir(ref dir) = *node {
+        for (name, child) in dir.children.iter() {
+            let child_path = path.join(&**name);
+
+            f(child_path.clone(), child);
+            visit(child, &child_path, f);
+        }
+    }
+}
+
+/// Returns `len` as the length of a file, failing if there is none because it overflowed, or if
+/// it is longer than a file can be.
+fn checked_file_len(len: Option<u64>) -> Result<usize> {
+    match len {
+        Some(len) if len <= MAX_FILE_LEN => Ok(len as usize),
+        _ => Err(create_error(ErrorKind::FileTooLarge)),
+    }
+}
+
+pub fn create_error(kind: ErrorKind) -> Error {
     // Based on private std::io::ErrorKind::as_str()
     let description = match kind {
         ErrorKind::NotFound => "entity not found",
@@ -364,6 +2063,13 @@ fn create_error(kind: ErrorKind) -> Error {
         ErrorKind::Interrupted => "operation interrupted",
         ErrorKind::Other => "other os error",
         ErrorKind::UnexpectedEof => "unexpected end of file",
+        ErrorKind::StorageFull => "no storage space",
+        ErrorKind::InvalidFilename => "invalid filename",
+        ErrorKind::CrossesDevices => "cross-device link or rename",
+        ErrorKind::ResourceBusy => "resource busy",
+        ErrorKind::NotADirectory => "not a directory",
+        ErrorKind::IsADirectory => "is a directory",
+        ErrorKind::DirectoryNotEmpty => "directory not empty",
         _ => "other",
     };
 
@@ -0,0 +1,331 @@
+use std::collections::BTreeMap;
+#[cfg(feature = "std-os")]
+use std::env;
+use std::fmt;
+#[cfg(feature = "std-os")]
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+#[cfg(feature = "std-os")]
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+use serde_json;
+
+use super::node::{Dir, File, Node, Special, Symlink};
+use super::registry::Registry;
+use super::FakeFileSystem;
+use recover::Recover;
+use FileType;
+
+/// A node as it appears in a snapshot, keyed by its absolute path.
+///
+/// Modes are written as octal strings, such as `"644"`, and owners default to the current user.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Entry {
+    File {
+        contents: Contents,
+        #[serde(default, with = "octal", skip_serializing_if = "Option::is_none")]
+        mode: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        owner: Option<(u32, u32)>,
+    },
+    Dir {
+        #[serde(default, with = "octal", skip_serializing_if = "Option::is_none")]
+        mode: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        owner: Option<(u32, u32)>,
+    },
+    Symlink {
+        target: PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        owner: Option<(u32, u32)>,
+    },
+    Fifo {
+        #[serde(default, with = "octal", skip_serializing_if = "Option::is_none")]
+        mode: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        owner: Option<(u32, u32)>,
+    },
+    Socket {
+        #[serde(default, with = "octal", skip_serializing_if = "Option::is_none")]
+        mode: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        owner: Option<(u32, u32)>,
+    },
+    BlockDevice {
+        device: u64,
+        #[serde(default, with = "octal", skip_serializing_if = "Option::is_none")]
+        mode: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        owner: Option<(u32, u32)>,
+    },
+    CharDevice {
+        device: u64,
+        #[serde(default, with = "octal", skip_serializing_if = "Option::is_none")]
+        mode: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        owner: Option<(u32, u32)>,
+    },
+}
+
+/// File contents, written as a string when they are valid UTF-8 and as an array of bytes
+/// otherwise.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Contents {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl From<&Node> for Entry {
+    fn from(node: &Node) -> Self {
+        let owner = Some(node.owner());
+
+        match *node {
+            Node::File(ref file) => Entry::File {
+                contents: match String::from_utf8(file.contents.to_vec()) {
+                    Ok(text) => Contents::Text(text),
+                    Err(err) => Contents::Binary(err.into_bytes()),
+                },
+                mode: Some(file.mode),
+                owner,
+            },
+            Node::Dir(ref dir) => Entry::Dir {
+                mode: Some(dir.mode),
+                owner,
+            },
+            Node::Symlink(ref symlink) => Entry::Symlink {
+                target: symlink.target.clone(),
+                owner,
+            },
+            Node::Special(ref special) => {
+                let (mode, device) = (Some(special.mode), special.device);
+
+                match special.file_type {
+                    FileType::Fifo => Entry::Fifo { mode, owner },
+                    FileType::Socket => Entry::Socket { mode, owner },
+                    FileType::BlockDevice => Entry::BlockDevice {
+                        device,
+                        mode,
+                        owner,
+                    },
+                    _ => Entry::CharDevice {
+                        device,
+                        mode,
+                        owner,
+                    },
+                }
+            }
+        }
+    }
+}
+
+impl Entry {
+    fn into_node(self, default_owner: (u32, u32)) -> Node {
+        let (mut node, mode, owner) = match self {
+            Entry::File {
+                contents,
+                mode,
+                owner,
+            } => {
+                let contents = match contents {
+                    Contents::Text(text) => text.into_bytes(),
+                    Contents::Binary(bytes) => bytes,
+                };
+
+                (Node::File(File::new(contents)), mode, owner)
+            }
+            Entry::Dir { mode, owner } => (Node::Dir(Dir::new()), mode, owner),
+            Entry::Symlink { target, owner } => (Node::Symlink(Symlink::new(target)), None, owner),
+            Entry::Fifo { mode, owner } => (special(FileType::Fifo, 0), mode, owner),
+            Entry::Socket { mode, owner } => (special(FileType::Socket, 0), mode, owner),
+            Entry::BlockDevice {
+                device,
+                mode,
+                owner,
+            } => (special(FileType::BlockDevice, device), mode, owner),
+            Entry::CharDevice {
+                device,
+                mode,
+                owner,
+            } => (special(FileType::CharDevice, device), mode, owner),
+        };
+        let (uid, gid) = owner.unwrap_or(default_owner);
+
+        if let Some(mode) = mode {
+            node.set_mode(mode);
+        }
+
+        node.set_owner(uid, gid);
+        node
+    }
+}
+
+fn special(file_type: FileType, device: u64) -> Node {
+    Node::Special(Special::new(file_type, device))
+}
+
+/// Returns every node in `registry` as it appears in a snapshot.
+fn entries(registry: &Registry) -> BTreeMap<PathBuf, Entry> {
+    registry
+        .nodes()
+        .into_iter()
+        .map(|(path, node)| (path, Entry::from(node)))
+        .collect()
+}
+
+/// Returns a pretty-printed JSON snapshot of every node in `registry`.
+pub fn to_string(registry: &Registry) -> Result<String> {
+    serde_json::to_string_pretty(&entries(registry))
+        .map_err(|err| Error::new(ErrorKind::InvalidData, err))
+}
+
+impl Serialize for FakeFileSystem {
+    fn serialize<S: Serializer>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error> {
+        entries(&self.registry.read().recover()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FakeFileSystem {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> ::std::result::Result<Self, D::Error> {
+        let entries = BTreeMap::<PathBuf, Entry>::deserialize(deserializer)?;
+        let mut registry = Registry::new();
+        let owner = registry.current_user();
+
+        for (path, entry) in entries {
+            if path.is_relative() {
+                return Err(de::Error::custom(format!(
+                    "path {} is not absolute",
+                    path.display()
+                )));
+            }
+
+            registry
+                .restore(path.clone(), entry.into_node(owner))
+                .map_err(|_| {
+                    de::Error::custom(format!("parent of {} is not a directory", path.display()))
+                })?;
+        }
+
+        let fs = FakeFileSystem::new();
+
+        *fs.registry.write().recover() = registry;
+
+        Ok(fs)
+    }
+}
+
+impl FakeFileSystem {
+    /// Creates a file system from a JSON snapshot, as returned by [`to_snapshot_string`].
+    ///
+    /// A snapshot maps absolute paths to nodes, such as
+    /// `{ "/etc/app.conf": { "type": "file", "contents": "key=1", "mode": "600" } }`.
+    /// Missing parent directories are created, and modes and owners may be left out to use the
+    /// defaults.
+    /// Any other format supported by serde can be used by deserializing a `FakeFileSystem`
+    /// directly.
+    ///
+    /// # Errors
+    ///
+    /// * `snapshot` is not a valid snapshot, which is reported as `ErrorKind::InvalidData`.
+    ///
+    /// [`to_snapshot_string`]: #method.to_snapshot_string
+    pub fn from_snapshot_str(snapshot: &str) -> Result<Self> {
+        serde_json::from_str(snapshot).map_err(|err| Error::new(ErrorKind::InvalidData, err))
+    }
+
+    /// Returns a pretty-printed JSON snapshot of every node in the file system, sorted by path.
+    /// Timestamps are not included.
+    pub fn to_snapshot_string(&self) -> Result<String> {
+        to_string(&self.registry.read().recover())
+    }
+
+    /// Creates a file system that is kept in the file at `path` on disk, so that its contents
+    /// outlive the process, such as between runs of an interactive tool.
+    ///
+    /// The file holds a JSON snapshot, as returned by [`to_snapshot_string`], which is loaded if
+    /// it exists.
+    /// It is then replaced atomically after each operation that changes the file system, through
+    /// any clone or open file, and may be read back but should not be changed while in use.
+    /// Like in snapshots, only the nodes are kept, without their timestamps or any settings such
+    /// as the capacity.
+    /// If replacing the file fails, the changes stay in memory and are written out along with
+    /// the next ones, and [`persist`] reports why.
+    ///
+    /// # Errors
+    ///
+    /// * The file at `path` is not a valid snapshot, which is reported as
+    ///   `ErrorKind::InvalidData`.
+    /// * The parent directory of `path` does not exist.
+    /// * Current user has insufficient permissions.
+    ///
+    /// [`to_snapshot_string`]: #method.to_snapshot_string
+    /// [`persist`]: #method.persist
+    #[cfg(feature = "std-os")]
+    pub fn persistent<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = env::current_dir()?.join(path);
+        let fs = match fs::read_to_string(&path) {
+            Ok(snapshot) => Self::from_snapshot_str(&snapshot)?,
+            Err(ref err) if err.kind() == ErrorKind::NotFound => Self::new(),
+            Err(err) => return Err(err),
+        };
+
+        fs.lock().set_backing(path)?;
+
+        Ok(fs)
+    }
+
+    /// Writes any changes that a file system created with [`persistent`] has not written to its
+    /// file yet, such as after replacing the file failed.
+    /// Other file systems are left as is.
+    ///
+    /// # Errors
+    ///
+    /// * Current user has insufficient permissions to replace the file.
+    ///
+    /// [`persistent`]: #method.persistent
+    #[cfg(feature = "std-os")]
+    pub fn persist(&self) -> Result<()> {
+        self.lock().save()
+    }
+}
+
+mod octal {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        mode: &Option<u32>,
+        serializer: S,
+    ) -> ::std::result::Result<S::Ok, S::Error> {
+        match *mode {
+            Some(mode) => serializer.serialize_str(&format!("{:o}", mode)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> ::std::result::Result<Option<u32>, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = Option<u32>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a mode as an octal string")
+            }
+
+            fn visit_str<E: de::Error>(self, mode: &str) -> ::std::result::Result<Self::Value, E> {
+                u32::from_str_radix(mode.trim_start_matches("0o"), 8)
+                    .map(Some)
+                    .map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
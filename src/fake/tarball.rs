@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::io::{self, Error, ErrorKind, Read, Result, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use tar::{Archive, Builder, EntryType, Header};
+
+use super::node::{Dir, File, Node, Special, Symlink};
+use super::registry::Registry;
+use super::FakeFileSystem;
+use recover::Recover;
+use FileType;
+
+impl FakeFileSystem {
+    /// Creates a file system from a tar archive, such as one written by [`to_tar`], with every
+    /// entry at its path in the archive below `/`.
+    ///
+    /// Files, directories, symlinks, hard links, FIFOs, and devices are restored with their modes
+    /// and owners, and missing parent directories are created with the default mode.
+    /// Timestamps are not restored.
+    ///
+    /// # Errors
+    ///
+    /// * `reader` does not contain a valid tar archive, or an entry's path is outside of the
+    ///   archive or below a file, which is reported as `ErrorKind::InvalidData`.
+    /// * An entry is of another type, such as a GNU sparse file, which is reported as
+    ///   `ErrorKind::InvalidData`.
+    /// * Reading from `reader` fails.
+    ///
+    /// [`to_tar`]: #method.to_tar
+    pub fn from_tar<R: Read>(reader: R) -> Result<Self> {
+        let mut archive = Archive::new(reader);
+        let mut registry = Registry::new();
+        let mut contents = HashMap::new();
+
+        for entry in archive.entries().map_err(malformed)? {
+            let mut entry = entry.map_err(malformed)?;
+            let path = absolute(&entry.path().map_err(malformed)?)?;
+
+            // The root always exists, and can't be replaced
+            if path == Path::new("/") {
+                continue;
+            }
+
+            let header = entry.header();
+            let uid = header.uid().map_err(malformed)? as u32;
+            let gid = header.gid().map_err(malformed)? as u32;
+            let mode = header.mode().map_err(malformed)? & 0o7777;
+            let mut node = match header.entry_type() {
+                EntryType::Regular | EntryType::Continuous => {
+                    let mut buf = Vec::with_capacity(entry.size() as usize);
+
+                    entry.read_to_end(&mut buf)?;
+
+                    let buf = Arc::new(buf);
+
+                    contents.insert(path.clone(), buf.clone());
+                    Node::File(File::new(buf))
+                }
+                EntryType::Link => {
+                    let original = entry
+                        .link_name()
+                        .map_err(malformed)?
+                        .ok_or_else(|| invalid("missing link"))?;
+                    let buf = contents
+                        .get(&absolute(&original)?)
+                        .cloned()
+                        .ok_or_else(|| invalid("hard link to a file that is not in the archive"))?;
+
+                    contents.insert(path.clone(), buf.clone());
+                    Node::File(File::new(buf))
+                }
+                EntryType::Directory => Node::Dir(Dir::new()),
+                EntryType::Symlink => {
+                    let target = entry
+                        .link_name()
+                        .map_err(malformed)?
+                        .ok_or_else(|| invalid("missing link"))?;
+
+                    Node::Symlink(Symlink::new(target.into_owned()))
+                }
+                EntryType::Fifo => Node::Special(Special::new(FileType::Fifo, 0)),
+                EntryType::Block | EntryType::Char => {
+                    let major = header.device_major().map_err(malformed)?.unwrap_or(0);
+                    let minor = header.device_minor().map_err(malformed)?.unwrap_or(0);
+                    let file_type = if header.entry_type() == EntryType::Block {
+                        FileType::BlockDevice
+                    } else {
+                        FileType::CharDevice
+                    };
+
+                    Node::Special(Special::new(file_type, makedev(major, minor)))
+                }
+                // Extended headers describe the entries after them, and are read by `tar` itself
+                EntryType::XHeader | EntryType::XGlobalHeader => continue,
+                other => return Err(invalid(&format!("unsupported entry type {:?}", other))),
+            };
+
+            node.set_mode(mode);
+            node.set_owner(uid, gid);
+            registry.restore(path.clone(), node).map_err(|_| {
+                invalid(&format!("parent of {} is not a directory", path.display()))
+            })?;
+        }
+
+        let fs = FakeFileSystem::new();
+
+        *fs.registry.write().recover() = registry;
+
+        Ok(fs)
+    }
+
+    /// Writes every node in the file system to `writer` as a tar archive, sorted by path and
+    /// below `/`, which is the inverse of [`from_tar`].
+    ///
+    /// Modes, owners, symlink targets, and device numbers are written, but timestamps are not,
+    /// so archives of the same files are identical, such as to compare against a golden file in
+    /// a test.
+    /// Sockets are left out, since tar archives can't hold them.
+    ///
+    /// # Errors
+    ///
+    /// * Writing to `writer` fails.
+    ///
+    /// [`from_tar`]: #method.from_tar
+    pub fn to_tar<W: Write>(&self, writer: W) -> Result<()> {
+        let registry = self.registry.read().recover();
+        let mut builder = Builder::new(writer);
+
+        for (path, node) in registry.nodes() {
+            let path = match path.strip_prefix("/") {
+                Ok(path) if path != Path::new("") => path.to_path_buf(),
+                _ => continue,
+            };
+            let (uid, gid) = node.owner();
+            let mut header = Header::new_gnu();
+
+            header.set_mode(node.mode() & 0o7777);
+            header.set_uid(u64::from(uid));
+            header.set_gid(u64::from(gid));
+            header.set_mtime(0);
+
+            match *node {
+                Node::File(ref file) => {
+                    header.set_entry_type(EntryType::Regular);
+                    header.set_size(file.contents.len() as u64);
+                    builder.append_data(&mut header, &path, file.contents.clone().into_reader())?;
+                }
+                Node::Dir(_) => {
+                    header.set_entry_type(EntryType::Directory);
+                    header.set_size(0);
+                    builder.append_data(&mut header, &path, io::empty())?;
+                }
+                Node::Symlink(ref symlink) => {
+                    header.set_entry_type(EntryType::Symlink);
+                    header.set_size(0);
+                    builder.append_link(&mut header, &path, &symlink.target)?;
+                }
+                Node::Special(ref special) => {
+                    header.set_entry_type(match special.file_type {
+                        FileType::Fifo => EntryType::Fifo,
+                        FileType::BlockDevice => EntryType::Block,
+                        FileType::CharDevice => EntryType::Char,
+                        _ => continue,
+                    });
+                    header.set_size(0);
+                    header.set_device_major(major(special.device))?;
+                    header.set_device_minor(minor(special.device))?;
+                    builder.append_data(&mut header, &path, io::empty())?;
+                }
+            }
+        }
+
+        builder.finish()
+    }
+}
+
+/// Returns the path of an entry in an archive below `/`.
+fn absolute(path: &Path) -> Result<PathBuf> {
+    let mut absolute = PathBuf::from("/");
+
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => absolute.push(name),
+            Component::CurDir => {}
+            _ => {
+                return Err(invalid(&format!(
+                    "entry is outside of the archive: {}",
+                    path.display()
+                )))
+            }
+        }
+    }
+
+    Ok(absolute)
+}
+
+// Device numbers are split into major and minor numbers as Linux does
+
+fn major(device: u64) -> u32 {
+    (((device >> 32) & 0xffff_f000) | ((device >> 8) & 0xfff)) as u32
+}
+
+fn minor(device: u64) -> u32 {
+    (((device >> 12) & 0xffff_ff00) | (device & 0xff)) as u32
+}
+
+fn makedev(major: u32, minor: u32) -> u64 {
+    let (major, minor) = (u64::from(major), u64::from(minor));
+
+    ((major & 0xffff_f000) << 32)
+        | ((major & 0xfff) << 8)
+        | ((minor & 0xffff_ff00) << 12)
+        | (minor & 0xff)
+}
+
+/// Reports the errors that `tar` returns for malformed archives as `ErrorKind::InvalidData`.
+fn malformed(err: Error) -> Error {
+    match err.kind() {
+        ErrorKind::Other | ErrorKind::UnexpectedEof => Error::new(ErrorKind::InvalidData, err),
+        _ => err,
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
@@ -1,9 +1,16 @@
+use std::mem;
 use std::path::{Path, PathBuf};
-use std::sync::{Mutex, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock, Weak};
 
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+use getrandom;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
 use rand;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
 use rand::Rng;
 
+use recover::Recover;
 use TempDir;
 
 use super::Registry;
@@ -12,31 +19,96 @@ const SUFFIX_LENGTH: usize = 10;
 
 #[derive(Debug, Clone)]
 pub struct FakeTempDir {
-    registry: Weak<Mutex<Registry>>,
+    registry: Weak<RwLock<Registry>>,
     path: PathBuf,
 }
 
 impl FakeTempDir {
-    pub fn new(registry: Weak<Mutex<Registry>>, base: &Path, prefix: &str) -> Self {
-        let mut rng = rand::thread_rng();
-        let suffix: String = rng.gen_ascii_chars().take(SUFFIX_LENGTH).collect();
-        let name = format!("{}_{}", prefix, suffix);
-        let path = base.join(prefix).join(name);
-
+    pub fn new(registry: Weak<RwLock<Registry>>, path: PathBuf) -> Self {
         FakeTempDir { registry, path }
     }
 }
 
+/// Generates the names of temporary directories and files, shared between clones.
+#[derive(Clone, Debug, Default)]
+pub struct TempNames(Option<Arc<AtomicUsize>>);
+
+impl TempNames {
+    /// Returns a generator whose names end in sequential numbers rather than random characters.
+    pub fn sequential() -> Self {
+        TempNames(Some(Arc::new(AtomicUsize::new(0))))
+    }
+
+    /// Returns an independent generator that continues from where this one is, so that both
+    /// generate the same names from now on.
+    pub fn snapshot(&self) -> Self {
+        TempNames(
+            self.0
+                .as_ref()
+                .map(|count| Arc::new(AtomicUsize::new(count.load(Ordering::Relaxed)))),
+        )
+    }
+
+    /// Returns the part of a new name that makes it unique.
+    pub fn next(&self) -> String {
+        match self.0 {
+            Some(ref count) => count.fetch_add(1, Ordering::Relaxed).to_string(),
+            None => random_suffix(),
+        }
+    }
+}
+
+/// Returns `SUFFIX_LENGTH` random alphanumeric characters.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn random_suffix() -> String {
+    rand::thread_rng()
+        .gen_ascii_chars()
+        .take(SUFFIX_LENGTH)
+        .collect()
+}
+
+/// Returns `SUFFIX_LENGTH` random alphanumeric characters.
+/// `rand` has no source of randomness in the browser, so the bytes come from its crypto API.
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+fn random_suffix() -> String {
+    const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+    let mut bytes = [0; SUFFIX_LENGTH];
+
+    getrandom::getrandom(&mut bytes).expect("failed to generate a temporary name");
+
+    bytes
+        .iter()
+        .map(|byte| CHARS[*byte as usize % CHARS.len()] as char)
+        .collect()
+}
+
 impl TempDir for FakeTempDir {
     fn path(&self) -> &Path {
         self.path.as_ref()
     }
+
+    fn into_path(mut self) -> PathBuf {
+        // Dropping a handle without a registry leaves the directory in place
+        self.registry = Weak::new();
+
+        mem::replace(&mut self.path, PathBuf::new())
+    }
+}
+
+impl AsRef<Path> for FakeTempDir {
+    fn as_ref(&self) -> &Path {
+        self.path.as_ref()
+    }
 }
 
 impl Drop for FakeTempDir {
     fn drop(&mut self) {
         if let Some(registry) = self.registry.upgrade() {
-            let _ = registry.lock().unwrap().remove_dir_all(&self.path);
+            let _ = registry
+                .write()
+                .recover()
+                .remove_dir_all(&self.path, |_| Ok(()));
         }
     }
 }
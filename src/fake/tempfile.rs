@@ -0,0 +1,33 @@
+use std::path::{Path, PathBuf};
+use std::sync::{RwLock, Weak};
+
+use TempFile;
+
+use super::process::Locked;
+use super::Registry;
+
+#[derive(Debug, Clone)]
+pub struct FakeTempFile {
+    registry: Weak<RwLock<Registry>>,
+    path: PathBuf,
+}
+
+impl FakeTempFile {
+    pub fn new(registry: Weak<RwLock<Registry>>, path: PathBuf) -> Self {
+        FakeTempFile { registry, path }
+    }
+}
+
+impl TempFile for FakeTempFile {
+    fn path(&self) -> &Path {
+        self.path.as_ref()
+    }
+}
+
+impl Drop for FakeTempFile {
+    fn drop(&mut self) {
+        if let Some(registry) = self.registry.upgrade() {
+            let _ = Locked::new(&registry, None).remove_file(&self.path);
+        }
+    }
+}
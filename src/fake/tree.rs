@@ -0,0 +1,143 @@
+use std::fmt::{self, Write};
+use std::path::{Path, PathBuf};
+
+use recover::Recover;
+use {FileType, Result};
+
+use super::node::Node;
+use super::FakeFileSystem;
+
+/// A line of a formatted tree, before its columns are aligned.
+struct Row {
+    mode: String,
+    size: String,
+    name: String,
+}
+
+impl FakeFileSystem {
+    /// Returns a drawing of the tree under the directory at `path`, like `exa --tree --long`
+    /// prints, with the mode and size of each node, for debugging tests and failing fixtures.
+    ///
+    /// ```text
+    /// drwxr-xr-x  - /app
+    /// .rw-r--r-- 12 ├── Cargo.toml
+    /// lrwxrwxrwx  - ├── latest -> src
+    /// drwxr-xr-x  - └── src
+    /// .rw-r--r--  0     └── main.rs
+    /// ```
+    ///
+    /// Symlinks are followed to reach `path`, but not below it.
+    /// Sizes are in bytes, and only given for files.
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    pub fn format_tree<P: AsRef<Path>>(&self, path: P) -> Result<String> {
+        self.apply(path.as_ref(), |registry, path| {
+            let node = registry.node(path)?;
+
+            Ok(format(&registry.user_path(path), node))
+        })
+    }
+}
+
+/// Formats the whole tree, from the root, as [`format_tree`] does.
+///
+/// [`format_tree`]: struct.FakeFileSystem.html#method.format_tree
+impl fmt::Display for FakeFileSystem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let registry = self.registry.read().recover();
+        let root = PathBuf::from("/");
+        let node = registry.node(&root).map_err(|_| fmt::Error)?;
+
+        f.write_str(&format(&registry.user_path(&root), node))
+    }
+}
+
+/// Formats the tree of `node`, which is at `path`.
+fn format(path: &Path, node: &Node) -> String {
+    let mut rows = vec![row(node, path.display().to_string())];
+
+    add_children(node, "", &mut rows);
+
+    let width = rows.iter().map(|row| row.size.len()).max().unwrap_or(0);
+    let mut tree = String::new();
+
+    for row in rows {
+        let _ = writeln!(
+            tree,
+            "{} {:>width$} {}",
+            row.mode,
+            row.size,
+            row.name,
+            width = width
+        );
+    }
+
+    tree
+}
+
+/// Adds rows for the descendants of `node`, with each name following `indent` and a branch.
+fn add_children(node: &Node, indent: &str, rows: &mut Vec<Row>) {
+    let children = match *node {
+        Node::Dir(ref dir) => &dir.children,
+        _ => return,
+    };
+
+    for (i, (name, child)) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        let branch = if last { "└── " } else { "├── " };
+
+        rows.push(row(
+            child,
+            format!("{}{}{}", indent, branch, Path::new(&**name).display()),
+        ));
+        add_children(
+            child,
+            &format!("{}{}", indent, if last { "    " } else { "│   " }),
+            rows,
+        );
+    }
+}
+
+fn row(node: &Node, mut name: String) -> Row {
+    let size = match *node {
+        Node::File(ref file) => file.contents.len().to_string(),
+        _ => String::from("-"),
+    };
+
+    if let Node::Symlink(ref symlink) = *node {
+        name = format!("{} -> {}", name, symlink.target.display());
+    }
+
+    Row {
+        mode: mode_string(node.file_type(), node.mode()),
+        size,
+        name,
+    }
+}
+
+/// Returns the type and permission bits of a node as `ls -l` shows them, such as `drwxr-xr-x`,
+/// but with `.` for files, as `exa` shows them.
+fn mode_string(file_type: FileType, mode: u32) -> String {
+    let kind = match file_type {
+        FileType::File => '.',
+        FileType::Dir => 'd',
+        FileType::Symlink => 'l',
+        FileType::Fifo => '|',
+        FileType::Socket => 's',
+        FileType::BlockDevice => 'b',
+        FileType::CharDevice => 'c',
+    };
+    let mut string = kind.to_string();
+
+    for shift in [6, 3, 0] {
+        let bits = mode >> shift;
+
+        string.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        string.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        string.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+    }
+
+    string
+}
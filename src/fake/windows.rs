@@ -0,0 +1,104 @@
+use std::ffi::{OsStr, OsString};
+use std::path::{Component, Path, PathBuf};
+
+/// The names that Windows reserves for devices, which cannot name files or directories, even
+/// with an extension.
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// The drive that paths without one are resolved against if the current directory is gone.
+const DEFAULT_DRIVE: &str = "C:";
+
+/// Returns the directory that the registry keeps the root of `drive` in, such as `/C:`.
+pub fn drive_root(drive: char) -> PathBuf {
+    Path::new("/").join(format!("{}:", drive.to_ascii_uppercase()))
+}
+
+/// Converts the Windows `path` to the absolute path that the registry uses for it, resolving it
+/// against `cwd`, which is such a path itself, if it has no drive or does not start at the root
+/// of one.
+/// Both `\` and `/` separate components.
+pub fn to_registry(path: &Path, cwd: &Path) -> PathBuf {
+    let path = match path.to_str() {
+        Some(path) => path.replace('\\', "/"),
+        None => return cwd.join(path),
+    };
+    let bytes = path.as_bytes();
+    let cwd_drive = match cwd.components().nth(1) {
+        Some(Component::Normal(drive)) => drive.to_os_string(),
+        _ => OsString::from(DEFAULT_DRIVE),
+    };
+
+    if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+        let root = drive_root(bytes[0] as char);
+        let rest = &path[2..];
+
+        if rest.starts_with('/') {
+            root.join(rest.trim_start_matches('/'))
+        } else if root.file_name() == Some(&cwd_drive) {
+            cwd.join(rest)
+        } else {
+            root.join(rest)
+        }
+    } else if path.starts_with('/') {
+        Path::new("/")
+            .join(cwd_drive)
+            .join(path.trim_start_matches('/'))
+    } else {
+        cwd.join(path)
+    }
+}
+
+/// Converts an absolute path in the registry back to a Windows path, such as `C:\dir\file`.
+pub fn from_registry(path: &Path) -> PathBuf {
+    let mut names = path.components().filter_map(|component| match component {
+        Component::Normal(name) => Some(name),
+        _ => None,
+    });
+    let mut windows = OsString::new();
+
+    match names.next() {
+        Some(drive) => windows.push(drive),
+        None => return PathBuf::from("\\"),
+    }
+
+    windows.push("\\");
+
+    for (i, name) in names.enumerate() {
+        if i > 0 {
+            windows.push("\\");
+        }
+
+        windows.push(name);
+    }
+
+    PathBuf::from(windows)
+}
+
+/// Joins `name` onto the Windows `path`, separating them with a backslash.
+pub fn join(path: &Path, name: &OsStr) -> PathBuf {
+    let mut joined = path.as_os_str().to_os_string();
+    let ends_with_separator = path
+        .to_str()
+        .is_some_and(|path| path.ends_with('\\') || path.ends_with('/'));
+
+    if !path.as_os_str().is_empty() && !ends_with_separator {
+        joined.push("\\");
+    }
+
+    joined.push(name);
+    PathBuf::from(joined)
+}
+
+/// Determines whether Windows reserves `name` for a device, regardless of its case and
+/// extension.
+pub fn is_reserved(name: &OsStr) -> bool {
+    let name = name.to_string_lossy();
+    let stem = name.split('.').next().unwrap_or("").trim_end();
+
+    RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+}
@@ -0,0 +1,122 @@
+use std::fmt;
+use std::io::{ErrorKind, Read, Result};
+
+use sha1::Sha1;
+use sha2::digest::Digest as _;
+use sha2::{Sha256, Sha512};
+
+/// How many bytes of a file are hashed at a time.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A hash function for [`FileSystem::hash_file`], which is available with the `hash` feature.
+///
+/// [`FileSystem::hash_file`]: trait.FileSystem.html#method.hash_file
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    /// SHA-1, which is no longer secure but is still used to identify contents, such as by Git.
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+/// The digest of a file's contents, as returned by [`FileSystem::hash_file`].
+/// It is displayed as lowercase hexadecimal, like the output of `sha256sum`.
+///
+/// [`FileSystem::hash_file`]: trait.FileSystem.html#method.hash_file
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Digest {
+    algorithm: Algorithm,
+    bytes: Vec<u8>,
+}
+
+impl Digest {
+    /// Returns the hash function that produced the digest.
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Returns the digest as raw bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the digest as lowercase hexadecimal.
+    pub fn to_hex(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl AsRef<[u8]> for Digest {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in &self.bytes {
+            write!(f, "{:02x}", byte)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The state of a hash function part of the way through its input.
+enum Hasher {
+    Sha1(Sha1),
+    Sha256(Sha256),
+    Sha512(Sha512),
+}
+
+impl Hasher {
+    fn new(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Sha1 => Hasher::Sha1(Sha1::new()),
+            Algorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            Algorithm::Sha512 => Hasher::Sha512(Sha512::new()),
+        }
+    }
+
+    fn update(&mut self, buf: &[u8]) {
+        match *self {
+            Hasher::Sha1(ref mut hasher) => hasher.update(buf),
+            Hasher::Sha256(ref mut hasher) => hasher.update(buf),
+            Hasher::Sha512(ref mut hasher) => hasher.update(buf),
+        }
+    }
+
+    fn finish(self, algorithm: Algorithm) -> Digest {
+        let bytes = match self {
+            Hasher::Sha1(hasher) => hasher.finalize().to_vec(),
+            Hasher::Sha256(hasher) => hasher.finalize().to_vec(),
+            Hasher::Sha512(hasher) => hasher.finalize().to_vec(),
+        };
+
+        Digest { algorithm, bytes }
+    }
+}
+
+/// Hashes everything that `reader` returns, a chunk at a time.
+pub fn hash_reader<R: Read>(mut reader: R, algorithm: Algorithm) -> Result<Digest> {
+    let mut hasher = Hasher::new(algorithm);
+    let mut buf = vec![0; CHUNK_SIZE];
+
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => return Ok(hasher.finish(algorithm)),
+            Ok(n) => hasher.update(&buf[..n]),
+            Err(ref err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Hashes contents that are already in memory.
+#[cfg(feature = "mock")]
+pub fn hash_bytes(buf: &[u8], algorithm: Algorithm) -> Digest {
+    let mut hasher = Hasher::new(algorithm);
+
+    hasher.update(buf);
+    hasher.finish(algorithm)
+}
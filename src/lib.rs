@@ -1,32 +1,156 @@
-#[cfg(any(feature = "mock", test))]
-extern crate pseudo;
+#[cfg(feature = "lock")]
+extern crate fs4;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown", feature = "temp"))]
+extern crate getrandom;
+extern crate glob;
+#[cfg(feature = "include_dir")]
+extern crate include_dir;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown", feature = "fake"))]
+extern crate js_sys;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(feature = "object_store")]
+extern crate object_store;
+#[cfg(feature = "proptest")]
+extern crate proptest;
 #[cfg(feature = "temp")]
 extern crate rand;
-#[cfg(feature = "temp")]
-extern crate tempdir;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
+#[cfg(feature = "hash")]
+extern crate sha1;
+#[cfg(feature = "hash")]
+extern crate sha2;
+#[cfg(feature = "tar")]
+extern crate tar;
+#[cfg(all(feature = "std-os", feature = "temp"))]
+extern crate tempfile;
+#[cfg(any(feature = "async", feature = "object_store"))]
+extern crate tokio;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+#[cfg(feature = "fake")]
+extern crate unicode_normalization;
+#[cfg(windows)]
+extern crate winapi;
+#[cfg(feature = "zip")]
+extern crate zip;
 
 use std::ffi::OsString;
-use std::io::Result;
+use std::io::{self, BufRead, BufReader, ErrorKind, Lines, Read, Seek, Write};
 use std::path::{Path, PathBuf};
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
 
+#[cfg(feature = "async")]
+pub use async_fs::{AsyncFileSystem, FsFuture};
+pub use chroot::ChrootFileSystem;
+pub use dynamic::{DynDirEntry, DynFileSystem, DynMetadata, DynOpenFile, DynReadDir, DynWalkDir};
+pub use environment::Environment;
+use error::Context;
+pub use error::{is_filesystem_loop, Error, Result};
+#[cfg(feature = "proptest")]
+pub use fake::ArbitraryOptions;
 #[cfg(feature = "fake")]
-pub use fake::{FakeFileSystem, FakeTempDir};
-#[cfg(any(feature = "mock", test))]
-pub use mock::{FakeError, MockFileSystem};
+pub use fake::{
+    AllPaths, CaseSensitivity, FakeFileSystem, FakeOpenFile, FsChange, Operation, OperationKind,
+    PathLimits, UnicodeNormalization,
+};
+#[cfg(all(feature = "fake", feature = "temp"))]
+pub use fake::{FakeTempDir, FakeTempFile};
+#[cfg(feature = "hash")]
+pub use hash::{Algorithm, Digest};
+#[cfg(feature = "mock")]
+pub use mock::{FakeError, MockDirEntry, MockFileSystem, MockMetadata, MockOpenFile};
+#[cfg(all(feature = "mock", feature = "temp"))]
+pub use mock::{MockTempDir, MockTempFile};
+pub use mount::MountFileSystem;
+#[cfg(feature = "object_store")]
+pub use object::ObjectStoreFileSystem;
+#[cfg(feature = "std-os")]
 pub use os::OsFileSystem;
+#[cfg(all(feature = "std-os", feature = "temp"))]
+pub use os::{OsTempDir, OsTempFile};
+pub use overlay::OverlayFileSystem;
+pub use pattern::GlobOptions;
+pub use read_dir::ReadDirOptions;
 #[cfg(feature = "temp")]
-pub use os::OsTempDir;
+pub use temp::TempOptions;
+pub use trace::{Call, TracingFileSystem};
+pub use walk::{WalkDir, WalkDirEntry, WalkDirOptions};
+#[cfg(feature = "watch")]
+pub use watch::{FsEvent, FsWatcher, WatchFileSystem};
+#[cfg(feature = "zip")]
+pub use zip_fs::ZipFileSystem;
 
+#[cfg(feature = "assertions")]
+pub mod assertions;
+#[cfg(feature = "async")]
+mod async_fs;
+mod chroot;
+#[cfg(feature = "conformance")]
+pub mod conformance;
+mod dynamic;
+mod environment;
+mod error;
 #[cfg(feature = "fake")]
 mod fake;
-#[cfg(any(feature = "mock", test))]
+#[cfg(feature = "hash")]
+mod hash;
+#[cfg(feature = "mock")]
 mod mock;
+mod mount;
+mod normalize;
+#[cfg(feature = "object_store")]
+mod object;
+#[cfg(feature = "std-os")]
 mod os;
+mod overlay;
+mod pattern;
+mod read_dir;
+mod recover;
+#[cfg(feature = "temp")]
+mod temp;
+#[cfg(feature = "testkit")]
+pub mod testkit;
+mod trace;
+mod walk;
+#[cfg(feature = "watch")]
+mod watch;
+#[cfg(feature = "zip")]
+mod zip_fs;
 
 /// Provides standard file system operations.
+///
+/// Backends only need to implement the methods without defaults, which work on one node at a
+/// time.
+/// The others, such as [`read_file`], [`create_dir_all`], and [`copy_file`], are built on them,
+/// and can be overridden where a backend can do better, such as copying a file without reading
+/// it into memory.
+///
+/// Every file system in this crate is `Send` and `Sync`, as long as the file systems or readers
+/// it wraps are, so a clone or a reference can be handed to other threads without wrapping it in
+/// a lock.
+/// Each operation is safe to run concurrently with any other, although operations made up of
+/// several steps, such as [`create_dir_all`] and [`copy_file`], may see the changes of other
+/// threads between steps.
+/// The trait itself doesn't require `Send` or `Sync`, so that single-threaded backends and
+/// `Box<dyn DynFileSystem>` can implement it; code that shares a generic file system between
+/// threads should add those bounds.
+///
+/// [`read_file`]: #method.read_file
+/// [`create_dir_all`]: #method.create_dir_all
+/// [`copy_file`]: #method.copy_file
 pub trait FileSystem {
-    type DirEntry: DirEntry;
+    type DirEntry: DirEntry<Metadata = Self::Metadata>;
     type ReadDir: ReadDir<Self::DirEntry>;
+    type Metadata: Metadata;
+    type OpenFile: OpenFile;
+    type WalkDir: Iterator<Item = io::Result<WalkDirEntry>>;
 
     /// Returns the current working directory.
     /// This is based on [`std::env::current_dir`].
@@ -39,10 +163,59 @@ pub trait FileSystem {
     /// [`std::env::set_current_dir`]: https://doc.rust-lang.org/std/env/fn.set_current_dir.html
     fn set_current_dir<P: AsRef<Path>>(&self, path: P) -> Result<()>;
 
+    /// Returns metadata about the node at `path`.
+    /// This is based on [`std::fs::metadata`].
+    ///
+    /// [`std::fs::metadata`]: https://doc.rust-lang.org/std/fs/fn.metadata.html
+    fn metadata<P: AsRef<Path>>(&self, path: P) -> Result<Self::Metadata>;
+
+    /// Returns the absolute form of `path`, with `.` and `..` components removed and all symlinks
+    /// resolved.
+    /// This is based on [`std::fs::canonicalize`].
+    ///
+    /// [`std::fs::canonicalize`]: https://doc.rust-lang.org/std/fs/fn.canonicalize.html
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * A non-final component in `path` is not a directory.
+    fn canonicalize<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf>;
+
     /// Determines whether the path exists and points to a directory.
-    fn is_dir<P: AsRef<Path>>(&self, path: P) -> bool;
+    fn is_dir<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.metadata(path).map(|m| m.is_dir()).unwrap_or(false)
+    }
     /// Determines whether the path exists and points to a file.
-    fn is_file<P: AsRef<Path>>(&self, path: P) -> bool;
+    fn is_file<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.metadata(path).map(|m| m.is_file()).unwrap_or(false)
+    }
+    /// Determines whether the path exists, following symlinks.
+    /// Returns `false` for broken symlinks and whenever the path cannot be accessed.
+    /// This is based on [`std::path::Path::exists`].
+    ///
+    /// [`std::path::Path::exists`]: https://doc.rust-lang.org/std/path/struct.Path.html#method.exists
+    fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.metadata(path).is_ok()
+    }
+    /// Determines whether the path exists, following symlinks.
+    /// Unlike [`exists`](#method.exists), errors other than the path not existing are returned.
+    /// This is based on [`std::fs::try_exists`].
+    ///
+    /// [`std::fs::try_exists`]: https://doc.rust-lang.org/std/fs/fn.exists.html
+    ///
+    /// # Errors
+    ///
+    /// * Current user has insufficient permissions to traverse a parent of `path`.
+    /// * `path` contains a symlink loop.
+    fn try_exists<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+
+        match self.metadata(path) {
+            Ok(_) => Ok(true),
+            Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err).context("try_exists", &[path]),
+        }
+    }
 
     /// Creates a new directory.
     /// This is based on [`std::fs::create_dir`].
@@ -53,7 +226,33 @@ pub trait FileSystem {
     /// This is based on [`std::fs::create_dir`].
     ///
     /// [`std::fs::create_dir_all`]: https://doc.rust-lang.org/std/fs/fn.create_dir_all.html
-    fn create_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()>;
+    fn create_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let create = || -> Result<()> {
+            if path.as_os_str().is_empty() {
+                return Ok(());
+            }
+
+            // Parents are only created if the directory can't be, as by `std::fs::create_dir_all`
+            match self.create_dir(path) {
+                Err(ref err) if err.kind() == ErrorKind::NotFound => {}
+                Err(_) if self.is_dir(path) => return Ok(()),
+                result => return result,
+            }
+
+            if let Some(parent) = path.parent() {
+                self.create_dir_all(parent)?;
+            }
+
+            // Another thread may have created the directory in the meantime
+            match self.create_dir(path) {
+                Err(_) if self.is_dir(path) => Ok(()),
+                result => result,
+            }
+        };
+
+        create().context("create_dir_all", &[path])
+    }
     /// Removes an empty directory.
     /// This is based on [`std::fs::remove_dir`].
     ///
@@ -63,12 +262,130 @@ pub trait FileSystem {
     /// This is based on [`std::fs::remove_dir_all`].
     ///
     /// [`std::fs::remove_dir_all`]: https://doc.rust-lang.org/std/fs/fn.remove_dir_all.html
-    fn remove_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()>;
+    fn remove_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let remove = || -> Result<()> {
+            let entries = self.walk_dir(path)?.collect::<io::Result<Vec<_>>>()?;
+
+            if entries
+                .first()
+                .is_some_and(|root| !root.file_type().is_dir())
+            {
+                return Err(ErrorKind::NotADirectory.into());
+            }
+
+            // Directories are walked before their contents, so they are removed after them
+            for entry in entries.iter().rev() {
+                if entry.file_type().is_dir() {
+                    self.remove_dir(entry.path())?;
+                } else {
+                    self.remove_file(entry.path())?;
+                }
+            }
+
+            Ok(())
+        };
+
+        remove().context("remove_dir_all", &[path])
+    }
     /// Returns an iterator over the entries in a directory.
     /// This is based on [`std::fs::read_dir`].
     ///
     /// [`std::fs::read_dir`]: https://doc.rust-lang.org/std/fs/fn.read_dir.html
     fn read_dir<P: AsRef<Path>>(&self, path: P) -> Result<Self::ReadDir>;
+    /// Returns the entries in a directory, sorted by file name.
+    /// Unlike [`read_dir`](#tymethod.read_dir), whose order depends on the file system, entries
+    /// are returned in the same order on every backend and platform.
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * `path` is not a directory.
+    /// * An entry cannot be read.
+    /// * Current user has insufficient permissions.
+    fn read_dir_sorted<P: AsRef<Path>>(&self, path: P) -> Result<Vec<Self::DirEntry>> {
+        let mut entries = self.read_dir(path)?.collect::<io::Result<Vec<_>>>()?;
+
+        entries.sort_by_key(DirEntry::file_name);
+
+        Ok(entries)
+    }
+    /// Returns the entries of the directory at `path` that `options` lets through, such as only
+    /// those with certain extensions.
+    /// File systems that can tell the names of entries apart without looking them up, like the
+    /// fake, skip the rest without creating their entries.
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * `path` is not a directory.
+    /// * An entry cannot be read.
+    /// * Current user has insufficient permissions.
+    fn read_dir_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &ReadDirOptions,
+    ) -> Result<Vec<Self::DirEntry>> {
+        let mut entries = Vec::new();
+
+        for entry in self.read_dir(path)? {
+            let entry = entry?;
+
+            if options.matches(&entry.file_name()) {
+                entries.push(entry);
+            }
+        }
+
+        if options.sorted() {
+            entries.sort_by_key(DirEntry::file_name);
+        }
+
+        Ok(entries)
+    }
+    /// Returns an iterator over `path` and all of its descendants.
+    /// Directories are returned before their contents and symbolic links are not followed.
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * Current user has insufficient permissions.
+    fn walk_dir<P: AsRef<Path>>(&self, path: P) -> Result<Self::WalkDir> {
+        self.walk_dir_with_options(path, &WalkDirOptions::new())
+    }
+    /// Returns an iterator over `path` and its descendants, as configured by `options`.
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * Current user has insufficient permissions.
+    fn walk_dir_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &WalkDirOptions,
+    ) -> Result<Self::WalkDir>;
+    /// Returns the paths that match the glob `pattern`, such as `src/**/*.rs`.
+    /// This is based on [`glob::glob`].
+    ///
+    /// Relative patterns are matched against the current directory and return relative paths.
+    ///
+    /// # Errors
+    ///
+    /// * `pattern` is invalid.
+    /// * A directory being searched cannot be read.
+    ///
+    /// [`glob::glob`]: https://docs.rs/glob/0.3/glob/fn.glob.html
+    fn glob(&self, pattern: &str) -> Result<Vec<PathBuf>> {
+        self.glob_with(pattern, &GlobOptions::new())
+    }
+    /// Returns the paths that match the glob `pattern`, as configured by `options`.
+    ///
+    /// # Errors
+    ///
+    /// * `pattern` is invalid.
+    /// * A directory being searched cannot be read.
+    fn glob_with(&self, pattern: &str, options: &GlobOptions) -> Result<Vec<PathBuf>> {
+        pattern::glob(self, pattern, options).context("glob", &[Path::new(pattern)])
+    }
 
     /// Writes `buf` to a new file at `path`.
     ///
@@ -80,7 +397,14 @@ pub trait FileSystem {
     fn create_file<P, B>(&self, path: P, buf: B) -> Result<()>
     where
         P: AsRef<Path>,
-        B: AsRef<[u8]>;
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+
+        self.open_with_options(path, OpenOptions::new().write(true).create_new(true))
+            .and_then(|mut file| Ok(file.write_all(buf.as_ref())?))
+            .context("create_file", &[path])
+    }
     /// Writes `buf` to a new or existing file at `buf`.
     /// This will overwrite any contents that already exist.
     ///
@@ -91,7 +415,16 @@ pub trait FileSystem {
     fn write_file<P, B>(&self, path: P, buf: B) -> Result<()>
     where
         P: AsRef<Path>,
-        B: AsRef<[u8]>;
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+        let mut options = OpenOptions::new();
+
+        options.write(true).create(true).truncate(true);
+        self.open_with_options(path, &options)
+            .and_then(|mut file| Ok(file.write_all(buf.as_ref())?))
+            .context("write_file", &[path])
+    }
     /// Writes `buf` to an existing file at `buf`.
     /// This will overwrite any contents that already exist.
     ///
@@ -103,7 +436,110 @@ pub trait FileSystem {
     fn overwrite_file<P, B>(&self, path: P, buf: B) -> Result<()>
     where
         P: AsRef<Path>,
-        B: AsRef<[u8]>;
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+
+        self.open_with_options(path, OpenOptions::new().write(true).truncate(true))
+            .and_then(|mut file| Ok(file.write_all(buf.as_ref())?))
+            .context("overwrite_file", &[path])
+    }
+    /// Truncates or extends the existing file at `path` to `size` bytes, filling any extension
+    /// with zeros.
+    /// This is based on [`std::fs::File::set_len`].
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * The node at `path` is a directory.
+    /// * Current user has insufficient permissions.
+    ///
+    /// [`std::fs::File::set_len`]: https://doc.rust-lang.org/std/fs/struct.File.html#method.set_len
+    fn set_len<P: AsRef<Path>>(&self, path: P, size: u64) -> Result<()> {
+        let path = path.as_ref();
+
+        self.open_with_options(path, OpenOptions::new().write(true))
+            .and_then(|file| Ok(file.set_len(size)?))
+            .context("set_len", &[path])
+    }
+    /// Waits until the contents of the file at `path` are stored durably, such as on disk.
+    /// This is based on [`std::fs::File::sync_all`].
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * Current user has insufficient permissions.
+    ///
+    /// [`std::fs::File::sync_all`]: https://doc.rust-lang.org/std/fs/struct.File.html#method.sync_all
+    fn sync_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.open(path)
+            .and_then(|file| Ok(file.sync_all()?))
+            .context("sync_file", &[path])
+    }
+    /// Waits until the entries of the directory at `path`, such as ones created, removed, or
+    /// renamed, are stored durably.
+    /// On Windows, this only checks that `path` exists, since directories cannot be synced.
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * Current user has insufficient permissions.
+    fn sync_dir<P: AsRef<Path>>(&self, path: P) -> Result<()>;
+    /// Writes everything read from `reader` to a new or existing file at `path`, returning the
+    /// number of bytes written.
+    /// This will overwrite any contents that already exist.
+    /// Unlike [`write_file`], the contents are copied in chunks rather than all at once, as by
+    /// [`std::io::copy`].
+    ///
+    /// # Errors
+    ///
+    /// * The parent directory of `path` does not exist.
+    /// * The node at `path` is a directory.
+    /// * Current user has insufficient permissions.
+    /// * Reading from `reader` fails, which leaves the file with the chunks written so far.
+    ///
+    /// [`write_file`]: #method.write_file
+    /// [`std::io::copy`]: https://doc.rust-lang.org/std/io/fn.copy.html
+    fn write_from_reader<P, R>(&self, path: P, reader: &mut R) -> Result<u64>
+    where
+        P: AsRef<Path>,
+        R: Read + ?Sized,
+    {
+        let path = path.as_ref();
+        let mut options = OpenOptions::new();
+
+        options.write(true).create(true).truncate(true);
+        io::copy(reader, &mut self.open_with_options(path, &options)?)
+            .context("write_from_reader", &[path])
+    }
+    /// Replaces the contents of the file at `path` with `buf` atomically, by writing them to a
+    /// temporary file in the same directory and renaming it over `path`.
+    /// Readers see either the old contents or the new ones, never a partial write.
+    /// The replaced file's permissions are not preserved.
+    ///
+    /// # Errors
+    ///
+    /// * The parent directory of `path` does not exist.
+    /// * The node at `path` is a directory.
+    /// * Current user has insufficient permissions.
+    ///
+    /// The temporary file is removed if writing or renaming it fails, leaving `path` unchanged.
+    fn write_file_atomic<P, B>(&self, path: P, buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+        let temp = temp_sibling(path).context("write_file_atomic", &[path])?;
+
+        self.create_file(&temp, buf)
+            .and_then(|_| self.rename(&temp, path))
+            .inspect_err(|_| {
+                let _ = self.remove_file(&temp);
+            })
+    }
     /// Returns the contents of `path`.
     ///
     /// # Errors
@@ -111,7 +547,14 @@ pub trait FileSystem {
     /// * `path` does not exist.
     /// * `path` is a directory.
     /// * Current user has insufficient permissions.
-    fn read_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>>;
+    fn read_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
+        let path = path.as_ref();
+        let mut contents = Vec::new();
+
+        self.read_file_into(path, &mut contents)
+            .map(|_| contents)
+            .context("read_file", &[path])
+    }
     /// Returns the contents of `path` as a string.
     ///
     /// # Errors
@@ -120,7 +563,16 @@ pub trait FileSystem {
     /// * `path` is a directory.
     /// * Current user has insufficient permissions.
     /// * Contents are not valid UTF-8
-    fn read_file_to_string<P: AsRef<Path>>(&self, path: P) -> Result<String>;
+    fn read_file_to_string<P: AsRef<Path>>(&self, path: P) -> Result<String> {
+        let path = path.as_ref();
+
+        self.read_file(path)
+            .and_then(|contents| {
+                String::from_utf8(contents)
+                    .map_err(|err| io::Error::new(ErrorKind::InvalidData, err).into())
+            })
+            .context("read_file_to_string", &[path])
+    }
     /// Writes the contents of `path` into the buffer. If successful, returns
     /// the number of bytes that were read.
     ///
@@ -129,10 +581,114 @@ pub trait FileSystem {
     /// * `path` does not exist.
     /// * `path` is a directory.
     /// * Current user has insufficient permissions.
-    fn read_file_into<P, B>(&self, path: P, buf: B) -> Result<usize>
+    fn read_file_into<P, B>(&self, path: P, mut buf: B) -> Result<usize>
     where
         P: AsRef<Path>,
-        B: AsMut<Vec<u8>>;
+        B: AsMut<Vec<u8>>,
+    {
+        let path = path.as_ref();
+
+        self.open(path)
+            .and_then(|mut file| Ok(file.read_to_end(buf.as_mut())?))
+            .context("read_file_into", &[path])
+    }
+    /// Opens the file at `path` in read-only mode.
+    /// This is based on [`std::fs::File::open`].
+    ///
+    /// [`std::fs::File::open`]: https://doc.rust-lang.org/std/fs/struct.File.html#method.open
+    fn open<P: AsRef<Path>>(&self, path: P) -> Result<Self::OpenFile> {
+        let path = path.as_ref();
+
+        self.open_with_options(path, OpenOptions::new().read(true))
+            .context("open", &[path])
+    }
+    /// Opens the file at `path` with the given options.
+    /// This is based on [`std::fs::OpenOptions::open`].
+    ///
+    /// # Errors
+    ///
+    /// * `options` is not a valid combination of flags.
+    /// * `path` does not exist and `options` does not allow creating it.
+    /// * `path` exists and `options` requires creating a new file.
+    /// * `path` is a directory.
+    /// * Current user has insufficient permissions.
+    ///
+    /// [`std::fs::OpenOptions::open`]: https://doc.rust-lang.org/std/fs/struct.OpenOptions.html#method.open
+    fn open_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &OpenOptions,
+    ) -> Result<Self::OpenFile>;
+    /// Returns a builder for opening the file at `path`, which sets the same flags as
+    /// [`OpenOptions`] in a single chain, like [`std::fs::OpenOptions`] does, and opens the file
+    /// with [`open_with_options`].
+    ///
+    /// ```
+    /// use std::io::Write;
+    ///
+    /// use filesystem::{FakeFileSystem, FileSystem};
+    ///
+    /// let fs = FakeFileSystem::new();
+    /// let mut file = fs
+    ///     .open_with("/log")
+    ///     .append(true)
+    ///     .create(true)
+    ///     .open()
+    ///     .unwrap();
+    ///
+    /// file.write_all(b"started").unwrap();
+    /// ```
+    ///
+    /// [`OpenOptions`]: struct.OpenOptions.html
+    /// [`std::fs::OpenOptions`]: https://doc.rust-lang.org/std/fs/struct.OpenOptions.html
+    /// [`open_with_options`]: #tymethod.open_with_options
+    fn open_with<P: AsRef<Path>>(&self, path: P) -> OpenWith<'_, Self>
+    where
+        Self: Sized,
+    {
+        OpenWith {
+            fs: self,
+            path: path.as_ref().to_path_buf(),
+            options: OpenOptions::new(),
+        }
+    }
+    /// Opens the file at `path` in read-only mode, wrapped in a [`BufReader`].
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * Current user has insufficient permissions.
+    ///
+    /// [`BufReader`]: https://doc.rust-lang.org/std/io/struct.BufReader.html
+    fn open_buffered<P: AsRef<Path>>(&self, path: P) -> Result<BufReader<Self::OpenFile>> {
+        self.open(path).map(BufReader::new)
+    }
+    /// Returns an iterator over the lines of the file at `path`, without their line endings.
+    /// The file is read as the iterator advances, rather than all at once.
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * Current user has insufficient permissions.
+    fn read_lines<P: AsRef<Path>>(&self, path: P) -> Result<Lines<BufReader<Self::OpenFile>>> {
+        self.open_buffered(path).map(BufRead::lines)
+    }
+    /// Returns the digest of the contents of the file at `path` with `algorithm`, such as to
+    /// verify a download against a published checksum.
+    /// The file is read a chunk at a time, rather than all at once.
+    /// This is available with the `hash` feature.
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * `path` is a directory.
+    /// * Current user has insufficient permissions.
+    #[cfg(feature = "hash")]
+    fn hash_file<P: AsRef<Path>>(&self, path: P, algorithm: Algorithm) -> Result<Digest> {
+        let path = path.as_ref();
+
+        hash::hash_reader(self.open(path)?, algorithm).context("hash_file", &[path])
+    }
     /// Removes the file at `path`.
     /// This is based on [`std::fs::remove_file`].
     ///
@@ -143,6 +699,36 @@ pub trait FileSystem {
     ///
     /// [`std::fs::copy`]: https://doc.rust-lang.org/std/fs/fn.copy.html
     fn copy_file<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref(), to.as_ref());
+        let copy = || -> Result<()> {
+            let readonly = self.metadata(from)?.readonly();
+
+            self.write_file(to, self.read_file(from)?)?;
+
+            if readonly {
+                self.set_readonly(to, true)?;
+            }
+
+            Ok(())
+        };
+
+        copy().context("copy_file", &[from, to])
+    }
+    /// Recursively copies the directory at `from` and all of its contents to the new path `to`.
+    /// The permissions of every copied file and directory are preserved,
+    /// and symbolic links are copied as links rather than followed.
+    ///
+    /// # Errors
+    ///
+    /// * `from` does not exist or is not a directory.
+    /// * A file or directory already exists at `to`.
+    /// * `to` is inside `from`.
+    /// * Current user has insufficient permissions.
+    fn copy_dir<P, Q>(&self, from: P, to: Q) -> Result<()>
     where
         P: AsRef<Path>,
         Q: AsRef<Path>;
@@ -156,6 +742,42 @@ pub trait FileSystem {
     where
         P: AsRef<Path>,
         Q: AsRef<Path>;
+    /// Renames a file or directory like [`rename`](#tymethod.rename), but if `from` and `to` are
+    /// on different devices, copies `from` to `to` and then removes `from` instead, as `mv` does.
+    ///
+    /// Symbolic links are followed, so a link is replaced by a copy of what it points to.
+    ///
+    /// # Errors
+    ///
+    /// * `rename` fails with anything but `ErrorKind::CrossesDevices`.
+    /// * `from` is a directory and `to` is a directory that is not empty.
+    /// * Copying or removing fails, which may leave `to` partially copied.
+    fn rename_or_copy<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref(), to.as_ref());
+
+        match self.rename(from, to) {
+            Err(ref err) if err.kind() == ErrorKind::CrossesDevices => {}
+            result => return result,
+        }
+
+        if !self.is_dir(from) {
+            self.copy_file(from, to)?;
+
+            return self.remove_file(from);
+        }
+
+        // Like `rename`, replace an empty directory
+        if self.is_dir(to) {
+            self.remove_dir(to)?;
+        }
+
+        self.copy_dir(from, to)?;
+        self.remove_dir_all(from)
+    }
 
     /// Returns `true` if `path` is a readonly file.
     ///
@@ -163,7 +785,13 @@ pub trait FileSystem {
     ///
     /// * `path` does not exist.
     /// * Current user has insufficient permissions.
-    fn readonly<P: AsRef<Path>>(&self, path: P) -> Result<bool>;
+    fn readonly<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+
+        self.metadata(path)
+            .map(|m| m.readonly())
+            .context("readonly", &[path])
+    }
     /// Sets or unsets the readonly flag of `path`.
     ///
     /// # Errors
@@ -171,18 +799,543 @@ pub trait FileSystem {
     /// * `path` does not exist.
     /// * Current user has insufficient permissions.
     fn set_readonly<P: AsRef<Path>>(&self, path: P, readonly: bool) -> Result<()>;
+    /// Returns `true` if `path` is hidden by the convention of its platform: on Windows, that it
+    /// has the `FILE_ATTRIBUTE_HIDDEN` attribute, and elsewhere, that its name starts with `.`.
+    ///
+    /// File systems without attributes go by the name on every platform, while
+    /// `FakeFileSystem` follows the platform its paths are for, checking a simulated attribute
+    /// if created with `with_windows_paths`.
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * Current user has insufficient permissions.
+    fn is_hidden<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+
+        self.metadata(path)
+            .map(|_| path.file_name().is_some_and(read_dir::is_hidden_name))
+            .context("is_hidden", &[path])
+    }
+
+    /// Returns the last modification time of `path`.
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * Current user has insufficient permissions.
+    fn modified<P: AsRef<Path>>(&self, path: P) -> Result<SystemTime> {
+        let path = path.as_ref();
+
+        self.metadata(path)
+            .and_then(|m| Ok(m.modified()?))
+            .context("modified", &[path])
+    }
+    /// Sets the last modification time of `path`.
+    /// This is based on [`std::fs::File::set_modified`].
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * Current user has insufficient permissions.
+    ///
+    /// [`std::fs::File::set_modified`]: https://doc.rust-lang.org/std/fs/struct.File.html#method.set_modified
+    fn set_modified<P: AsRef<Path>>(&self, path: P, time: SystemTime) -> Result<()>;
+    /// Returns the last access time of `path`.
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * Current user has insufficient permissions.
+    fn accessed<P: AsRef<Path>>(&self, path: P) -> Result<SystemTime> {
+        let path = path.as_ref();
+
+        self.metadata(path)
+            .and_then(|m| Ok(m.accessed()?))
+            .context("accessed", &[path])
+    }
+    /// Sets the last access time of `path`.
+    /// This is based on [`std::fs::File::set_times`].
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * Current user has insufficient permissions.
+    ///
+    /// [`std::fs::File::set_times`]: https://doc.rust-lang.org/std/fs/struct.File.html#method.set_times
+    fn set_accessed<P: AsRef<Path>>(&self, path: P, time: SystemTime) -> Result<()>;
 
     /// Returns the length of the node at the path
     /// or 0 if the node does not exist.
-    fn len<P: AsRef<Path>>(&self, path: P) -> u64;
+    fn len<P: AsRef<Path>>(&self, path: P) -> u64 {
+        self.metadata(path).map(|m| m.len()).unwrap_or(0)
+    }
+    /// Returns the total length of the files among the descendants of the directory at `path`,
+    /// like `du --apparent-size --bytes`.
+    /// If `follow_links` is set, symbolic links to files are counted as those files and symbolic
+    /// links to directories are descended into, otherwise they are not counted.
+    /// A file is counted once for each path that it is reached through.
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * `path` is not a directory.
+    /// * A directory being walked cannot be read.
+    /// * `follow_links` is set and a symbolic link is broken or leads to one of its ancestors.
+    fn dir_size<P: AsRef<Path>>(&self, path: P, follow_links: bool) -> Result<u64> {
+        let path = path.as_ref();
+        let size = || -> Result<u64> {
+            let mut options = WalkDirOptions::new();
+            let mut size = 0;
+
+            options.follow_links(follow_links);
+
+            for entry in self.walk_dir_with_options(path, &options)? {
+                let entry = entry?;
+
+                if entry.depth() == 0 && !entry.file_type().is_dir() {
+                    return Err(ErrorKind::NotADirectory.into());
+                }
+
+                if entry.file_type().is_file() {
+                    size += self.metadata(entry.path())?.len();
+                }
+            }
+
+            Ok(size)
+        };
+
+        size().context("dir_size", &[path])
+    }
+
+    /// Returns the number of bytes that the current user can still write to the file system that
+    /// `path` is on, such as to check for enough free space before a large download.
+    /// This is based on `statvfs(3)` on Unix and `GetDiskFreeSpaceExW` on Windows.
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * Current user has insufficient permissions to traverse a parent of `path`.
+    fn available_space<P: AsRef<Path>>(&self, path: P) -> Result<u64>;
+    /// Returns the size in bytes of the file system that `path` is on.
+    /// This is based on `statvfs(3)` on Unix and `GetDiskFreeSpaceExW` on Windows.
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * Current user has insufficient permissions to traverse a parent of `path`.
+    fn total_space<P: AsRef<Path>>(&self, path: P) -> Result<u64>;
 }
 
 pub trait DirEntry {
+    type Metadata: Metadata;
+
     fn file_name(&self) -> OsString;
     fn path(&self) -> PathBuf;
+    /// Returns the type of the entry, without following symbolic links.
+    /// This is based on [`std::fs::DirEntry::file_type`], which usually doesn't need to query
+    /// the file system again.
+    ///
+    /// [`std::fs::DirEntry::file_type`]: https://doc.rust-lang.org/std/fs/struct.DirEntry.html#method.file_type
+    fn file_type(&self) -> io::Result<FileType> {
+        self.metadata().map(|metadata| metadata.file_type())
+    }
+    /// Returns metadata about the entry, without following symbolic links.
+    /// This is based on [`std::fs::DirEntry::metadata`].
+    ///
+    /// # Errors
+    ///
+    /// * The entry was removed after the directory was read.
+    /// * Current user has insufficient permissions.
+    ///
+    /// [`std::fs::DirEntry::metadata`]: https://doc.rust-lang.org/std/fs/struct.DirEntry.html#method.metadata
+    fn metadata(&self) -> io::Result<Self::Metadata>;
 }
 
-pub trait ReadDir<T: DirEntry>: Iterator<Item = Result<T>> {}
+pub trait ReadDir<T: DirEntry>: Iterator<Item = io::Result<T>> {}
+
+/// An open file that can be read from, written to, and seeked within.
+#[cfg(not(feature = "lock"))]
+pub trait OpenFile: Read + Write + Seek {
+    /// Truncates or extends the file to `size` bytes, filling any extension with zeros, without
+    /// moving the cursor.
+    /// This is based on [`std::fs::File::set_len`].
+    ///
+    /// # Errors
+    ///
+    /// * The file was not opened for writing, in which case the error kind is
+    ///   `ErrorKind::InvalidInput`.
+    ///
+    /// [`std::fs::File::set_len`]: https://doc.rust-lang.org/std/fs/struct.File.html#method.set_len
+    fn set_len(&self, size: u64) -> io::Result<()>;
+    /// Waits until the contents of the file are stored durably, such as on disk.
+    /// This is based on [`std::fs::File::sync_all`].
+    ///
+    /// [`std::fs::File::sync_all`]: https://doc.rust-lang.org/std/fs/struct.File.html#method.sync_all
+    fn sync_all(&self) -> io::Result<()>;
+}
+
+/// An open file that can be read from, written to, seeked within, and locked.
+#[cfg(feature = "lock")]
+pub trait OpenFile: Read + Write + Seek + LockableFile {
+    /// Truncates or extends the file to `size` bytes, filling any extension with zeros, without
+    /// moving the cursor.
+    /// This is based on [`std::fs::File::set_len`].
+    ///
+    /// # Errors
+    ///
+    /// * The file was not opened for writing, in which case the error kind is
+    ///   `ErrorKind::InvalidInput`.
+    ///
+    /// [`std::fs::File::set_len`]: https://doc.rust-lang.org/std/fs/struct.File.html#method.set_len
+    fn set_len(&self, size: u64) -> io::Result<()>;
+    /// Waits until the contents of the file are stored durably, such as on disk.
+    /// This is based on [`std::fs::File::sync_all`].
+    ///
+    /// [`std::fs::File::sync_all`]: https://doc.rust-lang.org/std/fs/struct.File.html#method.sync_all
+    fn sync_all(&self) -> io::Result<()>;
+}
+
+/// Advisory locking of an open file.
+/// This is based on [`fs4::FileExt`].
+///
+/// Locks belong to the handle that acquired them, so two handles to the same file contend for
+/// its lock even within a single process. A handle's lock is released by [`unlock`] or when the
+/// handle is dropped.
+///
+/// [`fs4::FileExt`]: https://docs.rs/fs4/1/fs4/trait.FileExt.html
+/// [`unlock`]: #tymethod.unlock
+#[cfg(feature = "lock")]
+pub trait LockableFile {
+    /// Acquires a shared lock, blocking until any exclusive lock held by another handle is
+    /// released.
+    fn lock_shared(&self) -> io::Result<()>;
+    /// Acquires an exclusive lock, blocking until every lock held by another handle is released.
+    fn lock_exclusive(&self) -> io::Result<()>;
+    /// Acquires a shared lock without blocking.
+    ///
+    /// # Errors
+    ///
+    /// * Another handle holds an exclusive lock, in which case the error kind is
+    ///   `ErrorKind::WouldBlock`.
+    fn try_lock_shared(&self) -> io::Result<()>;
+    /// Acquires an exclusive lock without blocking.
+    ///
+    /// # Errors
+    ///
+    /// * Another handle holds a lock, in which case the error kind is `ErrorKind::WouldBlock`.
+    fn try_lock_exclusive(&self) -> io::Result<()>;
+    /// Releases any lock held by this handle.
+    fn unlock(&self) -> io::Result<()>;
+}
+
+/// Options and flags which can be used to configure how a file is opened.
+/// This is based on [`std::fs::OpenOptions`].
+///
+/// [`std::fs::OpenOptions`]: https://doc.rust-lang.org/std/fs/struct.OpenOptions.html
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpenOptions {
+    read: bool,
+    write: bool,
+    append: bool,
+    truncate: bool,
+    create: bool,
+    create_new: bool,
+}
+
+impl OpenOptions {
+    /// Creates a blank set of options with every flag set to `false`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the option for read access.
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.read = read;
+        self
+    }
+
+    /// Sets the option for write access.
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.write = write;
+        self
+    }
+
+    /// Sets the option for appending to the end of the file on every write.
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.append = append;
+        self
+    }
+
+    /// Sets the option for truncating an existing file when it is opened.
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.truncate = truncate;
+        self
+    }
+
+    /// Sets the option to create the file if it does not exist.
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.create = create;
+        self
+    }
+
+    /// Sets the option to create a new file, failing if it already exists.
+    pub fn create_new(&mut self, create_new: bool) -> &mut Self {
+        self.create_new = create_new;
+        self
+    }
+
+    /// Returns `true` if the file will be opened for reading.
+    pub fn is_read(&self) -> bool {
+        self.read
+    }
+
+    /// Returns `true` if the file will be opened for writing.
+    pub fn is_write(&self) -> bool {
+        self.write || self.append
+    }
+
+    /// Returns `true` if every write will append to the end of the file.
+    pub fn is_append(&self) -> bool {
+        self.append
+    }
+
+    /// Returns `true` if an existing file will be truncated.
+    pub fn is_truncate(&self) -> bool {
+        self.truncate
+    }
+
+    /// Returns `true` if the file will be created if it does not exist.
+    pub fn is_create(&self) -> bool {
+        self.create
+    }
+
+    /// Returns `true` if opening will fail unless a new file is created.
+    pub fn is_create_new(&self) -> bool {
+        self.create_new
+    }
+}
+
+/// A builder for opening a file of a file system, as returned by [`FileSystem::open_with`].
+///
+/// Every flag starts as `false`, as with [`OpenOptions::new`].
+///
+/// [`FileSystem::open_with`]: trait.FileSystem.html#method.open_with
+/// [`OpenOptions::new`]: struct.OpenOptions.html#method.new
+#[derive(Debug)]
+pub struct OpenWith<'a, T: 'a> {
+    fs: &'a T,
+    path: PathBuf,
+    options: OpenOptions,
+}
+
+impl<'a, T: FileSystem> OpenWith<'a, T> {
+    /// Sets the option for read access.
+    pub fn read(mut self, read: bool) -> Self {
+        self.options.read(read);
+        self
+    }
+
+    /// Sets the option for write access.
+    pub fn write(mut self, write: bool) -> Self {
+        self.options.write(write);
+        self
+    }
+
+    /// Sets the option for appending to the end of the file on every write.
+    pub fn append(mut self, append: bool) -> Self {
+        self.options.append(append);
+        self
+    }
+
+    /// Sets the option for truncating an existing file when it is opened, like `O_TRUNC`.
+    pub fn truncate(mut self, truncate: bool) -> Self {
+        self.options.truncate(truncate);
+        self
+    }
+
+    /// Sets the option to create the file if it does not exist, like `O_CREAT`.
+    pub fn create(mut self, create: bool) -> Self {
+        self.options.create(create);
+        self
+    }
+
+    /// Sets the option to create a new file, failing if it already exists, like
+    /// `O_CREAT | O_EXCL`.
+    /// A symlink at the path counts as existing, even if it is broken.
+    pub fn create_new(mut self, create_new: bool) -> Self {
+        self.options.create_new(create_new);
+        self
+    }
+
+    /// Returns the options set so far.
+    pub fn options(&self) -> &OpenOptions {
+        &self.options
+    }
+
+    /// Opens the file with the options set so far, as [`FileSystem::open_with_options`] does.
+    ///
+    /// [`FileSystem::open_with_options`]: trait.FileSystem.html#tymethod.open_with_options
+    pub fn open(&self) -> Result<T::OpenFile> {
+        self.fs.open_with_options(&self.path, &self.options)
+    }
+}
+
+/// The type of a node in a file system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileType {
+    File,
+    Dir,
+    Symlink,
+    /// A named pipe, as created by [`UnixFileSystem::mkfifo`].
+    ///
+    /// [`UnixFileSystem::mkfifo`]: trait.UnixFileSystem.html#method.mkfifo
+    Fifo,
+    /// A Unix domain socket.
+    Socket,
+    /// A block device, such as a disk.
+    BlockDevice,
+    /// A character device, such as a terminal or `/dev/null`.
+    CharDevice,
+}
+
+impl FileType {
+    /// Returns `true` if this is the type of a regular file.
+    pub fn is_file(self) -> bool {
+        self == FileType::File
+    }
+
+    /// Returns `true` if this is the type of a directory.
+    pub fn is_dir(self) -> bool {
+        self == FileType::Dir
+    }
+
+    /// Returns `true` if this is the type of a symbolic link.
+    pub fn is_symlink(self) -> bool {
+        self == FileType::Symlink
+    }
+
+    /// Returns `true` if this is the type of a named pipe.
+    pub fn is_fifo(self) -> bool {
+        self == FileType::Fifo
+    }
+
+    /// Returns `true` if this is the type of a socket.
+    pub fn is_socket(self) -> bool {
+        self == FileType::Socket
+    }
+
+    /// Returns `true` if this is the type of a block device.
+    pub fn is_block_device(self) -> bool {
+        self == FileType::BlockDevice
+    }
+
+    /// Returns `true` if this is the type of a character device.
+    pub fn is_char_device(self) -> bool {
+        self == FileType::CharDevice
+    }
+
+    /// Returns `true` if this is the type of a FIFO, socket, or device, which are neither
+    /// regular files, directories, nor symbolic links.
+    pub fn is_special(self) -> bool {
+        !self.is_file() && !self.is_dir() && !self.is_symlink()
+    }
+}
+
+/// Information about a node in a file system.
+/// This is based on [`std::fs::Metadata`].
+///
+/// [`std::fs::Metadata`]: https://doc.rust-lang.org/std/fs/struct.Metadata.html
+#[allow(clippy::len_without_is_empty)]
+pub trait Metadata {
+    /// Returns the type of the node.
+    fn file_type(&self) -> FileType;
+    /// Returns `true` if the node is a directory.
+    fn is_dir(&self) -> bool {
+        self.file_type().is_dir()
+    }
+    /// Returns `true` if the node is a regular file.
+    fn is_file(&self) -> bool {
+        self.file_type().is_file()
+    }
+    /// Returns the size of the node in bytes.
+    fn len(&self) -> u64;
+    /// Returns `true` if the node is readonly.
+    fn readonly(&self) -> bool;
+    /// Returns the creation time of the node.
+    ///
+    /// # Errors
+    ///
+    /// * The creation time is not available on this platform.
+    fn created(&self) -> io::Result<SystemTime>;
+    /// Returns the last modification time of the node.
+    ///
+    /// # Errors
+    ///
+    /// * The modification time is not available on this platform.
+    fn modified(&self) -> io::Result<SystemTime>;
+    /// Returns the last access time of the node.
+    ///
+    /// # Errors
+    ///
+    /// * The access time is not available on this platform.
+    fn accessed(&self) -> io::Result<SystemTime>;
+}
+
+/// Provides operations on symbolic links.
+pub trait SymlinkFileSystem: FileSystem {
+    /// Creates a new symbolic link at `link` pointing to the file at `original`.
+    /// This is based on [`std::os::unix::fs::symlink`] and [`std::os::windows::fs::symlink_file`].
+    ///
+    /// `original` is stored as given, so a relative path is resolved against the directory that
+    /// holds `link` whenever the link is followed, rather than against the current directory.
+    ///
+    /// # Errors
+    ///
+    /// * A node already exists at `link`.
+    /// * The parent directory of `link` does not exist.
+    /// * Current user has insufficient permissions.
+    ///
+    /// [`std::os::unix::fs::symlink`]: https://doc.rust-lang.org/std/os/unix/fs/fn.symlink.html
+    /// [`std::os::windows::fs::symlink_file`]: https://doc.rust-lang.org/std/os/windows/fs/fn.symlink_file.html
+    fn symlink_file<P, Q>(&self, original: P, link: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>;
+    /// Creates a new symbolic link at `link` pointing to the directory at `original`.
+    /// This is based on [`std::os::unix::fs::symlink`] and [`std::os::windows::fs::symlink_dir`].
+    ///
+    /// `original` is stored as given, so a relative path is resolved against the directory that
+    /// holds `link` whenever the link is followed, rather than against the current directory.
+    ///
+    /// # Errors
+    ///
+    /// * A node already exists at `link`.
+    /// * The parent directory of `link` does not exist.
+    /// * Current user has insufficient permissions.
+    ///
+    /// [`std::os::unix::fs::symlink`]: https://doc.rust-lang.org/std/os/unix/fs/fn.symlink.html
+    /// [`std::os::windows::fs::symlink_dir`]: https://doc.rust-lang.org/std/os/windows/fs/fn.symlink_dir.html
+    fn symlink_dir<P, Q>(&self, original: P, link: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>;
+    /// Returns the path that the symbolic link at `path` points to.
+    /// This is based on [`std::fs::read_link`].
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * `path` is not a symbolic link.
+    ///
+    /// [`std::fs::read_link`]: https://doc.rust-lang.org/std/fs/fn.read_link.html
+    fn read_link<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf>;
+    /// Returns metadata about the node at `path` without following symbolic links.
+    /// This is based on [`std::fs::symlink_metadata`].
+    ///
+    /// [`std::fs::symlink_metadata`]: https://doc.rust-lang.org/std/fs/fn.symlink_metadata.html
+    fn symlink_metadata<P: AsRef<Path>>(&self, path: P) -> Result<Self::Metadata>;
+}
 
 #[cfg(unix)]
 pub trait UnixFileSystem {
@@ -200,6 +1353,119 @@ pub trait UnixFileSystem {
     /// * `path` does not exist.
     /// * Current user has insufficient permissions.
     fn set_mode<P: AsRef<Path>>(&self, path: P, mode: u32) -> Result<()>;
+    /// Returns the user and group IDs of the owner of `path`.
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * Current user has insufficient permissions.
+    fn owner<P: AsRef<Path>>(&self, path: P) -> Result<(u32, u32)>;
+    /// Changes the owner of `path` to the given user and group IDs.
+    /// This is based on [`std::os::unix::fs::chown`].
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * Current user has insufficient permissions.
+    ///
+    /// [`std::os::unix::fs::chown`]: https://doc.rust-lang.org/std/os/unix/fs/fn.chown.html
+    fn set_owner<P: AsRef<Path>>(&self, path: P, uid: u32, gid: u32) -> Result<()>;
+    /// Writes `buf` to a new file at `path` with the mode bits `mode`, less those in the umask.
+    /// This is based on [`std::os::unix::fs::OpenOptionsExt::mode`].
+    ///
+    /// # Errors
+    ///
+    /// * A file or directory already exists at `path`.
+    /// * The parent directory of `path` does not exist.
+    /// * Current user has insufficient permissions.
+    ///
+    /// [`std::os::unix::fs::OpenOptionsExt::mode`]: https://doc.rust-lang.org/std/os/unix/fs/trait.OpenOptionsExt.html#tymethod.mode
+    fn create_file_with_mode<P, B>(&self, path: P, buf: B, mode: u32) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>;
+    /// Creates a new directory with the mode bits `mode`, less those in the umask.
+    /// This is based on [`std::os::unix::fs::DirBuilderExt::mode`].
+    ///
+    /// # Errors
+    ///
+    /// * A file or directory already exists at `path`.
+    /// * The parent directory of `path` does not exist.
+    /// * Current user has insufficient permissions.
+    ///
+    /// [`std::os::unix::fs::DirBuilderExt::mode`]: https://doc.rust-lang.org/std/os/unix/fs/trait.DirBuilderExt.html#tymethod.mode
+    fn create_dir_with_mode<P: AsRef<Path>>(&self, path: P, mode: u32) -> Result<()>;
+    /// Creates a FIFO, socket, or device node of type `file_type` with the mode bits `mode`,
+    /// less those in the umask, and the device number `device`, which is ignored unless the node
+    /// is a device.
+    /// This is based on `mknod(2)`.
+    ///
+    /// # Errors
+    ///
+    /// * `file_type` is a regular file, directory, or symbolic link, which is reported as
+    ///   `ErrorKind::InvalidInput`.
+    /// * A node already exists at `path`.
+    /// * The parent directory of `path` does not exist.
+    /// * Current user has insufficient permissions, such as to create a device as anyone but
+    ///   root.
+    fn mknod<P: AsRef<Path>>(
+        &self,
+        path: P,
+        file_type: FileType,
+        mode: u32,
+        device: u64,
+    ) -> Result<()>;
+    /// Creates a FIFO, also known as a named pipe, with the mode bits `mode`, less those in the
+    /// umask.
+    /// This is based on `mkfifo(3)`.
+    ///
+    /// # Errors
+    ///
+    /// * A node already exists at `path`.
+    /// * The parent directory of `path` does not exist.
+    /// * Current user has insufficient permissions.
+    fn mkfifo<P: AsRef<Path>>(&self, path: P, mode: u32) -> Result<()> {
+        self.mknod(path, FileType::Fifo, mode, 0)
+    }
+}
+
+/// Provides Windows-specific file system operations.
+/// Symbolic links are created through [`SymlinkFileSystem`], which this extends.
+///
+/// [`SymlinkFileSystem`]: trait.SymlinkFileSystem.html
+#[cfg(windows)]
+pub trait WindowsFileSystem: SymlinkFileSystem {
+    /// Returns the attribute bits of `path`, such as `FILE_ATTRIBUTE_HIDDEN` (`0x2`),
+    /// `FILE_ATTRIBUTE_SYSTEM` (`0x4`), and `FILE_ATTRIBUTE_ARCHIVE` (`0x20`).
+    /// This is based on [`std::os::windows::fs::MetadataExt::file_attributes`].
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * Current user has insufficient permissions.
+    ///
+    /// [`std::os::windows::fs::MetadataExt::file_attributes`]: https://doc.rust-lang.org/std/os/windows/fs/trait.MetadataExt.html#tymethod.file_attributes
+    fn attributes<P: AsRef<Path>>(&self, path: P) -> Result<u32>;
+    /// Sets the attribute bits of `path`.
+    /// Bits that describe the type of node, such as `FILE_ATTRIBUTE_DIRECTORY`, are ignored.
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * Current user has insufficient permissions.
+    fn set_attributes<P: AsRef<Path>>(&self, path: P, attributes: u32) -> Result<()>;
+    /// Creates a new junction at `junction` pointing to the directory at `original`.
+    /// Unlike directory symlinks, junctions can be created without elevated privileges.
+    /// A relative `original` is resolved against the current directory.
+    ///
+    /// # Errors
+    ///
+    /// * A node already exists at `junction`.
+    /// * The parent directory of `junction` does not exist.
+    fn create_junction<P, Q>(&self, original: P, junction: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>;
 }
 
 #[cfg(feature = "temp")]
@@ -209,12 +1475,102 @@ pub trait TempDir {
     ///
     /// [`Path`]: https://doc.rust-lang.org/std/path/struct.Path.html
     fn path(&self) -> &Path;
+    /// Returns the path of the temporary directory without deleting it, so that it and its
+    /// contents are kept after the struct goes out of scope.
+    fn into_path(self) -> PathBuf
+    where
+        Self: Sized;
+}
+
+#[cfg(feature = "temp")]
+/// Tracks a temporary file that will be deleted once the struct goes out of scope.
+pub trait TempFile {
+    /// Returns the [`Path`] of the temporary file.
+    ///
+    /// [`Path`]: https://doc.rust-lang.org/std/path/struct.Path.html
+    fn path(&self) -> &Path;
 }
 
 #[cfg(feature = "temp")]
 pub trait TempFileSystem {
     type TempDir: TempDir;
+    type TempFile: TempFile;
 
     /// Creates a new temporary directory.
     fn temp_dir<S: AsRef<str>>(&self, prefix: S) -> Result<Self::TempDir>;
+    /// Creates a new temporary directory inside `base`, rather than the default temporary
+    /// directory, whose name starts with `prefix`.
+    ///
+    /// # Errors
+    ///
+    /// * `base` does not exist.
+    /// * Current user has insufficient permissions.
+    fn temp_dir_in<P, S>(&self, base: P, prefix: S) -> Result<Self::TempDir>
+    where
+        P: AsRef<Path>,
+        S: AsRef<str>,
+    {
+        let mut options = TempOptions::new();
+
+        options.prefix(prefix);
+        self.temp_dir_with_options(base, &options)
+    }
+    /// Creates a new temporary directory inside `base` with the given options.
+    ///
+    /// # Errors
+    ///
+    /// * `base` does not exist.
+    /// * Current user has insufficient permissions.
+    fn temp_dir_with_options<P: AsRef<Path>>(
+        &self,
+        base: P,
+        options: &TempOptions,
+    ) -> Result<Self::TempDir>;
+    /// Creates a new, empty temporary file whose name starts with `prefix`.
+    fn temp_file<S: AsRef<str>>(&self, prefix: S) -> Result<Self::TempFile>;
+    /// Creates a new, empty temporary file inside `base` with the given options.
+    ///
+    /// # Errors
+    ///
+    /// * `base` does not exist.
+    /// * Current user has insufficient permissions.
+    fn temp_file_with_options<P: AsRef<Path>>(
+        &self,
+        base: P,
+        options: &TempOptions,
+    ) -> Result<Self::TempFile>;
+}
+
+/// Returns a path in the same directory as `path` to write new contents to before renaming them
+/// over it, unique within this process.
+fn temp_sibling(path: &Path) -> io::Result<PathBuf> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "path has no file name"))?;
+    let mut temp = OsString::from(".");
+
+    temp.push(name);
+    temp.push(format!(
+        ".{}.{}.tmp",
+        process_id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    Ok(path.with_file_name(temp))
+}
+
+/// Returns the id of the current process.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn process_id() -> u32 {
+    process::id()
+}
+
+/// Returns the id of the current process.
+/// The browser has no processes, and `process::id` panics there, but neither is there another
+/// process to write the same temporary file.
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+fn process_id() -> u32 {
+    0
 }
@@ -0,0 +1,202 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use recover::Recover;
+
+type Matcher<C> = dyn Fn(&C) -> bool + Send + Sync;
+type MockFn<C, R> = dyn Fn(C) -> R + Send + Sync;
+
+/// The mock of a single file system method, which records the arguments of every call.
+///
+/// Clones share their calls and responses, and can be used from several threads at once.
+///
+/// A call returns, in order of precedence:
+///
+/// * the value of the most recently added response whose arguments or matcher fit the call
+/// * the next value queued with `Mock::return_values`
+/// * the value set with `Mock::return_value`, or the output of the function set with
+///   `Mock::use_fn` or `Mock::use_closure`
+#[derive(Clone)]
+pub struct Mock<C, R>
+where
+    C: Clone,
+    R: Clone,
+{
+    state: Arc<Mutex<State<C, R>>>,
+}
+
+struct State<C, R> {
+    calls: Vec<C>,
+    responses: Vec<(Arc<Matcher<C>>, R)>,
+    queue: VecDeque<R>,
+    fallback: Fallback<C, R>,
+}
+
+enum Fallback<C, R> {
+    Value(R),
+    Fn(Arc<MockFn<C, R>>),
+}
+
+impl<C, R> Mock<C, R>
+where
+    C: Clone,
+    R: Clone,
+{
+    /// Creates a mock that returns `return_value` until it is told otherwise.
+    pub fn new<T: Into<R>>(return_value: T) -> Self {
+        Mock {
+            state: Arc::new(Mutex::new(State {
+                calls: Vec::new(),
+                responses: Vec::new(),
+                queue: VecDeque::new(),
+                fallback: Fallback::Value(return_value.into()),
+            })),
+        }
+    }
+
+    /// Records a call with `args` and returns its result.
+    ///
+    /// A function set with `Mock::use_fn` or `Mock::use_closure` runs without holding the mock's
+    /// lock, so it may call the mock itself.
+    pub fn call(&self, args: C) -> R {
+        let mock_fn = {
+            let mut state = self.state();
+
+            state.calls.push(args.clone());
+
+            let matched = state
+                .responses
+                .iter()
+                .rev()
+                .find(|(matcher, _)| matcher(&args))
+                .map(|(_, value)| value.clone());
+
+            if let Some(value) = matched.or_else(|| state.queue.pop_front()) {
+                return value;
+            }
+
+            match state.fallback {
+                Fallback::Value(ref value) => return value.clone(),
+                Fallback::Fn(ref mock_fn) => Arc::clone(mock_fn),
+            }
+        };
+
+        mock_fn(args)
+    }
+
+    /// Sets the value returned by calls without a more specific response.
+    pub fn return_value<T: Into<R>>(&self, return_value: T) {
+        self.state().fallback = Fallback::Value(return_value.into());
+    }
+
+    /// Sets a function that computes the result of calls without a more specific response.
+    pub fn use_fn(&self, mock_fn: fn(C) -> R)
+    where
+        C: 'static,
+        R: 'static,
+    {
+        self.state().fallback = Fallback::Fn(Arc::new(mock_fn));
+    }
+
+    /// Sets a closure that computes the result of calls without a more specific response.
+    pub fn use_closure(&self, mock_fn: Box<MockFn<C, R>>) {
+        self.state().fallback = Fallback::Fn(Arc::from(mock_fn));
+    }
+
+    /// Returns `value` from every call whose arguments `matcher` accepts.
+    pub fn returns_when<F>(&self, matcher: F, value: R)
+    where
+        F: Fn(&C) -> bool + Send + Sync + 'static,
+    {
+        self.state().responses.push((Arc::new(matcher), value));
+    }
+
+    /// Queues `values` to be returned by the next calls, one per call, before falling back to
+    /// the return value.
+    pub fn return_values<I: IntoIterator<Item = R>>(&self, values: I) {
+        self.state().queue.extend(values);
+    }
+
+    /// Forgets the responses added with `Mock::returns_for`, `Mock::returns_when`, and
+    /// `Mock::return_values`.
+    pub fn reset_responses(&self) {
+        let mut state = self.state();
+
+        state.responses.clear();
+        state.queue.clear();
+    }
+
+    pub fn called(&self) -> bool {
+        !self.state().calls.is_empty()
+    }
+
+    pub fn num_calls(&self) -> usize {
+        self.state().calls.len()
+    }
+
+    /// Returns the arguments of every call, in order.
+    pub fn calls(&self) -> Vec<C> {
+        self.state().calls.clone()
+    }
+
+    pub fn reset_calls(&self) {
+        self.state().calls.clear()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State<C, R>> {
+        self.state.lock().recover()
+    }
+}
+
+impl<C, R> Mock<C, R>
+where
+    C: Clone + PartialEq + Send + Sync + 'static,
+    R: Clone,
+{
+    /// Returns `value` from every call with `args`.
+    pub fn returns_for<A: Into<C>>(&self, args: A, value: R) {
+        let args = args.into();
+
+        self.returns_when(move |call| *call == args, value)
+    }
+
+    pub fn called_with<A: Into<C>>(&self, args: A) -> bool {
+        self.state().calls.contains(&args.into())
+    }
+}
+
+impl<C, O, E> Mock<C, Result<O, E>>
+where
+    C: Clone,
+    O: Clone,
+    E: Clone,
+{
+    pub fn return_ok<T: Into<O>>(&self, return_value: T) {
+        self.return_value(Ok(return_value.into()))
+    }
+
+    pub fn return_err<T: Into<E>>(&self, return_value: T) {
+        self.return_value(Err(return_value.into()))
+    }
+}
+
+impl<C, R> fmt::Debug for Mock<C, R>
+where
+    C: Clone + fmt::Debug,
+    R: Clone + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state();
+        let mut debug = f.debug_struct("Mock");
+
+        if let Fallback::Value(ref value) = state.fallback {
+            debug.field("return_value", value);
+        }
+
+        debug
+            .field("calls", &state.calls)
+            .field("queue", &state.queue)
+            .finish()
+    }
+}
@@ -0,0 +1,854 @@
+use std::cell::RefCell;
+use std::ffi::OsString;
+use std::io::{BufRead, BufReader, Cursor, Error, ErrorKind, Lines, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::vec::IntoIter;
+
+use self::method::Mock;
+
+#[cfg(feature = "hash")]
+use hash::{self, Algorithm, Digest};
+#[cfg(feature = "temp")]
+use temp::TempOptions;
+#[cfg(feature = "temp")]
+use TempFileSystem;
+#[cfg(unix)]
+use UnixFileSystem;
+#[cfg(windows)]
+use WindowsFileSystem;
+use {
+    FileSystem, FileType, GlobOptions, OpenOptions, ReadDirOptions, SymlinkFileSystem,
+    WalkDirEntry, WalkDirOptions,
+};
+
+mod method;
+
+type WalkDirEntries = Vec<Result<WalkDirEntry, FakeError>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FakeError {
+    kind: ErrorKind,
+    description: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockDirEntry {
+    file_name: PathBuf,
+    is_file: bool,
+}
+
+impl MockDirEntry {
+    pub fn new<P: AsRef<Path>>(file_name: P, is_file: bool) -> Self {
+        MockDirEntry {
+            file_name: file_name.as_ref().to_path_buf(),
+            is_file,
+        }
+    }
+}
+
+impl crate::DirEntry for MockDirEntry {
+    type Metadata = MockMetadata;
+
+    fn path(&self) -> PathBuf {
+        self.file_name.clone()
+    }
+
+    fn file_name(&self) -> OsString {
+        self.file_name.clone().into_os_string()
+    }
+
+    fn metadata(&self) -> Result<MockMetadata, Error> {
+        let file_type = if self.is_file {
+            FileType::File
+        } else {
+            FileType::Dir
+        };
+
+        Ok(MockMetadata::new(file_type, 0, false))
+    }
+}
+
+#[derive(Debug)]
+pub struct MockReadDir(IntoIter<Result<MockDirEntry, Error>>);
+
+impl MockReadDir {
+    pub fn new() -> Self {
+        MockReadDir(vec![].into_iter())
+    }
+}
+
+impl Default for MockReadDir {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Iterator for MockReadDir {
+    type Item = Result<MockDirEntry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+impl crate::ReadDir<MockDirEntry> for MockReadDir {}
+
+impl From<Vec<Result<MockDirEntry, FakeError>>> for MockReadDir {
+    fn from(entries: Vec<Result<MockDirEntry, FakeError>>) -> Self {
+        let entries: Vec<Result<MockDirEntry, Error>> = entries
+            .into_iter()
+            .map(|e| e.map_err(Error::from))
+            .collect();
+
+        MockReadDir(entries.into_iter())
+    }
+}
+
+#[derive(Debug)]
+pub struct MockWalkDir(IntoIter<Result<WalkDirEntry, Error>>);
+
+impl Iterator for MockWalkDir {
+    type Item = Result<WalkDirEntry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+impl From<WalkDirEntries> for MockWalkDir {
+    fn from(entries: WalkDirEntries) -> Self {
+        let entries: Vec<Result<WalkDirEntry, Error>> = entries
+            .into_iter()
+            .map(|e| e.map_err(Error::from))
+            .collect();
+
+        MockWalkDir(entries.into_iter())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockMetadata {
+    file_type: FileType,
+    len: u64,
+    readonly: bool,
+}
+
+impl MockMetadata {
+    pub fn new(file_type: FileType, len: u64, readonly: bool) -> Self {
+        MockMetadata {
+            file_type,
+            len,
+            readonly,
+        }
+    }
+}
+
+impl crate::Metadata for MockMetadata {
+    fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn readonly(&self) -> bool {
+        self.readonly
+    }
+
+    fn created(&self) -> Result<SystemTime, Error> {
+        Ok(UNIX_EPOCH)
+    }
+
+    fn modified(&self) -> Result<SystemTime, Error> {
+        Ok(UNIX_EPOCH)
+    }
+
+    fn accessed(&self) -> Result<SystemTime, Error> {
+        Ok(UNIX_EPOCH)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockOpenFile(RefCell<Cursor<Vec<u8>>>);
+
+impl MockOpenFile {
+    pub fn new(contents: Vec<u8>) -> Self {
+        MockOpenFile(RefCell::new(Cursor::new(contents)))
+    }
+}
+
+impl Read for MockOpenFile {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.0.get_mut().read(buf)
+    }
+}
+
+impl Write for MockOpenFile {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.0.get_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.0.get_mut().flush()
+    }
+}
+
+impl Seek for MockOpenFile {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Error> {
+        self.0.get_mut().seek(pos)
+    }
+}
+
+impl crate::OpenFile for MockOpenFile {
+    fn set_len(&self, size: u64) -> Result<(), Error> {
+        self.0.borrow_mut().get_mut().resize(size as usize, 0);
+
+        Ok(())
+    }
+
+    fn sync_all(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "lock")]
+impl crate::LockableFile for MockOpenFile {
+    fn lock_shared(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn lock_exclusive(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn try_lock_shared(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn try_lock_exclusive(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn unlock(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl From<Error> for FakeError {
+    fn from(err: Error) -> Self {
+        FakeError {
+            kind: err.kind(),
+            description: err.to_string(),
+        }
+    }
+}
+
+impl From<FakeError> for Error {
+    fn from(err: FakeError) -> Self {
+        Error::new(err.kind, err.description)
+    }
+}
+
+impl From<FakeError> for crate::Error {
+    fn from(err: FakeError) -> Self {
+        Error::from(err).into()
+    }
+}
+
+#[cfg(feature = "temp")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockTempDir(PathBuf);
+
+#[cfg(feature = "temp")]
+impl MockTempDir {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        MockTempDir(path.as_ref().to_path_buf())
+    }
+}
+
+#[cfg(feature = "temp")]
+impl crate::TempDir for MockTempDir {
+    fn path(&self) -> &Path {
+        &self.0
+    }
+
+    fn into_path(self) -> PathBuf {
+        self.0
+    }
+}
+
+#[cfg(feature = "temp")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockTempFile(PathBuf);
+
+#[cfg(feature = "temp")]
+impl MockTempFile {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        MockTempFile(path.as_ref().to_path_buf())
+    }
+}
+
+#[cfg(feature = "temp")]
+impl crate::TempFile for MockTempFile {
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+/// Converts the arguments of a file system method into the arguments recorded by its `Mock`.
+trait MockArg<T> {
+    fn into_arg(self) -> T;
+}
+
+impl<P: AsRef<Path>> MockArg<PathBuf> for P {
+    fn into_arg(self) -> PathBuf {
+        self.as_ref().to_path_buf()
+    }
+}
+
+impl<B: AsRef<[u8]>> MockArg<Vec<u8>> for B {
+    fn into_arg(self) -> Vec<u8> {
+        self.as_ref().to_vec()
+    }
+}
+
+impl<S: AsRef<str>> MockArg<String> for S {
+    fn into_arg(self) -> String {
+        self.as_ref().to_string()
+    }
+}
+
+impl MockArg<GlobOptions> for &GlobOptions {
+    fn into_arg(self) -> GlobOptions {
+        self.clone()
+    }
+}
+
+impl MockArg<OpenOptions> for &OpenOptions {
+    fn into_arg(self) -> OpenOptions {
+        self.clone()
+    }
+}
+
+impl MockArg<ReadDirOptions> for &ReadDirOptions {
+    fn into_arg(self) -> ReadDirOptions {
+        self.clone()
+    }
+}
+
+#[cfg(feature = "temp")]
+impl MockArg<TempOptions> for &TempOptions {
+    fn into_arg(self) -> TempOptions {
+        self.clone()
+    }
+}
+
+impl MockArg<WalkDirOptions> for &WalkDirOptions {
+    fn into_arg(self) -> WalkDirOptions {
+        self.clone()
+    }
+}
+
+#[cfg(feature = "hash")]
+impl MockArg<Algorithm> for Algorithm {
+    fn into_arg(self) -> Algorithm {
+        self
+    }
+}
+
+impl MockArg<bool> for bool {
+    fn into_arg(self) -> bool {
+        self
+    }
+}
+
+impl MockArg<FileType> for FileType {
+    fn into_arg(self) -> FileType {
+        self
+    }
+}
+
+impl MockArg<u32> for u32 {
+    fn into_arg(self) -> u32 {
+        self
+    }
+}
+
+impl MockArg<u64> for u64 {
+    fn into_arg(self) -> u64 {
+        self
+    }
+}
+
+impl MockArg<SystemTime> for SystemTime {
+    fn into_arg(self) -> SystemTime {
+        self
+    }
+}
+
+/// Converts the value returned by a `Mock` into the return value of its file system method.
+trait MockReturn<T> {
+    fn into_return(self) -> T;
+}
+
+impl<T, U: From<T>> MockReturn<crate::Result<U>> for Result<T, FakeError> {
+    fn into_return(self) -> crate::Result<U> {
+        self.map(U::from).map_err(crate::Error::from)
+    }
+}
+
+impl MockReturn<bool> for bool {
+    fn into_return(self) -> bool {
+        self
+    }
+}
+
+impl MockReturn<u64> for u64 {
+    fn into_return(self) -> u64 {
+        self
+    }
+}
+
+/// Generates `MockFileSystem` from the signatures of the trait methods it implements.
+///
+/// Each method is followed by the type of its `Mock` field and the value that the field returns
+/// by default. The method passes its arguments to the mock and returns its result, unless it is
+/// followed by a block, which is used as its body instead.
+/// Attributes on an `impl` only apply to the implementation, so methods of traits that are
+/// behind a `cfg` repeat it for their fields.
+/// Methods with default bodies would silently keep them if left out, so the tests check that
+/// every trait method is listed.
+macro_rules! mock_file_system {
+    (
+        $(
+            $(#[$impl_attr:meta])*
+            impl $trait:ident for $mock:ident {
+                $(type $assoc:ident = $assoc_ty:ty;)*
+
+                $(
+                    $(#[$attr:meta])*
+                    fn $method:ident $(<$($generic:ident: $(?$unsized:ident +)* $bound:path),*>)* (
+                        &$this:ident $(, $arg:ident: $arg_ty:ty)* $(,)*
+                    ) -> $ret:ty
+                        => Mock<$args:ty, $output:ty> = $default:expr;
+                    $($body:block)*
+                )*
+            }
+        )*
+    ) => {
+        #[derive(Debug, Clone)]
+        pub struct MockFileSystem {
+            $($(
+                $(#[$attr])*
+                pub $method: Mock<$args, $output>,
+            )*)*
+        }
+
+        impl MockFileSystem {
+            pub fn new() -> Self {
+                MockFileSystem {
+                    $($(
+                        $(#[$attr])*
+                        $method: Mock::new($default),
+                    )*)*
+                }
+            }
+        }
+
+        $(
+            $(#[$impl_attr])*
+            impl $trait for $mock {
+                $(type $assoc = $assoc_ty;)*
+
+                $(
+                    $(#[$attr])*
+                    fn $method $(<$($generic: $(?$unsized +)* $bound),*>)* (
+                        &$this $(, $arg: $arg_ty)*
+                    ) -> $ret {
+                        mock_call!($this.$method($($arg),*) $($body)*)
+                    }
+                )*
+            }
+        )*
+    };
+}
+
+macro_rules! mock_call {
+    ($this:ident.$method:ident($($arg:ident),*)) => {
+        MockReturn::into_return($this.$method.call(($(MockArg::into_arg($arg)),*)))
+    };
+    ($this:ident.$method:ident($($arg:ident),*) $body:block) => {
+        $body
+    };
+}
+
+mock_file_system! {
+    impl FileSystem for MockFileSystem {
+        type DirEntry = MockDirEntry;
+        type ReadDir = MockReadDir;
+        type Metadata = MockMetadata;
+        type OpenFile = MockOpenFile;
+        type WalkDir = MockWalkDir;
+
+        fn current_dir(&self) -> crate::Result<PathBuf>
+            => Mock<(), Result<PathBuf, FakeError>> = Ok(PathBuf::new());
+
+        fn set_current_dir<P: AsRef<Path>>(&self, path: P) -> crate::Result<()>
+            => Mock<PathBuf, Result<(), FakeError>> = Ok(());
+
+        fn metadata<P: AsRef<Path>>(&self, path: P) -> crate::Result<MockMetadata>
+            => Mock<PathBuf, Result<MockMetadata, FakeError>>
+                = Ok(MockMetadata::new(FileType::File, 0, false));
+
+        fn canonicalize<P: AsRef<Path>>(&self, path: P) -> crate::Result<PathBuf>
+            => Mock<PathBuf, Result<PathBuf, FakeError>> = Ok(PathBuf::new());
+
+        fn is_dir<P: AsRef<Path>>(&self, path: P) -> bool
+            => Mock<PathBuf, bool> = true;
+
+        fn is_file<P: AsRef<Path>>(&self, path: P) -> bool
+            => Mock<PathBuf, bool> = true;
+
+        fn exists<P: AsRef<Path>>(&self, path: P) -> bool
+            => Mock<PathBuf, bool> = true;
+
+        fn try_exists<P: AsRef<Path>>(&self, path: P) -> crate::Result<bool>
+            => Mock<PathBuf, Result<bool, FakeError>> = Ok(true);
+
+        fn create_dir<P: AsRef<Path>>(&self, path: P) -> crate::Result<()>
+            => Mock<PathBuf, Result<(), FakeError>> = Ok(());
+
+        fn create_dir_all<P: AsRef<Path>>(&self, path: P) -> crate::Result<()>
+            => Mock<PathBuf, Result<(), FakeError>> = Ok(());
+
+        fn remove_dir<P: AsRef<Path>>(&self, path: P) -> crate::Result<()>
+            => Mock<PathBuf, Result<(), FakeError>> = Ok(());
+
+        fn remove_dir_all<P: AsRef<Path>>(&self, path: P) -> crate::Result<()>
+            => Mock<PathBuf, Result<(), FakeError>> = Ok(());
+
+        fn read_dir<P: AsRef<Path>>(&self, path: P) -> crate::Result<MockReadDir>
+            => Mock<PathBuf, Result<Vec<Result<MockDirEntry, FakeError>>, FakeError>>
+                = Ok(vec![]);
+
+        fn read_dir_sorted<P: AsRef<Path>>(&self, path: P) -> crate::Result<Vec<MockDirEntry>>
+            => Mock<PathBuf, Result<Vec<MockDirEntry>, FakeError>> = Ok(vec![]);
+
+        fn read_dir_with_options<P: AsRef<Path>>(
+            &self,
+            path: P,
+            options: &ReadDirOptions,
+        ) -> crate::Result<Vec<MockDirEntry>>
+            => Mock<(PathBuf, ReadDirOptions), Result<Vec<MockDirEntry>, FakeError>> = Ok(vec![]);
+
+        fn walk_dir<P: AsRef<Path>>(&self, path: P) -> crate::Result<MockWalkDir>
+            => Mock<PathBuf, Result<WalkDirEntries, FakeError>> = Ok(vec![]);
+
+        fn walk_dir_with_options<P: AsRef<Path>>(
+            &self,
+            path: P,
+            options: &WalkDirOptions,
+        ) -> crate::Result<MockWalkDir>
+            => Mock<(PathBuf, WalkDirOptions), Result<WalkDirEntries, FakeError>> = Ok(vec![]);
+
+        fn glob(&self, pattern: &str) -> crate::Result<Vec<PathBuf>>
+            => Mock<String, Result<Vec<PathBuf>, FakeError>> = Ok(vec![]);
+
+        fn glob_with(&self, pattern: &str, options: &GlobOptions) -> crate::Result<Vec<PathBuf>>
+            => Mock<(String, GlobOptions), Result<Vec<PathBuf>, FakeError>> = Ok(vec![]);
+
+        fn create_file<P: AsRef<Path>, B: AsRef<[u8]>>(&self, path: P, buf: B) -> crate::Result<()>
+            => Mock<(PathBuf, Vec<u8>), Result<(), FakeError>> = Ok(());
+
+        fn write_file<P: AsRef<Path>, B: AsRef<[u8]>>(&self, path: P, buf: B) -> crate::Result<()>
+            => Mock<(PathBuf, Vec<u8>), Result<(), FakeError>> = Ok(());
+
+        fn overwrite_file<P: AsRef<Path>, B: AsRef<[u8]>>(
+            &self,
+            path: P,
+            buf: B,
+        ) -> crate::Result<()>
+            => Mock<(PathBuf, Vec<u8>), Result<(), FakeError>> = Ok(());
+
+        fn set_len<P: AsRef<Path>>(&self, path: P, size: u64) -> crate::Result<()>
+            => Mock<(PathBuf, u64), Result<(), FakeError>> = Ok(());
+
+        fn sync_file<P: AsRef<Path>>(&self, path: P) -> crate::Result<()>
+            => Mock<PathBuf, Result<(), FakeError>> = Ok(());
+
+        fn sync_dir<P: AsRef<Path>>(&self, path: P) -> crate::Result<()>
+            => Mock<PathBuf, Result<(), FakeError>> = Ok(());
+
+        fn write_from_reader<P: AsRef<Path>, R: ?Sized + Read>(
+            &self,
+            path: P,
+            reader: &mut R,
+        ) -> crate::Result<u64>
+            => Mock<(PathBuf, Vec<u8>), Result<u64, FakeError>> = Ok(0);
+        {
+            let mut buf = Vec::new();
+
+            reader.read_to_end(&mut buf)?;
+            MockReturn::into_return(self.write_from_reader.call((path.into_arg(), buf)))
+        }
+
+        fn write_file_atomic<P: AsRef<Path>, B: AsRef<[u8]>>(
+            &self,
+            path: P,
+            buf: B,
+        ) -> crate::Result<()>
+            => Mock<(PathBuf, Vec<u8>), Result<(), FakeError>> = Ok(());
+
+        fn read_file<P: AsRef<Path>>(&self, path: P) -> crate::Result<Vec<u8>>
+            => Mock<PathBuf, Result<Vec<u8>, FakeError>> = Ok(vec![]);
+
+        fn read_file_to_string<P: AsRef<Path>>(&self, path: P) -> crate::Result<String>
+            => Mock<PathBuf, Result<String, FakeError>> = Ok(String::new());
+
+        fn read_file_into<P: AsRef<Path>, B: AsMut<Vec<u8>>>(
+            &self,
+            path: P,
+            buf: B,
+        ) -> crate::Result<usize>
+            => Mock<(PathBuf, Vec<u8>), Result<usize, FakeError>> = Ok(0);
+        {
+            let mut buf = buf;
+
+            MockReturn::into_return(
+                self.read_file_into
+                    .call((path.into_arg(), buf.as_mut().clone())),
+            )
+        }
+
+        fn open<P: AsRef<Path>>(&self, path: P) -> crate::Result<MockOpenFile>
+            => Mock<PathBuf, Result<MockOpenFile, FakeError>> = Ok(MockOpenFile::new(vec![]));
+
+        fn open_with_options<P: AsRef<Path>>(
+            &self,
+            path: P,
+            options: &OpenOptions,
+        ) -> crate::Result<MockOpenFile>
+            => Mock<(PathBuf, OpenOptions), Result<MockOpenFile, FakeError>>
+                = Ok(MockOpenFile::new(vec![]));
+
+        fn open_buffered<P: AsRef<Path>>(&self, path: P) -> crate::Result<BufReader<MockOpenFile>>
+            => Mock<PathBuf, Result<MockOpenFile, FakeError>> = Ok(MockOpenFile::new(vec![]));
+        {
+            self.open_buffered
+                .call(path.into_arg())
+                .map(BufReader::new)
+                .map_err(crate::Error::from)
+        }
+
+        fn read_lines<P: AsRef<Path>>(
+            &self,
+            path: P,
+        ) -> crate::Result<Lines<BufReader<MockOpenFile>>>
+            => Mock<PathBuf, Result<MockOpenFile, FakeError>> = Ok(MockOpenFile::new(vec![]));
+        {
+            self.read_lines
+                .call(path.into_arg())
+                .map(|file| BufReader::new(file).lines())
+                .map_err(crate::Error::from)
+        }
+
+        // The mock returns the contents to hash, which are empty by default.
+        #[cfg(feature = "hash")]
+        fn hash_file<P: AsRef<Path>>(&self, path: P, algorithm: Algorithm) -> crate::Result<Digest>
+            => Mock<(PathBuf, Algorithm), Result<Vec<u8>, FakeError>> = Ok(vec![]);
+        {
+            self.hash_file
+                .call((path.into_arg(), algorithm))
+                .map(|contents| hash::hash_bytes(&contents, algorithm))
+                .map_err(crate::Error::from)
+        }
+
+        fn remove_file<P: AsRef<Path>>(&self, path: P) -> crate::Result<()>
+            => Mock<PathBuf, Result<(), FakeError>> = Ok(());
+
+        fn copy_file<P: AsRef<Path>, Q: AsRef<Path>>(&self, from: P, to: Q) -> crate::Result<()>
+            => Mock<(PathBuf, PathBuf), Result<(), FakeError>> = Ok(());
+
+        fn copy_dir<P: AsRef<Path>, Q: AsRef<Path>>(&self, from: P, to: Q) -> crate::Result<()>
+            => Mock<(PathBuf, PathBuf), Result<(), FakeError>> = Ok(());
+
+        fn rename<P: AsRef<Path>, Q: AsRef<Path>>(&self, from: P, to: Q) -> crate::Result<()>
+            => Mock<(PathBuf, PathBuf), Result<(), FakeError>> = Ok(());
+
+        fn rename_or_copy<P: AsRef<Path>, Q: AsRef<Path>>(
+            &self,
+            from: P,
+            to: Q,
+        ) -> crate::Result<()>
+            => Mock<(PathBuf, PathBuf), Result<(), FakeError>> = Ok(());
+
+        fn readonly<P: AsRef<Path>>(&self, path: P) -> crate::Result<bool>
+            => Mock<PathBuf, Result<bool, FakeError>> = Ok(false);
+
+        fn set_readonly<P: AsRef<Path>>(&self, path: P, readonly: bool) -> crate::Result<()>
+            => Mock<(PathBuf, bool), Result<(), FakeError>> = Ok(());
+
+        fn is_hidden<P: AsRef<Path>>(&self, path: P) -> crate::Result<bool>
+            => Mock<PathBuf, Result<bool, FakeError>> = Ok(false);
+
+        fn modified<P: AsRef<Path>>(&self, path: P) -> crate::Result<SystemTime>
+            => Mock<PathBuf, Result<SystemTime, FakeError>> = Ok(UNIX_EPOCH);
+
+        fn set_modified<P: AsRef<Path>>(&self, path: P, time: SystemTime) -> crate::Result<()>
+            => Mock<(PathBuf, SystemTime), Result<(), FakeError>> = Ok(());
+
+        fn accessed<P: AsRef<Path>>(&self, path: P) -> crate::Result<SystemTime>
+            => Mock<PathBuf, Result<SystemTime, FakeError>> = Ok(UNIX_EPOCH);
+
+        fn set_accessed<P: AsRef<Path>>(&self, path: P, time: SystemTime) -> crate::Result<()>
+            => Mock<(PathBuf, SystemTime), Result<(), FakeError>> = Ok(());
+
+        fn len<P: AsRef<Path>>(&self, path: P) -> u64
+            => Mock<PathBuf, u64> = u64::default();
+
+        fn dir_size<P: AsRef<Path>>(&self, path: P, follow_links: bool) -> crate::Result<u64>
+            => Mock<(PathBuf, bool), Result<u64, FakeError>> = Ok(0);
+
+        fn available_space<P: AsRef<Path>>(&self, path: P) -> crate::Result<u64>
+            => Mock<PathBuf, Result<u64, FakeError>> = Ok(u64::MAX);
+
+        fn total_space<P: AsRef<Path>>(&self, path: P) -> crate::Result<u64>
+            => Mock<PathBuf, Result<u64, FakeError>> = Ok(u64::MAX);
+    }
+
+    impl SymlinkFileSystem for MockFileSystem {
+        fn symlink_file<P: AsRef<Path>, Q: AsRef<Path>>(
+            &self,
+            original: P,
+            link: Q,
+        ) -> crate::Result<()>
+            => Mock<(PathBuf, PathBuf), Result<(), FakeError>> = Ok(());
+
+        fn symlink_dir<P: AsRef<Path>, Q: AsRef<Path>>(
+            &self,
+            original: P,
+            link: Q,
+        ) -> crate::Result<()>
+            => Mock<(PathBuf, PathBuf), Result<(), FakeError>> = Ok(());
+
+        fn read_link<P: AsRef<Path>>(&self, path: P) -> crate::Result<PathBuf>
+            => Mock<PathBuf, Result<PathBuf, FakeError>> = Ok(PathBuf::new());
+
+        fn symlink_metadata<P: AsRef<Path>>(&self, path: P) -> crate::Result<MockMetadata>
+            => Mock<PathBuf, Result<MockMetadata, FakeError>>
+                = Ok(MockMetadata::new(FileType::File, 0, false));
+    }
+
+    #[cfg(unix)]
+    impl UnixFileSystem for MockFileSystem {
+        #[cfg(unix)]
+        fn mode<P: AsRef<Path>>(&self, path: P) -> crate::Result<u32>
+            => Mock<PathBuf, Result<u32, FakeError>> = Ok(0o644);
+
+        #[cfg(unix)]
+        fn set_mode<P: AsRef<Path>>(&self, path: P, mode: u32) -> crate::Result<()>
+            => Mock<(PathBuf, u32), Result<(), FakeError>> = Ok(());
+
+        #[cfg(unix)]
+        fn owner<P: AsRef<Path>>(&self, path: P) -> crate::Result<(u32, u32)>
+            => Mock<PathBuf, Result<(u32, u32), FakeError>> = Ok((0, 0));
+
+        #[cfg(unix)]
+        fn set_owner<P: AsRef<Path>>(&self, path: P, uid: u32, gid: u32) -> crate::Result<()>
+            => Mock<(PathBuf, u32, u32), Result<(), FakeError>> = Ok(());
+
+        #[cfg(unix)]
+        fn create_file_with_mode<P: AsRef<Path>, B: AsRef<[u8]>>(
+            &self,
+            path: P,
+            buf: B,
+            mode: u32,
+        ) -> crate::Result<()>
+            => Mock<(PathBuf, Vec<u8>, u32), Result<(), FakeError>> = Ok(());
+
+        #[cfg(unix)]
+        fn create_dir_with_mode<P: AsRef<Path>>(&self, path: P, mode: u32) -> crate::Result<()>
+            => Mock<(PathBuf, u32), Result<(), FakeError>> = Ok(());
+
+        #[cfg(unix)]
+        fn mknod<P: AsRef<Path>>(
+            &self,
+            path: P,
+            file_type: FileType,
+            mode: u32,
+            device: u64,
+        ) -> crate::Result<()>
+            => Mock<(PathBuf, FileType, u32, u64), Result<(), FakeError>> = Ok(());
+
+        #[cfg(unix)]
+        fn mkfifo<P: AsRef<Path>>(&self, path: P, mode: u32) -> crate::Result<()>
+            => Mock<(PathBuf, u32), Result<(), FakeError>> = Ok(());
+    }
+
+    #[cfg(windows)]
+    impl WindowsFileSystem for MockFileSystem {
+        #[cfg(windows)]
+        fn attributes<P: AsRef<Path>>(&self, path: P) -> crate::Result<u32>
+            => Mock<PathBuf, Result<u32, FakeError>> = Ok(0);
+
+        #[cfg(windows)]
+        fn set_attributes<P: AsRef<Path>>(&self, path: P, attributes: u32) -> crate::Result<()>
+            => Mock<(PathBuf, u32), Result<(), FakeError>> = Ok(());
+
+        #[cfg(windows)]
+        fn create_junction<P: AsRef<Path>, Q: AsRef<Path>>(
+            &self,
+            original: P,
+            junction: Q,
+        ) -> crate::Result<()>
+            => Mock<(PathBuf, PathBuf), Result<(), FakeError>> = Ok(());
+    }
+
+    #[cfg(feature = "temp")]
+    impl TempFileSystem for MockFileSystem {
+        type TempDir = MockTempDir;
+        type TempFile = MockTempFile;
+
+        #[cfg(feature = "temp")]
+        fn temp_dir<S: AsRef<str>>(&self, prefix: S) -> crate::Result<MockTempDir>
+            => Mock<String, Result<MockTempDir, FakeError>> = Ok(MockTempDir::new(""));
+
+        #[cfg(feature = "temp")]
+        fn temp_dir_in<P: AsRef<Path>, S: AsRef<str>>(
+            &self,
+            base: P,
+            prefix: S,
+        ) -> crate::Result<MockTempDir>
+            => Mock<(PathBuf, String), Result<MockTempDir, FakeError>> = Ok(MockTempDir::new(""));
+
+        #[cfg(feature = "temp")]
+        fn temp_dir_with_options<P: AsRef<Path>>(
+            &self,
+            base: P,
+            options: &TempOptions,
+        ) -> crate::Result<MockTempDir>
+            => Mock<(PathBuf, TempOptions), Result<MockTempDir, FakeError>>
+                = Ok(MockTempDir::new(""));
+
+        #[cfg(feature = "temp")]
+        fn temp_file<S: AsRef<str>>(&self, prefix: S) -> crate::Result<MockTempFile>
+            => Mock<String, Result<MockTempFile, FakeError>> = Ok(MockTempFile::new(""));
+
+        #[cfg(feature = "temp")]
+        fn temp_file_with_options<P: AsRef<Path>>(
+            &self,
+            base: P,
+            options: &TempOptions,
+        ) -> crate::Result<MockTempFile>
+            => Mock<(PathBuf, TempOptions), Result<MockTempFile, FakeError>>
+                = Ok(MockTempFile::new(""));
+    }
+}
+
+impl Default for MockFileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
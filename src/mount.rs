@@ -0,0 +1,842 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::io::{self, ErrorKind};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
+use std::vec::IntoIter;
+
+use dynamic;
+use error::Context;
+use normalize::normalize;
+use recover::Recover;
+use walk::WalkSource;
+#[cfg(feature = "hash")]
+use {Algorithm, Digest};
+use {
+    DynDirEntry, DynMetadata, DynOpenFile, Error, FileSystem, FileType, OpenOptions, Result,
+    WalkDir, WalkDirOptions,
+};
+
+type Mounted = Arc<dyn dynamic::DynFileSystem + Send + Sync>;
+
+/// A file system that combines other file systems, each mounted at a directory, like a Unix
+/// mount table.
+///
+/// Every path is routed to the file system mounted at its longest mounted ancestor, with the
+/// mount point taking the place of that file system's `/`.
+/// Mounting [`ChrootFileSystem`] gives a file system whose `/` is a directory of another, such
+/// as a temporary directory of [`OsFileSystem`].
+///
+/// Paths are made absolute against a current directory that is tracked separately from any of
+/// the mounted file systems', starting at `/`.
+/// `.` and `..` components are resolved lexically, and symlinks are resolved within the file
+/// system that contains them.
+///
+/// Mount points are listed in the directory that contains them, whether or not the file system
+/// that directory belongs to has a node of that name.
+/// Ancestors of mount points that are not in any mounted file system exist as read-only
+/// directories without timestamps, so mount points can be reached from `/`.
+///
+/// Renaming a node to another mounted file system fails with `ErrorKind::CrossesDevices`, like
+/// `EXDEV`, while copying between them reads from one and writes to the other.
+/// Removing or renaming a mount point, or a directory that contains one, fails with
+/// `ErrorKind::ResourceBusy`.
+///
+/// [`ChrootFileSystem`]: struct.ChrootFileSystem.html
+/// [`OsFileSystem`]: struct.OsFileSystem.html
+#[derive(Clone)]
+pub struct MountFileSystem {
+    mounts: Arc<RwLock<BTreeMap<PathBuf, Mounted>>>,
+    cwd: Arc<Mutex<PathBuf>>,
+}
+
+/// A path that has been routed to the file system that is mounted above it.
+struct Route {
+    mount_point: PathBuf,
+    fs: Mounted,
+    path: PathBuf,
+}
+
+impl MountFileSystem {
+    /// Creates a file system without any mounts, where only `/` exists.
+    pub fn new() -> Self {
+        MountFileSystem {
+            mounts: Arc::new(RwLock::new(BTreeMap::new())),
+            cwd: Arc::new(Mutex::new(PathBuf::from("/"))),
+        }
+    }
+
+    /// Mounts `fs` at `path`, so that `path` is `fs`'s `/`.
+    ///
+    /// `path` does not need to exist, and hides whatever it refers to until it is unmounted.
+    ///
+    /// # Errors
+    ///
+    /// * Another file system is already mounted at `path`.
+    pub fn mount<P, F>(&self, path: P, fs: F) -> Result<()>
+    where
+        P: AsRef<Path>,
+        F: FileSystem + Send + Sync + 'static,
+    {
+        let guest = path.as_ref();
+        let path = self.normalize(guest);
+        let mut mounts = self.mounts.write().recover();
+
+        if mounts.contains_key(&path) {
+            return Err(Error::new("mount", &[guest], already_exists()));
+        }
+
+        mounts.insert(path, Arc::new(fs));
+
+        Ok(())
+    }
+
+    /// Unmounts the file system mounted at `path`.
+    ///
+    /// # Errors
+    ///
+    /// * No file system is mounted at `path`, which is reported as `ErrorKind::InvalidInput`.
+    pub fn unmount<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let guest = path.as_ref();
+        let path = self.normalize(guest);
+
+        match self.mounts.write().recover().remove(&path) {
+            Some(_) => Ok(()),
+            None => Err(Error::new(
+                "unmount",
+                &[guest],
+                io::Error::new(ErrorKind::InvalidInput, "not a mount point"),
+            )),
+        }
+    }
+
+    /// Returns the paths that file systems are mounted at, sorted.
+    pub fn mount_points(&self) -> Vec<PathBuf> {
+        self.mounts.read().recover().keys().cloned().collect()
+    }
+
+    /// Returns `path` as an absolute path with `.` and `..` components removed.
+    fn normalize(&self, path: &Path) -> PathBuf {
+        normalize(&self.cwd.lock().recover(), path)
+    }
+
+    /// Returns the file system that `path` belongs to, along with the path within it.
+    fn route(&self, path: &Path) -> Result<Route> {
+        let mounts = self.mounts.read().recover();
+
+        for mount_point in path.ancestors() {
+            if let Some(fs) = mounts.get(mount_point) {
+                let relative = path.strip_prefix(mount_point).unwrap_or(path);
+
+                return Ok(Route {
+                    mount_point: mount_point.to_path_buf(),
+                    fs: fs.clone(),
+                    path: Path::new("/").join(relative),
+                });
+            }
+        }
+
+        Err(not_found().into())
+    }
+
+    /// Calls `f` with the file system that `path` belongs to and the path within it, with
+    /// `operation` and `path` attached to any error in place of the mounted file system's path.
+    fn with<T, F>(&self, operation: &'static str, path: &Path, f: F) -> Result<T>
+    where
+        F: FnOnce(&dyn dynamic::DynFileSystem, &Path) -> Result<T>,
+    {
+        self.route(&self.normalize(path))
+            .and_then(|route| f(&*route.fs, &route.path))
+            .context(operation, &[path])
+    }
+
+    fn is_mount_point(&self, path: &Path) -> bool {
+        self.mounts.read().recover().contains_key(path)
+    }
+
+    /// Returns `true` if a file system is mounted below `dir`.
+    fn contains_mount(&self, dir: &Path) -> bool {
+        self.mounts
+            .read()
+            .unwrap()
+            .keys()
+            .any(|mount_point| mount_point != dir && mount_point.starts_with(dir))
+    }
+
+    /// Returns `true` if `path` only exists because it contains a mount point.
+    fn is_virtual(&self, path: &Path) -> bool {
+        if !self.contains_mount(path) {
+            return false;
+        }
+
+        match self.route(path) {
+            Ok(route) => !route.fs.exists(&route.path),
+            Err(_) => true,
+        }
+    }
+
+    /// Fails if `path` is a mount point or contains one, so it can't be removed or renamed.
+    fn check_not_busy(&self, path: &Path) -> Result<()> {
+        if self.is_mount_point(path) || self.contains_mount(path) {
+            return Err(io::Error::new(ErrorKind::ResourceBusy, "device or resource busy").into());
+        }
+
+        Ok(())
+    }
+
+    fn file_type(&self, path: &Path) -> Result<FileType> {
+        self.metadata(path).map(|m| ::Metadata::file_type(&m))
+    }
+}
+
+impl Default for MountFileSystem {
+    fn default() -> Self {
+        MountFileSystem::new()
+    }
+}
+
+impl fmt::Debug for MountFileSystem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MountFileSystem")
+            .field("mount_points", &self.mount_points())
+            .field("cwd", &*self.cwd.lock().recover())
+            .finish()
+    }
+}
+
+impl FileSystem for MountFileSystem {
+    type DirEntry = DirEntry;
+    type ReadDir = ReadDir;
+    type Metadata = Metadata;
+    type OpenFile = DynOpenFile;
+    type WalkDir = WalkDir;
+
+    fn current_dir(&self) -> Result<PathBuf> {
+        Ok(self.cwd.lock().recover().clone())
+    }
+
+    fn set_current_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let guest = path.as_ref();
+        let path = self.normalize(guest);
+
+        if !::Metadata::is_dir(&self.metadata(&path).context("set_current_dir", &[guest])?) {
+            return Err(Error::new(
+                "set_current_dir",
+                &[guest],
+                io::Error::new(ErrorKind::NotADirectory, "not a directory"),
+            ));
+        }
+
+        *self.cwd.lock().recover() = path;
+
+        Ok(())
+    }
+
+    fn metadata<P: AsRef<Path>>(&self, path: P) -> Result<Self::Metadata> {
+        let path = path.as_ref();
+
+        if self.is_virtual(&self.normalize(path)) {
+            return Ok(Metadata(None));
+        }
+
+        self.with("metadata", path, |fs, path| {
+            fs.metadata(path).map(|m| Metadata(Some(m)))
+        })
+    }
+
+    fn canonicalize<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf> {
+        let guest = path.as_ref();
+        let path = self.normalize(guest);
+
+        if self.is_virtual(&path) {
+            return Ok(path);
+        }
+
+        self.route(&path)
+            .and_then(|route| {
+                let canonical = route.fs.canonicalize(&route.path)?;
+
+                Ok(route
+                    .mount_point
+                    .join(canonical.strip_prefix("/").unwrap_or(&canonical)))
+            })
+            .context("canonicalize", &[guest])
+    }
+
+    fn is_dir<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.metadata(path)
+            .map(|m| ::Metadata::is_dir(&m))
+            .unwrap_or(false)
+    }
+
+    fn is_file<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.metadata(path)
+            .map(|m| ::Metadata::is_file(&m))
+            .unwrap_or(false)
+    }
+
+    fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.metadata(path).is_ok()
+    }
+
+    fn try_exists<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let guest = path.as_ref();
+        let path = self.normalize(guest);
+
+        if self.is_virtual(&path) {
+            return Ok(true);
+        }
+
+        match self.route(&path) {
+            Ok(route) => route
+                .fs
+                .try_exists(&route.path)
+                .context("try_exists", &[guest]),
+            Err(_) => Ok(false),
+        }
+    }
+
+    fn create_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        if self.is_virtual(&self.normalize(path)) {
+            return Err(Error::new("create_dir", &[path], already_exists()));
+        }
+
+        self.with("create_dir", path, |fs, path| fs.create_dir(path))
+    }
+
+    fn create_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        if self.is_dir(path) {
+            return Ok(());
+        }
+
+        self.with("create_dir_all", path, |fs, path| fs.create_dir_all(path))
+    }
+
+    fn remove_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.check_not_busy(&self.normalize(path))
+            .context("remove_dir", &[path])?;
+        self.with("remove_dir", path, |fs, path| fs.remove_dir(path))
+    }
+
+    fn remove_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.check_not_busy(&self.normalize(path))
+            .context("remove_dir_all", &[path])?;
+        self.with("remove_dir_all", path, |fs, path| fs.remove_dir_all(path))
+    }
+
+    fn read_dir<P: AsRef<Path>>(&self, path: P) -> Result<Self::ReadDir> {
+        let path = path.as_ref();
+
+        self.list(path).context("read_dir", &[path])
+    }
+
+    fn walk_dir_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &WalkDirOptions,
+    ) -> Result<Self::WalkDir> {
+        let path = path.as_ref();
+
+        WalkDir::new(MountWalkSource(self.clone()), path, options).context("walk_dir", &[path])
+    }
+
+    fn create_file<P, B>(&self, path: P, buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        self.with("create_file", path.as_ref(), |fs, path| {
+            fs.create_file(path, buf.as_ref())
+        })
+    }
+
+    fn write_file<P, B>(&self, path: P, buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        self.with("write_file", path.as_ref(), |fs, path| {
+            fs.write_file(path, buf.as_ref())
+        })
+    }
+
+    fn overwrite_file<P, B>(&self, path: P, buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        self.with("overwrite_file", path.as_ref(), |fs, path| {
+            fs.overwrite_file(path, buf.as_ref())
+        })
+    }
+
+    fn set_len<P: AsRef<Path>>(&self, path: P, size: u64) -> Result<()> {
+        self.with("set_len", path.as_ref(), |fs, path| fs.set_len(path, size))
+    }
+
+    fn sync_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.with("sync_file", path.as_ref(), |fs, path| fs.sync_file(path))
+    }
+
+    fn sync_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        if self.is_virtual(&self.normalize(path)) {
+            return Ok(());
+        }
+
+        self.with("sync_dir", path, |fs, path| fs.sync_dir(path))
+    }
+
+    fn read_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
+        self.with("read_file", path.as_ref(), |fs, path| fs.read_file(path))
+    }
+
+    fn read_file_to_string<P: AsRef<Path>>(&self, path: P) -> Result<String> {
+        self.with("read_file_to_string", path.as_ref(), |fs, path| {
+            fs.read_file_to_string(path)
+        })
+    }
+
+    fn read_file_into<P, B>(&self, path: P, mut buf: B) -> Result<usize>
+    where
+        P: AsRef<Path>,
+        B: AsMut<Vec<u8>>,
+    {
+        self.with("read_file_into", path.as_ref(), |fs, path| {
+            fs.read_file_into(path, buf.as_mut())
+        })
+    }
+
+    fn open<P: AsRef<Path>>(&self, path: P) -> Result<Self::OpenFile> {
+        self.with("open", path.as_ref(), |fs, path| fs.open(path))
+    }
+
+    #[cfg(feature = "hash")]
+    fn hash_file<P: AsRef<Path>>(&self, path: P, algorithm: Algorithm) -> Result<Digest> {
+        self.with("hash_file", path.as_ref(), |fs, path| {
+            fs.hash_file(path, algorithm)
+        })
+    }
+
+    fn open_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &OpenOptions,
+    ) -> Result<Self::OpenFile> {
+        self.with("open_with_options", path.as_ref(), |fs, path| {
+            fs.open_with_options(path, options)
+        })
+    }
+
+    fn remove_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.check_not_busy(&self.normalize(path))
+            .context("remove_file", &[path])?;
+        self.with("remove_file", path, |fs, path| fs.remove_file(path))
+    }
+
+    fn copy_file<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref(), to.as_ref());
+
+        self.copy_file_between(&self.normalize(from), &self.normalize(to))
+            .context("copy_file", &[from, to])
+    }
+
+    fn copy_dir<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref(), to.as_ref());
+
+        self.copy_dir_between(&self.normalize(from), &self.normalize(to))
+            .context("copy_dir", &[from, to])
+    }
+
+    fn rename<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref(), to.as_ref());
+
+        self.rename_between(&self.normalize(from), &self.normalize(to))
+            .context("rename", &[from, to])
+    }
+
+    fn readonly<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+
+        self.metadata(path)
+            .map(|m| ::Metadata::readonly(&m))
+            .context("readonly", &[path])
+    }
+
+    fn set_readonly<P: AsRef<Path>>(&self, path: P, readonly: bool) -> Result<()> {
+        self.with("set_readonly", path.as_ref(), |fs, path| {
+            fs.set_readonly(path, readonly)
+        })
+    }
+
+    fn is_hidden<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        self.with("is_hidden", path.as_ref(), |fs, path| fs.is_hidden(path))
+    }
+
+    fn modified<P: AsRef<Path>>(&self, path: P) -> Result<SystemTime> {
+        let path = path.as_ref();
+
+        self.metadata(path)
+            .and_then(|m| Ok(::Metadata::modified(&m)?))
+            .context("modified", &[path])
+    }
+
+    fn set_modified<P: AsRef<Path>>(&self, path: P, time: SystemTime) -> Result<()> {
+        self.with("set_modified", path.as_ref(), |fs, path| {
+            fs.set_modified(path, time)
+        })
+    }
+
+    fn accessed<P: AsRef<Path>>(&self, path: P) -> Result<SystemTime> {
+        let path = path.as_ref();
+
+        self.metadata(path)
+            .and_then(|m| Ok(::Metadata::accessed(&m)?))
+            .context("accessed", &[path])
+    }
+
+    fn set_accessed<P: AsRef<Path>>(&self, path: P, time: SystemTime) -> Result<()> {
+        self.with("set_accessed", path.as_ref(), |fs, path| {
+            fs.set_accessed(path, time)
+        })
+    }
+
+    fn len<P: AsRef<Path>>(&self, path: P) -> u64 {
+        self.with("len", path.as_ref(), |fs, path| Ok(fs.len(path)))
+            .unwrap_or(0)
+    }
+
+    fn available_space<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+        self.with("available_space", path.as_ref(), |fs, path| {
+            fs.available_space(path)
+        })
+    }
+
+    fn total_space<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+        self.with("total_space", path.as_ref(), |fs, path| {
+            fs.total_space(path)
+        })
+    }
+}
+
+impl MountFileSystem {
+    /// Returns the entries of `dir`, including any mount points in it.
+    fn list(&self, path: &Path) -> Result<ReadDir> {
+        let dir = self.normalize(path);
+        let mut names = BTreeMap::new();
+
+        match self
+            .route(&dir)
+            .and_then(|route| route.fs.read_dir(&route.path))
+        {
+            Ok(entries) => {
+                for entry in entries {
+                    let entry = entry?;
+
+                    names.insert(::DirEntry::file_name(&entry), Entry::Inner(entry));
+                }
+            }
+            Err(err) => {
+                if !self.contains_mount(&dir) {
+                    return Err(err);
+                }
+            }
+        }
+
+        for (mount_point, fs) in self.mounts.read().recover().iter() {
+            let relative = match mount_point.strip_prefix(&dir) {
+                Ok(relative) => relative,
+                Err(_) => continue,
+            };
+            let mut components = relative.components();
+            let name = match components.next() {
+                Some(Component::Normal(name)) => name.to_os_string(),
+                _ => continue,
+            };
+
+            if components.next().is_none() {
+                names.insert(name, Entry::MountPoint(fs.clone()));
+            } else {
+                names.entry(name).or_insert(Entry::Virtual);
+            }
+        }
+
+        let entries = names
+            .into_iter()
+            .map(|(file_name, entry)| {
+                Ok(DirEntry {
+                    path: path.join(&file_name),
+                    file_name,
+                    entry,
+                })
+            })
+            .collect();
+
+        Ok(ReadDir(Vec::into_iter(entries)))
+    }
+
+    /// Copies the file at the normalized path `from` to `to`, reading it from one file system and
+    /// writing it to the other if they are mounted separately.
+    fn copy_file_between(&self, from: &Path, to: &Path) -> Result<()> {
+        let (source, target) = (self.route(from)?, self.route(to)?);
+
+        if source.mount_point == target.mount_point {
+            return source.fs.copy_file(&source.path, &target.path);
+        }
+
+        if self.is_dir(to) {
+            return Err(io::Error::new(ErrorKind::IsADirectory, "is a directory").into());
+        }
+
+        target
+            .fs
+            .write_file(&target.path, &source.fs.read_file(&source.path)?)?;
+
+        if source.fs.readonly(&source.path)? {
+            target.fs.set_readonly(&target.path, true)?;
+        }
+
+        Ok(())
+    }
+
+    /// Copies the directory at the normalized path `from` to `to`, one node at a time if it
+    /// spans more than one mounted file system.
+    fn copy_dir_between(&self, from: &Path, to: &Path) -> Result<()> {
+        let (source, target) = (self.route(from)?, self.route(to)?);
+
+        if source.mount_point == target.mount_point && !self.contains_mount(from) {
+            return source.fs.copy_dir(&source.path, &target.path);
+        }
+
+        if !self.is_dir(from) {
+            return Err(io::Error::new(ErrorKind::NotADirectory, "not a directory").into());
+        }
+
+        if self.exists(to) {
+            return Err(already_exists().into());
+        }
+
+        if to.starts_with(from) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "cannot copy a directory into itself",
+            )
+            .into());
+        }
+
+        self.create_dir(to)?;
+
+        for entry in self.read_dir(from)? {
+            let file_name = ::DirEntry::file_name(&entry?);
+            let (from, to) = (from.join(&file_name), to.join(&file_name));
+
+            match self.file_type(&from)? {
+                FileType::Dir => self.copy_dir(&from, &to)?,
+                FileType::File => self.copy_file(&from, &to)?,
+                _ => {
+                    return Err(io::Error::new(
+                        ErrorKind::Unsupported,
+                        "cannot copy symlinks or special files between mounted file systems",
+                    )
+                    .into())
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Renames the node at the normalized path `from` to `to`, which must be in the same
+    /// mounted file system.
+    fn rename_between(&self, from: &Path, to: &Path) -> Result<()> {
+        self.check_not_busy(from)?;
+        self.check_not_busy(to)?;
+
+        let (source, target) = (self.route(from)?, self.route(to)?);
+
+        if source.mount_point != target.mount_point {
+            return Err(
+                io::Error::new(ErrorKind::CrossesDevices, "invalid cross-device link").into(),
+            );
+        }
+
+        source.fs.rename(&source.path, &target.path)
+    }
+}
+
+/// The metadata of a node in a [`MountFileSystem`], or of a directory that only exists to
+/// contain mount points.
+///
+/// [`MountFileSystem`]: struct.MountFileSystem.html
+pub struct Metadata(Option<DynMetadata>);
+
+impl ::Metadata for Metadata {
+    fn file_type(&self) -> FileType {
+        self.0.as_ref().map_or(FileType::Dir, |m| m.file_type())
+    }
+
+    fn len(&self) -> u64 {
+        self.0.as_ref().map_or(0, |m| m.len())
+    }
+
+    fn readonly(&self) -> bool {
+        self.0.as_ref().is_none_or(|m| m.readonly())
+    }
+
+    fn created(&self) -> io::Result<SystemTime> {
+        self.0
+            .as_ref()
+            .map_or_else(|| Err(no_times()), |m| m.created())
+    }
+
+    fn modified(&self) -> io::Result<SystemTime> {
+        self.0
+            .as_ref()
+            .map_or_else(|| Err(no_times()), |m| m.modified())
+    }
+
+    fn accessed(&self) -> io::Result<SystemTime> {
+        self.0
+            .as_ref()
+            .map_or_else(|| Err(no_times()), |m| m.accessed())
+    }
+}
+
+impl fmt::Debug for Metadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Metadata")
+            .field("file_type", &::Metadata::file_type(self))
+            .finish()
+    }
+}
+
+/// An entry of a directory in a [`MountFileSystem`], which may be a mount point.
+///
+/// [`MountFileSystem`]: struct.MountFileSystem.html
+pub struct DirEntry {
+    path: PathBuf,
+    file_name: OsString,
+    entry: Entry,
+}
+
+enum Entry {
+    Inner(DynDirEntry),
+    MountPoint(Mounted),
+    Virtual,
+}
+
+impl ::DirEntry for DirEntry {
+    type Metadata = Metadata;
+
+    fn file_name(&self) -> OsString {
+        self.file_name.clone()
+    }
+
+    fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    fn file_type(&self) -> io::Result<FileType> {
+        match self.entry {
+            Entry::Inner(ref entry) => ::DirEntry::file_type(entry),
+            Entry::MountPoint(_) | Entry::Virtual => Ok(FileType::Dir),
+        }
+    }
+
+    fn metadata(&self) -> io::Result<Metadata> {
+        match self.entry {
+            Entry::Inner(ref entry) => ::DirEntry::metadata(entry).map(|m| Metadata(Some(m))),
+            Entry::MountPoint(ref fs) => fs
+                .metadata(Path::new("/"))
+                .map(|m| Metadata(Some(m)))
+                .map_err(io::Error::from),
+            Entry::Virtual => Ok(Metadata(None)),
+        }
+    }
+}
+
+impl fmt::Debug for DirEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DirEntry")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+/// The entries of a directory in a [`MountFileSystem`], including mount points, sorted by name.
+///
+/// [`MountFileSystem`]: struct.MountFileSystem.html
+#[derive(Debug)]
+pub struct ReadDir(IntoIter<io::Result<DirEntry>>);
+
+impl Iterator for ReadDir {
+    type Item = io::Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+impl ::ReadDir<DirEntry> for ReadDir {}
+
+struct MountWalkSource(MountFileSystem);
+
+impl WalkSource for MountWalkSource {
+    fn list(&self, dir: &Path) -> io::Result<Vec<(PathBuf, FileType)>> {
+        let mut children = vec![];
+
+        for entry in self.0.read_dir(dir)? {
+            let entry = entry?;
+
+            children.push((::DirEntry::path(&entry), ::DirEntry::file_type(&entry)?));
+        }
+
+        Ok(children)
+    }
+
+    fn follow(&self, path: &Path) -> io::Result<(PathBuf, FileType)> {
+        let file_type = self.0.file_type(path)?;
+
+        Ok((self.0.canonicalize(path)?, file_type))
+    }
+}
+
+fn not_found() -> io::Error {
+    io::Error::new(ErrorKind::NotFound, "no such file or directory")
+}
+
+fn already_exists() -> io::Error {
+    io::Error::new(ErrorKind::AlreadyExists, "file exists")
+}
+
+fn no_times() -> io::Error {
+    io::Error::new(
+        ErrorKind::Unsupported,
+        "directories that contain mount points have no timestamps",
+    )
+}
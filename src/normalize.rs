@@ -0,0 +1,25 @@
+use std::path::{Component, Path, PathBuf};
+
+/// Returns `path` as an absolute path with `.` and `..` components removed, resolving it against
+/// `cwd` if it is relative.
+///
+/// Symlinks aren't followed, so `..` always removes the name before it, as in the paths that the
+/// wrapping file systems pass on.
+/// A prefix, such as a drive on Windows, replaces all of `cwd`.
+pub fn normalize(cwd: &Path, path: &Path) -> PathBuf {
+    let mut normalized = cwd.to_path_buf();
+
+    for component in path.components() {
+        match component {
+            Component::Prefix(prefix) => normalized = PathBuf::from(prefix.as_os_str()),
+            Component::RootDir => normalized.push(component),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::Normal(name) => normalized.push(name),
+        }
+    }
+
+    normalized
+}
@@ -0,0 +1,1122 @@
+use std::ffi::OsString;
+use std::io::{self, Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::vec::IntoIter;
+
+use object_store::path::{Path as ObjectPath, PathPart};
+use object_store::{Error as StoreError, ObjectMeta, ObjectStore, PutMode, PutPayload};
+use tokio::runtime::{Builder, Runtime};
+
+use error::Context;
+use normalize::normalize;
+use recover::Recover;
+use walk::WalkSource;
+#[cfg(feature = "lock")]
+use LockableFile;
+use {FileSystem, FileType, OpenOptions, Result, WalkDir, WalkDirOptions};
+
+/// The name of the empty objects that keep empty directories in existence.
+/// It is hidden from directory listings, and paths cannot use it.
+const DIR_MARKER: &str = ".filesystem-dir";
+
+/// A file system over an [`ObjectStore`], such as Amazon S3, Google Cloud Storage, or Azure Blob
+/// Storage, which is available with the `object_store` feature.
+///
+/// Files are objects whose keys are their absolute paths without the leading `/`, so `/a/b` is
+/// stored at `a/b`.
+/// Directories are key prefixes: a directory exists if it is the root or if any object's key
+/// starts with its path.
+/// Creating a directory stores an empty object named `.filesystem-dir` in it so that it exists
+/// while it is empty, which is hidden from [`read_dir`] and cannot be used as a name.
+/// Objects that are created by other clients make their parent directories exist without them.
+///
+/// Paths are made absolute against a current directory, starting at `/`, and `.` and `..`
+/// components are resolved lexically.
+/// Every operation blocks on a Tokio runtime that is owned by the file system, so it must not be
+/// used from within another Tokio runtime.
+///
+/// Object stores have no permissions, symlinks, or access times, so [`readonly`] always returns
+/// `false`, while changing permissions or times fails with [`ErrorKind::Unsupported`].
+/// Modification times are the time that an object was last written, and directories have none.
+/// Stores have no fixed size, so both the available and total space are `u64::MAX`.
+/// Renaming and removing directories take an operation per object, and are not atomic.
+///
+/// Open files are read into memory, and written back to the store when they are flushed, synced,
+/// or dropped.
+///
+/// To use a particular store, enable the `object_store` crate's feature for it, such as `aws`.
+///
+/// [`ObjectStore`]: https://docs.rs/object_store/0.12/object_store/trait.ObjectStore.html
+/// [`read_dir`]: trait.FileSystem.html#tymethod.read_dir
+/// [`readonly`]: trait.FileSystem.html#method.readonly
+/// [`ErrorKind::Unsupported`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.Unsupported
+#[derive(Clone, Debug)]
+pub struct ObjectStoreFileSystem {
+    store: Arc<dyn ObjectStore>,
+    runtime: Arc<Runtime>,
+    cwd: Arc<Mutex<PathBuf>>,
+}
+
+/// A node in the store, which is found from the objects under its key.
+enum Node {
+    File(ObjectMeta),
+    Dir,
+}
+
+impl ObjectStoreFileSystem {
+    /// Creates a file system over `store`, with a new Tokio runtime to run its operations on.
+    pub fn new(store: Arc<dyn ObjectStore>) -> Result<Self> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+
+        Ok(ObjectStoreFileSystem {
+            store,
+            runtime: Arc::new(runtime),
+            cwd: Arc::new(Mutex::new(PathBuf::from("/"))),
+        })
+    }
+
+    /// Returns the store that files are kept in.
+    pub fn store(&self) -> &Arc<dyn ObjectStore> {
+        &self.store
+    }
+
+    /// Returns `path` as an absolute path with `.` and `..` components removed.
+    fn normalize(&self, path: &Path) -> PathBuf {
+        normalize(&self.cwd.lock().recover(), path)
+    }
+
+    /// Returns the key of the normalized `path`, or `None` for the root.
+    fn key(&self, path: &Path) -> io::Result<Option<ObjectPath>> {
+        let mut parts = vec![];
+
+        for component in path.components() {
+            if let Component::Normal(name) = component {
+                let name = name.to_str().ok_or_else(|| {
+                    io::Error::new(ErrorKind::InvalidInput, "path is not valid UTF-8")
+                })?;
+
+                if name == DIR_MARKER {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidInput,
+                        "name is reserved for directory markers",
+                    ));
+                }
+
+                parts.push(PathPart::from(name));
+            }
+        }
+
+        if parts.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(parts.into_iter().collect()))
+        }
+    }
+
+    /// Returns the key of the normalized `path`, which cannot be the root.
+    fn file_key(&self, path: &Path) -> io::Result<ObjectPath> {
+        self.key(path)?.ok_or_else(is_a_directory)
+    }
+
+    fn block_on<F: ::std::future::Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Returns the node at the normalized `path`.
+    fn node(&self, path: &Path) -> io::Result<Node> {
+        let key = match self.key(path)? {
+            Some(key) => key,
+            None => return Ok(Node::Dir),
+        };
+
+        match self.block_on(self.store.head(&key)) {
+            Ok(meta) => Ok(Node::File(meta)),
+            Err(StoreError::NotFound { .. }) => {
+                let list = self.list(Some(&key))?;
+
+                if list.0.is_empty() && list.1.is_empty() {
+                    Err(not_found())
+                } else {
+                    Ok(Node::Dir)
+                }
+            }
+            Err(err) => Err(store_error(err)),
+        }
+    }
+
+    /// Lists the objects and prefixes directly under `key`, including directory markers.
+    fn list(&self, key: Option<&ObjectPath>) -> io::Result<(Vec<ObjectMeta>, Vec<ObjectPath>)> {
+        let list = self
+            .block_on(self.store.list_with_delimiter(key))
+            .map_err(store_error)?;
+
+        Ok((list.objects, list.common_prefixes))
+    }
+
+    /// Makes sure that the parent directory of the normalized `path` exists.
+    fn check_parent(&self, path: &Path) -> io::Result<()> {
+        match path.parent().map(|parent| self.node(parent)) {
+            None | Some(Ok(Node::Dir)) => Ok(()),
+            Some(Ok(Node::File(_))) => Err(not_a_directory()),
+            Some(Err(err)) => Err(err),
+        }
+    }
+
+    /// Keeps the parent directory of the normalized `path` in existence once `path` is removed.
+    fn keep_parent(&self, path: &Path) -> io::Result<()> {
+        match path.parent().map(|parent| self.key(parent)) {
+            Some(Ok(Some(key))) => self.put(&key.child(DIR_MARKER), vec![]),
+            Some(Err(err)) => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    fn get(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let key = self.file_key(path)?;
+        let result = self
+            .block_on(self.store.get(&key))
+            .and_then(|result| self.block_on(result.bytes()));
+
+        match result {
+            Ok(bytes) => Ok(bytes.to_vec()),
+            Err(StoreError::NotFound { .. }) => match self.node(path)? {
+                Node::Dir => Err(is_a_directory()),
+                Node::File(_) => Err(not_found()),
+            },
+            Err(err) => Err(store_error(err)),
+        }
+    }
+
+    fn put(&self, key: &ObjectPath, buf: Vec<u8>) -> io::Result<()> {
+        self.block_on(self.store.put(key, PutPayload::from(buf)))
+            .map(|_| ())
+            .map_err(store_error)
+    }
+
+    fn delete(&self, key: &ObjectPath) -> io::Result<()> {
+        self.block_on(self.store.delete(key)).map_err(store_error)
+    }
+
+    /// Returns all of the objects under `key`, including directory markers.
+    fn descendants(&self, key: Option<&ObjectPath>) -> io::Result<Vec<ObjectPath>> {
+        let (objects, prefixes) = self.list(key)?;
+        let mut keys: Vec<ObjectPath> = objects.into_iter().map(|meta| meta.location).collect();
+
+        for prefix in prefixes {
+            keys.extend(self.descendants(Some(&prefix))?);
+        }
+
+        Ok(keys)
+    }
+
+    /// Returns `true` if the directory at `key` has any entries other than its marker.
+    fn has_entries(&self, key: Option<&ObjectPath>) -> io::Result<bool> {
+        let (objects, prefixes) = self.list(key)?;
+
+        Ok(!prefixes.is_empty() || objects.iter().any(|meta| !is_marker(&meta.location)))
+    }
+
+    fn entries(&self, dir: &Path) -> io::Result<Vec<(OsString, Metadata)>> {
+        let (objects, prefixes) = self.list(self.key(dir)?.as_ref())?;
+        let mut entries = vec![];
+
+        for meta in objects {
+            if !is_marker(&meta.location) {
+                entries.push((file_name(&meta.location), Metadata::file(&meta)));
+            }
+        }
+
+        for prefix in prefixes {
+            entries.push((file_name(&prefix), Metadata::dir()));
+        }
+
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Ok(entries)
+    }
+
+    /// Runs `f`, with `operation` and `paths` attached to any error.
+    fn with_context<T, F>(&self, operation: &'static str, paths: &[&Path], f: F) -> Result<T>
+    where
+        F: FnOnce() -> io::Result<T>,
+    {
+        f().context(operation, paths)
+    }
+
+    fn open_file(&self, path: &Path, options: &OpenOptions) -> io::Result<OpenFile> {
+        // Based on the validation in std's unix OpenOptions
+        let invalid = if options.is_write() {
+            options.is_truncate() && options.is_append() && !options.is_create_new()
+        } else {
+            !options.is_read()
+                || options.is_truncate()
+                || options.is_create()
+                || options.is_create_new()
+        };
+
+        if invalid {
+            return Err(io::Error::from(ErrorKind::InvalidInput));
+        }
+
+        let contents = match self.node(path) {
+            Ok(_) if options.is_create_new() => return Err(already_exists()),
+            Ok(Node::Dir) => return Err(is_a_directory()),
+            Ok(Node::File(_)) if options.is_truncate() => {
+                self.put(&self.file_key(path)?, vec![])?;
+                vec![]
+            }
+            Ok(Node::File(_)) => self.get(path)?,
+            Err(ref err)
+                if err.kind() == ErrorKind::NotFound
+                    && (options.is_create() || options.is_create_new()) =>
+            {
+                self.create_file(path, [])?;
+                vec![]
+            }
+            Err(err) => return Err(err),
+        };
+
+        Ok(OpenFile {
+            fs: self.clone(),
+            key: self.file_key(path)?,
+            options: options.clone(),
+            buffer: Mutex::new(Buffer {
+                contents: Cursor::new(contents),
+                dirty: false,
+            }),
+        })
+    }
+}
+
+impl FileSystem for ObjectStoreFileSystem {
+    type DirEntry = DirEntry;
+    type ReadDir = ReadDir;
+    type Metadata = Metadata;
+    type OpenFile = OpenFile;
+    type WalkDir = WalkDir;
+
+    fn current_dir(&self) -> Result<PathBuf> {
+        Ok(self.cwd.lock().recover().clone())
+    }
+
+    fn set_current_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("set_current_dir", &[path], || {
+            let path = self.normalize(path);
+
+            match self.node(&path)? {
+                Node::Dir => {
+                    *self.cwd.lock().recover() = path;
+                    Ok(())
+                }
+                Node::File(_) => Err(not_a_directory()),
+            }
+        })
+    }
+
+    fn metadata<P: AsRef<Path>>(&self, path: P) -> Result<Self::Metadata> {
+        let path = path.as_ref();
+
+        self.with_context("metadata", &[path], || {
+            match self.node(&self.normalize(path))? {
+                Node::File(meta) => Ok(Metadata::file(&meta)),
+                Node::Dir => Ok(Metadata::dir()),
+            }
+        })
+    }
+
+    fn canonicalize<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf> {
+        let path = path.as_ref();
+
+        self.with_context("canonicalize", &[path], || {
+            let path = self.normalize(path);
+
+            self.node(&path)?;
+
+            Ok(path)
+        })
+    }
+
+    fn is_dir<P: AsRef<Path>>(&self, path: P) -> bool {
+        matches!(self.node(&self.normalize(path.as_ref())), Ok(Node::Dir))
+    }
+
+    fn is_file<P: AsRef<Path>>(&self, path: P) -> bool {
+        matches!(self.node(&self.normalize(path.as_ref())), Ok(Node::File(_)))
+    }
+
+    fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.node(&self.normalize(path.as_ref())).is_ok()
+    }
+
+    fn try_exists<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+
+        self.with_context("try_exists", &[path], || {
+            match self.node(&self.normalize(path)) {
+                Ok(_) => Ok(true),
+                Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(false),
+                Err(err) => Err(err),
+            }
+        })
+    }
+
+    fn create_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("create_dir", &[path], || {
+            let path = self.normalize(path);
+
+            if self.exists(&path) {
+                return Err(already_exists());
+            }
+
+            self.check_parent(&path)?;
+            self.put(&self.file_key(&path)?.child(DIR_MARKER), vec![])
+        })
+    }
+
+    fn create_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("create_dir_all", &[path], || {
+            let path = self.normalize(path);
+
+            if self.is_dir(&path) {
+                return Ok(());
+            }
+
+            if let Some(parent) = path.parent() {
+                self.create_dir_all(parent)?;
+            }
+
+            self.create_dir(&path).map_err(io::Error::from)
+        })
+    }
+
+    fn remove_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("remove_dir", &[path], || {
+            let path = self.normalize(path);
+
+            if let Node::File(_) = self.node(&path)? {
+                return Err(not_a_directory());
+            }
+
+            let key = self.file_key(&path)?;
+
+            if self.has_entries(Some(&key))? {
+                return Err(directory_not_empty());
+            }
+
+            self.keep_parent(&path)?;
+            self.delete(&key.child(DIR_MARKER))
+        })
+    }
+
+    fn remove_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("remove_dir_all", &[path], || {
+            let path = self.normalize(path);
+
+            if let Node::File(_) = self.node(&path)? {
+                return Err(not_a_directory());
+            }
+
+            self.keep_parent(&path)?;
+
+            for key in self.descendants(self.key(&path)?.as_ref())? {
+                self.delete(&key)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    fn read_dir<P: AsRef<Path>>(&self, path: P) -> Result<Self::ReadDir> {
+        let path = path.as_ref();
+
+        self.with_context("read_dir", &[path], || {
+            let dir = self.normalize(path);
+
+            if let Node::File(_) = self.node(&dir)? {
+                return Err(not_a_directory());
+            }
+
+            let entries = self
+                .entries(&dir)?
+                .into_iter()
+                .map(|(file_name, metadata)| {
+                    Ok(DirEntry {
+                        path: path.join(&file_name),
+                        file_name,
+                        metadata,
+                    })
+                })
+                .collect();
+
+            Ok(ReadDir::new(entries))
+        })
+    }
+
+    fn walk_dir_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &WalkDirOptions,
+    ) -> Result<Self::WalkDir> {
+        let path = path.as_ref();
+
+        WalkDir::new(ObjectWalkSource(self.clone()), path, options).context("walk_dir", &[path])
+    }
+
+    fn create_file<P, B>(&self, path: P, buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+
+        self.with_context("create_file", &[path], || {
+            let path = self.normalize(path);
+
+            if self.exists(&path) {
+                return Err(already_exists());
+            }
+
+            self.check_parent(&path)?;
+
+            let payload = PutPayload::from(buf.as_ref().to_vec());
+
+            match self.block_on(self.store.put_opts(
+                &self.file_key(&path)?,
+                payload,
+                PutMode::Create.into(),
+            )) {
+                Ok(_) => Ok(()),
+                Err(StoreError::AlreadyExists { .. }) => Err(already_exists()),
+                Err(err) => Err(store_error(err)),
+            }
+        })
+    }
+
+    fn write_file<P, B>(&self, path: P, buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+
+        self.with_context("write_file", &[path], || {
+            let path = self.normalize(path);
+
+            match self.node(&path) {
+                Ok(Node::Dir) => return Err(is_a_directory()),
+                Ok(Node::File(_)) => {}
+                Err(ref err) if err.kind() == ErrorKind::NotFound => self.check_parent(&path)?,
+                Err(err) => return Err(err),
+            }
+
+            self.put(&self.file_key(&path)?, buf.as_ref().to_vec())
+        })
+    }
+
+    fn overwrite_file<P, B>(&self, path: P, buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+
+        self.with_context("overwrite_file", &[path], || {
+            let path = self.normalize(path);
+
+            if let Node::Dir = self.node(&path)? {
+                return Err(is_a_directory());
+            }
+
+            self.put(&self.file_key(&path)?, buf.as_ref().to_vec())
+        })
+    }
+
+    fn set_len<P: AsRef<Path>>(&self, path: P, size: u64) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("set_len", &[path], || {
+            let path = self.normalize(path);
+            let mut contents = self.get(&path)?;
+
+            contents.resize(size as usize, 0);
+            self.put(&self.file_key(&path)?, contents)
+        })
+    }
+
+    fn sync_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("sync_file", &[path], || {
+            // Objects are durable once they are written
+            match self.node(&self.normalize(path))? {
+                Node::File(_) => Ok(()),
+                Node::Dir => Err(is_a_directory()),
+            }
+        })
+    }
+
+    fn sync_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("sync_dir", &[path], || {
+            match self.node(&self.normalize(path))? {
+                Node::File(_) => Err(not_a_directory()),
+                Node::Dir => Ok(()),
+            }
+        })
+    }
+
+    fn read_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
+        let path = path.as_ref();
+
+        self.with_context("read_file", &[path], || self.get(&self.normalize(path)))
+    }
+
+    fn read_file_to_string<P: AsRef<Path>>(&self, path: P) -> Result<String> {
+        let path = path.as_ref();
+
+        self.with_context("read_file_to_string", &[path], || {
+            String::from_utf8(self.read_file(path)?)
+                .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))
+        })
+    }
+
+    fn read_file_into<P, B>(&self, path: P, mut buf: B) -> Result<usize>
+    where
+        P: AsRef<Path>,
+        B: AsMut<Vec<u8>>,
+    {
+        let path = path.as_ref();
+
+        self.with_context("read_file_into", &[path], || {
+            let contents = self.read_file(path)?;
+
+            buf.as_mut().extend_from_slice(&contents);
+
+            Ok(contents.len())
+        })
+    }
+
+    fn open<P: AsRef<Path>>(&self, path: P) -> Result<Self::OpenFile> {
+        self.open_with_options(path, OpenOptions::new().read(true))
+    }
+
+    fn open_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &OpenOptions,
+    ) -> Result<Self::OpenFile> {
+        let path = path.as_ref();
+
+        self.with_context("open_with_options", &[path], || {
+            self.open_file(&self.normalize(path), options)
+        })
+    }
+
+    fn remove_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("remove_file", &[path], || {
+            let path = self.normalize(path);
+
+            if let Node::Dir = self.node(&path)? {
+                return Err(is_a_directory());
+            }
+
+            self.keep_parent(&path)?;
+            self.delete(&self.file_key(&path)?)
+        })
+    }
+
+    fn copy_file<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref(), to.as_ref());
+
+        self.with_context("copy_file", &[from, to], || {
+            let (from, to) = (self.normalize(from), self.normalize(to));
+
+            if let Node::Dir = self.node(&from)? {
+                return Err(is_a_directory());
+            }
+
+            if self.is_dir(&to) {
+                return Err(is_a_directory());
+            }
+
+            self.check_parent(&to)?;
+            self.block_on(
+                self.store
+                    .copy(&self.file_key(&from)?, &self.file_key(&to)?),
+            )
+            .map_err(store_error)
+        })
+    }
+
+    fn copy_dir<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref(), to.as_ref());
+
+        self.with_context("copy_dir", &[from, to], || {
+            let (from, to) = (self.normalize(from), self.normalize(to));
+
+            if !self.is_dir(&from) {
+                return Err(not_a_directory());
+            }
+
+            if self.exists(&to) {
+                return Err(already_exists());
+            }
+
+            if to.starts_with(&from) {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "cannot copy a directory into itself",
+                ));
+            }
+
+            self.create_dir(&to)?;
+
+            for (file_name, metadata) in self.entries(&from)? {
+                let (from, to) = (from.join(&file_name), to.join(&file_name));
+
+                if ::Metadata::is_dir(&metadata) {
+                    self.copy_dir(&from, &to)?;
+                } else {
+                    self.copy_file(&from, &to)?;
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    fn rename<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref(), to.as_ref());
+
+        self.with_context("rename", &[from, to], || {
+            let (from, to) = (self.normalize(from), self.normalize(to));
+            let node = self.node(&from)?;
+
+            if from == to {
+                return Ok(());
+            }
+
+            if to.starts_with(&from) {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "cannot move a directory into itself",
+                ));
+            }
+
+            self.check_parent(&to)?;
+
+            let (from_key, to_key) = (self.file_key(&from)?, self.file_key(&to)?);
+            let replaced = match self.node(&to) {
+                Ok(node) => Some(node),
+                Err(ref err) if err.kind() == ErrorKind::NotFound => None,
+                Err(err) => return Err(err),
+            };
+
+            match (&node, replaced) {
+                (&Node::File(_), Some(Node::Dir)) => return Err(is_a_directory()),
+                (&Node::Dir, Some(Node::File(_))) => return Err(not_a_directory()),
+                (&Node::Dir, Some(Node::Dir)) if self.has_entries(Some(&to_key))? => {
+                    return Err(directory_not_empty());
+                }
+                _ => {}
+            }
+
+            self.keep_parent(&from)?;
+
+            if let Node::File(_) = node {
+                return self
+                    .block_on(self.store.rename(&from_key, &to_key))
+                    .map_err(store_error);
+            }
+
+            for key in self.descendants(Some(&from_key))? {
+                let moved = match key.prefix_match(&from_key) {
+                    Some(rest) => to_key.parts().chain(rest).collect(),
+                    None => continue,
+                };
+
+                self.block_on(self.store.rename(&key, &moved))
+                    .map_err(store_error)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    fn readonly<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+
+        self.with_context("readonly", &[path], || {
+            self.node(&self.normalize(path)).map(|_| false)
+        })
+    }
+
+    fn set_readonly<P: AsRef<Path>>(&self, path: P, _readonly: bool) -> Result<()> {
+        Err(unsupported()).context("set_readonly", &[path.as_ref()])
+    }
+
+    fn modified<P: AsRef<Path>>(&self, path: P) -> Result<SystemTime> {
+        let path = path.as_ref();
+
+        self.with_context("modified", &[path], || {
+            ::Metadata::modified(&self.metadata(path)?)
+        })
+    }
+
+    fn set_modified<P: AsRef<Path>>(&self, path: P, _time: SystemTime) -> Result<()> {
+        Err(unsupported()).context("set_modified", &[path.as_ref()])
+    }
+
+    fn accessed<P: AsRef<Path>>(&self, path: P) -> Result<SystemTime> {
+        let path = path.as_ref();
+
+        self.with_context("accessed", &[path], || {
+            ::Metadata::accessed(&self.metadata(path)?)
+        })
+    }
+
+    fn set_accessed<P: AsRef<Path>>(&self, path: P, _time: SystemTime) -> Result<()> {
+        Err(unsupported()).context("set_accessed", &[path.as_ref()])
+    }
+
+    fn len<P: AsRef<Path>>(&self, path: P) -> u64 {
+        self.metadata(path)
+            .map(|m| ::Metadata::len(&m))
+            .unwrap_or(0)
+    }
+
+    fn available_space<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+        self.total_space(path)
+    }
+
+    fn total_space<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+        let path = path.as_ref();
+
+        self.with_context("total_space", &[path], || {
+            self.node(&self.normalize(path))?;
+
+            Ok(u64::MAX)
+        })
+    }
+}
+
+/// The metadata of an object or a key prefix in an [`ObjectStoreFileSystem`].
+///
+/// [`ObjectStoreFileSystem`]: struct.ObjectStoreFileSystem.html
+#[derive(Clone, Debug)]
+pub struct Metadata {
+    file_type: FileType,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl Metadata {
+    fn file(meta: &ObjectMeta) -> Self {
+        let modified = Duration::new(
+            meta.last_modified.timestamp() as u64,
+            meta.last_modified.timestamp_subsec_nanos(),
+        );
+
+        Metadata {
+            file_type: FileType::File,
+            len: meta.size,
+            modified: UNIX_EPOCH.checked_add(modified),
+        }
+    }
+
+    fn dir() -> Self {
+        Metadata {
+            file_type: FileType::Dir,
+            len: 0,
+            modified: None,
+        }
+    }
+}
+
+impl ::Metadata for Metadata {
+    fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn readonly(&self) -> bool {
+        false
+    }
+
+    fn created(&self) -> io::Result<SystemTime> {
+        Err(unsupported())
+    }
+
+    fn modified(&self) -> io::Result<SystemTime> {
+        self.modified.ok_or_else(unsupported)
+    }
+
+    fn accessed(&self) -> io::Result<SystemTime> {
+        Err(unsupported())
+    }
+}
+
+/// A file opened from an [`ObjectStoreFileSystem`], whose contents are kept in memory until
+/// they are written back to the store.
+///
+/// [`ObjectStoreFileSystem`]: struct.ObjectStoreFileSystem.html
+#[derive(Debug)]
+pub struct OpenFile {
+    fs: ObjectStoreFileSystem,
+    key: ObjectPath,
+    options: OpenOptions,
+    buffer: Mutex<Buffer>,
+}
+
+#[derive(Debug)]
+struct Buffer {
+    contents: Cursor<Vec<u8>>,
+    dirty: bool,
+}
+
+impl OpenFile {
+    /// Writes the contents back to the store if they have changed since they were last written.
+    fn upload(&self) -> io::Result<()> {
+        let mut buffer = self.buffer.lock().recover();
+
+        if buffer.dirty {
+            self.fs.put(&self.key, buffer.contents.get_ref().clone())?;
+            buffer.dirty = false;
+        }
+
+        Ok(())
+    }
+}
+
+impl Read for OpenFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.options.is_read() {
+            return Err(io::Error::from(ErrorKind::PermissionDenied));
+        }
+
+        self.buffer.lock().recover().contents.read(buf)
+    }
+}
+
+impl Write for OpenFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.options.is_write() {
+            return Err(io::Error::from(ErrorKind::PermissionDenied));
+        }
+
+        let mut buffer = self.buffer.lock().recover();
+
+        if self.options.is_append() {
+            buffer.contents.seek(SeekFrom::End(0))?;
+        }
+
+        buffer.dirty = true;
+        buffer.contents.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.upload()
+    }
+}
+
+impl Seek for OpenFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.buffer.lock().recover().contents.seek(pos)
+    }
+}
+
+impl ::OpenFile for OpenFile {
+    fn set_len(&self, size: u64) -> io::Result<()> {
+        // Like `ftruncate`, this needs a handle opened for writing
+        if !self.options.is_write() {
+            return Err(io::Error::from(ErrorKind::InvalidInput));
+        }
+
+        let mut buffer = self.buffer.lock().recover();
+
+        buffer.contents.get_mut().resize(size as usize, 0);
+        buffer.dirty = true;
+
+        Ok(())
+    }
+
+    fn sync_all(&self) -> io::Result<()> {
+        self.upload()
+    }
+}
+
+#[cfg(feature = "lock")]
+impl LockableFile for OpenFile {
+    fn lock_shared(&self) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    fn lock_exclusive(&self) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    fn try_lock_shared(&self) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    fn try_lock_exclusive(&self) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    fn unlock(&self) -> io::Result<()> {
+        Err(unsupported())
+    }
+}
+
+impl Drop for OpenFile {
+    fn drop(&mut self) {
+        // Like closing a file, errors are ignored, so call `flush` or `sync_all` to see them
+        let _ = self.upload();
+    }
+}
+
+/// An entry of a directory in an [`ObjectStoreFileSystem`].
+///
+/// [`ObjectStoreFileSystem`]: struct.ObjectStoreFileSystem.html
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    path: PathBuf,
+    file_name: OsString,
+    metadata: Metadata,
+}
+
+impl ::DirEntry for DirEntry {
+    type Metadata = Metadata;
+
+    fn file_name(&self) -> OsString {
+        self.file_name.clone()
+    }
+
+    fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    fn metadata(&self) -> io::Result<Self::Metadata> {
+        Ok(self.metadata.clone())
+    }
+}
+
+/// The entries of a directory in an [`ObjectStoreFileSystem`], sorted by name.
+///
+/// [`ObjectStoreFileSystem`]: struct.ObjectStoreFileSystem.html
+#[derive(Debug)]
+pub struct ReadDir(IntoIter<io::Result<DirEntry>>);
+
+impl ReadDir {
+    fn new(entries: Vec<io::Result<DirEntry>>) -> Self {
+        ReadDir(entries.into_iter())
+    }
+}
+
+impl Iterator for ReadDir {
+    type Item = io::Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+impl ::ReadDir<DirEntry> for ReadDir {}
+
+struct ObjectWalkSource(ObjectStoreFileSystem);
+
+impl WalkSource for ObjectWalkSource {
+    fn list(&self, dir: &Path) -> io::Result<Vec<(PathBuf, FileType)>> {
+        let dir_path = self.0.normalize(dir);
+
+        if let Node::File(_) = self.0.node(&dir_path)? {
+            return Err(not_a_directory());
+        }
+
+        Ok(self
+            .0
+            .entries(&dir_path)?
+            .into_iter()
+            .map(|(file_name, metadata)| (dir.join(file_name), metadata.file_type))
+            .collect())
+    }
+
+    fn follow(&self, path: &Path) -> io::Result<(PathBuf, FileType)> {
+        let file_type = ::Metadata::file_type(&self.0.metadata(path)?);
+
+        Ok((self.0.canonicalize(path)?, file_type))
+    }
+}
+
+fn is_marker(key: &ObjectPath) -> bool {
+    key.filename() == Some(DIR_MARKER)
+}
+
+fn file_name(key: &ObjectPath) -> OsString {
+    OsString::from(key.filename().unwrap_or_default())
+}
+
+fn store_error(err: StoreError) -> io::Error {
+    let kind = match err {
+        StoreError::NotFound { .. } => ErrorKind::NotFound,
+        StoreError::AlreadyExists { .. } => ErrorKind::AlreadyExists,
+        StoreError::NotSupported { .. } | StoreError::NotImplemented => ErrorKind::Unsupported,
+        _ => ErrorKind::Other,
+    };
+
+    io::Error::new(kind, err)
+}
+
+fn not_found() -> io::Error {
+    io::Error::new(ErrorKind::NotFound, "no such file or directory")
+}
+
+fn already_exists() -> io::Error {
+    io::Error::new(ErrorKind::AlreadyExists, "file exists")
+}
+
+fn not_a_directory() -> io::Error {
+    io::Error::new(ErrorKind::NotADirectory, "not a directory")
+}
+
+fn directory_not_empty() -> io::Error {
+    io::Error::new(ErrorKind::DirectoryNotEmpty, "directory not empty")
+}
+
+fn is_a_directory() -> io::Error {
+    io::Error::new(ErrorKind::IsADirectory, "is a directory")
+}
+
+fn unsupported() -> io::Error {
+    io::Error::new(ErrorKind::Unsupported, "not supported by object stores")
+}
@@ -1,88 +1,422 @@
+use std::borrow::Cow;
 use std::env;
+#[cfg(unix)]
+use std::ffi::CString;
 use std::ffi::OsString;
-use std::fs::{self, File, OpenOptions, Permissions};
-use std::io::{Read, Result, Write};
+use std::fs::{self, DirBuilder, File, FileTimes, OpenOptions, Permissions};
+use std::io::{self, Error, ErrorKind, Read, Write};
+use std::mem;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
 #[cfg(unix)]
-use std::os::unix::fs::PermissionsExt;
+use std::os::unix::fs::{
+    chown, symlink, DirBuilderExt, FileTypeExt, MetadataExt, OpenOptionsExt, PermissionsExt,
+};
+#[cfg(windows)]
+use std::os::windows::ffi::OsStrExt;
+#[cfg(windows)]
+use std::os::windows::fs::{symlink_dir, symlink_file, MetadataExt};
 use std::path::{Path, PathBuf};
+#[cfg(windows)]
+use std::process::Command;
+#[cfg(windows)]
+use std::ptr;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
+#[cfg(feature = "lock")]
+use fs4::FileExt;
+#[cfg(unix)]
+use libc;
 #[cfg(feature = "temp")]
-use tempdir;
+use tempfile;
+#[cfg(windows)]
+use winapi::um::fileapi::{GetDiskFreeSpaceExW, SetFileAttributesW};
+#[cfg(windows)]
+use winapi::um::winnt::{
+    FILE_ATTRIBUTE_DIRECTORY, FILE_ATTRIBUTE_HIDDEN, FILE_ATTRIBUTE_REPARSE_POINT, ULARGE_INTEGER,
+};
 
+#[cfg(feature = "async")]
+use async_fs::{self, AsyncFileSystem, FsFuture};
+use chroot;
+use error::Context;
+#[cfg(not(windows))]
+use read_dir;
+use recover::Recover;
+#[cfg(feature = "temp")]
+use temp;
+use temp_sibling;
+use walk::WalkSource;
+#[cfg(feature = "lock")]
+use LockableFile;
 #[cfg(unix)]
 use UnixFileSystem;
-use {DirEntry, FileSystem, ReadDir};
+#[cfg(windows)]
+use WindowsFileSystem;
+use {
+    DirEntry, FileSystem, FileType, Metadata, OpenFile, ReadDir, Result, SymlinkFileSystem,
+    WalkDir, WalkDirOptions,
+};
 #[cfg(feature = "temp")]
-use {TempDir, TempFileSystem};
+use {TempDir, TempFile, TempFileSystem, TempOptions};
 
 /// Tracks a temporary directory that will be deleted once the struct goes out of scope.
 ///
 /// This is a wrapper around a [`TempDir`].
 ///
-/// [`TempDir`]: https://doc.rust-lang.org/tempdir/tempdir/struct.TempDir.html
+/// [`TempDir`]: https://docs.rs/tempfile/3/tempfile/struct.TempDir.html
 #[cfg(feature = "temp")]
 #[derive(Debug)]
-pub struct OsTempDir(tempdir::TempDir);
+pub struct OsTempDir(tempfile::TempDir);
 
 #[cfg(feature = "temp")]
 impl TempDir for OsTempDir {
     fn path(&self) -> &Path {
         self.0.path()
     }
+
+    fn into_path(self) -> PathBuf {
+        self.0.keep()
+    }
+}
+
+#[cfg(feature = "temp")]
+impl AsRef<Path> for OsTempDir {
+    fn as_ref(&self) -> &Path {
+        self.0.path()
+    }
+}
+
+/// Tracks a temporary file that will be deleted once the struct goes out of scope.
+///
+/// This is a wrapper around a [`NamedTempFile`].
+///
+/// [`NamedTempFile`]: https://docs.rs/tempfile/3/tempfile/struct.NamedTempFile.html
+#[cfg(feature = "temp")]
+#[derive(Debug)]
+pub struct OsTempFile(tempfile::NamedTempFile);
+
+#[cfg(feature = "temp")]
+impl TempFile for OsTempFile {
+    fn path(&self) -> &Path {
+        self.0.path()
+    }
 }
 
 /// An implementation of `FileSystem` that interacts with the actual operating system's file system.
 ///
 /// This is primarily a wrapper for [`fs`] methods.
+/// It has no state of its own, so it can be used from several threads at once, with the same
+/// guarantees as the OS gives for concurrent calls.
+///
+/// A file system created with [`with_own_current_dir`] instead resolves relative paths against a
+/// current directory of its own, rather than the process's, and one created with [`rooted`] is
+/// also confined to a base directory, for tests that need the real file system but should not
+/// reach outside of a scratch directory.
 ///
 /// [`fs`]: https://doc.rust-lang.org/std/fs/index.html
+/// [`with_own_current_dir`]: #method.with_own_current_dir
+/// [`rooted`]: #method.rooted
 #[derive(Clone, Debug, Default)]
-pub struct OsFileSystem {}
+pub struct OsFileSystem {
+    cwd: Option<Arc<CurrentDir>>,
+}
+
+/// The current directory of an `OsFileSystem` that keeps its own, shared between its clones.
+#[derive(Debug)]
+struct CurrentDir {
+    /// An absolute path, with symlinks resolved.
+    path: Mutex<PathBuf>,
+    /// The absolute path of the directory that paths are confined to, if the file system is
+    /// rooted.
+    base: Option<PathBuf>,
+    /// The temporary directory at `base`, which is deleted along with the last clone.
+    #[cfg(feature = "temp")]
+    _temp_dir: Option<OsTempDir>,
+}
 
 impl OsFileSystem {
     pub fn new() -> Self {
-        OsFileSystem {}
+        OsFileSystem { cwd: None }
+    }
+
+    /// Creates a file system that resolves relative paths against a current directory of its own,
+    /// starting at the process's current directory.
+    ///
+    /// [`set_current_dir`] only changes the current directory of the file system and its clones,
+    /// not the process's, which is shared by every thread, so tests running in parallel can each
+    /// change directory without changing where each other's paths lead.
+    /// This matches how a [`FakeFileSystem`] keeps its current directory.
+    ///
+    /// # Errors
+    ///
+    /// * The process's current directory does not exist or cannot be accessed.
+    ///
+    /// [`set_current_dir`]: trait.FileSystem.html#tymethod.set_current_dir
+    /// [`FakeFileSystem`]: struct.FakeFileSystem.html
+    pub fn with_own_current_dir() -> Result<Self> {
+        let path = env::current_dir().context("with_own_current_dir", &[])?;
+
+        Ok(OsFileSystem::with_current_dir(CurrentDir {
+            path: Mutex::new(path),
+            base: None,
+            #[cfg(feature = "temp")]
+            _temp_dir: None,
+        }))
+    }
+
+    /// Creates a file system confined to the directory `base`, such as a scratch directory for an
+    /// integration test.
+    ///
+    /// Relative paths are resolved against a current directory that starts at `base` and is kept
+    /// apart from the process's, as with [`with_own_current_dir`].
+    /// Absolute paths are used as is, and must be under `base`.
+    /// A relative `base` is resolved against the process's current directory once, here.
+    ///
+    /// Paths that would leave `base`, whether through `..` components, absolute paths elsewhere,
+    /// or symlinks to anywhere else, fail with `ErrorKind::PermissionDenied`.
+    /// Unlike a [`ChrootFileSystem`], paths keep their meaning on the host: [`current_dir`],
+    /// [`canonicalize`] and the entries of [`read_dir`] give real paths under `base`.
+    ///
+    /// Temporary files and directories are created under `base` too.
+    ///
+    /// Confinement is checked before each call, so it does not hold against other processes
+    /// changing the directory at the same time.
+    ///
+    /// [`with_own_current_dir`]: #method.with_own_current_dir
+    /// [`ChrootFileSystem`]: struct.ChrootFileSystem.html
+    /// [`current_dir`]: trait.FileSystem.html#tymethod.current_dir
+    /// [`canonicalize`]: trait.FileSystem.html#tymethod.canonicalize
+    /// [`read_dir`]: trait.FileSystem.html#tymethod.read_dir
+    pub fn rooted<P: AsRef<Path>>(base: P) -> Self {
+        let base = base.as_ref();
+        let base = match env::current_dir() {
+            Ok(cwd) => cwd.join(base),
+            Err(_) => base.to_path_buf(),
+        };
+
+        OsFileSystem::with_current_dir(CurrentDir {
+            path: Mutex::new(base.clone()),
+            base: Some(base),
+            #[cfg(feature = "temp")]
+            _temp_dir: None,
+        })
+    }
+
+    /// Creates a file system confined to a new temporary directory, as [`rooted`] does, with a
+    /// name starting with `prefix`.
+    /// The directory and everything in it is deleted once the file system and all of its clones
+    /// are dropped.
+    ///
+    /// [`rooted`]: #method.rooted
+    #[cfg(feature = "temp")]
+    pub fn rooted_in_temp_dir<S: AsRef<str>>(prefix: S) -> Result<Self> {
+        let temp_dir = OsFileSystem::new().temp_dir(prefix)?;
+        // The path of the temporary directory may lead through symlinks, such as on macOS
+        let base = fs::canonicalize(temp_dir.path()).context("rooted_in_temp_dir", &[])?;
+
+        Ok(OsFileSystem::with_current_dir(CurrentDir {
+            path: Mutex::new(base.clone()),
+            base: Some(base),
+            _temp_dir: Some(temp_dir),
+        }))
+    }
+
+    /// Returns the directory that the file system is confined to, if it was created with
+    /// [`rooted`] or [`rooted_in_temp_dir`].
+    ///
+    /// [`rooted`]: #method.rooted
+    /// [`rooted_in_temp_dir`]: #method.rooted_in_temp_dir
+    pub fn base(&self) -> Option<&Path> {
+        self.cwd.as_ref().and_then(|cwd| cwd.base.as_deref())
+    }
+
+    fn with_current_dir(cwd: CurrentDir) -> Self {
+        OsFileSystem {
+            cwd: Some(Arc::new(cwd)),
+        }
+    }
+
+    /// Returns the path on the host that `path` refers to, after checking that it stays under the
+    /// base directory if the file system is rooted.
+    /// Relative paths are joined to the file system's own current directory, if it has one, but
+    /// otherwise the path is kept as is, so that the OS interprets `..` components and symlinks
+    /// along it as usual.
+    /// If `follow` is false, the last component of `path` may be a symlink to anywhere.
+    fn host<'a>(&self, path: &'a Path, follow: bool) -> io::Result<Cow<'a, Path>> {
+        let cwd = match self.cwd {
+            Some(ref cwd) => cwd,
+            None => return Ok(Cow::Borrowed(path)),
+        };
+
+        if cwd.base.is_some() {
+            cwd.confine(path, follow)?;
+        }
+
+        if path.is_absolute() {
+            Ok(Cow::Borrowed(path))
+        } else {
+            Ok(Cow::Owned(cwd.get().join(path)))
+        }
+    }
+}
+
+impl CurrentDir {
+    fn get(&self) -> PathBuf {
+        self.path.lock().recover().clone()
+    }
+
+    fn set(&self, path: &Path) -> io::Result<()> {
+        let path = match self.base {
+            Some(_) => self.confine(path, true)?,
+            None => fs::canonicalize(self.get().join(path))?,
+        };
+
+        if !fs::metadata(&path)?.is_dir() {
+            return Err(Error::new(ErrorKind::NotADirectory, "not a directory"));
+        }
+
+        *self.path.lock().recover() = path;
+
+        Ok(())
+    }
+
+    /// Returns the path on the host that `path` refers to, with symlinks resolved, or fails with
+    /// `ErrorKind::PermissionDenied` if it is not under the base directory.
+    /// If `follow` is false and the last component of `path` is a symlink, it is left unresolved.
+    fn confine(&self, path: &Path, follow: bool) -> io::Result<PathBuf> {
+        let base = self.base.as_ref().expect("file system is rooted");
+        let path = chroot::guest_path(base, self.get().join(path))?;
+
+        chroot::resolve(
+            &OsFileSystem::new(),
+            base,
+            PathBuf::from("/"),
+            &path,
+            follow,
+        )
+        .map(|path| chroot::host_path(base, &path))
+        .map_err(Into::into)
     }
 }
 
 impl FileSystem for OsFileSystem {
     type DirEntry = fs::DirEntry;
     type ReadDir = fs::ReadDir;
+    type Metadata = fs::Metadata;
+    type OpenFile = File;
+    type WalkDir = WalkDir;
 
     fn current_dir(&self) -> Result<PathBuf> {
-        env::current_dir()
+        match self.cwd {
+            Some(ref cwd) => Ok(cwd.get()),
+            None => env::current_dir().context("current_dir", &[]),
+        }
     }
 
     fn set_current_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        env::set_current_dir(path)
+        let path = path.as_ref();
+
+        match self.cwd {
+            Some(ref cwd) => cwd.set(path),
+            None => env::set_current_dir(path),
+        }
+        .context("set_current_dir", &[path])
+    }
+
+    fn metadata<P: AsRef<Path>>(&self, path: P) -> Result<Self::Metadata> {
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(fs::metadata)
+            .context("metadata", &[path])
+    }
+
+    fn canonicalize<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf> {
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(fs::canonicalize)
+            .context("canonicalize", &[path])
     }
 
     fn is_dir<P: AsRef<Path>>(&self, path: P) -> bool {
-        path.as_ref().is_dir()
+        self.host(path.as_ref(), true)
+            .map(|host| host.is_dir())
+            .unwrap_or(false)
     }
 
     fn is_file<P: AsRef<Path>>(&self, path: P) -> bool {
-        path.as_ref().is_file()
+        self.host(path.as_ref(), true)
+            .map(|host| host.is_file())
+            .unwrap_or(false)
+    }
+
+    fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.host(path.as_ref(), true)
+            .map(|host| host.exists())
+            .unwrap_or(false)
+    }
+
+    fn try_exists<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(|host| host.try_exists())
+            .context("try_exists", &[path])
     }
 
     fn create_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        fs::create_dir(path)
+        let path = path.as_ref();
+
+        self.host(path, false)
+            .and_then(fs::create_dir)
+            .context("create_dir", &[path])
     }
 
     fn create_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        fs::create_dir_all(path)
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(fs::create_dir_all)
+            .context("create_dir_all", &[path])
     }
 
     fn remove_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        fs::remove_dir(path)
+        let path = path.as_ref();
+
+        self.host(path, false)
+            .and_then(fs::remove_dir)
+            .context("remove_dir", &[path])
     }
 
     fn remove_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        fs::remove_dir_all(path)
+        let path = path.as_ref();
+
+        self.host(path, false)
+            .and_then(fs::remove_dir_all)
+            .context("remove_dir_all", &[path])
     }
 
     fn read_dir<P: AsRef<Path>>(&self, path: P) -> Result<Self::ReadDir> {
-        fs::read_dir(path)
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(fs::read_dir)
+            .context("read_dir", &[path])
+    }
+
+    fn walk_dir_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &WalkDirOptions,
+    ) -> Result<Self::WalkDir> {
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(|host| WalkDir::new(OsWalkSource(self.cwd.clone()), &host, options))
+            .context("walk_dir", &[path])
     }
 
     fn write_file<P, B>(&self, path: P, buf: B) -> Result<()>
@@ -90,8 +424,12 @@ impl FileSystem for OsFileSystem {
         P: AsRef<Path>,
         B: AsRef<[u8]>,
     {
-        let mut file = File::create(path)?;
-        file.write_all(buf.as_ref())
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(File::create)
+            .and_then(|mut file| file.write_all(buf.as_ref()))
+            .context("write_file", &[path])
     }
 
     fn overwrite_file<P, B>(&self, path: P, buf: B) -> Result<()>
@@ -99,17 +437,70 @@ impl FileSystem for OsFileSystem {
         P: AsRef<Path>,
         B: AsRef<[u8]>,
     {
-        let mut file = OpenOptions::new().write(true).truncate(true).open(path)?;
-        file.write_all(buf.as_ref())
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(|host| OpenOptions::new().write(true).truncate(true).open(host))
+            .and_then(|mut file| file.write_all(buf.as_ref()))
+            .context("overwrite_file", &[path])
     }
 
-    fn read_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
-        let mut contents = Vec::<u8>::new();
-        let mut file = File::open(path)?;
+    fn set_len<P: AsRef<Path>>(&self, path: P, size: u64) -> Result<()> {
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(|host| OpenOptions::new().write(true).open(host))
+            .and_then(|file| file.set_len(size))
+            .context("set_len", &[path])
+    }
+
+    fn sync_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        // Windows only flushes files that are open for writing
+        self.host(path, true)
+            .and_then(|host| {
+                OpenOptions::new()
+                    .read(true)
+                    .write(cfg!(windows))
+                    .open(host)
+            })
+            .and_then(|file| file.sync_all())
+            .context("sync_file", &[path])
+    }
+
+    fn sync_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        let host = self.host(path, true).context("sync_dir", &[path])?;
+
+        if cfg!(windows) {
+            return fs::metadata(host).map(|_| ()).context("sync_dir", &[path]);
+        }
+
+        File::open(host)
+            .and_then(|file| file.sync_all())
+            .context("sync_dir", &[path])
+    }
+
+    fn write_file_atomic<P, B>(&self, path: P, buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
 
-        file.read_to_end(&mut contents)?;
+        self.host(path, true)
+            .and_then(|host| write_file_atomic(&host, buf.as_ref()))
+            .context("write_file_atomic", &[path])
+    }
+
+    fn read_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
+        let path = path.as_ref();
 
-        Ok(contents)
+        self.host(path, true)
+            .and_then(fs::read)
+            .context("read_file", &[path])
     }
 
     fn read_file_into<P, B>(&self, path: P, mut buf: B) -> Result<usize>
@@ -117,17 +508,20 @@ impl FileSystem for OsFileSystem {
         P: AsRef<Path>,
         B: AsMut<Vec<u8>>,
     {
-        let mut file = File::open(path)?;
-        file.read_to_end(buf.as_mut())
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(File::open)
+            .and_then(|mut file| file.read_to_end(buf.as_mut()))
+            .context("read_file_into", &[path])
     }
 
     fn read_file_to_string<P: AsRef<Path>>(&self, path: P) -> Result<String> {
-        let mut contents = String::new();
-        let mut file = File::open(path)?;
-
-        file.read_to_string(&mut contents)?;
+        let path = path.as_ref();
 
-        Ok(contents)
+        self.host(path, true)
+            .and_then(fs::read_to_string)
+            .context("read_file_to_string", &[path])
     }
 
     fn create_file<P, B>(&self, path: P, buf: B) -> Result<()>
@@ -135,13 +529,49 @@ impl FileSystem for OsFileSystem {
         P: AsRef<Path>,
         B: AsRef<[u8]>,
     {
-        let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+        let path = path.as_ref();
 
-        file.write_all(buf.as_ref())
+        self.host(path, false)
+            .and_then(|host| OpenOptions::new().write(true).create_new(true).open(host))
+            .and_then(|mut file| file.write_all(buf.as_ref()))
+            .context("create_file", &[path])
+    }
+
+    fn open<P: AsRef<Path>>(&self, path: P) -> Result<Self::OpenFile> {
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(File::open)
+            .context("open", &[path])
+    }
+
+    fn open_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &::OpenOptions,
+    ) -> Result<Self::OpenFile> {
+        let path = path.as_ref();
+
+        self.host(path, !options.is_create_new())
+            .and_then(|host| {
+                OpenOptions::new()
+                    .read(options.is_read())
+                    .write(options.is_write())
+                    .append(options.is_append())
+                    .truncate(options.is_truncate())
+                    .create(options.is_create())
+                    .create_new(options.is_create_new())
+                    .open(host)
+            })
+            .context("open_with_options", &[path])
     }
 
     fn remove_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        fs::remove_file(path)
+        let path = path.as_ref();
+
+        self.host(path, false)
+            .and_then(fs::remove_file)
+            .context("remove_file", &[path])
     }
 
     fn copy_file<P, Q>(&self, from: P, to: Q) -> Result<()>
@@ -149,7 +579,24 @@ impl FileSystem for OsFileSystem {
         P: AsRef<Path>,
         Q: AsRef<Path>,
     {
-        fs::copy(from, to).and(Ok(()))
+        let (from, to) = (from.as_ref(), to.as_ref());
+
+        self.host(from, true)
+            .and_then(|host| fs::copy(host, self.host(to, true)?))
+            .and(Ok(()))
+            .context("copy_file", &[from, to])
+    }
+
+    fn copy_dir<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref(), to.as_ref());
+
+        self.host(from, true)
+            .and_then(|host| copy_dir_checked(&host, &self.host(to, false)?))
+            .context("copy_dir", &[from, to])
     }
 
     fn rename<P, Q>(&self, from: P, to: Q) -> Result<()>
@@ -157,27 +604,368 @@ impl FileSystem for OsFileSystem {
         P: AsRef<Path>,
         Q: AsRef<Path>,
     {
-        fs::rename(from, to)
+        let (from, to) = (from.as_ref(), to.as_ref());
+
+        self.host(from, false)
+            .and_then(|host| fs::rename(host, self.host(to, false)?))
+            .context("rename", &[from, to])
     }
 
     fn readonly<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
-        permissions(path.as_ref()).map(|p| p.readonly())
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(|host| permissions(&host))
+            .map(|p| p.readonly())
+            .context("readonly", &[path])
     }
 
     fn set_readonly<P: AsRef<Path>>(&self, path: P, readonly: bool) -> Result<()> {
-        let mut permissions = permissions(path.as_ref())?;
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(|host| {
+                let mut permissions = permissions(&host)?;
 
-        permissions.set_readonly(readonly);
+                permissions.set_readonly(readonly);
+                fs::set_permissions(host, permissions)
+            })
+            .context("set_readonly", &[path])
+    }
+
+    #[cfg(not(windows))]
+    fn is_hidden<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let path = path.as_ref();
 
-        fs::set_permissions(path, permissions)
+        self.host(path, false)
+            .and_then(fs::symlink_metadata)
+            .map(|_| path.file_name().is_some_and(read_dir::is_hidden_name))
+            .context("is_hidden", &[path])
+    }
+
+    #[cfg(windows)]
+    fn is_hidden<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+
+        self.host(path, false)
+            .and_then(fs::symlink_metadata)
+            .map(|m| m.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+            .context("is_hidden", &[path])
+    }
+
+    fn modified<P: AsRef<Path>>(&self, path: P) -> Result<SystemTime> {
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(fs::metadata)
+            .and_then(|metadata| metadata.modified())
+            .context("modified", &[path])
+    }
+
+    fn set_modified<P: AsRef<Path>>(&self, path: P, time: SystemTime) -> Result<()> {
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(File::open)
+            .and_then(|file| file.set_modified(time))
+            .context("set_modified", &[path])
+    }
+
+    fn accessed<P: AsRef<Path>>(&self, path: P) -> Result<SystemTime> {
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(fs::metadata)
+            .and_then(|metadata| metadata.accessed())
+            .context("accessed", &[path])
+    }
+
+    fn set_accessed<P: AsRef<Path>>(&self, path: P, time: SystemTime) -> Result<()> {
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(File::open)
+            .and_then(|file| file.set_times(FileTimes::new().set_accessed(time)))
+            .context("set_accessed", &[path])
     }
 
     fn len<P: AsRef<Path>>(&self, path: P) -> u64 {
-        fs::metadata(path.as_ref()).map(|md| md.len()).unwrap_or(0)
+        self.host(path.as_ref(), true)
+            .and_then(fs::metadata)
+            .map(|md| md.len())
+            .unwrap_or(0)
+    }
+
+    fn available_space<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(|host| disk_space(&host))
+            .map(|(available, _)| available)
+            .context("available_space", &[path])
+    }
+
+    fn total_space<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(|host| disk_space(&host))
+            .map(|(_, total)| total)
+            .context("total_space", &[path])
+    }
+}
+
+/// Writes `buf` to a temporary file next to `path`, flushes it to disk, and renames it over
+/// `path`.
+pub fn write_file_atomic(path: &Path, buf: &[u8]) -> io::Result<()> {
+    let temp = temp_sibling(path)?;
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temp)?;
+
+    // The contents are flushed to disk before the rename, so that a crash cannot leave
+    // `path` pointing to an empty or partial file
+    let result = file
+        .write_all(buf)
+        .and_then(|_| file.sync_all())
+        .and_then(|_| {
+            drop(file);
+            fs::rename(&temp, path)
+        });
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+
+    result
+}
+
+/// Copies the directory `from` to `to`, unless `to` would be inside of `from`.
+fn copy_dir_checked(from: &Path, to: &Path) -> io::Result<()> {
+    let to_parent = match to.parent() {
+        Some(p) if p != Path::new("") => p,
+        _ => Path::new("."),
+    };
+
+    if fs::canonicalize(to_parent)?.starts_with(fs::canonicalize(from)?) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "cannot copy a directory into itself",
+        ));
+    }
+
+    copy_dir(from, to)
+}
+
+#[cfg(feature = "async")]
+impl AsyncFileSystem for OsFileSystem {
+    fn current_dir(&self) -> FsFuture<Result<PathBuf>> {
+        let fs = self.clone();
+
+        async_fs::blocking(move || FileSystem::current_dir(&fs))
+    }
+
+    fn set_current_dir<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<()>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::blocking(move || FileSystem::set_current_dir(&fs, path))
+    }
+
+    fn metadata<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<Self::Metadata>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::blocking(move || FileSystem::metadata(&fs, path))
+    }
+
+    fn canonicalize<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<PathBuf>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::blocking(move || FileSystem::canonicalize(&fs, path))
+    }
+
+    fn is_dir<P: AsRef<Path>>(&self, path: P) -> FsFuture<bool> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::blocking(move || FileSystem::is_dir(&fs, path))
+    }
+
+    fn is_file<P: AsRef<Path>>(&self, path: P) -> FsFuture<bool> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::blocking(move || FileSystem::is_file(&fs, path))
+    }
+
+    fn exists<P: AsRef<Path>>(&self, path: P) -> FsFuture<bool> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::blocking(move || FileSystem::exists(&fs, path))
+    }
+
+    fn try_exists<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<bool>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::blocking(move || FileSystem::try_exists(&fs, path))
+    }
+
+    fn create_dir<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<()>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::blocking(move || FileSystem::create_dir(&fs, path))
+    }
+
+    fn create_dir_all<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<()>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::blocking(move || FileSystem::create_dir_all(&fs, path))
+    }
+
+    fn remove_dir<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<()>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::blocking(move || FileSystem::remove_dir(&fs, path))
+    }
+
+    fn remove_dir_all<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<()>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::blocking(move || FileSystem::remove_dir_all(&fs, path))
+    }
+
+    fn read_dir<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<Vec<Self::DirEntry>>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::blocking(move || {
+            FileSystem::read_dir(&fs, &path)?
+                .collect::<io::Result<_>>()
+                .context("read_dir", &[&path])
+        })
+    }
+
+    fn create_file<P, B>(&self, path: P, buf: B) -> FsFuture<Result<()>>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let (fs, path, buf) = (
+            self.clone(),
+            path.as_ref().to_path_buf(),
+            buf.as_ref().to_vec(),
+        );
+
+        async_fs::blocking(move || FileSystem::create_file(&fs, path, buf))
+    }
+
+    fn write_file<P, B>(&self, path: P, buf: B) -> FsFuture<Result<()>>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let (fs, path, buf) = (
+            self.clone(),
+            path.as_ref().to_path_buf(),
+            buf.as_ref().to_vec(),
+        );
+
+        async_fs::blocking(move || FileSystem::write_file(&fs, path, buf))
+    }
+
+    fn overwrite_file<P, B>(&self, path: P, buf: B) -> FsFuture<Result<()>>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let (fs, path, buf) = (
+            self.clone(),
+            path.as_ref().to_path_buf(),
+            buf.as_ref().to_vec(),
+        );
+
+        async_fs::blocking(move || FileSystem::overwrite_file(&fs, path, buf))
+    }
+
+    fn read_file<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<Vec<u8>>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::blocking(move || FileSystem::read_file(&fs, path))
+    }
+
+    fn read_file_to_string<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<String>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::blocking(move || FileSystem::read_file_to_string(&fs, path))
+    }
+
+    fn remove_file<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<()>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::blocking(move || FileSystem::remove_file(&fs, path))
+    }
+
+    fn copy_file<P, Q>(&self, from: P, to: Q) -> FsFuture<Result<()>>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (fs, from, to) = (
+            self.clone(),
+            from.as_ref().to_path_buf(),
+            to.as_ref().to_path_buf(),
+        );
+
+        async_fs::blocking(move || FileSystem::copy_file(&fs, from, to))
+    }
+
+    fn copy_dir<P, Q>(&self, from: P, to: Q) -> FsFuture<Result<()>>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (fs, from, to) = (
+            self.clone(),
+            from.as_ref().to_path_buf(),
+            to.as_ref().to_path_buf(),
+        );
+
+        async_fs::blocking(move || FileSystem::copy_dir(&fs, from, to))
+    }
+
+    fn rename<P, Q>(&self, from: P, to: Q) -> FsFuture<Result<()>>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (fs, from, to) = (
+            self.clone(),
+            from.as_ref().to_path_buf(),
+            to.as_ref().to_path_buf(),
+        );
+
+        async_fs::blocking(move || FileSystem::rename(&fs, from, to))
+    }
+
+    fn readonly<P: AsRef<Path>>(&self, path: P) -> FsFuture<Result<bool>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::blocking(move || FileSystem::readonly(&fs, path))
+    }
+
+    fn set_readonly<P: AsRef<Path>>(&self, path: P, readonly: bool) -> FsFuture<Result<()>> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::blocking(move || FileSystem::set_readonly(&fs, path, readonly))
+    }
+
+    fn len<P: AsRef<Path>>(&self, path: P) -> FsFuture<u64> {
+        let (fs, path) = (self.clone(), path.as_ref().to_path_buf());
+
+        async_fs::blocking(move || FileSystem::len(&fs, path))
     }
 }
 
 impl DirEntry for fs::DirEntry {
+    type Metadata = fs::Metadata;
+
     fn file_name(&self) -> OsString {
         self.file_name()
     }
@@ -185,35 +973,536 @@ impl DirEntry for fs::DirEntry {
     fn path(&self) -> PathBuf {
         self.path()
     }
+
+    fn file_type(&self) -> io::Result<FileType> {
+        fs::DirEntry::file_type(self).map(FileType::from)
+    }
+
+    fn metadata(&self) -> io::Result<Self::Metadata> {
+        fs::DirEntry::metadata(self)
+    }
 }
 
 impl ReadDir<fs::DirEntry> for fs::ReadDir {}
 
+impl OpenFile for File {
+    fn set_len(&self, size: u64) -> io::Result<()> {
+        File::set_len(self, size)
+    }
+
+    fn sync_all(&self) -> io::Result<()> {
+        File::sync_all(self)
+    }
+}
+
+#[cfg(feature = "lock")]
+impl LockableFile for File {
+    fn lock_shared(&self) -> io::Result<()> {
+        FileExt::lock_shared(self)
+    }
+
+    fn lock_exclusive(&self) -> io::Result<()> {
+        FileExt::lock(self)
+    }
+
+    fn try_lock_shared(&self) -> io::Result<()> {
+        FileExt::try_lock_shared(self).map_err(Error::from)
+    }
+
+    fn try_lock_exclusive(&self) -> io::Result<()> {
+        FileExt::try_lock(self).map_err(Error::from)
+    }
+
+    fn unlock(&self) -> io::Result<()> {
+        FileExt::unlock(self)
+    }
+}
+
+/// Walks the OS's file system, following symlinks only within the base directory of a rooted
+/// `OsFileSystem`, if given one.
+#[derive(Debug)]
+struct OsWalkSource(Option<Arc<CurrentDir>>);
+
+impl WalkSource for OsWalkSource {
+    fn list(&self, dir: &Path) -> io::Result<Vec<(PathBuf, FileType)>> {
+        fs::read_dir(dir)?
+            .map(|entry| {
+                let entry = entry?;
+
+                Ok((entry.path(), entry.file_type()?.into()))
+            })
+            .collect()
+    }
+
+    fn follow(&self, path: &Path) -> io::Result<(PathBuf, FileType)> {
+        if let Some(ref cwd) = self.0 {
+            if cwd.base.is_some() {
+                cwd.confine(path, true)?;
+            }
+        }
+
+        let file_type = fs::metadata(path)?.file_type().into();
+
+        Ok((fs::canonicalize(path)?, file_type))
+    }
+}
+
+impl From<fs::FileType> for FileType {
+    fn from(file_type: fs::FileType) -> Self {
+        if file_type.is_symlink() {
+            FileType::Symlink
+        } else if file_type.is_dir() {
+            FileType::Dir
+        } else {
+            special_file_type(file_type).unwrap_or(FileType::File)
+        }
+    }
+}
+
+#[cfg(unix)]
+fn special_file_type(file_type: fs::FileType) -> Option<FileType> {
+    if file_type.is_fifo() {
+        Some(FileType::Fifo)
+    } else if file_type.is_socket() {
+        Some(FileType::Socket)
+    } else if file_type.is_block_device() {
+        Some(FileType::BlockDevice)
+    } else if file_type.is_char_device() {
+        Some(FileType::CharDevice)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn special_file_type(_: fs::FileType) -> Option<FileType> {
+    None
+}
+
+impl Metadata for fs::Metadata {
+    fn file_type(&self) -> FileType {
+        self.file_type().into()
+    }
+
+    fn len(&self) -> u64 {
+        self.len()
+    }
+
+    fn readonly(&self) -> bool {
+        self.permissions().readonly()
+    }
+
+    fn created(&self) -> io::Result<SystemTime> {
+        self.created()
+    }
+
+    fn modified(&self) -> io::Result<SystemTime> {
+        self.modified()
+    }
+
+    fn accessed(&self) -> io::Result<SystemTime> {
+        self.accessed()
+    }
+}
+
+impl SymlinkFileSystem for OsFileSystem {
+    #[cfg(unix)]
+    fn symlink_file<P, Q>(&self, original: P, link: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (original, link) = (original.as_ref(), link.as_ref());
+
+        self.host(link, false)
+            .and_then(|host| symlink(original, host))
+            .context("symlink_file", &[original, link])
+    }
+
+    #[cfg(windows)]
+    fn symlink_file<P, Q>(&self, original: P, link: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (original, link) = (original.as_ref(), link.as_ref());
+
+        self.host(link, false)
+            .and_then(|host| symlink_file(original, host))
+            .context("symlink_file", &[original, link])
+    }
+
+    #[cfg(unix)]
+    fn symlink_dir<P, Q>(&self, original: P, link: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (original, link) = (original.as_ref(), link.as_ref());
+
+        self.host(link, false)
+            .and_then(|host| symlink(original, host))
+            .context("symlink_dir", &[original, link])
+    }
+
+    #[cfg(windows)]
+    fn symlink_dir<P, Q>(&self, original: P, link: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (original, link) = (original.as_ref(), link.as_ref());
+
+        self.host(link, false)
+            .and_then(|host| symlink_dir(original, host))
+            .context("symlink_dir", &[original, link])
+    }
+
+    fn read_link<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf> {
+        let path = path.as_ref();
+
+        self.host(path, false)
+            .and_then(fs::read_link)
+            .context("read_link", &[path])
+    }
+
+    fn symlink_metadata<P: AsRef<Path>>(&self, path: P) -> Result<Self::Metadata> {
+        let path = path.as_ref();
+
+        self.host(path, false)
+            .and_then(fs::symlink_metadata)
+            .context("symlink_metadata", &[path])
+    }
+}
+
 #[cfg(unix)]
 impl UnixFileSystem for OsFileSystem {
     fn mode<P: AsRef<Path>>(&self, path: P) -> Result<u32> {
-        permissions(path.as_ref()).map(|p| p.mode())
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(|host| permissions(&host))
+            .map(|p| p.mode())
+            .context("mode", &[path])
     }
 
     fn set_mode<P: AsRef<Path>>(&self, path: P, mode: u32) -> Result<()> {
-        let mut permissions = permissions(path.as_ref())?;
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(|host| {
+                let mut permissions = permissions(&host)?;
+
+                permissions.set_mode(mode);
+                fs::set_permissions(host, permissions)
+            })
+            .context("set_mode", &[path])
+    }
+
+    fn owner<P: AsRef<Path>>(&self, path: P) -> Result<(u32, u32)> {
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(fs::metadata)
+            .map(|m| (m.uid(), m.gid()))
+            .context("owner", &[path])
+    }
+
+    fn set_owner<P: AsRef<Path>>(&self, path: P, uid: u32, gid: u32) -> Result<()> {
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(|host| chown(host, Some(uid), Some(gid)))
+            .context("set_owner", &[path])
+    }
+
+    fn create_file_with_mode<P, B>(&self, path: P, buf: B, mode: u32) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+
+        self.host(path, false)
+            .and_then(|host| {
+                OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .mode(mode)
+                    .open(host)
+            })
+            .and_then(|mut file| file.write_all(buf.as_ref()))
+            .context("create_file_with_mode", &[path])
+    }
+
+    fn create_dir_with_mode<P: AsRef<Path>>(&self, path: P, mode: u32) -> Result<()> {
+        let path = path.as_ref();
+
+        self.host(path, false)
+            .and_then(|host| DirBuilder::new().mode(mode).create(host))
+            .context("create_dir_with_mode", &[path])
+    }
+
+    fn mknod<P: AsRef<Path>>(
+        &self,
+        path: P,
+        file_type: FileType,
+        mode: u32,
+        device: u64,
+    ) -> Result<()> {
+        let path = path.as_ref();
+
+        self.host(path, false)
+            .and_then(|host| mknod(&host, file_type, mode, device))
+            .context("mknod", &[path])
+    }
+}
+
+#[cfg(windows)]
+impl WindowsFileSystem for OsFileSystem {
+    fn attributes<P: AsRef<Path>>(&self, path: P) -> Result<u32> {
+        let path = path.as_ref();
+
+        self.host(path, true)
+            .and_then(fs::metadata)
+            .map(|m| m.file_attributes())
+            .context("attributes", &[path])
+    }
+
+    fn set_attributes<P: AsRef<Path>>(&self, path: P, attributes: u32) -> Result<()> {
+        let path = path.as_ref();
+        let host = self.host(path, true).context("set_attributes", &[path])?;
+        let wide: Vec<u16> = host.as_os_str().encode_wide().chain(Some(0)).collect();
+        let attributes = attributes & !(FILE_ATTRIBUTE_DIRECTORY | FILE_ATTRIBUTE_REPARSE_POINT);
+
+        if unsafe { SetFileAttributesW(wide.as_ptr(), attributes) } == 0 {
+            return Err(Error::last_os_error()).context("set_attributes", &[path]);
+        }
+
+        Ok(())
+    }
+
+    fn create_junction<P, Q>(&self, original: P, junction: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (original, junction) = (original.as_ref(), junction.as_ref());
+
+        self.host(junction, false)
+            .and_then(|host| create_junction(original, &host))
+            .context("create_junction", &[original, junction])
+    }
+}
+
+#[cfg(windows)]
+fn create_junction(original: &Path, junction: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(junction).is_ok() {
+        return Err(Error::new(
+            ErrorKind::AlreadyExists,
+            "junction already exists",
+        ));
+    }
 
-        permissions.set_mode(mode);
+    // std has no API for junctions, so defer to the shell's built-in `mklink`
+    let output = Command::new("cmd")
+        .arg("/C")
+        .arg("mklink")
+        .arg("/J")
+        .arg(junction)
+        .arg(original)
+        .output()?;
 
-        fs::set_permissions(path, permissions)
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stdout);
+
+        return Err(Error::other(message.trim().to_string()));
     }
+
+    Ok(())
 }
 
 #[cfg(feature = "temp")]
 impl TempFileSystem for OsFileSystem {
     type TempDir = OsTempDir;
+    type TempFile = OsTempFile;
 
     fn temp_dir<S: AsRef<str>>(&self, prefix: S) -> Result<Self::TempDir> {
-        tempdir::TempDir::new(prefix.as_ref()).map(OsTempDir)
+        let mut builder = tempfile::Builder::new();
+
+        builder.prefix(prefix.as_ref());
+
+        // Rooted file systems keep their temporary files under the base directory
+        match self.base() {
+            Some(base) => builder.tempdir_in(base),
+            None => builder.tempdir(),
+        }
+        .map(OsTempDir)
+        .context("temp_dir", &[])
+    }
+
+    fn temp_dir_with_options<P: AsRef<Path>>(
+        &self,
+        base: P,
+        options: &TempOptions,
+    ) -> Result<Self::TempDir> {
+        let base = base.as_ref();
+
+        self.host(base, true)
+            .and_then(|host| temp::builder(options).tempdir_in(host))
+            .map(OsTempDir)
+            .context("temp_dir_with_options", &[base])
+    }
+
+    fn temp_file<S: AsRef<str>>(&self, prefix: S) -> Result<Self::TempFile> {
+        let mut builder = tempfile::Builder::new();
+
+        builder.prefix(prefix.as_ref());
+
+        // Rooted file systems keep their temporary files under the base directory
+        match self.base() {
+            Some(base) => builder.tempfile_in(base),
+            None => builder.tempfile(),
+        }
+        .map(OsTempFile)
+        .context("temp_file", &[])
+    }
+
+    fn temp_file_with_options<P: AsRef<Path>>(
+        &self,
+        base: P,
+        options: &TempOptions,
+    ) -> Result<Self::TempFile> {
+        let base = base.as_ref();
+
+        self.host(base, true)
+            .and_then(|host| temp::builder(options).tempfile_in(host))
+            .map(OsTempFile)
+            .context("temp_file_with_options", &[base])
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    let permissions = permissions(from)?;
+
+    fs::create_dir(to)?;
+
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let target = to.join(entry.file_name());
+
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else if file_type.is_symlink() {
+            copy_symlink(&entry.path(), &target)?;
+        } else if let Some(file_type) = special_file_type(file_type) {
+            copy_special(&entry.path(), &target, file_type)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+
+    fs::set_permissions(to, permissions)
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    symlink(fs::read_link(from)?, to)
+}
+
+/// Creates a node like the FIFO, socket, or device at `from`, since reading it as a file
+/// would block or fail.
+#[cfg(unix)]
+fn copy_special(from: &Path, to: &Path, file_type: FileType) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(from)?;
+
+    mknod(to, file_type, metadata.mode(), metadata.rdev())
+}
+
+#[cfg(not(unix))]
+fn copy_special(_: &Path, _: &Path, _: FileType) -> io::Result<()> {
+    Err(Error::new(
+        ErrorKind::Unsupported,
+        "special files are not supported",
+    ))
+}
+
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)]
+fn mknod(path: &Path, file_type: FileType, mode: u32, device: u64) -> io::Result<()> {
+    let kind = match file_type {
+        FileType::Fifo => libc::S_IFIFO,
+        FileType::Socket => libc::S_IFSOCK,
+        FileType::BlockDevice => libc::S_IFBLK,
+        FileType::CharDevice => libc::S_IFCHR,
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "not a FIFO, socket, or device",
+            ))
+        }
+    };
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mode = kind | (mode & 0o7777) as libc::mode_t;
+
+    if unsafe { libc::mknod(path.as_ptr(), mode, device as libc::dev_t) } != 0 {
+        return Err(Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Returns the space available to the current user and the total size of the file system that
+/// `path` is on, in bytes.
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)]
+fn disk_space(path: &Path) -> io::Result<(u64, u64)> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { mem::zeroed() };
+
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(Error::last_os_error());
+    }
+
+    let block_size = stat.f_frsize as u64;
+
+    Ok((
+        stat.f_bavail as u64 * block_size,
+        stat.f_blocks as u64 * block_size,
+    ))
+}
+
+#[cfg(windows)]
+fn disk_space(path: &Path) -> io::Result<(u64, u64)> {
+    // Like `statvfs`, fail for paths that don't exist rather than reporting their volume
+    fs::metadata(path)?;
+
+    let path: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut available: ULARGE_INTEGER = unsafe { mem::zeroed() };
+    let mut total: ULARGE_INTEGER = unsafe { mem::zeroed() };
+
+    if unsafe { GetDiskFreeSpaceExW(path.as_ptr(), &mut available, &mut total, ptr::null_mut()) }
+        == 0
+    {
+        return Err(Error::last_os_error());
+    }
+
+    Ok(unsafe { (*available.QuadPart(), *total.QuadPart()) })
+}
+
+#[cfg(windows)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    let original = fs::read_link(from)?;
+
+    if fs::metadata(from).map(|m| m.is_dir()).unwrap_or(false) {
+        symlink_dir(original, to)
+    } else {
+        symlink_file(original, to)
     }
 }
 
-fn permissions(path: &Path) -> Result<Permissions> {
+fn permissions(path: &Path) -> io::Result<Permissions> {
     let metadata = fs::metadata(path)?;
 
     Ok(metadata.permissions())
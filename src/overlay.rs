@@ -0,0 +1,1304 @@
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsString;
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use std::vec::IntoIter;
+
+use error::Context;
+use normalize::normalize;
+use recover::Recover;
+use walk::WalkSource;
+#[cfg(feature = "lock")]
+use LockableFile;
+#[cfg(unix)]
+use UnixFileSystem;
+#[cfg(feature = "hash")]
+use {Algorithm, Digest};
+use {
+    Error, FileSystem, FileType, OpenOptions, Result, SymlinkFileSystem, WalkDir, WalkDirOptions,
+};
+
+/// A file system that layers a writable file system on top of another, like overlayfs.
+///
+/// Reads fall through to the `lower` layer unless a path exists in the `upper` layer.
+/// Modifications only ever touch the `upper` layer: files in the `lower` layer are copied up
+/// before they are changed, and removing them records a whiteout that hides them from then on.
+/// Directories that exist in both layers are merged.
+///
+/// Paths are made absolute against a current directory that is tracked separately from either
+/// layer's, starting at the `upper` layer's current directory.
+/// `.` and `..` components are resolved lexically, and symlinks are resolved within the layer
+/// that contains them.
+///
+/// Files that are copied up keep their contents, readonly flag, and modification time,
+/// but not any other permissions.
+#[derive(Clone, Debug)]
+pub struct OverlayFileSystem<U, L> {
+    upper: U,
+    lower: L,
+    cwd: Arc<Mutex<PathBuf>>,
+    whiteouts: Arc<Mutex<HashSet<PathBuf>>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Layer {
+    Upper,
+    Lower,
+}
+
+impl<U, L> OverlayFileSystem<U, L>
+where
+    U: SymlinkFileSystem,
+    L: SymlinkFileSystem,
+{
+    /// Creates a file system that writes to `upper` and reads from `upper`, then `lower`.
+    pub fn new(upper: U, lower: L) -> Self {
+        let cwd = upper.current_dir().unwrap_or_else(|_| PathBuf::from("/"));
+
+        OverlayFileSystem {
+            upper,
+            lower,
+            cwd: Arc::new(Mutex::new(cwd)),
+            whiteouts: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Returns the layer that all modifications are made to.
+    pub fn upper(&self) -> &U {
+        &self.upper
+    }
+
+    /// Returns the layer that is only ever read from.
+    pub fn lower(&self) -> &L {
+        &self.lower
+    }
+
+    /// Returns `path` as an absolute path with `.` and `..` components removed.
+    fn normalize(&self, path: &Path) -> PathBuf {
+        normalize(&self.cwd.lock().recover(), path)
+    }
+
+    /// Returns `true` if `path` or one of its ancestors was removed from the lower layer.
+    fn hidden(&self, path: &Path) -> bool {
+        let whiteouts = self.whiteouts.lock().recover();
+
+        path.ancestors()
+            .any(|ancestor| whiteouts.contains(ancestor))
+    }
+
+    fn whiteout(&self, path: PathBuf) {
+        self.whiteouts.lock().recover().insert(path);
+    }
+
+    fn in_lower(&self, path: &Path) -> bool {
+        !self.hidden(path) && self.lower.symlink_metadata(path).is_ok()
+    }
+
+    /// Returns the layer that `path` is visible from, without following a final symlink.
+    fn layer(&self, path: &Path) -> Option<Layer> {
+        if self.upper.symlink_metadata(path).is_ok() {
+            Some(Layer::Upper)
+        } else if self.in_lower(path) {
+            Some(Layer::Lower)
+        } else {
+            None
+        }
+    }
+
+    /// Calls `upper` or `lower` depending on which layer `path` is visible from.
+    fn visible<T, A, B>(&self, path: &Path, upper: A, lower: B) -> Result<T>
+    where
+        A: FnOnce(&U, &Path) -> Result<T>,
+        B: FnOnce(&L, &Path) -> Result<T>,
+    {
+        match self.layer(path) {
+            Some(Layer::Upper) => upper(&self.upper, path),
+            Some(Layer::Lower) => lower(&self.lower, path),
+            None => Err(not_found()),
+        }
+    }
+
+    fn visible_file_type(&self, path: &Path) -> Result<FileType> {
+        self.visible(
+            path,
+            |fs, path| fs.symlink_metadata(path).map(|m| ::Metadata::file_type(&m)),
+            |fs, path| fs.symlink_metadata(path).map(|m| ::Metadata::file_type(&m)),
+        )
+    }
+
+    /// Returns the closest ancestor of the visible `path` in the upper layer, which is where
+    /// anything written below `path` takes up space.
+    fn upper_ancestor(&self, path: &Path) -> Result<PathBuf> {
+        let path = self.normalize(path);
+
+        if self.layer(&path).is_none() {
+            return Err(not_found());
+        }
+
+        path.ancestors()
+            .find(|ancestor| self.upper.symlink_metadata(ancestor).is_ok())
+            .map(Path::to_path_buf)
+            .ok_or_else(not_found)
+    }
+
+    /// Copies `path` from the lower layer to the upper layer, unless it is already there.
+    fn copy_up(&self, path: &Path) -> Result<()> {
+        match self.layer(path) {
+            Some(Layer::Upper) => return Ok(()),
+            Some(Layer::Lower) => {}
+            None => return Err(not_found()),
+        }
+
+        self.copy_up_parent(path)?;
+
+        let metadata = self.lower.symlink_metadata(path)?;
+
+        match ::Metadata::file_type(&metadata) {
+            FileType::Dir => self.upper.create_dir(path),
+            FileType::Symlink => {
+                let target = self.lower.read_link(path)?;
+
+                if self.lower.is_dir(path) {
+                    self.upper.symlink_dir(target, path)
+                } else {
+                    self.upper.symlink_file(target, path)
+                }
+            }
+            FileType::File => {
+                self.upper.create_file(path, self.lower.read_file(path)?)?;
+                self.upper
+                    .set_modified(path, ::Metadata::modified(&metadata)?)?;
+                self.upper
+                    .set_readonly(path, ::Metadata::readonly(&metadata))
+            }
+            _ => Err(special_file()),
+        }
+    }
+
+    /// Makes sure that the parent directory of `path` exists in the upper layer.
+    fn copy_up_parent(&self, path: &Path) -> Result<()> {
+        let parent = match path.parent() {
+            Some(parent) => parent,
+            None => return Ok(()),
+        };
+
+        let is_dir = self.visible(
+            parent,
+            |fs, path| Ok(fs.is_dir(path)),
+            |fs, path| Ok(fs.is_dir(path)),
+        )?;
+
+        if !is_dir {
+            return Err(io::Error::new(ErrorKind::NotADirectory, "not a directory").into());
+        }
+
+        self.copy_up(parent)
+    }
+
+    /// Runs `f`, with `operation` and `paths` attached to any error in place of those of the
+    /// layers, whose paths are normalized and may belong to a step such as copying up.
+    fn with_context<T, F>(&self, operation: &'static str, paths: &[&Path], f: F) -> Result<T>
+    where
+        F: FnOnce() -> Result<T>,
+    {
+        f().context(operation, paths)
+    }
+}
+
+impl<U, L> FileSystem for OverlayFileSystem<U, L>
+where
+    U: SymlinkFileSystem + Clone + Send + 'static,
+    L: SymlinkFileSystem + Clone + Send + 'static,
+{
+    type DirEntry = DirEntry<U::DirEntry, L::DirEntry>;
+    type ReadDir = ReadDir<U::DirEntry, L::DirEntry>;
+    type Metadata = Metadata<U::Metadata, L::Metadata>;
+    type OpenFile = OpenFile<U::OpenFile, L::OpenFile>;
+    type WalkDir = WalkDir;
+
+    fn current_dir(&self) -> Result<PathBuf> {
+        Ok(self.cwd.lock().recover().clone())
+    }
+
+    fn set_current_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("set_current_dir", &[path], || {
+            let path = self.normalize(path.as_ref());
+
+            if !::Metadata::is_dir(&self.metadata(&path)?) {
+                return Err(io::Error::new(ErrorKind::NotADirectory, "not a directory").into());
+            }
+
+            *self.cwd.lock().recover() = path;
+
+            Ok(())
+        })
+    }
+
+    fn metadata<P: AsRef<Path>>(&self, path: P) -> Result<Self::Metadata> {
+        let path = path.as_ref();
+
+        self.with_context("metadata", &[path], || {
+            self.visible(
+                &self.normalize(path.as_ref()),
+                |fs, path| fs.metadata(path).map(Metadata::Upper),
+                |fs, path| fs.metadata(path).map(Metadata::Lower),
+            )
+        })
+    }
+
+    fn canonicalize<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf> {
+        let path = path.as_ref();
+
+        self.with_context("canonicalize", &[path], || {
+            self.visible(
+                &self.normalize(path.as_ref()),
+                |fs, path| fs.canonicalize(path),
+                |fs, path| fs.canonicalize(path),
+            )
+        })
+    }
+
+    fn is_dir<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.visible(
+            &self.normalize(path.as_ref()),
+            |fs, path| Ok(fs.is_dir(path)),
+            |fs, path| Ok(fs.is_dir(path)),
+        )
+        .unwrap_or(false)
+    }
+
+    fn is_file<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.visible(
+            &self.normalize(path.as_ref()),
+            |fs, path| Ok(fs.is_file(path)),
+            |fs, path| Ok(fs.is_file(path)),
+        )
+        .unwrap_or(false)
+    }
+
+    fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.visible(
+            &self.normalize(path.as_ref()),
+            |fs, path| Ok(fs.exists(path)),
+            |fs, path| Ok(fs.exists(path)),
+        )
+        .unwrap_or(false)
+    }
+
+    fn try_exists<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+
+        self.with_context("try_exists", &[path], || {
+            let path = self.normalize(path.as_ref());
+
+            match self.layer(&path) {
+                Some(Layer::Upper) => self.upper.try_exists(&path),
+                Some(Layer::Lower) => self.lower.try_exists(&path),
+                None => Ok(false),
+            }
+        })
+    }
+
+    fn create_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("create_dir", &[path], || {
+            let path = self.normalize(path.as_ref());
+
+            if self.layer(&path).is_some() {
+                return Err(already_exists());
+            }
+
+            self.copy_up_parent(&path)?;
+            self.upper.create_dir(&path)
+        })
+    }
+
+    fn create_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("create_dir_all", &[path], || {
+            let path = self.normalize(path.as_ref());
+
+            if self.is_dir(&path) {
+                return Ok(());
+            }
+
+            if let Some(parent) = path.parent() {
+                self.create_dir_all(parent)?;
+            }
+
+            self.create_dir(&path)
+        })
+    }
+
+    fn remove_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("remove_dir", &[path], || {
+            let path = self.normalize(path.as_ref());
+
+            if self.visible_file_type(&path)? != FileType::Dir {
+                return Err(io::Error::new(ErrorKind::NotADirectory, "not a directory").into());
+            }
+
+            if self.read_dir(&path)?.next().is_some() {
+                return Err(
+                    io::Error::new(ErrorKind::DirectoryNotEmpty, "directory not empty").into(),
+                );
+            }
+
+            if self.layer(&path) == Some(Layer::Upper) {
+                self.upper.remove_dir(&path)?;
+            }
+
+            if self.in_lower(&path) {
+                self.whiteout(path);
+            }
+
+            Ok(())
+        })
+    }
+
+    fn remove_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("remove_dir_all", &[path], || {
+            let path = self.normalize(path.as_ref());
+
+            if self.visible_file_type(&path)? != FileType::Dir {
+                return Err(io::Error::new(ErrorKind::NotADirectory, "not a directory").into());
+            }
+
+            if self.layer(&path) == Some(Layer::Upper) {
+                self.upper.remove_dir_all(&path)?;
+            }
+
+            if self.in_lower(&path) {
+                self.whiteout(path);
+            }
+
+            Ok(())
+        })
+    }
+
+    fn read_dir<P: AsRef<Path>>(&self, path: P) -> Result<Self::ReadDir> {
+        let path = path.as_ref();
+
+        self.with_context("read_dir", &[path], || {
+            let dir = self.normalize(path.as_ref());
+            let layer = self.layer(&dir).ok_or_else(not_found)?;
+            let mut names = BTreeMap::new();
+
+            if layer == Layer::Upper {
+                for entry in self.upper.read_dir(&dir)? {
+                    let entry = entry?;
+
+                    names.insert(::DirEntry::file_name(&entry), LayerEntry::Upper(entry));
+                }
+            }
+
+            if layer == Layer::Lower || (self.in_lower(&dir) && self.lower.is_dir(&dir)) {
+                for entry in self.lower.read_dir(&dir)? {
+                    let entry = entry?;
+                    let name = ::DirEntry::file_name(&entry);
+
+                    if !names.contains_key(&name) && !self.hidden(&dir.join(&name)) {
+                        names.insert(name, LayerEntry::Lower(entry));
+                    }
+                }
+            }
+
+            let entries = names
+                .into_iter()
+                .map(|(file_name, entry)| {
+                    Ok(DirEntry {
+                        path: path.join(file_name),
+                        entry,
+                    })
+                })
+                .collect();
+
+            Ok(ReadDir::new(entries))
+        })
+    }
+
+    fn walk_dir_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &WalkDirOptions,
+    ) -> Result<Self::WalkDir> {
+        let path = path.as_ref();
+
+        WalkDir::new(OverlayWalkSource(self.clone()), path, options).context("walk_dir", &[path])
+    }
+
+    fn create_file<P, B>(&self, path: P, buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+
+        self.with_context("create_file", &[path], || {
+            let path = self.normalize(path.as_ref());
+
+            if self.layer(&path).is_some() {
+                return Err(already_exists());
+            }
+
+            self.copy_up_parent(&path)?;
+            self.upper.create_file(&path, buf)
+        })
+    }
+
+    fn write_file<P, B>(&self, path: P, buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+
+        self.with_context("write_file", &[path], || {
+            let path = self.normalize(path.as_ref());
+
+            match self.layer(&path) {
+                Some(_) => self.copy_up(&path)?,
+                None => self.copy_up_parent(&path)?,
+            }
+
+            self.upper.write_file(&path, buf)
+        })
+    }
+
+    fn overwrite_file<P, B>(&self, path: P, buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+
+        self.with_context("overwrite_file", &[path], || {
+            let path = self.normalize(path.as_ref());
+
+            self.copy_up(&path)?;
+            self.upper.overwrite_file(&path, buf)
+        })
+    }
+
+    fn set_len<P: AsRef<Path>>(&self, path: P, size: u64) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("set_len", &[path], || {
+            let path = self.normalize(path.as_ref());
+
+            self.copy_up(&path)?;
+            self.upper.set_len(&path, size)
+        })
+    }
+
+    fn sync_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("sync_file", &[path], || {
+            self.visible(
+                &self.normalize(path.as_ref()),
+                |fs, path| fs.sync_file(path),
+                |fs, path| fs.sync_file(path),
+            )
+        })
+    }
+
+    fn sync_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("sync_dir", &[path], || {
+            self.visible(
+                &self.normalize(path.as_ref()),
+                |fs, path| fs.sync_dir(path),
+                |fs, path| fs.sync_dir(path),
+            )
+        })
+    }
+
+    fn read_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
+        let path = path.as_ref();
+
+        self.with_context("read_file", &[path], || {
+            self.visible(
+                &self.normalize(path.as_ref()),
+                |fs, path| fs.read_file(path),
+                |fs, path| fs.read_file(path),
+            )
+        })
+    }
+
+    fn read_file_to_string<P: AsRef<Path>>(&self, path: P) -> Result<String> {
+        let path = path.as_ref();
+
+        self.with_context("read_file_to_string", &[path], || {
+            self.visible(
+                &self.normalize(path.as_ref()),
+                |fs, path| fs.read_file_to_string(path),
+                |fs, path| fs.read_file_to_string(path),
+            )
+        })
+    }
+
+    fn read_file_into<P, B>(&self, path: P, buf: B) -> Result<usize>
+    where
+        P: AsRef<Path>,
+        B: AsMut<Vec<u8>>,
+    {
+        let path = path.as_ref();
+
+        self.with_context("read_file_into", &[path], || {
+            let path = self.normalize(path.as_ref());
+
+            match self.layer(&path) {
+                Some(Layer::Upper) => self.upper.read_file_into(&path, buf),
+                Some(Layer::Lower) => self.lower.read_file_into(&path, buf),
+                None => Err(not_found()),
+            }
+        })
+    }
+
+    fn open<P: AsRef<Path>>(&self, path: P) -> Result<Self::OpenFile> {
+        let path = path.as_ref();
+
+        self.with_context("open", &[path], || {
+            self.visible(
+                &self.normalize(path.as_ref()),
+                |fs, path| fs.open(path).map(OpenFile::Upper),
+                |fs, path| fs.open(path).map(OpenFile::Lower),
+            )
+        })
+    }
+
+    #[cfg(feature = "hash")]
+    fn hash_file<P: AsRef<Path>>(&self, path: P, algorithm: Algorithm) -> Result<Digest> {
+        let path = path.as_ref();
+
+        self.with_context("hash_file", &[path], || {
+            self.visible(
+                &self.normalize(path.as_ref()),
+                |fs, path| fs.hash_file(path, algorithm),
+                |fs, path| fs.hash_file(path, algorithm),
+            )
+        })
+    }
+
+    fn open_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &OpenOptions,
+    ) -> Result<Self::OpenFile> {
+        let path = path.as_ref();
+
+        self.with_context("open_with_options", &[path], || {
+            let path = self.normalize(path.as_ref());
+
+            if !options.is_write() && !options.is_truncate() {
+                return self.visible(
+                    &path,
+                    |fs, path| fs.open_with_options(path, options).map(OpenFile::Upper),
+                    |fs, path| fs.open_with_options(path, options).map(OpenFile::Lower),
+                );
+            }
+
+            match self.layer(&path) {
+                Some(_) if options.is_create_new() => return Err(already_exists()),
+                Some(_) => self.copy_up(&path)?,
+                None => self.copy_up_parent(&path)?,
+            }
+
+            self.upper
+                .open_with_options(&path, options)
+                .map(OpenFile::Upper)
+        })
+    }
+
+    fn remove_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("remove_file", &[path], || {
+            let path = self.normalize(path.as_ref());
+
+            if self.visible_file_type(&path)? == FileType::Dir {
+                return Err(io::Error::new(ErrorKind::IsADirectory, "is a directory").into());
+            }
+
+            if self.layer(&path) == Some(Layer::Upper) {
+                self.upper.remove_file(&path)?;
+            }
+
+            if self.in_lower(&path) {
+                self.whiteout(path);
+            }
+
+            Ok(())
+        })
+    }
+
+    fn copy_file<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref(), to.as_ref());
+
+        self.with_context("copy_file", &[from, to], || {
+            let (from, to) = (self.normalize(from.as_ref()), self.normalize(to.as_ref()));
+            let layer = self.layer(&from).ok_or_else(not_found)?;
+
+            if self.is_dir(&to) {
+                return Err(io::Error::new(ErrorKind::IsADirectory, "is a directory").into());
+            }
+
+            self.copy_up_parent(&to)?;
+
+            match layer {
+                Layer::Upper => self.upper.copy_file(&from, &to),
+                Layer::Lower => {
+                    self.upper.write_file(&to, self.lower.read_file(&from)?)?;
+                    self.upper.set_readonly(&to, self.lower.readonly(&from)?)
+                }
+            }
+        })
+    }
+
+    fn copy_dir<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref(), to.as_ref());
+
+        self.with_context("copy_dir", &[from, to], || {
+            let (from, to) = (self.normalize(from.as_ref()), self.normalize(to.as_ref()));
+
+            if !self.is_dir(&from) {
+                return Err(io::Error::new(ErrorKind::NotADirectory, "not a directory").into());
+            }
+
+            if self.layer(&to).is_some() {
+                return Err(already_exists());
+            }
+
+            if to.starts_with(&from) {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "cannot copy a directory into itself",
+                )
+                .into());
+            }
+
+            self.create_dir(&to)?;
+
+            for entry in self.read_dir(&from)? {
+                let file_name = ::DirEntry::file_name(&entry?);
+                let (from, to) = (from.join(&file_name), to.join(&file_name));
+
+                match self.visible_file_type(&from)? {
+                    FileType::Dir => self.copy_dir(&from, &to)?,
+                    FileType::File => self.copy_file(&from, &to)?,
+                    FileType::Symlink => {
+                        let target = self.read_link(&from)?;
+
+                        if self.is_dir(&from) {
+                            self.symlink_dir(target, &to)?;
+                        } else {
+                            self.symlink_file(target, &to)?;
+                        }
+                    }
+                    _ => return Err(special_file()),
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    fn rename<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref(), to.as_ref());
+
+        self.with_context("rename", &[from, to], || {
+            let (from, to) = (self.normalize(from.as_ref()), self.normalize(to.as_ref()));
+            let file_type = self.visible_file_type(&from)?;
+
+            match self.layer(&to).map(|_| self.visible_file_type(&to)) {
+                Some(Ok(FileType::Dir)) => {
+                    return Err(io::Error::new(ErrorKind::IsADirectory, "is a directory").into());
+                }
+                Some(_) if file_type == FileType::Dir => {
+                    return Err(io::Error::new(ErrorKind::NotADirectory, "not a directory").into());
+                }
+                _ => {}
+            }
+
+            if file_type == FileType::Dir && self.in_lower(&from) {
+                self.copy_dir(&from, &to)?;
+
+                return self.remove_dir_all(&from);
+            }
+
+            self.copy_up(&from)?;
+            self.copy_up_parent(&to)?;
+            self.upper.rename(&from, &to)?;
+
+            if self.in_lower(&from) {
+                self.whiteout(from);
+            }
+
+            Ok(())
+        })
+    }
+
+    fn readonly<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+
+        self.with_context("readonly", &[path], || {
+            self.visible(
+                &self.normalize(path.as_ref()),
+                |fs, path| fs.readonly(path),
+                |fs, path| fs.readonly(path),
+            )
+        })
+    }
+
+    fn set_readonly<P: AsRef<Path>>(&self, path: P, readonly: bool) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("set_readonly", &[path], || {
+            let path = self.normalize(path.as_ref());
+
+            self.copy_up(&path)?;
+            self.upper.set_readonly(&path, readonly)
+        })
+    }
+
+    fn is_hidden<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+
+        self.with_context("is_hidden", &[path], || {
+            self.visible(
+                &self.normalize(path.as_ref()),
+                |fs, path| fs.is_hidden(path),
+                |fs, path| fs.is_hidden(path),
+            )
+        })
+    }
+
+    fn modified<P: AsRef<Path>>(&self, path: P) -> Result<SystemTime> {
+        let path = path.as_ref();
+
+        self.with_context("modified", &[path], || {
+            self.visible(
+                &self.normalize(path.as_ref()),
+                |fs, path| fs.modified(path),
+                |fs, path| fs.modified(path),
+            )
+        })
+    }
+
+    fn set_modified<P: AsRef<Path>>(&self, path: P, time: SystemTime) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("set_modified", &[path], || {
+            let path = self.normalize(path.as_ref());
+
+            self.copy_up(&path)?;
+            self.upper.set_modified(&path, time)
+        })
+    }
+
+    fn accessed<P: AsRef<Path>>(&self, path: P) -> Result<SystemTime> {
+        let path = path.as_ref();
+
+        self.with_context("accessed", &[path], || {
+            self.visible(
+                &self.normalize(path.as_ref()),
+                |fs, path| fs.accessed(path),
+                |fs, path| fs.accessed(path),
+            )
+        })
+    }
+
+    fn set_accessed<P: AsRef<Path>>(&self, path: P, time: SystemTime) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("set_accessed", &[path], || {
+            let path = self.normalize(path.as_ref());
+
+            self.copy_up(&path)?;
+            self.upper.set_accessed(&path, time)
+        })
+    }
+
+    fn len<P: AsRef<Path>>(&self, path: P) -> u64 {
+        self.visible(
+            &self.normalize(path.as_ref()),
+            |fs, path| Ok(fs.len(path)),
+            |fs, path| Ok(fs.len(path)),
+        )
+        .unwrap_or(0)
+    }
+
+    fn available_space<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+        let path = path.as_ref();
+
+        self.with_context("available_space", &[path], || {
+            self.upper
+                .available_space(self.upper_ancestor(path.as_ref())?)
+        })
+    }
+
+    fn total_space<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+        let path = path.as_ref();
+
+        self.with_context("total_space", &[path], || {
+            self.upper.total_space(self.upper_ancestor(path.as_ref())?)
+        })
+    }
+}
+
+impl<U, L> SymlinkFileSystem for OverlayFileSystem<U, L>
+where
+    U: SymlinkFileSystem + Clone + Send + 'static,
+    L: SymlinkFileSystem + Clone + Send + 'static,
+{
+    fn symlink_file<P, Q>(&self, original: P, link: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (original, link) = (original.as_ref(), link.as_ref());
+
+        self.with_context("symlink_file", &[original, link], || {
+            let link = self.normalize(link.as_ref());
+
+            if self.layer(&link).is_some() {
+                return Err(already_exists());
+            }
+
+            self.copy_up_parent(&link)?;
+            self.upper.symlink_file(original, &link)
+        })
+    }
+
+    fn symlink_dir<P, Q>(&self, original: P, link: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (original, link) = (original.as_ref(), link.as_ref());
+
+        self.with_context("symlink_dir", &[original, link], || {
+            let link = self.normalize(link.as_ref());
+
+            if self.layer(&link).is_some() {
+                return Err(already_exists());
+            }
+
+            self.copy_up_parent(&link)?;
+            self.upper.symlink_dir(original, &link)
+        })
+    }
+
+    fn read_link<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf> {
+        let path = path.as_ref();
+
+        self.with_context("read_link", &[path], || {
+            self.visible(
+                &self.normalize(path.as_ref()),
+                |fs, path| fs.read_link(path),
+                |fs, path| fs.read_link(path),
+            )
+        })
+    }
+
+    fn symlink_metadata<P: AsRef<Path>>(&self, path: P) -> Result<Self::Metadata> {
+        let path = path.as_ref();
+
+        self.with_context("symlink_metadata", &[path], || {
+            self.visible(
+                &self.normalize(path.as_ref()),
+                |fs, path| fs.symlink_metadata(path).map(Metadata::Upper),
+                |fs, path| fs.symlink_metadata(path).map(Metadata::Lower),
+            )
+        })
+    }
+}
+
+#[cfg(unix)]
+impl<U, L> UnixFileSystem for OverlayFileSystem<U, L>
+where
+    U: SymlinkFileSystem + UnixFileSystem,
+    L: SymlinkFileSystem + UnixFileSystem,
+{
+    fn mode<P: AsRef<Path>>(&self, path: P) -> Result<u32> {
+        let path = path.as_ref();
+
+        self.with_context("mode", &[path], || {
+            self.visible(
+                &self.normalize(path.as_ref()),
+                |fs, path| fs.mode(path),
+                |fs, path| fs.mode(path),
+            )
+        })
+    }
+
+    fn set_mode<P: AsRef<Path>>(&self, path: P, mode: u32) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("set_mode", &[path], || {
+            let path = self.normalize(path.as_ref());
+
+            self.copy_up(&path)?;
+            self.upper.set_mode(&path, mode)
+        })
+    }
+
+    fn owner<P: AsRef<Path>>(&self, path: P) -> Result<(u32, u32)> {
+        let path = path.as_ref();
+
+        self.with_context("owner", &[path], || {
+            self.visible(
+                &self.normalize(path.as_ref()),
+                |fs, path| fs.owner(path),
+                |fs, path| fs.owner(path),
+            )
+        })
+    }
+
+    fn set_owner<P: AsRef<Path>>(&self, path: P, uid: u32, gid: u32) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("set_owner", &[path], || {
+            let path = self.normalize(path.as_ref());
+
+            self.copy_up(&path)?;
+            self.upper.set_owner(&path, uid, gid)
+        })
+    }
+
+    fn create_file_with_mode<P, B>(&self, path: P, buf: B, mode: u32) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+
+        self.with_context("create_file_with_mode", &[path], || {
+            let path = self.normalize(path.as_ref());
+
+            if self.layer(&path).is_some() {
+                return Err(already_exists());
+            }
+
+            self.copy_up_parent(&path)?;
+            self.upper.create_file_with_mode(&path, buf, mode)
+        })
+    }
+
+    fn create_dir_with_mode<P: AsRef<Path>>(&self, path: P, mode: u32) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("create_dir_with_mode", &[path], || {
+            let path = self.normalize(path.as_ref());
+
+            if self.layer(&path).is_some() {
+                return Err(already_exists());
+            }
+
+            self.copy_up_parent(&path)?;
+            self.upper.create_dir_with_mode(&path, mode)
+        })
+    }
+
+    fn mknod<P: AsRef<Path>>(
+        &self,
+        path: P,
+        file_type: FileType,
+        mode: u32,
+        device: u64,
+    ) -> Result<()> {
+        let path = path.as_ref();
+
+        self.with_context("mknod", &[path], || {
+            let path = self.normalize(path.as_ref());
+
+            if self.layer(&path).is_some() {
+                return Err(already_exists());
+            }
+
+            self.copy_up_parent(&path)?;
+            self.upper.mknod(&path, file_type, mode, device)
+        })
+    }
+}
+
+/// The metadata of a node in either layer of an [`OverlayFileSystem`].
+///
+/// [`OverlayFileSystem`]: struct.OverlayFileSystem.html
+#[derive(Clone, Debug)]
+pub enum Metadata<U, L> {
+    Upper(U),
+    Lower(L),
+}
+
+impl<U: ::Metadata, L: ::Metadata> ::Metadata for Metadata<U, L> {
+    fn file_type(&self) -> FileType {
+        match *self {
+            Metadata::Upper(ref m) => m.file_type(),
+            Metadata::Lower(ref m) => m.file_type(),
+        }
+    }
+
+    fn len(&self) -> u64 {
+        match *self {
+            Metadata::Upper(ref m) => m.len(),
+            Metadata::Lower(ref m) => m.len(),
+        }
+    }
+
+    fn readonly(&self) -> bool {
+        match *self {
+            Metadata::Upper(ref m) => m.readonly(),
+            Metadata::Lower(ref m) => m.readonly(),
+        }
+    }
+
+    fn created(&self) -> io::Result<SystemTime> {
+        match *self {
+            Metadata::Upper(ref m) => m.created(),
+            Metadata::Lower(ref m) => m.created(),
+        }
+    }
+
+    fn modified(&self) -> io::Result<SystemTime> {
+        match *self {
+            Metadata::Upper(ref m) => m.modified(),
+            Metadata::Lower(ref m) => m.modified(),
+        }
+    }
+
+    fn accessed(&self) -> io::Result<SystemTime> {
+        match *self {
+            Metadata::Upper(ref m) => m.accessed(),
+            Metadata::Lower(ref m) => m.accessed(),
+        }
+    }
+}
+
+/// A file opened from either layer of an [`OverlayFileSystem`].
+/// Files are only opened from the lower layer when they are read-only.
+///
+/// [`OverlayFileSystem`]: struct.OverlayFileSystem.html
+#[derive(Debug)]
+pub enum OpenFile<U, L> {
+    Upper(U),
+    Lower(L),
+}
+
+impl<U: Read, L: Read> Read for OpenFile<U, L> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            OpenFile::Upper(ref mut file) => file.read(buf),
+            OpenFile::Lower(ref mut file) => file.read(buf),
+        }
+    }
+}
+
+impl<U: Write, L: Write> Write for OpenFile<U, L> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            OpenFile::Upper(ref mut file) => file.write(buf),
+            OpenFile::Lower(ref mut file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            OpenFile::Upper(ref mut file) => file.flush(),
+            OpenFile::Lower(ref mut file) => file.flush(),
+        }
+    }
+}
+
+impl<U: Seek, L: Seek> Seek for OpenFile<U, L> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match *self {
+            OpenFile::Upper(ref mut file) => file.seek(pos),
+            OpenFile::Lower(ref mut file) => file.seek(pos),
+        }
+    }
+}
+
+#[cfg(feature = "lock")]
+impl<U: LockableFile, L: LockableFile> LockableFile for OpenFile<U, L> {
+    fn lock_shared(&self) -> io::Result<()> {
+        match *self {
+            OpenFile::Upper(ref file) => file.lock_shared(),
+            OpenFile::Lower(ref file) => file.lock_shared(),
+        }
+    }
+
+    fn lock_exclusive(&self) -> io::Result<()> {
+        match *self {
+            OpenFile::Upper(ref file) => file.lock_exclusive(),
+            OpenFile::Lower(ref file) => file.lock_exclusive(),
+        }
+    }
+
+    fn try_lock_shared(&self) -> io::Result<()> {
+        match *self {
+            OpenFile::Upper(ref file) => file.try_lock_shared(),
+            OpenFile::Lower(ref file) => file.try_lock_shared(),
+        }
+    }
+
+    fn try_lock_exclusive(&self) -> io::Result<()> {
+        match *self {
+            OpenFile::Upper(ref file) => file.try_lock_exclusive(),
+            OpenFile::Lower(ref file) => file.try_lock_exclusive(),
+        }
+    }
+
+    fn unlock(&self) -> io::Result<()> {
+        match *self {
+            OpenFile::Upper(ref file) => file.unlock(),
+            OpenFile::Lower(ref file) => file.unlock(),
+        }
+    }
+}
+
+impl<U: ::OpenFile, L: ::OpenFile> ::OpenFile for OpenFile<U, L> {
+    fn set_len(&self, size: u64) -> io::Result<()> {
+        match *self {
+            OpenFile::Upper(ref file) => file.set_len(size),
+            OpenFile::Lower(ref file) => file.set_len(size),
+        }
+    }
+
+    fn sync_all(&self) -> io::Result<()> {
+        match *self {
+            OpenFile::Upper(ref file) => file.sync_all(),
+            OpenFile::Lower(ref file) => file.sync_all(),
+        }
+    }
+}
+
+/// An entry of a merged directory, from the layer that it is visible in.
+#[derive(Debug, Clone)]
+pub struct DirEntry<U, L> {
+    path: PathBuf,
+    entry: LayerEntry<U, L>,
+}
+
+#[derive(Debug, Clone)]
+enum LayerEntry<U, L> {
+    Upper(U),
+    Lower(L),
+}
+
+impl<U: ::DirEntry, L: ::DirEntry> ::DirEntry for DirEntry<U, L> {
+    type Metadata = Metadata<U::Metadata, L::Metadata>;
+
+    fn file_name(&self) -> OsString {
+        match self.entry {
+            LayerEntry::Upper(ref e) => e.file_name(),
+            LayerEntry::Lower(ref e) => e.file_name(),
+        }
+    }
+
+    fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    fn file_type(&self) -> io::Result<FileType> {
+        match self.entry {
+            LayerEntry::Upper(ref e) => e.file_type(),
+            LayerEntry::Lower(ref e) => e.file_type(),
+        }
+    }
+
+    fn metadata(&self) -> io::Result<Self::Metadata> {
+        match self.entry {
+            LayerEntry::Upper(ref e) => e.metadata().map(Metadata::Upper),
+            LayerEntry::Lower(ref e) => e.metadata().map(Metadata::Lower),
+        }
+    }
+}
+
+/// The merged entries of a directory in both layers, sorted by name.
+#[derive(Debug)]
+pub struct ReadDir<U, L>(IntoIter<io::Result<DirEntry<U, L>>>);
+
+impl<U, L> ReadDir<U, L> {
+    fn new(entries: Vec<io::Result<DirEntry<U, L>>>) -> Self {
+        ReadDir(entries.into_iter())
+    }
+}
+
+impl<U, L> Iterator for ReadDir<U, L> {
+    type Item = io::Result<DirEntry<U, L>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+impl<U: ::DirEntry, L: ::DirEntry> ::ReadDir<DirEntry<U, L>> for ReadDir<U, L> {}
+
+struct OverlayWalkSource<U, L>(OverlayFileSystem<U, L>);
+
+impl<U, L> WalkSource for OverlayWalkSource<U, L>
+where
+    U: SymlinkFileSystem + Clone + Send + 'static,
+    L: SymlinkFileSystem + Clone + Send + 'static,
+{
+    fn list(&self, dir: &Path) -> io::Result<Vec<(PathBuf, FileType)>> {
+        let mut children = vec![];
+
+        for entry in self.0.read_dir(dir)? {
+            let path = ::DirEntry::path(&entry?);
+            let file_type = self.0.visible_file_type(&self.0.normalize(&path))?;
+
+            children.push((path, file_type));
+        }
+
+        Ok(children)
+    }
+
+    fn follow(&self, path: &Path) -> io::Result<(PathBuf, FileType)> {
+        let file_type = ::Metadata::file_type(&self.0.metadata(path)?);
+
+        Ok((self.0.canonicalize(path)?, file_type))
+    }
+}
+
+fn not_found() -> Error {
+    io::Error::new(ErrorKind::NotFound, "no such file or directory").into()
+}
+
+fn already_exists() -> Error {
+    io::Error::new(ErrorKind::AlreadyExists, "file exists").into()
+}
+
+/// Special files can't be copied up, since the upper layer may not be able to create them.
+fn special_file() -> Error {
+    io::Error::new(
+        ErrorKind::Unsupported,
+        "cannot copy a FIFO, socket, or device",
+    )
+    .into()
+}
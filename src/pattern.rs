@@ -0,0 +1,124 @@
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Component, Path, PathBuf};
+
+use glob::{MatchOptions, Pattern};
+
+use {FileSystem, WalkDirOptions};
+
+/// Options which can be used to configure how paths are matched against a glob pattern.
+/// This is based on [`glob::MatchOptions`].
+///
+/// [`glob::MatchOptions`]: https://docs.rs/glob/0.3/glob/struct.MatchOptions.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobOptions {
+    case_sensitive: bool,
+    require_literal_leading_dot: bool,
+}
+
+impl GlobOptions {
+    /// Creates the default options: matching is case sensitive and wildcards match leading dots.
+    pub fn new() -> Self {
+        GlobOptions {
+            case_sensitive: true,
+            require_literal_leading_dot: false,
+        }
+    }
+
+    /// Sets whether letters must match with the same case.
+    pub fn case_sensitive(&mut self, case_sensitive: bool) -> &mut Self {
+        self.case_sensitive = case_sensitive;
+        self
+    }
+
+    /// Sets whether a leading `.` in a file name must be matched literally, which hides dotfiles
+    /// from wildcards.
+    pub fn require_literal_leading_dot(&mut self, require: bool) -> &mut Self {
+        self.require_literal_leading_dot = require;
+        self
+    }
+}
+
+impl Default for GlobOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the paths in `fs` that match `pattern`, in the order they are visited.
+pub fn glob<F: FileSystem + ?Sized>(
+    fs: &F,
+    pattern: &str,
+    options: &GlobOptions,
+) -> Result<Vec<PathBuf>> {
+    let compiled =
+        Pattern::new(pattern).map_err(|err| Error::new(ErrorKind::InvalidInput, err.msg))?;
+    let match_options = MatchOptions {
+        case_sensitive: options.case_sensitive,
+        require_literal_separator: true,
+        require_literal_leading_dot: options.require_literal_leading_dot,
+    };
+
+    // Only the components after the first wildcard need to be searched
+    let mut base = PathBuf::new();
+    let mut depth = 0;
+    let mut recursive = false;
+
+    for component in Path::new(pattern).components() {
+        let text = component.as_os_str().to_string_lossy();
+
+        if depth == 0 && !is_wildcard(&component) {
+            base.push(component);
+        } else {
+            depth += 1;
+            recursive |= text == "**";
+        }
+    }
+
+    if depth == 0 {
+        return Ok(match fs.metadata(&base) {
+            Ok(_) => vec![base],
+            Err(_) => vec![],
+        });
+    }
+
+    let relative = base.as_os_str().is_empty();
+    let root = if relative { fs.current_dir()? } else { base };
+    let mut walk_options = WalkDirOptions::new();
+
+    walk_options.min_depth(1).sort_by_file_name(true);
+
+    if !recursive {
+        walk_options.max_depth(depth);
+    }
+
+    let entries = match fs.walk_dir_with_options(&root, &walk_options) {
+        Ok(entries) => entries,
+        Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err.into()),
+    };
+    let mut paths = vec![];
+
+    for entry in entries {
+        let mut path = entry?.path();
+
+        if relative {
+            path = path
+                .strip_prefix(&root)
+                .map(Path::to_path_buf)
+                .unwrap_or(path);
+        }
+
+        if compiled.matches_path_with(&path, match_options) {
+            paths.push(path);
+        }
+    }
+
+    Ok(paths)
+}
+
+fn is_wildcard(component: &Component) -> bool {
+    component
+        .as_os_str()
+        .to_string_lossy()
+        .contains(&['*', '?', '['][..])
+}
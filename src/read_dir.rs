@@ -0,0 +1,83 @@
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+
+/// Options which can be used to configure which entries of a directory are listed.
+///
+/// Entries are chosen by name alone, so file systems can skip the others without looking them up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadDirOptions {
+    extensions: Option<Vec<OsString>>,
+    skip_hidden: bool,
+    sort_by_file_name: bool,
+}
+
+impl ReadDirOptions {
+    /// Creates the default options: every entry is listed, in the order provided by the file
+    /// system.
+    pub fn new() -> Self {
+        ReadDirOptions {
+            extensions: None,
+            skip_hidden: false,
+            sort_by_file_name: false,
+        }
+    }
+
+    /// Only lists entries whose names have one of `extensions`, such as `"toml"`, after their
+    /// last `.`.
+    /// This applies to directories as well as files.
+    pub fn extensions<I, S>(&mut self, extensions: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.extensions = Some(
+            extensions
+                .into_iter()
+                .map(|extension| extension.as_ref().to_os_string())
+                .collect(),
+        );
+        self
+    }
+
+    /// Sets whether entries whose names start with `.` are skipped, as `ls` does.
+    pub fn skip_hidden(&mut self, skip: bool) -> &mut Self {
+        self.skip_hidden = skip;
+        self
+    }
+
+    /// Sets whether the entries are sorted by file name.
+    pub fn sort_by_file_name(&mut self, sort: bool) -> &mut Self {
+        self.sort_by_file_name = sort;
+        self
+    }
+
+    /// Returns whether an entry named `file_name` is listed.
+    pub fn matches(&self, file_name: &OsStr) -> bool {
+        if self.skip_hidden && is_hidden_name(file_name) {
+            return false;
+        }
+
+        match self.extensions {
+            Some(ref extensions) => Path::new(file_name)
+                .extension()
+                .is_some_and(|extension| extensions.iter().any(|e| e == extension)),
+            None => true,
+        }
+    }
+
+    /// Returns whether the entries are sorted by file name.
+    pub fn sorted(&self) -> bool {
+        self.sort_by_file_name
+    }
+}
+
+impl Default for ReadDirOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns whether `file_name` starts with `.`, which marks hidden files outside of Windows.
+pub fn is_hidden_name(file_name: &OsStr) -> bool {
+    file_name.as_encoded_bytes().starts_with(b".")
+}
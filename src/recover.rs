@@ -0,0 +1,17 @@
+use std::sync::{LockResult, PoisonError};
+
+/// Recovers the guard of a lock that was poisoned by a thread panicking while holding it.
+///
+/// The locks of the file systems, such as those of the fake's nodes and of the current directory
+/// that wrapping file systems keep, are only held while their own operations run, so a panic
+/// elsewhere in a test shouldn't make every later operation on their clones panic as well, hiding
+/// the original failure.
+pub trait Recover<G> {
+    fn recover(self) -> G;
+}
+
+impl<G> Recover<G> for LockResult<G> {
+    fn recover(self) -> G {
+        self.unwrap_or_else(PoisonError::into_inner)
+    }
+}
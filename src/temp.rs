@@ -0,0 +1,84 @@
+#[cfg(all(unix, feature = "std-os"))]
+use std::fs::Permissions;
+#[cfg(all(unix, feature = "std-os"))]
+use std::os::unix::fs::PermissionsExt;
+
+#[cfg(feature = "std-os")]
+use tempfile::Builder;
+
+/// Options which can be used to configure how a temporary directory or file is created.
+/// This is based on [`tempfile::Builder`].
+///
+/// [`tempfile::Builder`]: https://docs.rs/tempfile/3/tempfile/struct.Builder.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TempOptions {
+    prefix: String,
+    suffix: String,
+    #[cfg(unix)]
+    mode: Option<u32>,
+}
+
+impl TempOptions {
+    /// Creates the default options: names start with `.tmp` and have no suffix, and permissions
+    /// are left to the file system.
+    pub fn new() -> Self {
+        TempOptions {
+            prefix: String::from(".tmp"),
+            suffix: String::new(),
+            #[cfg(unix)]
+            mode: None,
+        }
+    }
+
+    /// Sets the text that names start with, before their random characters.
+    pub fn prefix<S: AsRef<str>>(&mut self, prefix: S) -> &mut Self {
+        self.prefix = prefix.as_ref().to_string();
+        self
+    }
+
+    /// Sets the text that names end with, after their random characters, such as an extension.
+    pub fn suffix<S: AsRef<str>>(&mut self, suffix: S) -> &mut Self {
+        self.suffix = suffix.as_ref().to_string();
+        self
+    }
+
+    /// Sets the mode bits of the directory or file once created.
+    #[cfg(unix)]
+    pub fn mode(&mut self, mode: u32) -> &mut Self {
+        self.mode = Some(mode);
+        self
+    }
+}
+
+impl Default for TempOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns a [`tempfile::Builder`] configured with `options`.
+#[cfg(feature = "std-os")]
+pub fn builder(options: &TempOptions) -> Builder<'_, '_> {
+    let mut builder = Builder::new();
+
+    builder.prefix(&options.prefix).suffix(&options.suffix);
+
+    #[cfg(unix)]
+    if let Some(mode) = options.mode {
+        builder.permissions(Permissions::from_mode(mode));
+    }
+
+    builder
+}
+
+/// Returns a name with the prefix and suffix of `options` around `random`.
+#[cfg(feature = "fake")]
+pub fn name(options: &TempOptions, random: &str) -> String {
+    format!("{}{}{}", options.prefix, random, options.suffix)
+}
+
+/// Returns the mode bits set by `options`, if any.
+#[cfg(all(unix, feature = "fake"))]
+pub fn mode(options: &TempOptions) -> Option<u32> {
+    options.mode
+}
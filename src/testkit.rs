@@ -0,0 +1,85 @@
+//! Helpers for running the same test against several file systems, such as to check that an
+//! implementation of [`FileSystem`] behaves like [`OsFileSystem`] does, which are available with
+//! the `testkit` feature.
+//!
+//! Tests are given the file system as a `Box<dyn DynFileSystem>`, which implements
+//! [`FileSystem`], along with an empty directory to work in.
+//!
+//! ```
+//! testkit::run_for_all_backends(|fs, dir| {
+//!     let path = dir.join("file");
+//!
+//!     fs.create_file(&path, "contents").unwrap();
+//!     assert_eq!(fs.read_file_to_string(&path).unwrap(), "contents");
+//! });
+//! ```
+//!
+//! [`FileSystem`]: ../trait.FileSystem.html
+//! [`OsFileSystem`]: ../struct.OsFileSystem.html
+
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+use {DynFileSystem, FakeFileSystem, FileSystem, OsFileSystem, TempDir, TempFileSystem};
+
+/// Runs `test` against an [`OsFileSystem`] and then a [`FakeFileSystem`], each time in a new
+/// temporary directory that is removed afterwards.
+///
+/// # Panics
+///
+/// Panics if `test` panics, with its message prefixed by the name of the file system, or if a
+/// temporary directory cannot be created.
+///
+/// [`OsFileSystem`]: ../struct.OsFileSystem.html
+/// [`FakeFileSystem`]: ../struct.FakeFileSystem.html
+pub fn run_for_all_backends<F>(mut test: F)
+where
+    F: FnMut(Box<dyn DynFileSystem>, &Path),
+{
+    run_in_temp_dir("OsFileSystem", OsFileSystem::new(), &mut test);
+    run_in_temp_dir("FakeFileSystem", FakeFileSystem::new(), &mut test);
+}
+
+/// Runs `test` against `fs` in the existing directory `dir`, such as to run the tests given to
+/// [`run_for_all_backends`] against another implementation of [`FileSystem`].
+///
+/// # Panics
+///
+/// Panics if `test` panics, with its message prefixed by `name`.
+///
+/// [`run_for_all_backends`]: fn.run_for_all_backends.html
+/// [`FileSystem`]: ../trait.FileSystem.html
+pub fn run_for_backend<T, F>(name: &str, fs: T, dir: &Path, test: F)
+where
+    T: FileSystem + 'static,
+    F: FnOnce(Box<dyn DynFileSystem>, &Path),
+{
+    let result = panic::catch_unwind(AssertUnwindSafe(|| test(Box::new(fs), dir)));
+
+    if let Err(payload) = result {
+        panic!("{}: {}", name, message(&*payload));
+    }
+}
+
+fn run_in_temp_dir<T, F>(name: &str, fs: T, test: F)
+where
+    T: FileSystem + TempFileSystem + 'static,
+    F: FnOnce(Box<dyn DynFileSystem>, &Path),
+{
+    let temp_dir = fs
+        .temp_dir("testkit")
+        .unwrap_or_else(|err| panic!("{}: failed to create temporary directory: {}", name, err));
+
+    run_for_backend(name, fs, temp_dir.path(), test);
+}
+
+/// Returns the message of a panic, which is usually a string.
+fn message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload
+            .downcast_ref::<String>()
+            .map_or("test panicked", String::as_str),
+    }
+}
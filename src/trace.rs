@@ -0,0 +1,671 @@
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+#[cfg(windows)]
+use WindowsFileSystem;
+#[cfg(feature = "hash")]
+use {Algorithm, Digest};
+use {
+    FileSystem, GlobOptions, OpenOptions, ReadDirOptions, Result, SymlinkFileSystem, WalkDirOptions,
+};
+#[cfg(unix)]
+use {FileType, UnixFileSystem};
+#[cfg(feature = "temp")]
+use {TempFileSystem, TempOptions};
+
+/// A file system operation recorded by [`TracingFileSystem`].
+///
+/// [`TracingFileSystem`]: struct.TracingFileSystem.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Call {
+    /// The name of the method that was called, such as `"write_file"`.
+    pub method: &'static str,
+    /// The paths that were passed to the method, in order.
+    /// For `glob` and `glob_with`, this is the pattern.
+    pub paths: Vec<PathBuf>,
+    /// The kind of error that the method returned, if any.
+    pub error: Option<ErrorKind>,
+}
+
+impl Call {
+    fn new<T>(method: &'static str, paths: &[&Path], result: &Result<T>) -> Self {
+        Call {
+            method,
+            paths: paths.iter().map(|path| path.to_path_buf()).collect(),
+            error: result.as_ref().err().map(|err| err.kind()),
+        }
+    }
+}
+
+/// A shared, append-only log of [`Call`]s.
+///
+/// [`Call`]: struct.Call.html
+#[derive(Clone, Debug, Default)]
+pub struct CallLog(Arc<Mutex<Vec<Call>>>);
+
+impl CallLog {
+    pub fn push(&self, call: Call) {
+        #[cfg(feature = "tracing")]
+        ::tracing::debug!(
+            method = call.method,
+            paths = ?call.paths,
+            error = ?call.error,
+            "file system call"
+        );
+
+        self.0.lock().unwrap().push(call);
+    }
+
+    pub fn record<T>(&self, method: &'static str, paths: &[&Path], result: Result<T>) -> Result<T> {
+        self.push(Call::new(method, paths, &result));
+
+        result
+    }
+
+    /// Records a call to a method that cannot fail, such as `is_dir`.
+    pub fn record_value<T>(&self, method: &'static str, paths: &[&Path], value: T) -> T {
+        self.push(Call::new::<()>(method, paths, &Ok(())));
+
+        value
+    }
+
+    pub fn calls(&self) -> Vec<Call> {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub fn calls_for(&self, method: &str) -> Vec<Call> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|call| call.method == method)
+            .cloned()
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        self.0.lock().unwrap().clear();
+    }
+}
+
+/// A file system that records every operation performed on another file system.
+///
+/// Each call is recorded with the name of the method, the paths it was given, and the kind of
+/// error it returned, so that tests can assert on the sequence of operations as well as on the
+/// resulting state of a [`FakeFileSystem`].
+/// Contents, options, and other arguments are not recorded.
+/// Operations on handles returned by the file system, such as reads from an open file,
+/// are not recorded either.
+///
+/// With the `tracing` feature, every call is also emitted as a `DEBUG` event.
+///
+/// Clones share the same call log.
+///
+/// [`FakeFileSystem`]: struct.FakeFileSystem.html
+#[derive(Clone, Debug)]
+pub struct TracingFileSystem<T> {
+    inner: T,
+    log: CallLog,
+}
+
+impl<T> TracingFileSystem<T> {
+    /// Creates a file system that records every operation performed on `inner`.
+    pub fn new(inner: T) -> Self {
+        TracingFileSystem {
+            inner,
+            log: CallLog::default(),
+        }
+    }
+
+    /// Returns the inner file system.
+    /// Operations performed on it directly are not recorded.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns every call recorded so far, in the order they were made.
+    pub fn calls(&self) -> Vec<Call> {
+        self.log.calls()
+    }
+
+    /// Returns every call to `method` recorded so far, in the order they were made.
+    pub fn calls_for(&self, method: &str) -> Vec<Call> {
+        self.log.calls_for(method)
+    }
+
+    /// Forgets every call recorded so far.
+    pub fn clear_calls(&self) {
+        self.log.clear()
+    }
+
+    fn record<R>(&self, method: &'static str, paths: &[&Path], result: Result<R>) -> Result<R> {
+        self.log.record(method, paths, result)
+    }
+
+    fn record_value<R>(&self, method: &'static str, path: &Path, value: R) -> R {
+        self.log.record_value(method, &[path], value)
+    }
+}
+
+impl<T: FileSystem> FileSystem for TracingFileSystem<T> {
+    type DirEntry = T::DirEntry;
+    type ReadDir = T::ReadDir;
+    type Metadata = T::Metadata;
+    type OpenFile = T::OpenFile;
+    type WalkDir = T::WalkDir;
+
+    fn current_dir(&self) -> Result<PathBuf> {
+        self.record("current_dir", &[], self.inner.current_dir())
+    }
+
+    fn set_current_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.record("set_current_dir", &[path], self.inner.set_current_dir(path))
+    }
+
+    fn metadata<P: AsRef<Path>>(&self, path: P) -> Result<Self::Metadata> {
+        let path = path.as_ref();
+
+        self.record("metadata", &[path], self.inner.metadata(path))
+    }
+
+    fn canonicalize<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf> {
+        let path = path.as_ref();
+
+        self.record("canonicalize", &[path], self.inner.canonicalize(path))
+    }
+
+    fn is_dir<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path = path.as_ref();
+
+        self.record_value("is_dir", path, self.inner.is_dir(path))
+    }
+
+    fn is_file<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path = path.as_ref();
+
+        self.record_value("is_file", path, self.inner.is_file(path))
+    }
+
+    fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path = path.as_ref();
+
+        self.record_value("exists", path, self.inner.exists(path))
+    }
+
+    fn try_exists<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+
+        self.record("try_exists", &[path], self.inner.try_exists(path))
+    }
+
+    fn create_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.record("create_dir", &[path], self.inner.create_dir(path))
+    }
+
+    fn create_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.record("create_dir_all", &[path], self.inner.create_dir_all(path))
+    }
+
+    fn remove_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.record("remove_dir", &[path], self.inner.remove_dir(path))
+    }
+
+    fn remove_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.record("remove_dir_all", &[path], self.inner.remove_dir_all(path))
+    }
+
+    fn read_dir<P: AsRef<Path>>(&self, path: P) -> Result<Self::ReadDir> {
+        let path = path.as_ref();
+
+        self.record("read_dir", &[path], self.inner.read_dir(path))
+    }
+
+    fn read_dir_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &ReadDirOptions,
+    ) -> Result<Vec<Self::DirEntry>> {
+        let path = path.as_ref();
+
+        self.record(
+            "read_dir_with_options",
+            &[path],
+            self.inner.read_dir_with_options(path, options),
+        )
+    }
+
+    fn walk_dir<P: AsRef<Path>>(&self, path: P) -> Result<Self::WalkDir> {
+        let path = path.as_ref();
+
+        self.record("walk_dir", &[path], self.inner.walk_dir(path))
+    }
+
+    fn walk_dir_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &WalkDirOptions,
+    ) -> Result<Self::WalkDir> {
+        let path = path.as_ref();
+
+        self.record(
+            "walk_dir_with_options",
+            &[path],
+            self.inner.walk_dir_with_options(path, options),
+        )
+    }
+
+    fn glob(&self, pattern: &str) -> Result<Vec<PathBuf>> {
+        self.record("glob", &[Path::new(pattern)], self.inner.glob(pattern))
+    }
+
+    fn glob_with(&self, pattern: &str, options: &GlobOptions) -> Result<Vec<PathBuf>> {
+        self.record(
+            "glob_with",
+            &[Path::new(pattern)],
+            self.inner.glob_with(pattern, options),
+        )
+    }
+
+    fn create_file<P, B>(&self, path: P, buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+
+        self.record("create_file", &[path], self.inner.create_file(path, buf))
+    }
+
+    fn write_file<P, B>(&self, path: P, buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+
+        self.record("write_file", &[path], self.inner.write_file(path, buf))
+    }
+
+    fn overwrite_file<P, B>(&self, path: P, buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+
+        self.record(
+            "overwrite_file",
+            &[path],
+            self.inner.overwrite_file(path, buf),
+        )
+    }
+
+    fn set_len<P: AsRef<Path>>(&self, path: P, size: u64) -> Result<()> {
+        let path = path.as_ref();
+
+        self.record("set_len", &[path], self.inner.set_len(path, size))
+    }
+
+    fn sync_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.record("sync_file", &[path], self.inner.sync_file(path))
+    }
+
+    fn sync_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.record("sync_dir", &[path], self.inner.sync_dir(path))
+    }
+
+    fn read_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
+        let path = path.as_ref();
+
+        self.record("read_file", &[path], self.inner.read_file(path))
+    }
+
+    fn read_file_to_string<P: AsRef<Path>>(&self, path: P) -> Result<String> {
+        let path = path.as_ref();
+
+        self.record(
+            "read_file_to_string",
+            &[path],
+            self.inner.read_file_to_string(path),
+        )
+    }
+
+    fn read_file_into<P, B>(&self, path: P, buf: B) -> Result<usize>
+    where
+        P: AsRef<Path>,
+        B: AsMut<Vec<u8>>,
+    {
+        let path = path.as_ref();
+
+        self.record(
+            "read_file_into",
+            &[path],
+            self.inner.read_file_into(path, buf),
+        )
+    }
+
+    fn open<P: AsRef<Path>>(&self, path: P) -> Result<Self::OpenFile> {
+        let path = path.as_ref();
+
+        self.record("open", &[path], self.inner.open(path))
+    }
+
+    #[cfg(feature = "hash")]
+    fn hash_file<P: AsRef<Path>>(&self, path: P, algorithm: Algorithm) -> Result<Digest> {
+        let path = path.as_ref();
+
+        self.record("hash_file", &[path], self.inner.hash_file(path, algorithm))
+    }
+
+    fn open_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &OpenOptions,
+    ) -> Result<Self::OpenFile> {
+        let path = path.as_ref();
+
+        self.record(
+            "open_with_options",
+            &[path],
+            self.inner.open_with_options(path, options),
+        )
+    }
+
+    fn remove_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.record("remove_file", &[path], self.inner.remove_file(path))
+    }
+
+    fn copy_file<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref(), to.as_ref());
+
+        self.record("copy_file", &[from, to], self.inner.copy_file(from, to))
+    }
+
+    fn copy_dir<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref(), to.as_ref());
+
+        self.record("copy_dir", &[from, to], self.inner.copy_dir(from, to))
+    }
+
+    fn rename<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref(), to.as_ref());
+
+        self.record("rename", &[from, to], self.inner.rename(from, to))
+    }
+
+    fn readonly<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+
+        self.record("readonly", &[path], self.inner.readonly(path))
+    }
+
+    fn set_readonly<P: AsRef<Path>>(&self, path: P, readonly: bool) -> Result<()> {
+        let path = path.as_ref();
+
+        self.record(
+            "set_readonly",
+            &[path],
+            self.inner.set_readonly(path, readonly),
+        )
+    }
+
+    fn is_hidden<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+
+        self.record("is_hidden", &[path], self.inner.is_hidden(path))
+    }
+
+    fn modified<P: AsRef<Path>>(&self, path: P) -> Result<SystemTime> {
+        let path = path.as_ref();
+
+        self.record("modified", &[path], self.inner.modified(path))
+    }
+
+    fn set_modified<P: AsRef<Path>>(&self, path: P, time: SystemTime) -> Result<()> {
+        let path = path.as_ref();
+
+        self.record("set_modified", &[path], self.inner.set_modified(path, time))
+    }
+
+    fn accessed<P: AsRef<Path>>(&self, path: P) -> Result<SystemTime> {
+        let path = path.as_ref();
+
+        self.record("accessed", &[path], self.inner.accessed(path))
+    }
+
+    fn set_accessed<P: AsRef<Path>>(&self, path: P, time: SystemTime) -> Result<()> {
+        let path = path.as_ref();
+
+        self.record("set_accessed", &[path], self.inner.set_accessed(path, time))
+    }
+
+    fn len<P: AsRef<Path>>(&self, path: P) -> u64 {
+        let path = path.as_ref();
+
+        self.record_value("len", path, self.inner.len(path))
+    }
+
+    fn dir_size<P: AsRef<Path>>(&self, path: P, follow_links: bool) -> Result<u64> {
+        let path = path.as_ref();
+
+        self.record("dir_size", &[path], self.inner.dir_size(path, follow_links))
+    }
+
+    fn available_space<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+        let path = path.as_ref();
+
+        self.record("available_space", &[path], self.inner.available_space(path))
+    }
+
+    fn total_space<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+        let path = path.as_ref();
+
+        self.record("total_space", &[path], self.inner.total_space(path))
+    }
+}
+
+impl<T: SymlinkFileSystem> SymlinkFileSystem for TracingFileSystem<T> {
+    fn symlink_file<P, Q>(&self, original: P, link: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (original, link) = (original.as_ref(), link.as_ref());
+
+        self.record(
+            "symlink_file",
+            &[original, link],
+            self.inner.symlink_file(original, link),
+        )
+    }
+
+    fn symlink_dir<P, Q>(&self, original: P, link: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (original, link) = (original.as_ref(), link.as_ref());
+
+        self.record(
+            "symlink_dir",
+            &[original, link],
+            self.inner.symlink_dir(original, link),
+        )
+    }
+
+    fn read_link<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf> {
+        let path = path.as_ref();
+
+        self.record("read_link", &[path], self.inner.read_link(path))
+    }
+
+    fn symlink_metadata<P: AsRef<Path>>(&self, path: P) -> Result<Self::Metadata> {
+        let path = path.as_ref();
+
+        self.record(
+            "symlink_metadata",
+            &[path],
+            self.inner.symlink_metadata(path),
+        )
+    }
+}
+
+#[cfg(unix)]
+impl<T: UnixFileSystem> UnixFileSystem for TracingFileSystem<T> {
+    fn mode<P: AsRef<Path>>(&self, path: P) -> Result<u32> {
+        let path = path.as_ref();
+
+        self.record("mode", &[path], self.inner.mode(path))
+    }
+
+    fn set_mode<P: AsRef<Path>>(&self, path: P, mode: u32) -> Result<()> {
+        let path = path.as_ref();
+
+        self.record("set_mode", &[path], self.inner.set_mode(path, mode))
+    }
+
+    fn owner<P: AsRef<Path>>(&self, path: P) -> Result<(u32, u32)> {
+        let path = path.as_ref();
+
+        self.record("owner", &[path], self.inner.owner(path))
+    }
+
+    fn set_owner<P: AsRef<Path>>(&self, path: P, uid: u32, gid: u32) -> Result<()> {
+        let path = path.as_ref();
+
+        self.record("set_owner", &[path], self.inner.set_owner(path, uid, gid))
+    }
+
+    fn create_file_with_mode<P, B>(&self, path: P, buf: B, mode: u32) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        let path = path.as_ref();
+
+        self.record(
+            "create_file_with_mode",
+            &[path],
+            self.inner.create_file_with_mode(path, buf, mode),
+        )
+    }
+
+    fn create_dir_with_mode<P: AsRef<Path>>(&self, path: P, mode: u32) -> Result<()> {
+        let path = path.as_ref();
+
+        self.record(
+            "create_dir_with_mode",
+            &[path],
+            self.inner.create_dir_with_mode(path, mode),
+        )
+    }
+
+    fn mknod<P: AsRef<Path>>(
+        &self,
+        path: P,
+        file_type: FileType,
+        mode: u32,
+        device: u64,
+    ) -> Result<()> {
+        let path = path.as_ref();
+
+        self.record(
+            "mknod",
+            &[path],
+            self.inner.mknod(path, file_type, mode, device),
+        )
+    }
+}
+
+#[cfg(windows)]
+impl<T: WindowsFileSystem> WindowsFileSystem for TracingFileSystem<T> {
+    fn attributes<P: AsRef<Path>>(&self, path: P) -> Result<u32> {
+        let path = path.as_ref();
+
+        self.record("attributes", &[path], self.inner.attributes(path))
+    }
+
+    fn set_attributes<P: AsRef<Path>>(&self, path: P, attributes: u32) -> Result<()> {
+        let path = path.as_ref();
+
+        self.record(
+            "set_attributes",
+            &[path],
+            self.inner.set_attributes(path, attributes),
+        )
+    }
+
+    fn create_junction<P, Q>(&self, original: P, junction: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (original, junction) = (original.as_ref(), junction.as_ref());
+
+        self.record(
+            "create_junction",
+            &[original, junction],
+            self.inner.create_junction(original, junction),
+        )
+    }
+}
+
+#[cfg(feature = "temp")]
+impl<T: TempFileSystem> TempFileSystem for TracingFileSystem<T> {
+    type TempDir = T::TempDir;
+    type TempFile = T::TempFile;
+
+    fn temp_dir<S: AsRef<str>>(&self, prefix: S) -> Result<Self::TempDir> {
+        self.inner.temp_dir(prefix)
+    }
+
+    fn temp_dir_with_options<P: AsRef<Path>>(
+        &self,
+        base: P,
+        options: &TempOptions,
+    ) -> Result<Self::TempDir> {
+        self.inner.temp_dir_with_options(base, options)
+    }
+
+    fn temp_file<S: AsRef<str>>(&self, prefix: S) -> Result<Self::TempFile> {
+        self.inner.temp_file(prefix)
+    }
+
+    fn temp_file_with_options<P: AsRef<Path>>(
+        &self,
+        base: P,
+        options: &TempOptions,
+    ) -> Result<Self::TempFile> {
+        self.inner.temp_file_with_options(base, options)
+    }
+}
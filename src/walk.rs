@@ -0,0 +1,240 @@
+use std::ffi::OsString;
+use std::fmt;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+use std::vec::IntoIter;
+
+use error;
+use FileType;
+
+/// Options which can be used to configure how a directory is walked.
+/// This is based on the options of the [`walkdir`] crate.
+///
+/// [`walkdir`]: https://docs.rs/walkdir
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalkDirOptions {
+    min_depth: usize,
+    max_depth: usize,
+    follow_links: bool,
+    sort_by_file_name: bool,
+}
+
+impl WalkDirOptions {
+    /// Creates the default options: every descendant is visited, symbolic links are not followed,
+    /// and entries are returned in the order provided by the file system.
+    pub fn new() -> Self {
+        WalkDirOptions {
+            min_depth: 0,
+            max_depth: usize::MAX,
+            follow_links: false,
+            sort_by_file_name: false,
+        }
+    }
+
+    /// Skips entries that are fewer than `depth` levels below the root.
+    /// The root itself is at depth 0.
+    pub fn min_depth(&mut self, depth: usize) -> &mut Self {
+        self.min_depth = depth;
+        self
+    }
+
+    /// Skips entries that are more than `depth` levels below the root.
+    /// The root itself is at depth 0.
+    pub fn max_depth(&mut self, depth: usize) -> &mut Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Sets whether symbolic links to directories are descended into.
+    pub fn follow_links(&mut self, follow_links: bool) -> &mut Self {
+        self.follow_links = follow_links;
+        self
+    }
+
+    /// Sets whether the entries of each directory are sorted by file name.
+    pub fn sort_by_file_name(&mut self, sort: bool) -> &mut Self {
+        self.sort_by_file_name = sort;
+        self
+    }
+}
+
+impl Default for WalkDirOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An entry returned while walking a directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalkDirEntry {
+    path: PathBuf,
+    depth: usize,
+    file_type: FileType,
+}
+
+impl WalkDirEntry {
+    pub fn new(path: PathBuf, depth: usize, file_type: FileType) -> Self {
+        WalkDirEntry {
+            path,
+            depth,
+            file_type,
+        }
+    }
+
+    /// Returns the number of levels between the entry and the root of the walk.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the type of the entry.
+    /// If symbolic links are followed, this is the type of the link's target.
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    /// Returns the final component of the entry's path.
+    pub fn file_name(&self) -> OsString {
+        self.path
+            .file_name()
+            .unwrap_or_else(|| self.path.as_os_str())
+            .to_os_string()
+    }
+
+    /// Returns the path of the entry, starting with the path that the walk started from.
+    pub fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+}
+
+/// Backend-specific operations used by [`WalkDir`].
+pub trait WalkSource {
+    /// Returns the paths and types of the children of `dir`, without following symbolic links.
+    fn list(&self, dir: &Path) -> Result<Vec<(PathBuf, FileType)>>;
+    /// Returns the canonical path and type of the node that `path` points to.
+    fn follow(&self, path: &Path) -> Result<(PathBuf, FileType)>;
+}
+
+/// A recursive iterator over a directory and all of its descendants.
+///
+/// Directories are returned before their contents.
+pub struct WalkDir {
+    source: Box<dyn WalkSource + Send>,
+    options: WalkDirOptions,
+    root: Option<WalkDirEntry>,
+    stack: Vec<IntoIter<Result<WalkDirEntry>>>,
+    ancestors: Vec<PathBuf>,
+}
+
+impl WalkDir {
+    pub fn new<S>(source: S, root: &Path, options: &WalkDirOptions) -> Result<Self>
+    where
+        S: WalkSource + Send + 'static,
+    {
+        let (canonical, file_type) = source.follow(root)?;
+
+        Ok(WalkDir {
+            source: Box::new(source),
+            options: options.clone(),
+            root: Some(WalkDirEntry::new(root.to_path_buf(), 0, file_type)),
+            stack: vec![],
+            ancestors: vec![canonical],
+        })
+    }
+
+    fn push_children(&mut self, dir: &WalkDirEntry) {
+        let children = match self.source.list(&dir.path) {
+            Ok(mut children) => {
+                if self.options.sort_by_file_name {
+                    children.sort_by(|(a, _), (b, _)| a.file_name().cmp(&b.file_name()));
+                }
+
+                children
+                    .into_iter()
+                    .map(|(path, file_type)| Ok(WalkDirEntry::new(path, dir.depth + 1, file_type)))
+                    .collect()
+            }
+            Err(err) => vec![Err(err)],
+        };
+
+        self.stack.push(children.into_iter());
+    }
+
+    /// Resolves `entry` if it is a symlink that should be followed, returning the updated entry
+    /// and, if links are being followed, its canonical path.
+    fn follow(&self, entry: WalkDirEntry) -> Result<(WalkDirEntry, PathBuf)> {
+        if !self.options.follow_links {
+            let path = entry.path.clone();
+
+            return Ok((entry, path));
+        }
+
+        let (canonical, file_type) = self.source.follow(&entry.path)?;
+
+        if entry.file_type.is_symlink() && file_type.is_dir() && self.ancestors.contains(&canonical)
+        {
+            return Err(error::filesystem_loop(format!(
+                "file system loop found at {}",
+                entry.path.display()
+            )));
+        }
+
+        Ok((WalkDirEntry { file_type, ..entry }, canonical))
+    }
+}
+
+impl fmt::Debug for WalkDir {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WalkDir")
+            .field("options", &self.options)
+            .field("root", &self.root)
+            .field("ancestors", &self.ancestors)
+            .finish()
+    }
+}
+
+impl Iterator for WalkDir {
+    type Item = Result<WalkDirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(root) = self.root.take() {
+            if root.file_type.is_dir() && self.options.max_depth > 0 {
+                self.push_children(&root);
+            }
+
+            if self.options.min_depth == 0 {
+                return Some(Ok(root));
+            }
+        }
+
+        loop {
+            let next = match self.stack.last_mut() {
+                Some(entries) => entries.next(),
+                None => return None,
+            };
+
+            let entry = match next {
+                Some(Ok(entry)) => entry,
+                Some(Err(err)) => return Some(Err(err)),
+                None => {
+                    self.stack.pop();
+                    self.ancestors.pop();
+                    continue;
+                }
+            };
+
+            let (entry, canonical) = match self.follow(entry) {
+                Ok(followed) => followed,
+                Err(err) => return Some(Err(err)),
+            };
+
+            if entry.file_type.is_dir() && entry.depth < self.options.max_depth {
+                self.push_children(&entry);
+                self.ancestors.push(canonical);
+            }
+
+            if entry.depth >= self.options.min_depth {
+                return Some(Ok(entry));
+            }
+        }
+    }
+}
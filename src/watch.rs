@@ -0,0 +1,218 @@
+#[cfg(feature = "std-os")]
+use std::collections::BTreeMap;
+#[cfg(feature = "std-os")]
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
+#[cfg(feature = "std-os")]
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+#[cfg(feature = "std-os")]
+use std::thread;
+#[cfg(feature = "std-os")]
+use std::time::{Duration, SystemTime};
+
+#[cfg(feature = "std-os")]
+use error::Context;
+#[cfg(feature = "std-os")]
+use OsFileSystem;
+use {FileSystem, Result};
+
+/// How often `OsFileSystem` checks watched paths for changes.
+#[cfg(feature = "std-os")]
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A change to a watched node, as sent by [`WatchFileSystem::watch`].
+///
+/// Renames are reported as the removal of the old path followed by the creation of the new one.
+///
+/// [`WatchFileSystem::watch`]: trait.WatchFileSystem.html#tymethod.watch
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FsEvent {
+    /// A node was created at the path.
+    Created(PathBuf),
+    /// The contents or metadata of the node at the path changed.
+    Modified(PathBuf),
+    /// The node at the path was removed.
+    Removed(PathBuf),
+}
+
+impl FsEvent {
+    /// Returns the path of the node that changed.
+    pub fn path(&self) -> &Path {
+        match *self {
+            FsEvent::Created(ref path)
+            | FsEvent::Modified(ref path)
+            | FsEvent::Removed(ref path) => path,
+        }
+    }
+}
+
+/// Receives the changes to a watched node, as returned by [`WatchFileSystem::watch`].
+///
+/// It dereferences to the [`Receiver`] of the events, and stops watching once dropped.
+///
+/// [`WatchFileSystem::watch`]: trait.WatchFileSystem.html#tymethod.watch
+/// [`Receiver`]: https://doc.rust-lang.org/std/sync/mpsc/struct.Receiver.html
+#[derive(Debug)]
+pub struct FsWatcher {
+    receiver: Receiver<FsEvent>,
+    /// Set once the watcher is dropped, for a thread that polls for changes to stop.
+    stopped: Option<Arc<AtomicBool>>,
+}
+
+/// Creates a watcher for events sent from elsewhere, such as by the file system itself as it
+/// changes, which stop once sending to `receiver` fails.
+impl From<Receiver<FsEvent>> for FsWatcher {
+    fn from(receiver: Receiver<FsEvent>) -> Self {
+        FsWatcher {
+            receiver,
+            stopped: None,
+        }
+    }
+}
+
+impl Deref for FsWatcher {
+    type Target = Receiver<FsEvent>;
+
+    fn deref(&self) -> &Receiver<FsEvent> {
+        &self.receiver
+    }
+}
+
+impl Drop for FsWatcher {
+    fn drop(&mut self) {
+        if let Some(ref stopped) = self.stopped {
+            stopped.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Provides notifications of changes to nodes.
+pub trait WatchFileSystem: FileSystem {
+    /// Starts watching `path`, returning a watcher that receives every change to it from now on.
+    /// If `path` is a directory, changes to its children are included, along with those to
+    /// all of its descendants if `recursive` is `true`.
+    ///
+    /// `OsFileSystem` does not use the OS's notifications, but polls for changes on a thread of
+    /// its own every 50 milliseconds, rescanning the watched nodes each time.
+    /// Its events are therefore delayed and may coalesce several writes into one.
+    /// The thread stops once the watcher is dropped.
+    ///
+    /// # Errors
+    ///
+    /// * `path` does not exist.
+    /// * Current user has insufficient permissions.
+    fn watch<P: AsRef<Path>>(&self, path: P, recursive: bool) -> Result<FsWatcher>;
+}
+
+#[cfg(feature = "std-os")]
+impl WatchFileSystem for OsFileSystem {
+    fn watch<P: AsRef<Path>>(&self, path: P, recursive: bool) -> Result<FsWatcher> {
+        let path = path.as_ref();
+        let path = FileSystem::canonicalize(self, path).context("watch", &[path])?;
+        let mut nodes = scan(&path, recursive);
+        let (sender, receiver) = mpsc::channel();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let watcher = FsWatcher {
+            receiver,
+            stopped: Some(stopped.clone()),
+        };
+
+        thread::spawn(move || loop {
+            thread::sleep(POLL_INTERVAL);
+
+            if stopped.load(Ordering::Relaxed) {
+                return;
+            }
+
+            let next = scan(&path, recursive);
+
+            if !send_changes(&sender, &nodes, &next) {
+                return;
+            }
+
+            nodes = next;
+        });
+
+        Ok(watcher)
+    }
+}
+
+/// The parts of a node's metadata that are compared between polls.
+/// Directories only record their type and permissions, since changes to their children are
+/// reported separately.
+#[cfg(feature = "std-os")]
+#[derive(PartialEq)]
+struct State {
+    is_dir: bool,
+    len: u64,
+    modified: Option<SystemTime>,
+    permissions: fs::Permissions,
+}
+
+/// Returns the state of `path` and its watched descendants, keyed by path.
+/// Nodes that cannot be read, such as those removed mid-scan, are left out.
+#[cfg(feature = "std-os")]
+fn scan(path: &Path, recursive: bool) -> BTreeMap<PathBuf, State> {
+    let mut nodes = BTreeMap::new();
+    let mut pending = vec![(path.to_path_buf(), true)];
+
+    while let Some((path, expand)) = pending.pop() {
+        let metadata = match fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+
+        if expand && metadata.is_dir() {
+            if let Ok(entries) = fs::read_dir(&path) {
+                pending.extend(
+                    entries
+                        .filter_map(|e| e.ok())
+                        .map(|e| (e.path(), recursive)),
+                );
+            }
+        }
+
+        let is_dir = metadata.is_dir();
+
+        nodes.insert(
+            path,
+            State {
+                is_dir,
+                len: if is_dir { 0 } else { metadata.len() },
+                modified: metadata.modified().ok().filter(|_| !is_dir),
+                permissions: metadata.permissions(),
+            },
+        );
+    }
+
+    nodes
+}
+
+/// Sends an event for every difference between two scans, returning `false` once the receiver
+/// has been dropped.
+#[cfg(feature = "std-os")]
+fn send_changes(
+    sender: &Sender<FsEvent>,
+    before: &BTreeMap<PathBuf, State>,
+    after: &BTreeMap<PathBuf, State>,
+) -> bool {
+    let removed = before
+        .keys()
+        .filter(|path| !after.contains_key(*path))
+        .map(|path| FsEvent::Removed(path.clone()));
+    let changed = after
+        .iter()
+        .filter_map(|(path, state)| match before.get(path) {
+            None => Some(FsEvent::Created(path.clone())),
+            Some(old) if old != state => Some(FsEvent::Modified(path.clone())),
+            Some(_) => None,
+        });
+
+    removed
+        .chain(changed)
+        .all(|event| sender.send(event).is_ok())
+}
@@ -0,0 +1,673 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::io::{self, Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::vec::IntoIter;
+
+use zip::{DateTime, ZipArchive};
+
+use error::Context;
+use normalize::normalize;
+use recover::Recover;
+use walk::WalkSource;
+#[cfg(feature = "lock")]
+use LockableFile;
+use {FileSystem, FileType, OpenOptions, Result, WalkDir, WalkDirOptions};
+
+/// A read-only file system over a zip archive, which is available with the `zip` feature.
+///
+/// The archive's entries are listed when it is opened, and files are decompressed whenever they
+/// are read.
+/// Entries are found at their names within the archive below `/`, so `a/b` is at `/a/b`, and
+/// directories that are not in the archive themselves exist if any entry is inside of them.
+/// Paths are made absolute against a current directory, starting at `/`, and `.` and `..`
+/// components are resolved lexically.
+///
+/// Every operation that would change the archive fails with `ErrorKind::PermissionDenied`, and
+/// every node is [`readonly`].
+/// There is no available space, and the total space is the decompressed size of every file.
+/// Modification times are taken from the archive as UTC, and there are no creation or access
+/// times.
+/// Symlinks are read as files that contain their targets.
+///
+/// [`readonly`]: trait.FileSystem.html#method.readonly
+#[derive(Debug)]
+pub struct ZipFileSystem<R> {
+    archive: Arc<Mutex<ZipArchive<R>>>,
+    nodes: Arc<BTreeMap<PathBuf, Metadata>>,
+    cwd: Arc<Mutex<PathBuf>>,
+}
+
+impl<R> Clone for ZipFileSystem<R> {
+    fn clone(&self) -> Self {
+        ZipFileSystem {
+            archive: self.archive.clone(),
+            nodes: self.nodes.clone(),
+            cwd: self.cwd.clone(),
+        }
+    }
+}
+
+impl<R: Read + Seek> ZipFileSystem<R> {
+    /// Opens the zip archive that `reader` contains.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `ErrorKind::InvalidData` if `reader` does not contain a zip archive, or if the
+    /// archive has an entry whose name is not a relative path within it, or whose path is both a
+    /// file and a directory.
+    pub fn open(reader: R) -> Result<Self> {
+        let mut archive = ZipArchive::new(reader).map_err(io::Error::from)?;
+        let mut nodes = BTreeMap::new();
+
+        nodes.insert(PathBuf::from("/"), Metadata::implicit_dir());
+
+        for index in 0..archive.len() {
+            let entry = archive.by_index_raw(index).map_err(io::Error::from)?;
+            let name = entry.enclosed_name().ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("entry is outside of the archive: {}", entry.name()),
+                )
+            })?;
+            let path = Path::new("/").join(name);
+            let metadata = Metadata {
+                file_type: if entry.is_dir() {
+                    FileType::Dir
+                } else {
+                    FileType::File
+                },
+                index: Some(index),
+                len: if entry.is_dir() { 0 } else { entry.size() },
+                modified: entry.last_modified().and_then(system_time),
+            };
+
+            for ancestor in path.ancestors().skip(1) {
+                let parent = nodes
+                    .entry(ancestor.to_path_buf())
+                    .or_insert_with(Metadata::implicit_dir);
+
+                if parent.file_type != FileType::Dir {
+                    return Err(conflict(ancestor).into());
+                }
+            }
+
+            match nodes.get(&path) {
+                Some(existing) if existing.file_type != metadata.file_type => {
+                    return Err(conflict(&path).into())
+                }
+                // A later entry for the same path replaces the earlier one, like extracting would
+                _ => nodes.insert(path, metadata),
+            };
+        }
+
+        Ok(ZipFileSystem {
+            archive: Arc::new(Mutex::new(archive)),
+            nodes: Arc::new(nodes),
+            cwd: Arc::new(Mutex::new(PathBuf::from("/"))),
+        })
+    }
+
+    /// Returns the node at `path`.
+    fn node(&self, path: &Path) -> io::Result<&Metadata> {
+        self.nodes
+            .get(&normalize(&self.cwd.lock().recover(), path))
+            .ok_or_else(not_found)
+    }
+
+    /// Returns the decompressed contents of the file at `path`.
+    fn contents(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let node = self.node(path)?;
+        let index = match (node.file_type, node.index) {
+            (FileType::File, Some(index)) => index,
+            _ => return Err(io::Error::new(ErrorKind::IsADirectory, "is a directory")),
+        };
+        let mut archive = self.archive.lock().recover();
+        let mut entry = archive.by_index(index)?;
+        let mut contents = Vec::with_capacity(node.len as usize);
+
+        entry.read_to_end(&mut contents)?;
+
+        Ok(contents)
+    }
+}
+
+impl<R: Read + Seek> FileSystem for ZipFileSystem<R> {
+    type DirEntry = DirEntry;
+    type ReadDir = ReadDir;
+    type Metadata = Metadata;
+    type OpenFile = OpenFile;
+    type WalkDir = WalkDir;
+
+    fn current_dir(&self) -> Result<PathBuf> {
+        Ok(self.cwd.lock().recover().clone())
+    }
+
+    fn set_current_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let dir = normalize(&self.cwd.lock().recover(), path);
+
+        match self.node(&dir) {
+            Ok(node) if ::Metadata::is_dir(node) => {}
+            Ok(_) => {
+                return Err(io::Error::new(ErrorKind::NotADirectory, "not a directory"))
+                    .context("set_current_dir", &[path])
+            }
+            Err(err) => return Err(err).context("set_current_dir", &[path]),
+        }
+
+        *self.cwd.lock().recover() = dir;
+
+        Ok(())
+    }
+
+    fn metadata<P: AsRef<Path>>(&self, path: P) -> Result<Self::Metadata> {
+        let path = path.as_ref();
+
+        self.node(path).cloned().context("metadata", &[path])
+    }
+
+    fn canonicalize<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf> {
+        let path = path.as_ref();
+        let canonical = normalize(&self.cwd.lock().recover(), path);
+
+        self.node(&canonical).context("canonicalize", &[path])?;
+
+        Ok(canonical)
+    }
+
+    fn is_dir<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.node(path.as_ref())
+            .map(::Metadata::is_dir)
+            .unwrap_or(false)
+    }
+
+    fn is_file<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.node(path.as_ref())
+            .map(::Metadata::is_file)
+            .unwrap_or(false)
+    }
+
+    fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.node(path.as_ref()).is_ok()
+    }
+
+    fn try_exists<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        Ok(self.exists(path))
+    }
+
+    fn create_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        Err(read_only()).context("create_dir", &[path.as_ref()])
+    }
+
+    fn create_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        Err(read_only()).context("create_dir_all", &[path.as_ref()])
+    }
+
+    fn remove_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        Err(read_only()).context("remove_dir", &[path.as_ref()])
+    }
+
+    fn remove_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        Err(read_only()).context("remove_dir_all", &[path.as_ref()])
+    }
+
+    fn read_dir<P: AsRef<Path>>(&self, path: P) -> Result<Self::ReadDir> {
+        let path = path.as_ref();
+        let entries = ZipWalkSource::from(self)
+            .entries(path)
+            .context("read_dir", &[path])?
+            .map(|(file_name, metadata)| {
+                Ok(DirEntry {
+                    path: path.join(&file_name),
+                    file_name,
+                    metadata: metadata.clone(),
+                })
+            })
+            .collect();
+
+        Ok(ReadDir::new(entries))
+    }
+
+    fn walk_dir_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &WalkDirOptions,
+    ) -> Result<Self::WalkDir> {
+        let path = path.as_ref();
+
+        WalkDir::new(ZipWalkSource::from(self), path, options).context("walk_dir", &[path])
+    }
+
+    fn create_file<P, B>(&self, path: P, _buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        Err(read_only()).context("create_file", &[path.as_ref()])
+    }
+
+    fn write_file<P, B>(&self, path: P, _buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        Err(read_only()).context("write_file", &[path.as_ref()])
+    }
+
+    fn overwrite_file<P, B>(&self, path: P, _buf: B) -> Result<()>
+    where
+        P: AsRef<Path>,
+        B: AsRef<[u8]>,
+    {
+        Err(read_only()).context("overwrite_file", &[path.as_ref()])
+    }
+
+    fn set_len<P: AsRef<Path>>(&self, path: P, _size: u64) -> Result<()> {
+        Err(read_only()).context("set_len", &[path.as_ref()])
+    }
+
+    fn sync_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.node(path).map(|_| ()).context("sync_file", &[path])
+    }
+
+    fn sync_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.node(path).map(|_| ()).context("sync_dir", &[path])
+    }
+
+    fn read_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
+        let path = path.as_ref();
+
+        self.contents(path).context("read_file", &[path])
+    }
+
+    fn read_file_to_string<P: AsRef<Path>>(&self, path: P) -> Result<String> {
+        let path = path.as_ref();
+
+        self.contents(path)
+            .and_then(|contents| {
+                String::from_utf8(contents)
+                    .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))
+            })
+            .context("read_file_to_string", &[path])
+    }
+
+    fn read_file_into<P, B>(&self, path: P, mut buf: B) -> Result<usize>
+    where
+        P: AsRef<Path>,
+        B: AsMut<Vec<u8>>,
+    {
+        let path = path.as_ref();
+        let contents = self.contents(path).context("read_file_into", &[path])?;
+
+        buf.as_mut().extend_from_slice(&contents);
+
+        Ok(contents.len())
+    }
+
+    fn open<P: AsRef<Path>>(&self, path: P) -> Result<Self::OpenFile> {
+        self.open_with_options(path, OpenOptions::new().read(true))
+    }
+
+    fn open_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &OpenOptions,
+    ) -> Result<Self::OpenFile> {
+        let path = path.as_ref();
+
+        if options.is_write()
+            || options.is_append()
+            || options.is_truncate()
+            || options.is_create()
+            || options.is_create_new()
+        {
+            return Err(read_only()).context("open", &[path]);
+        }
+
+        if !options.is_read() {
+            return Err(io::Error::from(ErrorKind::InvalidInput)).context("open", &[path]);
+        }
+
+        self.contents(path)
+            .map(|contents| OpenFile(Cursor::new(contents)))
+            .context("open", &[path])
+    }
+
+    fn remove_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        Err(read_only()).context("remove_file", &[path.as_ref()])
+    }
+
+    fn copy_file<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        Err(read_only()).context("copy_file", &[from.as_ref(), to.as_ref()])
+    }
+
+    fn copy_dir<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        Err(read_only()).context("copy_dir", &[from.as_ref(), to.as_ref()])
+    }
+
+    fn rename<P, Q>(&self, from: P, to: Q) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        Err(read_only()).context("rename", &[from.as_ref(), to.as_ref()])
+    }
+
+    fn readonly<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+
+        self.node(path)
+            .map(::Metadata::readonly)
+            .context("readonly", &[path])
+    }
+
+    fn set_readonly<P: AsRef<Path>>(&self, path: P, _readonly: bool) -> Result<()> {
+        Err(read_only()).context("set_readonly", &[path.as_ref()])
+    }
+
+    fn modified<P: AsRef<Path>>(&self, path: P) -> Result<SystemTime> {
+        let path = path.as_ref();
+
+        self.node(path)
+            .and_then(::Metadata::modified)
+            .context("modified", &[path])
+    }
+
+    fn set_modified<P: AsRef<Path>>(&self, path: P, _time: SystemTime) -> Result<()> {
+        Err(read_only()).context("set_modified", &[path.as_ref()])
+    }
+
+    fn accessed<P: AsRef<Path>>(&self, path: P) -> Result<SystemTime> {
+        let path = path.as_ref();
+
+        self.node(path)
+            .and_then(::Metadata::accessed)
+            .context("accessed", &[path])
+    }
+
+    fn set_accessed<P: AsRef<Path>>(&self, path: P, _time: SystemTime) -> Result<()> {
+        Err(read_only()).context("set_accessed", &[path.as_ref()])
+    }
+
+    fn len<P: AsRef<Path>>(&self, path: P) -> u64 {
+        self.node(path.as_ref()).map(::Metadata::len).unwrap_or(0)
+    }
+
+    fn available_space<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+        let path = path.as_ref();
+
+        self.node(path)
+            .map(|_| 0)
+            .context("available_space", &[path])
+    }
+
+    fn total_space<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+        let path = path.as_ref();
+
+        self.node(path).context("total_space", &[path])?;
+
+        Ok(self.nodes.values().map(|node| node.len).sum())
+    }
+}
+
+/// The metadata of an entry in a [`ZipFileSystem`], or of a directory that entries are in.
+///
+/// [`ZipFileSystem`]: struct.ZipFileSystem.html
+#[derive(Clone, Debug)]
+pub struct Metadata {
+    file_type: FileType,
+    index: Option<usize>,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl Metadata {
+    fn implicit_dir() -> Self {
+        Metadata {
+            file_type: FileType::Dir,
+            index: None,
+            len: 0,
+            modified: None,
+        }
+    }
+}
+
+impl ::Metadata for Metadata {
+    fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn readonly(&self) -> bool {
+        true
+    }
+
+    fn created(&self) -> io::Result<SystemTime> {
+        Err(unsupported())
+    }
+
+    fn modified(&self) -> io::Result<SystemTime> {
+        self.modified.ok_or_else(unsupported)
+    }
+
+    fn accessed(&self) -> io::Result<SystemTime> {
+        Err(unsupported())
+    }
+}
+
+/// A file opened from a [`ZipFileSystem`], whose decompressed contents are kept in memory.
+///
+/// [`ZipFileSystem`]: struct.ZipFileSystem.html
+#[derive(Debug)]
+pub struct OpenFile(Cursor<Vec<u8>>);
+
+impl Read for OpenFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for OpenFile {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(read_only())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for OpenFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+impl ::OpenFile for OpenFile {
+    fn set_len(&self, _size: u64) -> io::Result<()> {
+        Err(read_only())
+    }
+
+    fn sync_all(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Nothing can change the archive, so every lock is granted
+#[cfg(feature = "lock")]
+impl LockableFile for OpenFile {
+    fn lock_shared(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn lock_exclusive(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn try_lock_shared(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn try_lock_exclusive(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn unlock(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// An entry of a directory in a [`ZipFileSystem`].
+///
+/// [`ZipFileSystem`]: struct.ZipFileSystem.html
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    path: PathBuf,
+    file_name: OsString,
+    metadata: Metadata,
+}
+
+impl ::DirEntry for DirEntry {
+    type Metadata = Metadata;
+
+    fn file_name(&self) -> OsString {
+        self.file_name.clone()
+    }
+
+    fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    fn metadata(&self) -> io::Result<Self::Metadata> {
+        Ok(self.metadata.clone())
+    }
+}
+
+/// The entries of a directory in a [`ZipFileSystem`], sorted by name.
+///
+/// [`ZipFileSystem`]: struct.ZipFileSystem.html
+#[derive(Debug)]
+pub struct ReadDir(IntoIter<io::Result<DirEntry>>);
+
+impl ReadDir {
+    fn new(entries: Vec<io::Result<DirEntry>>) -> Self {
+        ReadDir(entries.into_iter())
+    }
+}
+
+impl Iterator for ReadDir {
+    type Item = io::Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+impl ::ReadDir<DirEntry> for ReadDir {}
+
+/// The listing of a [`ZipFileSystem`], which does not need the archive itself.
+struct ZipWalkSource {
+    nodes: Arc<BTreeMap<PathBuf, Metadata>>,
+    cwd: Arc<Mutex<PathBuf>>,
+}
+
+impl<'a, R> From<&'a ZipFileSystem<R>> for ZipWalkSource {
+    fn from(fs: &'a ZipFileSystem<R>) -> Self {
+        ZipWalkSource {
+            nodes: fs.nodes.clone(),
+            cwd: fs.cwd.clone(),
+        }
+    }
+}
+
+impl ZipWalkSource {
+    /// Returns the names and metadata of the children of `dir`, sorted by name.
+    fn entries(&self, dir: &Path) -> io::Result<impl Iterator<Item = (OsString, &Metadata)>> {
+        let dir = normalize(&self.cwd.lock().recover(), dir);
+
+        match self.nodes.get(&dir) {
+            Some(node) if node.file_type == FileType::Dir => {}
+            Some(_) => return Err(io::Error::new(ErrorKind::NotADirectory, "not a directory")),
+            None => return Err(not_found()),
+        }
+
+        let depth = dir.components().count() + 1;
+
+        // Descendants of `dir` sort directly after it, with its children in order of their names
+        Ok(self
+            .nodes
+            .range(dir.clone()..)
+            .skip(1)
+            .take_while(move |&(path, _)| path.starts_with(&dir))
+            .filter(move |&(path, _)| path.components().count() == depth)
+            .filter_map(|(path, metadata)| Some((path.file_name()?.to_os_string(), metadata))))
+    }
+}
+
+impl WalkSource for ZipWalkSource {
+    fn list(&self, dir: &Path) -> io::Result<Vec<(PathBuf, FileType)>> {
+        Ok(self
+            .entries(dir)?
+            .map(|(file_name, metadata)| (dir.join(file_name), metadata.file_type))
+            .collect())
+    }
+
+    fn follow(&self, path: &Path) -> io::Result<(PathBuf, FileType)> {
+        let path = normalize(&self.cwd.lock().recover(), path);
+        let node = self.nodes.get(&path).ok_or_else(not_found)?;
+
+        Ok((path, node.file_type))
+    }
+}
+
+/// Converts a timestamp from a zip archive, which has no time zone, to a time in UTC.
+fn system_time(time: DateTime) -> Option<SystemTime> {
+    // Based on `days_from_civil` from http://howardhinnant.github.io/date_algorithms.html
+    let (month, day) = (u64::from(time.month()), u64::from(time.day()));
+    let year = u64::from(time.year()) - if month <= 2 { 1 } else { 0 };
+    let (era, year_of_era) = (year / 400, year % 400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = (era * 146_097 + day_of_era).checked_sub(719_468)?;
+    let seconds =
+        u64::from(time.hour()) * 3600 + u64::from(time.minute()) * 60 + u64::from(time.second());
+
+    UNIX_EPOCH.checked_add(Duration::from_secs(days * 86_400 + seconds))
+}
+
+fn conflict(path: &Path) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!("entry is both a file and a directory: {}", path.display()),
+    )
+}
+
+fn not_found() -> io::Error {
+    io::Error::new(ErrorKind::NotFound, "no such file or directory")
+}
+
+fn read_only() -> io::Error {
+    io::Error::new(ErrorKind::PermissionDenied, "read-only file system")
+}
+
+fn unsupported() -> io::Error {
+    io::Error::new(ErrorKind::Unsupported, "not supported by zip archives")
+}
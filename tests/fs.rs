@@ -1,11 +1,46 @@
 extern crate filesystem;
-
-use std::io::ErrorKind;
+#[cfg(feature = "include_dir")]
+extern crate include_dir;
+#[cfg(feature = "object_store")]
+extern crate object_store;
+#[cfg(feature = "proptest")]
+extern crate proptest;
+#[cfg(feature = "tar")]
+extern crate tar;
+#[cfg(any(feature = "async", feature = "object_store"))]
+extern crate tokio;
+#[cfg(feature = "zip")]
+extern crate zip;
+
+use std::io::{self, BufRead, ErrorKind, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
-
+use std::time::{Duration, UNIX_EPOCH};
+
+#[cfg(feature = "assertions")]
+use std::panic::{self, AssertUnwindSafe};
+
+#[cfg(feature = "async")]
+use std::future::Future;
+#[cfg(feature = "async")]
+use std::time::Instant;
+
+#[cfg(feature = "assertions")]
+use filesystem::assertions::FileSystemAssert;
+#[cfg(feature = "hash")]
+use filesystem::Algorithm;
+#[cfg(feature = "async")]
+use filesystem::AsyncFileSystem;
+#[cfg(feature = "lock")]
+use filesystem::LockableFile;
 #[cfg(unix)]
 use filesystem::UnixFileSystem;
-use filesystem::{DirEntry, FakeFileSystem, FileSystem, OsFileSystem, TempDir, TempFileSystem};
+#[cfg(windows)]
+use filesystem::WindowsFileSystem;
+use filesystem::{
+    DirEntry, DynFileSystem, FakeFileSystem, FileSystem, FileType, GlobOptions, Metadata, OpenFile,
+    OpenOptions, OsFileSystem, ReadDirOptions, SymlinkFileSystem, TempDir, TempFile,
+    TempFileSystem, TempOptions, WalkDirOptions,
+};
 
 macro_rules! make_test {
     ($test:ident, $fs:expr) => {
@@ -27,6 +62,21 @@ macro_rules! test_fs {
             make_test!(set_current_dir_fails_if_node_does_not_exists, $fs);
             make_test!(set_current_dir_fails_if_node_is_a_file, $fs);
 
+            make_test!(metadata_returns_file_metadata, $fs);
+            make_test!(metadata_returns_dir_metadata, $fs);
+            make_test!(metadata_modified_is_updated_by_write, $fs);
+            make_test!(metadata_fails_if_node_does_not_exist, $fs);
+
+            make_test!(canonicalize_removes_dot_components, $fs);
+            make_test!(canonicalize_resolves_symlinks, $fs);
+            make_test!(canonicalize_fails_if_node_does_not_exist, $fs);
+            make_test!(canonicalize_fails_if_parent_component_follows_file, $fs);
+
+            make_test!(paths_with_dot_components_are_resolved, $fs);
+            make_test!(paths_with_parent_dir_components_are_resolved, $fs);
+            make_test!(parent_dir_component_is_resolved_after_symlink, $fs);
+            make_test!(parent_dir_component_fails_if_dir_does_not_exist, $fs);
+
             make_test!(is_dir_returns_true_if_node_is_dir, $fs);
             make_test!(is_dir_returns_false_if_node_is_file, $fs);
             make_test!(is_dir_returns_false_if_node_does_not_exist, $fs);
@@ -35,6 +85,15 @@ macro_rules! test_fs {
             make_test!(is_file_returns_false_if_node_is_dir, $fs);
             make_test!(is_file_returns_false_if_node_does_not_exist, $fs);
 
+            make_test!(exists_returns_true_if_node_exists, $fs);
+            make_test!(exists_returns_false_if_node_does_not_exist, $fs);
+            make_test!(exists_returns_false_if_symlink_is_broken, $fs);
+            make_test!(try_exists_returns_true_if_node_exists, $fs);
+            make_test!(try_exists_returns_false_if_node_does_not_exist, $fs);
+            make_test!(try_exists_returns_false_if_symlink_is_broken, $fs);
+            #[cfg(unix)]
+            make_test!(try_exists_fails_if_parent_is_not_accessible, $fs);
+
             make_test!(create_dir_creates_new_dir, $fs);
             make_test!(create_dir_fails_if_dir_already_exists, $fs);
             make_test!(create_dir_fails_if_parent_does_not_exist, $fs);
@@ -64,9 +123,29 @@ macro_rules! test_fs {
             make_test!(remove_dir_all_fails_if_descendant_not_readable, $fs);
 
             make_test!(read_dir_returns_dir_entries, $fs);
+            make_test!(read_dir_entries_have_types_and_metadata, $fs);
+            make_test!(read_dir_sorted_returns_entries_by_name, $fs);
+            make_test!(read_dir_sorted_fails_if_node_does_not_exist, $fs);
+            make_test!(read_dir_with_options_filters_by_extension, $fs);
+            make_test!(read_dir_with_options_skips_hidden_entries, $fs);
+            make_test!(read_dir_with_options_fails_if_node_is_a_file, $fs);
             make_test!(read_dir_fails_if_node_does_not_exist, $fs);
             make_test!(read_dir_fails_if_node_is_a_file, $fs);
 
+            make_test!(walk_dir_returns_all_descendants, $fs);
+            make_test!(walk_dir_sorts_entries_by_file_name, $fs);
+            make_test!(walk_dir_respects_min_and_max_depth, $fs);
+            make_test!(walk_dir_does_not_follow_symlinks_by_default, $fs);
+            make_test!(walk_dir_follows_symlinks_if_enabled, $fs);
+            make_test!(walk_dir_fails_on_symlink_loop, $fs);
+            make_test!(walk_dir_fails_if_node_does_not_exist, $fs);
+
+            make_test!(glob_returns_matching_paths_in_dir, $fs);
+            make_test!(glob_returns_matching_paths_recursively, $fs);
+            make_test!(glob_with_matches_case_insensitively, $fs);
+            make_test!(glob_returns_nothing_if_base_does_not_exist, $fs);
+            make_test!(glob_fails_if_pattern_is_invalid, $fs);
+
             make_test!(write_file_writes_to_new_file, $fs);
             make_test!(write_file_overwrites_contents_of_existing_file, $fs);
             make_test!(write_file_fails_if_file_is_readonly, $fs);
@@ -77,6 +156,24 @@ macro_rules! test_fs {
             make_test!(overwrite_file_fails_if_file_is_readonly, $fs);
             make_test!(overwrite_file_fails_if_node_is_a_directory, $fs);
 
+            make_test!(set_len_truncates_file, $fs);
+            make_test!(set_len_extends_file_with_zeros, $fs);
+            make_test!(set_len_fails_if_node_does_not_exist, $fs);
+
+            make_test!(sync_file_succeeds_if_file_exists, $fs);
+            make_test!(sync_file_fails_if_node_does_not_exist, $fs);
+            make_test!(sync_dir_succeeds_if_dir_exists, $fs);
+            make_test!(sync_dir_fails_if_node_does_not_exist, $fs);
+
+            make_test!(write_from_reader_writes_to_new_file, $fs);
+            make_test!(write_from_reader_overwrites_contents_of_existing_file, $fs);
+            make_test!(write_from_reader_fails_if_node_is_a_directory, $fs);
+
+            make_test!(write_file_atomic_writes_to_new_file, $fs);
+            make_test!(write_file_atomic_replaces_contents_of_existing_file, $fs);
+            make_test!(write_file_atomic_fails_if_node_is_a_directory, $fs);
+            make_test!(write_file_atomic_fails_if_parent_does_not_exist, $fs);
+
             make_test!(read_file_returns_contents_as_bytes, $fs);
             make_test!(read_file_fails_if_file_does_not_exist, $fs);
 
@@ -90,6 +187,52 @@ macro_rules! test_fs {
             make_test!(create_file_writes_to_new_file, $fs);
             make_test!(create_file_fails_if_file_already_exists, $fs);
 
+            make_test!(open_reads_contents_of_file, $fs);
+            make_test!(open_fails_if_file_does_not_exist, $fs);
+            make_test!(open_fails_to_write_to_read_only_handle, $fs);
+
+            make_test!(open_buffered_reads_contents_of_file, $fs);
+            make_test!(read_lines_returns_each_line, $fs);
+            make_test!(read_lines_fails_if_file_does_not_exist, $fs);
+
+            #[cfg(feature = "hash")]
+            make_test!(hash_file_returns_digest_of_contents, $fs);
+            #[cfg(feature = "hash")]
+            make_test!(hash_file_reads_files_larger_than_a_chunk, $fs);
+            #[cfg(feature = "hash")]
+            make_test!(hash_file_fails_if_file_does_not_exist, $fs);
+
+            make_test!(open_with_options_creates_and_writes_to_file, $fs);
+            make_test!(open_with_options_seeks_and_overwrites_contents, $fs);
+            make_test!(open_with_options_appends_to_file, $fs);
+            make_test!(open_with_options_truncates_file, $fs);
+            make_test!(open_with_options_fails_if_create_new_and_file_exists, $fs);
+            make_test!(open_with_options_fails_if_file_does_not_exist, $fs);
+            make_test!(open_with_options_fails_if_options_are_invalid, $fs);
+            make_test!(open_with_options_fills_gap_after_end_with_zeros, $fs);
+            make_test!(open_with_sets_options_in_a_chain, $fs);
+            make_test!(open_with_read_only_fails_to_write, $fs);
+            make_test!(open_with_write_only_fails_to_read, $fs);
+            make_test!(open_with_truncate_and_create_new_creates_empty_file, $fs);
+            make_test!(open_with_create_new_fails_if_path_is_broken_symlink, $fs);
+            make_test!(open_with_create_follows_broken_symlink, $fs);
+
+            make_test!(open_file_set_len_truncates_file, $fs);
+            make_test!(open_file_set_len_extends_file_with_zeros, $fs);
+            make_test!(open_file_set_len_fails_if_not_open_for_writing, $fs);
+            make_test!(open_file_sync_all_succeeds, $fs);
+
+            #[cfg(feature = "lock")]
+            make_test!(try_lock_shared_fails_if_file_is_locked_exclusively, $fs);
+            #[cfg(feature = "lock")]
+            make_test!(try_lock_exclusive_fails_if_file_is_locked_shared, $fs);
+            #[cfg(feature = "lock")]
+            make_test!(try_lock_shared_succeeds_if_file_is_locked_shared, $fs);
+            #[cfg(feature = "lock")]
+            make_test!(unlock_releases_lock, $fs);
+            #[cfg(feature = "lock")]
+            make_test!(dropping_handle_releases_lock, $fs);
+
             make_test!(remove_file_removes_a_file, $fs);
             make_test!(remove_file_fails_if_file_does_not_exist, $fs);
             make_test!(remove_file_fails_if_node_is_a_directory, $fs);
@@ -101,17 +244,52 @@ macro_rules! test_fs {
             make_test!(copy_file_fails_if_original_node_is_directory, $fs);
             make_test!(copy_file_fails_if_destination_node_is_directory, $fs);
 
+            make_test!(copy_dir_copies_all_descendants, $fs);
+            make_test!(copy_dir_does_not_affect_original, $fs);
+            make_test!(copy_dir_copies_symlinks_as_symlinks, $fs);
+            #[cfg(unix)]
+            make_test!(copy_dir_preserves_modes, $fs);
+            make_test!(copy_dir_fails_if_original_does_not_exist, $fs);
+            make_test!(copy_dir_fails_if_original_is_a_file, $fs);
+            make_test!(copy_dir_fails_if_destination_exists, $fs);
+            make_test!(copy_dir_fails_if_destination_is_inside_original, $fs);
+
             make_test!(rename_renames_a_file, $fs);
             make_test!(rename_renames_a_directory, $fs);
             make_test!(rename_overwrites_destination_file, $fs);
             make_test!(rename_overwrites_empty_destination_directory, $fs);
             make_test!(rename_renames_all_descendants, $fs);
+            make_test!(rename_to_same_path_does_nothing, $fs);
             make_test!(rename_fails_if_original_path_does_not_exist, $fs);
             make_test!(
                 rename_fails_if_original_and_destination_are_different_types,
                 $fs
             );
             make_test!(rename_fails_if_destination_directory_is_not_empty, $fs);
+            make_test!(rename_fails_if_destination_is_inside_original, $fs);
+            make_test!(rename_keeps_destination_if_it_is_inside_original, $fs);
+            make_test!(rename_keeps_contents_and_times_of_descendants, $fs);
+
+            make_test!(symlink_file_creates_link_to_file, $fs);
+            make_test!(symlink_file_fails_if_link_already_exists, $fs);
+            make_test!(symlink_dir_creates_link_to_dir, $fs);
+            make_test!(symlink_to_missing_node_is_broken, $fs);
+            make_test!(symlink_with_relative_target_resolves_against_its_dir, $fs);
+            make_test!(symlink_with_relative_target_resolves_after_moving, $fs);
+            make_test!(
+                symlink_with_relative_target_resolves_through_symlinked_dir,
+                $fs
+            );
+            make_test!(read_file_fails_on_symlink_loop, $fs);
+
+            make_test!(read_link_returns_target_of_symlink, $fs);
+            make_test!(read_link_fails_if_node_is_not_a_symlink, $fs);
+            make_test!(read_link_fails_if_node_does_not_exist, $fs);
+
+            make_test!(symlink_metadata_does_not_follow_symlink, $fs);
+
+            make_test!(remove_file_removes_symlink_but_not_target, $fs);
+            make_test!(rename_renames_a_symlink, $fs);
 
             make_test!(readonly_returns_write_permission, $fs);
             make_test!(readonly_fails_if_node_does_not_exist, $fs);
@@ -120,10 +298,35 @@ macro_rules! test_fs {
             make_test!(set_readonly_toggles_write_permission_of_dir, $fs);
             make_test!(set_readonly_fails_if_node_does_not_exist, $fs);
 
+            #[cfg(unix)]
+            make_test!(is_hidden_checks_for_leading_dot, $fs);
+            #[cfg(unix)]
+            make_test!(is_hidden_does_not_follow_symlink, $fs);
+            make_test!(is_hidden_fails_if_node_does_not_exist, $fs);
+
+            make_test!(modified_is_updated_by_write, $fs);
+            make_test!(modified_fails_if_node_does_not_exist, $fs);
+
+            make_test!(set_modified_sets_modification_time, $fs);
+            make_test!(set_modified_sets_modification_time_of_dir, $fs);
+            make_test!(set_modified_fails_if_node_does_not_exist, $fs);
+
+            make_test!(set_accessed_sets_access_time, $fs);
+            make_test!(set_accessed_fails_if_node_does_not_exist, $fs);
+
             make_test!(len_returns_size_of_file, $fs);
             make_test!(len_returns_size_of_directory, $fs);
             make_test!(len_returns_0_if_node_does_not_exist, $fs);
 
+            make_test!(dir_size_adds_up_lengths_of_descendant_files, $fs);
+            make_test!(dir_size_follows_symlinks_if_enabled, $fs);
+            make_test!(dir_size_fails_on_symlink_loop_if_following, $fs);
+            make_test!(dir_size_fails_if_node_is_a_file, $fs);
+            make_test!(dir_size_fails_if_node_does_not_exist, $fs);
+
+            make_test!(available_space_is_at_most_total_space, $fs);
+            make_test!(available_space_fails_if_node_does_not_exist, $fs);
+
             #[cfg(unix)]
             make_test!(mode_returns_permissions, $fs);
             #[cfg(unix)]
@@ -134,15 +337,103 @@ macro_rules! test_fs {
             #[cfg(unix)]
             make_test!(set_mode_fails_if_node_does_not_exist, $fs);
 
+            #[cfg(unix)]
+            make_test!(owner_returns_owner_of_new_node, $fs);
+            #[cfg(unix)]
+            make_test!(owner_fails_if_node_does_not_exist, $fs);
+
+            #[cfg(unix)]
+            make_test!(set_owner_sets_owner, $fs);
+            #[cfg(unix)]
+            make_test!(set_owner_fails_if_node_does_not_exist, $fs);
+
+            #[cfg(unix)]
+            make_test!(create_file_with_mode_sets_mode, $fs);
+            #[cfg(unix)]
+            make_test!(create_file_with_mode_fails_if_file_exists, $fs);
+            #[cfg(unix)]
+            make_test!(create_dir_with_mode_sets_mode, $fs);
+            #[cfg(unix)]
+            make_test!(create_dir_with_mode_fails_if_parent_does_not_exist, $fs);
+
+            #[cfg(unix)]
+            make_test!(mkfifo_creates_fifo, $fs);
+            #[cfg(unix)]
+            make_test!(mkfifo_fails_if_node_exists, $fs);
+            #[cfg(unix)]
+            make_test!(mknod_fails_if_type_is_not_special, $fs);
+            #[cfg(unix)]
+            make_test!(copy_dir_recreates_fifos, $fs);
+
+            #[cfg(windows)]
+            make_test!(attributes_returns_set_attributes, $fs);
+            #[cfg(windows)]
+            make_test!(attributes_include_directory_and_readonly, $fs);
+            #[cfg(windows)]
+            make_test!(attributes_fails_if_node_does_not_exist, $fs);
+
+            #[cfg(windows)]
+            make_test!(create_junction_links_to_dir, $fs);
+            #[cfg(windows)]
+            make_test!(create_junction_fails_if_node_already_exists, $fs);
+
             make_test!(temp_dir_creates_tempdir, $fs);
             make_test!(temp_dir_creates_unique_dir, $fs);
+            make_test!(temp_dir_into_path_keeps_dir, $fs);
+
+            make_test!(temp_file_creates_empty_file, $fs);
+            make_test!(temp_file_creates_unique_file, $fs);
+
+            make_test!(temp_dir_in_creates_dir_inside_base, $fs);
+            make_test!(temp_dir_in_fails_if_base_does_not_exist, $fs);
+            make_test!(temp_file_with_options_uses_prefix_and_suffix, $fs);
+            #[cfg(unix)]
+            make_test!(temp_dir_with_options_sets_mode, $fs);
+
+            make_test!(errors_include_operation_and_paths, $fs);
+            make_test!(errors_convert_into_io_errors, $fs);
+
+            make_test!(dyn_file_system_delegates_to_inner_file_system, $fs);
+            make_test!(dyn_file_system_read_dir_returns_dir_entries, $fs);
+
+            #[cfg(feature = "async")]
+            make_test!(async_file_system_writes_and_reads_files, $fs);
+            #[cfg(feature = "async")]
+            make_test!(async_file_system_creates_and_reads_dirs, $fs);
+            #[cfg(feature = "async")]
+            make_test!(async_file_system_fails_if_file_does_not_exist, $fs);
+
+            #[cfg(feature = "assertions")]
+            make_test!(assert_passes_if_nodes_match, $fs);
+            #[cfg(all(feature = "assertions", unix))]
+            make_test!(assert_has_mode_passes_if_mode_matches, $fs);
+            #[cfg(feature = "assertions")]
+            make_test!(assert_has_content_fails_if_content_differs, $fs);
+            #[cfg(feature = "assertions")]
+            make_test!(assert_exists_fails_if_node_does_not_exist, $fs);
+            #[cfg(feature = "assertions")]
+            make_test!(assert_is_dir_fails_if_node_is_a_file, $fs);
+            #[cfg(feature = "assertions")]
+            make_test!(assert_is_empty_dir_fails_if_dir_has_entries, $fs);
+            #[cfg(all(feature = "assertions", unix))]
+            make_test!(assert_has_mode_fails_if_mode_differs, $fs);
         }
     };
 }
 
 test_fs!(os, OsFileSystem::new);
+test_fs!(os_own_current_dir, os_fs_with_own_current_dir);
+test_fs!(os_rooted, rooted_os_fs);
 test_fs!(fake, FakeFileSystem::new);
 
+fn os_fs_with_own_current_dir() -> OsFileSystem {
+    OsFileSystem::with_own_current_dir().unwrap()
+}
+
+fn rooted_os_fs() -> OsFileSystem {
+    OsFileSystem::rooted_in_temp_dir("rooted").unwrap()
+}
+
 fn set_current_dir_fails_if_node_does_not_exists<T: FileSystem>(fs: &T, parent: &Path) {
     let path = parent.join("does_not_exist");
 
@@ -160,7 +451,180 @@ fn set_current_dir_fails_if_node_is_a_file<T: FileSystem>(fs: &T, parent: &Path)
     let result = fs.set_current_dir(path);
 
     assert!(result.is_err());
-    assert_eq!(result.unwrap_err().kind(), ErrorKind::Other);
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotADirectory);
+}
+
+fn metadata_returns_file_metadata<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    fs.create_file(&path, "contents").unwrap();
+
+    let result = fs.metadata(&path);
+
+    assert!(result.is_ok());
+
+    let metadata = result.unwrap();
+
+    assert_eq!(metadata.file_type(), FileType::File);
+    assert!(metadata.is_file());
+    assert!(!metadata.is_dir());
+    assert_eq!(metadata.len(), 8);
+    assert!(!metadata.readonly());
+    assert!(metadata.modified().is_ok());
+    assert!(metadata.accessed().is_ok());
+}
+
+fn metadata_returns_dir_metadata<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("dir");
+
+    fs.create_dir(&path).unwrap();
+
+    let result = fs.metadata(&path);
+
+    assert!(result.is_ok());
+
+    let metadata = result.unwrap();
+
+    assert_eq!(metadata.file_type(), FileType::Dir);
+    assert!(metadata.is_dir());
+    assert!(!metadata.is_file());
+}
+
+fn metadata_modified_is_updated_by_write<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    fs.create_file(&path, "").unwrap();
+
+    let before = fs.metadata(&path).unwrap().modified().unwrap();
+
+    fs.write_file(&path, "new contents").unwrap();
+
+    let after = fs.metadata(&path).unwrap().modified().unwrap();
+
+    assert!(after >= before);
+}
+
+fn metadata_fails_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let result = fs.metadata(parent.join("does_not_exist"));
+
+    match result {
+        Ok(_) => panic!("should be an err"),
+        Err(err) => assert_eq!(err.kind(), ErrorKind::NotFound),
+    }
+}
+
+fn canonicalize_removes_dot_components<T: FileSystem>(fs: &T, parent: &Path) {
+    let parent = fs.canonicalize(parent).unwrap();
+    let path = parent.join("dir").join("file");
+
+    fs.create_dir(parent.join("dir")).unwrap();
+    fs.create_file(&path, "").unwrap();
+
+    let result = fs.canonicalize(parent.join("dir/./../dir/./file"));
+
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap(), path);
+}
+
+fn canonicalize_resolves_symlinks<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let parent = fs.canonicalize(parent).unwrap();
+    let original = parent.join("original");
+    let link = parent.join("link");
+
+    fs.create_dir(&original).unwrap();
+    fs.create_file(original.join("file"), "").unwrap();
+    fs.symlink_dir(&original, &link).unwrap();
+
+    let result = fs.canonicalize(link.join("file"));
+
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap(), original.join("file"));
+}
+
+fn canonicalize_fails_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let result = fs.canonicalize(parent.join("does_not_exist"));
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+fn canonicalize_fails_if_parent_component_follows_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    fs.create_file(&path, "").unwrap();
+
+    let result = fs.canonicalize(path.join(".."));
+
+    assert!(result.is_err());
+}
+
+fn paths_with_dot_components_are_resolved<T: FileSystem>(fs: &T, parent: &Path) {
+    let dir = parent.join("dir");
+
+    fs.create_dir(parent.join("./dir")).unwrap();
+    fs.create_file(dir.join("./file"), "contents").unwrap();
+
+    assert!(fs.is_dir(&dir));
+    assert_eq!(
+        fs.read_file_to_string(dir.join("file")).unwrap(),
+        "contents"
+    );
+    assert_eq!(
+        fs.read_file_to_string(parent.join("./dir/./file")).unwrap(),
+        "contents"
+    );
+}
+
+fn paths_with_parent_dir_components_are_resolved<T: FileSystem>(fs: &T, parent: &Path) {
+    let dir = parent.join("dir");
+
+    fs.create_dir_all(parent.join("dir/sub/../other")).unwrap();
+
+    assert!(fs.is_dir(dir.join("sub")));
+    assert!(fs.is_dir(dir.join("other")));
+
+    fs.write_file(dir.join("sub/../file"), "contents").unwrap();
+
+    assert_eq!(
+        fs.read_file_to_string(dir.join("file")).unwrap(),
+        "contents"
+    );
+
+    fs.rename(dir.join("other/../file"), dir.join("sub/../renamed"))
+        .unwrap();
+
+    assert!(!fs.is_file(dir.join("file")));
+    assert!(fs.is_file(dir.join("renamed")));
+
+    fs.remove_file(dir.join("sub/../renamed")).unwrap();
+    fs.remove_dir(dir.join("sub/../other")).unwrap();
+
+    assert!(!fs.is_file(dir.join("renamed")));
+    assert!(!fs.is_dir(dir.join("other")));
+}
+
+fn parent_dir_component_is_resolved_after_symlink<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let original = parent.join("original");
+    let link = parent.join("link");
+
+    fs.create_dir_all(original.join("sub")).unwrap();
+    fs.create_file(original.join("file"), "contents").unwrap();
+    fs.symlink_dir(original.join("sub"), &link).unwrap();
+
+    // Like the OS, `..` refers to the parent of the symlink's target, not of the symlink
+    let result = fs.read_file_to_string(link.join("../file"));
+
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap(), "contents");
+}
+
+fn parent_dir_component_fails_if_dir_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    fs.create_file(parent.join("file"), "").unwrap();
+
+    let result = fs.read_file(parent.join("does_not_exist/../file"));
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
 }
 
 fn is_dir_returns_true_if_node_is_dir<T: FileSystem>(fs: &T, parent: &Path) {
@@ -203,6 +667,80 @@ fn is_file_returns_false_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &
     assert!(!fs.is_file(parent.join("does_not_exist")));
 }
 
+fn exists_returns_true_if_node_exists<T: FileSystem>(fs: &T, parent: &Path) {
+    let file = parent.join("file");
+    let dir = parent.join("dir");
+
+    fs.create_file(&file, "").unwrap();
+    fs.create_dir(&dir).unwrap();
+
+    assert!(fs.exists(&file));
+    assert!(fs.exists(&dir));
+}
+
+fn exists_returns_false_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    assert!(!fs.exists(parent.join("does_not_exist")));
+}
+
+fn exists_returns_false_if_symlink_is_broken<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let link = parent.join("link");
+
+    fs.symlink_file(parent.join("does_not_exist"), &link)
+        .unwrap();
+
+    assert!(!fs.exists(&link));
+}
+
+fn try_exists_returns_true_if_node_exists<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    fs.create_file(&path, "").unwrap();
+
+    let result = fs.try_exists(&path);
+
+    assert!(result.is_ok());
+    assert!(result.unwrap());
+}
+
+fn try_exists_returns_false_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let result = fs.try_exists(parent.join("does_not_exist"));
+
+    assert!(result.is_ok());
+    assert!(!result.unwrap());
+}
+
+fn try_exists_returns_false_if_symlink_is_broken<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let link = parent.join("link");
+
+    fs.symlink_file(parent.join("does_not_exist"), &link)
+        .unwrap();
+
+    let result = fs.try_exists(&link);
+
+    assert!(result.is_ok());
+    assert!(!result.unwrap());
+}
+
+#[cfg(unix)]
+fn try_exists_fails_if_parent_is_not_accessible<T: FileSystem + UnixFileSystem>(
+    fs: &T,
+    parent: &Path,
+) {
+    let dir = parent.join("dir");
+    let path = dir.join("file");
+
+    fs.create_dir(&dir).unwrap();
+    fs.create_file(&path, "").unwrap();
+    fs.set_mode(&dir, 0o000).unwrap();
+
+    let result = fs.try_exists(&path);
+
+    fs.set_mode(&dir, 0o755).unwrap();
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+}
+
 fn create_dir_creates_new_dir<T: FileSystem>(fs: &T, parent: &Path) {
     let path = parent.join("new_dir");
 
@@ -290,7 +828,7 @@ fn remove_dir_fails_if_node_is_a_file<T: FileSystem>(fs: &T, parent: &Path) {
     let result = fs.remove_dir(&path);
 
     assert!(result.is_err());
-    assert_eq!(result.unwrap_err().kind(), ErrorKind::Other);
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotADirectory);
     assert!(fs.is_file(&path));
 }
 
@@ -304,7 +842,7 @@ fn remove_dir_fails_if_dir_is_not_empty<T: FileSystem>(fs: &T, parent: &Path) {
     let result = fs.remove_dir(&path);
 
     assert!(result.is_err());
-    assert_eq!(result.unwrap_err().kind(), ErrorKind::Other);
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::DirectoryNotEmpty);
     assert!(fs.is_dir(&path));
     assert!(fs.is_file(&child));
 }
@@ -332,7 +870,7 @@ fn remove_dir_all_fails_if_node_is_a_file<T: FileSystem>(fs: &T, parent: &Path)
     let result = fs.remove_dir_all(&path);
 
     assert!(result.is_err());
-    assert_eq!(result.unwrap_err().kind(), ErrorKind::Other);
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotADirectory);
     assert!(fs.is_file(&path));
 }
 
@@ -435,78 +973,448 @@ fn read_dir_returns_dir_entries<T: FileSystem>(fs: &T, parent: &Path) {
     assert_eq!(&entries, expected_paths);
 }
 
-fn read_dir_fails_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
-    let path = parent.join("does_not_exist");
-    let result = fs.read_dir(&path);
+fn read_dir_entries_have_types_and_metadata<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let file = parent.join("file");
 
-    assert!(result.is_err());
+    fs.create_file(&file, "contents").unwrap();
+    fs.create_dir(parent.join("dir")).unwrap();
+    fs.symlink_file(&file, parent.join("link")).unwrap();
 
-    match result {
-        Ok(_) => panic!("should be an err"),
-        Err(err) => assert_eq!(err.kind(), ErrorKind::NotFound),
-    }
-}
+    let mut entries: Vec<_> = fs
+        .read_dir(parent)
+        .unwrap()
+        .map(|e| {
+            let e = e.unwrap();
+            let metadata = e.metadata().unwrap();
 
-fn read_dir_fails_if_node_is_a_file<T: FileSystem>(fs: &T, parent: &Path) {
-    let path = parent.join("file");
+            (e.file_name(), e.file_type().unwrap(), metadata.file_type())
+        })
+        .collect();
 
-    fs.create_file(&path, "").unwrap();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
 
-    let result = fs.read_dir(&path);
+    assert_eq!(
+        entries,
+        vec![
+            ("dir".into(), FileType::Dir, FileType::Dir),
+            ("file".into(), FileType::File, FileType::File),
+            ("link".into(), FileType::Symlink, FileType::Symlink),
+        ]
+    );
 
-    assert!(result.is_err());
-    match result {
-        Ok(_) => panic!("should be an err"),
-        Err(err) => assert_eq!(err.kind(), ErrorKind::Other),
-    }
+    let entry = fs
+        .read_dir(parent)
+        .unwrap()
+        .map(Result::unwrap)
+        .find(|e| e.file_name() == "file")
+        .unwrap();
+
+    assert_eq!(entry.metadata().unwrap().len(), 8);
 }
 
-fn write_file_writes_to_new_file<T: FileSystem>(fs: &T, parent: &Path) {
-    let path = parent.join("new_file");
-    let result = fs.write_file(&path, "new contents");
+fn read_dir_sorted_returns_entries_by_name<T: FileSystem>(fs: &T, parent: &Path) {
+    for name in &["b", "d", "a", "c"] {
+        fs.create_file(parent.join(name), "").unwrap();
+    }
+
+    fs.create_dir(parent.join("b.dir")).unwrap();
+
+    let result = fs.read_dir_sorted(parent);
 
     assert!(result.is_ok());
 
-    let contents = String::from_utf8(fs.read_file(path).unwrap()).unwrap();
+    let entries: Vec<PathBuf> = result.unwrap().iter().map(DirEntry::path).collect();
 
-    assert_eq!(&contents, "new contents");
+    assert_eq!(
+        entries,
+        vec![
+            parent.join("a"),
+            parent.join("b"),
+            parent.join("b.dir"),
+            parent.join("c"),
+            parent.join("d"),
+        ]
+    );
 }
 
-fn write_file_overwrites_contents_of_existing_file<T: FileSystem>(fs: &T, parent: &Path) {
-    let path = parent.join("test_file");
-
-    fs.write_file(&path, "old contents").unwrap();
+fn read_dir_with_options_filters_by_extension<T: FileSystem>(fs: &T, parent: &Path) {
+    for name in &["b.toml", "a.rs", "c.txt", "rs", "d.tar.rs"] {
+        fs.create_file(parent.join(name), "").unwrap();
+    }
 
-    let result = fs.write_file(&path, "new contents");
+    fs.create_dir(parent.join("e.rs")).unwrap();
 
-    assert!(result.is_ok());
+    let result = fs.read_dir_with_options(
+        parent,
+        ReadDirOptions::new()
+            .extensions(["rs", "toml"])
+            .sort_by_file_name(true),
+    );
 
-    let contents = String::from_utf8(fs.read_file(path).unwrap()).unwrap();
+    let entries: Vec<PathBuf> = result.unwrap().iter().map(DirEntry::path).collect();
 
-    assert_eq!(&contents, "new contents");
+    assert_eq!(
+        entries,
+        vec![
+            parent.join("a.rs"),
+            parent.join("b.toml"),
+            parent.join("d.tar.rs"),
+            parent.join("e.rs"),
+        ]
+    );
 }
 
-fn write_file_fails_if_file_is_readonly<T: FileSystem>(fs: &T, parent: &Path) {
-    let path = parent.join("test_file");
+fn read_dir_with_options_skips_hidden_entries<T: FileSystem>(fs: &T, parent: &Path) {
+    fs.create_file(parent.join(".hidden"), "").unwrap();
+    fs.create_dir(parent.join(".git")).unwrap();
+    fs.create_file(parent.join("visible"), "").unwrap();
 
-    fs.create_file(&path, "").unwrap();
-    fs.set_readonly(&path, true).unwrap();
+    let all = fs.read_dir_with_options(parent, &ReadDirOptions::new());
+    let visible = fs.read_dir_with_options(parent, ReadDirOptions::new().skip_hidden(true));
 
-    let result = fs.write_file(&path, "test contents");
+    assert_eq!(all.unwrap().len(), 3);
 
-    assert!(result.is_err());
-    assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+    let entries: Vec<PathBuf> = visible.unwrap().iter().map(DirEntry::path).collect();
+
+    assert_eq!(entries, vec![parent.join("visible")]);
 }
 
-fn write_file_fails_if_node_is_a_directory<T: FileSystem>(fs: &T, parent: &Path) {
-    let path = parent.join("test_dir");
+fn read_dir_with_options_fails_if_node_is_a_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    fs.create_file(&path, "").unwrap();
+
+    let result = fs.read_dir_with_options(&path, &ReadDirOptions::new());
+
+    match result {
+        Ok(_) => panic!("should be an err"),
+        Err(err) => assert_eq!(err.kind(), ErrorKind::NotADirectory),
+    }
+}
+
+fn read_dir_sorted_fails_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let result = fs.read_dir_sorted(parent.join("does_not_exist"));
+
+    match result {
+        Ok(_) => panic!("should be an err"),
+        Err(err) => assert_eq!(err.kind(), ErrorKind::NotFound),
+    }
+}
+
+fn read_dir_fails_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("does_not_exist");
+    let result = fs.read_dir(&path);
+
+    assert!(result.is_err());
+
+    match result {
+        Ok(_) => panic!("should be an err"),
+        Err(err) => assert_eq!(err.kind(), ErrorKind::NotFound),
+    }
+}
+
+fn read_dir_fails_if_node_is_a_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    fs.create_file(&path, "").unwrap();
+
+    let result = fs.read_dir(&path);
+
+    assert!(result.is_err());
+    match result {
+        Ok(_) => panic!("should be an err"),
+        Err(err) => assert_eq!(err.kind(), ErrorKind::NotADirectory),
+    }
+}
+
+fn walk_dir_returns_all_descendants<T: FileSystem>(fs: &T, parent: &Path) {
+    let dir1 = parent.join("dir1");
+    let dir2 = dir1.join("dir2");
+    let file1 = parent.join("file1");
+    let file2 = dir1.join("file2");
+    let file3 = dir2.join("file3");
+
+    fs.create_dir_all(&dir2).unwrap();
+    fs.create_file(&file1, "").unwrap();
+    fs.create_file(&file2, "").unwrap();
+    fs.create_file(&file3, "").unwrap();
+
+    let result = fs.walk_dir(parent);
+
+    assert!(result.is_ok());
+
+    let mut entries: Vec<(PathBuf, usize)> = result
+        .unwrap()
+        .map(|e| e.unwrap())
+        .map(|e| (e.path(), e.depth()))
+        .collect();
+    let expected = &mut [
+        (parent.to_path_buf(), 0),
+        (dir1.clone(), 1),
+        (file1, 1),
+        (dir2.clone(), 2),
+        (file2, 2),
+        (file3, 3),
+    ];
+
+    entries.sort();
+    expected.sort();
+
+    assert_eq!(&entries, expected);
+}
+
+fn walk_dir_sorts_entries_by_file_name<T: FileSystem>(fs: &T, parent: &Path) {
+    let a = parent.join("a");
+    let b = parent.join("b");
+    let c = parent.join("c");
+
+    fs.create_file(&c, "").unwrap();
+    fs.create_dir(&a).unwrap();
+    fs.create_file(a.join("z"), "").unwrap();
+    fs.create_file(&b, "").unwrap();
+
+    let entries: Vec<PathBuf> = fs
+        .walk_dir_with_options(parent, WalkDirOptions::new().sort_by_file_name(true))
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .collect();
+
+    assert_eq!(
+        entries,
+        vec![parent.to_path_buf(), a.clone(), a.join("z"), b, c]
+    );
+}
+
+fn walk_dir_respects_min_and_max_depth<T: FileSystem>(fs: &T, parent: &Path) {
+    let dir1 = parent.join("dir1");
+    let dir2 = dir1.join("dir2");
+
+    fs.create_dir_all(&dir2).unwrap();
+    fs.create_file(dir2.join("file"), "").unwrap();
+
+    let entries: Vec<PathBuf> = fs
+        .walk_dir_with_options(parent, WalkDirOptions::new().min_depth(1).max_depth(2))
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .collect();
+
+    assert_eq!(entries, vec![dir1, dir2]);
+}
+
+fn walk_dir_does_not_follow_symlinks_by_default<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let original = parent.join("original");
+    let link = parent.join("link");
+
+    fs.create_dir(&original).unwrap();
+    fs.create_file(original.join("file"), "").unwrap();
+    fs.symlink_dir(&original, &link).unwrap();
+
+    let entries: Vec<(PathBuf, FileType)> = fs
+        .walk_dir_with_options(&link, WalkDirOptions::new().min_depth(1))
+        .unwrap()
+        .map(|e| e.unwrap())
+        .map(|e| (e.path(), e.file_type()))
+        .collect();
+
+    assert_eq!(entries, vec![(link.join("file"), FileType::File)]);
+
+    let entry = fs
+        .walk_dir(parent)
+        .unwrap()
+        .map(|e| e.unwrap())
+        .find(|e| e.path() == link)
+        .unwrap();
+
+    assert_eq!(entry.file_type(), FileType::Symlink);
+    assert!(fs
+        .walk_dir(parent)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .all(|path| path == link || !path.starts_with(&link)));
+}
+
+fn walk_dir_follows_symlinks_if_enabled<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let original = parent.join("original");
+    let link = parent.join("link");
+
+    fs.create_dir(&original).unwrap();
+    fs.create_file(original.join("file"), "").unwrap();
+    fs.symlink_dir(&original, &link).unwrap();
+
+    let entries: Vec<(PathBuf, FileType)> = fs
+        .walk_dir_with_options(
+            parent,
+            WalkDirOptions::new()
+                .min_depth(1)
+                .follow_links(true)
+                .sort_by_file_name(true),
+        )
+        .unwrap()
+        .map(|e| e.unwrap())
+        .map(|e| (e.path(), e.file_type()))
+        .collect();
+
+    assert_eq!(
+        entries,
+        vec![
+            (link.clone(), FileType::Dir),
+            (link.join("file"), FileType::File),
+            (original.clone(), FileType::Dir),
+            (original.join("file"), FileType::File),
+        ]
+    );
+}
+
+fn walk_dir_fails_on_symlink_loop<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let dir = parent.join("dir");
+    let link = dir.join("link");
+
+    fs.create_dir(&dir).unwrap();
+    fs.symlink_dir(&dir, &link).unwrap();
+
+    let results: Vec<_> = fs
+        .walk_dir_with_options(&dir, WalkDirOptions::new().follow_links(true))
+        .unwrap()
+        .collect();
+
+    assert_eq!(results.len(), 2);
+    assert!(results[0].is_ok());
+    assert!(filesystem::is_filesystem_loop(
+        results[1].as_ref().unwrap_err()
+    ));
+}
+
+fn walk_dir_fails_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("does_not_exist");
+    let result = fs.walk_dir(&path);
+
+    match result {
+        Ok(_) => panic!("should be an err"),
+        Err(err) => assert_eq!(err.kind(), ErrorKind::NotFound),
+    }
+}
+
+fn glob_returns_matching_paths_in_dir<T: FileSystem>(fs: &T, parent: &Path) {
+    let dir = parent.join("dir");
+
+    fs.create_dir(&dir).unwrap();
+    fs.create_file(parent.join("a.toml"), "").unwrap();
+    fs.create_file(parent.join("b.toml"), "").unwrap();
+    fs.create_file(parent.join("c.rs"), "").unwrap();
+    fs.create_file(dir.join("d.toml"), "").unwrap();
+
+    let pattern = parent.join("*.toml");
+    let result = fs.glob(pattern.to_str().unwrap());
+
+    assert!(result.is_ok());
+    assert_eq!(
+        result.unwrap(),
+        vec![parent.join("a.toml"), parent.join("b.toml")]
+    );
+}
+
+fn glob_returns_matching_paths_recursively<T: FileSystem>(fs: &T, parent: &Path) {
+    let dir = parent.join("dir");
+    let nested = dir.join("nested");
+
+    fs.create_dir_all(&nested).unwrap();
+    fs.create_file(parent.join("a.toml"), "").unwrap();
+    fs.create_file(dir.join("b.toml"), "").unwrap();
+    fs.create_file(nested.join("c.toml"), "").unwrap();
+    fs.create_file(nested.join("d.rs"), "").unwrap();
+
+    let pattern = parent.join("**").join("*.toml");
+    let result = fs.glob(pattern.to_str().unwrap());
+
+    assert!(result.is_ok());
+    assert_eq!(
+        result.unwrap(),
+        vec![
+            parent.join("a.toml"),
+            dir.join("b.toml"),
+            nested.join("c.toml"),
+        ]
+    );
+}
+
+fn glob_with_matches_case_insensitively<T: FileSystem>(fs: &T, parent: &Path) {
+    fs.create_file(parent.join("README.md"), "").unwrap();
+
+    let pattern = parent.join("readme.*");
+
+    assert!(fs.glob(pattern.to_str().unwrap()).unwrap().is_empty());
+
+    let result = fs.glob_with(
+        pattern.to_str().unwrap(),
+        GlobOptions::new().case_sensitive(false),
+    );
+
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap(), vec![parent.join("README.md")]);
+}
+
+fn glob_returns_nothing_if_base_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let pattern = parent.join("does_not_exist").join("*");
+    let result = fs.glob(pattern.to_str().unwrap());
+
+    assert!(result.is_ok());
+    assert!(result.unwrap().is_empty());
+}
+
+fn glob_fails_if_pattern_is_invalid<T: FileSystem>(fs: &T, parent: &Path) {
+    let pattern = parent.join("[");
+    let result = fs.glob(pattern.to_str().unwrap());
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+}
+
+fn write_file_writes_to_new_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("new_file");
+    let result = fs.write_file(&path, "new contents");
+
+    assert!(result.is_ok());
+
+    let contents = String::from_utf8(fs.read_file(path).unwrap()).unwrap();
+
+    assert_eq!(&contents, "new contents");
+}
+
+fn write_file_overwrites_contents_of_existing_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("test_file");
+
+    fs.write_file(&path, "old contents").unwrap();
+
+    let result = fs.write_file(&path, "new contents");
+
+    assert!(result.is_ok());
+
+    let contents = String::from_utf8(fs.read_file(path).unwrap()).unwrap();
+
+    assert_eq!(&contents, "new contents");
+}
+
+fn write_file_fails_if_file_is_readonly<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("test_file");
+
+    fs.create_file(&path, "").unwrap();
+    fs.set_readonly(&path, true).unwrap();
+
+    let result = fs.write_file(&path, "test contents");
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+}
+
+fn write_file_fails_if_node_is_a_directory<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("test_dir");
 
     fs.create_dir(&path).unwrap();
 
     let result = fs.write_file(&path, "test contents");
 
     assert!(result.is_err());
-    assert_eq!(result.unwrap_err().kind(), ErrorKind::Other);
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::IsADirectory);
 }
 
 fn overwrite_file_overwrites_contents_of_existing_file<T: FileSystem>(fs: &T, parent: &Path) {
@@ -551,7 +1459,140 @@ fn overwrite_file_fails_if_node_is_a_directory<T: FileSystem>(fs: &T, parent: &P
     let result = fs.overwrite_file(&path, "test contents");
 
     assert!(result.is_err());
-    assert_eq!(result.unwrap_err().kind(), ErrorKind::Other);
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::IsADirectory);
+}
+
+fn set_len_truncates_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("test_file");
+
+    fs.create_file(&path, "hello world").unwrap();
+
+    let result = fs.set_len(&path, 5);
+
+    assert!(result.is_ok());
+    assert_eq!(fs.read_file_to_string(&path).unwrap(), "hello");
+}
+
+fn set_len_extends_file_with_zeros<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("test_file");
+
+    fs.create_file(&path, "abc").unwrap();
+
+    let result = fs.set_len(&path, 6);
+
+    assert!(result.is_ok());
+    assert_eq!(fs.read_file(&path).unwrap(), b"abc\0\0\0");
+}
+
+fn set_len_fails_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("new_file");
+    let result = fs.set_len(&path, 0);
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+    assert!(!fs.exists(&path));
+}
+
+fn sync_file_succeeds_if_file_exists<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("test_file");
+
+    fs.create_file(&path, "contents").unwrap();
+
+    assert!(fs.sync_file(&path).is_ok());
+    assert_eq!(fs.read_file_to_string(&path).unwrap(), "contents");
+}
+
+fn sync_file_fails_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let result = fs.sync_file(parent.join("new_file"));
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+fn sync_dir_succeeds_if_dir_exists<T: FileSystem>(fs: &T, parent: &Path) {
+    fs.create_file(parent.join("test_file"), "").unwrap();
+
+    assert!(fs.sync_dir(parent).is_ok());
+}
+
+fn sync_dir_fails_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let result = fs.sync_dir(parent.join("new_dir"));
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+fn write_from_reader_writes_to_new_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("new_file");
+    let contents = vec![7; 100_000];
+
+    let result = fs.write_from_reader(&path, &mut &contents[..]);
+
+    assert_eq!(result.unwrap(), 100_000);
+    assert_eq!(fs.read_file(&path).unwrap(), contents);
+}
+
+fn write_from_reader_overwrites_contents_of_existing_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("test_file");
+
+    fs.write_file(&path, "old contents").unwrap();
+
+    let result = fs.write_from_reader(&path, &mut "new".as_bytes());
+
+    assert_eq!(result.unwrap(), 3);
+    assert_eq!(fs.read_file_to_string(&path).unwrap(), "new");
+}
+
+fn write_from_reader_fails_if_node_is_a_directory<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("test_dir");
+
+    fs.create_dir(&path).unwrap();
+
+    let result = fs.write_from_reader(&path, &mut "test contents".as_bytes());
+
+    assert!(result.is_err());
+}
+
+fn write_file_atomic_writes_to_new_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("new_file");
+
+    let result = fs.write_file_atomic(&path, "new contents");
+
+    assert!(result.is_ok());
+    assert_eq!(fs.read_file_to_string(&path).unwrap(), "new contents");
+}
+
+fn write_file_atomic_replaces_contents_of_existing_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("test_file");
+
+    fs.write_file(&path, "old contents").unwrap();
+
+    let result = fs.write_file_atomic(&path, "new");
+
+    assert!(result.is_ok());
+    assert_eq!(fs.read_file_to_string(&path).unwrap(), "new");
+    assert_eq!(fs.read_dir(parent).unwrap().count(), 1);
+}
+
+fn write_file_atomic_fails_if_node_is_a_directory<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("test_dir");
+
+    fs.create_dir(&path).unwrap();
+
+    let result = fs.write_file_atomic(&path, "test contents");
+
+    assert!(result.is_err());
+    assert!(fs.is_dir(&path));
+    assert_eq!(fs.read_dir(parent).unwrap().count(), 1);
+}
+
+fn write_file_atomic_fails_if_parent_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("parent").join("test_file");
+
+    let result = fs.write_file_atomic(&path, "test contents");
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
 }
 
 fn read_file_returns_contents_as_bytes<T: FileSystem>(fs: &T, parent: &Path) {
@@ -595,7 +1636,7 @@ fn read_file_to_string_fails_if_file_does_not_exist<T: FileSystem>(fs: &T, paren
 fn read_file_to_string_fails_if_contents_are_not_utf8<T: FileSystem>(fs: &T, parent: &Path) {
     let path = parent.join("test.txt");
 
-    fs.write_file(&path, &[0, 159, 146, 150]).unwrap();
+    fs.write_file(&path, [0, 159, 146, 150]).unwrap();
 
     let result = fs.read_file_to_string(&path);
 
@@ -613,7 +1654,7 @@ fn read_file_into_writes_bytes_to_buffer<T: FileSystem>(fs: &T, parent: &Path) {
     let result = fs.read_file_into(&path, &mut buf);
 
     assert!(result.is_ok());
-    assert_eq!(result.unwrap(), text.as_bytes().len());
+    assert_eq!(result.unwrap(), text.len());
     assert_eq!(buf, br"test text");
 }
 
@@ -648,488 +1689,7615 @@ fn create_file_fails_if_file_already_exists<T: FileSystem>(fs: &T, parent: &Path
     assert_eq!(result.unwrap_err().kind(), ErrorKind::AlreadyExists);
 }
 
-fn remove_file_removes_a_file<T: FileSystem>(fs: &T, parent: &Path) {
-    let path = parent.join("test_file");
+fn open_reads_contents_of_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
 
-    fs.create_file(&path, "").unwrap();
+    fs.create_file(&path, "contents").unwrap();
 
-    let result = fs.remove_file(&path);
+    let result = fs.open(&path);
 
     assert!(result.is_ok());
 
-    let result = fs.read_file(&path);
+    let mut contents = String::new();
 
-    assert!(result.is_err());
-    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+    result.unwrap().read_to_string(&mut contents).unwrap();
+
+    assert_eq!(contents, "contents");
 }
 
-fn remove_file_fails_if_file_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
-    let result = fs.remove_file(parent.join("does_not_exist"));
+fn open_fails_if_file_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let result = fs.open(parent.join("does_not_exist"));
 
-    assert!(result.is_err());
-    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+    match result {
+        Ok(_) => panic!("should be an err"),
+        Err(err) => assert_eq!(err.kind(), ErrorKind::NotFound),
+    }
 }
 
-fn remove_file_fails_if_node_is_a_directory<T: FileSystem>(fs: &T, parent: &Path) {
-    let path = parent.join("test_dir");
+fn open_fails_to_write_to_read_only_handle<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
 
-    fs.create_dir(&path).unwrap();
+    fs.create_file(&path, "contents").unwrap();
 
-    let result = fs.remove_file(&path);
+    let result = fs.open(&path).unwrap().write(b"new contents");
 
     assert!(result.is_err());
-    assert_eq!(result.unwrap_err().kind(), ErrorKind::Other);
+    assert_eq!(fs.read_file_to_string(&path).unwrap(), "contents");
 }
 
-fn copy_file_copies_a_file<T: FileSystem>(fs: &T, parent: &Path) {
-    let from = parent.join("from");
-    let to = parent.join("to");
+fn open_buffered_reads_contents_of_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
 
-    fs.create_file(&from, "test").unwrap();
+    fs.create_file(&path, "first\nsecond").unwrap();
 
-    let result = fs.copy_file(&from, &to);
+    let mut file = fs.open_buffered(&path).unwrap();
+    let mut line = String::new();
 
-    assert!(result.is_ok());
+    file.read_line(&mut line).unwrap();
 
-    let result = fs.read_file(&to);
+    assert_eq!(line, "first\n");
+    assert_eq!(file.fill_buf().unwrap(), b"second");
+}
 
-    assert!(result.is_ok());
-    assert_eq!(&result.unwrap(), b"test");
+fn read_lines_returns_each_line<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    fs.create_file(&path, "first\r\nsecond\n\nlast").unwrap();
+
+    let lines: Vec<String> = fs.read_lines(&path).unwrap().map(Result::unwrap).collect();
+
+    assert_eq!(lines, vec!["first", "second", "", "last"]);
 }
 
-fn copy_file_overwrites_destination_file<T: FileSystem>(fs: &T, parent: &Path) {
-    let from = parent.join("from");
-    let to = parent.join("to");
+fn read_lines_fails_if_file_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let result = fs.read_lines(parent.join("does_not_exist"));
 
-    fs.create_file(&from, "expected").unwrap();
-    fs.create_file(&to, "should be overwritten").unwrap();
+    match result {
+        Ok(_) => panic!("should be an err"),
+        Err(err) => assert_eq!(err.kind(), ErrorKind::NotFound),
+    }
+}
 
-    let result = fs.copy_file(&from, &to);
+#[cfg(feature = "hash")]
+fn hash_file_returns_digest_of_contents<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
 
-    assert!(result.is_ok());
+    fs.create_file(&path, "abc").unwrap();
 
-    let result = fs.read_file(&to);
+    let sha1 = fs.hash_file(&path, Algorithm::Sha1).unwrap();
+    let sha256 = fs.hash_file(&path, Algorithm::Sha256).unwrap();
+    let sha512 = fs.hash_file(&path, Algorithm::Sha512).unwrap();
 
-    assert!(result.is_ok());
-    assert_eq!(result.unwrap(), b"expected");
+    assert_eq!(sha1.to_hex(), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    assert_eq!(
+        sha256.to_hex(),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert_eq!(
+        sha512.to_hex(),
+        "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+         2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+    );
+    assert_eq!(sha256.algorithm(), Algorithm::Sha256);
+    assert_eq!(sha256.as_bytes().len(), 32);
 }
 
-fn copy_file_fails_if_original_file_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
-    let from = parent.join("from");
-    let to = parent.join("to");
+#[cfg(feature = "hash")]
+fn hash_file_reads_files_larger_than_a_chunk<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
 
-    let result = fs.copy_file(&from, &to);
+    fs.create_file(&path, vec![b'a'; 1_000_000]).unwrap();
+
+    let digest = fs.hash_file(&path, Algorithm::Sha256).unwrap();
+
+    assert_eq!(
+        digest.to_string(),
+        "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+    );
+}
+
+#[cfg(feature = "hash")]
+fn hash_file_fails_if_file_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let result = fs.hash_file(parent.join("does_not_exist"), Algorithm::Sha256);
 
     assert!(result.is_err());
     assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
-    assert!(!fs.is_file(&to));
 }
 
-fn copy_file_fails_if_destination_file_is_readonly<T: FileSystem>(fs: &T, parent: &Path) {
-    let from = parent.join("from");
-    let to = parent.join("to");
+fn open_with_options_creates_and_writes_to_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+    let result = fs.open_with_options(&path, OpenOptions::new().write(true).create(true));
 
-    fs.create_file(&from, "test").unwrap();
-    fs.create_file(&to, "").unwrap();
-    fs.set_readonly(&to, true).unwrap();
+    assert!(result.is_ok());
 
-    let result = fs.copy_file(&from, &to);
+    let mut file = result.unwrap();
 
-    assert!(result.is_err());
-    assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+    file.write_all(b"new ").unwrap();
+    file.write_all(b"contents").unwrap();
+    file.flush().unwrap();
+
+    assert_eq!(fs.read_file_to_string(&path).unwrap(), "new contents");
 }
 
-fn copy_file_fails_if_original_node_is_directory<T: FileSystem>(fs: &T, parent: &Path) {
-    let from = parent.join("from");
-    let to = parent.join("to");
+fn open_with_options_seeks_and_overwrites_contents<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
 
-    fs.create_dir(&from).unwrap();
+    fs.create_file(&path, "hello world").unwrap();
 
-    let result = fs.copy_file(&from, &to);
+    let mut file = fs
+        .open_with_options(&path, OpenOptions::new().read(true).write(true))
+        .unwrap();
 
-    assert!(result.is_err());
-    assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(file.seek(SeekFrom::Start(6)).unwrap(), 6);
+
+    file.write_all(b"there").unwrap();
+
+    assert_eq!(file.seek(SeekFrom::End(-5)).unwrap(), 6);
+
+    let mut contents = String::new();
+
+    file.read_to_string(&mut contents).unwrap();
+
+    assert_eq!(contents, "there");
+    assert_eq!(fs.read_file_to_string(&path).unwrap(), "hello there");
 }
 
-fn copy_file_fails_if_destination_node_is_directory<T: FileSystem>(fs: &T, parent: &Path) {
-    let from = parent.join("from");
-    let to = parent.join("to");
+fn open_with_options_appends_to_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
 
-    fs.create_file(&from, "").unwrap();
-    fs.create_dir(&to).unwrap();
+    fs.create_file(&path, "hello").unwrap();
 
-    let result = fs.copy_file(&from, &to);
+    let mut file = fs
+        .open_with_options(&path, OpenOptions::new().append(true))
+        .unwrap();
 
-    assert!(result.is_err());
-    assert_eq!(result.unwrap_err().kind(), ErrorKind::Other);
+    file.seek(SeekFrom::Start(0)).unwrap();
+    file.write_all(b" world").unwrap();
+
+    assert_eq!(fs.read_file_to_string(&path).unwrap(), "hello world");
 }
 
-fn rename_renames_a_file<T: FileSystem>(fs: &T, parent: &Path) {
-    let from = parent.join("from");
-    let to = parent.join("to");
+fn open_with_options_truncates_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
 
-    fs.create_file(&from, "contents").unwrap();
+    fs.create_file(&path, "old contents").unwrap();
 
-    let result = fs.rename(&from, &to);
+    let mut file = fs
+        .open_with_options(&path, OpenOptions::new().write(true).truncate(true))
+        .unwrap();
 
-    assert!(result.is_ok());
-    assert!(!fs.is_file(&from));
+    assert_eq!(fs.len(&path), 0);
 
-    let result = fs.read_file_to_string(&to);
+    file.write_all(b"new").unwrap();
 
-    assert!(result.is_ok());
-    assert_eq!(result.unwrap(), "contents");
+    assert_eq!(fs.read_file_to_string(&path).unwrap(), "new");
 }
 
-fn rename_renames_a_directory<T: FileSystem>(fs: &T, parent: &Path) {
-    let from = parent.join("from");
-    let to = parent.join("to");
-    let child = from.join("child");
+fn open_with_options_fails_if_create_new_and_file_exists<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
 
-    fs.create_dir(&from).unwrap();
-    fs.create_file(&child, "child").unwrap();
+    fs.create_file(&path, "contents").unwrap();
 
-    let result = fs.rename(&from, &to);
+    let result = fs.open_with_options(&path, OpenOptions::new().write(true).create_new(true));
 
-    assert!(result.is_ok());
-    assert!(!fs.is_dir(&from));
+    match result {
+        Ok(_) => panic!("should be an err"),
+        Err(err) => assert_eq!(err.kind(), ErrorKind::AlreadyExists),
+    }
+    assert_eq!(fs.read_file_to_string(&path).unwrap(), "contents");
+}
 
-    let result = fs.read_file_to_string(to.join("child"));
+fn open_with_options_fails_if_file_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("does_not_exist");
+    let result = fs.open_with_options(&path, OpenOptions::new().write(true));
 
-    assert!(result.is_ok());
-    assert_eq!(result.unwrap(), "child");
+    match result {
+        Ok(_) => panic!("should be an err"),
+        Err(err) => assert_eq!(err.kind(), ErrorKind::NotFound),
+    }
+    assert!(!fs.is_file(&path));
 }
 
-fn rename_overwrites_destination_file<T: FileSystem>(fs: &T, parent: &Path) {
-    let from = parent.join("from");
-    let to = parent.join("to");
+fn open_with_options_fails_if_options_are_invalid<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+    let result = fs.open_with_options(&path, OpenOptions::new().read(true).create(true));
 
-    fs.create_file(&from, "from").unwrap();
-    fs.create_file(&to, "to").unwrap();
+    match result {
+        Ok(_) => panic!("should be an err"),
+        Err(err) => assert_eq!(err.kind(), ErrorKind::InvalidInput),
+    }
+    assert!(!fs.is_file(&path));
+}
 
-    let result = fs.rename(&from, &to);
+fn open_with_options_fills_gap_after_end_with_zeros<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
 
-    assert!(result.is_ok());
-    assert!(!fs.is_file(&from));
+    fs.create_file(&path, "abc").unwrap();
 
-    let result = fs.read_file_to_string(&to);
+    let mut file = fs
+        .open_with_options(&path, OpenOptions::new().write(true))
+        .unwrap();
 
-    assert!(result.is_ok());
-    assert_eq!(result.unwrap(), "from");
+    assert_eq!(file.seek(SeekFrom::End(2)).unwrap(), 5);
+    assert_eq!(fs.len(&path), 3);
+
+    file.write_all(b"def").unwrap();
+
+    assert_eq!(fs.read_file(&path).unwrap(), b"abc\0\0def");
 }
 
-fn rename_overwrites_empty_destination_directory<T: FileSystem>(fs: &T, parent: &Path) {
-    let from = parent.join("from");
-    let to = parent.join("to");
-    let child = from.join("child");
+fn open_with_sets_options_in_a_chain<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+    let mut file = fs
+        .open_with(&path)
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open()
+        .unwrap();
 
-    fs.create_dir(&from).unwrap();
-    fs.create_dir(&to).unwrap();
-    fs.create_file(&child, "child").unwrap();
+    file.write_all(b"contents").unwrap();
+    file.seek(SeekFrom::Start(0)).unwrap();
 
-    let result = fs.rename(&from, &to);
+    let mut contents = String::new();
 
-    assert!(result.is_ok(), "err: {:?}", result);
-    assert!(!fs.is_dir(&from));
+    file.read_to_string(&mut contents).unwrap();
 
-    let result = fs.read_file_to_string(to.join("child"));
+    assert_eq!(contents, "contents");
 
-    assert!(result.is_ok());
-    assert_eq!(result.unwrap(), "child");
+    let builder = fs.open_with(&path).write(true).truncate(true);
+
+    assert_eq!(
+        builder.options(),
+        OpenOptions::new().write(true).truncate(true)
+    );
+
+    builder.open().unwrap();
+
+    assert_eq!(fs.len(&path), 0);
 }
 
-fn rename_renames_all_descendants<T: FileSystem>(fs: &T, parent: &Path) {
-    let from = parent.join("from");
-    let to = parent.join("to");
-    let child_file = from.join("child_file");
-    let child_dir = from.join("child_dir");
-    let grandchild = child_dir.join("grandchild");
+fn open_with_read_only_fails_to_write<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
 
-    fs.create_dir(&from).unwrap();
-    fs.create_file(&child_file, "child_file").unwrap();
-    fs.create_dir(&child_dir).unwrap();
-    fs.create_file(&grandchild, "grandchild").unwrap();
+    fs.create_file(&path, "contents").unwrap();
 
-    let result = fs.rename(&from, &to);
+    let mut file = fs.open_with(&path).read(true).open().unwrap();
 
-    assert!(result.is_ok());
-    assert!(!fs.is_dir(&from));
+    assert!(file.write_all(b"new").and_then(|_| file.flush()).is_err());
+    assert_eq!(fs.read_file_to_string(&path).unwrap(), "contents");
+}
 
-    let result = fs.read_file_to_string(to.join("child_file"));
-    assert!(result.is_ok());
-    assert_eq!(result.unwrap(), "child_file");
+fn open_with_write_only_fails_to_read<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
 
-    let result = fs.read_file_to_string(to.join("child_dir").join("grandchild"));
-    assert!(result.is_ok());
-    assert_eq!(result.unwrap(), "grandchild");
+    fs.create_file(&path, "contents").unwrap();
+
+    let mut file = fs.open_with(&path).write(true).open().unwrap();
+    let mut contents = Vec::new();
+
+    assert!(file.read_to_end(&mut contents).is_err());
 }
 
-fn rename_fails_if_original_path_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
-    let from = parent.join("from");
-    let to = parent.join("to");
+fn open_with_truncate_and_create_new_creates_empty_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
 
-    let result = fs.rename(&from, &to);
+    fs.open_with(&path)
+        .write(true)
+        .truncate(true)
+        .create_new(true)
+        .open()
+        .unwrap();
 
-    assert!(result.is_err());
-    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+    assert!(fs.is_file(&path));
+    assert_eq!(fs.len(&path), 0);
 }
 
-fn rename_fails_if_original_and_destination_are_different_types<T: FileSystem>(
+fn open_with_create_new_fails_if_path_is_broken_symlink<T: SymlinkFileSystem>(
     fs: &T,
     parent: &Path,
 ) {
-    let file = parent.join("file");
-    let dir = parent.join("dir");
+    let target = parent.join("target");
+    let link = parent.join("link");
 
-    fs.create_file(&file, "").unwrap();
-    fs.create_dir(&dir).unwrap();
+    fs.symlink_file(&target, &link).unwrap();
 
-    let result = fs.rename(&file, &dir);
+    let result = fs.open_with(&link).write(true).create_new(true).open();
 
-    assert!(result.is_err());
-    assert_eq!(result.unwrap_err().kind(), ErrorKind::Other);
+    match result {
+        Ok(_) => panic!("should be an err"),
+        Err(err) => assert_eq!(err.kind(), ErrorKind::AlreadyExists),
+    }
+    assert!(!fs.exists(&target));
+}
 
-    let result = fs.rename(&dir, &file);
+fn open_with_create_follows_broken_symlink<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let target = parent.join("target");
+    let link = parent.join("link");
 
-    assert!(result.is_err());
-    assert_eq!(result.unwrap_err().kind(), ErrorKind::Other);
+    fs.symlink_file(&target, &link).unwrap();
+    fs.open_with(&link)
+        .write(true)
+        .create(true)
+        .open()
+        .and_then(|mut file| Ok(file.write_all(b"contents")?))
+        .unwrap();
+
+    assert_eq!(fs.read_file_to_string(&target).unwrap(), "contents");
+    assert!(fs.symlink_metadata(&link).unwrap().file_type().is_symlink());
 }
 
-fn rename_fails_if_destination_directory_is_not_empty<T: FileSystem>(fs: &T, parent: &Path) {
-    let from = parent.join("from");
-    let to = parent.join("to");
-    let child = to.join("child");
+fn open_file_set_len_truncates_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
 
-    fs.create_dir(&from).unwrap();
-    fs.create_dir(&to).unwrap();
-    fs.create_file(&child, "child").unwrap();
+    fs.create_file(&path, "hello world").unwrap();
 
-    let result = fs.rename(&from, &to);
+    let mut file = fs
+        .open_with_options(&path, OpenOptions::new().read(true).write(true))
+        .unwrap();
 
-    assert!(result.is_err());
+    file.seek(SeekFrom::End(0)).unwrap();
+    file.set_len(5).unwrap();
+
+    assert_eq!(fs.read_file_to_string(&path).unwrap(), "hello");
+    assert_eq!(file.stream_position().unwrap(), 11);
 }
 
-fn readonly_returns_write_permission<T: FileSystem>(fs: &T, parent: &Path) {
-    let path = parent.join("test_file");
+fn open_file_set_len_extends_file_with_zeros<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
 
-    fs.create_file(&path, "").unwrap();
+    fs.create_file(&path, "abc").unwrap();
 
-    let result = fs.readonly(&path);
+    let file = fs
+        .open_with_options(&path, OpenOptions::new().write(true))
+        .unwrap();
 
-    assert!(result.is_ok());
-    assert!(!result.unwrap());
+    file.set_len(6).unwrap();
 
-    fs.set_readonly(&path, true).unwrap();
+    assert_eq!(fs.read_file(&path).unwrap(), b"abc\0\0\0");
+}
 
-    let result = fs.readonly(&path);
+fn open_file_set_len_fails_if_not_open_for_writing<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
 
-    assert!(result.is_ok());
-    assert!(result.unwrap());
-}
+    fs.create_file(&path, "contents").unwrap();
 
-fn readonly_fails_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
-    let result = fs.readonly(parent.join("does_not_exist"));
+    let file = fs.open(&path).unwrap();
+    let result = file.set_len(0);
 
     assert!(result.is_err());
-    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(fs.read_file_to_string(&path).unwrap(), "contents");
 }
 
-fn set_readonly_toggles_write_permission_of_file<T: FileSystem>(fs: &T, parent: &Path) {
-    let path = parent.join("test_file");
+fn open_file_sync_all_succeeds<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+    let mut file = fs
+        .open_with_options(&path, OpenOptions::new().write(true).create(true))
+        .unwrap();
+
+    file.write_all(b"contents").unwrap();
+
+    assert!(file.sync_all().is_ok());
+    assert_eq!(fs.read_file_to_string(&path).unwrap(), "contents");
+}
+
+#[cfg(feature = "lock")]
+fn try_lock_shared_fails_if_file_is_locked_exclusively<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
 
     fs.create_file(&path, "").unwrap();
 
-    let result = fs.set_readonly(&path, true);
+    let first = fs.open(&path).unwrap();
+    let second = fs.open(&path).unwrap();
 
-    assert!(result.is_ok());
-    assert!(fs.write_file(&path, "readonly").is_err());
+    first.lock_exclusive().unwrap();
 
-    let result = fs.set_readonly(&path, false);
+    let result = second.try_lock_shared();
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::WouldBlock);
+}
+
+#[cfg(feature = "lock")]
+fn try_lock_exclusive_fails_if_file_is_locked_shared<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    fs.create_file(&path, "").unwrap();
+
+    let first = fs.open(&path).unwrap();
+    let second = fs.open(&path).unwrap();
+
+    first.lock_shared().unwrap();
+
+    let result = second.try_lock_exclusive();
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::WouldBlock);
+}
+
+#[cfg(feature = "lock")]
+fn try_lock_shared_succeeds_if_file_is_locked_shared<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    fs.create_file(&path, "").unwrap();
+
+    let first = fs.open(&path).unwrap();
+    let second = fs.open(&path).unwrap();
+
+    first.lock_shared().unwrap();
+
+    let result = second.try_lock_shared();
 
     assert!(result.is_ok());
-    assert!(fs.write_file(&path, "no longer readonly").is_ok());
 }
 
-fn set_readonly_toggles_write_permission_of_dir<T: FileSystem>(fs: &T, parent: &Path) {
-    let path = parent.join("test_dir");
+#[cfg(feature = "lock")]
+fn unlock_releases_lock<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
 
-    fs.create_dir(&path).unwrap();
+    fs.create_file(&path, "").unwrap();
 
-    let result = fs.set_readonly(&path, true);
+    let first = fs.open(&path).unwrap();
+    let second = fs.open(&path).unwrap();
+
+    first.lock_exclusive().unwrap();
+    first.unlock().unwrap();
+
+    let result = second.try_lock_exclusive();
 
     assert!(result.is_ok());
-    assert!(fs.write_file(&path.join("file"), "").is_err());
+}
 
-    let result = fs.set_readonly(&path, false);
+#[cfg(feature = "lock")]
+fn dropping_handle_releases_lock<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    fs.create_file(&path, "").unwrap();
+
+    let first = fs.open(&path).unwrap();
+    let second = fs.open(&path).unwrap();
+
+    first.lock_exclusive().unwrap();
+    drop(first);
+
+    let result = second.try_lock_exclusive();
 
     assert!(result.is_ok());
-    assert!(fs.write_file(&path.join("file"), "").is_ok());
 }
 
-fn set_readonly_fails_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
-    let result = fs.set_readonly(parent.join("does_not_exist"), true);
+fn remove_file_removes_a_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("test_file");
+
+    fs.create_file(&path, "").unwrap();
+
+    let result = fs.remove_file(&path);
+
+    assert!(result.is_ok());
+
+    let result = fs.read_file(&path);
 
     assert!(result.is_err());
     assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+}
 
-    let result = fs.set_readonly(parent.join("does_not_exist"), true);
+fn remove_file_fails_if_file_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let result = fs.remove_file(parent.join("does_not_exist"));
 
     assert!(result.is_err());
     assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
 }
 
-fn len_returns_size_of_file<T: FileSystem>(fs: &T, parent: &Path) {
-    let path = parent.join("file");
-    let result = fs.create_file(&path, "");
+fn remove_file_fails_if_node_is_a_directory<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("test_dir");
 
-    assert!(result.is_ok());
+    fs.create_dir(&path).unwrap();
 
-    let len = fs.len(&path);
+    let result = fs.remove_file(&path);
 
-    assert_eq!(len, 0);
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::IsADirectory);
+}
 
-    let result = fs.write_file(&path, "contents");
+fn copy_file_copies_a_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+    let to = parent.join("to");
+
+    fs.create_file(&from, "test").unwrap();
+
+    let result = fs.copy_file(&from, &to);
 
     assert!(result.is_ok());
 
-    let len = fs.len(&path);
+    let result = fs.read_file(&to);
 
-    assert_eq!(len, 8);
+    assert!(result.is_ok());
+    assert_eq!(&result.unwrap(), b"test");
 }
 
-fn len_returns_size_of_directory<T: FileSystem>(fs: &T, parent: &Path) {
-    let path = parent.join("directory");
-    let result = fs.create_dir(&path);
+fn copy_file_overwrites_destination_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+    let to = parent.join("to");
+
+    fs.create_file(&from, "expected").unwrap();
+    fs.create_file(&to, "should be overwritten").unwrap();
+
+    let result = fs.copy_file(&from, &to);
 
     assert!(result.is_ok());
 
-    let len = fs.len(&path);
+    let result = fs.read_file(&to);
 
-    assert_ne!(len, 0);
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap(), b"expected");
 }
 
-fn len_returns_0_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
-    let path = parent.join("does-not-exist");
-    let len = fs.len(&path);
+fn copy_file_fails_if_original_file_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+    let to = parent.join("to");
 
-    assert_eq!(len, 0);
+    let result = fs.copy_file(&from, &to);
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+    assert!(!fs.is_file(&to));
 }
 
-#[cfg(unix)]
-fn mode_returns_permissions<T: FileSystem + UnixFileSystem>(fs: &T, parent: &Path) {
-    let path = parent.join("file");
+fn copy_file_fails_if_destination_file_is_readonly<T: FileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+    let to = parent.join("to");
 
-    fs.create_file(&path, "").unwrap();
-    fs.set_mode(&path, 0o644).unwrap();
+    fs.create_file(&from, "test").unwrap();
+    fs.create_file(&to, "").unwrap();
+    fs.set_readonly(&to, true).unwrap();
 
-    let result = fs.mode(&path);
+    let result = fs.copy_file(&from, &to);
 
-    assert!(result.is_ok());
-    assert_eq!(result.unwrap() % 0o100_000, 0o644);
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+}
 
-    fs.set_mode(&path, 0o600).unwrap();
+fn copy_file_fails_if_original_node_is_directory<T: FileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+    let to = parent.join("to");
 
-    let result = fs.mode(&path);
+    fs.create_dir(&from).unwrap();
 
-    assert!(result.is_ok());
-    assert_eq!(result.unwrap() % 0o100_000, 0o600);
+    let result = fs.copy_file(&from, &to);
 
-    fs.set_readonly(&path, true).unwrap();
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+}
 
-    let result = fs.mode(&path);
+fn copy_file_fails_if_destination_node_is_directory<T: FileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+    let to = parent.join("to");
 
-    assert!(result.is_ok());
-    assert_eq!(result.unwrap() % 0o100_000, 0o400);
-}
+    fs.create_file(&from, "").unwrap();
+    fs.create_dir(&to).unwrap();
 
-#[cfg(unix)]
-fn mode_fails_if_node_does_not_exist<T: UnixFileSystem>(fs: &T, parent: &Path) {
-    let result = fs.mode(parent.join("does_not_exist"));
+    let result = fs.copy_file(&from, &to);
 
     assert!(result.is_err());
-    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::IsADirectory);
 }
 
-#[cfg(unix)]
-fn set_mode_sets_permissions<T: FileSystem + UnixFileSystem>(fs: &T, parent: &Path) {
-    let path = parent.join("file");
+fn copy_dir_copies_all_descendants<T: FileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+    let to = parent.join("to");
 
-    fs.create_file(&path, "").unwrap();
+    fs.create_dir_all(from.join("dir").join("nested")).unwrap();
+    fs.create_file(from.join("file"), "file").unwrap();
+    fs.create_file(from.join("dir").join("nested").join("file"), "nested")
+        .unwrap();
 
-    let result = fs.set_mode(&path, 0o000);
+    let result = fs.copy_dir(&from, &to);
 
     assert!(result.is_ok());
+    assert!(fs.is_dir(to.join("dir").join("nested")));
+    assert_eq!(fs.read_file_to_string(to.join("file")).unwrap(), "file");
+    assert_eq!(
+        fs.read_file_to_string(to.join("dir").join("nested").join("file"))
+            .unwrap(),
+        "nested"
+    );
+}
 
-    let readonly_result = fs.readonly(&path);
+fn copy_dir_does_not_affect_original<T: FileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+    let to = parent.join("to");
 
-    assert!(readonly_result.is_ok());
-    assert!(readonly_result.unwrap());
+    fs.create_dir(&from).unwrap();
+    fs.create_file(from.join("file"), "original").unwrap();
 
-    let read_result = fs.read_file(&path);
-    let write_result = fs.write_file(&path, "should not be allowed");
+    fs.copy_dir(&from, &to).unwrap();
+    fs.write_file(to.join("file"), "copy").unwrap();
 
-    assert!(read_result.is_err());
-    assert!(write_result.is_err());
-    assert_eq!(read_result.unwrap_err().kind(), ErrorKind::PermissionDenied);
     assert_eq!(
-        write_result.unwrap_err().kind(),
-        ErrorKind::PermissionDenied
+        fs.read_file_to_string(from.join("file")).unwrap(),
+        "original"
     );
+}
 
-    let result = fs.set_mode(&path, 0o200);
+fn copy_dir_copies_symlinks_as_symlinks<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+    let to = parent.join("to");
 
-    assert!(result.is_ok());
+    fs.create_dir(&from).unwrap();
+    fs.create_file(from.join("file"), "").unwrap();
+    fs.symlink_file("file", from.join("link")).unwrap();
 
-    let read_result = fs.read_file(&path);
-    let write_result = fs.write_file(&path, "should be allowed");
+    let result = fs.copy_dir(&from, &to);
 
-    assert!(read_result.is_err());
-    assert!(write_result.is_ok());
-    assert_eq!(read_result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+    assert!(result.is_ok());
+    assert_eq!(
+        fs.read_link(to.join("link")).unwrap(),
+        PathBuf::from("file")
+    );
+}
 
-    let readonly_result = fs.readonly(&path);
+#[cfg(unix)]
+fn copy_dir_preserves_modes<T: FileSystem + UnixFileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+    let to = parent.join("to");
 
-    assert!(readonly_result.is_ok());
-    assert!(!readonly_result.unwrap());
+    fs.create_dir(&from).unwrap();
+    fs.create_file(from.join("file"), "").unwrap();
+    fs.set_mode(from.join("file"), 0o600).unwrap();
+    fs.set_mode(&from, 0o555).unwrap();
 
-    let result = fs.set_mode(&path, 0o644);
+    let result = fs.copy_dir(&from, &to);
 
     assert!(result.is_ok());
+    assert_eq!(fs.mode(to.join("file")).unwrap() % 0o1000, 0o600);
+    assert_eq!(fs.mode(&to).unwrap() % 0o1000, 0o555);
+}
 
-    let readonly_result = fs.readonly(&path);
+fn copy_dir_fails_if_original_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let result = fs.copy_dir(parent.join("does_not_exist"), parent.join("to"));
 
-    assert!(readonly_result.is_ok());
-    assert!(!readonly_result.unwrap());
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+    assert!(!fs.is_dir(parent.join("to")));
 }
 
-#[cfg(unix)]
-fn set_mode_fails_if_node_does_not_exist<T: UnixFileSystem>(fs: &T, parent: &Path) {
-    let result = fs.set_mode(parent.join("does_not_exist"), 0o644);
+fn copy_dir_fails_if_original_is_a_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+
+    fs.create_file(&from, "").unwrap();
+
+    let result = fs.copy_dir(&from, parent.join("to"));
 
     assert!(result.is_err());
-    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
 }
 
-fn temp_dir_creates_tempdir<T: FileSystem + TempFileSystem>(fs: &T, _: &Path) {
-    let path = {
-        let result = fs.temp_dir("test");
+fn copy_dir_fails_if_destination_exists<T: FileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+    let to = parent.join("to");
 
-        assert!(result.is_ok());
+    fs.create_dir(&from).unwrap();
+    fs.create_dir(&to).unwrap();
 
-        let temp_dir = result.unwrap();
+    let result = fs.copy_dir(&from, &to);
 
-        assert!(fs.is_dir(temp_dir.path()));
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::AlreadyExists);
+}
 
-        temp_dir.path().to_path_buf()
-    };
+fn copy_dir_fails_if_destination_is_inside_original<T: FileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
 
-    assert!(!fs.is_dir(&path));
-    assert!(fs.is_dir(path.parent().unwrap()));
+    fs.create_dir(&from).unwrap();
+
+    let result = fs.copy_dir(&from, from.join("to"));
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert!(!fs.is_dir(from.join("to")));
 }
 
-fn temp_dir_creates_unique_dir<T: FileSystem + TempFileSystem>(fs: &T, _: &Path) {
-    let first = fs.temp_dir("test").unwrap();
-    let second = fs.temp_dir("test").unwrap();
+fn rename_renames_a_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+    let to = parent.join("to");
 
-    assert_ne!(first.path(), second.path());
+    fs.create_file(&from, "contents").unwrap();
+
+    let result = fs.rename(&from, &to);
+
+    assert!(result.is_ok());
+    assert!(!fs.is_file(&from));
+
+    let result = fs.read_file_to_string(&to);
+
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap(), "contents");
+}
+
+fn rename_renames_a_directory<T: FileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+    let to = parent.join("to");
+    let child = from.join("child");
+
+    fs.create_dir(&from).unwrap();
+    fs.create_file(&child, "child").unwrap();
+
+    let result = fs.rename(&from, &to);
+
+    assert!(result.is_ok());
+    assert!(!fs.is_dir(&from));
+
+    let result = fs.read_file_to_string(to.join("child"));
+
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap(), "child");
+}
+
+fn rename_overwrites_destination_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+    let to = parent.join("to");
+
+    fs.create_file(&from, "from").unwrap();
+    fs.create_file(&to, "to").unwrap();
+
+    let result = fs.rename(&from, &to);
+
+    assert!(result.is_ok());
+    assert!(!fs.is_file(&from));
+
+    let result = fs.read_file_to_string(&to);
+
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap(), "from");
+}
+
+fn rename_overwrites_empty_destination_directory<T: FileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+    let to = parent.join("to");
+    let child = from.join("child");
+
+    fs.create_dir(&from).unwrap();
+    fs.create_dir(&to).unwrap();
+    fs.create_file(&child, "child").unwrap();
+
+    let result = fs.rename(&from, &to);
+
+    assert!(result.is_ok(), "err: {:?}", result);
+    assert!(!fs.is_dir(&from));
+
+    let result = fs.read_file_to_string(to.join("child"));
+
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap(), "child");
+}
+
+fn rename_renames_all_descendants<T: FileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+    let to = parent.join("to");
+    let child_file = from.join("child_file");
+    let child_dir = from.join("child_dir");
+    let grandchild = child_dir.join("grandchild");
+
+    fs.create_dir(&from).unwrap();
+    fs.create_file(&child_file, "child_file").unwrap();
+    fs.create_dir(&child_dir).unwrap();
+    fs.create_file(&grandchild, "grandchild").unwrap();
+
+    let result = fs.rename(&from, &to);
+
+    assert!(result.is_ok());
+    assert!(!fs.is_dir(&from));
+
+    let result = fs.read_file_to_string(to.join("child_file"));
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap(), "child_file");
+
+    let result = fs.read_file_to_string(to.join("child_dir").join("grandchild"));
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap(), "grandchild");
+}
+
+fn rename_to_same_path_does_nothing<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    fs.create_file(&path, "contents").unwrap();
+
+    let result = fs.rename(&path, &path);
+
+    assert!(result.is_ok());
+    assert_eq!(fs.read_file_to_string(&path).unwrap(), "contents");
+}
+
+fn rename_fails_if_original_path_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+    let to = parent.join("to");
+
+    let result = fs.rename(&from, &to);
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+fn rename_fails_if_original_and_destination_are_different_types<T: FileSystem>(
+    fs: &T,
+    parent: &Path,
+) {
+    let file = parent.join("file");
+    let dir = parent.join("dir");
+
+    fs.create_file(&file, "").unwrap();
+    fs.create_dir(&dir).unwrap();
+
+    let result = fs.rename(&file, &dir);
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::IsADirectory);
+
+    let result = fs.rename(&dir, &file);
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotADirectory);
+}
+
+fn rename_fails_if_destination_directory_is_not_empty<T: FileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+    let to = parent.join("to");
+    let child = to.join("child");
+
+    fs.create_dir(&from).unwrap();
+    fs.create_dir(&to).unwrap();
+    fs.create_file(&child, "child").unwrap();
+
+    let result = fs.rename(&from, &to);
+
+    assert!(result.is_err());
+}
+
+fn rename_fails_if_destination_is_inside_original<T: FileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+    let to = from.join("to");
+
+    fs.create_dir(&from).unwrap();
+
+    let result = fs.rename(&from, &to);
+
+    assert!(result.is_err());
+    assert!(fs.is_dir(&from));
+}
+
+fn rename_keeps_destination_if_it_is_inside_original<T: FileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+    let to = from.join("to");
+
+    fs.create_dir(&from).unwrap();
+    fs.create_dir(&to).unwrap();
+
+    let result = fs.rename(&from, &to);
+
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert!(fs.is_dir(&to));
+}
+
+fn rename_keeps_contents_and_times_of_descendants<T: FileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+    let to = parent.join("to");
+    let modified = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+
+    fs.create_dir_all(from.join("dir")).unwrap();
+    fs.create_file(from.join("dir/file"), "contents").unwrap();
+    fs.set_modified(from.join("dir/file"), modified).unwrap();
+    fs.create_dir(&to).unwrap();
+
+    fs.rename(&from, &to).unwrap();
+
+    assert!(!fs.exists(&from));
+    assert_eq!(fs.read_file(to.join("dir/file")).unwrap(), b"contents");
+    assert_eq!(fs.modified(to.join("dir/file")).unwrap(), modified);
+}
+
+fn symlink_file_creates_link_to_file<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let original = parent.join("original");
+    let link = parent.join("link");
+
+    fs.create_file(&original, "contents").unwrap();
+
+    let result = fs.symlink_file(&original, &link);
+
+    assert!(result.is_ok());
+    assert!(fs.is_file(&link));
+    assert_eq!(fs.read_file_to_string(&link).unwrap(), "contents");
+
+    fs.write_file(&link, "new contents").unwrap();
+
+    assert_eq!(fs.read_file_to_string(&original).unwrap(), "new contents");
+}
+
+fn symlink_file_fails_if_link_already_exists<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let original = parent.join("original");
+    let link = parent.join("link");
+
+    fs.create_file(&original, "").unwrap();
+    fs.create_file(&link, "contents").unwrap();
+
+    let result = fs.symlink_file(&original, &link);
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::AlreadyExists);
+    assert_eq!(fs.read_file_to_string(&link).unwrap(), "contents");
+}
+
+fn symlink_dir_creates_link_to_dir<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let original = parent.join("original");
+    let link = parent.join("link");
+
+    fs.create_dir(&original).unwrap();
+
+    let result = fs.symlink_dir(&original, &link);
+
+    assert!(result.is_ok());
+    assert!(fs.is_dir(&link));
+
+    fs.create_file(link.join("file"), "contents").unwrap();
+
+    assert_eq!(
+        fs.read_file_to_string(original.join("file")).unwrap(),
+        "contents"
+    );
+
+    let entries: Vec<PathBuf> = fs
+        .read_dir(&link)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .collect();
+
+    assert_eq!(entries, vec![link.join("file")]);
+}
+
+fn symlink_to_missing_node_is_broken<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let link = parent.join("link");
+
+    fs.symlink_file(parent.join("does_not_exist"), &link)
+        .unwrap();
+
+    assert!(!fs.is_file(&link));
+    assert!(!fs.is_dir(&link));
+
+    match fs.metadata(&link) {
+        Ok(_) => panic!("should be an err"),
+        Err(err) => assert_eq!(err.kind(), ErrorKind::NotFound),
+    }
+}
+
+fn symlink_with_relative_target_resolves_against_its_dir<T: SymlinkFileSystem>(
+    fs: &T,
+    parent: &Path,
+) {
+    let data = parent.join("data");
+    let releases = parent.join("releases");
+    let link = releases.join("current");
+
+    fs.create_dir(&data).unwrap();
+    fs.create_dir(&releases).unwrap();
+    fs.create_file(data.join("file"), "contents").unwrap();
+
+    fs.symlink_dir("../data", &link).unwrap();
+
+    assert_eq!(fs.read_link(&link).unwrap(), Path::new("../data"));
+    assert_eq!(fs.read_file(link.join("file")).unwrap(), b"contents");
+    assert_eq!(
+        fs.canonicalize(&link).unwrap(),
+        fs.canonicalize(&data).unwrap()
+    );
+}
+
+fn symlink_with_relative_target_resolves_after_moving<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let dir = parent.join("dir");
+    let link = parent.join("link");
+    let moved = dir.join("link");
+
+    fs.create_dir(&dir).unwrap();
+    fs.create_file(parent.join("file"), "outer").unwrap();
+    fs.create_file(dir.join("file"), "inner").unwrap();
+    fs.symlink_file("file", &link).unwrap();
+
+    fs.rename(&link, &moved).unwrap();
+
+    assert_eq!(fs.read_file(&moved).unwrap(), b"inner");
+}
+
+fn symlink_with_relative_target_resolves_through_symlinked_dir<T: SymlinkFileSystem>(
+    fs: &T,
+    parent: &Path,
+) {
+    let nested = parent.join("dir").join("nested");
+    let alias = parent.join("alias");
+
+    fs.create_dir_all(&nested).unwrap();
+    fs.create_file(parent.join("dir").join("file"), "contents")
+        .unwrap();
+    fs.symlink_file("../file", nested.join("link")).unwrap();
+    fs.symlink_dir(&nested, &alias).unwrap();
+
+    // `..` in the target leads to the parent of the directory the link is really in
+    assert_eq!(fs.read_file(alias.join("link")).unwrap(), b"contents");
+}
+
+fn read_file_fails_on_symlink_loop<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let a = parent.join("a");
+    let b = parent.join("b");
+
+    fs.symlink_file(&b, &a).unwrap();
+    fs.symlink_file(&a, &b).unwrap();
+
+    let result = fs.read_file(&a);
+
+    assert!(result.unwrap_err().is_filesystem_loop());
+}
+
+fn read_link_returns_target_of_symlink<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let original = parent.join("original");
+    let link = parent.join("link");
+
+    fs.create_file(&original, "").unwrap();
+    fs.symlink_file(&original, &link).unwrap();
+
+    let result = fs.read_link(&link);
+
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap(), original);
+}
+
+fn read_link_fails_if_node_is_not_a_symlink<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    fs.create_file(&path, "").unwrap();
+
+    let result = fs.read_link(&path);
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+}
+
+fn read_link_fails_if_node_does_not_exist<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let result = fs.read_link(parent.join("does_not_exist"));
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+fn symlink_metadata_does_not_follow_symlink<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let original = parent.join("original");
+    let link = parent.join("link");
+
+    fs.create_file(&original, "contents").unwrap();
+    fs.symlink_file(&original, &link).unwrap();
+
+    let result = fs.symlink_metadata(&link);
+
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap().file_type(), FileType::Symlink);
+    assert_eq!(fs.metadata(&link).unwrap().file_type(), FileType::File);
+}
+
+fn remove_file_removes_symlink_but_not_target<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let original = parent.join("original");
+    let link = parent.join("link");
+
+    fs.create_dir(&original).unwrap();
+    fs.symlink_dir(&original, &link).unwrap();
+
+    let result = fs.remove_file(&link);
+
+    assert!(result.is_ok());
+    assert!(fs.read_link(&link).is_err());
+    assert!(fs.is_dir(&original));
+}
+
+fn rename_renames_a_symlink<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let original = parent.join("original");
+    let link = parent.join("link");
+    let renamed = parent.join("renamed");
+
+    fs.create_file(&original, "contents").unwrap();
+    fs.symlink_file(&original, &link).unwrap();
+
+    let result = fs.rename(&link, &renamed);
+
+    assert!(result.is_ok());
+    assert!(fs.read_link(&link).is_err());
+    assert_eq!(fs.read_link(&renamed).unwrap(), original);
+    assert_eq!(fs.read_file_to_string(&renamed).unwrap(), "contents");
+}
+
+fn readonly_returns_write_permission<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("test_file");
+
+    fs.create_file(&path, "").unwrap();
+
+    let result = fs.readonly(&path);
+
+    assert!(result.is_ok());
+    assert!(!result.unwrap());
+
+    fs.set_readonly(&path, true).unwrap();
+
+    let result = fs.readonly(&path);
+
+    assert!(result.is_ok());
+    assert!(result.unwrap());
+}
+
+fn readonly_fails_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let result = fs.readonly(parent.join("does_not_exist"));
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+#[cfg(unix)]
+fn is_hidden_checks_for_leading_dot<T: FileSystem>(fs: &T, parent: &Path) {
+    fs.create_file(parent.join(".hidden"), "").unwrap();
+    fs.create_file(parent.join("visible.txt"), "").unwrap();
+    fs.create_dir(parent.join(".dir")).unwrap();
+    fs.create_file(parent.join(".dir/file"), "").unwrap();
+
+    assert!(fs.is_hidden(parent.join(".hidden")).unwrap());
+    assert!(fs.is_hidden(parent.join(".dir")).unwrap());
+    assert!(!fs.is_hidden(parent.join("visible.txt")).unwrap());
+    assert!(!fs.is_hidden(parent.join(".dir/file")).unwrap());
+}
+
+#[cfg(unix)]
+fn is_hidden_does_not_follow_symlink<T: FileSystem + SymlinkFileSystem>(fs: &T, parent: &Path) {
+    fs.create_file(parent.join(".target"), "").unwrap();
+    fs.symlink_file(parent.join(".target"), parent.join("link"))
+        .unwrap();
+    fs.symlink_file(parent.join("does_not_exist"), parent.join(".dangling"))
+        .unwrap();
+
+    assert!(!fs.is_hidden(parent.join("link")).unwrap());
+    assert!(fs.is_hidden(parent.join(".dangling")).unwrap());
+}
+
+fn is_hidden_fails_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let result = fs.is_hidden(parent.join("does_not_exist"));
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+fn set_readonly_toggles_write_permission_of_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("test_file");
+
+    fs.create_file(&path, "").unwrap();
+
+    let result = fs.set_readonly(&path, true);
+
+    assert!(result.is_ok());
+    assert!(fs.write_file(&path, "readonly").is_err());
+
+    let result = fs.set_readonly(&path, false);
+
+    assert!(result.is_ok());
+    assert!(fs.write_file(&path, "no longer readonly").is_ok());
+}
+
+fn set_readonly_toggles_write_permission_of_dir<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("test_dir");
+
+    fs.create_dir(&path).unwrap();
+
+    let result = fs.set_readonly(&path, true);
+
+    assert!(result.is_ok());
+    assert!(fs.write_file(path.join("file"), "").is_err());
+
+    let result = fs.set_readonly(&path, false);
+
+    assert!(result.is_ok());
+    assert!(fs.write_file(path.join("file"), "").is_ok());
+}
+
+fn set_readonly_fails_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let result = fs.set_readonly(parent.join("does_not_exist"), true);
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+
+    let result = fs.set_readonly(parent.join("does_not_exist"), true);
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+fn modified_is_updated_by_write<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+    let past = UNIX_EPOCH + Duration::from_secs(1_000_000);
+
+    fs.create_file(&path, "").unwrap();
+    fs.set_modified(&path, past).unwrap();
+    fs.write_file(&path, "contents").unwrap();
+
+    let result = fs.modified(&path);
+
+    assert!(result.is_ok());
+    assert!(result.unwrap() > past);
+}
+
+fn modified_fails_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let result = fs.modified(parent.join("does_not_exist"));
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+fn set_modified_sets_modification_time<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+    let time = UNIX_EPOCH + Duration::from_secs(1_000_000);
+
+    fs.create_file(&path, "").unwrap();
+
+    let result = fs.set_modified(&path, time);
+
+    assert!(result.is_ok());
+    assert_eq!(fs.modified(&path).unwrap(), time);
+    assert_eq!(fs.metadata(&path).unwrap().modified().unwrap(), time);
+}
+
+fn set_modified_sets_modification_time_of_dir<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("dir");
+    let time = UNIX_EPOCH + Duration::from_secs(1_000_000);
+
+    fs.create_dir(&path).unwrap();
+
+    let result = fs.set_modified(&path, time);
+
+    assert!(result.is_ok());
+    assert_eq!(fs.modified(&path).unwrap(), time);
+}
+
+fn set_modified_fails_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let result = fs.set_modified(parent.join("does_not_exist"), UNIX_EPOCH);
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+fn set_accessed_sets_access_time<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+    let time = UNIX_EPOCH + Duration::from_secs(1_000_000);
+
+    fs.create_file(&path, "").unwrap();
+
+    let modified = fs.modified(&path).unwrap();
+    let result = fs.set_accessed(&path, time);
+
+    assert!(result.is_ok());
+    assert_eq!(fs.accessed(&path).unwrap(), time);
+    assert_eq!(fs.modified(&path).unwrap(), modified);
+}
+
+fn set_accessed_fails_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let result = fs.set_accessed(parent.join("does_not_exist"), UNIX_EPOCH);
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+fn len_returns_size_of_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+    let result = fs.create_file(&path, "");
+
+    assert!(result.is_ok());
+
+    let len = fs.len(&path);
+
+    assert_eq!(len, 0);
+
+    let result = fs.write_file(&path, "contents");
+
+    assert!(result.is_ok());
+
+    let len = fs.len(&path);
+
+    assert_eq!(len, 8);
+}
+
+fn len_returns_size_of_directory<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("directory");
+    let result = fs.create_dir(&path);
+
+    assert!(result.is_ok());
+
+    let len = fs.len(&path);
+
+    assert_ne!(len, 0);
+}
+
+fn len_returns_0_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("does-not-exist");
+    let len = fs.len(&path);
+
+    assert_eq!(len, 0);
+}
+
+fn dir_size_adds_up_lengths_of_descendant_files<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("dir");
+    let nested = path.join("nested");
+
+    fs.create_dir_all(&nested).unwrap();
+    fs.create_dir(path.join("empty")).unwrap();
+    fs.create_file(path.join("file"), "abc").unwrap();
+    fs.create_file(nested.join("file"), "defgh").unwrap();
+
+    assert_eq!(fs.dir_size(&path, false).unwrap(), 8);
+    assert_eq!(fs.dir_size(&nested, false).unwrap(), 5);
+}
+
+fn dir_size_follows_symlinks_if_enabled<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("dir");
+    let outside = parent.join("outside");
+
+    fs.create_dir(&path).unwrap();
+    fs.create_dir(&outside).unwrap();
+    fs.create_file(path.join("file"), "abc").unwrap();
+    fs.create_file(parent.join("file"), "defgh").unwrap();
+    fs.create_file(outside.join("file"), "ij").unwrap();
+    fs.symlink_file(parent.join("file"), path.join("file_link"))
+        .unwrap();
+    fs.symlink_dir(&outside, path.join("dir_link")).unwrap();
+
+    assert_eq!(fs.dir_size(&path, false).unwrap(), 3);
+    assert_eq!(fs.dir_size(&path, true).unwrap(), 10);
+}
+
+fn dir_size_fails_on_symlink_loop_if_following<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("dir");
+
+    fs.create_dir(&path).unwrap();
+    fs.create_file(path.join("file"), "abc").unwrap();
+    fs.symlink_dir(&path, path.join("link")).unwrap();
+
+    assert_eq!(fs.dir_size(&path, false).unwrap(), 3);
+    assert!(fs.dir_size(&path, true).unwrap_err().is_filesystem_loop());
+}
+
+fn dir_size_fails_if_node_is_a_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    fs.create_file(&path, "contents").unwrap();
+
+    let result = fs.dir_size(&path, false);
+
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotADirectory);
+}
+
+fn dir_size_fails_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let result = fs.dir_size(parent.join("does_not_exist"), false);
+
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+fn available_space_is_at_most_total_space<T: FileSystem>(fs: &T, parent: &Path) {
+    let available = fs.available_space(parent).unwrap();
+    let total = fs.total_space(parent).unwrap();
+
+    assert!(total > 0);
+    assert!(available <= total);
+}
+
+fn available_space_fails_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("does-not-exist");
+
+    let result = fs.available_space(&path);
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+
+    let result = fs.total_space(&path);
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+#[cfg(unix)]
+fn mode_returns_permissions<T: FileSystem + UnixFileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    fs.create_file(&path, "").unwrap();
+    fs.set_mode(&path, 0o644).unwrap();
+
+    let result = fs.mode(&path);
+
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap() % 0o100_000, 0o644);
+
+    fs.set_mode(&path, 0o600).unwrap();
+
+    let result = fs.mode(&path);
+
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap() % 0o100_000, 0o600);
+
+    fs.set_readonly(&path, true).unwrap();
+
+    let result = fs.mode(&path);
+
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap() % 0o100_000, 0o400);
+}
+
+#[cfg(unix)]
+fn mode_fails_if_node_does_not_exist<T: UnixFileSystem>(fs: &T, parent: &Path) {
+    let result = fs.mode(parent.join("does_not_exist"));
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+#[cfg(unix)]
+fn set_mode_sets_permissions<T: FileSystem + UnixFileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    fs.create_file(&path, "").unwrap();
+
+    let result = fs.set_mode(&path, 0o000);
+
+    assert!(result.is_ok());
+
+    let readonly_result = fs.readonly(&path);
+
+    assert!(readonly_result.is_ok());
+    assert!(readonly_result.unwrap());
+
+    let read_result = fs.read_file(&path);
+    let write_result = fs.write_file(&path, "should not be allowed");
+
+    assert!(read_result.is_err());
+    assert!(write_result.is_err());
+    assert_eq!(read_result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+    assert_eq!(
+        write_result.unwrap_err().kind(),
+        ErrorKind::PermissionDenied
+    );
+
+    let result = fs.set_mode(&path, 0o200);
+
+    assert!(result.is_ok());
+
+    let read_result = fs.read_file(&path);
+    let write_result = fs.write_file(&path, "should be allowed");
+
+    assert!(read_result.is_err());
+    assert!(write_result.is_ok());
+    assert_eq!(read_result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+
+    let readonly_result = fs.readonly(&path);
+
+    assert!(readonly_result.is_ok());
+    assert!(!readonly_result.unwrap());
+
+    let result = fs.set_mode(&path, 0o644);
+
+    assert!(result.is_ok());
+
+    let readonly_result = fs.readonly(&path);
+
+    assert!(readonly_result.is_ok());
+    assert!(!readonly_result.unwrap());
+}
+
+#[cfg(unix)]
+fn set_mode_fails_if_node_does_not_exist<T: UnixFileSystem>(fs: &T, parent: &Path) {
+    let result = fs.set_mode(parent.join("does_not_exist"), 0o644);
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+#[cfg(unix)]
+fn owner_returns_owner_of_new_node<T: FileSystem + UnixFileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    fs.create_file(&path, "").unwrap();
+
+    let result = fs.owner(&path);
+
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap(), fs.owner(parent).unwrap());
+}
+
+#[cfg(unix)]
+fn owner_fails_if_node_does_not_exist<T: UnixFileSystem>(fs: &T, parent: &Path) {
+    let result = fs.owner(parent.join("does_not_exist"));
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+#[cfg(unix)]
+fn set_owner_sets_owner<T: FileSystem + UnixFileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    fs.create_file(&path, "").unwrap();
+
+    // Without root, a file can only be given to its current owner
+    let (uid, gid) = fs.owner(&path).unwrap();
+    let result = fs.set_owner(&path, uid, gid);
+
+    assert!(result.is_ok());
+    assert_eq!(fs.owner(&path).unwrap(), (uid, gid));
+}
+
+#[cfg(unix)]
+fn set_owner_fails_if_node_does_not_exist<T: UnixFileSystem>(fs: &T, parent: &Path) {
+    let result = fs.set_owner(parent.join("does_not_exist"), 0, 0);
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+#[cfg(unix)]
+fn create_file_with_mode_sets_mode<T: FileSystem + UnixFileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("secret");
+
+    let result = fs.create_file_with_mode(&path, "contents", 0o600);
+
+    assert!(result.is_ok());
+    assert_eq!(fs.read_file(&path).unwrap(), b"contents");
+    assert_eq!(fs.mode(&path).unwrap() & 0o777, 0o600);
+}
+
+#[cfg(unix)]
+fn create_file_with_mode_fails_if_file_exists<T: FileSystem + UnixFileSystem>(
+    fs: &T,
+    parent: &Path,
+) {
+    let path = parent.join("secret");
+
+    fs.create_file(&path, "").unwrap();
+
+    let result = fs.create_file_with_mode(&path, "contents", 0o600);
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::AlreadyExists);
+    assert_eq!(fs.read_file(&path).unwrap(), b"");
+}
+
+#[cfg(unix)]
+fn create_dir_with_mode_sets_mode<T: FileSystem + UnixFileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("private");
+
+    let result = fs.create_dir_with_mode(&path, 0o700);
+
+    assert!(result.is_ok());
+    assert!(fs.is_dir(&path));
+    assert_eq!(fs.mode(&path).unwrap() & 0o777, 0o700);
+}
+
+#[cfg(unix)]
+fn create_dir_with_mode_fails_if_parent_does_not_exist<T: UnixFileSystem>(fs: &T, parent: &Path) {
+    let result = fs.create_dir_with_mode(parent.join("does_not_exist/private"), 0o700);
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+#[cfg(unix)]
+fn mkfifo_creates_fifo<T: FileSystem + UnixFileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("fifo");
+
+    let result = fs.mkfifo(&path, 0o600);
+
+    assert!(result.is_ok());
+    assert_eq!(fs.metadata(&path).unwrap().file_type(), FileType::Fifo);
+    assert!(!fs.is_file(&path));
+    assert!(!fs.is_dir(&path));
+    assert_eq!(fs.mode(&path).unwrap() & 0o777, 0o600);
+
+    let entry = fs.read_dir(parent).unwrap().next().unwrap().unwrap();
+
+    assert_eq!(entry.file_type().unwrap(), FileType::Fifo);
+}
+
+#[cfg(unix)]
+fn mkfifo_fails_if_node_exists<T: FileSystem + UnixFileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("fifo");
+
+    fs.create_file(&path, "").unwrap();
+
+    let result = fs.mkfifo(&path, 0o600);
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::AlreadyExists);
+}
+
+#[cfg(unix)]
+fn mknod_fails_if_type_is_not_special<T: FileSystem + UnixFileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    let result = fs.mknod(&path, FileType::File, 0o644, 0);
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert!(!fs.exists(&path));
+}
+
+#[cfg(unix)]
+fn copy_dir_recreates_fifos<T: FileSystem + UnixFileSystem>(fs: &T, parent: &Path) {
+    let from = parent.join("from");
+    let to = parent.join("to");
+
+    fs.create_dir(&from).unwrap();
+    fs.mkfifo(from.join("fifo"), 0o600).unwrap();
+
+    let result = fs.copy_dir(&from, &to);
+
+    assert!(result.is_ok());
+    assert_eq!(
+        fs.metadata(to.join("fifo")).unwrap().file_type(),
+        FileType::Fifo
+    );
+}
+
+#[cfg(windows)]
+fn attributes_returns_set_attributes<T: WindowsFileSystem>(fs: &T, parent: &Path) {
+    let hidden_and_system = 0x2 | 0x4;
+    let path = parent.join("file");
+
+    fs.create_file(&path, "").unwrap();
+
+    let result = fs.set_attributes(&path, hidden_and_system);
+
+    assert!(result.is_ok());
+
+    let result = fs.attributes(&path);
+
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap() & hidden_and_system, hidden_and_system);
+
+    fs.set_attributes(&path, 0).unwrap();
+
+    assert_eq!(fs.attributes(&path).unwrap() & hidden_and_system, 0);
+}
+
+#[cfg(windows)]
+fn attributes_include_directory_and_readonly<T: WindowsFileSystem>(fs: &T, parent: &Path) {
+    let readonly = 0x1;
+    let directory = 0x10;
+    let dir = parent.join("dir");
+    let file = parent.join("file");
+
+    fs.create_dir(&dir).unwrap();
+    fs.create_file(&file, "").unwrap();
+
+    assert_eq!(fs.attributes(&dir).unwrap() & directory, directory);
+    assert_eq!(fs.attributes(&file).unwrap() & directory, 0);
+
+    fs.set_attributes(&file, readonly).unwrap();
+
+    assert!(fs.readonly(&file).unwrap());
+
+    fs.set_readonly(&file, false).unwrap();
+
+    assert_eq!(fs.attributes(&file).unwrap() & readonly, 0);
+}
+
+#[cfg(windows)]
+fn attributes_fails_if_node_does_not_exist<T: WindowsFileSystem>(fs: &T, parent: &Path) {
+    let result = fs.attributes(parent.join("does_not_exist"));
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+#[cfg(windows)]
+fn create_junction_links_to_dir<T: WindowsFileSystem>(fs: &T, parent: &Path) {
+    let original = parent.join("original");
+    let junction = parent.join("junction");
+
+    fs.create_dir(&original).unwrap();
+    fs.create_file(original.join("file"), "contents").unwrap();
+
+    let result = fs.create_junction(&original, &junction);
+
+    assert!(result.is_ok());
+    assert!(fs.is_dir(&junction));
+    assert_eq!(
+        fs.read_file_to_string(junction.join("file")).unwrap(),
+        "contents"
+    );
+}
+
+#[cfg(windows)]
+fn create_junction_fails_if_node_already_exists<T: WindowsFileSystem>(fs: &T, parent: &Path) {
+    let original = parent.join("original");
+    let junction = parent.join("junction");
+
+    fs.create_dir(&original).unwrap();
+    fs.create_dir(&junction).unwrap();
+
+    let result = fs.create_junction(&original, &junction);
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::AlreadyExists);
+}
+
+fn temp_dir_creates_tempdir<T: FileSystem + TempFileSystem>(fs: &T, _: &Path) {
+    let path = {
+        let result = fs.temp_dir("test");
+
+        assert!(result.is_ok());
+
+        let temp_dir = result.unwrap();
+
+        assert!(fs.is_dir(temp_dir.path()));
+
+        temp_dir.path().to_path_buf()
+    };
+
+    assert!(!fs.is_dir(&path));
+    assert!(fs.is_dir(path.parent().unwrap()));
+}
+
+fn temp_dir_creates_unique_dir<T: FileSystem + TempFileSystem>(fs: &T, _: &Path) {
+    let first = fs.temp_dir("test").unwrap();
+    let second = fs.temp_dir("test").unwrap();
+
+    assert_ne!(first.path(), second.path());
+}
+
+fn temp_dir_into_path_keeps_dir<T: FileSystem + TempFileSystem>(fs: &T, _: &Path) {
+    let temp_dir = fs.temp_dir("test").unwrap();
+
+    fs.create_file(temp_dir.path().join("file"), "contents")
+        .unwrap();
+
+    let path = temp_dir.into_path();
+
+    assert!(fs.is_dir(&path));
+    assert_eq!(
+        fs.read_file_to_string(path.join("file")).unwrap(),
+        "contents"
+    );
+
+    fs.remove_dir_all(&path).unwrap();
+}
+
+fn temp_file_creates_empty_file<T: FileSystem + TempFileSystem>(fs: &T, _: &Path) {
+    let path = {
+        let result = fs.temp_file("test");
+
+        assert!(result.is_ok());
+
+        let temp_file = result.unwrap();
+
+        assert!(fs.is_file(temp_file.path()));
+        assert_eq!(fs.read_file(temp_file.path()).unwrap(), b"");
+        assert!(temp_file
+            .path()
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("test"));
+
+        temp_file.path().to_path_buf()
+    };
+
+    assert!(!fs.exists(&path));
+    assert!(fs.is_dir(path.parent().unwrap()));
+}
+
+fn temp_file_creates_unique_file<T: FileSystem + TempFileSystem>(fs: &T, _: &Path) {
+    let first = fs.temp_file("test").unwrap();
+    let second = fs.temp_file("test").unwrap();
+
+    assert_ne!(first.path(), second.path());
+}
+
+fn temp_dir_in_creates_dir_inside_base<T: FileSystem + TempFileSystem>(fs: &T, parent: &Path) {
+    let path = {
+        let temp_dir = fs.temp_dir_in(parent, "test").unwrap();
+        let name = temp_dir.path().file_name().unwrap().to_string_lossy();
+
+        assert!(fs.is_dir(temp_dir.path()));
+        assert_eq!(temp_dir.path().parent().unwrap(), parent);
+        assert!(name.starts_with("test"));
+
+        temp_dir.path().to_path_buf()
+    };
+
+    assert!(!fs.exists(&path));
+}
+
+fn temp_dir_in_fails_if_base_does_not_exist<T: FileSystem + TempFileSystem>(fs: &T, parent: &Path) {
+    let result = fs.temp_dir_in(parent.join("base"), "test");
+
+    match result {
+        Ok(_) => panic!("should be an err"),
+        Err(err) => assert_eq!(err.kind(), ErrorKind::NotFound),
+    }
+}
+
+fn temp_file_with_options_uses_prefix_and_suffix<T: FileSystem + TempFileSystem>(
+    fs: &T,
+    parent: &Path,
+) {
+    let mut options = TempOptions::new();
+
+    options.prefix("test").suffix(".txt");
+
+    let temp_file = fs.temp_file_with_options(parent, &options).unwrap();
+    let name = temp_file.path().file_name().unwrap().to_string_lossy();
+
+    assert!(fs.is_file(temp_file.path()));
+    assert_eq!(temp_file.path().parent().unwrap(), parent);
+    assert!(name.starts_with("test"));
+    assert!(name.ends_with(".txt"));
+}
+
+#[cfg(unix)]
+fn temp_dir_with_options_sets_mode<T>(fs: &T, parent: &Path)
+where
+    T: FileSystem + TempFileSystem + UnixFileSystem,
+{
+    let mut options = TempOptions::new();
+
+    options.mode(0o700);
+
+    let temp_dir = fs.temp_dir_with_options(parent, &options).unwrap();
+
+    assert_eq!(fs.mode(temp_dir.path()).unwrap() & 0o777, 0o700);
+}
+
+fn errors_include_operation_and_paths<T: FileSystem>(fs: &T, parent: &Path) {
+    let (from, to) = (parent.join("from"), parent.join("to"));
+
+    let err = fs.read_file(&from).unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert_eq!(err.operation(), Some("read_file"));
+    assert_eq!(err.paths(), [parent.join("from")]);
+    assert!(err
+        .to_string()
+        .starts_with(&format!("read_file {:?}:", from)));
+
+    let err = fs.rename(&from, &to).unwrap_err();
+
+    assert_eq!(err.operation(), Some("rename"));
+    assert_eq!(err.paths(), [from, to]);
+}
+
+fn errors_convert_into_io_errors<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("does_not_exist");
+    let err = io::Error::from(fs.remove_file(&path).unwrap_err());
+
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert!(err.to_string().contains(&format!("{:?}", path)));
+}
+
+fn dyn_file_system_delegates_to_inner_file_system<T>(fs: &T, parent: &Path)
+where
+    T: FileSystem + Clone + 'static,
+{
+    let path = parent.join("file");
+    let boxed: Box<dyn DynFileSystem> = Box::new(fs.clone());
+
+    let result = (*boxed).create_file(&path, b"contents");
+
+    assert!(result.is_ok());
+    assert!(fs.is_file(&path));
+    assert_eq!((*boxed).read_file(&path).unwrap(), b"contents");
+    assert_eq!(FileSystem::len(&boxed, &path), 8);
+}
+
+fn dyn_file_system_read_dir_returns_dir_entries<T>(fs: &T, parent: &Path)
+where
+    T: FileSystem + Clone + 'static,
+{
+    let file = parent.join("file");
+    let dir = parent.join("dir");
+
+    fs.create_file(&file, "").unwrap();
+    fs.create_dir(&dir).unwrap();
+
+    let boxed: Box<dyn DynFileSystem> = Box::new(fs.clone());
+    let result = FileSystem::read_dir(&boxed, parent);
+
+    assert!(result.is_ok());
+
+    let mut entries: Vec<PathBuf> = result.unwrap().map(|e| e.unwrap().path()).collect();
+    let expected_paths = &mut [file, dir];
+
+    entries.sort();
+    expected_paths.sort();
+
+    assert_eq!(&entries, expected_paths);
+}
+
+#[cfg(feature = "async")]
+fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+#[cfg(feature = "async")]
+fn async_file_system_writes_and_reads_files<T: AsyncFileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    let result = block_on(AsyncFileSystem::create_file(fs, &path, "test"));
+
+    assert!(result.is_ok());
+    assert!(FileSystem::is_file(fs, &path));
+
+    block_on(AsyncFileSystem::write_file(fs, &path, "new contents")).unwrap();
+
+    assert_eq!(
+        block_on(AsyncFileSystem::read_file(fs, &path)).unwrap(),
+        br"new contents"
+    );
+    assert_eq!(
+        block_on(AsyncFileSystem::read_file_to_string(fs, &path)).unwrap(),
+        "new contents"
+    );
+    assert_eq!(block_on(AsyncFileSystem::len(fs, &path)), 12);
+}
+
+#[cfg(feature = "async")]
+fn async_file_system_creates_and_reads_dirs<T: AsyncFileSystem>(fs: &T, parent: &Path) {
+    let dir = parent.join("dir");
+    let nested = dir.join("nested");
+
+    let result = block_on(AsyncFileSystem::create_dir_all(fs, &nested));
+
+    assert!(result.is_ok());
+    assert!(block_on(AsyncFileSystem::is_dir(fs, &nested)));
+
+    let entries: Vec<PathBuf> = block_on(AsyncFileSystem::read_dir(fs, &dir))
+        .unwrap()
+        .iter()
+        .map(DirEntry::path)
+        .collect();
+
+    assert_eq!(entries, vec![nested.clone()]);
+
+    block_on(AsyncFileSystem::remove_dir_all(fs, &dir)).unwrap();
+
+    assert!(!FileSystem::is_dir(fs, &dir));
+}
+
+#[cfg(feature = "async")]
+fn async_file_system_fails_if_file_does_not_exist<T: AsyncFileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("does_not_exist");
+
+    let result = block_on(AsyncFileSystem::read_file(fs, &path));
+
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+#[cfg(feature = "assertions")]
+fn assertion_failure<F: FnOnce()>(f: F) -> String {
+    let payload = panic::catch_unwind(AssertUnwindSafe(f)).unwrap_err();
+
+    *payload.downcast::<String>().unwrap()
+}
+
+#[cfg(feature = "assertions")]
+fn assert_passes_if_nodes_match<T: SymlinkFileSystem>(fs: &T, parent: &Path) {
+    let empty = parent.join("empty");
+    let file = parent.join("file");
+    let link = parent.join("link");
+
+    fs.create_dir(&empty).unwrap();
+    fs.create_file(&file, "key = 1\n").unwrap();
+    fs.symlink_file("file", &link).unwrap();
+
+    fs.assert(parent).exists().is_dir();
+    fs.assert(&empty).is_empty_dir();
+    fs.assert(&file)
+        .is_file()
+        .has_content("key = 1\n")
+        .contains("key")
+        .has_len(8);
+    fs.assert(&link)
+        .is_symlink()
+        .points_to("file")
+        .has_content("key = 1\n");
+    fs.assert(parent.join("missing")).does_not_exist();
+}
+
+#[cfg(all(feature = "assertions", unix))]
+fn assert_has_mode_passes_if_mode_matches<T: FileSystem + UnixFileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    fs.create_file(&path, "").unwrap();
+    fs.set_mode(&path, 0o600).unwrap();
+
+    fs.assert(&path).has_mode(0o600);
+}
+
+#[cfg(feature = "assertions")]
+fn assert_has_content_fails_if_content_differs<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    fs.create_file(&path, "key = 1\n").unwrap();
+
+    let message = assertion_failure(|| {
+        fs.assert(&path).has_content("key = 2");
+    });
+
+    assert_eq!(
+        message,
+        format!(
+            "expected {} to contain \"key = 2\", but it contains \"key = 1\\n\"",
+            path.display()
+        )
+    );
+}
+
+#[cfg(feature = "assertions")]
+fn assert_exists_fails_if_node_does_not_exist<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("missing");
+
+    let message = assertion_failure(|| {
+        fs.assert(&path).exists();
+    });
+
+    assert!(message.starts_with(&format!("expected {} to exist, but", path.display())));
+}
+
+#[cfg(feature = "assertions")]
+fn assert_is_dir_fails_if_node_is_a_file<T: FileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    fs.create_file(&path, "").unwrap();
+
+    let message = assertion_failure(|| {
+        fs.assert(&path).is_dir();
+    });
+
+    assert_eq!(
+        message,
+        format!(
+            "expected {} to be a directory, but it is a File",
+            path.display()
+        )
+    );
+}
+
+#[cfg(feature = "assertions")]
+fn assert_is_empty_dir_fails_if_dir_has_entries<T: FileSystem>(fs: &T, parent: &Path) {
+    fs.create_dir(parent.join("dir")).unwrap();
+    fs.create_file(parent.join("file"), "").unwrap();
+
+    let message = assertion_failure(|| {
+        fs.assert(parent).is_empty_dir();
+    });
+
+    assert_eq!(
+        message,
+        format!(
+            "expected {} to be empty, but it contains [\"dir\", \"file\"]",
+            parent.display()
+        )
+    );
+}
+
+#[cfg(all(feature = "assertions", unix))]
+fn assert_has_mode_fails_if_mode_differs<T: FileSystem + UnixFileSystem>(fs: &T, parent: &Path) {
+    let path = parent.join("file");
+
+    fs.create_file(&path, "").unwrap();
+    fs.set_mode(&path, 0o644).unwrap();
+
+    let message = assertion_failure(|| {
+        fs.assert(&path).has_mode(0o600);
+    });
+
+    assert_eq!(
+        message,
+        format!(
+            "expected {} to have mode 600, but it has 644",
+            path.display()
+        )
+    );
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn fake_async_operations_run_when_awaited_after_latency() {
+    let latency = Duration::from_millis(20);
+    let fs = FakeFileSystem::new().with_async_latency(latency);
+    let future = AsyncFileSystem::create_dir(&fs, "/dir");
+
+    assert!(!FileSystem::is_dir(&fs, "/dir"));
+
+    let start = Instant::now();
+
+    block_on(future).unwrap();
+
+    assert!(start.elapsed() >= latency);
+    assert!(FileSystem::is_dir(&fs, "/dir"));
+}
+
+mod fake_capacity {
+    use std::io::{ErrorKind, Write};
+
+    use filesystem::{FakeFileSystem, FileSystem, OpenOptions};
+
+    #[test]
+    fn fails_writes_that_exceed_capacity() {
+        let fs = FakeFileSystem::with_capacity(10);
+
+        fs.create_file("/file", "12345").unwrap();
+
+        let result = fs.create_file("/other", "123456");
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::StorageFull);
+        assert!(!fs.is_file("/other"));
+
+        let result = fs.write_file("/file", "12345678901");
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::StorageFull);
+        assert_eq!(fs.read_file_to_string("/file").unwrap(), "12345");
+    }
+
+    #[test]
+    fn counts_space_freed_by_writes_and_removals() {
+        let fs = FakeFileSystem::with_capacity(10);
+
+        fs.create_file("/file", "1234567890").unwrap();
+        fs.overwrite_file("/file", "12345").unwrap();
+        fs.create_file("/other", "12345").unwrap();
+        fs.remove_file("/file").unwrap();
+
+        assert!(fs.write_file("/file", "12345").is_ok());
+    }
+
+    #[test]
+    fn fails_open_file_writes_that_exceed_capacity() {
+        let fs = FakeFileSystem::with_capacity(4);
+        let mut file = fs
+            .open_with_options("/file", OpenOptions::new().write(true).create(true))
+            .unwrap();
+
+        file.write_all(b"1234").unwrap();
+
+        let err = file.write_all(b"5").unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::StorageFull);
+        assert_eq!(fs.read_file("/file").unwrap(), b"1234");
+    }
+
+    #[test]
+    fn reports_space_left_in_capacity() {
+        let fs = FakeFileSystem::with_capacity(10);
+
+        fs.create_dir("/dir").unwrap();
+        fs.create_file("/dir/file", "1234").unwrap();
+
+        assert_eq!(fs.total_space("/dir").unwrap(), 10);
+        assert_eq!(fs.available_space("/dir").unwrap(), 6);
+    }
+
+    #[test]
+    fn counts_space_of_copied_moved_and_removed_dirs() {
+        let fs = FakeFileSystem::with_capacity(10);
+
+        fs.create_dir("/dir").unwrap();
+        fs.create_file("/dir/file", "1234").unwrap();
+        fs.copy_dir("/dir", "/copy").unwrap();
+        fs.rename("/copy", "/moved").unwrap();
+
+        assert_eq!(fs.available_space("/").unwrap(), 2);
+
+        fs.remove_dir_all("/dir").unwrap();
+
+        assert_eq!(fs.available_space("/").unwrap(), 6);
+    }
+
+    #[test]
+    fn reports_unlimited_space_without_capacity() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "1234").unwrap();
+
+        assert_eq!(fs.total_space("/").unwrap(), u64::MAX);
+        assert_eq!(fs.available_space("/").unwrap(), u64::MAX - 4);
+    }
+}
+
+mod fake_memory {
+    use std::io::{ErrorKind, Seek, SeekFrom, Write};
+
+    use filesystem::{FakeFileSystem, FileSystem, OpenFile, OpenOptions};
+
+    #[test]
+    fn reports_memory_used_by_nodes_and_contents() {
+        let fs = FakeFileSystem::new();
+        let empty = fs.memory_usage();
+
+        fs.create_dir("/dir").unwrap();
+
+        let with_dir = fs.memory_usage();
+
+        fs.write_file("/dir/file", vec![0; 1 << 20]).unwrap();
+
+        assert!(with_dir > empty);
+        assert!(fs.memory_usage() >= with_dir + (1 << 20));
+
+        fs.remove_dir_all("/dir").unwrap();
+
+        assert_eq!(fs.memory_usage(), empty);
+    }
+
+    #[test]
+    fn counts_shared_contents_once() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/dir").unwrap();
+        fs.write_file("/dir/file", vec![0; 1 << 20]).unwrap();
+
+        let before = fs.memory_usage();
+
+        fs.copy_dir("/dir", "/copy").unwrap();
+
+        assert!(fs.memory_usage() < before + (1 << 20));
+        assert_eq!(fs.fork().memory_usage(), fs.memory_usage());
+    }
+
+    #[test]
+    fn does_not_store_ancestors_in_every_path() {
+        let fs = FakeFileSystem::new();
+        let deep = "/a-long-directory-name".repeat(50);
+
+        fs.create_dir_all(&deep).unwrap();
+
+        let before = fs.memory_usage();
+
+        for i in 0..100 {
+            fs.create_file(format!("{}/{}", deep, i), "").unwrap();
+        }
+
+        let per_file = (fs.memory_usage() - before) / 100;
+
+        assert!(per_file < deep.len(), "{} bytes per file", per_file);
+    }
+
+    #[test]
+    fn counts_repeated_names_once() {
+        let fs = FakeFileSystem::new();
+        let name = "a-name-that-many-directories-contain".repeat(10);
+
+        fs.create_dir("/0").unwrap();
+        fs.create_file(format!("/0/{}", name), "").unwrap();
+
+        let first = fs.memory_usage();
+
+        for i in 1..=100 {
+            fs.create_dir(format!("/{}", i)).unwrap();
+            fs.create_file(format!("/{}/{}", i, name), "").unwrap();
+        }
+
+        let per_dir = (fs.memory_usage() - first) / 100;
+
+        assert!(per_dir < name.len(), "{} bytes per directory", per_dir);
+    }
+
+    #[test]
+    fn fails_writes_that_exceed_the_limit() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "contents").unwrap();
+        fs.set_memory_limit(fs.memory_usage() + 1000);
+
+        let result = fs.write_file("/file", vec![0; 2000]);
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::OutOfMemory);
+        assert_eq!(fs.read_file_to_string("/file").unwrap(), "contents");
+
+        let mut file = fs
+            .open_with_options("/file", OpenOptions::new().write(true))
+            .unwrap();
+
+        file.seek(SeekFrom::Start(1 << 40)).unwrap();
+
+        assert_eq!(
+            file.write(b"far").unwrap_err().kind(),
+            ErrorKind::OutOfMemory
+        );
+        assert_eq!(
+            file.set_len(1 << 40).unwrap_err().kind(),
+            ErrorKind::OutOfMemory
+        );
+        assert_eq!(fs.len("/file"), 8);
+    }
+
+    #[test]
+    fn fails_writes_past_the_largest_file_that_exceed_the_limit() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "contents").unwrap();
+        fs.set_memory_limit(fs.memory_usage() + 1000);
+
+        let mut file = fs
+            .open_with_options("/file", OpenOptions::new().write(true))
+            .unwrap();
+
+        file.seek(SeekFrom::Start(u64::MAX)).unwrap();
+
+        assert_eq!(
+            file.write(b"far").unwrap_err().kind(),
+            ErrorKind::OutOfMemory
+        );
+        assert_eq!(
+            file.set_len(u64::MAX).unwrap_err().kind(),
+            ErrorKind::OutOfMemory
+        );
+        assert_eq!(fs.len("/file"), 8);
+    }
+
+    #[test]
+    fn frees_the_memory_of_removed_nodes() {
+        let fs = FakeFileSystem::new();
+
+        fs.set_memory_limit(fs.memory_usage() + 10_000);
+
+        for _ in 0..10 {
+            fs.create_dir("/dir").unwrap();
+            fs.write_file("/dir/file", vec![0; 4_000]).unwrap();
+            fs.rename("/dir/file", "/file").unwrap();
+            fs.write_file("/file", vec![0; 8_000]).unwrap();
+            fs.remove_file("/file").unwrap();
+            fs.remove_dir("/dir").unwrap();
+        }
+    }
+
+    #[test]
+    fn fails_to_create_nodes_that_exceed_the_limit() {
+        let fs = FakeFileSystem::new();
+
+        fs.set_memory_limit(fs.memory_usage() + 10_000);
+
+        let created = (0..1_000)
+            .take_while(|i| fs.create_dir(format!("/{}", i)).is_ok())
+            .count();
+
+        assert!(created > 0 && created < 1_000);
+        assert_eq!(
+            fs.create_file("/file", "").unwrap_err().kind(),
+            ErrorKind::OutOfMemory
+        );
+        assert!(fs.memory_usage() <= 10_000 + FakeFileSystem::new().memory_usage());
+    }
+}
+
+mod fake_read_dir {
+    use std::path::PathBuf;
+
+    use filesystem::{DirEntry, FakeFileSystem, FileSystem};
+
+    fn paths(fs: &FakeFileSystem, path: &str) -> Vec<PathBuf> {
+        fs.read_dir(path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect()
+    }
+
+    #[test]
+    fn entries_are_sorted_by_name() {
+        let fs = FakeFileSystem::new();
+
+        for name in &["c", "a", "b"] {
+            fs.create_file(format!("/{}", name), "").unwrap();
+        }
+
+        assert_eq!(
+            paths(&fs, "/"),
+            vec![
+                PathBuf::from("/a"),
+                PathBuf::from("/b"),
+                PathBuf::from("/c")
+            ]
+        );
+    }
+
+    #[test]
+    fn entries_removed_before_they_are_reached_are_skipped() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/a", "").unwrap();
+        fs.create_file("/b", "").unwrap();
+
+        let mut entries = fs.read_dir("/").unwrap();
+
+        assert_eq!(entries.next().unwrap().unwrap().path(), PathBuf::from("/a"));
+
+        fs.remove_file("/b").unwrap();
+
+        assert!(entries.next().is_none());
+    }
+
+    #[test]
+    fn renamed_dirs_keep_their_children() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir_all("/from/nested").unwrap();
+        fs.create_file("/from/nested/file", "").unwrap();
+        fs.create_file("/from/file", "").unwrap();
+        fs.rename("/from", "/to").unwrap();
+
+        assert_eq!(paths(&fs, "/"), vec![PathBuf::from("/to")]);
+        assert_eq!(
+            paths(&fs, "/to"),
+            vec![PathBuf::from("/to/file"), PathBuf::from("/to/nested")]
+        );
+        assert_eq!(
+            paths(&fs, "/to/nested"),
+            vec![PathBuf::from("/to/nested/file")]
+        );
+    }
+
+    #[test]
+    fn removed_dirs_take_their_descendants_with_them() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir_all("/dir/nested").unwrap();
+        fs.create_file("/dir/nested/file", "").unwrap();
+        fs.remove_dir_all("/dir").unwrap();
+        fs.create_dir("/dir").unwrap();
+
+        assert!(paths(&fs, "/dir").is_empty());
+        assert!(!fs.exists("/dir/nested/file"));
+    }
+}
+
+mod fake_relative_paths {
+    use std::path::PathBuf;
+
+    use filesystem::{FakeFileSystem, FileSystem};
+
+    #[test]
+    fn resolves_parent_dir_components_from_current_dir() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir_all("/dir/sub").unwrap();
+        fs.set_current_dir("/dir/sub").unwrap();
+        fs.create_file("../file", "contents").unwrap();
+
+        assert_eq!(fs.read_file_to_string("/dir/file").unwrap(), "contents");
+
+        fs.set_current_dir("..").unwrap();
+
+        assert_eq!(fs.current_dir().unwrap(), PathBuf::from("/dir"));
+        assert_eq!(fs.read_file_to_string("./file").unwrap(), "contents");
+    }
+
+    #[test]
+    fn does_not_resolve_parent_dir_components_above_root() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "contents").unwrap();
+
+        assert_eq!(fs.read_file_to_string("/../../file").unwrap(), "contents");
+    }
+}
+
+#[cfg(unix)]
+mod fake_ownership {
+    use std::io::ErrorKind;
+
+    use filesystem::{FakeFileSystem, FileSystem, UnixFileSystem};
+
+    #[test]
+    fn nodes_given_to_another_user_cannot_be_changed() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "").unwrap();
+
+        let (uid, gid) = fs.owner("/file").unwrap();
+
+        fs.set_owner("/file", uid + 1, gid + 1).unwrap();
+
+        assert_eq!(fs.owner("/file").unwrap(), (uid + 1, gid + 1));
+
+        let result = fs.set_mode("/file", 0o600);
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+
+        let result = fs.set_owner("/file", uid, gid);
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn copies_are_owned_by_current_user() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "").unwrap();
+
+        let owner = fs.owner("/file").unwrap();
+
+        fs.set_owner("/file", owner.0 + 1, owner.1 + 1).unwrap();
+        fs.copy_file("/file", "/copy").unwrap();
+
+        assert_eq!(fs.owner("/copy").unwrap(), owner);
+    }
+}
+
+mod fake_access_rules {
+    use std::io::ErrorKind;
+
+    use filesystem::{FakeFileSystem, FileSystem};
+
+    #[test]
+    fn deny_read_overrides_mode_even_for_root() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/secret", "hunter2").unwrap();
+        fs.deny_read("/secret").unwrap();
+
+        let result = fs.read_file("/secret");
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+
+        fs.set_current_user(0, 0);
+
+        assert!(fs.read_file("/secret").is_err());
+        assert!(fs.write_file("/secret", "changed").is_ok());
+    }
+
+    #[test]
+    fn deny_write_applies_to_nodes_created_later() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/etc").unwrap();
+        fs.deny_write("/etc/config").unwrap();
+        fs.create_file("/etc/config", "").unwrap();
+
+        let result = fs.write_file("/etc/config", "value");
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+        assert!(fs.read_file("/etc/config").is_ok());
+    }
+
+    #[test]
+    fn allow_write_overrides_readonly_mode() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "").unwrap();
+        fs.set_readonly("/file", true).unwrap();
+        fs.allow_write("/file").unwrap();
+
+        assert!(fs.write_file("/file", "contents").is_ok());
+    }
+
+    #[test]
+    fn clear_access_rules_restores_mode() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/dir").unwrap();
+        fs.deny_write("/dir").unwrap();
+
+        assert!(fs.create_file("/dir/file", "").is_err());
+
+        fs.clear_access_rules("/dir").unwrap();
+
+        assert!(fs.create_file("/dir/file", "").is_ok());
+    }
+
+    #[test]
+    fn latest_rule_wins() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "").unwrap();
+        fs.deny_read("/file").unwrap();
+        fs.allow_read("/file").unwrap();
+
+        assert!(fs.read_file("/file").is_ok());
+    }
+}
+
+#[cfg(unix)]
+mod fake_special_files {
+    use std::io::ErrorKind;
+
+    use filesystem::{FakeFileSystem, FileSystem, FileType, Metadata, UnixFileSystem};
+
+    #[test]
+    fn only_root_can_create_devices() {
+        let fs = FakeFileSystem::new();
+
+        let err = fs
+            .mknod("/null", FileType::CharDevice, 0o666, 0x103)
+            .unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+
+        fs.set_current_user(0, 0);
+        fs.mknod("/null", FileType::CharDevice, 0o666, 0x103)
+            .unwrap();
+        fs.mknod("/sda", FileType::BlockDevice, 0o660, 0x800)
+            .unwrap();
+        fs.mknod("/socket", FileType::Socket, 0o755, 0).unwrap();
+
+        assert!(fs.metadata("/null").unwrap().file_type().is_char_device());
+        assert!(fs.metadata("/sda").unwrap().file_type().is_block_device());
+        assert!(fs.metadata("/socket").unwrap().file_type().is_socket());
+    }
+
+    #[test]
+    fn special_files_have_no_contents() {
+        let fs = FakeFileSystem::new();
+
+        fs.mkfifo("/fifo", 0o644).unwrap();
+
+        assert!(fs.metadata("/fifo").unwrap().file_type().is_special());
+        assert_eq!(fs.len("/fifo"), 0);
+        assert!(fs.read_file("/fifo").is_err());
+    }
+
+    #[test]
+    fn walk_dir_reports_special_files() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/run").unwrap();
+        fs.mkfifo("/run/fifo", 0o644).unwrap();
+
+        let types: Vec<_> = fs
+            .walk_dir("/run")
+            .unwrap()
+            .map(|entry| entry.unwrap().file_type())
+            .collect();
+
+        assert_eq!(types, vec![FileType::Dir, FileType::Fifo]);
+    }
+}
+
+#[cfg(unix)]
+mod fake_umask {
+    use filesystem::{FakeFileSystem, FileSystem, UnixFileSystem};
+
+    #[test]
+    fn new_nodes_have_default_umask_cleared() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/dir").unwrap();
+        fs.create_file("/file", "").unwrap();
+
+        assert_eq!(fs.mode("/dir").unwrap(), 0o755);
+        assert_eq!(fs.mode("/file").unwrap(), 0o644);
+    }
+
+    #[test]
+    fn set_umask_clears_mode_bits_of_new_nodes() {
+        let fs = FakeFileSystem::new();
+
+        fs.set_umask(0o077);
+        fs.create_dir_all("/a/b").unwrap();
+        fs.create_file("/a/b/file", "").unwrap();
+        fs.create_file_with_mode("/a/b/script", "", 0o755).unwrap();
+        fs.create_dir_with_mode("/a/b/dir", 0o775).unwrap();
+
+        assert_eq!(fs.mode("/a").unwrap(), 0o700);
+        assert_eq!(fs.mode("/a/b").unwrap(), 0o700);
+        assert_eq!(fs.mode("/a/b/file").unwrap(), 0o600);
+        assert_eq!(fs.mode("/a/b/script").unwrap(), 0o700);
+        assert_eq!(fs.mode("/a/b/dir").unwrap(), 0o700);
+    }
+
+    #[test]
+    fn set_umask_does_not_change_existing_nodes() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "").unwrap();
+        fs.set_umask(0o777);
+
+        assert_eq!(fs.mode("/file").unwrap(), 0o644);
+    }
+}
+
+#[cfg(unix)]
+mod fake_strict_permissions {
+    use std::io::ErrorKind;
+    use std::path::Path;
+
+    use filesystem::{FakeFileSystem, FileSystem, UnixFileSystem};
+
+    #[test]
+    fn directories_without_execute_bit_are_traversable_by_default() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir_all("/a/b").unwrap();
+        fs.create_file("/a/b/file", "contents").unwrap();
+        fs.set_mode("/a", 0o644).unwrap();
+
+        assert_eq!(fs.read_file("/a/b/file").unwrap(), b"contents");
+        assert!(fs.set_current_dir("/a").is_ok());
+    }
+
+    #[test]
+    fn lookups_require_execute_bit_on_every_directory() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir_all("/a/b").unwrap();
+        fs.create_file("/a/b/file", "contents").unwrap();
+        fs.set_strict_permissions(true);
+        fs.set_mode("/a", 0o644).unwrap();
+
+        let result = fs.read_file("/a/b/file");
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+
+        let result = fs.create_file("/a/b/other", "");
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+
+        // Listing a directory only requires its read bit
+        assert!(fs.read_dir("/a").is_ok());
+
+        fs.set_mode("/a", 0o711).unwrap();
+
+        assert_eq!(fs.read_file("/a/b/file").unwrap(), b"contents");
+    }
+
+    #[test]
+    fn set_current_dir_requires_execute_bit() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/dir").unwrap();
+        fs.set_strict_permissions(true);
+        fs.set_mode("/dir", 0o600).unwrap();
+
+        let result = fs.set_current_dir("/dir");
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+        assert_eq!(fs.current_dir().unwrap(), Path::new("/"));
+    }
+
+    #[test]
+    fn root_traverses_directories_without_execute_bit() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/dir").unwrap();
+        fs.create_file("/dir/file", "contents").unwrap();
+        fs.set_strict_permissions(true);
+        fs.set_mode("/dir", 0o000).unwrap();
+        fs.set_current_user(0, 0);
+
+        assert_eq!(fs.read_file("/dir/file").unwrap(), b"contents");
+        assert!(fs.set_current_dir("/dir").is_ok());
+    }
+}
+
+#[cfg(unix)]
+mod fake_users {
+    use std::io::ErrorKind;
+
+    use filesystem::{FakeFileSystem, FileSystem, UnixFileSystem};
+
+    #[test]
+    fn checks_mode_bits_for_class_of_current_user() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "contents").unwrap();
+        fs.set_mode("/file", 0o640).unwrap();
+
+        let (uid, gid) = fs.owner("/file").unwrap();
+
+        fs.set_current_user(uid + 1, gid);
+
+        assert!(fs.read_file("/file").is_ok());
+
+        let result = fs.write_file("/file", "");
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+
+        fs.set_current_user(uid + 1, gid + 1);
+
+        let result = fs.read_file("/file");
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+
+        fs.set_current_user(uid, gid + 1);
+
+        assert!(fs.write_file("/file", "").is_ok());
+    }
+
+    #[test]
+    fn root_bypasses_mode_bits() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "contents").unwrap();
+        fs.set_mode("/file", 0o000).unwrap();
+        fs.set_current_user(0, 0);
+
+        assert!(fs.read_file("/file").is_ok());
+        assert!(fs.write_file("/file", "").is_ok());
+        assert!(fs.set_owner("/file", 2000, 2000).is_ok());
+    }
+
+    #[test]
+    fn new_nodes_are_owned_by_current_user() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/shared").unwrap();
+        fs.set_mode("/shared", 0o777).unwrap();
+        fs.set_current_user(2000, 3000);
+        fs.create_file("/shared/file", "").unwrap();
+
+        assert_eq!(fs.owner("/shared/file").unwrap(), (2000, 3000));
+
+        let result = fs.create_file("/file", "");
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+    }
+}
+
+mod chroot {
+    use std::io::ErrorKind;
+    use std::path::{Path, PathBuf};
+
+    use filesystem::{
+        ChrootFileSystem, DirEntry, FakeFileSystem, FileSystem, OsFileSystem, SymlinkFileSystem,
+        TempDir, TempFileSystem,
+    };
+
+    fn chroot() -> (FakeFileSystem, ChrootFileSystem<FakeFileSystem>) {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir_all("/jail/dir").unwrap();
+
+        (fs.clone(), ChrootFileSystem::new(fs, "/jail"))
+    }
+
+    #[test]
+    fn rebases_paths_under_root() {
+        let (fs, chroot) = chroot();
+
+        chroot.create_file("/dir/file", "contents").unwrap();
+
+        assert!(fs.is_file("/jail/dir/file"));
+        assert_eq!(chroot.read_file("/dir/file").unwrap(), b"contents");
+        assert_eq!(
+            chroot.canonicalize("/dir/../dir/file").unwrap(),
+            Path::new("/dir/file")
+        );
+    }
+
+    #[test]
+    fn resolves_relative_paths_against_own_current_dir() {
+        let (fs, chroot) = chroot();
+
+        chroot.set_current_dir("/dir").unwrap();
+        chroot.create_file("file", "").unwrap();
+
+        assert_eq!(chroot.current_dir().unwrap(), Path::new("/dir"));
+        assert!(fs.is_file("/jail/dir/file"));
+        assert_eq!(fs.current_dir().unwrap(), Path::new("/"));
+    }
+
+    #[test]
+    fn rejects_parent_dir_escapes() {
+        let (fs, chroot) = chroot();
+
+        fs.create_file("/secret", "").unwrap();
+
+        let result = chroot.read_file("/dir/../../secret");
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+        assert!(!chroot.exists("/../secret"));
+    }
+
+    #[test]
+    fn rejects_absolute_symlinks_outside_root() {
+        let (fs, chroot) = chroot();
+
+        fs.create_file("/secret", "").unwrap();
+        fs.symlink_file("/secret", "/jail/link").unwrap();
+
+        let result = chroot.read_file("/link");
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn fails_on_symlink_loops_like_the_os() {
+        let (fs, chroot) = chroot();
+
+        fs.symlink_file("/jail/b", "/jail/a").unwrap();
+        fs.symlink_file("/jail/a", "/jail/b").unwrap();
+
+        let result = chroot.read_file("/a");
+
+        assert!(result.unwrap_err().is_filesystem_loop());
+    }
+
+    #[test]
+    fn rebases_absolute_symlinks_inside_root() {
+        let (fs, chroot) = chroot();
+
+        chroot.create_file("/dir/file", "contents").unwrap();
+        chroot.symlink_file("/dir/file", "/link").unwrap();
+
+        assert_eq!(
+            fs.read_link("/jail/link").unwrap(),
+            Path::new("/jail/dir/file")
+        );
+        assert_eq!(chroot.read_link("/link").unwrap(), Path::new("/dir/file"));
+        assert_eq!(chroot.read_file("/link").unwrap(), b"contents");
+    }
+
+    #[test]
+    fn returns_paths_under_root() {
+        let (_, chroot) = chroot();
+
+        chroot.create_file("/dir/file", "").unwrap();
+
+        let entries: Vec<PathBuf> = chroot
+            .read_dir("/dir")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+
+        assert_eq!(entries, vec![PathBuf::from("/dir/file")]);
+
+        let mut walked: Vec<PathBuf> = chroot
+            .walk_dir("/")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+
+        walked.sort();
+
+        assert_eq!(
+            walked,
+            vec![
+                PathBuf::from("/"),
+                PathBuf::from("/dir"),
+                PathBuf::from("/dir/file"),
+            ]
+        );
+        assert_eq!(
+            chroot.glob("/*/*").unwrap(),
+            vec![PathBuf::from("/dir/file")]
+        );
+    }
+
+    #[test]
+    fn errors_have_paths_under_root() {
+        let (_, chroot) = chroot();
+
+        let err = chroot.read_file("/dir/missing").unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(err.operation(), Some("read_file"));
+        assert_eq!(err.paths(), [PathBuf::from("/dir/missing")]);
+    }
+
+    #[test]
+    fn confines_os_file_system_to_temp_dir() {
+        let fs = OsFileSystem::new();
+        let temp_dir = fs.temp_dir("test").unwrap();
+        let chroot = ChrootFileSystem::new(fs.clone(), temp_dir.path());
+
+        chroot.create_dir("/dir").unwrap();
+        chroot.create_file("/dir/file", "contents").unwrap();
+
+        assert_eq!(
+            fs.read_file(temp_dir.path().join("dir/file")).unwrap(),
+            b"contents"
+        );
+
+        let result = chroot.metadata("/..");
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+    }
+}
+
+mod os_current_dir {
+    use std::env;
+    use std::io::ErrorKind;
+    use std::thread;
+
+    use filesystem::{FileSystem, OsFileSystem, SymlinkFileSystem, TempDir, TempFileSystem};
+
+    #[test]
+    fn starts_at_process_current_dir() {
+        let fs = OsFileSystem::with_own_current_dir().unwrap();
+
+        assert_eq!(fs.current_dir().unwrap(), env::current_dir().unwrap());
+        assert_eq!(fs.base(), None);
+    }
+
+    #[test]
+    fn resolves_relative_paths_against_own_current_dir() {
+        let fs = OsFileSystem::with_own_current_dir().unwrap();
+        let temp_dir = fs.temp_dir("test").unwrap();
+        let process_dir = env::current_dir().unwrap();
+        let canonical = fs.canonicalize(temp_dir.path()).unwrap();
+
+        fs.set_current_dir(temp_dir.path()).unwrap();
+        fs.create_dir("dir").unwrap();
+        fs.write_file("dir/file", "contents").unwrap();
+
+        assert_eq!(fs.current_dir().unwrap(), canonical);
+        assert_eq!(
+            fs.read_file(temp_dir.path().join("dir/file")).unwrap(),
+            b"contents"
+        );
+        assert_eq!(env::current_dir().unwrap(), process_dir);
+
+        fs.set_current_dir("dir").unwrap();
+
+        assert_eq!(fs.current_dir().unwrap(), canonical.join("dir"));
+        assert_eq!(fs.read_file_to_string("file").unwrap(), "contents");
+    }
+
+    #[test]
+    fn resolves_symlinks_in_current_dir() {
+        let fs = OsFileSystem::with_own_current_dir().unwrap();
+        let temp_dir = fs.temp_dir("test").unwrap();
+        let canonical = fs.canonicalize(temp_dir.path()).unwrap();
+
+        fs.create_dir(temp_dir.path().join("original")).unwrap();
+        fs.symlink_dir(
+            temp_dir.path().join("original"),
+            temp_dir.path().join("link"),
+        )
+        .unwrap();
+        fs.set_current_dir(temp_dir.path().join("link")).unwrap();
+
+        assert_eq!(fs.current_dir().unwrap(), canonical.join("original"));
+    }
+
+    #[test]
+    fn fails_to_set_current_dir_to_file() {
+        let fs = OsFileSystem::with_own_current_dir().unwrap();
+        let temp_dir = fs.temp_dir("test").unwrap();
+        let before = fs.current_dir().unwrap();
+
+        fs.create_file(temp_dir.path().join("file"), "").unwrap();
+
+        let result = fs.set_current_dir(temp_dir.path().join("file"));
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::NotADirectory);
+        assert_eq!(fs.current_dir().unwrap(), before);
+    }
+
+    #[test]
+    fn keeps_current_dir_apart_between_instances() {
+        let os = OsFileSystem::new();
+        let temp_dirs: Vec<_> = (0..4).map(|_| os.temp_dir("test").unwrap()).collect();
+        let threads: Vec<_> = temp_dirs
+            .iter()
+            .map(|temp_dir| {
+                let path = temp_dir.path().to_path_buf();
+
+                thread::spawn(move || {
+                    let fs = OsFileSystem::with_own_current_dir().unwrap();
+
+                    fs.set_current_dir(&path).unwrap();
+
+                    for _ in 0..100 {
+                        fs.write_file("file", path.to_str().unwrap()).unwrap();
+
+                        assert_eq!(
+                            fs.read_file_to_string("file").unwrap(),
+                            path.to_str().unwrap()
+                        );
+                    }
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn shares_current_dir_between_clones() {
+        let fs = OsFileSystem::with_own_current_dir().unwrap();
+        let temp_dir = fs.temp_dir("test").unwrap();
+        let clone = fs.clone();
+
+        fs.set_current_dir(temp_dir.path()).unwrap();
+
+        assert_eq!(clone.current_dir().unwrap(), fs.current_dir().unwrap());
+    }
+}
+
+mod rooted_os {
+    use std::env;
+    use std::io::ErrorKind;
+    use std::path::{Path, PathBuf};
+
+    use filesystem::{
+        FileSystem, OsFileSystem, SymlinkFileSystem, TempDir, TempFileSystem, WalkDirOptions,
+    };
+
+    #[test]
+    fn resolves_relative_paths_under_base() {
+        let os = OsFileSystem::new();
+        let temp_dir = os.temp_dir("test").unwrap();
+        let fs = OsFileSystem::rooted(temp_dir.path());
+
+        fs.create_dir("dir").unwrap();
+        fs.create_file("dir/file", "contents").unwrap();
+
+        assert_eq!(fs.base(), Some(temp_dir.path()));
+        assert_eq!(fs.current_dir().unwrap(), temp_dir.path());
+        assert_eq!(
+            os.read_file(temp_dir.path().join("dir/file")).unwrap(),
+            b"contents"
+        );
+        assert_eq!(
+            fs.read_file(temp_dir.path().join("dir/file")).unwrap(),
+            b"contents"
+        );
+    }
+
+    #[test]
+    fn tracks_current_dir_apart_from_process() {
+        let fs = OsFileSystem::rooted_in_temp_dir("test").unwrap();
+        let base = fs.base().unwrap().to_path_buf();
+        let process_dir = env::current_dir().unwrap();
+
+        fs.create_dir("dir").unwrap();
+        fs.set_current_dir("dir").unwrap();
+        fs.create_file("file", "").unwrap();
+
+        assert_eq!(fs.current_dir().unwrap(), base.join("dir"));
+        assert!(fs.is_file(base.join("dir/file")));
+        assert_eq!(env::current_dir().unwrap(), process_dir);
+        assert_eq!(
+            fs.clone().current_dir().unwrap(),
+            base.join("dir"),
+            "clones share the current dir"
+        );
+    }
+
+    #[test]
+    fn rejects_parent_dir_escapes() {
+        let fs = OsFileSystem::rooted_in_temp_dir("test").unwrap();
+
+        fs.create_dir("dir").unwrap();
+
+        let result = fs.metadata("dir/../..");
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+        assert!(!fs.exists(".."));
+        assert!(fs.set_current_dir("..").is_err());
+    }
+
+    #[test]
+    fn rejects_absolute_paths_outside_base() {
+        let os = OsFileSystem::new();
+        let temp_dir = os.temp_dir("test").unwrap();
+        let fs = OsFileSystem::rooted(temp_dir.path().join("base"));
+
+        os.create_dir(temp_dir.path().join("base")).unwrap();
+        os.create_file(temp_dir.path().join("secret"), "").unwrap();
+
+        let result = fs.read_file(temp_dir.path().join("secret"));
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+
+        let result = fs.create_file(temp_dir.path().join("base/../escaped"), "");
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+        assert!(!os.exists(temp_dir.path().join("escaped")));
+    }
+
+    #[test]
+    fn rejects_symlinks_outside_base() {
+        let os = OsFileSystem::new();
+        let temp_dir = os.temp_dir("test").unwrap();
+        let fs = OsFileSystem::rooted(temp_dir.path().join("base"));
+
+        os.create_dir(temp_dir.path().join("base")).unwrap();
+        os.create_file(temp_dir.path().join("secret"), "").unwrap();
+        fs.symlink_file(temp_dir.path().join("secret"), "link")
+            .unwrap();
+
+        let result = fs.read_file("link");
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+        assert!(fs.symlink_metadata("link").is_ok());
+
+        fs.remove_file("link").unwrap();
+
+        assert!(os.exists(temp_dir.path().join("secret")));
+    }
+
+    #[test]
+    fn walk_dir_does_not_follow_symlinks_outside_base() {
+        let os = OsFileSystem::new();
+        let temp_dir = os.temp_dir("test").unwrap();
+        let base = temp_dir.path().join("base");
+        let fs = OsFileSystem::rooted(&base);
+
+        os.create_dir_all(base.join("dir")).unwrap();
+        os.create_dir(temp_dir.path().join("outside")).unwrap();
+        os.create_file(temp_dir.path().join("outside/secret"), "")
+            .unwrap();
+        fs.symlink_dir(temp_dir.path().join("outside"), "link")
+            .unwrap();
+
+        let entries: Vec<_> = fs
+            .walk_dir_with_options(&base, WalkDirOptions::new().follow_links(true))
+            .unwrap()
+            .collect();
+        let paths: Vec<PathBuf> = entries
+            .iter()
+            .filter_map(|entry| entry.as_ref().ok())
+            .map(|entry| entry.path())
+            .collect();
+        let errors: Vec<ErrorKind> = entries
+            .iter()
+            .filter_map(|entry| entry.as_ref().err())
+            .map(|err| err.kind())
+            .collect();
+
+        assert_eq!(paths, vec![base.clone(), base.join("dir")]);
+        assert_eq!(errors, vec![ErrorKind::PermissionDenied]);
+    }
+
+    #[test]
+    fn creates_temp_files_under_base() {
+        let fs = OsFileSystem::rooted_in_temp_dir("test").unwrap();
+        let temp_dir = fs.temp_dir("nested").unwrap();
+
+        assert!(temp_dir.path().starts_with(fs.base().unwrap()));
+    }
+
+    #[test]
+    fn removes_temp_base_once_dropped() {
+        let fs = OsFileSystem::rooted_in_temp_dir("test").unwrap();
+        let base = fs.base().unwrap().to_path_buf();
+        let clone = fs.clone();
+
+        fs.create_file("file", "").unwrap();
+        drop(fs);
+
+        assert!(Path::new(&base).is_dir());
+
+        drop(clone);
+
+        assert!(!Path::new(&base).exists());
+    }
+}
+
+mod overlay {
+    use std::io::{ErrorKind, Read, Write};
+    use std::path::PathBuf;
+
+    use filesystem::{
+        DirEntry, FakeFileSystem, FileSystem, FileType, Metadata, OpenOptions, OsFileSystem,
+        OverlayFileSystem, TempDir, TempFileSystem,
+    };
+
+    fn overlay() -> (
+        FakeFileSystem,
+        FakeFileSystem,
+        OverlayFileSystem<FakeFileSystem, FakeFileSystem>,
+    ) {
+        let (upper, lower) = (FakeFileSystem::new(), FakeFileSystem::new());
+
+        lower.create_dir_all("/fixtures/dir").unwrap();
+        lower.create_file("/fixtures/file", "lower").unwrap();
+        lower.create_file("/fixtures/dir/nested", "nested").unwrap();
+
+        (
+            upper.clone(),
+            lower.clone(),
+            OverlayFileSystem::new(upper, lower),
+        )
+    }
+
+    fn names(fs: &OverlayFileSystem<FakeFileSystem, FakeFileSystem>, path: &str) -> Vec<PathBuf> {
+        fs.read_dir(path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect()
+    }
+
+    #[test]
+    fn dir_entries_have_metadata_from_visible_layer() {
+        let (_, _, fs) = overlay();
+
+        fs.write_file("/fixtures/file", "upper!").unwrap();
+
+        let entries: Vec<_> = fs
+            .read_dir("/fixtures")
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+
+                (entry.file_type().unwrap(), entry.metadata().unwrap().len())
+            })
+            .collect();
+
+        assert_eq!(entries, vec![(FileType::Dir, 4096), (FileType::File, 6)]);
+    }
+
+    #[test]
+    fn reads_fall_through_to_lower_layer() {
+        let (upper, _, fs) = overlay();
+
+        assert_eq!(fs.read_file("/fixtures/file").unwrap(), b"lower");
+        assert!(fs.is_dir("/fixtures/dir"));
+        assert!(!upper.exists("/fixtures"));
+    }
+
+    #[test]
+    fn writes_copy_up_to_upper_layer() {
+        let (upper, lower, fs) = overlay();
+
+        fs.write_file("/fixtures/file", "upper").unwrap();
+
+        assert_eq!(fs.read_file("/fixtures/file").unwrap(), b"upper");
+        assert_eq!(upper.read_file("/fixtures/file").unwrap(), b"upper");
+        assert_eq!(lower.read_file("/fixtures/file").unwrap(), b"lower");
+    }
+
+    #[test]
+    fn copy_up_preserves_readonly_flag() {
+        let (_, lower, fs) = overlay();
+
+        lower.set_readonly("/fixtures/file", true).unwrap();
+
+        let result = fs.write_file("/fixtures/file", "upper");
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+
+        fs.set_readonly("/fixtures/file", false).unwrap();
+        fs.write_file("/fixtures/file", "upper").unwrap();
+
+        assert!(lower.readonly("/fixtures/file").unwrap());
+    }
+
+    #[test]
+    fn merges_directories_from_both_layers() {
+        let (_, _, fs) = overlay();
+
+        fs.create_file("/fixtures/new", "").unwrap();
+        fs.write_file("/fixtures/file", "upper").unwrap();
+
+        assert_eq!(
+            names(&fs, "/fixtures"),
+            vec![
+                PathBuf::from("/fixtures/dir"),
+                PathBuf::from("/fixtures/file"),
+                PathBuf::from("/fixtures/new"),
+            ]
+        );
+    }
+
+    #[test]
+    fn removed_lower_nodes_stay_hidden() {
+        let (_, lower, fs) = overlay();
+
+        fs.remove_file("/fixtures/file").unwrap();
+        fs.remove_dir_all("/fixtures/dir").unwrap();
+
+        assert!(!fs.exists("/fixtures/file"));
+        assert!(!fs.exists("/fixtures/dir/nested"));
+        assert!(names(&fs, "/fixtures").is_empty());
+        assert!(lower.exists("/fixtures/dir/nested"));
+
+        fs.create_dir("/fixtures/dir").unwrap();
+
+        assert!(names(&fs, "/fixtures/dir").is_empty());
+        assert_eq!(
+            fs.read_file("/fixtures/file").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn renames_merged_directories() {
+        let (_, lower, fs) = overlay();
+
+        fs.create_file("/fixtures/dir/upper", "").unwrap();
+        fs.rename("/fixtures/dir", "/fixtures/renamed").unwrap();
+
+        assert!(!fs.exists("/fixtures/dir"));
+        assert_eq!(
+            names(&fs, "/fixtures/renamed"),
+            vec![
+                PathBuf::from("/fixtures/renamed/nested"),
+                PathBuf::from("/fixtures/renamed/upper"),
+            ]
+        );
+        assert!(lower.exists("/fixtures/dir/nested"));
+    }
+
+    #[test]
+    fn opens_lower_files_for_reading_and_copies_up_for_writing() {
+        let (upper, lower, fs) = overlay();
+        let mut contents = String::new();
+
+        fs.open("/fixtures/file")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+
+        assert_eq!(contents, "lower");
+        assert!(!upper.exists("/fixtures/file"));
+
+        fs.open_with_options("/fixtures/file", OpenOptions::new().append(true))
+            .unwrap()
+            .write_all(b" and upper")
+            .unwrap();
+
+        assert_eq!(
+            fs.read_file_to_string("/fixtures/file").unwrap(),
+            "lower and upper"
+        );
+        assert_eq!(lower.read_file("/fixtures/file").unwrap(), b"lower");
+    }
+
+    #[test]
+    fn records_modifications_to_os_fixtures_separately() {
+        let os = OsFileSystem::new();
+        let temp_dir = os.temp_dir("test").unwrap();
+        let fixtures = temp_dir.path();
+
+        os.create_file(fixtures.join("file"), "fixture").unwrap();
+
+        let fs = OverlayFileSystem::new(FakeFileSystem::new(), os.clone());
+
+        fs.write_file(fixtures.join("file"), "modified").unwrap();
+        fs.create_file(fixtures.join("new"), "").unwrap();
+
+        assert_eq!(fs.read_file(fixtures.join("file")).unwrap(), b"modified");
+        assert_eq!(os.read_file(fixtures.join("file")).unwrap(), b"fixture");
+        assert!(!os.exists(fixtures.join("new")));
+    }
+}
+
+mod mount {
+    use std::io::ErrorKind;
+    use std::path::PathBuf;
+
+    use filesystem::{
+        ChrootFileSystem, DirEntry, FakeFileSystem, FileSystem, FileType, Metadata,
+        MountFileSystem, OsFileSystem, TempDir, TempFileSystem,
+    };
+
+    fn mounted() -> (FakeFileSystem, FakeFileSystem, MountFileSystem) {
+        let (root, data) = (FakeFileSystem::new(), FakeFileSystem::new());
+        let fs = MountFileSystem::new();
+
+        root.create_dir("/etc").unwrap();
+        root.create_file("/etc/config", "root").unwrap();
+        data.create_file("/file", "data").unwrap();
+
+        fs.mount("/", root.clone()).unwrap();
+        fs.mount("/srv/data", data.clone()).unwrap();
+
+        (root, data, fs)
+    }
+
+    fn names(fs: &MountFileSystem, path: &str) -> Vec<PathBuf> {
+        fs.read_dir(path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect()
+    }
+
+    #[test]
+    fn routes_paths_to_the_longest_mount_point() {
+        let (root, data, fs) = mounted();
+
+        assert_eq!(fs.read_file("/etc/config").unwrap(), b"root");
+        assert_eq!(fs.read_file("/srv/data/file").unwrap(), b"data");
+
+        fs.set_current_dir("/srv/data").unwrap();
+        fs.create_file("new", "").unwrap();
+
+        assert!(data.exists("/new"));
+        assert!(!root.exists("/srv/data/new"));
+        assert_eq!(
+            fs.canonicalize("new").unwrap(),
+            PathBuf::from("/srv/data/new")
+        );
+    }
+
+    #[test]
+    fn lists_mount_points_in_their_parent() {
+        let (_, _, fs) = mounted();
+
+        assert_eq!(
+            names(&fs, "/"),
+            vec![PathBuf::from("/etc"), PathBuf::from("/srv")]
+        );
+        assert_eq!(names(&fs, "/srv"), vec![PathBuf::from("/srv/data")]);
+        assert!(fs.is_dir("/srv"));
+
+        let entry = fs.read_dir("/srv").unwrap().next().unwrap().unwrap();
+
+        assert_eq!(entry.file_type().unwrap(), FileType::Dir);
+        assert!(entry.metadata().unwrap().is_dir());
+    }
+
+    #[test]
+    fn ancestors_of_mount_points_exist_without_a_root_mount() {
+        let fs = MountFileSystem::new();
+
+        fs.mount("/a/b", FakeFileSystem::new()).unwrap();
+
+        assert!(fs.is_dir("/"));
+        assert!(fs.is_dir("/a"));
+        assert_eq!(names(&fs, "/"), vec![PathBuf::from("/a")]);
+        assert_eq!(
+            fs.create_file("/file", "").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        assert_eq!(
+            fs.create_dir("/a").unwrap_err().kind(),
+            ErrorKind::AlreadyExists
+        );
+    }
+
+    #[test]
+    fn renaming_across_mounts_fails() {
+        let (_, data, fs) = mounted();
+
+        let err = fs.rename("/etc/config", "/srv/data/config").unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::CrossesDevices);
+        assert!(fs.exists("/etc/config"));
+        assert!(!data.exists("/config"));
+
+        fs.rename("/srv/data/file", "/srv/data/renamed").unwrap();
+
+        assert!(data.exists("/renamed"));
+    }
+
+    #[test]
+    fn rename_or_copy_moves_across_mounts() {
+        let (root, data, fs) = mounted();
+
+        fs.rename_or_copy("/etc/config", "/srv/data/config")
+            .unwrap();
+
+        assert_eq!(data.read_file("/config").unwrap(), b"root");
+        assert!(!root.exists("/etc/config"));
+    }
+
+    #[test]
+    fn copies_across_mounts() {
+        let (root, data, fs) = mounted();
+
+        fs.create_dir_all("/etc/dir/nested").unwrap();
+        fs.create_file("/etc/dir/nested/file", "nested").unwrap();
+
+        fs.copy_file("/etc/config", "/srv/data/config").unwrap();
+        fs.copy_dir("/etc/dir", "/srv/data/dir").unwrap();
+
+        assert_eq!(data.read_file("/config").unwrap(), b"root");
+        assert_eq!(data.read_file("/dir/nested/file").unwrap(), b"nested");
+        assert!(root.exists("/etc/config"));
+    }
+
+    #[test]
+    fn mount_points_are_busy() {
+        let (_, _, fs) = mounted();
+
+        for err in [
+            fs.remove_dir("/srv/data").unwrap_err(),
+            fs.remove_dir_all("/srv").unwrap_err(),
+            fs.rename("/srv/data", "/srv/moved").unwrap_err(),
+        ] {
+            assert_eq!(err.kind(), ErrorKind::ResourceBusy);
+        }
+
+        fs.unmount("/srv/data").unwrap();
+
+        assert!(!fs.exists("/srv"));
+        assert_eq!(
+            fs.unmount("/srv/data").unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn errors_have_paths_across_mount_points() {
+        let (_, _, fs) = mounted();
+
+        let err = fs.read_file("/srv/data/missing").unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(err.operation(), Some("read_file"));
+        assert_eq!(err.paths(), [PathBuf::from("/srv/data/missing")]);
+    }
+
+    #[test]
+    fn walks_across_mount_points() {
+        let (_, _, fs) = mounted();
+
+        let paths: Vec<_> = fs
+            .walk_dir("/")
+            .unwrap()
+            .map(|entry| entry.unwrap().path().to_path_buf())
+            .collect();
+
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("/"),
+                PathBuf::from("/etc"),
+                PathBuf::from("/etc/config"),
+                PathBuf::from("/srv"),
+                PathBuf::from("/srv/data"),
+                PathBuf::from("/srv/data/file"),
+            ]
+        );
+    }
+
+    #[test]
+    fn mounts_a_directory_of_the_os_file_system() {
+        let os = OsFileSystem::new();
+        let temp_dir = os.temp_dir("test").unwrap();
+        let fs = MountFileSystem::new();
+
+        fs.mount("/", FakeFileSystem::new()).unwrap();
+        fs.mount("/cache", ChrootFileSystem::new(os.clone(), temp_dir.path()))
+            .unwrap();
+
+        fs.write_file("/cache/file", "cached").unwrap();
+
+        assert_eq!(
+            os.read_file(temp_dir.path().join("file")).unwrap(),
+            b"cached"
+        );
+    }
+}
+
+mod default_methods {
+    use std::io::{ErrorKind, Read};
+    use std::path::{Path, PathBuf};
+    use std::time::SystemTime;
+
+    use filesystem::{
+        FakeFileSystem, FileSystem, OpenOptions, Result, UnixFileSystem, WalkDirOptions,
+    };
+
+    /// Implements only the methods of `FileSystem` without defaults, by forwarding them to a fake.
+    struct Minimal(FakeFileSystem);
+
+    impl FileSystem for Minimal {
+        type DirEntry = <FakeFileSystem as FileSystem>::DirEntry;
+        type ReadDir = <FakeFileSystem as FileSystem>::ReadDir;
+        type Metadata = <FakeFileSystem as FileSystem>::Metadata;
+        type OpenFile = <FakeFileSystem as FileSystem>::OpenFile;
+        type WalkDir = <FakeFileSystem as FileSystem>::WalkDir;
+
+        fn current_dir(&self) -> Result<PathBuf> {
+            self.0.current_dir()
+        }
+
+        fn set_current_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+            self.0.set_current_dir(path)
+        }
+
+        fn metadata<P: AsRef<Path>>(&self, path: P) -> Result<Self::Metadata> {
+            self.0.metadata(path)
+        }
+
+        fn canonicalize<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf> {
+            self.0.canonicalize(path)
+        }
+
+        fn create_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+            self.0.create_dir(path)
+        }
+
+        fn remove_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+            self.0.remove_dir(path)
+        }
+
+        fn read_dir<P: AsRef<Path>>(&self, path: P) -> Result<Self::ReadDir> {
+            self.0.read_dir(path)
+        }
+
+        fn walk_dir_with_options<P: AsRef<Path>>(
+            &self,
+            path: P,
+            options: &WalkDirOptions,
+        ) -> Result<Self::WalkDir> {
+            self.0.walk_dir_with_options(path, options)
+        }
+
+        fn sync_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+            self.0.sync_dir(path)
+        }
+
+        fn open_with_options<P: AsRef<Path>>(
+            &self,
+            path: P,
+            options: &OpenOptions,
+        ) -> Result<Self::OpenFile> {
+            self.0.open_with_options(path, options)
+        }
+
+        fn remove_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+            self.0.remove_file(path)
+        }
+
+        fn copy_dir<P, Q>(&self, from: P, to: Q) -> Result<()>
+        where
+            P: AsRef<Path>,
+            Q: AsRef<Path>,
+        {
+            self.0.copy_dir(from, to)
+        }
+
+        fn rename<P, Q>(&self, from: P, to: Q) -> Result<()>
+        where
+            P: AsRef<Path>,
+            Q: AsRef<Path>,
+        {
+            self.0.rename(from, to)
+        }
+
+        fn set_readonly<P: AsRef<Path>>(&self, path: P, readonly: bool) -> Result<()> {
+            self.0.set_readonly(path, readonly)
+        }
+
+        fn set_modified<P: AsRef<Path>>(&self, path: P, time: SystemTime) -> Result<()> {
+            self.0.set_modified(path, time)
+        }
+
+        fn set_accessed<P: AsRef<Path>>(&self, path: P, time: SystemTime) -> Result<()> {
+            self.0.set_accessed(path, time)
+        }
+
+        fn available_space<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+            self.0.available_space(path)
+        }
+
+        fn total_space<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+            self.0.total_space(path)
+        }
+    }
+
+    #[test]
+    fn write_and_read_files() {
+        let fs = Minimal(FakeFileSystem::new());
+
+        fs.create_file("/file", "contents").unwrap();
+
+        assert!(fs.is_file("/file"));
+        assert!(fs.exists("/file"));
+        assert_eq!(fs.len("/file"), 8);
+        assert_eq!(fs.read_file_to_string("/file").unwrap(), "contents");
+        assert_eq!(
+            fs.create_file("/file", "").unwrap_err().kind(),
+            ErrorKind::AlreadyExists
+        );
+
+        fs.overwrite_file("/file", "new").unwrap();
+        fs.write_file("/other", [0xff]).unwrap();
+
+        let mut buf = b"old ".to_vec();
+
+        assert_eq!(fs.read_file_into("/file", &mut buf).unwrap(), 3);
+        assert_eq!(buf, b"old new");
+        assert_eq!(
+            fs.read_file_to_string("/other").unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        assert_eq!(
+            fs.overwrite_file("/missing", "").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+
+        fs.set_len("/file", 1).unwrap();
+        fs.sync_file("/file").unwrap();
+
+        let mut contents = String::new();
+
+        fs.open("/file")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+
+        assert_eq!(contents, "n");
+    }
+
+    #[test]
+    fn copy_file_keeps_readonly_flag() {
+        let fs = Minimal(FakeFileSystem::new());
+
+        fs.create_file("/from", "contents").unwrap();
+        fs.set_readonly("/from", true).unwrap();
+        fs.copy_file("/from", "/to").unwrap();
+
+        assert_eq!(fs.read_file("/to").unwrap(), b"contents");
+        assert!(fs.readonly("/to").unwrap());
+        assert_eq!(fs.0.mode("/to").unwrap(), 0o444);
+    }
+
+    #[test]
+    fn create_and_remove_dirs_recursively() {
+        let fs = Minimal(FakeFileSystem::new());
+
+        fs.create_dir_all("/a/b/c").unwrap();
+        fs.create_dir_all("/a/b/c").unwrap();
+        fs.create_file("/a/b/file", "").unwrap();
+        fs.create_file("/a/b/c/file", "").unwrap();
+
+        assert!(fs.is_dir("/a/b/c"));
+        assert!(fs.try_exists("/a/b/c/file").unwrap());
+        assert_eq!(fs.glob("/a/**/file").unwrap().len(), 2);
+        assert_eq!(fs.walk_dir("/a").unwrap().count(), 5);
+        assert_eq!(
+            fs.create_dir_all("/a/b/file/d").unwrap_err().kind(),
+            ErrorKind::NotADirectory
+        );
+        assert_eq!(
+            fs.remove_dir_all("/a/b/file").unwrap_err().kind(),
+            ErrorKind::NotADirectory
+        );
+
+        fs.remove_dir_all("/a/b").unwrap();
+
+        assert!(fs.is_dir("/a"));
+        assert!(!fs.try_exists("/a/b").unwrap());
+    }
+
+    #[test]
+    fn reads_times_from_metadata() {
+        let fs = Minimal(FakeFileSystem::new());
+        let time = SystemTime::UNIX_EPOCH;
+
+        fs.create_file("/file", "").unwrap();
+        fs.set_modified("/file", time).unwrap();
+        fs.set_accessed("/file", time).unwrap();
+
+        assert_eq!(fs.modified("/file").unwrap(), time);
+        assert_eq!(fs.accessed("/file").unwrap(), time);
+        assert_eq!(
+            fs.modified("/missing").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+    }
+}
+
+mod trace {
+    use std::io::ErrorKind;
+    use std::path::PathBuf;
+
+    use filesystem::{Call, FakeFileSystem, FileSystem, TracingFileSystem};
+
+    fn call(method: &'static str, paths: &[&str], error: Option<ErrorKind>) -> Call {
+        Call {
+            method,
+            paths: paths.iter().map(PathBuf::from).collect(),
+            error,
+        }
+    }
+
+    #[test]
+    fn records_calls_in_order() {
+        let fs = TracingFileSystem::new(FakeFileSystem::new());
+
+        fs.create_dir("/dir").unwrap();
+        fs.write_file("/dir/file", "contents").unwrap();
+        fs.rename("/dir/file", "/dir/renamed").unwrap();
+        assert!(fs.read_file("/dir/file").is_err());
+        assert!(fs.is_file("/dir/renamed"));
+
+        assert_eq!(
+            fs.calls(),
+            vec![
+                call("create_dir", &["/dir"], None),
+                call("write_file", &["/dir/file"], None),
+                call("rename", &["/dir/file", "/dir/renamed"], None),
+                call("read_file", &["/dir/file"], Some(ErrorKind::NotFound)),
+                call("is_file", &["/dir/renamed"], None),
+            ]
+        );
+    }
+
+    #[test]
+    fn filters_calls_by_method() {
+        let fs = TracingFileSystem::new(FakeFileSystem::new());
+
+        fs.create_file("/a", "").unwrap();
+        fs.create_file("/b", "").unwrap();
+        fs.read_file("/a").unwrap();
+
+        assert_eq!(
+            fs.calls_for("create_file"),
+            vec![
+                call("create_file", &["/a"], None),
+                call("create_file", &["/b"], None),
+            ]
+        );
+    }
+
+    #[test]
+    fn clones_share_call_log() {
+        let fs = TracingFileSystem::new(FakeFileSystem::new());
+        let clone = fs.clone();
+
+        clone.create_dir("/dir").unwrap();
+
+        assert_eq!(fs.calls(), vec![call("create_dir", &["/dir"], None)]);
+
+        fs.clear_calls();
+
+        assert!(clone.calls().is_empty());
+    }
+
+    #[test]
+    fn does_not_record_calls_to_inner_file_system() {
+        let fs = TracingFileSystem::new(FakeFileSystem::new());
+
+        fs.inner().create_dir("/dir").unwrap();
+
+        assert!(fs.calls().is_empty());
+        assert!(fs.is_dir("/dir"));
+    }
+}
+
+#[cfg(feature = "mock")]
+mod mock {
+    use std::io::{self, ErrorKind};
+    use std::path::{Path, PathBuf};
+    use std::thread;
+
+    #[cfg(unix)]
+    use filesystem::UnixFileSystem;
+    use filesystem::{
+        FakeError, FileSystem, FileType, Metadata, MockFileSystem, MockMetadata, MockOpenFile,
+        MockTempDir, SymlinkFileSystem, TempDir, TempFileSystem,
+    };
+
+    #[test]
+    fn returns_configured_values() {
+        let fs = MockFileSystem::new();
+
+        fs.read_file_to_string.return_ok("contents");
+        fs.remove_file.return_err(FakeError::from(io::Error::new(
+            ErrorKind::NotFound,
+            "missing",
+        )));
+
+        assert_eq!(fs.read_file_to_string("/file").unwrap(), "contents");
+        assert_eq!(
+            fs.remove_file("/file").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn returns_values_for_matching_arguments() {
+        let fs = MockFileSystem::new();
+
+        fs.read_file.return_ok(b"default".to_vec());
+        fs.read_file.returns_for("/a", Ok(b"a".to_vec()));
+        fs.read_file.returns_when(
+            |path| path.extension().is_some_and(|ext| ext == "toml"),
+            Ok(b"toml".to_vec()),
+        );
+        fs.read_file.returns_for(
+            "/b.toml",
+            Err(FakeError::from(io::Error::new(
+                ErrorKind::NotFound,
+                "missing",
+            ))),
+        );
+
+        assert_eq!(fs.read_file("/a").unwrap(), b"a");
+        assert_eq!(fs.read_file("/a.toml").unwrap(), b"toml");
+        assert_eq!(
+            fs.read_file("/b.toml").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        assert_eq!(fs.read_file("/c").unwrap(), b"default");
+        assert_eq!(fs.read_file.num_calls(), 4);
+    }
+
+    #[test]
+    fn returns_queued_values_in_order() {
+        let fs = MockFileSystem::new();
+
+        fs.exists.return_values(vec![false, true]);
+
+        assert!(!fs.exists("/file"));
+        assert!(fs.exists("/file"));
+        assert!(fs.exists("/file"));
+
+        fs.is_file.return_value(false);
+        fs.is_file.return_values(vec![true]);
+        fs.is_file.reset_responses();
+
+        assert!(!fs.is_file("/file"));
+    }
+
+    #[test]
+    fn shares_calls_between_threads() {
+        let fs = MockFileSystem::new();
+
+        fs.len
+            .use_closure(Box::new(|path: PathBuf| path.as_os_str().len() as u64));
+
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let fs = fs.clone();
+
+                thread::spawn(move || fs.len(format!("/{}", i)))
+            })
+            .collect();
+
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 2);
+        }
+
+        assert_eq!(fs.len.num_calls(), 4);
+    }
+
+    #[test]
+    fn records_arguments_of_calls() {
+        let fs = MockFileSystem::new();
+
+        fs.write_file("/file", "contents").unwrap();
+        fs.set_len("/file", 3).unwrap();
+        fs.rename("/file", "/renamed").unwrap();
+
+        assert_eq!(
+            fs.write_file.calls(),
+            vec![(PathBuf::from("/file"), b"contents".to_vec())]
+        );
+        assert!(fs.set_len.called_with((PathBuf::from("/file"), 3)));
+        assert!(fs
+            .rename
+            .called_with((PathBuf::from("/file"), PathBuf::from("/renamed"))));
+        assert!(!fs.remove_file.called());
+    }
+
+    #[test]
+    fn converts_mocked_entries() {
+        let fs = MockFileSystem::new();
+
+        fs.walk_dir
+            .return_ok(vec![Err(FakeError::from(io::Error::new(
+                ErrorKind::PermissionDenied,
+                "denied",
+            )))]);
+
+        let entries: Vec<_> = fs.walk_dir("/").unwrap().collect();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            entries[0].as_ref().unwrap_err().kind(),
+            ErrorKind::PermissionDenied
+        );
+        assert_eq!(fs.read_dir("/").unwrap().count(), 0);
+    }
+
+    #[test]
+    fn mocks_methods_with_default_implementations() {
+        let fs = MockFileSystem::new();
+
+        fs.read_lines
+            .return_ok(MockOpenFile::new(b"first\nsecond\n".to_vec()));
+
+        let lines: Vec<_> = fs
+            .read_lines("/file")
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(lines, vec!["first", "second"]);
+        assert!(!fs.open.called());
+
+        fs.write_from_reader("/copy", &mut &b"contents"[..])
+            .unwrap();
+
+        assert!(fs
+            .write_from_reader
+            .called_with((PathBuf::from("/copy"), b"contents".to_vec())));
+        assert!(!fs.write_file.called());
+    }
+
+    #[test]
+    fn mocks_symlinks() {
+        let fs = MockFileSystem::new();
+
+        fs.symlink_metadata
+            .return_ok(MockMetadata::new(FileType::Symlink, 0, false));
+
+        fs.symlink_file("/file", "/link").unwrap();
+
+        assert!(fs
+            .symlink_file
+            .called_with((PathBuf::from("/file"), PathBuf::from("/link"))));
+        assert_eq!(
+            fs.symlink_metadata("/link").unwrap().file_type(),
+            FileType::Symlink
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn mocks_unix_file_system() {
+        let fs = MockFileSystem::new();
+
+        fs.mode.return_ok(0o600u32);
+
+        fs.set_mode("/file", 0o600).unwrap();
+
+        assert_eq!(fs.mode("/file").unwrap(), 0o600);
+        assert!(fs.set_mode.called_with((PathBuf::from("/file"), 0o600)));
+    }
+
+    #[test]
+    fn mocks_temp_file_system() {
+        let fs = MockFileSystem::new();
+
+        fs.temp_dir.return_ok(MockTempDir::new("/tmp/test"));
+
+        let temp_dir = fs.temp_dir("test").unwrap();
+
+        assert_eq!(temp_dir.path(), Path::new("/tmp/test"));
+        assert!(fs.temp_dir.called_with("test".to_string()));
+    }
+
+    // `open_with` only builds the options that it passes to `open_with_options`, which is mocked.
+    const UNMOCKED_METHODS: &[&str] = &["open_with"];
+
+    /// Returns the names of the methods declared with `indent` in the block that starts with the
+    /// line `header`, which ends at the first line that closes it at the same depth.
+    fn block_methods(source: &str, header: &str, indent: &str) -> Vec<String> {
+        let end = format!("{}}}", &indent[4..]);
+        let prefix = format!("{}fn ", indent);
+
+        source
+            .lines()
+            .skip_while(|line| !line.starts_with(header))
+            .skip(1)
+            .take_while(|line| *line != end)
+            .filter_map(|line| line.strip_prefix(prefix.as_str()))
+            .map(|line| {
+                line.chars()
+                    .take_while(|c| c.is_alphanumeric() || *c == '_')
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn mocks_every_trait_method() {
+        let traits = include_str!("../src/lib.rs");
+        let mock = include_str!("../src/mock/mod.rs");
+
+        for name in &[
+            "FileSystem",
+            "SymlinkFileSystem",
+            "UnixFileSystem",
+            "WindowsFileSystem",
+            "TempFileSystem",
+        ] {
+            let methods = block_methods(traits, &format!("pub trait {}", name), "    ");
+            let mocked = block_methods(
+                mock,
+                &format!("    impl {} for MockFileSystem {{", name),
+                "        ",
+            );
+
+            assert!(!methods.is_empty(), "no methods found in {}", name);
+
+            for method in &methods {
+                assert!(
+                    mocked.contains(method) || UNMOCKED_METHODS.contains(&method.as_str()),
+                    "{}::{} is not mocked",
+                    name,
+                    method
+                );
+            }
+        }
+    }
+}
+
+mod fake_calls {
+    use std::io::ErrorKind;
+    use std::path::PathBuf;
+
+    use filesystem::{Call, FakeFileSystem, FileSystem};
+
+    #[test]
+    fn records_calls_when_enabled() {
+        let fs = FakeFileSystem::new().with_call_recording();
+
+        fs.create_dir("/dir").unwrap();
+        fs.write_file("/dir/file", "contents").unwrap();
+        assert!(fs.read_file("/missing").is_err());
+
+        assert_eq!(fs.read_file("/dir/file").unwrap(), b"contents");
+        assert_eq!(
+            fs.calls_for("read_file"),
+            vec![
+                Call {
+                    method: "read_file",
+                    paths: vec![PathBuf::from("/missing")],
+                    error: Some(ErrorKind::NotFound),
+                },
+                Call {
+                    method: "read_file",
+                    paths: vec![PathBuf::from("/dir/file")],
+                    error: None,
+                },
+            ]
+        );
+        assert_eq!(fs.calls().len(), 4);
+    }
+
+    #[test]
+    fn does_not_record_calls_by_default() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/dir").unwrap();
+
+        assert!(fs.calls().is_empty());
+    }
+
+    #[test]
+    fn records_only_calls_made_directly() {
+        let fs = FakeFileSystem::new().with_call_recording();
+
+        fs.create_dir_all("/dir/nested").unwrap();
+        fs.clear_calls();
+
+        fs.glob("/dir/*").unwrap();
+        fs.open("/dir/nested").unwrap_err();
+        fs.dir_size("/dir", true).unwrap();
+
+        let methods: Vec<&str> = fs.calls().iter().map(|call| call.method).collect();
+
+        assert_eq!(methods, vec!["glob", "open", "dir_size"]);
+    }
+}
+
+#[cfg(all(unix, feature = "serde"))]
+mod fake_snapshot {
+    use std::io::{ErrorKind, Write};
+    use std::path::Path;
+
+    use filesystem::{
+        FakeFileSystem, FileSystem, OpenOptions, OsFileSystem, SymlinkFileSystem, TempDir,
+        TempFileSystem, UnixFileSystem,
+    };
+
+    #[test]
+    fn round_trips_through_snapshot() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir_all("/etc/app").unwrap();
+        fs.create_file("/etc/app/app.conf", "key=1").unwrap();
+        fs.create_file("/etc/app/data.bin", [0xff, 0x00]).unwrap();
+        fs.set_mode("/etc/app/app.conf", 0o600).unwrap();
+        fs.symlink_file("/etc/app/app.conf", "/etc/app.conf")
+            .unwrap();
+
+        let snapshot = fs.to_snapshot_string().unwrap();
+        let loaded = FakeFileSystem::from_snapshot_str(&snapshot).unwrap();
+
+        assert_eq!(loaded.read_file("/etc/app.conf").unwrap(), b"key=1");
+        assert_eq!(loaded.read_file("/etc/app/data.bin").unwrap(), [0xff, 0x00]);
+        assert_eq!(loaded.mode("/etc/app/app.conf").unwrap(), 0o600);
+        assert_eq!(
+            loaded.read_link("/etc/app.conf").unwrap(),
+            Path::new("/etc/app/app.conf")
+        );
+        assert_eq!(loaded.to_snapshot_string().unwrap(), snapshot);
+    }
+
+    #[test]
+    fn creates_missing_parents_and_uses_defaults() {
+        let fs = FakeFileSystem::from_snapshot_str(
+            r#"{
+                "/var/lib/app/state": { "type": "file", "contents": "ready" },
+                "/var/log": { "type": "dir", "mode": "700" }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            fs.read_file_to_string("/var/lib/app/state").unwrap(),
+            "ready"
+        );
+        assert!(fs.is_dir("/var/lib/app"));
+        assert_eq!(fs.mode("/var/log").unwrap(), 0o700);
+
+        fs.write_file("/var/lib/app/state", "done").unwrap();
+    }
+
+    #[test]
+    fn rejects_invalid_snapshots() {
+        let snapshots = [
+            r#"{ "relative": { "type": "dir" } }"#,
+            r#"{ "/file": { "type": "file", "contents": "" }, "/file/child": { "type": "dir" } }"#,
+            r#"{ "/dir": { "type": "dir", "mode": "999" } }"#,
+        ];
+
+        for snapshot in snapshots.iter() {
+            let result = FakeFileSystem::from_snapshot_str(snapshot);
+
+            assert!(result.is_err());
+            assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn persistent_fake_keeps_changes_between_runs() {
+        let os = OsFileSystem::new();
+        let temp_dir = os.temp_dir("test").unwrap();
+        let backing = temp_dir.path().join("fs.json");
+        let fs = FakeFileSystem::persistent(&backing).unwrap();
+
+        assert!(os.is_file(&backing));
+
+        fs.create_dir("/dir").unwrap();
+        fs.create_file("/dir/file", "contents").unwrap();
+        fs.set_mode("/dir/file", 0o600).unwrap();
+
+        let mut file = fs
+            .open_with_options("/dir/file", OpenOptions::new().append(true))
+            .unwrap();
+
+        file.write_all(b", appended").unwrap();
+        drop((file, fs));
+
+        let fs = FakeFileSystem::persistent(&backing).unwrap();
+
+        assert_eq!(
+            fs.read_file_to_string("/dir/file").unwrap(),
+            "contents, appended"
+        );
+        assert_eq!(fs.mode("/dir/file").unwrap(), 0o600);
+        assert_eq!(
+            os.read_file_to_string(&backing).unwrap(),
+            fs.to_snapshot_string().unwrap()
+        );
+
+        fs.remove_dir_all("/dir").unwrap();
+
+        assert!(!FakeFileSystem::persistent(&backing).unwrap().exists("/dir"));
+    }
+
+    #[test]
+    fn persistent_fake_rejects_invalid_backing_file() {
+        let os = OsFileSystem::new();
+        let temp_dir = os.temp_dir("test").unwrap();
+        let backing = temp_dir.path().join("fs.json");
+
+        os.create_file(&backing, "not a snapshot").unwrap();
+
+        let result = FakeFileSystem::persistent(&backing);
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn persist_reports_failed_writes_until_they_succeed() {
+        let os = OsFileSystem::new();
+        let temp_dir = os.temp_dir("test").unwrap();
+        let dir = temp_dir.path().join("state");
+
+        os.create_dir(&dir).unwrap();
+
+        let fs = FakeFileSystem::persistent(dir.join("fs.json")).unwrap();
+
+        os.remove_dir_all(&dir).unwrap();
+        fs.create_file("/file", "contents").unwrap();
+
+        assert_eq!(fs.persist().unwrap_err().kind(), ErrorKind::NotFound);
+
+        os.create_dir(&dir).unwrap();
+        fs.persist().unwrap();
+
+        let loaded = FakeFileSystem::persistent(dir.join("fs.json")).unwrap();
+
+        assert_eq!(loaded.read_file("/file").unwrap(), b"contents");
+        assert!(FakeFileSystem::new().persist().is_ok());
+    }
+}
+
+#[cfg(all(unix, feature = "tar"))]
+mod fake_tar {
+    use std::io::{self, ErrorKind};
+    use std::path::Path;
+
+    use filesystem::{
+        FakeFileSystem, FileSystem, FileType, Metadata, SymlinkFileSystem, UnixFileSystem,
+    };
+    use tar::{Builder, EntryType, Header};
+
+    fn tree() -> FakeFileSystem {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir_all("/etc/app").unwrap();
+        fs.create_file("/etc/app/app.conf", "key=1").unwrap();
+        fs.create_file("/etc/app/data.bin", [0xff, 0x00]).unwrap();
+        fs.set_mode("/etc/app/app.conf", 0o600).unwrap();
+        fs.set_mode("/etc/app", 0o750).unwrap();
+        fs.set_owner("/etc/app/data.bin", 0, 0).unwrap();
+        fs.symlink_file("/etc/app/app.conf", "/etc/app.conf")
+            .unwrap();
+        fs
+    }
+
+    fn header(entry_type: EntryType, mode: u32, size: u64) -> Header {
+        let mut header = Header::new_gnu();
+
+        header.set_entry_type(entry_type);
+        header.set_mode(mode);
+        header.set_size(size);
+        header.set_uid(1000);
+        header.set_gid(1000);
+        header.set_mtime(0);
+        header
+    }
+
+    #[test]
+    fn round_trips_through_tar() {
+        let mut archive = vec![];
+
+        tree().to_tar(&mut archive).unwrap();
+
+        let loaded = FakeFileSystem::from_tar(&archive[..]).unwrap();
+
+        assert_eq!(loaded.read_file("/etc/app.conf").unwrap(), b"key=1");
+        assert_eq!(loaded.read_file("/etc/app/data.bin").unwrap(), [0xff, 0x00]);
+        assert_eq!(loaded.mode("/etc/app/app.conf").unwrap(), 0o600);
+        assert_eq!(loaded.mode("/etc/app").unwrap(), 0o750);
+        assert_eq!(loaded.owner("/etc/app/data.bin").unwrap(), (0, 0));
+        assert_eq!(
+            loaded.read_link("/etc/app.conf").unwrap(),
+            Path::new("/etc/app/app.conf")
+        );
+    }
+
+    #[test]
+    fn round_trips_fifos_and_devices_through_tar() {
+        let fs = FakeFileSystem::new();
+        let mut archive = vec![];
+
+        fs.set_current_user(0, 0);
+        fs.create_dir("/dev").unwrap();
+        fs.mknod("/dev/null", FileType::CharDevice, 0o666, 0x103)
+            .unwrap();
+        fs.mkfifo("/dev/fifo", 0o600).unwrap();
+        fs.to_tar(&mut archive).unwrap();
+
+        let loaded = FakeFileSystem::from_tar(&archive[..]).unwrap();
+        let mut reloaded = vec![];
+
+        assert_eq!(
+            loaded.metadata("/dev/null").unwrap().file_type(),
+            FileType::CharDevice
+        );
+        assert_eq!(
+            loaded.metadata("/dev/fifo").unwrap().file_type(),
+            FileType::Fifo
+        );
+
+        loaded.to_tar(&mut reloaded).unwrap();
+
+        assert_eq!(reloaded, archive);
+    }
+
+    #[test]
+    fn to_tar_is_reproducible() {
+        let (mut first, mut second) = (vec![], vec![]);
+
+        tree().to_tar(&mut first).unwrap();
+        tree().to_tar(&mut second).unwrap();
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn from_tar_creates_missing_parents_and_hard_links() {
+        let mut builder = Builder::new(vec![]);
+
+        builder
+            .append_data(
+                &mut header(EntryType::Regular, 0o640, 5),
+                "./var/lib/state",
+                &b"ready"[..],
+            )
+            .unwrap();
+        builder
+            .append_link(
+                &mut header(EntryType::Link, 0o640, 0),
+                "var/lib/copy",
+                "var/lib/state",
+            )
+            .unwrap();
+
+        let fs = FakeFileSystem::from_tar(&builder.into_inner().unwrap()[..]).unwrap();
+
+        assert!(fs.is_dir("/var/lib"));
+        assert_eq!(fs.read_file_to_string("/var/lib/copy").unwrap(), "ready");
+        assert_eq!(fs.mode("/var/lib/copy").unwrap(), 0o640);
+
+        fs.write_file("/var/lib/copy", "done").unwrap();
+
+        assert_eq!(fs.read_file_to_string("/var/lib/state").unwrap(), "ready");
+    }
+
+    #[test]
+    fn from_tar_rejects_invalid_archives() {
+        let mut builder = Builder::new(vec![]);
+        let mut outside = header(EntryType::Regular, 0o644, 0);
+
+        outside.as_gnu_mut().unwrap().name[..8].copy_from_slice(b"../file\0");
+        outside.set_cksum();
+        builder.append(&outside, io::empty()).unwrap();
+
+        let archives = [builder.into_inner().unwrap(), b"not a tar archive".to_vec()];
+
+        for archive in archives.iter() {
+            let result = FakeFileSystem::from_tar(&archive[..]);
+
+            assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+        }
+    }
+}
+
+#[cfg(feature = "include_dir")]
+mod fake_include_dir {
+    use filesystem::{FakeFileSystem, FileSystem};
+    use include_dir::Dir;
+
+    static FIXTURES: Dir = include_dir::include_dir!("$CARGO_MANIFEST_DIR/tests/fixtures/embedded");
+
+    #[test]
+    fn copies_embedded_files_to_root() {
+        let fs = FakeFileSystem::from_include_dir(&FIXTURES).unwrap();
+
+        assert_eq!(fs.read_file_to_string("/app.conf").unwrap(), "key=1\n");
+        assert!(fs.is_dir("/data"));
+        assert_eq!(
+            fs.read_file_to_string("/data/values.txt").unwrap(),
+            "1\n2\n3\n"
+        );
+    }
+
+    #[test]
+    fn adds_embedded_files_below_path() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/etc").unwrap();
+        fs.create_file("/etc/app.conf", "key=0").unwrap();
+        fs.add_include_dir("/etc/app", &FIXTURES).unwrap();
+
+        assert_eq!(fs.read_file_to_string("/etc/app.conf").unwrap(), "key=0");
+        assert_eq!(
+            fs.read_file_to_string("/etc/app/app.conf").unwrap(),
+            "key=1\n"
+        );
+        assert!(fs.is_file("/etc/app/data/values.txt"));
+
+        fs.add_include_dir("/etc", &FIXTURES).unwrap();
+
+        assert_eq!(fs.read_file_to_string("/etc/app.conf").unwrap(), "key=1\n");
+    }
+}
+
+mod fake_tree {
+    use filesystem::{FakeFileSystem, FileSystem};
+
+    #[test]
+    fn creates_files_and_parents_from_tree() {
+        let fs = FakeFileSystem::from_tree(&[
+            ("/etc/app.conf", "key=1"),
+            ("/var/lib/app/state", "ready"),
+        ])
+        .unwrap();
+
+        assert_eq!(fs.read_file("/etc/app.conf").unwrap(), b"key=1");
+        assert_eq!(fs.read_file("/var/lib/app/state").unwrap(), b"ready");
+        assert!(fs.is_dir("/var/lib"));
+    }
+
+    #[test]
+    fn creates_files_and_dirs_from_macro() {
+        let fs = filesystem::fake_fs! {
+            "/etc/app.conf" => "key=1",
+            "/var/lib/app" => dir,
+            "/var/lib/app/data.bin" => vec![0u8, 1, 2],
+        };
+
+        assert_eq!(fs.read_file("/etc/app.conf").unwrap(), b"key=1");
+        assert_eq!(fs.read_file("/var/lib/app/data.bin").unwrap(), [0, 1, 2]);
+        assert!(fs.is_dir("/var/lib/app"));
+
+        let empty = filesystem::fake_fs! {};
+
+        assert!(empty.read_dir("/").unwrap().next().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "failed to create file")]
+    fn macro_panics_on_conflicting_entries() {
+        filesystem::fake_fs! {
+            "/conflict" => "",
+            "/conflict/file" => "",
+        };
+    }
+}
+
+#[cfg(unix)]
+mod fake_disk {
+    use std::io::ErrorKind;
+    use std::path::Path;
+
+    use filesystem::{
+        FakeFileSystem, FileSystem, OsFileSystem, SymlinkFileSystem, TempDir, TempFileSystem,
+        UnixFileSystem,
+    };
+
+    #[test]
+    fn loads_tree_from_disk() {
+        let os = OsFileSystem::new();
+        let temp_dir = os.temp_dir("test").unwrap();
+        let root = temp_dir.path().join("fixture");
+
+        os.create_dir_all(root.join("dir")).unwrap();
+        os.create_file(root.join("dir/file"), "contents").unwrap();
+        os.set_mode(root.join("dir/file"), 0o600).unwrap();
+        os.symlink_file("dir/file", root.join("link")).unwrap();
+
+        let fs = FakeFileSystem::load_from_disk(&root).unwrap();
+
+        assert_eq!(fs.read_file(root.join("link")).unwrap(), b"contents");
+        assert_eq!(fs.mode(root.join("dir/file")).unwrap(), 0o600);
+        assert_eq!(
+            fs.read_link(root.join("link")).unwrap(),
+            Path::new("dir/file")
+        );
+    }
+
+    #[test]
+    fn dumps_tree_to_disk() {
+        let os = OsFileSystem::new();
+        let temp_dir = os.temp_dir("test").unwrap();
+        let root = temp_dir.path().join("dump");
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir_all(root.join("dir")).unwrap();
+        fs.create_file(root.join("dir/file"), "contents").unwrap();
+        fs.set_mode(root.join("dir/file"), 0o640).unwrap();
+
+        fs.dump_to_disk(&root).unwrap();
+
+        assert_eq!(os.read_file(root.join("dir/file")).unwrap(), b"contents");
+        assert_eq!(os.mode(root.join("dir/file")).unwrap() & 0o777, 0o640);
+
+        let result = fs.dump_to_disk(&root);
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::AlreadyExists);
+    }
+}
+
+mod fake_diff {
+    use std::path::Path;
+
+    use filesystem::{FakeFileSystem, FileSystem, FsChange, Metadata};
+
+    #[test]
+    fn reports_added_removed_and_modified_nodes() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/dir").unwrap();
+        fs.create_file("/dir/kept", "kept").unwrap();
+        fs.create_file("/dir/modified", "before").unwrap();
+        fs.create_file("/dir/removed", "").unwrap();
+
+        let before = fs.snapshot();
+
+        fs.write_file("/dir/modified", "after").unwrap();
+        fs.remove_file("/dir/removed").unwrap();
+        fs.create_file("/dir/added", "").unwrap();
+        fs.read_file("/dir/kept").unwrap();
+
+        let changes = before.diff(&fs);
+        let paths: Vec<&Path> = changes.iter().map(FsChange::path).collect();
+
+        assert_eq!(
+            paths,
+            vec![
+                Path::new("/dir/added"),
+                Path::new("/dir/modified"),
+                Path::new("/dir/removed"),
+            ]
+        );
+        assert!(changes[0].is_added());
+        assert!(changes[2].is_removed());
+
+        match changes[1] {
+            FsChange::Modified {
+                ref before,
+                ref after,
+                ..
+            } => {
+                assert_eq!(before.len(), 6);
+                assert_eq!(after.len(), 5);
+            }
+            ref change => panic!("unexpected change: {:?}", change),
+        }
+    }
+
+    #[test]
+    fn snapshot_is_independent() {
+        let fs = FakeFileSystem::new();
+        let snapshot = fs.snapshot();
+
+        fs.create_file("/file", "").unwrap();
+
+        assert!(!snapshot.exists("/file"));
+        assert!(fs.diff(&fs.clone()).is_empty());
+        assert_eq!(snapshot.diff(&fs).len(), 1);
+    }
+}
+
+mod fake_fork {
+    use std::sync::Arc;
+    use std::thread;
+
+    use filesystem::{Environment, FakeFileSystem, FileSystem, TempDir, TempFileSystem};
+
+    #[test]
+    fn isolates_changes_between_forks() {
+        let fs = filesystem::fake_fs! {
+            "/data/kept" => "kept",
+            "/data/changed" => "before",
+        };
+        let first = fs.fork();
+        let second = fs.fork();
+
+        first.write_file("/data/changed", "first").unwrap();
+        first.remove_file("/data/kept").unwrap();
+        second.rename("/data", "/moved").unwrap();
+        second.create_file("/added", "").unwrap();
+
+        assert_eq!(fs.read_file_to_string("/data/changed").unwrap(), "before");
+        assert!(fs.is_file("/data/kept"));
+        assert!(!fs.exists("/added"));
+
+        assert_eq!(first.read_file_to_string("/data/changed").unwrap(), "first");
+        assert!(!first.exists("/data/kept"));
+        assert!(!first.exists("/moved"));
+
+        assert_eq!(
+            second.read_file_to_string("/moved/changed").unwrap(),
+            "before"
+        );
+        assert!(second.is_file("/added"));
+        assert!(fs.diff(&fs.fork()).is_empty());
+    }
+
+    #[test]
+    fn shares_contents_until_changed() {
+        let fs = filesystem::fake_fs! { "/kept" => "kept" };
+        let fork = fs.fork();
+
+        assert!(Arc::ptr_eq(
+            &fs.read_file_arc("/kept").unwrap(),
+            &fork.read_file_arc("/kept").unwrap()
+        ));
+
+        fork.write_file("/kept", "changed").unwrap();
+
+        assert_eq!(*fs.read_file_arc("/kept").unwrap(), b"kept");
+    }
+
+    #[test]
+    fn has_its_own_environment_temp_names_and_calls() {
+        let fs = FakeFileSystem::new()
+            .with_deterministic_temp_names()
+            .with_call_recording();
+
+        fs.set_var("MODE", "fixture");
+        fs.create_dir("/tmp").unwrap();
+
+        let fork = fs.fork();
+
+        fork.set_var("MODE", "test");
+
+        assert_eq!(fs.var("MODE").unwrap(), "fixture");
+        assert_eq!(fork.var("MODE").unwrap(), "test");
+        assert_eq!(
+            TempFileSystem::temp_dir(&fs, "test").unwrap().path(),
+            TempFileSystem::temp_dir(&fork, "test").unwrap().path()
+        );
+        assert!(fork.calls_for("create_dir").is_empty());
+        assert_eq!(fs.calls_for("create_dir").len(), 1);
+    }
+
+    #[test]
+    fn supports_parallel_tests_from_one_fixture() {
+        let fs = filesystem::fake_fs! { "/changed" => "before" };
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let fork = fs.fork();
+
+                thread::spawn(move || {
+                    let contents = i.to_string();
+
+                    fork.write_file("/changed", &contents).unwrap();
+
+                    assert_eq!(fork.read_file_to_string("/changed").unwrap(), contents);
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(fs.read_file_to_string("/changed").unwrap(), "before");
+    }
+}
+
+mod fake_all_paths {
+    use std::io::ErrorKind;
+    use std::path::PathBuf;
+
+    use filesystem::{FakeFileSystem, FileSystem, FileType, SymlinkFileSystem};
+
+    fn paths(all: &[(PathBuf, FileType)]) -> Vec<&str> {
+        all.iter().map(|(path, _)| path.to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn lists_every_path_with_its_type() {
+        let fs = filesystem::fake_fs! { "/dir/file" => "" };
+
+        fs.symlink_dir("/dir", "/link").unwrap();
+
+        let all: Vec<_> = fs.all_paths().collect();
+
+        assert_eq!(paths(&all), ["/dir", "/dir/file", "/link"]);
+        assert_eq!(
+            all.iter()
+                .map(|&(_, file_type)| file_type)
+                .collect::<Vec<_>>(),
+            [FileType::Dir, FileType::File, FileType::Symlink]
+        );
+    }
+
+    #[test]
+    fn lists_paths_under_a_directory() {
+        let fs = filesystem::fake_fs! { "/dir/file" => "" };
+
+        fs.symlink_dir("/dir", "/link").unwrap();
+
+        let under: Vec<_> = fs.all_paths_under("/dir").unwrap().collect();
+        let through_link: Vec<_> = fs.all_paths_under("/link").unwrap().collect();
+
+        assert_eq!(paths(&under), ["/dir/file"]);
+        assert_eq!(paths(&through_link), ["/link/file"]);
+        assert_eq!(
+            fs.all_paths_under("/dir/file").unwrap_err().kind(),
+            ErrorKind::NotADirectory
+        );
+        assert_eq!(
+            fs.all_paths_under("/missing").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn is_unaffected_by_later_changes() {
+        let fs = filesystem::fake_fs! { "/dir/file" => "" };
+        let mut all = fs.all_paths();
+
+        assert_eq!(all.next().unwrap().0, PathBuf::from("/dir"));
+
+        fs.remove_dir_all("/dir").unwrap();
+        fs.create_file("/new", "").unwrap();
+
+        assert_eq!(all.count(), 1);
+        assert_eq!(fs.all_paths().count(), 1);
+    }
+
+    #[test]
+    fn lists_windows_paths() {
+        let fs = FakeFileSystem::with_windows_paths();
+
+        fs.create_dir_all(r"C:\app").unwrap();
+        fs.create_file(r"C:\app\main.rs", "").unwrap();
+
+        let all: Vec<_> = fs.all_paths_under(r"C:\app").unwrap().collect();
+
+        assert_eq!(paths(&all), [r"C:\app\main.rs"]);
+    }
+}
+
+mod fake_format_tree {
+    use std::io::ErrorKind;
+
+    use filesystem::{FakeFileSystem, FileSystem, SymlinkFileSystem};
+
+    #[test]
+    fn formats_the_tree_under_a_directory() {
+        let fs = filesystem::fake_fs! {
+            "/app/src/main.rs" => "",
+            "/app/Cargo.toml" => "[package]\n",
+        };
+
+        fs.symlink_dir("src", "/app/latest").unwrap();
+
+        assert_eq!(
+            fs.format_tree("/app").unwrap(),
+            "\
+drwxr-xr-x  - /app
+.rw-r--r-- 10 ├── Cargo.toml
+lrwxrwxrwx  - ├── latest -> src
+drwxr-xr-x  - └── src
+.rw-r--r--  0     └── main.rs
+"
+        );
+        assert_eq!(
+            fs.format_tree("/app/latest").unwrap(),
+            "\
+drwxr-xr-x - /app/latest
+.rw-r--r-- 0 └── main.rs
+"
+        );
+        assert_eq!(
+            fs.format_tree("/missing").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn displays_the_whole_tree() {
+        let fs = filesystem::fake_fs! { "/file" => "" };
+
+        assert_eq!(fs.to_string(), fs.format_tree("/").unwrap());
+        assert_eq!(
+            fs.to_string(),
+            "\
+drwxr-xr-x - /
+.rw-r--r-- 0 └── file
+"
+        );
+    }
+
+    #[test]
+    fn asserts_file_systems_are_equal() {
+        let fs = filesystem::fake_fs! { "/dir/file" => "contents" };
+
+        filesystem::assert_fs_eq!(fs, fs.snapshot());
+        filesystem::assert_fs_eq!(
+            fs,
+            filesystem::fake_fs! { "/dir/file" => "contents" },
+            "built the same way"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "left:\ndrwxr-xr-x - /\n")]
+    fn fails_with_both_trees() {
+        filesystem::assert_fs_eq!(
+            FakeFileSystem::new(),
+            filesystem::fake_fs! { "/file" => "" }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion `left == right` failed\nleft:\n")]
+    fn fails_without_a_message() {
+        filesystem::assert_fs_eq!(
+            FakeFileSystem::new(),
+            filesystem::fake_fs! { "/file" => "" }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion `left == right` failed: after changes\nleft:\n")]
+    fn fails_with_the_message() {
+        filesystem::assert_fs_eq!(
+            FakeFileSystem::new(),
+            filesystem::fake_fs! { "/file" => "" },
+            "after {}",
+            "changes"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "changes from left to right:\n  modified /file\n  added /new")]
+    fn fails_with_the_changes() {
+        let fs = filesystem::fake_fs! { "/file" => "before" };
+        let changed = fs.fork();
+
+        changed.write_file("/file", "").unwrap();
+        changed.create_file("/new", "").unwrap();
+
+        filesystem::assert_fs_eq!(fs, changed, "after {}", "changes");
+    }
+}
+
+#[cfg(feature = "watch")]
+mod watch {
+    use std::io::ErrorKind;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use filesystem::{
+        FakeFileSystem, FileSystem, FsEvent, OsFileSystem, TempDir, TempFileSystem, WatchFileSystem,
+    };
+
+    #[test]
+    fn fake_sends_event_for_each_change() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/dir").unwrap();
+
+        let events = fs.watch("/dir", false).unwrap();
+
+        fs.create_file("/dir/file", "").unwrap();
+        fs.write_file("/dir/file", "contents").unwrap();
+        fs.rename("/dir/file", "/dir/renamed").unwrap();
+        fs.remove_file("/dir/renamed").unwrap();
+
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            vec![
+                FsEvent::Created(PathBuf::from("/dir/file")),
+                FsEvent::Modified(PathBuf::from("/dir/file")),
+                FsEvent::Removed(PathBuf::from("/dir/file")),
+                FsEvent::Created(PathBuf::from("/dir/renamed")),
+                FsEvent::Removed(PathBuf::from("/dir/renamed")),
+            ]
+        );
+    }
+
+    #[test]
+    fn fake_only_sends_events_for_descendants_when_recursive() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir_all("/dir/nested").unwrap();
+
+        let children = fs.watch("/dir", false).unwrap();
+        let descendants = fs.watch("/dir", true).unwrap();
+
+        fs.create_file("/dir/nested/file", "").unwrap();
+        fs.create_file("/other", "").unwrap();
+
+        assert_eq!(children.try_iter().count(), 0);
+        assert_eq!(
+            descendants.try_iter().collect::<Vec<_>>(),
+            vec![FsEvent::Created(PathBuf::from("/dir/nested/file"))]
+        );
+    }
+
+    #[test]
+    fn fake_sends_events_for_descendants_of_renamed_dir() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir_all("/from/nested").unwrap();
+        fs.create_file("/from/nested/file", "").unwrap();
+
+        let events = fs.watch("/", true).unwrap();
+
+        fs.rename("/from", "/to").unwrap();
+
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            vec![
+                FsEvent::Removed(PathBuf::from("/from")),
+                FsEvent::Created(PathBuf::from("/to")),
+                FsEvent::Removed(PathBuf::from("/from/nested")),
+                FsEvent::Created(PathBuf::from("/to/nested")),
+                FsEvent::Removed(PathBuf::from("/from/nested/file")),
+                FsEvent::Created(PathBuf::from("/to/nested/file")),
+            ]
+        );
+    }
+
+    #[test]
+    fn fake_sends_no_events_for_interrupted_remove_dir_all() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir_all("/dir/nested").unwrap();
+        fs.create_file("/dir/file", "").unwrap();
+
+        let events = fs.watch("/", true).unwrap();
+
+        fs.interrupt("remove_dir_all", "/dir", ErrorKind::Interrupted);
+
+        assert!(fs.remove_dir_all("/dir").is_err());
+        assert_eq!(events.try_iter().count(), 0);
+
+        fs.remove_dir_all("/dir").unwrap();
+
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            vec![
+                FsEvent::Removed(PathBuf::from("/dir/nested")),
+                FsEvent::Removed(PathBuf::from("/dir/file")),
+                FsEvent::Removed(PathBuf::from("/dir")),
+            ]
+        );
+    }
+
+    #[test]
+    fn fake_watch_fails_if_path_does_not_exist() {
+        let fs = FakeFileSystem::new();
+
+        let result = fs.watch("/missing", true);
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn os_sends_event_for_new_file() {
+        let fs = OsFileSystem::new();
+        let temp_dir = fs.temp_dir("test").unwrap();
+        let path = temp_dir.path().canonicalize().unwrap();
+
+        let events = fs.watch(&path, false).unwrap();
+
+        fs.create_file(path.join("file"), "").unwrap();
+
+        let event = events.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        assert_eq!(event, FsEvent::Created(path.join("file")));
+    }
+
+    #[test]
+    fn os_resolves_relative_paths_against_own_current_dir() {
+        let fs = OsFileSystem::with_own_current_dir().unwrap();
+        let temp_dir = fs.temp_dir("test").unwrap();
+        let path = temp_dir.path().canonicalize().unwrap();
+
+        fs.create_dir(path.join("dir")).unwrap();
+        fs.set_current_dir(&path).unwrap();
+
+        let events = fs.watch("dir", false).unwrap();
+
+        fs.create_file("dir/file", "").unwrap();
+
+        let event = events.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        assert_eq!(event, FsEvent::Created(path.join("dir").join("file")));
+    }
+
+    #[test]
+    fn os_watch_fails_if_path_is_outside_base() {
+        let fs = OsFileSystem::rooted_in_temp_dir("rooted").unwrap();
+
+        let result = fs.watch("/etc", false);
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+    }
+}
+
+mod fake_hooks {
+    use std::io::{Error, ErrorKind, Write};
+    use std::path::Path;
+
+    use filesystem::{FakeFileSystem, FileSystem, OpenOptions};
+
+    #[test]
+    fn write_hook_can_veto_writes() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/tmp").unwrap();
+        fs.on_write(|path, _| {
+            if path.starts_with("/tmp") {
+                Ok(())
+            } else {
+                Err(Error::new(
+                    ErrorKind::PermissionDenied,
+                    "write outside /tmp",
+                ))
+            }
+        });
+
+        let result = fs.create_file("/file", "");
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+        assert!(!fs.exists("/file"));
+        assert!(fs.write_file("/tmp/file", "").is_ok());
+    }
+
+    #[test]
+    fn write_hook_can_change_contents() {
+        let fs = FakeFileSystem::new();
+
+        fs.on_write(|_, buf| {
+            buf.make_ascii_uppercase();
+            Ok(())
+        });
+
+        fs.write_file("/file", "contents").unwrap();
+        fs.copy_file("/file", "/copy").unwrap();
+        fs.open_with_options("/open", OpenOptions::new().write(true).create(true))
+            .unwrap()
+            .write_all(b"open")
+            .unwrap();
+
+        assert_eq!(fs.read_file("/file").unwrap(), b"CONTENTS");
+        assert_eq!(fs.read_file("/copy").unwrap(), b"CONTENTS");
+        assert_eq!(fs.read_file("/open").unwrap(), b"OPEN");
+    }
+
+    #[test]
+    fn remove_hook_can_veto_removal() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/dir").unwrap();
+        fs.on_remove(|path| {
+            assert_eq!(path, Path::new("/dir"));
+            Err(Error::other("vetoed"))
+        });
+
+        assert!(fs.remove_dir_all("/dir").is_err());
+        assert!(fs.is_dir("/dir"));
+
+        fs.clear_hooks();
+
+        assert!(fs.remove_dir_all("/dir").is_ok());
+    }
+
+    #[test]
+    fn hooks_can_modify_file_system() {
+        let fs = FakeFileSystem::new();
+        let other = fs.clone();
+
+        fs.create_file("/file", "").unwrap();
+        fs.on_remove(move |_| Ok(other.create_file("/concurrent", "")?));
+
+        fs.remove_file("/file").unwrap();
+
+        assert!(fs.is_file("/concurrent"));
+    }
+}
+
+mod fake_latency {
+    use std::io::Read;
+    use std::time::{Duration, Instant};
+
+    use filesystem::{FakeFileSystem, FileSystem};
+
+    fn elapsed<F: FnOnce()>(f: F) -> Duration {
+        let start = Instant::now();
+
+        f();
+        start.elapsed()
+    }
+
+    #[test]
+    fn delays_every_operation() {
+        let fs = FakeFileSystem::new();
+
+        fs.set_latency(Duration::from_millis(20));
+
+        assert!(elapsed(|| fs.create_file("/file", "").unwrap()) >= Duration::from_millis(20));
+        assert!(elapsed(|| assert!(fs.exists("/file"))) >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn operation_latency_overrides_default() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "").unwrap();
+        fs.set_latency(Duration::from_secs(60));
+        fs.set_operation_latency("read_file", Duration::from_millis(20));
+
+        let duration = elapsed(|| {
+            fs.read_file("/file").unwrap();
+        });
+
+        assert!(duration >= Duration::from_millis(20));
+        assert!(duration < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn byte_latency_scales_with_contents() {
+        let fs = FakeFileSystem::new();
+
+        fs.set_byte_latency(Duration::from_millis(2));
+
+        assert!(elapsed(|| fs.write_file("/file", [0; 10]).unwrap()) >= Duration::from_millis(20));
+        assert!(
+            elapsed(|| {
+                let mut buf = [0; 5];
+
+                fs.open("/file").unwrap().read_exact(&mut buf).unwrap();
+            }) >= Duration::from_millis(10)
+        );
+    }
+}
+
+mod fake_faults {
+    use std::io::{Error, ErrorKind, Read, Write};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use filesystem::{FakeFileSystem, FileSystem, OpenOptions};
+
+    #[test]
+    fn read_limit_causes_short_reads() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "contents").unwrap();
+        fs.set_read_limit(Some(3));
+
+        let mut file = fs.open("/file").unwrap();
+        let mut buf = [0; 8];
+
+        assert_eq!(file.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], b"con");
+
+        let mut rest = String::new();
+
+        file.read_to_string(&mut rest).unwrap();
+
+        assert_eq!(rest, "tents");
+    }
+
+    #[test]
+    fn write_limit_causes_short_writes() {
+        let fs = FakeFileSystem::new();
+
+        fs.set_write_limit(Some(3));
+
+        let mut file = fs
+            .open_with_options("/file", OpenOptions::new().write(true).create(true))
+            .unwrap();
+
+        assert_eq!(file.write(b"contents").unwrap(), 3);
+
+        file.write_all(b"tents").unwrap();
+
+        assert_eq!(fs.read_file("/file").unwrap(), b"contents");
+    }
+
+    #[test]
+    fn failed_write_leaves_truncated_file() {
+        let fs = FakeFileSystem::new();
+
+        fs.fail_write_after("/file", 3, ErrorKind::Interrupted);
+
+        let result = fs.write_file("/file", "contents");
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::Interrupted);
+        assert_eq!(fs.read_file("/file").unwrap(), b"con");
+
+        fs.write_file("/file", "contents").unwrap();
+
+        assert_eq!(fs.read_file("/file").unwrap(), b"contents");
+    }
+
+    #[test]
+    fn write_from_reader_writes_in_chunks() {
+        let fs = FakeFileSystem::new();
+        let chunks = Arc::new(AtomicUsize::new(0));
+        let counter = chunks.clone();
+
+        fs.set_write_limit(Some(4));
+        fs.on_write(move |_, _| {
+            if counter.fetch_add(1, Ordering::SeqCst) == 2 {
+                Err(Error::new(ErrorKind::StorageFull, "disk full"))
+            } else {
+                Ok(())
+            }
+        });
+
+        let result = fs.write_from_reader("/file", &mut "contents!!".as_bytes());
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::StorageFull);
+        assert_eq!(fs.read_file("/file").unwrap(), b"contents");
+        assert_eq!(chunks.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn write_file_atomic_leaves_file_unchanged_if_interrupted() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "old contents").unwrap();
+        fs.fail_write_after("/file", 3, ErrorKind::StorageFull);
+
+        let result = fs.write_file_atomic("/file", "new contents");
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::StorageFull);
+        assert_eq!(fs.read_file("/file").unwrap(), b"old contents");
+        assert_eq!(fs.read_dir("/").unwrap().count(), 1);
+    }
+
+    #[test]
+    fn flake_fails_calls_until_exhausted() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "contents").unwrap();
+        fs.flake("read_file", "/file", 2, ErrorKind::Interrupted);
+
+        let mut attempts = 0;
+        let contents = loop {
+            attempts += 1;
+
+            match fs.read_file("/file") {
+                Err(ref err) if err.kind() == ErrorKind::Interrupted => continue,
+                result => break result.unwrap(),
+            }
+        };
+
+        assert_eq!(attempts, 3);
+        assert_eq!(contents, b"contents");
+    }
+
+    #[test]
+    fn flake_leaves_file_system_unchanged() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/from", "").unwrap();
+        fs.flake("rename", "/to", 1, ErrorKind::PermissionDenied);
+
+        let result = fs.rename("/from", "/to");
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+        assert!(fs.is_file("/from"));
+        assert!(!fs.exists("/to"));
+
+        fs.rename("/from", "/to").unwrap();
+
+        assert!(fs.is_file("/to"));
+    }
+
+    #[test]
+    fn interrupted_remove_dir_all_puts_back_removed_nodes() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir_all("/dir/a").unwrap();
+        fs.create_dir_all("/dir/b/nested").unwrap();
+        fs.create_file("/dir/b/nested/file", "contents").unwrap();
+        fs.create_file("/dir/c", "").unwrap();
+        fs.set_operation_logging(true);
+
+        let before = fs.snapshot();
+
+        // Descendants are removed deepest and last first, so `/dir/a` is reached after the rest
+        fs.interrupt("remove_dir_all", "/dir/a", ErrorKind::Interrupted);
+
+        let result = fs.remove_dir_all("/dir");
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::Interrupted);
+        assert!(before.diff(&fs).is_empty());
+        assert!(fs.operation_log().is_empty());
+
+        fs.remove_dir_all("/dir").unwrap();
+
+        assert!(!fs.exists("/dir"));
+    }
+
+    #[test]
+    fn interrupted_copy_dir_removes_copies() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir_all("/from/nested").unwrap();
+        fs.create_file("/from/nested/file", "contents").unwrap();
+        fs.create_file("/from/other", "").unwrap();
+
+        let before = fs.snapshot();
+
+        fs.interrupt("copy_dir", "/to/other", ErrorKind::StorageFull);
+
+        let result = fs.copy_dir("/from", "/to");
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::StorageFull);
+        assert!(before.diff(&fs).is_empty());
+        assert!(!fs.exists("/to"));
+
+        fs.copy_dir("/from", "/to").unwrap();
+
+        assert_eq!(fs.read_file("/to/nested/file").unwrap(), b"contents");
+    }
+}
+
+mod fake_read_file_arc {
+    use std::io::{ErrorKind, Write};
+    use std::sync::Arc;
+
+    use filesystem::{FakeFileSystem, FileSystem, OpenOptions};
+
+    #[test]
+    fn shares_contents_until_file_is_written() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "contents").unwrap();
+
+        let first = fs.read_file_arc("/file").unwrap();
+        let second = fs.read_file_arc("/file").unwrap();
+
+        assert!(Arc::ptr_eq(&first, &second));
+
+        fs.open_with_options("/file", OpenOptions::new().write(true))
+            .unwrap()
+            .write_all(b"new")
+            .unwrap();
+
+        assert_eq!(*first, b"contents");
+        assert_eq!(*fs.read_file_arc("/file").unwrap(), b"newtents");
+    }
+
+    #[test]
+    fn fails_if_node_is_not_a_file() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/dir").unwrap();
+
+        assert_eq!(
+            fs.read_file_arc("/missing").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        assert!(fs.read_file_arc("/dir").is_err());
+    }
+}
+
+mod fake_large_files {
+    use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+
+    use filesystem::{FakeFileSystem, FileSystem, OpenFile, OpenOptions};
+
+    const LEN: usize = 1 << 20;
+
+    fn large_file(fs: &FakeFileSystem) -> Vec<u8> {
+        let contents: Vec<u8> = (0..LEN).map(|i| (i % 251) as u8).collect();
+
+        fs.write_file("/file", &contents).unwrap();
+
+        contents
+    }
+
+    #[test]
+    fn partial_writes_keep_the_rest_of_the_file() {
+        let fs = FakeFileSystem::new();
+        let mut expected = large_file(&fs);
+        let shared = fs.read_file_arc("/file").unwrap();
+        let mut file = fs
+            .open_with_options("/file", OpenOptions::new().read(true).write(true))
+            .unwrap();
+
+        file.seek(SeekFrom::Start(500_000)).unwrap();
+        file.write_all(b"patched").unwrap();
+        file.seek(SeekFrom::End(0)).unwrap();
+        file.write_all(b"appended").unwrap();
+        expected[500_000..500_007].copy_from_slice(b"patched");
+        expected.extend_from_slice(b"appended");
+
+        let mut buf = [0; 11];
+
+        file.seek(SeekFrom::Start(499_998)).unwrap();
+        file.read_exact(&mut buf).unwrap();
+
+        assert_eq!(&buf, &expected[499_998..500_009]);
+        assert_eq!(fs.read_file("/file").unwrap(), expected);
+        assert_eq!(shared.len(), LEN);
+        assert_eq!(shared[500_000], (500_000 % 251) as u8);
+    }
+
+    #[test]
+    fn scattered_writes_match_the_same_writes_to_a_buffer() {
+        let fs = FakeFileSystem::new();
+        let mut expected = large_file(&fs);
+        let mut file = fs
+            .open_with_options("/file", OpenOptions::new().write(true))
+            .unwrap();
+        let mut seed = 7_u64;
+
+        for i in 0..2_000 {
+            seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+
+            let position = (seed >> 33) as usize % (LEN + 1_000);
+            let buf = vec![i as u8; (seed >> 20) as usize % 100];
+
+            if i % 500 == 499 {
+                file.set_len(position as u64).unwrap();
+                expected.resize(position, 0);
+                continue;
+            }
+
+            // Syncing shares the contents, so that later writes cannot change them in place
+            if i % 100 == 0 {
+                file.sync_all().unwrap();
+            }
+
+            file.seek(SeekFrom::Start(position as u64)).unwrap();
+            file.write_all(&buf).unwrap();
+
+            if expected.len() < position + buf.len() {
+                expected.resize(position + buf.len(), 0);
+            }
+
+            expected[position..position + buf.len()].copy_from_slice(&buf);
+        }
+
+        assert_eq!(fs.read_file("/file").unwrap(), expected);
+    }
+
+    #[test]
+    fn crash_keeps_synced_contents_of_partially_written_files() {
+        let fs = FakeFileSystem::new();
+
+        fs.set_crash_simulation(true);
+
+        let expected = large_file(&fs);
+        let mut file = fs
+            .open_with_options("/file", OpenOptions::new().write(true))
+            .unwrap();
+
+        file.sync_all().unwrap();
+        fs.sync_dir("/").unwrap();
+        file.seek(SeekFrom::Start(1_000)).unwrap();
+        file.write_all(b"unsynced").unwrap();
+        file.seek(SeekFrom::End(0)).unwrap();
+        file.write_all(b"unsynced").unwrap();
+        drop(file);
+        fs.simulate_crash();
+
+        assert_eq!(fs.read_file("/file").unwrap(), expected);
+    }
+
+    #[test]
+    fn writes_past_the_largest_file_fail() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "contents").unwrap();
+
+        let mut file = fs
+            .open_with_options("/file", OpenOptions::new().write(true))
+            .unwrap();
+
+        file.seek(SeekFrom::Start(u64::MAX)).unwrap();
+
+        assert_eq!(
+            file.write(b"far").unwrap_err().kind(),
+            ErrorKind::FileTooLarge
+        );
+        assert_eq!(fs.read_file_to_string("/file").unwrap(), "contents");
+    }
+
+    #[test]
+    fn set_len_past_the_largest_file_fails() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "contents").unwrap();
+
+        let file = fs
+            .open_with_options("/file", OpenOptions::new().write(true))
+            .unwrap();
+
+        assert_eq!(
+            file.set_len(u64::MAX).unwrap_err().kind(),
+            ErrorKind::FileTooLarge
+        );
+        assert_eq!(
+            fs.set_len("/file", u64::MAX).unwrap_err().kind(),
+            ErrorKind::FileTooLarge
+        );
+        assert_eq!(fs.read_file_to_string("/file").unwrap(), "contents");
+    }
+}
+
+mod fake_devices {
+    use std::io::ErrorKind;
+
+    use filesystem::{FakeFileSystem, FileSystem};
+
+    fn devices() -> FakeFileSystem {
+        let fs = FakeFileSystem::from_tree(&[
+            ("/home/file", "home"),
+            ("/home/dir/nested", "nested"),
+            ("/mnt/usb/existing", ""),
+        ])
+        .unwrap();
+
+        fs.add_device("/mnt/usb").unwrap();
+
+        fs
+    }
+
+    #[test]
+    fn renaming_across_devices_fails() {
+        let fs = devices();
+
+        let err = fs.rename("/home/file", "/mnt/usb/file").unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::CrossesDevices);
+        assert!(fs.exists("/home/file"));
+
+        fs.rename("/mnt/usb/existing", "/mnt/usb/renamed").unwrap();
+        fs.rename("/home/file", "/home/renamed").unwrap();
+    }
+
+    #[test]
+    fn device_roots_are_busy() {
+        let fs = devices();
+
+        for err in [
+            fs.rename("/mnt/usb", "/mnt/other").unwrap_err(),
+            fs.remove_dir_all("/mnt").unwrap_err(),
+        ] {
+            assert_eq!(err.kind(), ErrorKind::ResourceBusy);
+        }
+    }
+
+    #[test]
+    fn rename_or_copy_falls_back_to_copying() {
+        let fs = devices();
+
+        fs.rename_or_copy("/home/file", "/mnt/usb/file").unwrap();
+        fs.rename_or_copy("/home/dir", "/mnt/usb/dir").unwrap();
+
+        assert_eq!(fs.read_file("/mnt/usb/file").unwrap(), b"home");
+        assert_eq!(fs.read_file("/mnt/usb/dir/nested").unwrap(), b"nested");
+        assert!(!fs.exists("/home/file"));
+        assert!(!fs.exists("/home/dir"));
+    }
+
+    #[test]
+    fn rename_or_copy_renames_on_the_same_device() {
+        let fs = devices();
+
+        fs.rename_or_copy("/home/file", "/home/renamed").unwrap();
+
+        assert_eq!(fs.read_file("/home/renamed").unwrap(), b"home");
+        assert_eq!(
+            fs.rename_or_copy("/home/missing", "/mnt/usb/missing")
+                .unwrap_err()
+                .kind(),
+            ErrorKind::NotFound
+        );
+    }
+}
+
+mod fake_temp_names {
+    use std::env;
+
+    use filesystem::{FakeFileSystem, TempDir, TempFile, TempFileSystem};
+
+    #[test]
+    fn deterministic_temp_names_are_sequential() {
+        let fs = FakeFileSystem::new().with_deterministic_temp_names();
+        let clone = fs.clone();
+
+        let dir = fs.temp_dir("test").unwrap();
+        let file = clone.temp_file("test").unwrap();
+
+        assert_eq!(dir.path(), env::temp_dir().join("test/test_0"));
+        assert_eq!(file.path(), env::temp_dir().join("test_1"));
+    }
+
+    #[test]
+    fn deterministic_temp_names_are_the_same_across_file_systems() {
+        let first = FakeFileSystem::new().with_deterministic_temp_names();
+        let second = FakeFileSystem::new().with_deterministic_temp_names();
+
+        assert_eq!(
+            first.temp_dir("test").unwrap().path(),
+            second.temp_dir("test").unwrap().path()
+        );
+    }
+}
+
+mod environment {
+    use std::env::{self, VarError};
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+
+    use filesystem::{Environment, FakeFileSystem, FileSystem, OsFileSystem};
+
+    #[test]
+    fn os_environment_reads_and_writes_process_variables() {
+        let fs = OsFileSystem::new();
+        let key = "FILESYSTEM_RS_TEST_OS_ENVIRONMENT";
+
+        fs.set_var(key, "value");
+
+        assert_eq!(fs.var(key), Ok(String::from("value")));
+        assert_eq!(env::var(key), Ok(String::from("value")));
+
+        fs.remove_var(key);
+
+        assert_eq!(fs.var(key), Err(VarError::NotPresent));
+        assert_eq!(fs.temp_dir(), env::temp_dir());
+    }
+
+    #[test]
+    fn fake_environment_starts_empty() {
+        let fs = FakeFileSystem::new();
+
+        assert!(fs.vars().is_empty());
+        assert_eq!(fs.var("PATH"), Err(VarError::NotPresent));
+        assert_eq!(fs.home_dir(), None);
+        assert_eq!(fs.temp_dir(), env::temp_dir());
+    }
+
+    #[test]
+    fn fake_environment_is_shared_between_clones() {
+        let fs = FakeFileSystem::new();
+        let clone = fs.clone();
+
+        fs.set_var("B", "2");
+        clone.set_var("A", "1");
+        fs.set_var("C", "3");
+        clone.remove_var("C");
+
+        assert_eq!(clone.var("B"), Ok(String::from("2")));
+        assert_eq!(
+            fs.vars(),
+            vec![
+                (OsString::from("A"), OsString::from("1")),
+                (OsString::from("B"), OsString::from("2")),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn fake_environment_resolves_directories_against_current_dir() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir_all("/home/user").unwrap();
+        fs.set_current_dir("/home").unwrap();
+        fs.set_var("HOME", "user");
+        fs.set_var("TMPDIR", "/var/tmp");
+
+        assert_eq!(fs.home_dir(), Some(PathBuf::from("/home/user")));
+        assert_eq!(fs.temp_dir(), PathBuf::from("/var/tmp"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn fake_temp_files_are_created_in_environment_temp_dir() {
+        use filesystem::{TempFile, TempFileSystem};
+
+        let fs = FakeFileSystem::new();
+
+        fs.set_var("TMPDIR", "/scratch");
+
+        let file = fs.temp_file("test").unwrap();
+
+        assert!(file.path().starts_with("/scratch"));
+        assert!(fs.is_file(file.path()));
+    }
+}
+
+mod fake_isolated_cwd {
+    use std::path::Path;
+
+    use filesystem::{FakeFileSystem, FileSystem};
+
+    #[test]
+    fn isolated_cwd_is_not_shared_with_other_handles() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/a").unwrap();
+        fs.create_dir("/b").unwrap();
+        fs.set_current_dir("/a").unwrap();
+
+        let isolated = fs.clone().with_isolated_cwd();
+
+        assert_eq!(isolated.current_dir().unwrap(), Path::new("/a"));
+
+        isolated.set_current_dir("/b").unwrap();
+        isolated.create_file("file", "b").unwrap();
+        fs.create_file("file", "a").unwrap();
+
+        assert_eq!(fs.current_dir().unwrap(), Path::new("/a"));
+        assert_eq!(fs.read_file("/a/file").unwrap(), b"a");
+        assert_eq!(fs.read_file("/b/file").unwrap(), b"b");
+    }
+
+    #[test]
+    fn isolated_cwd_is_shared_with_later_clones() {
+        let fs = FakeFileSystem::new().with_isolated_cwd();
+        let clone = fs.clone();
+
+        fs.create_dir("/dir").unwrap();
+        clone.set_current_dir("/dir").unwrap();
+
+        assert_eq!(fs.current_dir().unwrap(), Path::new("/dir"));
+    }
+
+    #[test]
+    fn isolated_cwd_fails_once_removed() {
+        let fs = FakeFileSystem::new();
+        let isolated = fs.clone().with_isolated_cwd();
+
+        fs.create_dir("/dir").unwrap();
+        isolated.set_current_dir("/dir").unwrap();
+        fs.remove_dir("/dir").unwrap();
+
+        assert!(isolated.current_dir().is_err());
+        assert_eq!(fs.current_dir().unwrap(), Path::new("/"));
+    }
+}
+
+mod fake_thread_local_cwd {
+    use std::path::{Path, PathBuf};
+    use std::thread;
+
+    #[cfg(unix)]
+    use filesystem::UnixFileSystem;
+    use filesystem::{FakeFileSystem, FileSystem};
+
+    #[test]
+    fn threads_start_from_cwd_of_handle() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/dir").unwrap();
+        fs.set_current_dir("/dir").unwrap();
+
+        let fs = fs.with_thread_local_cwd();
+        let clone = fs.clone();
+
+        let cwd = thread::spawn(move || clone.current_dir().unwrap())
+            .join()
+            .unwrap();
+
+        assert_eq!(cwd, Path::new("/dir"));
+    }
+
+    #[test]
+    fn set_current_dir_does_not_affect_other_threads() {
+        let fs = FakeFileSystem::new().with_thread_local_cwd();
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                let fs = fs.clone();
+
+                thread::spawn(move || {
+                    let dir = PathBuf::from(format!("/{}", i));
+
+                    fs.create_dir(&dir).unwrap();
+                    fs.set_current_dir(&dir).unwrap();
+
+                    for _ in 0..100 {
+                        fs.write_file("file", i.to_string()).unwrap();
+
+                        assert_eq!(fs.current_dir().unwrap(), dir);
+                        assert_eq!(fs.read_file_to_string("file").unwrap(), i.to_string());
+                    }
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(fs.current_dir().unwrap(), Path::new("/"));
+    }
+
+    #[test]
+    fn thread_local_cwd_is_not_shared_with_other_handles() {
+        let fs = FakeFileSystem::new();
+        let thread_local = fs.clone().with_thread_local_cwd();
+
+        fs.create_dir("/dir").unwrap();
+        thread_local.set_current_dir("/dir").unwrap();
+
+        assert_eq!(thread_local.current_dir().unwrap(), Path::new("/dir"));
+        assert_eq!(fs.current_dir().unwrap(), Path::new("/"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn thread_local_cwd_keeps_user_of_process() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/dir").unwrap();
+        fs.set_mode("/dir", 0o777).unwrap();
+
+        let process = fs.process(1001, 1001, "/").unwrap().with_thread_local_cwd();
+
+        process.set_current_dir("/dir").unwrap();
+        process.create_file("file", "").unwrap();
+
+        assert_eq!(fs.owner("/dir/file").unwrap(), (1001, 1001));
+    }
+}
+
+#[cfg(unix)]
+mod fake_process {
+    use std::io::ErrorKind;
+    use std::path::Path;
+
+    use filesystem::{FakeFileSystem, FileSystem, UnixFileSystem};
+
+    #[test]
+    fn processes_share_files_but_not_users() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir_all("/srv/data").unwrap();
+        fs.set_mode("/srv/data", 0o777).unwrap();
+
+        let writer = fs.process(1001, 1001, "/srv").unwrap();
+        let reader = fs.process(1002, 1002, "/srv/data").unwrap();
+
+        writer.create_file("data/secret", "contents").unwrap();
+        writer.set_mode("data/secret", 0o600).unwrap();
+
+        assert_eq!(reader.owner("secret").unwrap(), (1001, 1001));
+        assert_eq!(
+            reader.read_file("secret").unwrap_err().kind(),
+            ErrorKind::PermissionDenied
+        );
+
+        writer.set_mode("data/secret", 0o644).unwrap();
+
+        assert_eq!(reader.read_file("secret").unwrap(), b"contents");
+    }
+
+    #[test]
+    fn process_user_and_cwd_do_not_affect_other_handles() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/tmp").unwrap();
+        fs.set_mode("/tmp", 0o777).unwrap();
+
+        let process = fs.process(0, 0, "/tmp").unwrap();
+
+        process.set_current_user(1001, 1001);
+        process.create_file("file", "").unwrap();
+        fs.create_file("/file", "").unwrap();
+
+        assert_eq!(process.current_dir().unwrap(), Path::new("/tmp"));
+        assert_eq!(fs.current_dir().unwrap(), Path::new("/"));
+        assert_eq!(fs.owner("/tmp/file").unwrap(), (1001, 1001));
+        assert_eq!(fs.owner("/file").unwrap(), (1000, 1000));
+    }
+
+    #[test]
+    fn processes_create_files_under_their_own_umask() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/tmp").unwrap();
+        fs.set_mode("/tmp", 0o777).unwrap();
+
+        let private = fs.process(1001, 1001, "/tmp").unwrap();
+        let shared = fs.process(1002, 1002, "/tmp").unwrap();
+
+        private.set_umask(0o077);
+        shared.set_umask(0o002);
+        private.create_file("private", "").unwrap();
+        shared.create_file("shared", "").unwrap();
+        fs.create_file("/tmp/default", "").unwrap();
+
+        assert_eq!(fs.mode("/tmp/private").unwrap() & 0o777, 0o600);
+        assert_eq!(fs.mode("/tmp/shared").unwrap() & 0o777, 0o664);
+        assert_eq!(fs.mode("/tmp/default").unwrap() & 0o777, 0o644);
+    }
+
+    #[test]
+    fn process_keeps_its_user_with_isolated_cwd() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/tmp").unwrap();
+        fs.set_mode("/tmp", 0o777).unwrap();
+
+        let process = fs.process(1001, 1001, "/tmp").unwrap().with_isolated_cwd();
+
+        process.create_file("file", "").unwrap();
+
+        assert_eq!(process.current_dir().unwrap(), Path::new("/tmp"));
+        assert_eq!(fs.owner("/tmp/file").unwrap(), (1001, 1001));
+    }
+
+    #[test]
+    fn process_fails_if_cwd_does_not_exist() {
+        let fs = FakeFileSystem::new();
+
+        let result = fs.process(1000, 1000, "/missing");
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+    }
+}
+
+mod threads {
+    #[cfg(feature = "mock")]
+    use filesystem::MockFileSystem;
+    #[cfg(feature = "object_store")]
+    use filesystem::ObjectStoreFileSystem;
+    #[cfg(feature = "zip")]
+    use filesystem::ZipFileSystem;
+    use filesystem::{
+        ChrootFileSystem, DynFileSystem, FakeFileSystem, FakeOpenFile, FakeTempDir,
+        MountFileSystem, OsFileSystem, OsTempDir, OverlayFileSystem, TracingFileSystem,
+    };
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn file_systems_are_send_and_sync() {
+        assert_send_sync::<OsFileSystem>();
+        assert_send_sync::<OsTempDir>();
+        assert_send_sync::<FakeFileSystem>();
+        assert_send_sync::<FakeOpenFile>();
+        assert_send_sync::<FakeTempDir>();
+        assert_send_sync::<ChrootFileSystem<FakeFileSystem>>();
+        assert_send_sync::<OverlayFileSystem<FakeFileSystem, OsFileSystem>>();
+        assert_send_sync::<MountFileSystem>();
+        assert_send_sync::<TracingFileSystem<FakeFileSystem>>();
+        assert_send_sync::<Box<dyn DynFileSystem + Send + Sync>>();
+        #[cfg(feature = "mock")]
+        assert_send_sync::<MockFileSystem>();
+        #[cfg(feature = "object_store")]
+        assert_send_sync::<ObjectStoreFileSystem>();
+        #[cfg(feature = "zip")]
+        assert_send_sync::<ZipFileSystem<std::fs::File>>();
+    }
+}
+
+mod fake_threads {
+    use std::io::{ErrorKind, Write};
+    use std::thread;
+
+    #[cfg(unix)]
+    use filesystem::UnixFileSystem;
+    use filesystem::{DirEntry, FakeFileSystem, FileSystem, OpenOptions};
+
+    const THREADS: usize = 8;
+    const ITERATIONS: usize = 200;
+
+    fn run<F>(f: F)
+    where
+        F: Fn(usize) + Clone + Send + 'static,
+    {
+        let threads: Vec<_> = (0..THREADS)
+            .map(|n| {
+                let f = f.clone();
+
+                thread::spawn(move || f(n))
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn clones_can_read_and_write_from_several_threads() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/shared", "shared").unwrap();
+
+        let threads: Vec<_> = (0..4)
+            .map(|n| {
+                let fs = fs.clone();
+
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        assert_eq!(fs.read_file("/shared").unwrap(), b"shared");
+                    }
+
+                    fs.write_file(format!("/{}", n), n.to_string()).unwrap();
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        for n in 0..4 {
+            assert_eq!(
+                fs.read_file_to_string(format!("/{}", n)).unwrap(),
+                n.to_string()
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn processes_with_their_own_users_can_read_from_several_threads() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/shared", "shared").unwrap();
+
+        let threads: Vec<_> = (0..4)
+            .map(|uid| {
+                let fs = fs.process(uid, uid, "/").unwrap();
+
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        assert_eq!(fs.read_file("/shared").unwrap(), b"shared");
+                    }
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        fs.create_file("/new", "").unwrap();
+
+        assert_eq!(fs.owner("/new").unwrap(), fs.owner("/shared").unwrap());
+    }
+
+    #[test]
+    fn appends_from_several_threads_are_not_lost() {
+        let fs = FakeFileSystem::new();
+        let shared = fs.clone();
+
+        run(move |n| {
+            for _ in 0..ITERATIONS {
+                let mut file = shared
+                    .open_with_options("/log", OpenOptions::new().append(true).create(true))
+                    .unwrap();
+
+                file.write_all(format!("{}\n", n).as_bytes()).unwrap();
+            }
+        });
+
+        let log = fs.read_file_to_string("/log").unwrap();
+
+        assert_eq!(log.lines().count(), THREADS * ITERATIONS);
+
+        for n in 0..THREADS {
+            let count = log.lines().filter(|line| *line == n.to_string()).count();
+
+            assert_eq!(count, ITERATIONS);
+        }
+    }
+
+    #[test]
+    fn renames_from_several_threads_keep_every_file() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/from").unwrap();
+        fs.create_dir("/to").unwrap();
+
+        for n in 0..THREADS {
+            for i in 0..ITERATIONS {
+                fs.create_file(format!("/from/{}-{}", n, i), "").unwrap();
+            }
+        }
+
+        let shared = fs.clone();
+
+        run(move |n| {
+            for i in 0..ITERATIONS {
+                let name = format!("{}-{}", n, i);
+
+                shared
+                    .rename(format!("/from/{}", name), format!("/to/{}", name))
+                    .unwrap();
+
+                if i % 20 != 0 {
+                    continue;
+                }
+
+                // A file moved between the two listings shows up in both, but never in neither.
+                let listed = shared
+                    .read_dir("/from")
+                    .unwrap()
+                    .map(Result::unwrap)
+                    .count()
+                    + shared.read_dir("/to").unwrap().map(Result::unwrap).count();
+
+                assert!(listed >= THREADS * ITERATIONS);
+            }
+        });
+
+        let moved: Vec<_> = fs
+            .read_dir("/to")
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+
+        assert_eq!(moved.len(), THREADS * ITERATIONS);
+        assert_eq!(fs.read_dir("/from").unwrap().count(), 0);
+    }
+
+    #[test]
+    fn tree_operations_from_several_threads_leave_a_consistent_tree() {
+        let fs = FakeFileSystem::new();
+        let shared = fs.clone();
+
+        run(move |n| {
+            for i in 0..ITERATIONS {
+                let dir = format!("/tree/{}/{}", n % 2, i % 4);
+
+                let written = shared
+                    .create_dir_all(&dir)
+                    .and_then(|()| shared.write_file(format!("{}/{}", dir, n), ""));
+
+                // Another thread may remove the tree at any point, but nothing else goes wrong.
+                if let Err(err) = written {
+                    assert_eq!(err.kind(), ErrorKind::NotFound);
+                }
+
+                if i % 10 == 0 {
+                    if let Err(err) = shared.remove_dir_all("/tree") {
+                        assert_eq!(err.kind(), ErrorKind::NotFound);
+                    }
+                }
+            }
+        });
+
+        for entry in fs.walk_dir("/").unwrap() {
+            let entry = entry.unwrap();
+
+            assert!(fs.metadata(entry.path()).is_ok());
+        }
+
+        if fs.exists("/tree") {
+            fs.remove_dir_all("/tree").unwrap();
+        }
+
+        assert_eq!(fs.read_dir("/").unwrap().count(), 0);
+    }
+}
+
+mod fake_case_insensitivity {
+    use std::io::ErrorKind;
+    use std::path::PathBuf;
+
+    use filesystem::{CaseSensitivity, DirEntry, FakeFileSystem, FileSystem};
+
+    fn names(fs: &FakeFileSystem, path: &str) -> Vec<PathBuf> {
+        fs.read_dir(path)
+            .unwrap()
+            .map(|entry| PathBuf::from(entry.unwrap().file_name()))
+            .collect()
+    }
+
+    #[test]
+    fn is_case_sensitive_by_default() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "lower").unwrap();
+        fs.create_file("/FILE", "upper").unwrap();
+
+        assert_eq!(fs.read_file("/file").unwrap(), b"lower");
+        assert_eq!(fs.read_file("/FILE").unwrap(), b"upper");
+    }
+
+    #[test]
+    fn lookups_ignore_case_and_names_are_preserved() {
+        let fs = FakeFileSystem::with_case_insensitivity(CaseSensitivity::InsensitivePreserving);
+
+        fs.create_dir("/Dir").unwrap();
+        fs.create_file("/dir/File", "contents").unwrap();
+
+        assert_eq!(fs.read_file("/DIR/FILE").unwrap(), b"contents");
+        assert!(fs.is_dir("/dIR"));
+        assert_eq!(names(&fs, "/"), vec![PathBuf::from("Dir")]);
+        assert_eq!(names(&fs, "/DIR"), vec![PathBuf::from("File")]);
+    }
+
+    #[test]
+    fn creating_a_node_whose_name_differs_only_in_case_fails() {
+        let fs = FakeFileSystem::with_case_insensitivity(CaseSensitivity::InsensitivePreserving);
+
+        fs.create_file("/file", "").unwrap();
+
+        let result = fs.create_file("/FILE", "");
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::AlreadyExists);
+
+        let result = fs.create_dir("/File");
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::AlreadyExists);
+    }
+
+    #[test]
+    fn renaming_can_change_only_the_case_of_a_name() {
+        let fs = FakeFileSystem::with_case_insensitivity(CaseSensitivity::InsensitivePreserving);
+
+        fs.create_dir("/dir").unwrap();
+        fs.create_file("/dir/file", "contents").unwrap();
+        fs.rename("/dir", "/DIR").unwrap();
+
+        assert_eq!(names(&fs, "/"), vec![PathBuf::from("DIR")]);
+        assert_eq!(fs.read_file("/dir/file").unwrap(), b"contents");
+    }
+
+    #[test]
+    fn removing_ignores_case() {
+        let fs = FakeFileSystem::with_case_insensitivity(CaseSensitivity::InsensitivePreserving);
+
+        fs.create_file("/File", "").unwrap();
+        fs.remove_file("/FILE").unwrap();
+
+        assert!(names(&fs, "/").is_empty());
+    }
+}
+
+mod fake_windows_paths {
+    use std::io::ErrorKind;
+    use std::path::PathBuf;
+
+    use filesystem::{DirEntry, FakeFileSystem, FileSystem, SymlinkFileSystem};
+
+    #[test]
+    fn accepts_drives_and_either_separator() {
+        let fs = FakeFileSystem::with_windows_paths();
+
+        fs.create_dir("C:\\dir").unwrap();
+        fs.create_file("C:\\dir\\file.txt", "contents").unwrap();
+
+        assert_eq!(fs.read_file("c:/DIR/file.txt").unwrap(), b"contents");
+        assert!(fs.is_file("\\dir\\File.txt"));
+        assert!(fs.is_file("dir\\file.txt"));
+    }
+
+    #[test]
+    fn returns_windows_paths() {
+        let fs = FakeFileSystem::with_windows_paths();
+
+        assert_eq!(fs.current_dir().unwrap(), PathBuf::from("C:\\"));
+
+        fs.create_dir("C:\\dir").unwrap();
+        fs.create_file("C:\\dir\\file", "").unwrap();
+        fs.set_current_dir("C:\\dir").unwrap();
+
+        assert_eq!(fs.current_dir().unwrap(), PathBuf::from("C:\\dir"));
+        assert_eq!(
+            fs.canonicalize("file").unwrap(),
+            PathBuf::from("C:\\dir\\file")
+        );
+
+        let paths: Vec<_> = fs
+            .read_dir("C:\\dir")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+
+        assert_eq!(paths, vec![PathBuf::from("C:\\dir\\file")]);
+    }
+
+    #[test]
+    fn drives_have_separate_roots() {
+        let fs = FakeFileSystem::with_windows_paths();
+
+        fs.add_drive('d').unwrap();
+        fs.create_file("D:\\file", "d").unwrap();
+
+        assert_eq!(fs.read_file("d:file").unwrap(), b"d");
+        assert!(!fs.exists("C:\\file"));
+        assert_eq!(
+            fs.read_file("E:\\file").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        assert_eq!(
+            fs.add_drive('D').unwrap_err().kind(),
+            ErrorKind::AlreadyExists
+        );
+    }
+
+    #[test]
+    fn follows_symlinks_to_windows_paths() {
+        let fs = FakeFileSystem::with_windows_paths();
+
+        fs.create_dir_all("C:\\dir\\nested").unwrap();
+        fs.create_file("C:\\dir\\nested\\file", "contents").unwrap();
+        fs.symlink_dir("C:\\dir", "C:\\absolute").unwrap();
+        fs.symlink_dir("dir\\nested", "C:\\relative").unwrap();
+
+        assert_eq!(
+            fs.read_file("C:\\absolute\\nested\\file").unwrap(),
+            b"contents"
+        );
+        assert_eq!(fs.read_file("C:\\relative\\file").unwrap(), b"contents");
+    }
+
+    #[test]
+    fn rejects_names_reserved_for_devices() {
+        let fs = FakeFileSystem::with_windows_paths();
+
+        for name in &["C:\\CON", "nul.txt", "C:\\com1"] {
+            let result = fs.create_file(name, "");
+
+            assert!(result.is_err());
+            assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+        }
+
+        assert!(fs.create_dir("C:\\console").is_ok());
+    }
+
+    #[test]
+    fn is_hidden_checks_simulated_attribute() {
+        let fs = FakeFileSystem::with_windows_paths();
+
+        fs.create_file("C:\\.profile", "").unwrap();
+        fs.create_file("C:\\desktop.ini", "").unwrap();
+        fs.set_hidden("C:\\desktop.ini", true).unwrap();
+
+        assert!(!fs.is_hidden("C:\\.profile").unwrap());
+        assert!(fs.is_hidden("c:/DESKTOP.INI").unwrap());
+
+        fs.set_hidden("C:\\desktop.ini", false).unwrap();
+
+        assert!(!fs.is_hidden("C:\\desktop.ini").unwrap());
+        assert_eq!(
+            fs.set_hidden("C:\\does_not_exist", true)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn simulated_attribute_is_ignored_without_windows_paths() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "").unwrap();
+        fs.set_hidden("/file", true).unwrap();
+
+        assert!(!fs.is_hidden("/file").unwrap());
+    }
+}
+
+mod fake_path_limits {
+    use std::io::ErrorKind;
+
+    use filesystem::{FakeFileSystem, FileSystem, PathLimits};
+    #[cfg(target_os = "linux")]
+    use filesystem::{OsFileSystem, TempDir, TempFileSystem};
+
+    fn name(len: usize) -> String {
+        "a".repeat(len)
+    }
+
+    #[test]
+    fn accepts_every_path_by_default() {
+        let fs = FakeFileSystem::new();
+
+        assert!(fs.create_file(format!("/{}", name(1000)), "").is_ok());
+        assert!(fs.create_file("/a\0b", "").is_ok());
+    }
+
+    #[test]
+    fn linux_limits_reject_long_names_and_paths() {
+        let fs = FakeFileSystem::new();
+
+        fs.set_path_limits(&PathLimits::linux());
+
+        assert!(fs.create_file(format!("/{}", name(255)), "").is_ok());
+
+        let long_name = format!("/{}", name(256));
+
+        assert_eq!(
+            fs.create_file(&long_name, "").unwrap_err().kind(),
+            ErrorKind::InvalidFilename
+        );
+        assert_eq!(
+            fs.metadata(&long_name).unwrap_err().kind(),
+            ErrorKind::InvalidFilename
+        );
+
+        let long_path = format!("/{}", vec![name(200); 21].join("/"));
+
+        assert_eq!(
+            fs.create_dir_all(&long_path).unwrap_err().kind(),
+            ErrorKind::InvalidFilename
+        );
+        assert_eq!(
+            fs.create_file("/a\0b", "").unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn windows_limits_reject_invalid_characters_and_long_paths() {
+        let fs = FakeFileSystem::with_windows_paths();
+
+        fs.set_path_limits(&PathLimits::windows());
+
+        for path in &["C:\\a?b", "C:\\a<b", "C:\\a|b", "C:\\a\tb"] {
+            assert_eq!(
+                fs.create_file(path, "").unwrap_err().kind(),
+                ErrorKind::InvalidFilename
+            );
+        }
+
+        assert!(fs.create_file(format!("C:\\{}", name(256)), "").is_err());
+        assert!(fs.create_dir(format!("C:\\{}", name(100))).is_ok());
+
+        // These paths are 259 and 260 bytes long, and `MAX_PATH` leaves room for 259
+        assert!(fs
+            .create_file(format!("C:\\{}\\{}", name(100), name(155)), "")
+            .is_ok());
+        assert_eq!(
+            fs.create_file(format!("C:\\{}\\{}", name(100), name(156)), "")
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidFilename
+        );
+    }
+
+    #[test]
+    fn custom_limits_apply_to_every_operation() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/long", "").unwrap();
+        fs.set_path_limits(PathLimits::new().max_name_len(3));
+
+        assert_eq!(
+            fs.read_file("/long").unwrap_err().kind(),
+            ErrorKind::InvalidFilename
+        );
+        assert!(!fs.exists("/long"));
+        assert!(fs.create_file("/abc", "").is_ok());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn linux_limits_match_the_os() {
+        let os = OsFileSystem::new();
+        let temp_dir = os.temp_dir("test").unwrap();
+        let fake = FakeFileSystem::new();
+
+        fake.set_path_limits(&PathLimits::linux());
+
+        let os_result = os.create_file(temp_dir.path().join(name(256)), "");
+        let fake_result = fake.create_file(format!("/{}", name(256)), "");
+
+        assert_eq!(
+            os_result.unwrap_err().kind(),
+            fake_result.unwrap_err().kind()
+        );
+    }
+}
+
+mod fake_symlink_depth {
+    use filesystem::{FakeFileSystem, FileSystem, SymlinkFileSystem};
+
+    #[test]
+    fn follows_symlinks_up_to_max_depth() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "contents").unwrap();
+        fs.symlink_file("/file", "/a").unwrap();
+        fs.symlink_file("/a", "/b").unwrap();
+        fs.set_max_symlink_depth(2);
+
+        assert_eq!(fs.read_file("/b").unwrap(), b"contents");
+
+        fs.set_max_symlink_depth(1);
+
+        assert!(fs.read_file("/a").is_ok());
+        assert!(fs.read_file("/b").unwrap_err().is_filesystem_loop());
+    }
+
+    #[test]
+    fn other_errors_are_not_loops() {
+        let fs = FakeFileSystem::new();
+
+        fs.symlink_file("/missing", "/link").unwrap();
+
+        assert!(!fs.read_file("/link").unwrap_err().is_filesystem_loop());
+    }
+}
+
+mod fake_unicode_normalization {
+    use std::ffi::OsString;
+    use std::io::ErrorKind;
+    use std::path::PathBuf;
+
+    use filesystem::{CaseSensitivity, DirEntry, FakeFileSystem, FileSystem, UnicodeNormalization};
+
+    // "café", with the "é" composed and decomposed
+    const NFC: &str = "/caf\u{e9}";
+    const NFD: &str = "/cafe\u{301}";
+
+    fn names(fs: &FakeFileSystem) -> Vec<OsString> {
+        fs.read_dir("/")
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect()
+    }
+
+    #[test]
+    fn keeps_names_byte_for_byte_by_default() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file(NFC, "composed").unwrap();
+        fs.create_file(NFD, "decomposed").unwrap();
+
+        assert_eq!(fs.read_file_to_string(NFC).unwrap(), "composed");
+        assert_eq!(fs.read_file_to_string(NFD).unwrap(), "decomposed");
+    }
+
+    #[test]
+    fn nfd_stores_names_decomposed() {
+        let fs = FakeFileSystem::new();
+
+        fs.set_unicode_normalization(UnicodeNormalization::Nfd);
+        fs.create_file(NFC, "contents").unwrap();
+
+        assert_eq!(names(&fs), vec![OsString::from(&NFD[1..])]);
+        assert_eq!(fs.read_file_to_string(NFC).unwrap(), "contents");
+        assert_eq!(fs.read_file_to_string(NFD).unwrap(), "contents");
+        assert_eq!(fs.canonicalize(NFD).unwrap(), PathBuf::from(NFD));
+    }
+
+    #[test]
+    fn nfc_stores_names_composed() {
+        let fs = FakeFileSystem::new();
+
+        fs.set_unicode_normalization(UnicodeNormalization::Nfc);
+        fs.create_dir(NFD).unwrap();
+
+        assert_eq!(names(&fs), vec![OsString::from(&NFC[1..])]);
+        assert!(fs.is_dir(NFC));
+    }
+
+    #[test]
+    fn insensitive_preserving_keeps_names_as_created() {
+        let fs = FakeFileSystem::new();
+
+        fs.set_unicode_normalization(UnicodeNormalization::InsensitivePreserving);
+        fs.create_file(NFD, "contents").unwrap();
+
+        assert_eq!(names(&fs), vec![OsString::from(&NFD[1..])]);
+        assert_eq!(fs.read_file_to_string(NFC).unwrap(), "contents");
+
+        let result = fs.create_file(NFC, "");
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::AlreadyExists);
+
+        fs.rename(NFD, NFC).unwrap();
+
+        assert_eq!(names(&fs), vec![OsString::from(&NFC[1..])]);
+    }
+
+    #[test]
+    fn combines_with_case_insensitivity() {
+        let fs = FakeFileSystem::with_case_insensitivity(CaseSensitivity::InsensitivePreserving);
+
+        fs.set_unicode_normalization(UnicodeNormalization::Nfd);
+        fs.create_file("/CAF\u{c9}", "contents").unwrap();
+
+        assert_eq!(fs.read_file_to_string(NFD).unwrap(), "contents");
+        assert_eq!(names(&fs), vec![OsString::from("CAFE\u{301}")]);
+    }
+}
+
+mod fake_crash_simulation {
+    use std::io::Write;
+
+    use filesystem::{FakeFileSystem, FileSystem, OpenFile, OpenOptions};
+
+    fn crash_simulation() -> FakeFileSystem {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/dir").unwrap();
+        fs.create_file("/dir/file", "old").unwrap();
+        fs.set_crash_simulation(true);
+        fs
+    }
+
+    #[test]
+    fn drops_writes_that_were_not_synced() {
+        let fs = crash_simulation();
+
+        fs.overwrite_file("/dir/file", "new").unwrap();
+        fs.simulate_crash();
+
+        assert_eq!(fs.read_file_to_string("/dir/file").unwrap(), "old");
+    }
+
+    #[test]
+    fn keeps_writes_that_were_synced() {
+        let fs = crash_simulation();
+        let mut file = fs
+            .open_with_options("/dir/file", OpenOptions::new().write(true).truncate(true))
+            .unwrap();
+
+        file.write_all(b"new").unwrap();
+        file.sync_all().unwrap();
+        drop(file);
+        fs.simulate_crash();
+
+        assert_eq!(fs.read_file_to_string("/dir/file").unwrap(), "new");
+    }
+
+    #[test]
+    fn drops_new_files_unless_their_dir_was_synced() {
+        let fs = crash_simulation();
+
+        fs.create_file("/dir/unsynced", "contents").unwrap();
+        fs.sync_file("/dir/unsynced").unwrap();
+        fs.simulate_crash();
+
+        assert!(!fs.exists("/dir/unsynced"));
+
+        fs.create_file("/dir/synced", "contents").unwrap();
+        fs.sync_dir("/dir").unwrap();
+        fs.simulate_crash();
+
+        // The file's entry was synced, but its contents were not
+        assert_eq!(fs.read_file_to_string("/dir/synced").unwrap(), "");
+    }
+
+    #[test]
+    fn undoes_renames_and_removals_that_were_not_synced() {
+        let fs = crash_simulation();
+
+        fs.rename("/dir/file", "/dir/renamed").unwrap();
+        fs.create_file("/other", "").unwrap();
+        fs.remove_file("/other").unwrap();
+        fs.remove_dir_all("/dir").unwrap();
+        fs.simulate_crash();
+
+        assert_eq!(fs.read_file_to_string("/dir/file").unwrap(), "old");
+        assert!(!fs.exists("/dir/renamed"));
+        assert!(!fs.exists("/other"));
+    }
+
+    #[test]
+    fn keeps_files_replaced_by_synced_rename() {
+        let fs = crash_simulation();
+
+        fs.write_file("/dir/file.tmp", "new").unwrap();
+        fs.sync_file("/dir/file.tmp").unwrap();
+        fs.rename("/dir/file.tmp", "/dir/file").unwrap();
+        fs.sync_dir("/dir").unwrap();
+        fs.simulate_crash();
+
+        assert_eq!(fs.read_file_to_string("/dir/file").unwrap(), "new");
+        assert!(!fs.exists("/dir/file.tmp"));
+    }
+
+    #[test]
+    fn empties_files_renamed_before_being_synced() {
+        let fs = crash_simulation();
+
+        fs.write_file("/dir/file.tmp", "new").unwrap();
+        fs.rename("/dir/file.tmp", "/dir/file").unwrap();
+        fs.sync_dir("/dir").unwrap();
+        fs.simulate_crash();
+
+        assert_eq!(fs.read_file_to_string("/dir/file").unwrap(), "");
+    }
+
+    #[test]
+    fn does_nothing_without_crash_simulation() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "contents").unwrap();
+        fs.simulate_crash();
+
+        assert_eq!(fs.read_file_to_string("/file").unwrap(), "contents");
+    }
+}
+
+mod fake_rename {
+    use std::io::{ErrorKind, Write};
+
+    use filesystem::{FakeFileSystem, FileSystem, OpenOptions, OperationKind};
+
+    #[test]
+    fn replacing_a_node_is_logged_as_one_operation() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir_all("/from/dir").unwrap();
+        fs.create_dir("/to").unwrap();
+        fs.set_operation_logging(true);
+
+        fs.rename("/from", "/to").unwrap();
+
+        let kinds: Vec<_> = fs
+            .operation_log()
+            .into_iter()
+            .map(|operation| operation.kind)
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![OperationKind::Rename {
+                from: "/from".into(),
+                to: "/to".into(),
+            }]
+        );
+        assert!(fs.is_dir("/to/dir"));
+    }
+
+    #[test]
+    fn fails_without_changes_if_original_parent_is_readonly() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/dir").unwrap();
+        fs.create_file("/dir/file", "").unwrap();
+        fs.create_file("/file", "").unwrap();
+        fs.set_readonly("/dir", true).unwrap();
+
+        let result = fs.rename("/dir/file", "/file");
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+        assert!(fs.is_file("/dir/file"));
+        assert!(fs.is_file("/file"));
+    }
+
+    // Unlike on the OS, where an open file follows its node, open files refer to their path
+    #[test]
+    fn open_files_lose_their_node_when_an_ancestor_is_renamed() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/from").unwrap();
+        fs.create_file("/from/file", "").unwrap();
+
+        let mut file = fs
+            .open_with_options("/from/file", OpenOptions::new().write(true))
+            .unwrap();
+
+        fs.rename("/from", "/to").unwrap();
+
+        assert_eq!(
+            file.write_all(b"contents").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        assert_eq!(fs.read_file("/to/file").unwrap(), b"");
+    }
+}
+
+mod fake_operation_log {
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use filesystem::{FakeFileSystem, FileSystem, OpenOptions, OperationKind};
+
+    fn operation_logging() -> FakeFileSystem {
+        let fs = FakeFileSystem::new();
+
+        fs.set_operation_logging(true);
+        fs
+    }
+
+    #[test]
+    fn logs_nothing_by_default() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_file("/file", "contents").unwrap();
+
+        assert!(fs.operation_log().is_empty());
+    }
+
+    #[test]
+    fn logs_changes_in_order() {
+        let fs = operation_logging();
+
+        fs.create_dir("/dir").unwrap();
+        fs.create_file("/dir/file", "old").unwrap();
+        fs.read_file("/dir/file").unwrap();
+        fs.overwrite_file("/dir/file", "new").unwrap();
+        fs.remove_file("/dir/file").unwrap();
+
+        let log = fs.operation_log();
+        let seqs: Vec<_> = log.iter().map(|operation| operation.seq).collect();
+        let kinds: Vec<_> = log.into_iter().map(|operation| operation.kind).collect();
+
+        assert_eq!(seqs, vec![0, 1, 2, 3]);
+        assert_eq!(
+            kinds,
+            vec![
+                OperationKind::CreateDir {
+                    path: PathBuf::from("/dir"),
+                    mode: 0o755,
+                },
+                OperationKind::CreateFile {
+                    path: PathBuf::from("/dir/file"),
+                    mode: 0o644,
+                    contents: b"old".to_vec(),
+                },
+                OperationKind::Write {
+                    path: PathBuf::from("/dir/file"),
+                    contents: b"new".to_vec(),
+                },
+                OperationKind::Remove {
+                    path: PathBuf::from("/dir/file"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn logs_writes_through_open_files() {
+        let fs = operation_logging();
+
+        fs.create_file("/file", "").unwrap();
+        fs.clear_operation_log();
+
+        let mut file = fs
+            .open_with_options("/file", OpenOptions::new().write(true))
+            .unwrap();
+
+        file.write_all(b"contents").unwrap();
+
+        let kinds: Vec<_> = fs
+            .operation_log()
+            .into_iter()
+            .map(|operation| operation.kind)
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![OperationKind::WriteAt {
+                path: PathBuf::from("/file"),
+                position: 0,
+                buf: b"contents".to_vec(),
+            }]
+        );
+    }
+
+    #[test]
+    fn logs_renames_once() {
+        let fs = operation_logging();
+
+        fs.create_file("/from", "").unwrap();
+        fs.clear_operation_log();
+        fs.rename("/from", "/to").unwrap();
+
+        let kinds: Vec<_> = fs
+            .operation_log()
+            .into_iter()
+            .map(|operation| operation.kind)
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![OperationKind::Rename {
+                from: PathBuf::from("/from"),
+                to: PathBuf::from("/to"),
+            }]
+        );
+    }
+
+    #[test]
+    fn keeps_counting_after_clearing() {
+        let fs = operation_logging();
+
+        fs.create_dir("/a").unwrap();
+        fs.create_dir("/b").unwrap();
+        fs.clear_operation_log();
+        fs.create_dir("/c").unwrap();
+
+        let seqs: Vec<_> = fs
+            .operation_log()
+            .iter()
+            .map(|operation| operation.seq)
+            .collect();
+
+        assert_eq!(seqs, vec![2]);
+    }
+
+    #[test]
+    fn replays_a_prefix_of_the_log() {
+        let fs = operation_logging();
+
+        fs.create_dir("/dir").unwrap();
+        fs.create_file("/dir/file.tmp", "new").unwrap();
+        fs.sync_file("/dir/file.tmp").unwrap();
+        fs.rename("/dir/file.tmp", "/dir/file").unwrap();
+        fs.sync_dir("/dir").unwrap();
+
+        let log = fs.operation_log();
+
+        for len in 0..=log.len() {
+            let replayed = FakeFileSystem::new();
+
+            replayed.set_crash_simulation(true);
+            replayed.replay(&log[..len]).unwrap();
+            replayed.simulate_crash();
+
+            // A crash leaves either no file or the complete one
+            if replayed.exists("/dir/file") {
+                assert_eq!(replayed.read_file_to_string("/dir/file").unwrap(), "new");
+            }
+        }
+
+        let replayed = FakeFileSystem::new();
+
+        replayed.replay(&log).unwrap();
+
+        assert_eq!(replayed.read_file_to_string("/dir/file").unwrap(), "new");
+        assert!(!replayed.exists("/dir/file.tmp"));
+    }
+}
+
+#[cfg(all(feature = "proptest", unix))]
+mod fake_arbitrary {
+    use std::path::Path;
+
+    use proptest::collection;
+    use proptest::prelude::*;
+
+    use filesystem::{
+        ArbitraryOptions, FakeFileSystem, FileSystem, FileType, Metadata, OperationKind,
+        SymlinkFileSystem, UnixFileSystem, WalkDirOptions,
+    };
+
+    fn small() -> ArbitraryOptions {
+        let mut options = ArbitraryOptions::new();
+
+        options
+            .names(vec!["x", "y"])
+            .max_depth(2)
+            .max_file_len(8)
+            .symlinks(false)
+            .modes(false);
+        options
+    }
+
+    proptest! {
+        #[test]
+        fn generates_trees_within_the_options(fs in any_with::<FakeFileSystem>(small())) {
+            let mut options = WalkDirOptions::new();
+
+            options.min_depth(1);
+
+            for entry in fs.walk_dir_with_options("/", &options).unwrap() {
+                let entry = entry.unwrap();
+                let path = entry.path();
+                let names: Vec<_> = path.strip_prefix("/").unwrap().iter().collect();
+
+                prop_assert!(names.len() <= 2);
+                prop_assert!(names.iter().all(|name| *name == "x" || *name == "y"));
+
+                match fs.symlink_metadata(&path).unwrap().file_type() {
+                    FileType::File => {
+                        prop_assert!(fs.len(&path) <= 8);
+                        prop_assert_eq!(fs.mode(&path).unwrap(), 0o644);
+                    }
+                    FileType::Dir => prop_assert_eq!(fs.mode(&path).unwrap(), 0o755),
+                    other => prop_assert!(false, "unexpected {:?} {:?}", other, path),
+                }
+            }
+        }
+
+        #[test]
+        fn generates_symlinks(fs in any::<FakeFileSystem>()) {
+            for name in &["a", "b", "c", "d", "e"] {
+                let path = Path::new("/").join(name);
+
+                if let Ok(target) = fs.read_link(&path) {
+                    prop_assert!(target.is_absolute());
+                }
+            }
+        }
+
+        #[test]
+        fn operations_replay_onto_a_snapshot(
+            fs in any::<FakeFileSystem>(),
+            operations in collection::vec(any::<OperationKind>(), 0..32),
+        ) {
+            let before = fs.snapshot();
+
+            fs.set_operation_logging(true);
+
+            for operation in &operations {
+                let _ = fs.apply_operation(operation);
+            }
+
+            before.replay(&fs.operation_log()).unwrap();
+
+            let changes = before.diff(&fs);
+
+            prop_assert!(changes.is_empty(), "{:?}", changes);
+        }
+    }
+}
+
+#[cfg(feature = "testkit")]
+mod testkit {
+    use std::path::{Path, PathBuf};
+
+    use filesystem::testkit;
+    use filesystem::{FakeFileSystem, FileSystem};
+
+    #[test]
+    fn runs_test_for_each_backend_in_an_empty_dir() {
+        let mut dirs: Vec<PathBuf> = vec![];
+
+        testkit::run_for_all_backends(|fs, dir| {
+            assert!(fs.is_dir(dir));
+            assert_eq!(fs.read_dir(dir).unwrap().count(), 0);
+
+            fs.create_file(dir.join("file"), "contents").unwrap();
+
+            assert_eq!(
+                fs.read_file_to_string(dir.join("file")).unwrap(),
+                "contents"
+            );
+
+            dirs.push(dir.to_path_buf());
+        });
+
+        assert_eq!(dirs.len(), 2);
+    }
+
+    #[test]
+    fn runs_test_for_other_backend() {
+        let fs = FakeFileSystem::new();
+
+        fs.create_dir("/scratch").unwrap();
+
+        testkit::run_for_backend("custom", fs.clone(), Path::new("/scratch"), |fs, dir| {
+            fs.create_file(dir.join("file"), "").unwrap();
+        });
+
+        assert!(fs.is_file("/scratch/file"));
+    }
+
+    #[test]
+    #[should_panic(expected = "custom: failed here")]
+    fn failures_name_the_backend() {
+        let fs = FakeFileSystem::new();
+
+        testkit::run_for_backend("custom", fs, Path::new("/"), |_, _| panic!("failed here"));
+    }
+
+    #[test]
+    #[should_panic(expected = "OsFileSystem: ")]
+    fn failures_name_the_first_backend_that_fails() {
+        testkit::run_for_all_backends(|fs, dir| {
+            fs.remove_file(dir.join("missing")).unwrap();
+        });
+    }
+}
+
+#[cfg(feature = "conformance")]
+mod conformance {
+    mod os {
+        use filesystem::{OsFileSystem, OsTempDir, TempFileSystem};
+
+        fn setup() -> (OsFileSystem, OsTempDir) {
+            let fs = OsFileSystem::new();
+            let dir = fs.temp_dir("conformance").unwrap();
+
+            (fs, dir)
+        }
+
+        filesystem::conformance_tests!(setup, symlinks);
+    }
+
+    mod fake {
+        use filesystem::{FakeFileSystem, FileSystem};
+
+        fn setup() -> (FakeFileSystem, &'static str) {
+            let fs = FakeFileSystem::new();
+
+            fs.create_dir("/test").unwrap();
+            (fs, "/test")
+        }
+
+        filesystem::conformance_tests!(setup, symlinks);
+    }
+
+    #[cfg(feature = "object_store")]
+    mod object {
+        use std::sync::Arc;
+
+        use filesystem::{FileSystem, ObjectStoreFileSystem};
+        use object_store::memory::InMemory;
+
+        fn setup() -> (ObjectStoreFileSystem, &'static str) {
+            let fs = ObjectStoreFileSystem::new(Arc::new(InMemory::new())).unwrap();
+
+            fs.create_dir("/test").unwrap();
+            (fs, "/test")
+        }
+
+        filesystem::conformance_tests!(setup);
+    }
+}
+
+#[cfg(feature = "object_store")]
+mod object_store_fs {
+    use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+    use std::sync::Arc;
+
+    use filesystem::{DirEntry, FileSystem, ObjectStoreFileSystem, OpenFile, OpenOptions};
+    use object_store::memory::InMemory;
+    use object_store::path::Path as ObjectPath;
+    use object_store::{ObjectStore, PutPayload};
+
+    fn setup() -> (ObjectStoreFileSystem, Arc<InMemory>) {
+        let store = Arc::new(InMemory::new());
+        let fs = ObjectStoreFileSystem::new(store.clone()).unwrap();
+
+        (fs, store)
+    }
+
+    fn block_on<F: ::std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn files_are_stored_at_their_paths() {
+        let (fs, store) = setup();
+
+        fs.create_dir_all("/a/b").unwrap();
+        fs.create_file("/a/b/file", "contents").unwrap();
+
+        let result = block_on(store.get(&ObjectPath::from("a/b/file"))).unwrap();
+
+        assert_eq!(&block_on(result.bytes()).unwrap()[..], b"contents");
+    }
+
+    #[test]
+    fn objects_from_other_clients_imply_directories() {
+        let (fs, store) = setup();
+
+        block_on(store.put(&ObjectPath::from("a/b/file"), PutPayload::from("contents"))).unwrap();
+
+        assert!(fs.is_dir("/a"));
+        assert!(fs.is_dir("/a/b"));
+        assert_eq!(fs.read_file_to_string("/a/b/file").unwrap(), "contents");
+
+        fs.remove_file("/a/b/file").unwrap();
+
+        assert!(fs.is_dir("/a/b"));
+    }
+
+    #[test]
+    fn empty_dirs_exist_without_showing_markers() {
+        let (fs, _) = setup();
+
+        fs.create_dir_all("/a/b").unwrap();
+
+        assert!(fs.is_dir("/a/b"));
+
+        let names: Vec<_> = fs
+            .read_dir("/a")
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+
+        assert_eq!(names, vec!["b"]);
+        assert!(fs.read_dir("/a/b").unwrap().next().is_none());
+    }
+
+    #[test]
+    fn marker_name_is_reserved() {
+        let (fs, _) = setup();
+
+        let result = fs.create_file("/.filesystem-dir", "");
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn open_files_are_written_back_when_flushed() {
+        let (fs, _) = setup();
+
+        fs.create_file("/file", "abc").unwrap();
+
+        let mut file = fs
+            .open_with_options("/file", OpenOptions::new().read(true).write(true))
+            .unwrap();
+
+        file.seek(SeekFrom::End(0)).unwrap();
+        file.write_all(b"def").unwrap();
+
+        assert_eq!(fs.read_file("/file").unwrap(), b"abc");
+
+        file.flush().unwrap();
+
+        assert_eq!(fs.read_file("/file").unwrap(), b"abcdef");
+
+        file.set_len(2).unwrap();
+        drop(file);
+
+        let mut contents = String::new();
+
+        fs.open("/file")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+
+        assert_eq!(contents, "ab");
+    }
+
+    #[test]
+    fn times_and_permissions_are_unsupported() {
+        let (fs, _) = setup();
+
+        fs.create_file("/file", "").unwrap();
+
+        assert!(fs.modified("/file").is_ok());
+        assert!(!fs.readonly("/file").unwrap());
+        assert_eq!(
+            fs.set_readonly("/file", true).unwrap_err().kind(),
+            ErrorKind::Unsupported
+        );
+        assert_eq!(
+            fs.accessed("/file").unwrap_err().kind(),
+            ErrorKind::Unsupported
+        );
+    }
+}
+
+#[cfg(feature = "zip")]
+mod zip_fs {
+    use std::io::{Cursor, ErrorKind, Read, Write};
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use filesystem::{DirEntry, FakeFileSystem, FileSystem, OpenOptions, ZipFileSystem};
+    use zip::write::SimpleFileOptions;
+    use zip::{DateTime, ZipWriter};
+
+    fn archive() -> ZipFileSystem<Cursor<Vec<u8>>> {
+        let mut writer = ZipWriter::new(Cursor::new(vec![]));
+        let time = DateTime::from_date_and_time(2024, 2, 29, 12, 30, 16).unwrap();
+        let options = SimpleFileOptions::default().last_modified_time(time);
+
+        writer.add_directory("empty/", options).unwrap();
+        writer.start_file("a/b/file", options).unwrap();
+        writer.write_all(b"contents").unwrap();
+        writer.start_file("top", options).unwrap();
+        writer.write_all(b"top").unwrap();
+
+        ZipFileSystem::open(writer.finish().unwrap()).unwrap()
+    }
+
+    /// Reads every file below `dir`, as a tool that takes a directory or an archive would.
+    fn read_all<T: FileSystem>(fs: &T, dir: &Path) -> Vec<(PathBuf, String)> {
+        fs.walk_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| {
+                let contents = fs.read_file_to_string(entry.path()).unwrap();
+
+                (entry.path().to_path_buf(), contents)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn entries_are_found_at_their_names() {
+        let fs = archive();
+
+        assert!(fs.is_dir("/empty"));
+        assert!(fs.is_dir("/a/b"));
+        assert_eq!(fs.read_file_to_string("/a/b/file").unwrap(), "contents");
+        assert_eq!(fs.len("/a/b/file"), 8);
+
+        fs.set_current_dir("/a").unwrap();
+
+        assert_eq!(fs.read_file("b/../b/file").unwrap(), b"contents");
+        assert_eq!(fs.canonicalize("b").unwrap(), PathBuf::from("/a/b"));
+    }
+
+    #[test]
+    fn read_dir_lists_entries_in_order() {
+        let fs = archive();
+        let names: Vec<_> = fs
+            .read_dir("/")
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+
+        assert_eq!(names, vec!["a", "empty", "top"]);
+        assert!(fs.read_dir("/empty").unwrap().next().is_none());
+        assert!(fs.read_dir("/top").is_err());
+        assert_eq!(
+            fs.read_dir("/missing").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn archives_and_directories_share_code_paths() {
+        let zip = archive();
+        let fake = FakeFileSystem::new();
+
+        fake.create_dir_all("/a/b").unwrap();
+        fake.create_file("/a/b/file", "contents").unwrap();
+
+        assert_eq!(
+            read_all(&zip, Path::new("/a")),
+            read_all(&fake, Path::new("/a"))
+        );
+    }
+
+    #[test]
+    fn open_files_can_only_be_read() {
+        let fs = archive();
+        let mut contents = String::new();
+        let mut file = fs.open("/top").unwrap();
+
+        file.read_to_string(&mut contents).unwrap();
+
+        assert_eq!(contents, "top");
+        assert_eq!(
+            file.write(b"more").unwrap_err().kind(),
+            ErrorKind::PermissionDenied
+        );
+        assert_eq!(
+            fs.open_with_options("/top", OpenOptions::new().write(true))
+                .unwrap_err()
+                .kind(),
+            ErrorKind::PermissionDenied
+        );
+    }
+
+    #[test]
+    fn changes_fail_with_permission_denied() {
+        let fs = archive();
+
+        assert_eq!(
+            fs.create_file("/new", "").unwrap_err().kind(),
+            ErrorKind::PermissionDenied
+        );
+        assert_eq!(
+            fs.remove_file("/top").unwrap_err().kind(),
+            ErrorKind::PermissionDenied
+        );
+        assert_eq!(
+            fs.rename("/top", "/moved").unwrap_err().kind(),
+            ErrorKind::PermissionDenied
+        );
+        assert!(fs.readonly("/top").unwrap());
+    }
+
+    #[test]
+    fn modified_times_are_read_as_utc() {
+        let fs = archive();
+
+        assert_eq!(
+            fs.modified("/top").unwrap(),
+            UNIX_EPOCH + Duration::from_secs(1_709_209_816)
+        );
+        assert!(fs.modified("/a").is_err());
+    }
+
+    #[test]
+    fn open_fails_for_invalid_archives() {
+        let result = ZipFileSystem::open(Cursor::new(b"not a zip archive".to_vec()));
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
 }
@@ -0,0 +1,49 @@
+#![cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+
+extern crate filesystem;
+extern crate wasm_bindgen_test;
+
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use filesystem::{FakeFileSystem, FileSystem, Metadata, TempDir, TempFileSystem};
+
+#[wasm_bindgen_test]
+fn fake_reads_back_written_files() {
+    let fs = FakeFileSystem::new();
+
+    fs.create_dir_all("/app/data").unwrap();
+    fs.write_file("/app/data/config.toml", "key = 1").unwrap();
+    fs.write_file_atomic("/app/data/config.toml", "key = 2")
+        .unwrap();
+
+    assert_eq!(
+        fs.read_file_to_string("/app/data/config.toml").unwrap(),
+        "key = 2"
+    );
+    assert_eq!(fs.read_dir("/app/data").unwrap().count(), 1);
+}
+
+#[wasm_bindgen_test]
+fn fake_timestamps_nodes() {
+    let fs = FakeFileSystem::new();
+
+    fs.write_file("/file", "").unwrap();
+
+    let modified = fs.metadata("/file").unwrap().modified().unwrap();
+
+    assert!(modified > UNIX_EPOCH);
+}
+
+#[wasm_bindgen_test]
+fn fake_creates_temp_dirs_without_an_os() {
+    let fs = FakeFileSystem::new();
+    let first = fs.temp_dir("test").unwrap();
+    let second = fs.temp_dir("test").unwrap();
+
+    assert!(first.path().starts_with(Path::new("/tmp")));
+    assert_ne!(first.path(), second.path());
+    assert!(fs.is_dir(first.path()));
+}